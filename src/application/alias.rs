@@ -0,0 +1,89 @@
+use super::ResourceService;
+use crate::domain::{Alias, DomainError, Resource};
+use crate::ports::ResourceRepository;
+
+impl ResourceService {
+    /// The resource ID a user means by `reference`: the target of an alias,
+    /// the resource a URI or Notion or Linear web link names, a Linear issue
+    /// identifier such as `ENG-123`, or otherwise `reference` itself.
+    pub async fn resolve_id(&self, reference: &str) -> String {
+        if let Some(cache) = &self.cache {
+            match cache.repository.resolve_alias(reference).await {
+                Ok(Some(id)) => return id,
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Alias lookup failed: {}", e),
+            }
+        }
+
+        if let Some(id) =
+            Resource::id_from_uri(reference).or_else(|| Resource::id_from_web_url(reference))
+        {
+            id
+        } else if is_issue_identifier(reference) {
+            // Linear looks issues up by identifier as well as by ID.
+            format!("linear_{}", reference.to_uppercase())
+        } else {
+            reference.to_string()
+        }
+    }
+
+    /// Points `alias` at the resource `target` refers to, returning its ID.
+    pub async fn set_alias(&self, alias: &str, target: &str) -> Result<String, DomainError> {
+        let invalid = |reason: &str| {
+            Err(DomainError::InvalidQuery(format!(
+                "Invalid alias {:?}: {}",
+                alias, reason
+            )))
+        };
+        if alias.is_empty() || alias.chars().any(char::is_whitespace) {
+            return invalid("must be non-empty without spaces");
+        }
+        if alias.starts_with("notion_")
+            || alias.starts_with("linear_")
+            || Resource::id_from_uri(alias).is_some()
+            || Resource::id_from_web_url(alias).is_some()
+        {
+            return invalid("looks like a resource ID");
+        }
+        if is_issue_identifier(alias) {
+            return invalid("looks like a Linear issue identifier");
+        }
+
+        let repository = self.alias_repository()?;
+        let id = self.resolve_id(target).await;
+        repository.set_alias(alias, &id).await?;
+        Ok(id)
+    }
+
+    /// Removes an alias, returning whether it existed.
+    pub async fn remove_alias(&self, alias: &str) -> Result<bool, DomainError> {
+        self.alias_repository()?.remove_alias(alias).await
+    }
+
+    pub async fn list_aliases(&self) -> Result<Vec<Alias>, DomainError> {
+        self.alias_repository()?.list_aliases().await
+    }
+
+    fn alias_repository(&self) -> Result<&dyn ResourceRepository, DomainError> {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.repository.as_ref())
+            .ok_or_else(|| {
+                DomainError::StorageError(
+                    "Aliases are stored in the cache, which is disabled".to_string(),
+                )
+            })
+    }
+}
+
+/// Whether `text` has the shape of a Linear issue identifier: a team key, a
+/// dash, and a number.
+fn is_issue_identifier(text: &str) -> bool {
+    let Some((team, number)) = text.split_once('-') else {
+        return false;
+    };
+    team.starts_with(|c: char| c.is_ascii_alphabetic())
+        && team.chars().all(|c| c.is_ascii_alphanumeric())
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+}
@@ -0,0 +1,86 @@
+use super::ResourceService;
+use crate::domain::{Attachment, Blob, DomainError, Resource};
+
+impl ResourceService {
+    /// Downloads the `index`th file attached to resource `id`, or reads it
+    /// from the cache if it was downloaded since the resource last changed.
+    /// Links to uploads can expire while the resource sits in the cache, so
+    /// a failed download is retried once with the resource fetched afresh.
+    pub async fn attachment(
+        &self,
+        id: &str,
+        index: usize,
+    ) -> Result<(Attachment, Blob), DomainError> {
+        let resource = self.fetch_resource_by_id(id).await?;
+        if let Some(stored) = self.stored_attachment(&resource, index).await {
+            return Ok(stored);
+        }
+        let (resource, downloaded) = match self.download_attachment(&resource, index).await {
+            Ok(downloaded) => (resource, downloaded),
+            Err(e @ (DomainError::ResourceNotFound(_) | DomainError::InvalidQuery(_))) => {
+                return Err(e)
+            }
+            Err(e) => {
+                tracing::debug!("Refreshing {} after a failed download: {}", id, e);
+                let resource = self.refresh_resource(&resource.id).await?;
+                let downloaded = self.download_attachment(&resource, index).await?;
+                (resource, downloaded)
+            }
+        };
+        if let Some(cache) = &self.cache {
+            let (_, blob) = &downloaded;
+            if let Err(e) = cache
+                .repository
+                .save_attachment(&resource.id, index, resource.updated_at, blob)
+                .await
+            {
+                tracing::warn!("Failed to cache {}: {}", resource.attachment_uri(index), e);
+            }
+        }
+        Ok(downloaded)
+    }
+
+    async fn stored_attachment(
+        &self,
+        resource: &Resource,
+        index: usize,
+    ) -> Option<(Attachment, Blob)> {
+        let cache = self.cache.as_ref()?;
+        let attachment = resource.attachments().into_iter().nth(index)?;
+        match cache
+            .repository
+            .find_attachment(&resource.id, index, resource.updated_at)
+            .await
+        {
+            Ok(blob) => blob.map(|blob| (attachment, blob)),
+            Err(e) => {
+                tracing::debug!(
+                    "Cached {} unreadable: {}",
+                    resource.attachment_uri(index),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    async fn download_attachment(
+        &self,
+        resource: &Resource,
+        index: usize,
+    ) -> Result<(Attachment, Blob), DomainError> {
+        let attachment = resource
+            .attachments()
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| DomainError::ResourceNotFound(resource.attachment_uri(index)))?;
+        let provider = resource.source.provider_name();
+        let provider = self
+            .get_provider(provider)
+            .ok_or_else(|| DomainError::NotConfigured {
+                provider: provider.to_string(),
+            })?;
+        let blob = provider.download(&attachment).await?;
+        Ok((attachment, blob))
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use crate::{
+    domain::{DomainError, Query, Resource},
+    ports::ResourceProvider,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchOperation {
+    Fetch,
+    Search,
+    Get,
+}
+
+impl BenchOperation {
+    pub fn parse(name: &str) -> Result<Self, DomainError> {
+        match name.to_lowercase().as_str() {
+            "fetch" => Ok(Self::Fetch),
+            "search" => Ok(Self::Search),
+            "get" => Ok(Self::Get),
+            _ => Err(DomainError::InvalidQuery(format!(
+                "Unknown benchmark operation: {}",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fetch => "fetch",
+            Self::Search => "search",
+            Self::Get => "get",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub iterations: usize,
+    pub concurrency: usize,
+    pub query: Query,
+    pub search_term: String,
+    pub resource_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct BenchReport {
+    pub operation: BenchOperation,
+    /// Latencies of successful calls, sorted ascending.
+    pub latencies: Vec<Duration>,
+    pub errors: Vec<DomainError>,
+    /// Total number of resources returned across all successful calls.
+    pub items: usize,
+    pub elapsed: Duration,
+    /// Resources returned by the first successful call, kept for recording
+    /// fixtures and picking an ID for `get`.
+    pub sample: Vec<Resource>,
+}
+
+impl BenchReport {
+    pub fn calls(&self) -> usize {
+        self.latencies.len() + self.errors.len()
+    }
+
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = (percentile / 100.0 * (self.latencies.len() - 1) as f64).round() as usize;
+        self.latencies.get(rank).copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        Some(self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32)
+    }
+
+    /// Successful calls per second of wall-clock time.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return 0.0;
+        }
+        self.latencies.len() as f64 / secs
+    }
+}
+
+/// Runs `options.iterations` calls of `operation` against `provider`, spread
+/// over `options.concurrency` workers.
+pub async fn run(
+    provider: Arc<dyn ResourceProvider>,
+    operation: BenchOperation,
+    options: &BenchOptions,
+) -> Result<BenchReport, DomainError> {
+    if operation == BenchOperation::Get && options.resource_id.is_none() {
+        return Err(DomainError::InvalidQuery(
+            "get benchmark requires a resource ID".to_string(),
+        ));
+    }
+
+    let options = Arc::new(options.clone());
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+
+    let mut workers = Vec::new();
+    for _ in 0..options.concurrency.max(1) {
+        let provider = provider.clone();
+        let options = options.clone();
+        let next = next.clone();
+
+        workers.push(tokio::spawn(async move {
+            let mut samples = Vec::new();
+            while next.fetch_add(1, Ordering::SeqCst) < options.iterations {
+                let call_started = Instant::now();
+                let result = call(provider.as_ref(), operation, &options).await;
+                samples.push((call_started.elapsed(), result));
+            }
+            samples
+        }));
+    }
+
+    let mut report = BenchReport {
+        operation,
+        latencies: Vec::new(),
+        errors: Vec::new(),
+        items: 0,
+        elapsed: Duration::ZERO,
+        sample: Vec::new(),
+    };
+
+    for worker in workers {
+        let samples = worker
+            .await
+            .map_err(|e| DomainError::ProviderError(format!("Benchmark worker failed: {}", e)))?;
+
+        for (latency, result) in samples {
+            match result {
+                Ok(resources) => {
+                    report.latencies.push(latency);
+                    report.items += resources.len();
+                    if report.sample.is_empty() {
+                        report.sample = resources;
+                    }
+                }
+                Err(e) => report.errors.push(e),
+            }
+        }
+    }
+
+    report.elapsed = started.elapsed();
+    report.latencies.sort();
+
+    Ok(report)
+}
+
+async fn call(
+    provider: &dyn ResourceProvider,
+    operation: BenchOperation,
+    options: &BenchOptions,
+) -> Result<Vec<Resource>, DomainError> {
+    match operation {
+        BenchOperation::Fetch => provider.fetch_resources(&options.query).await,
+        BenchOperation::Search => provider.search(&options.search_term).await,
+        BenchOperation::Get => {
+            let id = options.resource_id.as_deref().unwrap_or_default();
+            provider.fetch_resource_by_id(id).await.map(|r| vec![r])
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::cmp::Ordering;
+
+use super::shape::HEADING;
+use crate::domain::{Chunk, Resource};
+
+/// Passages a chunk search returns unless asked for a number.
+pub const DEFAULT_CHUNKS: usize = 10;
+
+/// Characters a passage grows to before it is cut at the next paragraph.
+const CHUNK_CHARS: usize = 1500;
+
+/// Words too common to search for or rank by.
+const STOP_WORDS: [&str; 40] = [
+    "a", "about", "an", "and", "any", "are", "as", "at", "be", "by", "can", "did", "do", "does",
+    "for", "from", "how", "in", "is", "it", "of", "on", "or", "our", "should", "that", "the",
+    "there", "this", "to", "was", "we", "were", "what", "when", "where", "which", "who", "why",
+    "with",
+];
+
+/// A passage being collected.
+#[derive(Default)]
+struct Passage {
+    /// Character offset of its first line.
+    start: Option<usize>,
+    text: String,
+    /// Length of `text` in characters.
+    chars: usize,
+    /// Whether it has more than headings.
+    has_body: bool,
+}
+
+/// Splits `resource`'s content into passages: one per section, with
+/// sections longer than about 1500 characters cut between paragraphs, or
+/// between lines if a paragraph runs to twice that. A section's first
+/// passage starts with its heading; headings in code blocks don't count.
+pub fn chunks(resource: &Resource) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut passage = Passage::default();
+    let mut offset = 0;
+    let mut fenced = false;
+
+    let mut flush = |passage: &mut Passage, headings: &[(usize, String)]| {
+        let passage = std::mem::take(passage);
+        let (Some(start), true) = (passage.start, passage.has_body) else {
+            return;
+        };
+        let text = passage.text.trim_end();
+        chunks.push(Chunk {
+            id: resource.id.clone(),
+            uri: resource.uri(),
+            title: resource.title.clone(),
+            section: headings.iter().map(|(_, title)| title.clone()).collect(),
+            start,
+            end: start + text.chars().count(),
+            text: text.to_string(),
+        });
+    };
+
+    for line in resource.content.split_inclusive('\n') {
+        let length = line.chars().count();
+        let trimmed = line.trim();
+        let fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+
+        match (!fenced)
+            .then(|| HEADING.captures(line.trim_end()))
+            .flatten()
+        {
+            Some(captures) => {
+                flush(&mut passage, &headings);
+                let level = captures[1].len();
+                headings.retain(|(other, _)| *other < level);
+                headings.push((level, captures[2].to_string()));
+            }
+            None => {
+                let size = passage.chars;
+                if size >= 2 * CHUNK_CHARS || size >= CHUNK_CHARS && trimmed.is_empty() && !fenced {
+                    flush(&mut passage, &headings);
+                }
+                passage.has_body |= !trimmed.is_empty();
+            }
+        }
+        if fence {
+            fenced = !fenced;
+        }
+
+        if passage.start.is_some() || !trimmed.is_empty() {
+            passage.start.get_or_insert(offset);
+            passage.text.push_str(line);
+            passage.chars += length;
+        }
+        offset += length;
+    }
+    flush(&mut passage, &headings);
+    chunks
+}
+
+/// The words of `question` worth searching for, lowercase, or the whole
+/// question if it has none.
+pub fn search_terms(question: &str) -> String {
+    let terms: Vec<String> = question
+        .split(|c: char| !c.is_alphanumeric() && c != '-' && c != '_')
+        .map(|word| word.trim_matches('-').to_lowercase())
+        .filter(|word| word.chars().count() > 1 && !STOP_WORDS.contains(&word.as_str()))
+        .collect();
+    if terms.is_empty() {
+        question.trim().to_string()
+    } else {
+        terms.join(" ")
+    }
+}
+
+/// The `limit` passages of `resources` most relevant to `query`, by the
+/// share of its search terms they contain; terms only in a passage's title
+/// or headings count half. Ties keep the resources' order, and passages
+/// with none of the terms are left out.
+pub fn rank(resources: &[Resource], query: &str, limit: usize) -> Vec<Chunk> {
+    let terms = search_terms(query);
+    let words: Vec<&str> = terms.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(f64, Chunk)> = resources
+        .iter()
+        .flat_map(chunks)
+        .filter_map(|chunk| {
+            let text = chunk.text.to_lowercase();
+            let context = format!("{} {}", chunk.title, chunk.section.join(" ")).to_lowercase();
+            let found: f64 = words
+                .iter()
+                .map(|word| {
+                    if text.contains(word) {
+                        1.0
+                    } else if context.contains(word) {
+                        0.5
+                    } else {
+                        0.0
+                    }
+                })
+                .sum();
+            (found > 0.0).then(|| (found / words.len() as f64, chunk))
+        })
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, chunk)| chunk)
+        .collect()
+}
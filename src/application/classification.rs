@@ -0,0 +1,76 @@
+use std::sync::{Arc, RwLock};
+
+use super::ResourceService;
+use crate::domain::{Classification, ClassificationRule, DomainError, Resource};
+use crate::ports::ResourceHook;
+
+/// Tags every resource with its classification, after every other hook so
+/// scripts can't change it.
+pub(super) struct ClassificationHook {
+    /// Replaced when the rules change while serving.
+    rules: RwLock<Rules>,
+}
+
+struct Rules {
+    default: Classification,
+    rules: Vec<ClassificationRule>,
+}
+
+impl Rules {
+    fn classify(&self, resource: &Resource) -> Classification {
+        self.rules
+            .iter()
+            .find(|rule| rule.sources.contains(resource))
+            .map_or(self.default, |rule| rule.classification)
+    }
+}
+
+impl ResourceHook for ClassificationHook {
+    fn name(&self) -> &str {
+        "classification"
+    }
+
+    fn applies_to(&self, _provider: &str) -> bool {
+        true
+    }
+
+    fn transform(&self, resource: &Resource) -> Result<Option<Resource>, DomainError> {
+        let classification = self.rules.read().unwrap().classify(resource);
+        let mut resource = resource.clone();
+        resource.metadata.insert(
+            Classification::METADATA_KEY.to_string(),
+            classification.as_str().into(),
+        );
+        Ok(Some(resource))
+    }
+
+    fn rank(&self, _resource: &Resource, _query: &str) -> Result<Option<f64>, DomainError> {
+        Ok(None)
+    }
+}
+
+impl ResourceService {
+    /// Classifies resources by the first of `rules` covering them, else as
+    /// `default`, tagging them under the `classification` metadata key.
+    /// Call it after adding the other hooks so they can't change the tag.
+    pub fn set_classification(&mut self, default: Classification, rules: Vec<ClassificationRule>) {
+        let hook = Arc::new(ClassificationHook {
+            rules: RwLock::new(Rules { default, rules }),
+        });
+        self.hooks.push(hook.clone());
+        self.classification = Some(hook);
+    }
+
+    /// Changes the classification rules. Does nothing if resources aren't
+    /// classified.
+    pub fn update_classification(&self, default: Classification, rules: Vec<ClassificationRule>) {
+        if let Some(hook) = &self.classification {
+            *hook.rules.write().unwrap() = Rules { default, rules };
+        }
+    }
+
+    /// Whether resources are classified, so an update would take effect.
+    pub fn classifies(&self) -> bool {
+        self.classification.is_some()
+    }
+}
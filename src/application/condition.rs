@@ -0,0 +1,478 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde_json::Value as Json;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::domain::{DomainError, Resource};
+
+/// A condition on resources, as `--where` takes it, for filtering results
+/// by what providers can't filter on themselves, e.g.
+/// `metadata.state != "Done" && updated_at > now() - 7d`.
+///
+/// Operands are fields (`id`, `title`, `content`, `provider`, `uri`,
+/// `short_id`, `created_at`, `updated_at`, and `metadata.<key>`, following
+/// nested objects with more dots), quoted strings, numbers, `true`,
+/// `false`, `null`, durations such as `7d` or `12h`, and `now()`. Times and
+/// durations add and subtract. Comparisons are `==`, `!=`, `<`, `<=`, `>`,
+/// `>=` and `~` (contains, ignoring case), combined with `&&`, `||`, `!`
+/// and parentheses.
+///
+/// Strings compared with times are read as RFC 3339 timestamps or dates,
+/// and a list compares equal to anything one of its items equals. A field
+/// a resource doesn't have is `null`, which only equals `null`, and on its
+/// own a field is true if it's `true`, a non-empty string or list, or a
+/// number other than zero.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn matches(&self, resource: &Resource) -> bool {
+        self.expr.eval(resource, Utc::now()).is_truthy()
+    }
+}
+
+impl FromStr for Condition {
+    type Err = DomainError;
+
+    fn from_str(source: &str) -> Result<Self, DomainError> {
+        let mut parser = Parser {
+            tokens: lex(source)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected {}", token)));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Number(f64),
+    Duration(Duration),
+    Name(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Str(text) => write!(f, "{:?}", text),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Duration(duration) => write!(f, "duration {}", duration),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+/// Symbols, longest first so `<=` isn't read as `<`.
+const SYMBOLS: [&str; 14] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "~", "!", "+", "-", "(", ")",
+];
+
+fn lex(source: &str) -> Result<Vec<Token>, DomainError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = if c == '"' || c == '\'' {
+            let (text, length) = lex_string(rest, c)?;
+            tokens.push(Token::Str(text));
+            length
+        } else if c.is_ascii_digit() {
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                .unwrap_or(rest.len());
+            let word = &rest[..length];
+            tokens.push(match word.parse() {
+                Ok(number) => Token::Number(number),
+                Err(_) => humantime::parse_duration(word)
+                    .ok()
+                    .and_then(|duration| Duration::from_std(duration).ok())
+                    .map(Token::Duration)
+                    .ok_or_else(|| {
+                        invalid(format!("{} is neither a number nor a duration", word))
+                    })?,
+            });
+            length
+        } else if c.is_alphabetic() || c == '_' {
+            let length = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..length].to_string()));
+            length
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        } else {
+            return Err(invalid(format!("unexpected {:?}", c)));
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// The string `rest` starts with, quoted by `quote`, and its length in
+/// bytes with the quotes. Backslashes escape the next character.
+fn lex_string(rest: &str, quote: char) -> Result<(String, usize), DomainError> {
+    let mut text = String::new();
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, escaped)) => text.push(escaped),
+                None => break,
+            },
+            c if c == quote => return Ok((text, i + c.len_utf8())),
+            c => text.push(c),
+        }
+    }
+    Err(invalid("unterminated string".to_string()))
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Box<Expr>, &'static str, Box<Expr>),
+    Add(Box<Expr>, &'static str, Box<Expr>),
+    Field(Vec<String>),
+    Literal(Operand),
+    Now,
+}
+
+/// Fields other than metadata.
+const FIELDS: [&str; 8] = [
+    "id",
+    "title",
+    "content",
+    "provider",
+    "uri",
+    "short_id",
+    "created_at",
+    "updated_at",
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Takes the next token if it's one of `symbols`.
+    fn symbol(&mut self, symbols: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Symbol(symbol)) if symbols.contains(symbol) => {
+                let symbol = *symbol;
+                self.position += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, DomainError> {
+        let mut expr = self.and()?;
+        while self.symbol(&["||"]).is_some() {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, DomainError> {
+        let mut expr = self.not()?;
+        while self.symbol(&["&&"]).is_some() {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, DomainError> {
+        if self.symbol(&["!"]).is_some() {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, DomainError> {
+        let left = self.sum()?;
+        match self.symbol(&["==", "!=", "<=", ">=", "<", ">", "~"]) {
+            Some(operator) => Ok(Expr::Compare(
+                Box::new(left),
+                operator,
+                Box::new(self.sum()?),
+            )),
+            None => Ok(left),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, DomainError> {
+        let mut expr = self.operand()?;
+        while let Some(operator) = self.symbol(&["+", "-"]) {
+            expr = Expr::Add(Box::new(expr), operator, Box::new(self.operand()?));
+        }
+        Ok(expr)
+    }
+
+    fn operand(&mut self) -> Result<Expr, DomainError> {
+        match self.next() {
+            Some(Token::Str(text)) => Ok(Expr::Literal(Operand::Str(text))),
+            Some(Token::Number(number)) => Ok(Expr::Literal(Operand::Number(number))),
+            Some(Token::Duration(duration)) => Ok(Expr::Literal(Operand::Duration(duration))),
+            Some(Token::Symbol("-")) => match self.next() {
+                Some(Token::Number(number)) => Ok(Expr::Literal(Operand::Number(-number))),
+                _ => Err(invalid("expected a number after -".to_string())),
+            },
+            Some(Token::Symbol("(")) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Symbol(")")) => Ok(expr),
+                    _ => Err(invalid("missing )".to_string())),
+                }
+            }
+            Some(Token::Name(name)) => self.name(name),
+            Some(token) => Err(invalid(format!("unexpected {}", token))),
+            None => Err(invalid("unexpected end".to_string())),
+        }
+    }
+
+    fn name(&mut self, name: String) -> Result<Expr, DomainError> {
+        match name.as_str() {
+            "true" => return Ok(Expr::Literal(Operand::Bool(true))),
+            "false" => return Ok(Expr::Literal(Operand::Bool(false))),
+            "null" => return Ok(Expr::Literal(Operand::Null)),
+            "now" => {
+                return match (self.next(), self.next()) {
+                    (Some(Token::Symbol("(")), Some(Token::Symbol(")"))) => Ok(Expr::Now),
+                    _ => Err(invalid("now takes no arguments: now()".to_string())),
+                }
+            }
+            _ => {}
+        }
+
+        let path: Vec<String> = name.split('.').map(str::to_string).collect();
+        let known = match path.as_slice() {
+            [field] => FIELDS.contains(&field.as_str()),
+            [metadata, keys @ ..] => metadata == "metadata" && keys.iter().all(|k| !k.is_empty()),
+            [] => false,
+        };
+        if !known {
+            return Err(invalid(format!(
+                "unknown field {}; fields are {} and metadata.<key>",
+                name,
+                FIELDS.join(", ")
+            )));
+        }
+        Ok(Expr::Field(path))
+    }
+}
+
+/// A value a condition works with.
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Time(DateTime<Utc>),
+    Duration(Duration),
+    List(Vec<Operand>),
+}
+
+impl Operand {
+    fn from_json(value: &Json) -> Self {
+        match value {
+            Json::Null => Operand::Null,
+            Json::Bool(b) => Operand::Bool(*b),
+            Json::Number(number) => number.as_f64().map_or(Operand::Null, Operand::Number),
+            Json::String(text) => Operand::Str(text.clone()),
+            Json::Array(items) => Operand::List(items.iter().map(Self::from_json).collect()),
+            Json::Object(_) => Operand::Str(value.to_string()),
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Operand::Null => false,
+            Operand::Bool(b) => *b,
+            Operand::Number(number) => *number != 0.0,
+            Operand::Str(text) => !text.is_empty(),
+            Operand::List(items) => !items.is_empty(),
+            Operand::Time(_) | Operand::Duration(_) => true,
+        }
+    }
+
+    /// As a time, if it is one or is a string naming one.
+    fn as_time(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Operand::Time(time) => Some(*time),
+            Operand::Str(text) => DateTime::parse_from_rfc3339(text)
+                .map(|time| time.with_timezone(&Utc))
+                .ok()
+                .or_else(|| {
+                    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                        .ok()
+                        .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+                }),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Operand::Number(number) => Some(*number),
+            Operand::Str(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// How this compares to `other`, if they're comparable.
+    fn compare(&self, other: &Operand) -> Option<Ordering> {
+        match (self, other) {
+            (Operand::Null, Operand::Null) => Some(Ordering::Equal),
+            (Operand::Null, _) | (_, Operand::Null) => None,
+            (Operand::Time(_), _) | (_, Operand::Time(_)) => {
+                self.as_time()?.partial_cmp(&other.as_time()?)
+            }
+            (Operand::Number(_), _) | (_, Operand::Number(_)) => {
+                self.as_number()?.partial_cmp(&other.as_number()?)
+            }
+            (Operand::Str(a), Operand::Str(b)) => Some(a.cmp(b)),
+            (Operand::Bool(a), Operand::Bool(b)) => Some(a.cmp(b)),
+            (Operand::Duration(a), Operand::Duration(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    fn contains(&self, other: &Operand) -> bool {
+        match (self, other) {
+            (Operand::Str(text), Operand::Str(part)) => {
+                text.to_lowercase().contains(&part.to_lowercase())
+            }
+            (Operand::List(items), _) => items
+                .iter()
+                .any(|item| item.compare(other) == Some(Ordering::Equal) || item.contains(other)),
+            _ => false,
+        }
+    }
+
+    fn test(&self, operator: &str, other: &Operand) -> bool {
+        if let Operand::List(items) = self {
+            let any = |operator| items.iter().any(|item| item.test(operator, other));
+            return match operator {
+                "~" => self.contains(other),
+                "!=" => !any("=="),
+                _ => any(operator),
+            };
+        }
+        if operator == "~" {
+            return self.contains(other);
+        }
+        let ordering = self.compare(other);
+        match operator {
+            "==" => ordering == Some(Ordering::Equal),
+            "!=" => ordering != Some(Ordering::Equal),
+            "<" => ordering == Some(Ordering::Less),
+            "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            ">" => ordering == Some(Ordering::Greater),
+            ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            _ => false,
+        }
+    }
+
+    fn add(&self, operator: &str, other: &Operand) -> Operand {
+        let sign = if operator == "-" { -1 } else { 1 };
+        match (self, other) {
+            (Operand::Time(time), Operand::Duration(duration)) => time
+                .checked_add_signed(*duration * sign)
+                .map_or(Operand::Null, Operand::Time),
+            (Operand::Duration(a), Operand::Duration(b)) => Operand::Duration(*a + *b * sign),
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(a), Some(b)) => Operand::Number(a + b * f64::from(sign)),
+                _ => Operand::Null,
+            },
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, resource: &Resource, now: DateTime<Utc>) -> Operand {
+        match self {
+            Expr::Or(a, b) => Operand::Bool(
+                a.eval(resource, now).is_truthy() || b.eval(resource, now).is_truthy(),
+            ),
+            Expr::And(a, b) => Operand::Bool(
+                a.eval(resource, now).is_truthy() && b.eval(resource, now).is_truthy(),
+            ),
+            Expr::Not(a) => Operand::Bool(!a.eval(resource, now).is_truthy()),
+            Expr::Compare(a, operator, b) => {
+                Operand::Bool(a.eval(resource, now).test(operator, &b.eval(resource, now)))
+            }
+            Expr::Add(a, operator, b) => {
+                a.eval(resource, now).add(operator, &b.eval(resource, now))
+            }
+            Expr::Field(path) => field(resource, path),
+            Expr::Literal(operand) => operand.clone(),
+            Expr::Now => Operand::Time(now),
+        }
+    }
+}
+
+fn field(resource: &Resource, path: &[String]) -> Operand {
+    let text = |text: &str| Operand::Str(text.to_string());
+    match path {
+        [field] => match field.as_str() {
+            "id" => text(&resource.id),
+            "title" => text(&resource.title),
+            "content" => text(&resource.content),
+            "provider" => text(&resource.source.provider_name().to_lowercase()),
+            "uri" => Operand::Str(resource.uri()),
+            "short_id" => resource.short_id().map_or(Operand::Null, text),
+            "created_at" => Operand::Time(resource.created_at),
+            "updated_at" => Operand::Time(resource.updated_at),
+            _ => Operand::Null,
+        },
+        [_, keys @ ..] => {
+            let mut value = None;
+            for (i, key) in keys.iter().enumerate() {
+                value = if i == 0 {
+                    resource.metadata.get(key)
+                } else {
+                    value.and_then(|value: &Json| value.get(key))
+                };
+            }
+            value.map_or(Operand::Null, Operand::from_json)
+        }
+        [] => Operand::Null,
+    }
+}
+
+fn invalid(message: String) -> DomainError {
+    DomainError::InvalidQuery(message)
+}
@@ -0,0 +1,233 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::domain::Resource;
+
+/// Changed lines a summary's diff shows at most.
+const MAX_DIFF_LINES: usize = 20;
+
+/// Characters of a changed line shown in a diff.
+const MAX_LINE_CHARS: usize = 120;
+
+/// Cells of the table lines are matched with at most. Larger changes are
+/// shown as the old lines removed and the new ones added, rather than
+/// matched at the cost of tens of megabytes.
+const MAX_MATCH_CELLS: usize = 4_000_000;
+
+/// What changed between two versions of a resource, compact enough to put
+/// in a notification so its recipient can tell whether it matters.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeSummary {
+    /// One line, e.g. `state: Todo → Done; content: +3 -1 lines`.
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_title: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldChange>,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    /// The changed lines, `+` added and `-` removed, with `...` between
+    /// separate changes. Empty when the content didn't change.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub diff: String,
+}
+
+/// A metadata value that changed. `from` is absent for added fields and
+/// `to` for removed ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Value>,
+}
+
+enum Line<'a> {
+    Same,
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Summarizes the changes from `before` to `after`: the title, metadata
+/// fields, and lines of content that differ.
+pub fn summarize(before: &Resource, after: &Resource) -> ChangeSummary {
+    let mut fields: Vec<FieldChange> = after
+        .metadata
+        .iter()
+        .filter(|(field, value)| before.metadata.get(*field) != Some(value))
+        .map(|(field, value)| FieldChange {
+            field: field.clone(),
+            from: before.metadata.get(field).cloned(),
+            to: Some(value.clone()),
+        })
+        .chain(
+            before
+                .metadata
+                .iter()
+                .filter(|(field, _)| !after.metadata.contains_key(*field))
+                .map(|(field, value)| FieldChange {
+                    field: field.clone(),
+                    from: Some(value.clone()),
+                    to: None,
+                }),
+        )
+        .collect();
+    fields.sort_by(|a, b| a.field.cmp(&b.field));
+
+    let lines = diff_lines(&before.content, &after.content);
+    let lines_added = lines
+        .iter()
+        .filter(|line| matches!(line, Line::Added(_)))
+        .count();
+    let lines_removed = lines
+        .iter()
+        .filter(|line| matches!(line, Line::Removed(_)))
+        .count();
+    let previous_title = (before.title != after.title).then(|| before.title.clone());
+
+    let mut parts = Vec::new();
+    if let Some(previous) = &previous_title {
+        parts.push(format!("title: {} → {}", previous, after.title));
+    }
+    for change in &fields {
+        parts.push(format!(
+            "{}: {} → {}",
+            change.field,
+            show(change.from.as_ref()),
+            show(change.to.as_ref())
+        ));
+    }
+    if lines_added + lines_removed > 0 {
+        parts.push(format!(
+            "content: +{} -{} lines",
+            lines_added, lines_removed
+        ));
+    }
+    let text = if parts.is_empty() {
+        "no changes to the title, metadata or content".to_string()
+    } else {
+        parts.join("; ")
+    };
+
+    ChangeSummary {
+        text,
+        previous_title,
+        fields,
+        lines_added,
+        lines_removed,
+        diff: render(&lines),
+    }
+}
+
+/// A metadata value as it reads in a summary's text.
+fn show(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "(none)".to_string(),
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(_) | Value::Object(_)) => "(changed)".to_string(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// The lines of `before` and `after`, each the same in both, removed, or
+/// added, matching as many as possible. Blank lines added or removed are
+/// left out, being only layout.
+fn diff_lines<'a>(before: &'a str, after: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<Line> = (0..prefix).map(|_| Line::Same).collect();
+    if (old_middle.len() + 1) * (new_middle.len() + 1) > MAX_MATCH_CELLS {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        lines.extend(matched(old_middle, new_middle));
+    }
+    lines.extend((0..suffix).map(|_| Line::Same));
+    lines.retain(
+        |line| !matches!(line, Line::Removed(text) | Line::Added(text) if text.trim().is_empty()),
+    );
+    lines
+}
+
+/// Matches the lines of `old` and `new` by their longest common
+/// subsequence.
+fn matched<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let width = new.len() + 1;
+    // common[i * width + j]: the longest common subsequence of old[i..] and
+    // new[j..].
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same);
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || i < old.len() && common[(i + 1) * width + j] >= common[i * width + j + 1]
+        {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// The changed lines, at most `MAX_DIFF_LINES` of them.
+fn render(lines: &[Line]) -> String {
+    let changed = lines
+        .iter()
+        .filter(|line| !matches!(line, Line::Same))
+        .count();
+    let mut diff = String::new();
+    let mut shown = 0;
+    let mut gap = false;
+    for line in lines {
+        let (mark, text) = match line {
+            Line::Same => {
+                gap = shown > 0;
+                continue;
+            }
+            Line::Removed(text) => ('-', text),
+            Line::Added(text) => ('+', text),
+        };
+        if shown == MAX_DIFF_LINES {
+            diff.push_str(&format!("... {} more changed lines\n", changed - shown));
+            break;
+        }
+        if std::mem::take(&mut gap) {
+            diff.push_str("...\n");
+        }
+        let text = match text.char_indices().nth(MAX_LINE_CHARS) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.to_string(),
+        };
+        diff.push_str(&format!("{} {}\n", mark, text));
+        shown += 1;
+    }
+    diff
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+use super::ResourceService;
+use crate::domain::{Change, DomainError, Resource, VersionConflict};
+
+/// A lock per resource being written, so that of two writers holding the
+/// same version, the second checks it only after the first has written and
+/// finds it out of date. Locks are dropped once no writer holds them. They
+/// only order writes made through this process; a change made elsewhere
+/// between the check and the write goes unnoticed.
+#[derive(Default)]
+pub(super) struct WriteLocks(Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>);
+
+impl WriteLocks {
+    async fn lock(&self, id: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.0.lock().unwrap();
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(id.to_string()).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+impl ResourceService {
+    /// Fetches a resource from its provider to be edited: never from the
+    /// cache, which may be behind, and as the provider has it rather than
+    /// as hooks rewrite it, so saving it back writes nothing hooks added.
+    /// Resources hooks leave out can't be edited.
+    pub async fn fetch_editable(&self, id: &str) -> Result<Resource, DomainError> {
+        let id = &self.resolve_id(id).await;
+        let resource = self.fetch_by_id_from_providers(id).await?;
+        self.apply_hooks_to(resource.clone())?;
+        Ok(resource)
+    }
+
+    /// Makes `change` like `apply`, one write at a time per resource. Given
+    /// the `version` of the resource the change was made against, as served
+    /// with it, the change is refused with `DomainError::Conflict` if the
+    /// resource has changed upstream since.
+    pub async fn apply_at(
+        &self,
+        source: &str,
+        change: &Change,
+        version: Option<&str>,
+    ) -> Result<Resource, DomainError> {
+        let Some(target) = change.target() else {
+            return self.apply(source, change).await;
+        };
+        let id = self.resolve_id(target).await;
+        let _guard = self.write_locks.lock(&id).await;
+
+        if let Some(expected) = version {
+            let current = self.fetch_editable(&id).await?;
+            let current_version = self.apply_hooks_to(current.clone())?.version();
+            if current_version != expected {
+                // Whatever the writer read is behind; caching what is
+                // upstream now means reading it again gets the new version.
+                if let Err(e) = self.store(std::slice::from_ref(&current)).await {
+                    tracing::warn!("Failed to cache {}: {}", current.id, e);
+                }
+                return Err(DomainError::Conflict(VersionConflict {
+                    id: current.id,
+                    expected_version: expected.to_string(),
+                    current_version,
+                    updated_at: current.updated_at,
+                }));
+            }
+        }
+        self.apply(source, change).await
+    }
+
+    /// Replaces the content of `edited`, a resource from `fetch_editable`,
+    /// with `content` upstream, unless it has changed there since it was
+    /// fetched.
+    pub async fn update_content(
+        &self,
+        edited: &Resource,
+        content: String,
+    ) -> Result<Resource, DomainError> {
+        let version = self.apply_hooks_to(edited.clone())?.version();
+        let change = Change::Update {
+            id: edited.id.clone(),
+            title: None,
+            content: Some(content),
+        };
+        self.apply_at(edited.source.provider_name(), &change, Some(&version))
+            .await
+    }
+}
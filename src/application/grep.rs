@@ -0,0 +1,84 @@
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::domain::DomainError;
+
+#[derive(Debug, Clone)]
+pub struct GrepOptions {
+    /// Lines of context to keep before and after each match.
+    pub context: usize,
+    pub ignore_case: bool,
+    /// Treat the pattern as a literal string rather than a regex.
+    pub fixed_strings: bool,
+    /// Stop after this many matching lines.
+    pub max_matches: Option<usize>,
+}
+
+/// A contiguous run of lines around one or more matches. Sections whose
+/// context windows overlap are merged, as `grep -C` does.
+#[derive(Debug, Clone, Serialize)]
+pub struct Section {
+    /// 1-based line number of the first line in `lines`.
+    pub start_line: usize,
+    pub lines: Vec<String>,
+    /// 1-based line numbers of the matching lines.
+    pub matches: Vec<usize>,
+}
+
+impl Section {
+    pub fn is_match(&self, line_number: usize) -> bool {
+        self.matches.binary_search(&line_number).is_ok()
+    }
+}
+
+pub fn compile(pattern: &str, options: &GrepOptions) -> Result<Regex, DomainError> {
+    let pattern = if options.fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .map_err(|e| DomainError::InvalidQuery(format!("Invalid pattern: {}", e)))
+}
+
+/// Finds the lines of `content` matching `pattern`, grouped into sections
+/// with `options.context` lines around each match.
+pub fn find_sections(content: &str, pattern: &Regex, options: &GrepOptions) -> Vec<Section> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut sections: Vec<Section> = Vec::new();
+    let mut matched = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        if options.max_matches.is_some_and(|max| matched >= max) {
+            break;
+        }
+        if !pattern.is_match(line) {
+            continue;
+        }
+        matched += 1;
+
+        let start = index.saturating_sub(options.context);
+        let end = (index + options.context).min(lines.len() - 1);
+
+        match sections.last_mut() {
+            // Overlapping or adjacent to the previous section: extend it.
+            Some(section) if start < section.start_line + section.lines.len() => {
+                let next = section.start_line - 1 + section.lines.len();
+                section
+                    .lines
+                    .extend(lines[next..=end].iter().map(|l| l.to_string()));
+                section.matches.push(index + 1);
+            }
+            _ => sections.push(Section {
+                start_line: start + 1,
+                lines: lines[start..=end].iter().map(|l| l.to_string()).collect(),
+                matches: vec![index + 1],
+            }),
+        }
+    }
+
+    sections
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, RwLock};
+
+use super::{shape::CHARS_PER_TOKEN, ResourceService};
+use crate::domain::{DomainError, Guardrails, Query, QueryResult};
+use crate::ports::{current_call_budget, CallBudget};
+
+/// The guardrail call budgets come from.
+const MAX_API_CALLS: &str = "max_api_calls in [guardrails]";
+
+/// Charges provider calls to the budget of the surrounding
+/// `with_call_budget` scope, or else to the budget of the command the
+/// process runs, if it has one.
+#[derive(Default)]
+pub struct CallLimiter {
+    command: RwLock<Option<Arc<CallBudget>>>,
+}
+
+impl CallLimiter {
+    pub fn charge(&self, provider: &str) -> Result<(), DomainError> {
+        let budget = current_call_budget().or_else(|| self.command.read().unwrap().clone());
+        match budget {
+            Some(budget) => budget.charge(provider),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ResourceService {
+    pub fn set_guardrails(&mut self, guardrails: Guardrails) {
+        self.guardrails = guardrails;
+    }
+
+    /// Charges the provider calls made outside any `with_call_budget`
+    /// scope, i.e. by the one command a CLI process runs, to a single
+    /// budget of `max_api_calls`. Servers give each request its own budget
+    /// instead, so background polling is never cut off.
+    pub fn budget_command_calls(&self) {
+        let budget = self
+            .guardrails
+            .max_api_calls
+            .map(|max| Arc::new(CallBudget::new(max, MAX_API_CALLS)));
+        *self.calls.command.write().unwrap() = budget;
+    }
+
+    /// A budget of `max_api_calls` for one request, if calls are limited.
+    pub fn request_call_budget(&self) -> Option<Arc<CallBudget>> {
+        self.guardrails
+            .max_api_calls
+            .map(|max| Arc::new(CallBudget::new(max, MAX_API_CALLS)))
+    }
+
+    /// The characters a context bundle may take: `max_chars`, or the
+    /// `max_bundle_tokens` guardrail's worth when unset. Asking for more
+    /// than the guardrail allows is an error.
+    pub fn bundle_limit(&self, max_chars: Option<usize>) -> Result<Option<usize>, DomainError> {
+        let Some(max_tokens) = self.guardrails.max_bundle_tokens else {
+            return Ok(max_chars);
+        };
+        let allowed = max_tokens.saturating_mul(CHARS_PER_TOKEN);
+        match max_chars {
+            Some(max_chars) if max_chars > allowed => Err(DomainError::LimitExceeded(format!(
+                "a bundle of {} characters is over the {} tokens (about {} characters) allowed by max_bundle_tokens in [guardrails]",
+                max_chars, max_tokens, allowed
+            ))),
+            Some(max_chars) => Ok(Some(max_chars)),
+            None => Ok(Some(allowed)),
+        }
+    }
+
+    /// `query` with its limit checked against `max_resources`, and set to
+    /// it when the query has none.
+    pub(super) fn limit_query(&self, query: &Query) -> Result<Query, DomainError> {
+        let mut query = query.clone();
+        if let Some(max) = self.guardrails.max_resources {
+            match query.limit {
+                Some(limit) if limit > max => {
+                    return Err(DomainError::LimitExceeded(format!(
+                        "a limit of {} resources is over the {} allowed by max_resources in [guardrails]",
+                        limit, max
+                    )))
+                }
+                Some(_) => {}
+                None => query.limit = Some(max),
+            }
+        }
+        Ok(query)
+    }
+
+    /// Cuts results gathered from several providers down to
+    /// `max_resources`, warning about those left out.
+    pub(super) fn cap_results(&self, result: &mut QueryResult) {
+        let Some(max) = self.guardrails.max_resources else {
+            return;
+        };
+        if result.resources.len() > max {
+            result.warnings.push(format!(
+                "{} resources left out by max_resources in [guardrails]",
+                result.resources.len() - max
+            ));
+            result.resources.truncate(max);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+
+use super::{search::SearchOptions, ResourceService};
+use crate::{
+    domain::{DomainError, QueryResult, QuerySource, Resource, ResultOrigin, SearchTier},
+    ports::ResourceRepository,
+};
+
+impl ResourceService {
+    /// The resource as the cache knew it at `as_of`, without asking its
+    /// provider.
+    pub async fn fetch_resource_as_of(
+        &self,
+        id: &str,
+        as_of: DateTime<Utc>,
+    ) -> Result<Resource, DomainError> {
+        let repository = self.history()?;
+        let id = self.resolve_id(id).await;
+        match repository.find_version(&id, as_of).await? {
+            Some(resource) => self.apply_hooks_to(resource),
+            None => Err(DomainError::ResourceNotFound(format!(
+                "{} as of {} (the cache holds no version from then)",
+                id,
+                as_of.to_rfc3339()
+            ))),
+        }
+    }
+
+    /// Searches the selected providers' resources as the cache knew them
+    /// at `as_of`, without asking the providers. `options.max_staleness`
+    /// doesn't apply.
+    pub async fn search_as_of(
+        &self,
+        query: &str,
+        sources: Option<Vec<QuerySource>>,
+        options: &SearchOptions,
+        as_of: DateTime<Utc>,
+    ) -> Result<QueryResult, DomainError> {
+        let repository = self.history()?;
+        let mut result = QueryResult::default();
+        let sources = sources.unwrap_or_else(|| vec![QuerySource::All]);
+
+        for provider in self.select_providers(&sources, &mut result) {
+            let name = provider.provider_name().to_lowercase();
+            match repository
+                .search_versions(&name, query, as_of, options.include_archived)
+                .await
+            {
+                Ok(mut resources) => {
+                    if !options.include_archived {
+                        resources.retain(|resource| !resource.is_archived());
+                    }
+                    result.resources.append(&mut resources);
+                    result.origins.insert(
+                        name,
+                        ResultOrigin {
+                            tier: SearchTier::History,
+                            as_of,
+                        },
+                    );
+                }
+                Err(e) => result.add_failure(&name, &e),
+            }
+        }
+
+        self.apply_hooks(&mut result, Some(query));
+        self.cap_results(&mut result);
+        Ok(result)
+    }
+
+    fn history(&self) -> Result<&dyn ResourceRepository, DomainError> {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.repository.as_ref())
+            .ok_or_else(|| {
+                DomainError::StorageError(
+                    "--as-of reads the local cache's history, and the cache is disabled"
+                        .to_string(),
+                )
+            })
+    }
+}
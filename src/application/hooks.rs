@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use super::ResourceService;
+use crate::domain::{DomainError, QueryResult, Resource};
+
+/// Failures of one hook during a call: how many resources it failed on, and
+/// the first error.
+type HookFailures = BTreeMap<String, (usize, DomainError)>;
+
+impl ResourceService {
+    /// Passes `result` through the hook chain: each resource through every
+    /// applicable hook in order, then, for searches, sorted by its total
+    /// rank. A hook that fails on a resource leaves it as it was, with a
+    /// warning.
+    pub(super) fn apply_hooks(&self, result: &mut QueryResult, query: Option<&str>) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let mut failures = HookFailures::new();
+
+        let resources = std::mem::take(&mut result.resources);
+        result.resources = resources
+            .into_iter()
+            .filter_map(|resource| self.run_hooks(resource, &mut failures))
+            .collect();
+
+        if let Some(query) = query {
+            self.rank(&mut result.resources, query, &mut failures);
+        }
+        report_failures(failures, &mut result.warnings);
+    }
+
+    /// Sorts resources that already went through the hooks by their total
+    /// rank for `query`, as searches are.
+    pub(super) fn rank_resources(
+        &self,
+        resources: &mut Vec<Resource>,
+        query: &str,
+        warnings: &mut Vec<String>,
+    ) {
+        let mut failures = HookFailures::new();
+        self.rank(resources, query, &mut failures);
+        report_failures(failures, warnings);
+    }
+
+    /// Like `apply_hooks`, for a single resource requested by ID.
+    pub(super) fn apply_hooks_to(&self, resource: Resource) -> Result<Resource, DomainError> {
+        let id = resource.id.clone();
+        let mut failures = HookFailures::new();
+        let resource = self.run_hooks(resource, &mut failures);
+
+        for (hook, (_, error)) in failures {
+            tracing::warn!("Hook {} failed on {}: {}", hook, id, error);
+        }
+        resource.ok_or_else(|| {
+            DomainError::ResourceNotFound(format!("{} was filtered out by a hook", id))
+        })
+    }
+
+    fn run_hooks(&self, mut resource: Resource, failures: &mut HookFailures) -> Option<Resource> {
+        let provider = resource.source.provider_name().to_lowercase();
+
+        for hook in self.hooks.iter().filter(|hook| hook.applies_to(&provider)) {
+            match hook.transform(&resource) {
+                Ok(Some(transformed)) => resource = transformed,
+                Ok(None) => return None,
+                Err(e) => record_failure(failures, hook.name(), e),
+            }
+        }
+        Some(resource)
+    }
+
+    /// Sorts `resources` by their summed scores, highest first. The order is
+    /// left alone if no hook ranks, and resources no hook scores count as 0.
+    fn rank(&self, resources: &mut Vec<Resource>, query: &str, failures: &mut HookFailures) {
+        let mut ranked = false;
+        let mut scored: Vec<(f64, Resource)> = Vec::with_capacity(resources.len());
+
+        for resource in resources.drain(..) {
+            let provider = resource.source.provider_name().to_lowercase();
+            let mut score = 0.0;
+            for hook in self.hooks.iter().filter(|hook| hook.applies_to(&provider)) {
+                match hook.rank(&resource, query) {
+                    Ok(Some(hook_score)) => {
+                        score += hook_score;
+                        ranked = true;
+                    }
+                    Ok(None) => {}
+                    Err(e) => record_failure(failures, hook.name(), e),
+                }
+            }
+            scored.push((score, resource));
+        }
+
+        if ranked {
+            scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        }
+        resources.extend(scored.into_iter().map(|(_, resource)| resource));
+    }
+}
+
+fn report_failures(failures: HookFailures, warnings: &mut Vec<String>) {
+    for (hook, (count, error)) in failures {
+        warnings.push(format!(
+            "Hook {} failed on {} resource(s): {}",
+            hook, count, error
+        ));
+    }
+}
+
+fn record_failure(failures: &mut HookFailures, hook: &str, error: DomainError) {
+    failures
+        .entry(hook.to_string())
+        .and_modify(|(count, _)| *count += 1)
+        .or_insert((1, error));
+}
@@ -0,0 +1,175 @@
+use regex::Regex;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use super::ResourceService;
+use crate::domain::Resource;
+use crate::ports::LinkResolver;
+
+/// How long a looked-up title, or the lack of one, is reused for.
+const TITLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Titles looked up at once.
+const CONCURRENT_LOOKUPS: usize = 8;
+
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"https?://[^\s<>()\[\]"'`]+"#).unwrap());
+
+/// Annotates bare links in resource content with the titles of the pages
+/// they point to.
+pub struct LinkEnricher {
+    resolver: Arc<dyn LinkResolver>,
+    /// Links annotated per resource; the rest are left as they are.
+    max_per_resource: usize,
+    titles: Mutex<HashMap<String, (Instant, Option<String>)>>,
+}
+
+impl LinkEnricher {
+    pub fn new(resolver: Arc<dyn LinkResolver>, max_per_resource: usize) -> Self {
+        Self {
+            resolver,
+            max_per_resource,
+            titles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Titles of `urls`, from earlier lookups where there were any. Failed
+    /// lookups count as untitled, and are retried once the TTL is up.
+    async fn titles(&self, urls: Vec<&str>) -> HashMap<String, String> {
+        let mut titles = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let cached = self.titles.lock().unwrap();
+            for url in urls {
+                match cached.get(url) {
+                    Some((looked_up, title)) if looked_up.elapsed() < TITLE_TTL => {
+                        if let Some(title) = title {
+                            titles.insert(url.to_string(), title.clone());
+                        }
+                    }
+                    _ => missing.push(url),
+                }
+            }
+        }
+
+        for chunk in missing.chunks(CONCURRENT_LOOKUPS) {
+            let lookups = chunk.iter().map(|url| async move {
+                let title = match self.resolver.title(url).await {
+                    Ok(title) => title,
+                    Err(e) => {
+                        tracing::debug!("No title for {}: {}", url, e);
+                        None
+                    }
+                };
+                (url.to_string(), title)
+            });
+            for (url, title) in futures::future::join_all(lookups).await {
+                self.titles
+                    .lock()
+                    .unwrap()
+                    .insert(url.clone(), (Instant::now(), title.clone()));
+                if let Some(title) = title {
+                    titles.insert(url, title);
+                }
+            }
+        }
+        titles
+    }
+}
+
+impl ResourceService {
+    /// Turns bare links in fetched content into Markdown links titled after
+    /// the pages they point to, and lists them under the `links` metadata
+    /// key. Links are left alone until this is called.
+    pub fn set_link_enricher(&mut self, enricher: LinkEnricher) {
+        self.links = Some(enricher);
+    }
+
+    pub(super) async fn enrich_links(&self, resources: &mut [Resource]) {
+        let Some(enricher) = &self.links else {
+            return;
+        };
+        let found: Vec<Vec<String>> = resources
+            .iter()
+            .map(|resource| bare_links(&resource.content, enricher.max_per_resource))
+            .collect();
+        let mut urls: Vec<&str> = found.iter().flatten().map(String::as_str).collect();
+        urls.sort();
+        urls.dedup();
+        if urls.is_empty() {
+            return;
+        }
+
+        let titles = enricher.titles(urls).await;
+        for (resource, links) in resources.iter_mut().zip(&found) {
+            let titled: BTreeMap<&str, &str> = links
+                .iter()
+                .filter_map(|url| Some((url.as_str(), titles.get(url)?.as_str())))
+                .collect();
+            if titled.is_empty() {
+                continue;
+            }
+            resource.content = annotate(&resource.content, &titled);
+            resource.metadata.insert(
+                "links".to_string(),
+                json!(titled
+                    .iter()
+                    .map(|(url, title)| json!({ "url": url, "title": title }))
+                    .collect::<Vec<_>>()),
+            );
+        }
+    }
+}
+
+/// Where each bare link in `content` starts and ends. Links already in
+/// Markdown link or autolink syntax are skipped, as are links to Notion
+/// pages and Linear issues, whose titles are behind a login.
+fn bare_link_spans(content: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    URL.find_iter(content).filter_map(|found| {
+        let before = content[..found.start()].chars().next_back();
+        if matches!(before, Some('(' | '<' | '[')) {
+            return None;
+        }
+        // Sentence punctuation after a link isn't part of it.
+        let url = found
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        if Resource::id_from_web_url(url).is_some() {
+            return None;
+        }
+        Some((found.start(), found.start() + url.len()))
+    })
+}
+
+/// The first `max` distinct bare links in `content`.
+fn bare_links(content: &str, max: usize) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for (start, end) in bare_link_spans(content) {
+        if links.len() == max {
+            break;
+        }
+        let url = &content[start..end];
+        if !links.iter().any(|link| link == url) {
+            links.push(url.to_string());
+        }
+    }
+    links
+}
+
+/// `content` with each bare link in `titled` replaced by a Markdown link.
+fn annotate(content: &str, titled: &BTreeMap<&str, &str>) -> String {
+    let mut annotated = String::with_capacity(content.len());
+    let mut copied = 0;
+    for (start, end) in bare_link_spans(content) {
+        let url = &content[start..end];
+        if let Some(title) = titled.get(url) {
+            annotated.push_str(&content[copied..start]);
+            annotated.push_str(&format!("[{}]({})", title.replace(['[', ']'], ""), url));
+            copied = end;
+        }
+    }
+    annotated.push_str(&content[copied..]);
+    annotated
+}
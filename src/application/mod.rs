@@ -1,132 +1,533 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+pub mod alias;
+pub mod attachments;
+pub mod bench;
+pub mod chunks;
+pub mod classification;
+pub mod condition;
+pub mod diff;
+pub mod edit;
+pub mod grep;
+pub mod guardrails;
+pub mod history;
+pub mod hooks;
+pub mod links;
+pub mod normalize;
+pub mod project;
+pub mod routing;
+pub mod scope;
+pub mod scoring;
+pub mod search;
+pub mod shape;
+pub mod snapshot;
+pub mod sync;
+pub mod testing;
+pub mod usage;
+pub mod watch;
+
+use chrono::Utc;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use crate::{
-    domain::{DomainError, Query, QuerySource, Resource},
-    ports::ResourceProvider,
+    domain::{
+        Change, DiscoveredItem, DomainError, Guardrails, ListingPage, Query, QueryResult,
+        QuerySource, Resource, SyncScope, UsageEvent,
+    },
+    ports::{
+        report_progress, ReadOnlyProvider, ResourceHook, ResourceProvider, ResourceRepository,
+    },
 };
 
+/// Results named in each provider's progress message.
+const PREVIEWED_RESULTS: usize = 3;
+
 pub struct ResourceService {
-    providers: HashMap<String, Arc<dyn ResourceProvider>>,
+    /// Replaced while serving when the config file changes.
+    providers: RwLock<HashMap<String, Arc<dyn ResourceProvider>>>,
+    cache: Option<ResourceCache>,
+    /// Default for `SearchOptions::max_staleness`.
+    max_staleness: Option<Duration>,
+    hooks: Vec<Arc<dyn ResourceHook>>,
+    links: Option<links::LinkEnricher>,
+    read_only: bool,
+    /// The scope served, if any, which providers added later must be in.
+    scope: Option<Arc<scope::ScopeHook>>,
+    /// Tags resources with their classification, if configured.
+    classification: Option<Arc<classification::ClassificationHook>>,
+    /// Counts provider calls and cache hits once there is a cache.
+    usage: Arc<usage::UsageRecorder>,
+    /// Named lists of sources, by lowercase name, usable as a source.
+    source_groups: RwLock<HashMap<String, Vec<String>>>,
+    guardrails: Guardrails,
+    calls: Arc<guardrails::CallLimiter>,
+    /// What `sync` mirrors of each provider, by lowercase name.
+    sync_scopes: RwLock<HashMap<String, Vec<SyncScope>>>,
+    write_locks: edit::WriteLocks,
+}
+
+/// Local repository that provider results are written through to, and that
+/// `fetch_resource_by_id` reads from while entries are fresh.
+struct ResourceCache {
+    repository: Arc<dyn ResourceRepository>,
+    ttl: Duration,
+    read: bool,
 }
 
 impl ResourceService {
     pub fn new() -> Self {
         Self {
-            providers: HashMap::new(),
+            providers: RwLock::default(),
+            cache: None,
+            max_staleness: None,
+            hooks: Vec::new(),
+            links: None,
+            read_only: false,
+            scope: None,
+            classification: None,
+            usage: Arc::default(),
+            source_groups: RwLock::default(),
+            guardrails: Guardrails::default(),
+            calls: Arc::default(),
+            sync_scopes: RwLock::default(),
+            write_locks: edit::WriteLocks::default(),
         }
     }
 
-    pub fn add_provider(&mut self, provider: Arc<dyn ResourceProvider>) {
-        let name = provider.provider_name().to_lowercase();
-        self.providers.insert(name, provider);
+    /// Stores provider results in `repository`. When `read` is set, cached
+    /// resources younger than `ttl` are served without calling the provider.
+    /// Provider calls and cache hits are counted in its usage statistics.
+    pub fn set_cache(
+        &mut self,
+        repository: Arc<dyn ResourceRepository>,
+        ttl: Duration,
+        read: bool,
+    ) {
+        self.usage.set_repository(repository.clone());
+        self.cache = Some(ResourceCache {
+            repository,
+            ttl,
+            read,
+        });
+    }
+
+    /// How stale search results may be when a search doesn't say. `None`
+    /// always searches live.
+    pub fn set_max_staleness(&mut self, max_staleness: Option<Duration>) {
+        self.max_staleness = max_staleness;
     }
 
-    pub async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
-        match &query.source {
-            QuerySource::Notion => {
-                let provider = self.providers.get("notion").ok_or_else(|| {
-                    DomainError::ProviderError("Notion provider not configured".to_string())
-                })?;
-                provider.fetch_resources(query).await
+    /// Replaces the source groups, each a list of providers, other groups
+    /// or `all` that a query for the group's name goes to. A group named
+    /// after a provider hides it.
+    pub fn set_source_groups(&self, groups: impl IntoIterator<Item = (String, Vec<String>)>) {
+        *self.source_groups.write().unwrap() = groups
+            .into_iter()
+            .map(|(name, members)| (name.to_lowercase(), members))
+            .collect();
+    }
+
+    /// The names of the providers `sources` stand for, in order, with
+    /// groups expanded and `all` left as is. A group that contains itself,
+    /// directly or through others, is expanded once.
+    pub fn expand_sources(&self, sources: &[QuerySource]) -> Vec<String> {
+        let groups = self.source_groups.read().unwrap();
+        let mut expanded = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending: Vec<String> = sources
+            .iter()
+            .rev()
+            .map(|source| source.name().to_string())
+            .collect();
+        while let Some(name) = pending.pop() {
+            match groups.get(&name) {
+                Some(members) => {
+                    if seen.insert(name) {
+                        pending.extend(members.iter().rev().map(|m| m.to_lowercase()));
+                    }
+                }
+                None => expanded.push(name),
             }
-            QuerySource::Linear => {
-                let provider = self.providers.get("linear").ok_or_else(|| {
-                    DomainError::ProviderError("Linear provider not configured".to_string())
-                })?;
-                provider.fetch_resources(query).await
+        }
+        expanded
+    }
+
+    /// Registers `provider`, normalizing the text of everything it returns
+    /// (see `normalize::normalize`) before it's cached or shown. Returns
+    /// false, adding nothing, if the scope served leaves the provider out.
+    /// Provider names are case-insensitive, and registering a second
+    /// provider under a name already taken, or the same provider twice, is
+    /// refused with `DuplicateProvider`.
+    pub fn add_provider(&self, provider: Arc<dyn ResourceProvider>) -> Result<bool, DomainError> {
+        self.register(provider, false)
+    }
+
+    /// Like `add_provider`, but replaces a provider registered under the
+    /// same name instead of refusing, e.g. to rebuild it with new settings.
+    pub fn replace_provider(&self, provider: Arc<dyn ResourceProvider>) -> bool {
+        self.register(provider, true).unwrap_or(false)
+    }
+
+    fn register(
+        &self,
+        provider: Arc<dyn ResourceProvider>,
+        replace: bool,
+    ) -> Result<bool, DomainError> {
+        let name = provider.provider_name().to_lowercase();
+        if !self.scope_includes(&name) {
+            return Ok(false);
+        }
+        let provider = Arc::new(usage::CountingProvider::new(
+            provider,
+            self.usage.clone(),
+            self.calls.clone(),
+        ));
+        let mut provider: Arc<dyn ResourceProvider> =
+            Arc::new(normalize::NormalizedProvider::new(provider));
+        if self.read_only {
+            provider = Arc::new(ReadOnlyProvider::new(provider));
+        }
+        match self.providers.write().unwrap().entry(name) {
+            Entry::Occupied(entry) if !replace => {
+                Err(DomainError::DuplicateProvider(entry.key().clone()))
             }
-            QuerySource::All => {
-                let mut all_resources = Vec::new();
-
-                for provider in self.providers.values() {
-                    match provider.fetch_resources(query).await {
-                        Ok(mut resources) => all_resources.append(&mut resources),
-                        Err(e) => {
-                            tracing::warn!("Provider {} failed: {}", provider.provider_name(), e)
-                        }
-                    }
+            Entry::Occupied(mut entry) => {
+                entry.insert(provider);
+                Ok(true)
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(provider);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Unregisters a provider, returning whether it was registered.
+    /// Requests already under way finish with it.
+    pub fn remove_provider(&self, name: &str) -> bool {
+        self.providers
+            .write()
+            .unwrap()
+            .remove(&name.to_lowercase())
+            .is_some()
+    }
+
+    /// Refuses every change to upstream data, through providers added before
+    /// or after.
+    pub fn set_read_only(&mut self) {
+        if self.read_only {
+            return;
+        }
+        self.read_only = true;
+        for provider in self.providers.get_mut().unwrap().values_mut() {
+            *provider = Arc::new(ReadOnlyProvider::new(provider.clone()));
+        }
+    }
+
+    /// Appends a hook to the chain that fetch, get, list and search results
+    /// pass through. The cache keeps resources as providers returned them.
+    #[cfg(feature = "scripting")]
+    pub fn add_hook(&mut self, hook: Arc<dyn ResourceHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub async fn fetch_resources(&self, query: &Query) -> Result<QueryResult, DomainError> {
+        let query = &self.limit_query(query)?;
+        let mut result = QueryResult::default();
+
+        for provider in self.select_providers(std::slice::from_ref(&query.source), &mut result) {
+            match provider.fetch_resources(query).await {
+                Ok(mut resources) => {
+                    report_progress(|| results_message(provider.provider_name(), &resources));
+                    result.resources.append(&mut resources);
                 }
+                Err(e) => result.add_failure(provider.provider_name(), &e),
+            }
+        }
+
+        self.store_results(&mut result).await;
+        self.enrich_links(&mut result.resources).await;
+        self.apply_hooks(&mut result, None);
+        self.cap_results(&mut result);
+        Ok(result)
+    }
+
+    /// Providers matching `sources`, groups expanded, each at most once.
+    /// Requested providers that aren't configured are recorded as failures.
+    fn select_providers(
+        &self,
+        sources: &[QuerySource],
+        result: &mut QueryResult,
+    ) -> Vec<Arc<dyn ResourceProvider>> {
+        let providers = self.providers.read().unwrap();
+        let mut selected: Vec<Arc<dyn ResourceProvider>> = Vec::new();
+        let mut add = |provider: &Arc<dyn ResourceProvider>| {
+            if !selected
+                .iter()
+                .any(|p| p.provider_name() == provider.provider_name())
+            {
+                selected.push(provider.clone());
+            }
+        };
 
-                Ok(all_resources)
+        for name in self.expand_sources(sources) {
+            if name == QuerySource::All.name() {
+                providers.values().for_each(&mut add);
+                continue;
+            }
+            match providers.get(&name) {
+                Some(provider) => add(provider),
+                None => result.add_failure(
+                    &name,
+                    &DomainError::NotConfigured {
+                        provider: name.to_string(),
+                    },
+                ),
             }
         }
+
+        selected
     }
 
+    /// Fetches a resource by ID, or by anything `resolve_id` accepts.
     pub async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let id = &self.resolve_id(id).await;
+        if let Some(cache) = self.cache.as_ref().filter(|cache| cache.read) {
+            match cache.repository.find_entry(id).await {
+                Ok(Some(entry))
+                    if entry.deleted_at.is_none()
+                        && Utc::now() - entry.cached_at < ttl(cache.ttl) =>
+                {
+                    tracing::debug!("Serving {} from cache", id);
+                    let provider = entry.resource.source.provider_name();
+                    self.usage.record(UsageEvent::CacheHit, provider).await;
+                    let mut resource = entry.resource;
+                    self.enrich_links(std::slice::from_mut(&mut resource)).await;
+                    return self.apply_hooks_to(resource);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Cache lookup failed: {}", e),
+            }
+        }
+
+        self.refresh_resource(id).await
+    }
+
+    /// Fetches a resource from its provider even if it is cached, updating
+    /// the cache.
+    pub async fn refresh_resource(&self, id: &str) -> Result<Resource, DomainError> {
+        let mut resource = self.fetch_by_id_from_providers(id).await?;
+        if let Err(e) = self.store(std::slice::from_ref(&resource)).await {
+            tracing::warn!("Failed to cache {}: {}", id, e);
+        }
+        self.enrich_links(std::slice::from_mut(&mut resource)).await;
+        self.apply_hooks_to(resource)
+    }
+
+    async fn fetch_by_id_from_providers(&self, id: &str) -> Result<Resource, DomainError> {
         // Determine provider from ID prefix
         if id.starts_with("notion_") {
-            let provider = self.providers.get("notion").ok_or_else(|| {
-                DomainError::ProviderError("Notion provider not configured".to_string())
-            })?;
+            let provider =
+                self.get_provider("notion")
+                    .ok_or_else(|| DomainError::NotConfigured {
+                        provider: "notion".to_string(),
+                    })?;
             provider.fetch_resource_by_id(id).await
         } else if id.starts_with("linear_") {
-            let provider = self.providers.get("linear").ok_or_else(|| {
-                DomainError::ProviderError("Linear provider not configured".to_string())
-            })?;
+            let provider =
+                self.get_provider("linear")
+                    .ok_or_else(|| DomainError::NotConfigured {
+                        provider: "linear".to_string(),
+                    })?;
+            provider.fetch_resource_by_id(id).await
+        } else if let Some(provider) = id
+            .split_once('_')
+            .and_then(|(prefix, _)| self.get_provider(prefix))
+        {
             provider.fetch_resource_by_id(id).await
+        } else if id.starts_with("https://") || id.starts_with("http://") {
+            // `resolve_id` maps the links providers can serve, so no provider
+            // knows this one.
+            Err(DomainError::InvalidQuery(format!(
+                "Unsupported link {}: paste a Notion page or Linear issue link",
+                id
+            )))
         } else {
-            // Try all providers
-            for provider in self.providers.values() {
-                match provider.fetch_resource_by_id(id).await {
+            // Ask every provider at once; the first to find it wins, and
+            // dropping the others cancels their requests.
+            let providers: Vec<_> = self.providers.read().unwrap().values().cloned().collect();
+            let mut lookups: FuturesUnordered<_> = providers
+                .iter()
+                .map(|provider| provider.fetch_resource_by_id(id))
+                .collect();
+            let mut failure = None;
+            while let Some(result) = lookups.next().await {
+                match result {
                     Ok(resource) => return Ok(resource),
-                    Err(DomainError::ResourceNotFound(_)) => continue,
-                    Err(e) => return Err(e),
+                    Err(DomainError::ResourceNotFound(_)) => {}
+                    Err(e) => {
+                        failure.get_or_insert(e);
+                    }
                 }
             }
-            Err(DomainError::ResourceNotFound(format!(
-                "Resource not found: {}",
-                id
-            )))
+            Err(failure.unwrap_or_else(|| {
+                DomainError::ResourceNotFound(format!("Resource not found: {}", id))
+            }))
+        }
+    }
+
+    pub async fn discover(&self, source: &str) -> Result<Vec<DiscoveredItem>, DomainError> {
+        let provider = self
+            .get_provider(source)
+            .ok_or_else(|| DomainError::NotConfigured {
+                provider: source.to_string(),
+            })?;
+        provider.discover().await
+    }
+
+    /// `provider`'s resources in the cache, without asking the provider,
+    /// e.g. for completing IDs. Nothing when the cache is disabled or can't
+    /// be read.
+    pub async fn cached_resources(&self, provider: &str) -> Vec<Resource> {
+        let Some(cache) = &self.cache else {
+            return Vec::new();
+        };
+        match cache.repository.list_entries(Some(provider), false).await {
+            Ok(entries) => entries.into_iter().map(|entry| entry.resource).collect(),
+            Err(e) => {
+                tracing::debug!("Failed to list cached {} resources: {}", provider, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Makes `change` upstream through `source`, caching the resource it
+    /// creates or changes and passing it through the hooks.
+    pub async fn apply(&self, source: &str, change: &Change) -> Result<Resource, DomainError> {
+        let provider = self
+            .get_provider(source)
+            .ok_or_else(|| DomainError::NotConfigured {
+                provider: source.to_string(),
+            })?;
+        let resource = provider.apply(change).await?;
+        if let Err(e) = self.store(std::slice::from_ref(&resource)).await {
+            tracing::warn!("Failed to cache {}: {}", resource.id, e);
         }
+        self.apply_hooks_to(resource)
     }
 
-    pub async fn search(
+    /// One page of everything `source` lists, starting at its `cursor`,
+    /// passed through the hooks. Resources may come without their content,
+    /// and a page may be left empty by hooks before the listing ends.
+    pub async fn list_page(
         &self,
-        query: &str,
-        sources: Option<Vec<QuerySource>>,
-    ) -> Result<Vec<Resource>, DomainError> {
-        let mut all_resources = Vec::new();
-
-        let search_sources = sources.unwrap_or_else(|| vec![QuerySource::All]);
-
-        for source in search_sources {
-            match source {
-                QuerySource::Notion => {
-                    if let Some(provider) = self.providers.get("notion") {
-                        match provider.search(query).await {
-                            Ok(mut resources) => all_resources.append(&mut resources),
-                            Err(e) => tracing::warn!("Notion search failed: {}", e),
-                        }
-                    }
-                }
-                QuerySource::Linear => {
-                    if let Some(provider) = self.providers.get("linear") {
-                        match provider.search(query).await {
-                            Ok(mut resources) => all_resources.append(&mut resources),
-                            Err(e) => tracing::warn!("Linear search failed: {}", e),
-                        }
-                    }
-                }
-                QuerySource::All => {
-                    for provider in self.providers.values() {
-                        match provider.search(query).await {
-                            Ok(mut resources) => all_resources.append(&mut resources),
-                            Err(e) => tracing::warn!(
-                                "Provider {} search failed: {}",
-                                provider.provider_name(),
-                                e
-                            ),
-                        }
-                    }
-                }
+        source: &str,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let provider = self
+            .get_provider(source)
+            .ok_or_else(|| DomainError::NotConfigured {
+                provider: source.to_string(),
+            })?;
+        let query = Query {
+            source: QuerySource::All,
+            filters: HashMap::new(),
+            limit: None,
+        };
+        let page = provider.list_page(&query, cursor).await?;
+
+        let mut result = QueryResult {
+            resources: page.resources,
+            ..Default::default()
+        };
+        self.apply_hooks(&mut result, None);
+        for warning in &result.warnings {
+            tracing::warn!("Listing {}: {}", source, warning);
+        }
+        Ok(ListingPage {
+            resources: result.resources,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Readies every provider at once, building those set up lazily and
+    /// connecting to upstream servers, so first requests don't wait for it.
+    /// Failures are logged; the providers are tried again when used.
+    pub async fn warm_up(&self) {
+        let providers: Vec<Arc<dyn ResourceProvider>> =
+            self.providers.read().unwrap().values().cloned().collect();
+        let ready = providers
+            .iter()
+            .map(|provider| async move { (provider.provider_name(), provider.warm_up().await) });
+        for (name, result) in futures::future::join_all(ready).await {
+            match result {
+                Ok(()) => tracing::debug!("{} is ready", name),
+                Err(e) => tracing::warn!("{} failed to warm up: {}", name, e),
             }
         }
+    }
+
+    pub fn list_providers(&self) -> Vec<&'static str> {
+        self.providers
+            .read()
+            .unwrap()
+            .values()
+            .map(|p| p.provider_name())
+            .collect()
+    }
+
+    /// Writes resources through to the cache. Failures only cost a cache
+    /// miss later, so they are logged rather than returned.
+    async fn store(&self, resources: &[Resource]) -> Result<(), DomainError> {
+        match &self.cache {
+            Some(cache) => cache.repository.save_all(resources).await,
+            None => Ok(()),
+        }
+    }
 
-        Ok(all_resources)
+    async fn store_results(&self, result: &mut QueryResult) {
+        if let Err(e) = self.store(&result.resources).await {
+            result
+                .warnings
+                .push(format!("Failed to cache results: {}", e));
+        }
+    }
+
+    pub fn get_provider(&self, name: &str) -> Option<Arc<dyn ResourceProvider>> {
+        self.providers
+            .read()
+            .unwrap()
+            .get(&name.to_lowercase())
+            .cloned()
     }
+}
 
-    pub fn list_providers(&self) -> Vec<&str> {
-        self.providers.values().map(|p| p.provider_name()).collect()
+/// Progress message for one provider's results, naming the first few so
+/// clients can show them before the whole call completes.
+fn results_message(provider: &str, resources: &[Resource]) -> String {
+    let titles: Vec<&str> = resources
+        .iter()
+        .take(PREVIEWED_RESULTS)
+        .map(|resource| resource.title.as_str())
+        .collect();
+    let more = if resources.len() > PREVIEWED_RESULTS {
+        "; ..."
+    } else {
+        ""
+    };
+    match resources.len() {
+        0 => format!("{}: no results", provider.to_lowercase()),
+        count => format!(
+            "{}: {} results: {}{}",
+            provider.to_lowercase(),
+            count,
+            titles.join("; "),
+            more
+        ),
     }
 }
+
+fn ttl(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX)
+}
@@ -0,0 +1,199 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::domain::{
+    Attachment, Blob, Change, DiscoveredItem, DomainError, ListingPage, Query, Resource,
+    SyncEstimate,
+};
+use crate::ports::ResourceProvider;
+
+/// Characters that take up no space and only get in the way of matching
+/// and diffing. The zero-width joiner is kept: emoji sequences need it.
+const ZERO_WIDTH: [char; 5] = ['\u{200B}', '\u{200C}', '\u{2060}', '\u{FEFF}', '\u{00AD}'];
+
+/// Cleans up text as adapters hand it over: repairs UTF-8 that was decoded
+/// as Windows-1252 somewhere upstream (the "â€¢" for "•"), drops
+/// zero-width characters, composes to Unicode NFC, and collapses runs of
+/// blank lines outside fenced code blocks into one.
+pub fn normalize(text: &str) -> String {
+    let text: String = repair_mojibake(&text.replace("\r\n", "\n"))
+        .chars()
+        .filter(|c| !ZERO_WIDTH.contains(c))
+        .nfc()
+        .collect();
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut in_fence = false;
+    let mut blank_run = 0;
+    for line in text.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence && line.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+            normalized.push('\n');
+            continue;
+        }
+        blank_run = 0;
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+    normalized.trim_end().to_string()
+}
+
+/// `text` with each run of characters that is really a UTF-8 sequence read
+/// as Windows-1252 replaced by the character it encodes.
+fn repair_mojibake(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut repaired = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some((decoded, len)) = decode_misread(&chars[i..]) {
+            repaired.push(decoded);
+            i += len;
+        } else {
+            repaired.push(chars[i]);
+            i += 1;
+        }
+    }
+    repaired
+}
+
+/// The character at the start of `chars` if they spell out its UTF-8
+/// encoding in Windows-1252, and how many of them it took.
+fn decode_misread(chars: &[char]) -> Option<(char, usize)> {
+    let lead = cp1252_byte(chars[0])?;
+    let len = match lead {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let mut bytes = vec![lead];
+    for &c in chars.get(1..len)? {
+        bytes.push(cp1252_byte(c).filter(|b| (0x80..=0xBF).contains(b))?);
+    }
+    let decoded = std::str::from_utf8(&bytes).ok()?.chars().next()?;
+    Some((decoded, len))
+}
+
+/// The Windows-1252 byte `c` decodes from, for bytes outside ASCII. Bytes
+/// Windows-1252 leaves undefined are commonly passed through as the C1
+/// control with the same value, so those count too.
+fn cp1252_byte(c: char) -> Option<u8> {
+    let byte = match c {
+        '€' => 0x80,
+        '‚' => 0x82,
+        'ƒ' => 0x83,
+        '„' => 0x84,
+        '…' => 0x85,
+        '†' => 0x86,
+        '‡' => 0x87,
+        'ˆ' => 0x88,
+        '‰' => 0x89,
+        'Š' => 0x8A,
+        '‹' => 0x8B,
+        'Œ' => 0x8C,
+        'Ž' => 0x8E,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '˜' => 0x98,
+        '™' => 0x99,
+        'š' => 0x9A,
+        '›' => 0x9B,
+        'œ' => 0x9C,
+        'ž' => 0x9E,
+        'Ÿ' => 0x9F,
+        '\u{81}' | '\u{8D}' | '\u{8F}' | '\u{90}' | '\u{9D}' => c as u8,
+        '\u{A0}'..='\u{FF}' => c as u8,
+        _ => return None,
+    };
+    Some(byte)
+}
+
+fn normalize_resource(mut resource: Resource) -> Resource {
+    resource.title = normalize(&resource.title);
+    resource.content = normalize(&resource.content);
+    resource
+}
+
+fn normalize_all(resources: Vec<Resource>) -> Vec<Resource> {
+    resources.into_iter().map(normalize_resource).collect()
+}
+
+/// Normalizes the titles and content of everything `inner` returns, so the
+/// cache, hooks and clients all see the same clean text.
+pub struct NormalizedProvider {
+    inner: Arc<dyn ResourceProvider>,
+}
+
+impl NormalizedProvider {
+    pub fn new(inner: Arc<dyn ResourceProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for NormalizedProvider {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        self.inner.fetch_resources(query).await.map(normalize_all)
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        self.inner
+            .fetch_resource_by_id(id)
+            .await
+            .map(normalize_resource)
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        self.inner.search(query).await.map(normalize_all)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let mut page = self.inner.list_page(query, cursor).await?;
+        page.resources = normalize_all(page.resources);
+        Ok(page)
+    }
+
+    async fn hydrate(&self, resource: Resource) -> Result<Resource, DomainError> {
+        self.inner.hydrate(resource).await.map(normalize_resource)
+    }
+
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        self.inner.discover().await
+    }
+
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        self.inner.download(attachment).await
+    }
+
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        self.inner.apply(change).await.map(normalize_resource)
+    }
+
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        self.inner.warm_up().await
+    }
+
+    async fn estimate_sync(&self, query: &Query) -> Result<Option<SyncEstimate>, DomainError> {
+        self.inner.estimate_sync(query).await
+    }
+}
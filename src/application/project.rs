@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use super::ResourceService;
+use crate::domain::{
+    DomainError, ProjectDefinition, ProviderFailure, Query, QueryResult, QuerySource, Resource,
+};
+
+/// Most resources gathered per project rule.
+const RULE_LIMIT: usize = 250;
+
+/// Characters of each resource's content included in a context bundle.
+const BUNDLE_CONTENT_CHARS: usize = 4000;
+
+/// A project's resources from every provider, with a summary of where they
+/// stand.
+#[derive(Debug, Serialize)]
+pub struct ProjectReport {
+    pub name: String,
+    pub description: Option<String>,
+    pub status: ProjectStatus,
+    pub resources: Vec<Resource>,
+    pub errors: Vec<ProviderFailure>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ProjectStatus {
+    /// Resources per provider.
+    pub providers: BTreeMap<String, usize>,
+    /// Linear issues per workflow state.
+    pub issue_states: BTreeMap<String, usize>,
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+impl ResourceService {
+    /// Gathers the resources matched by a project's rules. Rules that fail
+    /// are reported in `errors` without discarding the others' resources.
+    pub async fn project(
+        &self,
+        name: &str,
+        definition: &ProjectDefinition,
+    ) -> Result<ProjectReport, DomainError> {
+        let rules = definition
+            .linear_projects
+            .iter()
+            .map(|project| (QuerySource::Linear, "project", project))
+            .chain(
+                definition
+                    .notion_pages
+                    .iter()
+                    .map(|page| (QuerySource::Notion, "page_tree", page)),
+            );
+
+        let mut result = QueryResult::default();
+        for (source, filter, value) in rules {
+            let query = Query {
+                source,
+                filters: [(filter.to_string(), value.clone())].into(),
+                limit: Some(RULE_LIMIT),
+            };
+            let mut rule_result = self.fetch_resources(&query).await?;
+            result.resources.append(&mut rule_result.resources);
+            result.errors.append(&mut rule_result.errors);
+            result.warnings.append(&mut rule_result.warnings);
+        }
+
+        let mut seen = HashSet::new();
+        result
+            .resources
+            .retain(|r| !r.is_archived() && seen.insert(r.id.clone()));
+        // Most recent first, unless hooks or scoring rank them otherwise.
+        result
+            .resources
+            .sort_by_key(|r| std::cmp::Reverse(r.updated_at));
+        self.rank_resources(&mut result.resources, "", &mut result.warnings);
+
+        let mut status = ProjectStatus {
+            last_updated: result.resources.iter().map(|r| r.updated_at).max(),
+            ..ProjectStatus::default()
+        };
+        for resource in &result.resources {
+            *status
+                .providers
+                .entry(resource.source.provider_name().to_string())
+                .or_default() += 1;
+            if let Some(state) = resource.metadata.get("state").and_then(|s| s.as_str()) {
+                *status.issue_states.entry(state.to_string()).or_default() += 1;
+            }
+        }
+
+        Ok(ProjectReport {
+            name: name.to_string(),
+            description: definition.description.clone(),
+            status,
+            resources: result.resources,
+            errors: result.errors,
+            warnings: result.warnings,
+        })
+    }
+}
+
+impl ProjectReport {
+    /// Renders the project as one Markdown document to hand to a model as
+    /// context: the status summary, then each resource with its source, in
+    /// rank order. With `max_chars`, resources stop being added once the
+    /// next would take the bundle past it.
+    pub fn bundle(&self, max_chars: Option<usize>) -> String {
+        let mut bundle = format!("# Project: {}\n\n", self.name);
+        if let Some(description) = &self.description {
+            bundle.push_str(&format!("{}\n\n", description));
+        }
+
+        bundle.push_str("## Status\n\n");
+        for (provider, count) in &self.status.providers {
+            bundle.push_str(&format!("- {}: {} resources\n", provider, count));
+        }
+        for (state, count) in &self.status.issue_states {
+            bundle.push_str(&format!("- Issues {}: {}\n", state, count));
+        }
+        for failure in &self.errors {
+            bundle.push_str(&format!(
+                "- {} unavailable: {}\n",
+                failure.provider, failure.error
+            ));
+        }
+
+        for (included, resource) in self.resources.iter().enumerate() {
+            let section = section(resource);
+            if max_chars.is_some_and(|max| bundle.len() + section.len() > max) {
+                bundle.push_str(&format!(
+                    "\n_{} more resources left out._\n",
+                    self.resources.len() - included
+                ));
+                break;
+            }
+            bundle.push_str(&section);
+        }
+
+        bundle
+    }
+}
+
+/// One resource's part of a bundle.
+fn section(resource: &Resource) -> String {
+    let mut section = format!("\n## {}\n\n", resource.title);
+    section.push_str(&format!("Source: {}", resource.uri()));
+    if let Some(url) = resource.provenance.as_ref().and_then(|p| p.url.as_ref()) {
+        section.push_str(&format!(" ({})", url));
+    }
+    section.push_str(&format!(
+        "\nUpdated: {}\n",
+        resource.updated_at.format("%Y-%m-%d")
+    ));
+    if let Some(state) = resource.metadata.get("state").and_then(|s| s.as_str()) {
+        section.push_str(&format!("State: {}\n", state));
+    }
+
+    let content = resource.excerpt(BUNDLE_CONTENT_CHARS);
+    if !content.trim().is_empty() {
+        section.push_str(&format!("\n{}\n", content.trim()));
+    }
+    section
+}
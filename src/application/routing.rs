@@ -0,0 +1,241 @@
+use super::{search::SearchOptions, ResourceService};
+use crate::domain::{DomainError, QueryResult, QuerySource, Route};
+
+/// What a query can be about, each with the words that give it away and
+/// the providers that hold it when there is no `[sources]` group named
+/// after it.
+const INTENTS: [(&str, &[&str], &[&str]); 3] = [
+    (
+        "docs",
+        &[
+            "doc",
+            "docs",
+            "document",
+            "documentation",
+            "roadmap",
+            "spec",
+            "specs",
+            "rfc",
+            "design",
+            "wiki",
+            "notes",
+            "meeting",
+            "minutes",
+            "handbook",
+            "guide",
+            "policy",
+            "plan",
+            "okr",
+            "okrs",
+            "onboarding",
+            "runbook",
+            "postmortem",
+            "strategy",
+        ],
+        &["notion", "coda"],
+    ),
+    (
+        "tickets",
+        &[
+            "bug",
+            "bugs",
+            "issue",
+            "issues",
+            "ticket",
+            "tickets",
+            "task",
+            "tasks",
+            "todo",
+            "sprint",
+            "backlog",
+            "epic",
+            "story",
+            "incident",
+            "assigned",
+            "blocker",
+            "blocked",
+            "regression",
+            "crash",
+        ],
+        &["linear", "trello", "zendesk", "azuredevops", "todoist"],
+    ),
+    (
+        "mail",
+        &[
+            "email", "emails", "mail", "inbox", "message", "messages", "thread", "reply", "from:",
+            "to:", "subject:",
+        ],
+        &["imap"],
+    ),
+];
+
+/// Hosts whose links only one provider can answer for.
+const HOSTS: [(&str, &str); 6] = [
+    ("trello.com", "trello"),
+    ("todoist.com", "todoist"),
+    ("coda.io", "coda"),
+    ("zendesk.com", "zendesk"),
+    ("dev.azure.com", "azuredevops"),
+    ("visualstudio.com", "azuredevops"),
+];
+
+impl ResourceService {
+    /// Where a search given no sources should go: a fetch if the query is
+    /// one reference to a resource (anything `resolve_id` maps to a
+    /// configured provider's ID, or such an ID itself), the providers
+    /// answering for a link's host, or the providers of what the query's
+    /// words say it is about. Otherwise, and whenever those providers
+    /// aren't configured, every provider.
+    pub async fn route(&self, query: &str) -> Route {
+        let query = query.trim();
+        if !query.is_empty() && !query.contains(char::is_whitespace) {
+            let id = self.resolve_id(query).await;
+            let provider = id.split_once('_').map(|(prefix, _)| prefix);
+            if provider.is_some_and(|provider| self.get_provider(provider).is_some()) {
+                return Route::Get { id };
+            }
+            if let Some(host) = link_host(query) {
+                let providers: Vec<String> = HOSTS
+                    .iter()
+                    .filter(|(domain, _)| {
+                        host == *domain || host.ends_with(&format!(".{}", domain))
+                    })
+                    .map(|(_, provider)| provider.to_string())
+                    .filter(|provider| self.get_provider(provider).is_some())
+                    .collect();
+                if providers.is_empty() {
+                    return Route::All;
+                }
+                return Route::Sources {
+                    intent: "link".to_string(),
+                    providers,
+                    widened: false,
+                };
+            }
+        }
+
+        let words: Vec<String> = query
+            .split(|c: char| c.is_whitespace() || matches!(c, ',' | '.' | '?' | '!' | '"'))
+            .map(str::to_lowercase)
+            .collect();
+        let scores: Vec<usize> = INTENTS
+            .iter()
+            .map(|(_, keywords, _)| {
+                words
+                    .iter()
+                    .filter(|word| {
+                        keywords
+                            .iter()
+                            .any(|keyword| match keyword.strip_suffix(':') {
+                                // Search operators such as `from:alice`.
+                                Some(_) => word.starts_with(keyword),
+                                None => *word == keyword,
+                            })
+                    })
+                    .count()
+            })
+            .collect();
+        let best = scores.iter().copied().max().unwrap_or(0);
+        if best == 0 || scores.iter().filter(|score| **score == best).count() > 1 {
+            return Route::All;
+        }
+        let (intent, _, defaults) =
+            INTENTS[scores.iter().position(|score| *score == best).unwrap()];
+
+        let group = self.source_groups.read().unwrap().contains_key(intent);
+        let providers: Vec<String> = if group {
+            self.expand_sources(&[QuerySource::Named(intent.to_string())])
+        } else {
+            defaults.iter().map(|name| name.to_string()).collect()
+        };
+        if providers.iter().any(|name| name == QuerySource::All.name()) {
+            return Route::All;
+        }
+        let providers: Vec<String> = providers
+            .into_iter()
+            .filter(|name| self.get_provider(name).is_some())
+            .collect();
+        if providers.is_empty() {
+            return Route::All;
+        }
+        Route::Sources {
+            intent: intent.to_string(),
+            providers,
+            widened: false,
+        }
+    }
+
+    /// Searches where `route` sends `query`, falling back to every
+    /// provider when a fetch fails and to the rest when the routed
+    /// providers find nothing. The result records the route taken.
+    pub async fn search_routed(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<QueryResult, DomainError> {
+        let route = self.route(query).await;
+        tracing::debug!("Routing {:?}: {:?}", query, route);
+
+        let mut result = match &route {
+            Route::Get { id } => match self.fetch_resource_by_id(id).await {
+                Ok(resource) => QueryResult {
+                    resources: vec![resource],
+                    ..Default::default()
+                },
+                Err(e) => {
+                    let mut result = self.search(query, None, options).await?;
+                    result
+                        .warnings
+                        .push(format!("Fetching {} failed ({}); searched instead", id, e));
+                    result.route = Some(Route::All);
+                    return Ok(result);
+                }
+            },
+            Route::Sources {
+                intent, providers, ..
+            } => {
+                let sources = providers
+                    .iter()
+                    .map(|name| QuerySource::parse(name))
+                    .collect();
+                let mut result = self.search(query, Some(sources), options).await?;
+                if result.resources.is_empty() {
+                    let rest: Vec<QuerySource> = self
+                        .list_providers()
+                        .into_iter()
+                        .map(str::to_lowercase)
+                        .filter(|name| !providers.contains(name))
+                        .map(|name| QuerySource::parse(&name))
+                        .collect();
+                    if !rest.is_empty() {
+                        let mut more = self.search(query, Some(rest), options).await?;
+                        result.resources.append(&mut more.resources);
+                        result.errors.append(&mut more.errors);
+                        result.warnings.append(&mut more.warnings);
+                        result.origins.append(&mut more.origins);
+                        result.route = Some(Route::Sources {
+                            intent: intent.clone(),
+                            providers: providers.clone(),
+                            widened: true,
+                        });
+                        return Ok(result);
+                    }
+                }
+                result
+            }
+            Route::All => self.search(query, None, options).await?,
+        };
+        result.route = Some(route);
+        Ok(result)
+    }
+}
+
+/// The lowercase host of an http(s) link.
+fn link_host(text: &str) -> Option<String> {
+    let rest = text
+        .strip_prefix("https://")
+        .or_else(|| text.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, RwLock};
+
+use super::ResourceService;
+use crate::domain::{DomainError, Resource, ScopeDefinition};
+use crate::ports::ResourceHook;
+
+/// Leaves resources outside a scope out of every result.
+pub(super) struct ScopeHook {
+    name: String,
+    /// Replaced when the scope's definition changes while serving.
+    scope: RwLock<ScopeDefinition>,
+}
+
+impl ResourceHook for ScopeHook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn applies_to(&self, _provider: &str) -> bool {
+        true
+    }
+
+    fn transform(&self, resource: &Resource) -> Result<Option<Resource>, DomainError> {
+        Ok(self
+            .scope
+            .read()
+            .unwrap()
+            .contains(resource)
+            .then(|| resource.clone()))
+    }
+
+    fn rank(&self, _resource: &Resource, _query: &str) -> Result<Option<f64>, DomainError> {
+        Ok(None)
+    }
+}
+
+impl ResourceService {
+    /// Exposes only what the scope `name` covers: other providers are
+    /// removed, and other resources filtered out ahead of every other hook.
+    /// Resources already cached stay cached, but are filtered too.
+    pub fn set_scope(&mut self, name: &str, scope: ScopeDefinition) {
+        self.providers
+            .get_mut()
+            .unwrap()
+            .retain(|provider, _| scope.includes_provider(provider));
+        let hook = Arc::new(ScopeHook {
+            name: format!("scope {}", name),
+            scope: RwLock::new(scope),
+        });
+        self.hooks.insert(0, hook.clone());
+        self.scope = Some(hook);
+    }
+
+    /// Changes the definition of the scope served, removing providers it
+    /// no longer covers. Providers it newly covers have to be added again.
+    /// Does nothing if no scope is served.
+    pub fn update_scope(&self, scope: ScopeDefinition) {
+        let Some(hook) = &self.scope else {
+            return;
+        };
+        self.providers
+            .write()
+            .unwrap()
+            .retain(|provider, _| scope.includes_provider(provider));
+        *hook.scope.write().unwrap() = scope;
+    }
+
+    /// Whether the scope served, if any, covers `provider`.
+    pub fn scope_includes(&self, provider: &str) -> bool {
+        self.scope
+            .as_ref()
+            .is_none_or(|hook| hook.scope.read().unwrap().includes_provider(provider))
+    }
+}
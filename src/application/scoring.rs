@@ -0,0 +1,109 @@
+use chrono::Utc;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::ResourceService;
+use crate::domain::{DomainError, Resource, ResourceSource, ScoringWeights};
+use crate::ports::ResourceHook;
+
+/// Ranks resources by a weighted sum of relevance, recency, provider
+/// priority and pin status.
+struct ScoringHook {
+    weights: ScoringWeights,
+    /// Resource IDs the configured pins resolved to.
+    pins: HashSet<String>,
+}
+
+impl ScoringHook {
+    fn score(&self, resource: &Resource, query: &str) -> f64 {
+        let weights = &self.weights;
+        let provider = resource.source.provider_name().to_lowercase();
+        let priority = weights
+            .providers
+            .iter()
+            .find(|(listed, _)| listed.eq_ignore_ascii_case(&provider))
+            .map_or(0.0, |(_, priority)| priority.clamp(0.0, 1.0));
+        let pinned = if self.is_pinned(resource) { 1.0 } else { 0.0 };
+
+        weights.relevance * relevance(resource, query)
+            + weights.recency * self.recency(resource)
+            + weights.priority * priority
+            + weights.pinned * pinned
+    }
+
+    /// 1 for a resource updated now, halving every half-life.
+    fn recency(&self, resource: &Resource) -> f64 {
+        let half_life = self.weights.recency_half_life_days;
+        if half_life <= 0.0 {
+            return 0.0;
+        }
+        let age_days = (Utc::now() - resource.updated_at).num_seconds().max(0) as f64 / 86_400.0;
+        0.5_f64.powf(age_days / half_life)
+    }
+
+    /// Linear issues pinned by identifier resolve to `linear_<identifier>`
+    /// rather than their ID, so those are matched by identifier.
+    fn is_pinned(&self, resource: &Resource) -> bool {
+        self.pins.contains(&resource.id)
+            || matches!(resource.source, ResourceSource::Linear { .. })
+                && resource.short_id().is_some_and(|identifier| {
+                    self.pins
+                        .contains(&format!("linear_{}", identifier.to_uppercase()))
+                })
+    }
+}
+
+impl ResourceHook for ScoringHook {
+    fn name(&self) -> &str {
+        "scoring"
+    }
+
+    fn applies_to(&self, _provider: &str) -> bool {
+        true
+    }
+
+    fn transform(&self, resource: &Resource) -> Result<Option<Resource>, DomainError> {
+        Ok(Some(resource.clone()))
+    }
+
+    fn rank(&self, resource: &Resource, query: &str) -> Result<Option<f64>, DomainError> {
+        Ok(Some(self.score(resource, query)))
+    }
+}
+
+/// Share of the query's words found in the resource, counting those only
+/// in its content half. 0 for an empty query.
+fn relevance(resource: &Resource, query: &str) -> f64 {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return 0.0;
+    }
+    let title = resource.title.to_lowercase();
+    let content = resource.content.to_lowercase();
+    let found: f64 = words
+        .iter()
+        .map(|word| {
+            if title.contains(word.as_str()) {
+                1.0
+            } else if content.contains(word.as_str()) {
+                0.5
+            } else {
+                0.0
+            }
+        })
+        .sum();
+    found / words.len() as f64
+}
+
+impl ResourceService {
+    /// Orders search results and project bundles by `weights`, on top of
+    /// the ranks of hooks added before or after. Pins are resolved like
+    /// `get` arguments, so set up the cache first for aliases to work.
+    pub async fn set_scoring(&mut self, weights: ScoringWeights) {
+        let mut pins = HashSet::new();
+        for pin in &weights.pins {
+            pins.insert(self.resolve_id(pin).await);
+        }
+        self.hooks.push(Arc::new(ScoringHook { weights, pins }));
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::{DateTime, Utc};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use super::{results_message, ttl, ResourceService};
+use crate::domain::{
+    DomainError, QueryResult, QuerySource, Resource, ResultOrigin, SearchTier, UsageEvent,
+};
+use crate::ports::report_progress;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Include resources archived or deleted upstream.
+    pub include_archived: bool,
+    /// Age of the oldest data a search may be answered from. Within it, a
+    /// provider is searched in the local index if `sync` ran recently
+    /// enough, else answered from an earlier identical search, and only
+    /// otherwise searched live. `None` uses the service default; zero
+    /// always searches live.
+    pub max_staleness: Option<Duration>,
+}
+
+impl ResourceService {
+    /// Searches the selected providers, each from the fastest tier that is
+    /// fresh enough for `options.max_staleness`. Archived resources, and
+    /// resources tombstoned by `sync`, are left out unless
+    /// `options.include_archived` is set.
+    pub async fn search(
+        &self,
+        query: &str,
+        sources: Option<Vec<QuerySource>>,
+        options: &SearchOptions,
+    ) -> Result<QueryResult, DomainError> {
+        let mut result = QueryResult::default();
+        let sources = sources.unwrap_or_else(|| vec![QuerySource::All]);
+        let max_staleness = options.max_staleness.or(self.max_staleness).map(ttl);
+        let key = query.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        for provider in self.select_providers(&sources, &mut result) {
+            let name = provider.provider_name().to_lowercase();
+
+            if let Some(max_staleness) = max_staleness {
+                if let Some((mut resources, origin)) = self
+                    .search_cache(&name, &key, max_staleness, &mut result.warnings)
+                    .await
+                {
+                    tracing::debug!("Serving {} search from {}", name, origin.tier);
+                    self.usage.record(UsageEvent::CacheHit, &name).await;
+                    report_progress(|| results_message(&name, &resources));
+                    result.resources.append(&mut resources);
+                    result.origins.insert(name, origin);
+                    continue;
+                }
+            }
+
+            match provider.search(query).await {
+                Ok(mut resources) => {
+                    self.store_search(&name, &key, &resources, &mut result.warnings)
+                        .await;
+                    report_progress(|| results_message(&name, &resources));
+                    result.resources.append(&mut resources);
+                    result.origins.insert(
+                        name,
+                        ResultOrigin {
+                            tier: SearchTier::Live,
+                            as_of: Utc::now(),
+                        },
+                    );
+                }
+                Err(e) => result.add_failure(&name, &e),
+            }
+        }
+
+        if !options.include_archived {
+            let tombstoned = match &self.cache {
+                Some(cache) => match cache.repository.tombstoned_ids().await {
+                    Ok(ids) => ids.into_iter().collect(),
+                    Err(e) => {
+                        result
+                            .warnings
+                            .push(format!("Failed to read tombstones: {}", e));
+                        HashSet::new()
+                    }
+                },
+                None => HashSet::new(),
+            };
+            result
+                .resources
+                .retain(|r| !r.is_archived() && !tombstoned.contains(&r.id));
+        }
+
+        self.enrich_links(&mut result.resources).await;
+        self.apply_hooks(&mut result, Some(query));
+        self.cap_results(&mut result);
+        Ok(result)
+    }
+
+    /// Runs `queries` like `search`, at most `concurrency` at a time so a
+    /// batch stays within providers' rate limits, returning each query's
+    /// results in the order given.
+    pub async fn search_batch(
+        &self,
+        queries: &[String],
+        sources: &[QuerySource],
+        options: &SearchOptions,
+        concurrency: usize,
+    ) -> Vec<Result<QueryResult, DomainError>> {
+        stream::iter(queries)
+            .map(|query| self.search(query, Some(sources.to_vec()), options))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Searches for each of `terms`, at most `concurrency` at a time, merging the results
+    /// into one: resources found for the most terms come first, and each
+    /// provider's failure is listed once.
+    pub async fn search_any(
+        &self,
+        terms: &[String],
+        sources: &[QuerySource],
+        options: &SearchOptions,
+        concurrency: usize,
+    ) -> Result<QueryResult, DomainError> {
+        let mut merged = QueryResult::default();
+        let mut hits: HashMap<String, usize> = HashMap::new();
+        // Batches rather than `search_batch`'s stream, whose futures aren't
+        // `Send` enough to run in spawned tasks such as MCP requests.
+        let mut results = Vec::new();
+        for batch in terms.chunks(concurrency.max(1)) {
+            let searches = batch
+                .iter()
+                .map(|term| self.search(term, Some(sources.to_vec()), options));
+            results.extend(future::join_all(searches).await);
+        }
+        for result in results {
+            let result = result?;
+            for resource in result.resources {
+                let count = hits.entry(resource.id.clone()).or_default();
+                if *count == 0 {
+                    merged.resources.push(resource);
+                }
+                *count += 1;
+            }
+            for failure in result.errors {
+                if !merged.errors.iter().any(|f| f.provider == failure.provider) {
+                    merged.errors.push(failure);
+                }
+            }
+            for warning in result.warnings {
+                if !merged.warnings.contains(&warning) {
+                    merged.warnings.push(warning);
+                }
+            }
+            for (provider, origin) in result.origins {
+                merged.origins.entry(provider).or_insert(origin);
+            }
+        }
+
+        merged
+            .resources
+            .sort_by_key(|resource| std::cmp::Reverse(hits[&resource.id]));
+        self.cap_results(&mut merged);
+        Ok(merged)
+    }
+
+    /// Answers a search from the cache when it holds data younger than
+    /// `max_staleness`: the local index first, then the stored results of
+    /// the same search.
+    async fn search_cache(
+        &self,
+        provider: &str,
+        query: &str,
+        max_staleness: chrono::Duration,
+        warnings: &mut Vec<String>,
+    ) -> Option<(Vec<Resource>, ResultOrigin)> {
+        let repository = &self.cache.as_ref()?.repository;
+        let fresh = |at: DateTime<Utc>| Utc::now() - at <= max_staleness;
+
+        match repository.last_sync(provider).await {
+            Ok(Some(synced_at)) if fresh(synced_at) => {
+                match repository.search_entries(provider, query).await {
+                    Ok(entries) => {
+                        let origin = ResultOrigin {
+                            tier: SearchTier::LocalIndex,
+                            as_of: synced_at,
+                        };
+                        return Some((entries.into_iter().map(|e| e.resource).collect(), origin));
+                    }
+                    Err(e) => warnings.push(format!("Local {} search failed: {}", provider, e)),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warnings.push(format!("Failed to read {} sync time: {}", provider, e)),
+        }
+
+        let search = match repository.find_search(provider, query).await {
+            Ok(Some(search)) if fresh(search.searched_at) => search,
+            Ok(_) => return None,
+            Err(e) => {
+                warnings.push(format!("Failed to read cached {} search: {}", provider, e));
+                return None;
+            }
+        };
+
+        // Results evicted or tombstoned since the search can't be served, so
+        // the provider is searched again.
+        let mut resources = Vec::new();
+        for id in &search.ids {
+            match repository.find_entry(id).await {
+                Ok(Some(entry)) if entry.deleted_at.is_none() => resources.push(entry.resource),
+                _ => return None,
+            }
+        }
+
+        let origin = ResultOrigin {
+            tier: SearchTier::CachedResults,
+            as_of: search.searched_at,
+        };
+        Some((resources, origin))
+    }
+
+    /// Writes live results through to the cache, with the search that
+    /// produced them.
+    async fn store_search(
+        &self,
+        provider: &str,
+        query: &str,
+        resources: &[Resource],
+        warnings: &mut Vec<String>,
+    ) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+
+        let ids: Vec<String> = resources.iter().map(|r| r.id.clone()).collect();
+        let stored = match cache.repository.save_all(resources).await {
+            Ok(()) => cache.repository.save_search(provider, query, &ids).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            warnings.push(format!("Failed to cache results: {}", e));
+        }
+    }
+}
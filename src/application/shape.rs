@@ -0,0 +1,194 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::sync::LazyLock;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::domain::DomainError;
+
+/// Rough characters per token, for budgeting content without a tokenizer.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+pub(super) static HEADING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(#{1,6})\s+(.+?)\s*#*\s*$").unwrap());
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\]]*)\]\(([^)]*)\)").unwrap());
+static EMPHASIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\*\*|__|`").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    /// Content as the provider wrote it, with headings marked up.
+    Markdown,
+    /// Markup removed.
+    Text,
+}
+
+impl ContentFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ContentFormat::Markdown => "text/markdown",
+            ContentFormat::Text => "text/plain",
+        }
+    }
+}
+
+/// How to cut down a resource's content before returning it.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct ShapeOptions {
+    /// Only the section under this heading (matched ignoring case), up to
+    /// the next heading of the same or a higher level.
+    pub section: Option<String>,
+    /// Content format. Defaults to the content as stored.
+    pub format: Option<ContentFormat>,
+    /// Cut the content to roughly this many tokens.
+    pub max_tokens: Option<usize>,
+}
+
+impl ShapeOptions {
+    /// Sets the option named `key` from its text form, e.g. a URI query
+    /// parameter.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), DomainError> {
+        let invalid = || DomainError::InvalidQuery(format!("Invalid {}: {}", key, value));
+        match key {
+            "section" => self.section = Some(value.to_string()),
+            "format" => {
+                self.format = Some(match value {
+                    "markdown" => ContentFormat::Markdown,
+                    "text" => ContentFormat::Text,
+                    _ => return Err(invalid()),
+                })
+            }
+            "max_tokens" => self.max_tokens = Some(value.parse().map_err(|_| invalid())?),
+            _ => {
+                return Err(DomainError::InvalidQuery(format!(
+                    "Unknown option: {}",
+                    key
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies `options` to `content`: selects the section, converts the
+/// format, then trims to the token budget.
+pub fn shape(content: &str, options: &ShapeOptions) -> Result<String, DomainError> {
+    let mut content = match &options.section {
+        Some(heading) => section(content, heading)?,
+        None => content.to_string(),
+    };
+    if options.format == Some(ContentFormat::Text) {
+        content = to_text(&content);
+    }
+    if let Some(max_tokens) = options.max_tokens {
+        content = truncate(&content, max_tokens);
+    }
+    Ok(content)
+}
+
+/// The lines under `heading`, the heading included. Unknown headings are an
+/// error listing the ones there are.
+fn section(content: &str, heading: &str) -> Result<String, DomainError> {
+    let lines: Vec<&str> = content.lines().collect();
+    let headings: Vec<(usize, usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let captures = HEADING.captures(line)?;
+            let level = captures.get(1)?.as_str().len();
+            Some((index, level, captures.get(2)?.as_str()))
+        })
+        .collect();
+
+    let Some(&(start, level, _)) = headings
+        .iter()
+        .find(|(_, _, title)| title.eq_ignore_ascii_case(heading.trim()))
+    else {
+        let titles: Vec<&str> = headings.iter().map(|(_, _, title)| *title).collect();
+        return Err(DomainError::ResourceNotFound(if titles.is_empty() {
+            format!("No section {:?}: the content has no headings", heading)
+        } else {
+            format!("No section {:?}; sections: {}", heading, titles.join(", "))
+        }));
+    };
+
+    let end = headings
+        .iter()
+        .find(|(index, other, _)| *index > start && *other <= level)
+        .map_or(lines.len(), |(index, _, _)| *index);
+    Ok(lines[start..end].join("\n").trim_end().to_string())
+}
+
+/// Strips heading markers, emphasis and code markers, and spells out links.
+fn to_text(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| {
+            let line = match HEADING.captures(line) {
+                Some(captures) => captures[2].to_string(),
+                None => line.to_string(),
+            };
+            let line = LINK.replace_all(&line, "$1 ($2)");
+            EMPHASIS.replace_all(&line, "").into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cuts `content` to about `max_tokens` tokens at a word boundary, noting
+/// the cut.
+fn truncate(content: &str, max_tokens: usize) -> String {
+    let max_chars = max_tokens.saturating_mul(CHARS_PER_TOKEN);
+    let Some((cut, _)) = content.char_indices().nth(max_chars) else {
+        return content.to_string();
+    };
+    let cut = content[..cut].rfind(char::is_whitespace).unwrap_or(cut);
+    format!(
+        "{}\n\n[Truncated to about {} tokens]",
+        content[..cut].trim_end(),
+        max_tokens
+    )
+}
+
+/// The part of `content` from `offset` characters in that fits in
+/// `max_chars`, and where the next part starts if there's more. Parts end
+/// after the last line break in their second half, or else the last space,
+/// and never inside a character or grapheme cluster, so accented letters,
+/// emoji sequences and scripts without spaces come through whole.
+pub fn page(content: &str, offset: usize, max_chars: usize) -> (&str, Option<usize>) {
+    let start = content
+        .char_indices()
+        .nth(offset)
+        .map_or(content.len(), |(index, _)| index);
+    let rest = &content[start..];
+
+    let mut chars = 0;
+    let mut end = 0;
+    let mut space = None;
+    for (index, grapheme) in rest.grapheme_indices(true) {
+        chars += grapheme.chars().count();
+        if chars > max_chars {
+            break;
+        }
+        end = index + grapheme.len();
+        if grapheme.chars().all(char::is_whitespace) {
+            space = Some(end);
+        }
+    }
+    if end == rest.len() {
+        return (rest, None);
+    }
+
+    let line_break = rest[..end]
+        .rfind('\n')
+        .map(|index| index + 1)
+        .filter(|&index| index > end / 2);
+    let cut = match (line_break, space) {
+        (Some(index), _) => index,
+        (None, Some(index)) => index,
+        // A single grapheme longer than a whole part.
+        (None, None) if end == 0 => rest.graphemes(true).next().map_or(0, str::len),
+        (None, None) => end,
+    };
+    (&rest[..cut], Some(offset + rest[..cut].chars().count()))
+}
@@ -0,0 +1,118 @@
+use chrono::Utc;
+use std::collections::BTreeMap;
+
+use super::ResourceService;
+use crate::domain::{DomainError, Resource, Snapshot};
+use crate::ports::{report_progress, ResourceRepository};
+
+/// The snapshot format this version writes, and the newest it reads.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Resources written to the cache at once when importing a snapshot.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Outcome of importing a snapshot.
+#[derive(Debug, Default)]
+pub struct SnapshotImport {
+    pub imported: usize,
+    /// Resources the cache already held as recent as the snapshot's.
+    pub kept: usize,
+    /// Providers whose sync time was taken from the snapshot, so their
+    /// resources are searched locally.
+    pub synced: Vec<String>,
+}
+
+impl ResourceService {
+    /// The cache's live resources that pass the hooks and `allows`, as
+    /// they come out of the hooks, with when their providers were last
+    /// synced.
+    pub async fn export_snapshot(
+        &self,
+        allows: impl Fn(&Resource) -> bool,
+    ) -> Result<Snapshot, DomainError> {
+        let repository = self.snapshot_repository()?;
+        let resources: Vec<Resource> = repository
+            .list_entries(None, false)
+            .await?
+            .into_iter()
+            .filter_map(|entry| self.apply_hooks_to(entry.resource).ok())
+            .filter(|resource| allows(resource))
+            .collect();
+
+        let mut syncs = BTreeMap::new();
+        for resource in &resources {
+            let provider = resource.source.provider_name().to_lowercase();
+            if syncs.contains_key(&provider) {
+                continue;
+            }
+            if let Some(synced_at) = repository.last_sync(&provider).await? {
+                syncs.insert(provider, synced_at);
+            }
+        }
+
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            exported_at: Utc::now(),
+            syncs,
+            resources,
+        })
+    }
+
+    /// Stores `snapshot`'s resources in the cache, keeping those it holds
+    /// as recent already, and takes its sync times where they're later
+    /// than the cache's own.
+    pub async fn import_snapshot(&self, snapshot: Snapshot) -> Result<SnapshotImport, DomainError> {
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(DomainError::InvalidQuery(format!(
+                "The snapshot has format {}, newer than the {} this version reads",
+                snapshot.version, SNAPSHOT_VERSION
+            )));
+        }
+        let repository = self.snapshot_repository()?;
+        let mut report = SnapshotImport::default();
+
+        let total = snapshot.resources.len();
+        for batch in snapshot.resources.chunks(IMPORT_BATCH_SIZE) {
+            let mut newer = Vec::with_capacity(batch.len());
+            for resource in batch {
+                match repository.find_entry(&resource.id).await? {
+                    Some(entry) if entry.resource.updated_at >= resource.updated_at => {
+                        report.kept += 1
+                    }
+                    _ => newer.push(resource.clone()),
+                }
+            }
+            repository.save_all(&newer).await?;
+            report.imported += newer.len();
+            report_progress(|| {
+                format!(
+                    "Imported {} of {} resources",
+                    report.imported + report.kept,
+                    total
+                )
+            });
+        }
+
+        for (provider, synced_at) in snapshot.syncs {
+            match repository.last_sync(&provider).await? {
+                Some(last) if last >= synced_at => {}
+                _ => {
+                    repository.record_sync(&provider, synced_at).await?;
+                    report.synced.push(provider);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn snapshot_repository(&self) -> Result<&dyn ResourceRepository, DomainError> {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.repository.as_ref())
+            .ok_or_else(|| {
+                DomainError::StorageError(
+                    "Snapshots are of the local cache, and the cache is disabled".to_string(),
+                )
+            })
+    }
+}
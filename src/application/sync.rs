@@ -0,0 +1,441 @@
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use super::ResourceService;
+use crate::domain::{
+    DomainError, Query, QueryResult, Resource, ResourceSource, SyncEstimate, SyncScope,
+};
+use crate::ports::{
+    in_current_scope, is_listing_filter, report_progress, ResourceProvider, ResourceRepository,
+    NON_NARROWING_FILTERS, UPDATED_AFTER_FILTER,
+};
+
+/// Resources written to the cache per transaction.
+const WRITE_BATCH_SIZE: usize = 50;
+
+/// Outcome of syncing one provider.
+#[derive(Debug)]
+pub struct SyncReport {
+    pub provider: String,
+    pub fetched: usize,
+    /// Resources found archived upstream or missing from the listing.
+    pub tombstoned: usize,
+    /// Listed resources whose content couldn't be fetched.
+    pub skipped: usize,
+    /// Set when the provider couldn't be synced; nothing was tombstoned.
+    pub error: Option<String>,
+}
+
+impl SyncReport {
+    fn new(provider: String) -> Self {
+        Self {
+            provider,
+            fetched: 0,
+            tombstoned: 0,
+            skipped: 0,
+            error: None,
+        }
+    }
+}
+
+/// Concurrency of one provider's sync pipeline.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncLimits {
+    /// Tasks fetching resource content in parallel.
+    pub workers: usize,
+    /// Resources buffered between stages. Listing pauses when the content
+    /// workers fall this far behind, which bounds memory use.
+    pub queue_size: usize,
+}
+
+impl Default for SyncLimits {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            queue_size: 100,
+        }
+    }
+}
+
+/// What a sync of a query covers.
+struct SyncPlan {
+    /// The query providers are listed with.
+    query: Query,
+    /// Each provider synced, with the scopes it mirrors.
+    providers: Vec<(Arc<dyn ResourceProvider>, Vec<SyncScope>)>,
+    /// Reports for the providers that aren't available.
+    unavailable: Vec<SyncReport>,
+}
+
+impl ResourceService {
+    /// Replaces the scopes `sync` mirrors of each provider, by name.
+    pub fn set_sync_scopes(&self, scopes: impl IntoIterator<Item = (String, Vec<SyncScope>)>) {
+        *self.sync_scopes.write().unwrap() = scopes
+            .into_iter()
+            .map(|(name, scopes)| (name.to_lowercase(), scopes))
+            .collect();
+    }
+
+    /// Fetches everything matching `query` into the cache. Cached resources
+    /// that come back archived, or that a complete listing no longer
+    /// contains, are tombstoned. Listings capped by `query.limit` are not
+    /// complete, so they only tombstone archived resources.
+    ///
+    /// A query without narrowing filters mirrors each provider's sync
+    /// scopes, if it has any, one after another, rather than everything.
+    ///
+    /// Providers sync concurrently, each through a pipeline of a listing
+    /// task, `limits(provider).workers` content fetchers and a cache writer.
+    pub async fn sync(
+        &self,
+        query: &Query,
+        limits: impl Fn(&str) -> SyncLimits,
+    ) -> Result<Vec<SyncReport>, DomainError> {
+        let cache = self.cache.as_ref().ok_or_else(|| {
+            DomainError::StorageError("sync needs the local cache, which is disabled".to_string())
+        })?;
+
+        let SyncPlan {
+            query,
+            providers,
+            unavailable: mut reports,
+        } = self.sync_plan(query);
+        let pipelines: Vec<_> = providers
+            .into_iter()
+            .map(|(provider, scopes)| {
+                let limits = limits(&provider.provider_name().to_lowercase());
+                tokio::spawn(in_current_scope(sync_provider_scopes(
+                    provider,
+                    cache.repository.clone(),
+                    query.clone(),
+                    scopes,
+                    limits,
+                )))
+            })
+            .collect();
+
+        for pipeline in pipelines {
+            let report = pipeline
+                .await
+                .map_err(|e| DomainError::ProviderError(format!("Sync task failed: {}", e)))?;
+            reports.push(report?);
+        }
+
+        Ok(reports)
+    }
+
+    /// Works out what syncing `query` would cost each provider that can
+    /// tell, adding up its scopes, by provider name. A provider that fails
+    /// to estimate is left out, as the sync will report the failure.
+    pub async fn estimate_sync(&self, query: &Query) -> Vec<(String, SyncEstimate)> {
+        if self.cache.is_none() {
+            return Vec::new();
+        }
+        let SyncPlan {
+            query, providers, ..
+        } = self.sync_plan(query);
+        let estimates = providers.iter().map(|(provider, scopes)| {
+            let query = &query;
+            async move {
+                let mut total: Option<SyncEstimate> = None;
+                for scope in scopes {
+                    let (query, _) = scope_query(query, scope)?;
+                    if let Some(estimate) = provider.estimate_sync(&query).await? {
+                        total
+                            .get_or_insert_with(SyncEstimate::default)
+                            .add(&estimate);
+                    }
+                }
+                Ok::<_, DomainError>(total)
+            }
+        });
+
+        let mut result = Vec::new();
+        for ((provider, _), estimate) in providers.iter().zip(join_all(estimates).await) {
+            let name = provider.provider_name().to_lowercase();
+            match estimate {
+                Ok(Some(estimate)) => result.push((name, estimate)),
+                Ok(None) => {}
+                Err(e) => tracing::debug!("Couldn't estimate the sync of {}: {}", name, e),
+            }
+        }
+        result
+    }
+
+    /// The providers `query` syncs and how.
+    fn sync_plan(&self, query: &Query) -> SyncPlan {
+        let mut unavailable = QueryResult::default();
+        let providers =
+            self.select_providers(std::slice::from_ref(&query.source), &mut unavailable);
+
+        let unavailable = unavailable
+            .errors
+            .into_iter()
+            .map(|failure| SyncReport {
+                error: Some(failure.error),
+                ..SyncReport::new(failure.provider)
+            })
+            .collect();
+
+        let narrowed = query
+            .filters
+            .keys()
+            .any(|key| !NON_NARROWING_FILTERS.contains(&key.as_str()));
+        let mut query = query.clone();
+        query
+            .filters
+            .insert("include_archived".to_string(), "true".to_string());
+
+        let providers = providers
+            .into_iter()
+            .map(|provider| {
+                let name = provider.provider_name().to_lowercase();
+                let scopes = match self.sync_scopes.read().unwrap().get(&name) {
+                    Some(scopes) if !narrowed => scopes.clone(),
+                    _ => vec![SyncScope::default()],
+                };
+                (provider, scopes)
+            })
+            .collect();
+        SyncPlan {
+            query,
+            providers,
+            unavailable,
+        }
+    }
+}
+
+/// `query` narrowed to `scope`, with the oldest update the scope covers.
+fn scope_query(
+    query: &Query,
+    scope: &SyncScope,
+) -> Result<(Query, Option<DateTime<Utc>>), DomainError> {
+    let start = scope.start()?;
+    let mut query = query.clone();
+    query.filters.extend(scope.filters.clone());
+    if let Some(start) = start {
+        query
+            .filters
+            .insert(UPDATED_AFTER_FILTER.to_string(), start.to_rfc3339());
+    }
+    Ok((query, start))
+}
+
+/// Syncs each of `scopes` of one provider in turn, narrowing `query` to it,
+/// and adds up their reports. A scope that fails doesn't stop the rest.
+async fn sync_provider_scopes(
+    provider: Arc<dyn ResourceProvider>,
+    repository: Arc<dyn ResourceRepository>,
+    query: Query,
+    scopes: Vec<SyncScope>,
+    limits: SyncLimits,
+) -> Result<SyncReport, DomainError> {
+    let mut total = SyncReport::new(provider.provider_name().to_lowercase());
+    for scope in &scopes {
+        let report = match scope_query(&query, scope) {
+            Ok((query, start)) => {
+                sync_provider(provider.clone(), repository.clone(), query, start, limits).await?
+            }
+            Err(e) => SyncReport {
+                error: Some(e.to_string()),
+                ..SyncReport::new(total.provider.clone())
+            },
+        };
+
+        total.fetched += report.fetched;
+        total.tombstoned += report.tombstoned;
+        total.skipped += report.skipped;
+        if let Some(error) = report.error {
+            let error = match scope.describe() {
+                scope if scope.is_empty() => error,
+                scope => format!("{}: {}", scope, error),
+            };
+            total.error = Some(match total.error.take() {
+                Some(errors) => format!("{}; {}", errors, error),
+                None => error,
+            });
+        }
+    }
+    Ok(total)
+}
+
+/// Runs one provider's pipeline: a listing task pages through the provider,
+/// content workers hydrate what it lists, and the caller's task writes the
+/// results to the cache in batches. Stages are joined by bounded channels,
+/// so a slow stage holds back the ones before it. Resources last updated
+/// before `start`, if given, are left out.
+async fn sync_provider(
+    provider: Arc<dyn ResourceProvider>,
+    repository: Arc<dyn ResourceRepository>,
+    query: Query,
+    start: Option<DateTime<Utc>>,
+    limits: SyncLimits,
+) -> Result<SyncReport, DomainError> {
+    let name = provider.provider_name().to_lowercase();
+    let mut report = SyncReport::new(name.clone());
+    let in_window =
+        move |resource: &Resource| start.is_none_or(|start| resource.updated_at >= start);
+
+    let queue_size = limits.queue_size.max(1);
+    let (listed_tx, listed_rx) = mpsc::channel::<Resource>(queue_size);
+    let (hydrated_tx, mut hydrated_rx) = mpsc::channel::<Resource>(queue_size);
+
+    let complete = query.limit.is_none();
+    let started_at = Utc::now();
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let lister = tokio::spawn(in_current_scope({
+        let provider = provider.clone();
+        let query = query.clone();
+        async move {
+            let mut listed = HashSet::new();
+            let mut cursor = None;
+            loop {
+                let page = provider.list_page(&query, cursor.take()).await?;
+                let resources = page.resources.into_iter().filter(in_window);
+                for resource in resources.take(limit - listed.len()) {
+                    listed.insert(resource.id.clone());
+                    // The writer has stopped; nothing more will be saved.
+                    if listed_tx.send(resource).await.is_err() {
+                        return Ok(listed);
+                    }
+                }
+
+                cursor = page.next_cursor;
+                if cursor.is_none() || listed.len() >= limit {
+                    return Ok::<_, DomainError>(listed);
+                }
+            }
+        }
+    }));
+
+    let listed_rx = Arc::new(Mutex::new(listed_rx));
+    let workers: Vec<_> = (0..limits.workers.max(1))
+        .map(|_| {
+            let provider = provider.clone();
+            let listed_rx = listed_rx.clone();
+            let hydrated_tx = hydrated_tx.clone();
+            tokio::spawn(in_current_scope(async move {
+                let mut skipped = 0;
+                loop {
+                    let Some(resource) = listed_rx.lock().await.recv().await else {
+                        break;
+                    };
+                    let id = resource.id.clone();
+                    match provider.hydrate(resource).await {
+                        Ok(resource) => {
+                            if hydrated_tx.send(resource).await.is_err() {
+                                break;
+                            }
+                        }
+                        // Every fetch after this one would fail the same way.
+                        Err(e @ DomainError::LimitExceeded(_)) => return (skipped, Some(e)),
+                        Err(e) => {
+                            tracing::warn!("Failed to fetch content of {}: {}", id, e);
+                            skipped += 1;
+                        }
+                    }
+                }
+                (skipped, None)
+            }))
+        })
+        .collect();
+    // Once the workers are gone, whether done or stopped by a spent
+    // budget, the listing has nowhere to send to and stops too.
+    drop(listed_rx);
+    drop(hydrated_tx);
+
+    // Saving an archived resource tombstones it.
+    let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+    loop {
+        let next = hydrated_rx.recv().await;
+        let done = next.is_none();
+        batch.extend(next);
+        if batch.len() < WRITE_BATCH_SIZE && !done {
+            continue;
+        }
+
+        if batch.is_empty() {
+            break;
+        }
+        if let Err(e) = repository.save_all(&batch).await {
+            report.error = Some(e.to_string());
+            break;
+        }
+        report.fetched += batch.len();
+        report.tombstoned += batch.iter().filter(|r| r.is_archived()).count();
+        batch.clear();
+        report_progress(|| format!("Synced {} {} resources", report.fetched, name));
+
+        if done {
+            break;
+        }
+    }
+    // Stops the workers and the listing early if a write failed.
+    drop(hydrated_rx);
+
+    for worker in workers {
+        let (skipped, error) = worker.await.unwrap_or((0, None));
+        report.skipped += skipped;
+        if let Some(e) = error {
+            report.error.get_or_insert(e.to_string());
+        }
+    }
+    let listing = lister
+        .await
+        .unwrap_or_else(|e| Err(DomainError::ProviderError(e.to_string())));
+    let listed = match listing {
+        Ok(listed) => listed,
+        Err(e) => {
+            report.error.get_or_insert(e.to_string());
+            return Ok(report);
+        }
+    };
+
+    if complete && report.error.is_none() {
+        let missing: Vec<String> = repository
+            .list_entries(Some(&name), false)
+            .await?
+            .into_iter()
+            .filter(|entry| {
+                in_scope(&entry.resource, &query)
+                    && in_window(&entry.resource)
+                    && !listed.contains(&entry.resource.id)
+            })
+            .map(|entry| entry.resource.id)
+            .collect();
+
+        report.tombstoned += repository.tombstone(&missing).await?;
+
+        // What the listing covered can now be searched locally.
+        if query
+            .filters
+            .keys()
+            .all(|key| key == "database_id" || NON_NARROWING_FILTERS.contains(&key.as_str()))
+        {
+            repository.record_sync(&name, started_at).await?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Whether a complete listing for `query` would contain `resource`, so its
+/// absence means it was deleted upstream.
+fn in_scope(resource: &Resource, query: &Query) -> bool {
+    match &resource.source {
+        // Notion listings are per database. IDs may be given with or without
+        // dashes.
+        ResourceSource::Notion { database_id, .. } => {
+            let normalize = |id: &str| id.replace('-', "");
+            match (database_id, query.filters.get("database_id")) {
+                (Some(ours), Some(theirs)) => normalize(ours) == normalize(theirs),
+                _ => false,
+            }
+        }
+        // The window is checked separately.
+        _ => query.filters.keys().all(|key| is_listing_filter(key)),
+    }
+}
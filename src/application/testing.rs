@@ -0,0 +1,391 @@
+use std::collections::HashSet;
+
+use crate::{
+    domain::{DomainError, Query, Resource},
+    ports::ResourceProvider,
+};
+
+#[derive(Debug, Clone)]
+pub struct ConformanceOptions {
+    /// Query listed and fetched; providers that need filters to list
+    /// anything get them here.
+    pub query: Query,
+    pub search_term: String,
+    /// `page_size` listed with, unless the query sets one.
+    pub page_size: usize,
+    /// Pages walked before pagination is taken to work.
+    pub max_pages: usize,
+    /// Resources fetched by ID and hydrated.
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The contract couldn't be checked, e.g. for lack of resources.
+    Skip,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "FAIL",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub contract: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(contract: &'static str, outcome: Outcome, detail: impl Into<String>) -> Self {
+        Self {
+            contract,
+            outcome,
+            detail: detail.into(),
+        }
+    }
+
+    /// A pass, or a failure listing `problems`.
+    fn from_problems(contract: &'static str, problems: Vec<String>, passed: String) -> Self {
+        if problems.is_empty() {
+            Self::new(contract, Outcome::Pass, passed)
+        } else {
+            Self::new(contract, Outcome::Fail, problems.join("; "))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConformanceReport {
+    pub provider: &'static str,
+    pub checks: Vec<Check>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome != Outcome::Fail)
+    }
+}
+
+/// Checks `provider` against the contracts the rest of mcp-rs relies on:
+/// IDs and sources named after the provider, limits, pagination that ends
+/// without repeating itself, IDs and URIs that round-trip, missing
+/// resources reported as not found and bad input as an invalid query.
+/// Only reads; `apply` is never called.
+pub async fn run(
+    provider: &dyn ResourceProvider,
+    options: &ConformanceOptions,
+) -> ConformanceReport {
+    let mut checks = Vec::new();
+
+    let limit = options.query.limit.unwrap_or(options.samples).max(1);
+    let query = Query {
+        limit: Some(limit),
+        ..options.query.clone()
+    };
+    let fetched = match provider.fetch_resources(&query).await {
+        Ok(resources) => {
+            checks.push(check_fetch(&resources, limit));
+            resources
+        }
+        Err(e) => {
+            checks.push(error_check("fetch", e));
+            Vec::new()
+        }
+    };
+    let sample: Vec<&Resource> = fetched.iter().take(options.samples.max(1)).collect();
+
+    checks.push(check_naming(provider.provider_name(), &sample));
+    let listed = check_pagination(provider, options, &mut checks).await;
+    checks.push(check_hydrate(provider, &listed, options.samples.max(1)).await);
+    checks.push(check_id_round_trip(provider, &sample).await);
+    checks.push(check_uri_round_trip(&sample));
+    let seen: Vec<&Resource> = fetched.iter().chain(&listed).collect();
+    checks.push(check_not_found(provider, &seen).await);
+    checks.push(check_invalid_query(provider, &options.query).await);
+    checks.push(check_search(provider, &options.search_term).await);
+
+    ConformanceReport {
+        provider: provider.provider_name(),
+        checks,
+    }
+}
+
+/// A check that couldn't get going because of `error`. Providers that need
+/// filters to list anything say so with an invalid query, which is no
+/// fault of theirs.
+fn error_check(contract: &'static str, error: DomainError) -> Check {
+    match error {
+        DomainError::InvalidQuery(_) => Check::new(
+            contract,
+            Outcome::Skip,
+            format!("{} (does the provider need --filter?)", error),
+        ),
+        e => Check::new(contract, Outcome::Fail, e.to_string()),
+    }
+}
+
+fn duplicates<'a>(resources: impl IntoIterator<Item = &'a Resource>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    resources
+        .into_iter()
+        .filter(|resource| !seen.insert(resource.id.as_str()))
+        .map(|resource| format!("duplicate ID {}", resource.id))
+        .collect()
+}
+
+fn check_fetch(resources: &[Resource], limit: usize) -> Check {
+    let mut problems = duplicates(resources);
+    if resources.len() > limit {
+        problems.push(format!(
+            "returned {} resources for a limit of {}",
+            resources.len(),
+            limit
+        ));
+    }
+    Check::from_problems(
+        "fetch",
+        problems,
+        format!("{} resources within a limit of {}", resources.len(), limit),
+    )
+}
+
+/// Resources come from the provider they say they do, under IDs prefixed
+/// with its name, which is how IDs are routed back to it.
+fn check_naming(provider: &str, sample: &[&Resource]) -> Check {
+    if provider.is_empty() {
+        return Check::new("naming", Outcome::Fail, "provider name is empty");
+    }
+    if sample.is_empty() {
+        return Check::new("naming", Outcome::Skip, "no resources fetched");
+    }
+    let prefix = format!("{}_", provider.to_lowercase());
+    let mut problems = Vec::new();
+    for resource in sample {
+        let source = resource.source.provider_name();
+        if !source.eq_ignore_ascii_case(provider) {
+            problems.push(format!("{} has source {}", resource.id, source));
+        }
+        if !resource.id.to_lowercase().starts_with(&prefix) {
+            problems.push(format!("{} doesn't start with {}", resource.id, prefix));
+        }
+    }
+    Check::from_problems(
+        "naming",
+        problems,
+        format!("{} resources named after {}", sample.len(), provider),
+    )
+}
+
+/// Walks `list_page` until it runs out of cursors or `max_pages`, and
+/// returns what the pages listed.
+async fn check_pagination(
+    provider: &dyn ResourceProvider,
+    options: &ConformanceOptions,
+    checks: &mut Vec<Check>,
+) -> Vec<Resource> {
+    let mut query = options.query.clone();
+    query
+        .filters
+        .entry("page_size".to_string())
+        .or_insert_with(|| options.page_size.max(1).to_string());
+
+    let mut listed = Vec::new();
+    let mut cursors = HashSet::new();
+    let mut problems = Vec::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    while pages < options.max_pages.max(1) {
+        let page = match provider.list_page(&query, cursor.clone()).await {
+            Ok(page) => page,
+            Err(e) if pages == 0 => {
+                checks.push(error_check("pagination", e));
+                return listed;
+            }
+            Err(e) => {
+                problems.push(format!("page {} failed: {}", pages + 1, e));
+                break;
+            }
+        };
+        pages += 1;
+        listed.extend(page.resources);
+        match page.next_cursor {
+            Some(next) if !cursors.insert(next.clone()) => {
+                problems.push(format!("cursor {} came back twice", next));
+                break;
+            }
+            Some(next) => cursor = Some(next),
+            None => {
+                cursor = None;
+                break;
+            }
+        }
+    }
+    problems.extend(duplicates(&listed));
+
+    let more = if cursor.is_some() {
+        ", more left unlisted"
+    } else {
+        ""
+    };
+    checks.push(Check::from_problems(
+        "pagination",
+        problems,
+        format!("{} resources over {} pages{}", listed.len(), pages, more),
+    ));
+    listed
+}
+
+async fn check_hydrate(
+    provider: &dyn ResourceProvider,
+    listed: &[Resource],
+    samples: usize,
+) -> Check {
+    if listed.is_empty() {
+        return Check::new("hydrate", Outcome::Skip, "no resources listed");
+    }
+    let mut problems = Vec::new();
+    let mut hydrated = 0;
+    for resource in listed.iter().take(samples) {
+        match provider.hydrate(resource.clone()).await {
+            Ok(full) if full.id != resource.id => {
+                problems.push(format!("{} hydrated as {}", resource.id, full.id));
+            }
+            Ok(_) => hydrated += 1,
+            Err(e) => problems.push(format!("{}: {}", resource.id, e)),
+        }
+    }
+    Check::from_problems(
+        "hydrate",
+        problems,
+        format!("{} resources hydrated", hydrated),
+    )
+}
+
+async fn check_id_round_trip(provider: &dyn ResourceProvider, sample: &[&Resource]) -> Check {
+    if sample.is_empty() {
+        return Check::new("id-round-trip", Outcome::Skip, "no resources fetched");
+    }
+    let mut problems = Vec::new();
+    for resource in sample {
+        match provider.fetch_resource_by_id(&resource.id).await {
+            Ok(fetched) if fetched.id != resource.id => {
+                problems.push(format!("{} came back as {}", resource.id, fetched.id));
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(format!("{}: {}", resource.id, e)),
+        }
+    }
+    Check::from_problems(
+        "id-round-trip",
+        problems,
+        format!("{} resources fetched by ID", sample.len()),
+    )
+}
+
+fn check_uri_round_trip(sample: &[&Resource]) -> Check {
+    if sample.is_empty() {
+        return Check::new("uri-round-trip", Outcome::Skip, "no resources fetched");
+    }
+    let problems = sample
+        .iter()
+        .filter_map(|resource| {
+            let uri = resource.uri();
+            match Resource::id_from_uri(&uri) {
+                Some(id) if id == resource.id => None,
+                Some(id) => Some(format!("{} maps back to {}", uri, id)),
+                None => Some(format!("{} maps back to no ID", uri)),
+            }
+        })
+        .collect();
+    Check::from_problems(
+        "uri-round-trip",
+        problems,
+        format!("{} URIs map back to their IDs", sample.len()),
+    )
+}
+
+/// An ID shaped like `sample`'s but for nothing seen so far: its last hex
+/// digit is changed, so providers that check ID formats still look it up.
+fn missing_id(provider: &str, sample: Option<&Resource>, seen: &HashSet<&str>) -> String {
+    let fallback = || format!("{}_conformance-missing", provider.to_lowercase());
+    let Some(sample) = sample else {
+        return fallback();
+    };
+    let mut id = sample.id.clone();
+    if !id.pop().is_some_and(|last| last.is_ascii_hexdigit()) {
+        return format!("{}-missing", sample.id);
+    }
+    "0123456789abcdef"
+        .chars()
+        .map(|digit| format!("{}{}", id, digit))
+        .find(|candidate| !seen.contains(candidate.as_str()))
+        .unwrap_or_else(fallback)
+}
+
+async fn check_not_found(provider: &dyn ResourceProvider, seen: &[&Resource]) -> Check {
+    let ids: HashSet<&str> = seen.iter().map(|resource| resource.id.as_str()).collect();
+    let id = missing_id(provider.provider_name(), seen.first().copied(), &ids);
+    match provider.fetch_resource_by_id(&id).await {
+        Err(DomainError::ResourceNotFound(_)) => {
+            Check::new("not-found", Outcome::Pass, format!("{} not found", id))
+        }
+        Err(e) => Check::new(
+            "not-found",
+            Outcome::Fail,
+            format!("{} failed with {:?} rather than not found", id, e),
+        ),
+        Ok(_) => Check::new("not-found", Outcome::Fail, format!("{} was found", id)),
+    }
+}
+
+/// A page size of 0 is an invalid query to providers that page at all.
+async fn check_invalid_query(provider: &dyn ResourceProvider, query: &Query) -> Check {
+    let mut query = query.clone();
+    query
+        .filters
+        .insert("page_size".to_string(), "0".to_string());
+    match provider.fetch_resources(&query).await {
+        Err(DomainError::InvalidQuery(e)) => Check::new("invalid-query", Outcome::Pass, e),
+        Err(e) => Check::new(
+            "invalid-query",
+            Outcome::Fail,
+            format!(
+                "page_size=0 failed with {:?} rather than an invalid query",
+                e
+            ),
+        ),
+        Ok(_) => Check::new("invalid-query", Outcome::Skip, "page_size isn't used"),
+    }
+}
+
+async fn check_search(provider: &dyn ResourceProvider, term: &str) -> Check {
+    match provider.search(term).await {
+        Ok(resources) => {
+            let name = provider.provider_name();
+            let mut problems = duplicates(&resources);
+            problems.extend(
+                resources
+                    .iter()
+                    .filter(|resource| !resource.source.provider_name().eq_ignore_ascii_case(name))
+                    .map(|resource| format!("{} isn't from {}", resource.id, name)),
+            );
+            Check::from_problems(
+                "search",
+                problems,
+                format!("{} results for {:?}", resources.len(), term),
+            )
+        }
+        Err(e) => error_check("search", e),
+    }
+}
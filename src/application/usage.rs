@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use std::sync::{Arc, RwLock};
+
+use super::guardrails::CallLimiter;
+use crate::domain::{
+    Attachment, Blob, Change, DiscoveredItem, DomainError, ListingPage, Query, Resource,
+    SyncEstimate, UsageEvent,
+};
+use crate::ports::{ResourceProvider, ResourceRepository};
+
+/// Counts usage into the repository's local statistics, once the service
+/// has a repository. Counting never fails what's being counted.
+#[derive(Default)]
+pub struct UsageRecorder {
+    repository: RwLock<Option<Arc<dyn ResourceRepository>>>,
+}
+
+impl UsageRecorder {
+    pub fn set_repository(&self, repository: Arc<dyn ResourceRepository>) {
+        *self.repository.write().unwrap() = Some(repository);
+    }
+
+    pub async fn record(&self, event: UsageEvent, name: &str) {
+        let repository = self.repository.read().unwrap().clone();
+        if let Some(repository) = repository {
+            if let Err(e) = repository.record_usage(event, name).await {
+                tracing::debug!("Failed to record usage: {}", e);
+            }
+        }
+    }
+}
+
+/// Counts each call made to `inner` as a provider call, charging it to the
+/// current call budget and refusing it once that is spent. Hydrating isn't
+/// counted, since most providers list resources complete and hydrate
+/// without a request.
+pub struct CountingProvider {
+    inner: Arc<dyn ResourceProvider>,
+    usage: Arc<UsageRecorder>,
+    calls: Arc<CallLimiter>,
+}
+
+impl CountingProvider {
+    pub fn new(
+        inner: Arc<dyn ResourceProvider>,
+        usage: Arc<UsageRecorder>,
+        calls: Arc<CallLimiter>,
+    ) -> Self {
+        Self {
+            inner,
+            usage,
+            calls,
+        }
+    }
+
+    async fn count(&self) -> Result<(), DomainError> {
+        let provider = self.inner.provider_name();
+        self.calls.charge(provider)?;
+        self.usage.record(UsageEvent::ProviderCall, provider).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for CountingProvider {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        self.count().await?;
+        self.inner.fetch_resources(query).await
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        self.count().await?;
+        self.inner.fetch_resource_by_id(id).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        self.count().await?;
+        self.inner.search(query).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        self.count().await?;
+        self.inner.list_page(query, cursor).await
+    }
+
+    async fn hydrate(&self, resource: Resource) -> Result<Resource, DomainError> {
+        self.inner.hydrate(resource).await
+    }
+
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        self.count().await?;
+        self.inner.discover().await
+    }
+
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        self.count().await?;
+        self.inner.download(attachment).await
+    }
+
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        self.count().await?;
+        self.inner.apply(change).await
+    }
+
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        self.inner.warm_up().await
+    }
+
+    async fn estimate_sync(&self, query: &Query) -> Result<Option<SyncEstimate>, DomainError> {
+        self.count().await?;
+        self.inner.estimate_sync(query).await
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use super::{
+    diff::{self, ChangeSummary},
+    ResourceService,
+};
+use crate::domain::{DomainError, Resource};
+
+/// Updates buffered for each receiver; receivers that fall further behind
+/// miss the oldest.
+const UPDATE_CAPACITY: usize = 64;
+
+/// Polls the resources clients have subscribed to, and announces those
+/// whose `updated_at` changed since the last poll.
+pub struct ResourceWatcher {
+    service: Arc<ResourceService>,
+    watched: Mutex<HashMap<String, Watched>>,
+    updates: broadcast::Sender<ResourceUpdate>,
+}
+
+struct Watched {
+    /// The version last seen, which the next is compared with.
+    resource: Resource,
+    subscribers: usize,
+}
+
+/// A watched resource found changed, with what changed since the version
+/// seen before.
+#[derive(Debug, Clone)]
+pub struct ResourceUpdate {
+    pub resource: Resource,
+    pub changes: ChangeSummary,
+}
+
+impl ResourceWatcher {
+    pub fn new(service: Arc<ResourceService>) -> Self {
+        let (updates, _) = broadcast::channel(UPDATE_CAPACITY);
+        Self {
+            service,
+            watched: Mutex::new(HashMap::new()),
+            updates,
+        }
+    }
+
+    /// Starts watching a resource, fetching it to learn its current
+    /// version, or counts another subscriber if it is already watched.
+    pub async fn watch(&self, id: &str) -> Result<(), DomainError> {
+        if let Some(watched) = self.watched.lock().unwrap().get_mut(id) {
+            watched.subscribers += 1;
+            return Ok(());
+        }
+
+        let resource = self.service.refresh_resource(id).await?;
+        self.watched
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_insert(Watched {
+                resource,
+                subscribers: 0,
+            })
+            .subscribers += 1;
+        Ok(())
+    }
+
+    /// Drops one subscriber of a resource, and stops watching it after the
+    /// last.
+    pub fn unwatch(&self, id: &str) {
+        let mut watched = self.watched.lock().unwrap();
+        if let Some(entry) = watched.get_mut(id) {
+            entry.subscribers -= 1;
+            if entry.subscribers == 0 {
+                watched.remove(id);
+            }
+        }
+    }
+
+    /// Resources found changed by later polls.
+    pub fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Refetches every watched resource once, announcing the changed ones.
+    pub async fn poll(&self) {
+        let ids: Vec<String> = self.watched.lock().unwrap().keys().cloned().collect();
+
+        for id in ids {
+            let resource = match self.service.refresh_resource(&id).await {
+                Ok(resource) => resource,
+                Err(e) => {
+                    tracing::warn!("Failed to poll {}: {}", id, e);
+                    continue;
+                }
+            };
+
+            let changes = match self.watched.lock().unwrap().get_mut(&id) {
+                Some(watched) if watched.resource.updated_at != resource.updated_at => {
+                    let changes = diff::summarize(&watched.resource, &resource);
+                    watched.resource = resource.clone();
+                    Some(changes)
+                }
+                _ => None,
+            };
+            if let Some(changes) = changes {
+                tracing::debug!("{} changed upstream: {}", id, changes.text);
+                // Nobody listening just means nobody to tell.
+                let _ = self.updates.send(ResourceUpdate { resource, changes });
+            }
+        }
+    }
+
+    /// Polls every `interval` until the task is dropped.
+    pub async fn run(&self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick completes immediately, and everything was just
+        // fetched by `watch`.
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            self.poll().await;
+        }
+    }
+}
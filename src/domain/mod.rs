@@ -1,6 +1,9 @@
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Resource {
@@ -11,6 +14,187 @@ pub struct Resource {
     pub metadata: HashMap<String, serde_json::Value>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Where and when the resource was fetched. Absent for resources cached
+    /// or recorded before provenance was tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// Origin of a resource, for citing it: which API returned it, when, and the
+/// provider's ID for the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub provider: String,
+    pub fetched_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Link to the resource in the provider's web UI.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// A file attached to a resource, such as a Notion file block or a file
+/// uploaded to a Linear issue. Adapters list them under the `attachments`
+/// metadata key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    /// Where to download it. Links to some providers' files expire.
+    pub url: String,
+    /// As far as the provider says.
+    #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A passage of a resource's content: a section, or part of a long one.
+#[derive(Debug, Clone, Serialize)]
+pub struct Chunk {
+    /// ID of the resource it is from.
+    pub id: String,
+    pub uri: String,
+    pub title: String,
+    /// Headings it is under, outermost first.
+    pub section: Vec<String>,
+    /// Where it starts and ends in the content, in characters.
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Downloaded contents of an attachment.
+#[derive(Debug, Clone)]
+pub struct Blob {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Resource {
+    /// URI identifying the resource to MCP clients, e.g.
+    /// `notion://page/<page_id>` or `linear://issue/<issue_id>`.
+    pub fn uri(&self) -> String {
+        match &self.source {
+            ResourceSource::Notion { page_id, .. } => format!("notion://page/{}", page_id),
+            ResourceSource::Linear { issue_id, .. } => format!("linear://issue/{}", issue_id),
+            ResourceSource::Custom(_) => format!("mcp-rs://resource/{}", self.id),
+        }
+    }
+
+    /// The first `max_chars` characters of the content, with `...` appended
+    /// if anything was cut.
+    pub fn excerpt(&self, max_chars: usize) -> String {
+        match self.content.char_indices().nth(max_chars) {
+            Some((i, _)) => format!("{}...", &self.content[..i]),
+            None => self.content.clone(),
+        }
+    }
+
+    /// Short, human-friendly ID the provider assigns, e.g. Linear's
+    /// `ENG-123`.
+    pub fn short_id(&self) -> Option<&str> {
+        self.metadata.get("identifier").and_then(|i| i.as_str())
+    }
+
+    /// Files attached to the resource, in the order the provider lists them.
+    pub fn attachments(&self) -> Vec<Attachment> {
+        self.metadata
+            .get("attachments")
+            .and_then(|attachments| serde_json::from_value(attachments.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// URI of the `index`th attachment, e.g.
+    /// `notion://page/<page_id>/attachments/0`.
+    pub fn attachment_uri(&self, index: usize) -> String {
+        format!("{}/attachments/{}", self.uri(), index)
+    }
+
+    /// Maps a URI produced by `attachment_uri` back to the resource ID and
+    /// the attachment's index.
+    pub fn id_from_attachment_uri(uri: &str) -> Option<(String, usize)> {
+        let (resource_uri, index) = uri.rsplit_once("/attachments/")?;
+        Some((Self::id_from_uri(resource_uri)?, index.parse().ok()?))
+    }
+
+    /// Whether the provider reported the resource as archived or trashed.
+    pub fn is_archived(&self) -> bool {
+        self.metadata
+            .get("archived")
+            .and_then(|a| a.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Token identifying this version of the resource: a hash of when it
+    /// was last updated, its title and its content. A writer passes back the
+    /// version it read so changes made since can be detected rather than
+    /// overwritten.
+    pub fn version(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.updated_at.to_rfc3339().as_bytes());
+        hasher.update([0]);
+        hasher.update(self.title.as_bytes());
+        hasher.update([0]);
+        hasher.update(self.content.as_bytes());
+        hasher.finalize()[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Maps a URI produced by `uri` back to a resource ID.
+    pub fn id_from_uri(uri: &str) -> Option<String> {
+        if let Some(page_id) = uri.strip_prefix("notion://page/") {
+            Some(format!("notion_{}", page_id))
+        } else if let Some(issue_id) = uri.strip_prefix("linear://issue/") {
+            Some(format!("linear_{}", issue_id))
+        } else {
+            uri.strip_prefix("mcp-rs://resource/")
+                .map(|id| id.to_string())
+        }
+    }
+
+    /// Maps a link to a Notion page (`https://www.notion.so/Title-<id>`) or
+    /// Linear issue (`https://linear.app/<team>/issue/ENG-123/<slug>`), as
+    /// copied from a browser, to a resource ID.
+    pub fn id_from_web_url(url: &str) -> Option<String> {
+        let rest = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?;
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let path = path.split('#').next().unwrap_or_default();
+
+        if host == "notion.so" || host.ends_with(".notion.site") {
+            // A page open in peek view names it with `p=`; otherwise the ID
+            // ends the last path segment, after the title.
+            let peeked = query.split('&').find_map(|param| param.strip_prefix("p="));
+            let segment = peeked.or_else(|| path.rsplit('/').find(|s| !s.is_empty()))?;
+            let hex: String = segment.chars().filter(|c| *c != '-').collect();
+            let hex = hex.get(hex.len().checked_sub(32)?..)?;
+            if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            Some(format!(
+                "notion_{}-{}-{}-{}-{}",
+                &hex[..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..]
+            ))
+        } else if host == "linear.app" {
+            let mut segments = path.split('/');
+            segments.find(|segment| *segment == "issue")?;
+            let identifier = segments.next().filter(|s| !s.is_empty())?;
+            Some(format!("linear_{}", identifier.to_uppercase()))
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +210,589 @@ pub enum ResourceSource {
     Custom(String),
 }
 
+impl ResourceSource {
+    /// Lowercase name of the provider the resource came from.
+    pub fn provider_name(&self) -> &str {
+        match self {
+            ResourceSource::Notion { .. } => "notion",
+            ResourceSource::Linear { .. } => "linear",
+            ResourceSource::Custom(name) => name,
+        }
+    }
+}
+
+/// A name accepted in place of a resource ID.
+#[derive(Debug, Clone, Serialize)]
+pub struct Alias {
+    pub name: String,
+    pub id: String,
+    /// Whether it is a provider's short ID, kept up to date by the
+    /// repository, rather than one the user set.
+    pub automatic: bool,
+}
+
+/// A resource as stored in the local repository.
+#[derive(Debug, Clone)]
+pub struct CachedResource {
+    pub resource: Resource,
+    pub cached_at: DateTime<Utc>,
+    /// When the resource was found deleted or archived upstream.
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Resources gathered from one or more providers, together with the
+/// providers that failed, so callers can tell complete results from partial
+/// ones.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueryResult {
+    pub resources: Vec<Resource>,
+    pub errors: Vec<ProviderFailure>,
+    pub warnings: Vec<String>,
+    /// Where each provider's search results came from.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub origins: BTreeMap<String, ResultOrigin>,
+    /// Where the query was sent, when no sources were given and it was
+    /// routed by what it asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<Route>,
+}
+
+impl QueryResult {
+    pub fn is_partial(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    pub fn add_failure(&mut self, provider: &str, error: &DomainError) {
+        self.errors.push(ProviderFailure {
+            provider: provider.to_lowercase(),
+            error: error.to_string(),
+            details: ErrorDetails {
+                provider: None,
+                ..error.details()
+            },
+        });
+    }
+}
+
+/// Where a search given no sources was sent, chosen from the query itself.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Route {
+    /// The query named one resource, which was fetched instead.
+    Get { id: String },
+    /// The query read as being about `intent`, so the providers that hold
+    /// it were searched, and the others too if they found nothing.
+    Sources {
+        intent: String,
+        providers: Vec<String>,
+        widened: bool,
+    },
+    /// Nothing stood out, so every provider was searched.
+    All,
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Route::Get { id } => write!(f, "fetched {}", id),
+            Route::Sources {
+                intent,
+                providers,
+                widened,
+            } => {
+                write!(f, "{} query, searched {}", intent, providers.join(", "))?;
+                if *widened {
+                    f.write_str(" then every other provider")?;
+                }
+                Ok(())
+            }
+            Route::All => f.write_str("searched every provider"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderFailure {
+    pub provider: String,
+    pub error: String,
+    #[serde(flatten)]
+    pub details: ErrorDetails,
+}
+
+/// Search fallbacks, from fastest to freshest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchTier {
+    /// Resources pulled into the cache by `sync`, searched locally.
+    LocalIndex,
+    /// An earlier identical search against the provider.
+    CachedResults,
+    /// The provider's search API.
+    Live,
+    /// Versions of resources the cache stored before a given time.
+    History,
+}
+
+impl fmt::Display for SearchTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SearchTier::LocalIndex => "local index",
+            SearchTier::CachedResults => "cached results",
+            SearchTier::Live => "live",
+            SearchTier::History => "cache history",
+        })
+    }
+}
+
+/// The tier a provider's search results were served from, and how current
+/// they are.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultOrigin {
+    pub tier: SearchTier,
+    pub as_of: DateTime<Utc>,
+}
+
+/// Result IDs a provider returned for a search, as stored in the cache.
+#[derive(Debug, Clone)]
+pub struct CachedSearch {
+    pub ids: Vec<String>,
+    pub searched_at: DateTime<Utc>,
+}
+
+/// What the repository's local usage statistics count. They never leave
+/// the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageEvent {
+    /// A CLI command was run, named by its subcommand.
+    Command,
+    /// A provider was called, named by the provider.
+    ProviderCall,
+    /// A resource or search was served from the cache instead of calling
+    /// the provider it's named by.
+    CacheHit,
+}
+
+impl UsageEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsageEvent::Command => "command",
+            UsageEvent::ProviderCall => "provider_call",
+            UsageEvent::CacheHit => "cache_hit",
+        }
+    }
+
+    pub fn parse(event: &str) -> Option<Self> {
+        [
+            UsageEvent::Command,
+            UsageEvent::ProviderCall,
+            UsageEvent::CacheHit,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == event)
+    }
+}
+
+/// An object visible to a provider's credentials, as enumerated by
+/// `ResourceProvider::discover`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredItem {
+    pub id: String,
+    /// Provider-specific object type, e.g. `page` or `database`.
+    pub kind: String,
+    pub title: String,
+    /// Containing object, or `None` for top-level items.
+    pub parent_id: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Rules grouping resources from several providers into one initiative, as
+/// configured under `[projects.<name>]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectDefinition {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Linear projects, by ID or name, whose issues belong to the project.
+    #[serde(default)]
+    pub linear_projects: Vec<String>,
+    /// Notion pages that, with every page nested under them, belong to the
+    /// project.
+    #[serde(default)]
+    pub notion_pages: Vec<String>,
+}
+
+/// Limits that keep runaway callers, such as an agent stuck in a loop,
+/// from using up API quotas shared with everyone else, as configured under
+/// `[guardrails]`. Each is unlimited when unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guardrails {
+    /// Provider calls one CLI command or MCP request may make.
+    pub max_api_calls: Option<usize>,
+    /// Resources one fetch, search or listing may return.
+    pub max_resources: Option<usize>,
+    /// Estimated tokens a context bundle may take.
+    pub max_bundle_tokens: Option<usize>,
+}
+
+/// A slice of one provider that `sync` mirrors, as listed under
+/// `[[providers.<name>.sync.scopes]]`: the filters it is listed with, as
+/// `fetch --filter` takes them, and optionally a window of recent updates.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncScope {
+    /// Only resources updated within this long, e.g. `90d`, or since this
+    /// date, e.g. `2026-01-01`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    #[serde(flatten)]
+    pub filters: BTreeMap<String, String>,
+}
+
+impl SyncScope {
+    /// The oldest update the scope covers, if it has a window.
+    pub fn start(&self) -> Result<Option<DateTime<Utc>>, DomainError> {
+        let Some(since) = &self.since else {
+            return Ok(None);
+        };
+        if let Ok(window) = humantime::parse_duration(since) {
+            let window = chrono::Duration::from_std(window).map_err(|e| {
+                DomainError::InvalidQuery(format!("Invalid since {}: {}", since, e))
+            })?;
+            return Ok(Some(Utc::now() - window));
+        }
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+            return Ok(Some(date.and_time(chrono::NaiveTime::MIN).and_utc()));
+        }
+        DateTime::parse_from_rfc3339(since)
+            .map(|start| Some(start.with_timezone(&Utc)))
+            .map_err(|_| {
+                DomainError::InvalidQuery(format!(
+                    "Invalid since {}: expected a duration such as 90d or a date such as 2026-01-01",
+                    since
+                ))
+            })
+    }
+
+    /// The scope as `key=value` filters, e.g. `team=ENG since=90d`.
+    pub fn describe(&self) -> String {
+        self.filters
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .chain(self.since.iter().map(|since| format!("since={}", since)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// What syncing a query would cost a provider, as worked out by
+/// `ResourceProvider::estimate_sync` before it starts.
+#[derive(Debug, Clone, Default)]
+pub struct SyncEstimate {
+    /// Resources the sync would fetch.
+    pub resources: usize,
+    /// API calls listing them takes.
+    pub listing_calls: usize,
+    /// API calls fetching their content takes, extrapolated from a sample.
+    pub content_calls: usize,
+    /// Requests a second the provider allows over time.
+    pub requests_per_second: f64,
+}
+
+impl SyncEstimate {
+    pub fn calls(&self) -> usize {
+        self.listing_calls + self.content_calls
+    }
+
+    /// Content calls per resource, on average.
+    pub fn calls_per_resource(&self) -> f64 {
+        self.content_calls as f64 / self.resources.max(1) as f64
+    }
+
+    /// How long making the calls takes at the provider's rate limit.
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.calls() as f64 / self.requests_per_second.max(0.01))
+    }
+
+    /// Adds the cost of another sync with the same provider, such as of
+    /// another scope.
+    pub fn add(&mut self, other: &SyncEstimate) {
+        self.resources += other.resources;
+        self.listing_calls += other.listing_calls;
+        self.content_calls += other.content_calls;
+        self.requests_per_second = other.requests_per_second;
+    }
+}
+
+/// The slice of the knowledge base one server instance exposes, as
+/// configured under `[scopes.<name>]` and chosen with `serve --scope`.
+/// Providers the scope doesn't mention are left out entirely.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScopeDefinition {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Providers exposed in full.
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Notion databases whose pages are exposed.
+    #[serde(default)]
+    pub notion_databases: Vec<String>,
+    /// Linear teams, by key, whose issues are exposed.
+    #[serde(default)]
+    pub linear_teams: Vec<String>,
+    /// Directories whose files are exposed, as upstream MCP servers serve
+    /// them under `file://` URIs.
+    #[serde(default)]
+    pub directories: Vec<PathBuf>,
+}
+
+impl ScopeDefinition {
+    /// Whether any of `provider`'s resources may be in scope.
+    pub fn includes_provider(&self, provider: &str) -> bool {
+        let provider = provider.to_lowercase();
+        self.providers
+            .iter()
+            .any(|listed| listed.eq_ignore_ascii_case(&provider))
+            || match provider.as_str() {
+                "notion" => !self.notion_databases.is_empty(),
+                "linear" => !self.linear_teams.is_empty(),
+                "fixture" => false,
+                _ => !self.directories.is_empty(),
+            }
+    }
+
+    pub fn contains(&self, resource: &Resource) -> bool {
+        let provider = resource.source.provider_name();
+        if self
+            .providers
+            .iter()
+            .any(|listed| listed.eq_ignore_ascii_case(provider))
+        {
+            return true;
+        }
+
+        match &resource.source {
+            ResourceSource::Notion { database_id, .. } => {
+                database_id.as_deref().is_some_and(|database_id| {
+                    let database_id = database_id.replace('-', "");
+                    self.notion_databases
+                        .iter()
+                        .any(|listed| listed.replace('-', "").eq_ignore_ascii_case(&database_id))
+                })
+            }
+            // Issue identifiers start with their team's key, e.g. `ENG-123`.
+            ResourceSource::Linear { .. } => resource
+                .short_id()
+                .and_then(|identifier| identifier.rsplit_once('-'))
+                .is_some_and(|(team, _)| {
+                    self.linear_teams
+                        .iter()
+                        .any(|listed| listed.eq_ignore_ascii_case(team))
+                }),
+            ResourceSource::Custom(_) => resource
+                .metadata
+                .get("uri")
+                .and_then(serde_json::Value::as_str)
+                .and_then(|uri| uri.strip_prefix("file://"))
+                .is_some_and(|path| {
+                    self.directories
+                        .iter()
+                        .any(|directory| Path::new(path).starts_with(directory))
+                }),
+        }
+    }
+}
+
+/// How sensitive a resource is, from least to most. Resources are tagged
+/// under the `classification` metadata key by the rules configured under
+/// `[classification]`, and MCP clients can be kept from seeing anything
+/// above a level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Classification {
+    Public,
+    #[default]
+    Internal,
+    Confidential,
+}
+
+impl Classification {
+    /// The metadata key resources carry their classification under.
+    pub const METADATA_KEY: &'static str = "classification";
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Classification::Public => "public",
+            Classification::Internal => "internal",
+            Classification::Confidential => "confidential",
+        }
+    }
+
+    /// The classification `resource` is tagged with; untagged resources are
+    /// internal.
+    pub fn of(resource: &Resource) -> Self {
+        resource
+            .metadata
+            .get(Self::METADATA_KEY)
+            .and_then(serde_json::Value::as_str)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Classification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "public" => Ok(Classification::Public),
+            "internal" => Ok(Classification::Internal),
+            "confidential" => Ok(Classification::Confidential),
+            _ => Err(format!(
+                "unknown classification {}: expected public, internal or confidential",
+                s
+            )),
+        }
+    }
+}
+
+/// A rule under `[[classification.rules]]`: resources the sources it names
+/// cover, picked the way scopes pick them, get its classification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationRule {
+    pub classification: Classification,
+    #[serde(flatten)]
+    pub sources: ScopeDefinition,
+}
+
+/// How search results and project bundles are ordered, as configured under
+/// `[scoring]`. Each signal is scaled to between 0 and 1 and multiplied by
+/// its weight, and the sum is added to any rank hooks give.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringWeights {
+    /// Weight of how many of the query's words the title or content has.
+    pub relevance: f64,
+    /// Weight of how recently the resource was updated.
+    pub recency: f64,
+    /// Age at which an update counts half as recent, in days.
+    pub recency_half_life_days: f64,
+    /// Weight of the provider's priority.
+    pub priority: f64,
+    /// Each provider's priority, between 0 and 1; unlisted providers get 0.
+    pub providers: BTreeMap<String, f64>,
+    /// Weight of being pinned.
+    pub pinned: f64,
+    /// Pinned resources, by ID, URI, link, Linear issue identifier or alias.
+    pub pins: Vec<String>,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        Self {
+            relevance: 1.0,
+            recency: 0.5,
+            recency_half_life_days: 30.0,
+            priority: 0.5,
+            providers: BTreeMap::new(),
+            pinned: 2.0,
+            pins: Vec::new(),
+        }
+    }
+}
+
+/// A copy of a cache's resources, exported by one install for another to
+/// start from instead of syncing everything itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Format of the snapshot, so later formats can be told apart.
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// When each provider in the snapshot was last completely synced, by
+    /// lowercase name.
+    #[serde(default)]
+    pub syncs: BTreeMap<String, DateTime<Utc>>,
+    pub resources: Vec<Resource>,
+}
+
+/// One page of a provider listing, as returned by
+/// `ResourceProvider::list_page`.
+#[derive(Debug, Clone, Default)]
+pub struct ListingPage {
+    pub resources: Vec<Resource>,
+    /// Cursor for the next page, or `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// A change to upstream data, made through `ResourceProvider::apply`.
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// A new resource under `parent`, e.g. a Notion page or Linear team.
+    Create {
+        parent: String,
+        title: String,
+        content: String,
+        /// Names of labels to tag it with, for providers that have them.
+        labels: Vec<String>,
+    },
+    /// New title and/or content for an existing resource.
+    Update {
+        id: String,
+        title: Option<String>,
+        content: Option<String>,
+    },
+    /// Adds content, as Markdown, to the end of an existing resource.
+    Append { id: String, content: String },
+}
+
+impl Change {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::Create { .. } => "create",
+            Change::Update { .. } => "update",
+            Change::Append { .. } => "append",
+        }
+    }
+
+    /// ID of the existing resource the change is made to, or `None` for a
+    /// resource being created.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Change::Create { .. } => None,
+            Change::Update { id, .. } | Change::Append { id, .. } => Some(id),
+        }
+    }
+}
+
+/// A write refused because the resource changed after the version the
+/// writer read, which would otherwise overwrite that change.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionConflict {
+    pub id: String,
+    /// The version the writer read, from `Resource::version`.
+    pub expected_version: String,
+    /// The version upstream now.
+    pub current_version: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was changed at {} (now version {}, not {}); fetch it again and redo the change",
+            self.id,
+            self.updated_at.to_rfc3339(),
+            self.current_version,
+            self.expected_version
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Query {
     pub source: QuerySource,
@@ -33,11 +800,67 @@ pub struct Query {
     pub limit: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Query {
+    /// Returns the `page_size` filter, used to tune how many items each
+    /// provider request asks for.
+    pub fn page_size(&self) -> Result<Option<usize>, DomainError> {
+        self.filters
+            .get("page_size")
+            .map(|size| {
+                size.parse::<usize>()
+                    .ok()
+                    .filter(|size| *size > 0)
+                    .ok_or_else(|| {
+                        DomainError::InvalidQuery(format!("Invalid page_size: {}", size))
+                    })
+            })
+            .transpose()
+    }
+}
+
+/// Where a query goes, read and written as its name: `notion`, `linear`,
+/// `all`, or the name of another provider or of a source group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(from = "String", into = "String")]
+#[schemars(with = "String")]
 pub enum QuerySource {
     Notion,
     Linear,
     All,
+    /// Any other provider, or a group of sources from the config file.
+    Named(String),
+}
+
+impl QuerySource {
+    pub fn parse(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "notion" => QuerySource::Notion,
+            "linear" => QuerySource::Linear,
+            "all" => QuerySource::All,
+            name => QuerySource::Named(name.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            QuerySource::Notion => "notion",
+            QuerySource::Linear => "linear",
+            QuerySource::All => "all",
+            QuerySource::Named(name) => name,
+        }
+    }
+}
+
+impl From<String> for QuerySource {
+    fn from(name: String) -> Self {
+        QuerySource::parse(&name)
+    }
+}
+
+impl From<QuerySource> for String {
+    fn from(source: QuerySource) -> Self {
+        source.name().to_string()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -50,4 +873,136 @@ pub enum DomainError {
 
     #[error("Provider error: {0}")]
     ProviderError(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Read-only mode: {0}")]
+    ReadOnly(String),
+
+    #[error("Guardrail exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(VersionConflict),
+
+    #[error("Duplicate provider: {0} is already registered")]
+    DuplicateProvider(String),
+
+    #[error("Provider not configured: {provider}")]
+    NotConfigured { provider: String },
+
+    /// The provider couldn't be reached, or failed on its side (a 5xx), so
+    /// the same request may well succeed later.
+    #[error("Provider unavailable: {provider}: {message}")]
+    Unavailable { provider: String, message: String },
+
+    #[error(
+        "Rate limited: {provider} is throttling requests; {}",
+        retry_hint(*.retry_after_secs)
+    )]
+    RateLimited {
+        provider: String,
+        /// How long the provider asked to wait, if it said.
+        retry_after_secs: Option<u64>,
+    },
+
+    #[error(
+        "Unauthorized: {provider} refused the credentials{}: {message}",
+        scope_hint(.missing_scope)
+    )]
+    Unauthorized {
+        provider: String,
+        /// The permission the provider said the credentials need, if it
+        /// said.
+        missing_scope: Option<String>,
+        message: String,
+    },
+}
+
+fn scope_hint(missing_scope: &Option<String>) -> String {
+    match missing_scope {
+        Some(scope) => format!(", which lack the {} scope", scope),
+        None => String::new(),
+    }
+}
+
+fn retry_hint(retry_after_secs: Option<u64>) -> String {
+    match retry_after_secs {
+        Some(secs) => format!("retry in {}s", secs),
+        None => "retry later".to_string(),
+    }
+}
+
+impl DomainError {
+    /// What a client needs to recover from the error without parsing its
+    /// message.
+    pub fn details(&self) -> ErrorDetails {
+        let kind = match self {
+            DomainError::ResourceNotFound(_) => "not_found",
+            DomainError::InvalidQuery(_) => "invalid_query",
+            DomainError::ProviderError(_) => "provider_error",
+            DomainError::StorageError(_) => "storage_error",
+            DomainError::ReadOnly(_) => "read_only",
+            DomainError::LimitExceeded(_) => "limit_exceeded",
+            DomainError::Conflict(_) => "conflict",
+            DomainError::DuplicateProvider(_) => "duplicate_provider",
+            DomainError::NotConfigured { .. } => "not_configured",
+            DomainError::Unavailable { .. } => "unavailable",
+            DomainError::RateLimited { .. } => "rate_limited",
+            DomainError::Unauthorized { .. } => "unauthorized",
+        };
+        let mut details = ErrorDetails {
+            kind,
+            // Only outages and throttling pass by themselves; the rest need
+            // something changed first, if only the time.
+            retryable: matches!(
+                self,
+                DomainError::Unavailable { .. } | DomainError::RateLimited { .. }
+            ),
+            provider: None,
+            retry_after_secs: None,
+            missing_scope: None,
+        };
+        match self {
+            DomainError::RateLimited {
+                provider,
+                retry_after_secs,
+            } => {
+                details.provider = Some(provider.clone());
+                details.retry_after_secs = *retry_after_secs;
+            }
+            DomainError::Unauthorized {
+                provider,
+                missing_scope,
+                ..
+            } => {
+                details.provider = Some(provider.clone());
+                details.missing_scope = missing_scope.clone();
+            }
+            DomainError::DuplicateProvider(provider) => {
+                details.provider = Some(provider.to_lowercase())
+            }
+            DomainError::NotConfigured { provider } | DomainError::Unavailable { provider, .. } => {
+                details.provider = Some(provider.clone())
+            }
+            _ => {}
+        }
+        details
+    }
+}
+
+/// A `DomainError` in machine-readable form, as sent to MCP clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorDetails {
+    /// The error's variant, e.g. `rate_limited` or `not_found`.
+    pub kind: &'static str,
+    /// Whether the same request may succeed if simply made again.
+    pub retryable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_scope: Option<String>,
 }
@@ -0,0 +1,1047 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use futures::future;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    domain::{
+        DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource, ResourceSource,
+    },
+    infrastructure::http::{self, HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
+};
+
+/// REST API version requested, the newest that Azure DevOps Server 2020 and
+/// later understand as well as the cloud service.
+const API_VERSION: &str = "6.0";
+
+/// Most work items one request may fetch, and so the most listed a page.
+const WORK_ITEMS_PER_REQUEST: usize = 200;
+
+/// Wiki pages fetched at once when listing.
+const PAGE_CONCURRENCY: usize = 8;
+
+/// Fields of a work item holding its text, in the order they're shown, with
+/// the heading each gets after the description.
+const TEXT_FIELDS: [(&str, &str); 3] = [
+    ("System.Description", ""),
+    ("Microsoft.VSTS.TCM.ReproSteps", "Repro steps"),
+    (
+        "Microsoft.VSTS.Common.AcceptanceCriteria",
+        "Acceptance criteria",
+    ),
+];
+
+#[derive(Debug, Deserialize)]
+struct List<T> {
+    value: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkItem {
+    id: u64,
+    #[serde(default)]
+    fields: HashMap<String, Value>,
+    #[serde(rename = "_links")]
+    links: Option<Links>,
+}
+
+impl WorkItem {
+    fn text(&self, field: &str) -> Option<&str> {
+        self.fields
+            .get(field)
+            .and_then(Value::as_str)
+            .filter(|text| !text.is_empty())
+    }
+
+    fn date(&self, field: &str) -> Option<DateTime<Utc>> {
+        self.text(field)?.parse().ok()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Links {
+    html: Option<Link>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Link {
+    href: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WiqlResult {
+    /// Empty for queries of links between work items, which aren't
+    /// supported.
+    #[serde(default)]
+    work_items: Vec<WorkItemReference>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkItemReference {
+    id: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Wiki {
+    id: String,
+    name: String,
+    project_id: String,
+    repository_id: Option<String>,
+    #[serde(default)]
+    versions: Vec<WikiVersion>,
+}
+
+impl Wiki {
+    /// The branch the wiki is published from.
+    fn branch(&self) -> Option<&str> {
+        self.versions
+            .first()
+            .map(|version| version.version.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct WikiVersion {
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WikiPage {
+    id: Option<u64>,
+    path: String,
+    git_item_path: Option<String>,
+    content: Option<String>,
+    remote_url: Option<String>,
+    #[serde(default)]
+    sub_pages: Vec<WikiPage>,
+}
+
+impl WikiPage {
+    /// The page and every page under it, each without its subpages.
+    fn flatten(mut self, pages: &mut Vec<WikiPage>) {
+        for page in std::mem::take(&mut self.sub_pages) {
+            page.flatten(pages);
+        }
+        pages.push(self);
+    }
+}
+
+/// A file in a wiki's Git repository, with its last change.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitItem {
+    path: String,
+    latest_processed_change: Option<Commit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Commit {
+    committer: Option<Signature>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Signature {
+    date: DateTime<Utc>,
+}
+
+/// What a resource is in Azure DevOps, which its ID starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    WorkItem,
+    Page,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::WorkItem => "workitem",
+            Kind::Page => "page",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "workitem" => Some(Kind::WorkItem),
+            "page" => Some(Kind::Page),
+            _ => None,
+        }
+    }
+
+    /// The kinds the `kind` filter selects, both by default. `state`,
+    /// `type` and `wiql` only apply to work items and `wiki` to pages, so
+    /// they leave out the other kind.
+    fn selected(query: &Query) -> Result<Vec<Self>, DomainError> {
+        let mut kinds = match query.filters.get("kind") {
+            None => vec![Kind::WorkItem, Kind::Page],
+            Some(kind) => vec![Self::parse(kind).ok_or_else(|| {
+                DomainError::InvalidQuery(format!(
+                    "Unsupported kind filter: {} (expected \"workitem\" or \"page\")",
+                    kind
+                ))
+            })?],
+        };
+        if ["state", "type", "wiql"]
+            .iter()
+            .any(|filter| query.filters.contains_key(*filter))
+        {
+            kinds.retain(|kind| *kind == Kind::WorkItem);
+        }
+        if query.filters.contains_key("wiki") {
+            kinds.retain(|kind| *kind == Kind::Page);
+        }
+        Ok(kinds)
+    }
+}
+
+pub struct AzureDevOpsAdapter {
+    http: HttpClient,
+    /// The organization or, on Azure DevOps Server, the project
+    /// collection, e.g. `https://dev.azure.com/contoso` or
+    /// `https://tfs.contoso.com/tfs/DefaultCollection`, without a
+    /// trailing `/`.
+    base_url: String,
+}
+
+impl AzureDevOpsAdapter {
+    /// `url` is the organization's or collection's address, or just the
+    /// name of an organization on dev.azure.com. `api_key` is a personal
+    /// access token, optionally written `<user>:<token>` for servers that
+    /// want a user name.
+    pub fn new(url: &str, api_key: String) -> Result<Self, DomainError> {
+        let url = url.trim().trim_end_matches('/');
+        let base_url = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("https://dev.azure.com/{}", url)
+        };
+
+        // Personal access tokens go in basic auth with any user name.
+        let (user, token) = api_key.split_once(':').unwrap_or(("", &api_key));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!(
+                "Basic {}",
+                BASE64.encode(format!("{}:{}", user, token))
+            ))
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?,
+        );
+
+        let http = HttpClient::new("Azure DevOps", headers, vec![token.to_string()])?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self { http, base_url })
+    }
+
+    /// Azure DevOps delays rather than refuses most requests over its
+    /// limits, and says how long to wait when it does refuse.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![429, 500, 502, 503, 504],
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// `path` under the organization, or under `project` in it if given,
+    /// with the project name escaped.
+    fn url(&self, project: Option<&str>, path: &str) -> Result<Url, DomainError> {
+        let mut url = Url::parse(&self.base_url).map_err(|e| {
+            DomainError::InvalidQuery(format!("Invalid Azure DevOps URL {}: {}", self.base_url, e))
+        })?;
+        {
+            let mut segments = url.path_segments_mut().map_err(|_| {
+                DomainError::InvalidQuery(format!("Invalid Azure DevOps URL {}", self.base_url))
+            })?;
+            segments.pop_if_empty();
+            segments.extend(project);
+            segments.extend(path.split('/').filter(|segment| !segment.is_empty()));
+        }
+        url.query_pairs_mut()
+            .append_pair("api-version", API_VERSION);
+        Ok(url)
+    }
+
+    /// Sends `request`, returning the response and Azure DevOps's ID for
+    /// the request.
+    async fn send_json<T: DeserializeOwned>(
+        &self,
+        url: &Url,
+        request: reqwest::RequestBuilder,
+    ) -> Result<(T, Option<String>), DomainError> {
+        let response = self.http.send(request).await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(if status == StatusCode::NOT_FOUND {
+                DomainError::ResourceNotFound(format!("Azure DevOps has no {}", url.path()))
+            } else {
+                DomainError::ProviderError(format!("Azure DevOps API error: {}", error_text))
+            });
+        }
+
+        let data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((data, request_id))
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        params: &[(&str, &str)],
+    ) -> Result<(T, Option<String>), DomainError> {
+        let request = self.http.get(url.as_str()).query(params);
+        self.send_json(&url, request).await
+    }
+
+    /// The IDs of the work items matching `wiql`, a WIQL query, up to
+    /// `limit` if given, run in `project` if given so `@project` refers to
+    /// it. Without a limit, queries matching more than Azure DevOps returns
+    /// at once fail rather than come back cut short.
+    async fn query_work_items(
+        &self,
+        project: Option<&str>,
+        wiql: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<u64>, DomainError> {
+        let url = self.url(project, "_apis/wit/wiql")?;
+        let top = limit.map(|limit| limit.to_string());
+        let request = self
+            .http
+            .post(url.as_str())
+            .query(&[("$top", top.as_deref())])
+            .json(&serde_json::json!({ "query": wiql }));
+        let (result, _): (WiqlResult, _) = self.send_json(&url, request).await?;
+        Ok(result.work_items.into_iter().map(|item| item.id).collect())
+    }
+
+    /// The work items with `ids`, in that order.
+    async fn work_items(&self, ids: &[u64]) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::new();
+        for batch in ids.chunks(WORK_ITEMS_PER_REQUEST) {
+            let ids = batch
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            let url = self.url(None, "_apis/wit/workitems")?;
+            let (items, request_id): (List<WorkItem>, _) = self
+                .get_json(
+                    url,
+                    &[
+                        ("ids", ids.as_str()),
+                        ("$expand", "links"),
+                        ("errorPolicy", "omit"),
+                    ],
+                )
+                .await?;
+            resources.extend(
+                items
+                    .value
+                    .into_iter()
+                    .map(|item| self.work_item_to_resource(item, request_id.clone())),
+            );
+            report_progress(|| format!("Fetched {} Azure DevOps work items", resources.len()));
+        }
+        Ok(resources)
+    }
+
+    /// Up to `limit` work items, most recently changed first, matching the
+    /// `project`, `state`, `type` and `wiql` filters.
+    async fn list_work_items(
+        &self,
+        filters: &HashMap<String, String>,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let project = filters.get("project").map(String::as_str);
+        let wiql = wiql(filters, None, "[System.ChangedDate] DESC");
+        let ids = self.query_work_items(project, &wiql, Some(limit)).await?;
+        self.work_items(&ids[..ids.len().min(limit)]).await
+    }
+
+    /// A page of up to `page_size` work items matching the `project`,
+    /// `state`, `type` and `wiql` filters, in order of ID from the one
+    /// after `after`, with the last one's ID if there may be more. A whole
+    /// `SELECT` query can't be paged, so its work items come in one page.
+    async fn work_items_page(
+        &self,
+        filters: &HashMap<String, String>,
+        page_size: usize,
+        after: Option<u64>,
+    ) -> Result<(Vec<Resource>, Option<u64>), DomainError> {
+        let project = filters.get("project").map(String::as_str);
+        if let Some(select) = whole_query(filters) {
+            let ids = self.query_work_items(project, select, None).await?;
+            return Ok((self.work_items(&ids).await?, None));
+        }
+
+        let wiql = wiql(filters, after, "[System.Id]");
+        let ids = self
+            .query_work_items(project, &wiql, Some(page_size))
+            .await?;
+        let next = ids.last().copied().filter(|_| ids.len() >= page_size);
+        Ok((self.work_items(&ids).await?, next))
+    }
+
+    /// The wikis in `project`, or in every project if `None`.
+    async fn wikis(&self, project: Option<&str>) -> Result<Vec<Wiki>, DomainError> {
+        let url = self.url(project, "_apis/wiki/wikis")?;
+        let (wikis, _): (List<Wiki>, _) = self.get_json(url, &[]).await?;
+        Ok(wikis.value)
+    }
+
+    /// Every page of `wiki`, without content.
+    async fn page_tree(&self, wiki: &Wiki) -> Result<Vec<WikiPage>, DomainError> {
+        let url = self.url(
+            Some(&wiki.project_id),
+            &format!("_apis/wiki/wikis/{}/pages", wiki.id),
+        )?;
+        let (root, _): (WikiPage, _) = self
+            .get_json(url, &[("path", "/"), ("recursionLevel", "full")])
+            .await?;
+        let mut pages = Vec::new();
+        root.flatten(&mut pages);
+        // The root of a project wiki is the wiki itself, not a page.
+        pages.retain(|page| page.path != "/" && page.id.is_some());
+        Ok(pages)
+    }
+
+    /// When each file of `wiki`'s repository last changed, by path. Wiki
+    /// pages carry no dates of their own, so a page's date is its file's.
+    async fn page_dates(&self, wiki: &Wiki) -> HashMap<String, DateTime<Utc>> {
+        let Some(repository_id) = &wiki.repository_id else {
+            return HashMap::new();
+        };
+        let url = match self.url(
+            Some(&wiki.project_id),
+            &format!("_apis/git/repositories/{}/items", repository_id),
+        ) {
+            Ok(url) => url,
+            Err(_) => return HashMap::new(),
+        };
+        let mut params = vec![
+            ("scopePath", "/"),
+            ("recursionLevel", "Full"),
+            ("latestProcessedChange", "true"),
+        ];
+        if let Some(branch) = wiki.branch() {
+            params.push(("versionDescriptor.version", branch));
+            params.push(("versionDescriptor.versionType", "branch"));
+        }
+        match self.get_json::<List<GitItem>>(url, &params).await {
+            Ok((items, _)) => items
+                .value
+                .into_iter()
+                .filter_map(|item| {
+                    let date = item.latest_processed_change?.committer?.date;
+                    Some((item.path, date))
+                })
+                .collect(),
+            Err(e) => {
+                tracing::debug!("No dates for the pages of wiki {}: {}", wiki.name, e);
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Page `page_id` of `wiki` with its content.
+    async fn page(
+        &self,
+        wiki: &Wiki,
+        page_id: u64,
+        dates: &HashMap<String, DateTime<Utc>>,
+    ) -> Result<Resource, DomainError> {
+        let url = self.url(
+            Some(&wiki.project_id),
+            &format!("_apis/wiki/wikis/{}/pages/{}", wiki.id, page_id),
+        )?;
+        let (page, request_id): (WikiPage, _) =
+            self.get_json(url, &[("includeContent", "true")]).await?;
+        Ok(self.page_to_resource(wiki, page, dates, request_id))
+    }
+
+    /// The wikis matching the `project` and `wiki` filters.
+    async fn selected_wikis(
+        &self,
+        filters: &HashMap<String, String>,
+    ) -> Result<Vec<Wiki>, DomainError> {
+        let mut wikis = self
+            .wikis(filters.get("project").map(String::as_str))
+            .await?;
+        if let Some(name) = filters.get("wiki") {
+            wikis.retain(|wiki| wiki.name.eq_ignore_ascii_case(name) || wiki.id == *name);
+        }
+        Ok(wikis)
+    }
+
+    /// Up to `limit` wiki pages, most recently changed first, from the
+    /// wikis matching the `project` and `wiki` filters.
+    async fn list_pages(
+        &self,
+        filters: &HashMap<String, String>,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let wikis = self.selected_wikis(filters).await?;
+        let mut dates = Vec::new();
+        let mut pages = Vec::new();
+        for (index, wiki) in wikis.iter().enumerate() {
+            dates.push(self.page_dates(wiki).await);
+            for page in self.page_tree(wiki).await? {
+                let date = page_date(&page, &dates[index]);
+                pages.extend(page.id.map(|page_id| (index, page_id, date)));
+            }
+        }
+        pages.sort_by_key(|(_, _, date)| std::cmp::Reverse(*date));
+        pages.truncate(limit);
+
+        let mut resources = Vec::new();
+        for batch in pages.chunks(PAGE_CONCURRENCY) {
+            let fetched =
+                future::join_all(batch.iter().map(|(index, page_id, _)| {
+                    self.page(&wikis[*index], *page_id, &dates[*index])
+                }))
+                .await;
+            for resource in fetched {
+                resources.push(resource?);
+            }
+            report_progress(|| format!("Fetched {} Azure DevOps wiki pages", resources.len()));
+        }
+        Ok(resources)
+    }
+
+    /// Every page of the wiki with ID `wiki_id`, or of the first if `None`,
+    /// among those matching the `project` and `wiki` filters, without
+    /// content. Returns the next wiki's ID too, if there is one.
+    async fn pages_page(
+        &self,
+        filters: &HashMap<String, String>,
+        wiki_id: Option<&str>,
+    ) -> Result<(Vec<Resource>, Option<String>), DomainError> {
+        let wikis = self.selected_wikis(filters).await?;
+        let index = match wiki_id {
+            Some(wiki_id) => wikis
+                .iter()
+                .position(|wiki| wiki.id == wiki_id)
+                .ok_or_else(|| {
+                    DomainError::ResourceNotFound(format!(
+                        "Azure DevOps wiki not found: {}",
+                        wiki_id
+                    ))
+                })?,
+            None => 0,
+        };
+        let Some(wiki) = wikis.get(index) else {
+            return Ok((Vec::new(), None));
+        };
+
+        let dates = self.page_dates(wiki).await;
+        let resources = self
+            .page_tree(wiki)
+            .await?
+            .into_iter()
+            .map(|page| self.page_to_resource(wiki, page, &dates, None))
+            .collect();
+        let next = wikis.get(index + 1).map(|wiki| wiki.id.clone());
+        Ok((resources, next))
+    }
+
+    /// Wiki pages whose text matches `query`, through the Search extension,
+    /// which Azure DevOps Server may not have installed.
+    async fn search_pages(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct SearchResults {
+            #[serde(default)]
+            results: Vec<SearchResult>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct SearchResult {
+            path: String,
+            wiki: SearchWiki,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct SearchWiki {
+            id: String,
+        }
+
+        let url = self.search_url()?;
+        let request = self.http.post(url.as_str()).json(&serde_json::json!({
+            "searchText": query,
+            "$top": 25,
+            "$skip": 0,
+        }));
+        let (found, _): (SearchResults, _) = self.send_json(&url, request).await?;
+        if found.results.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Results name pages by file, so find them among their wikis' pages.
+        let wikis = self.wikis(None).await?;
+        let mut resources = Vec::new();
+        for wiki in &wikis {
+            let paths: Vec<&str> = found
+                .results
+                .iter()
+                .filter(|result| result.wiki.id == wiki.id)
+                .map(|result| result.path.as_str())
+                .collect();
+            if paths.is_empty() {
+                continue;
+            }
+            let dates = self.page_dates(wiki).await;
+            for page in self.page_tree(wiki).await? {
+                let matched = page
+                    .git_item_path
+                    .as_deref()
+                    .is_some_and(|path| paths.contains(&path));
+                if let (true, Some(page_id)) = (matched, page.id) {
+                    resources.push(self.page(wiki, page_id, &dates).await?);
+                }
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Where the Search extension answers: a separate host on the cloud
+    /// service, the collection itself on Azure DevOps Server.
+    fn search_url(&self) -> Result<Url, DomainError> {
+        let mut url = self.url(None, "_apis/search/wikisearchresults")?;
+        let host = url.host_str().unwrap_or_default().to_string();
+        let search_host = if host == "dev.azure.com" {
+            Some("almsearch.dev.azure.com".to_string())
+        } else {
+            host.strip_suffix(".visualstudio.com")
+                .map(|organization| format!("{}.almsearch.visualstudio.com", organization))
+        };
+        if let Some(search_host) = search_host {
+            url.set_host(Some(&search_host))
+                .map_err(|e| DomainError::InvalidQuery(e.to_string()))?;
+        }
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("api-version", "6.0-preview.1");
+        Ok(url)
+    }
+
+    fn work_item_to_resource(&self, item: WorkItem, request_id: Option<String>) -> Resource {
+        let mut metadata = HashMap::new();
+
+        metadata.insert("kind".to_string(), serde_json::json!("workitem"));
+        for (field, key) in [
+            ("System.WorkItemType", "type"),
+            ("System.State", "state"),
+            ("System.Reason", "reason"),
+            ("System.TeamProject", "project"),
+            ("System.AreaPath", "area_path"),
+            ("System.IterationPath", "iteration_path"),
+        ] {
+            if let Some(value) = item.text(field) {
+                metadata.insert(key.to_string(), serde_json::json!(value));
+            }
+        }
+        if let Some(priority) = item.fields.get("Microsoft.VSTS.Common.Priority") {
+            metadata.insert("priority".to_string(), priority.clone());
+        }
+        for (field, key) in [
+            ("System.AssignedTo", "assigned_to"),
+            ("System.CreatedBy", "created_by"),
+        ] {
+            if let Some(identity) = item.fields.get(field) {
+                metadata.insert(
+                    key.to_string(),
+                    serde_json::json!({
+                        "name": identity["displayName"],
+                        "email": identity["uniqueName"],
+                    }),
+                );
+            }
+        }
+        let tags: Vec<&str> = item
+            .text("System.Tags")
+            .map(|tags| tags.split(';').map(str::trim).collect())
+            .unwrap_or_default();
+        metadata.insert("tags".to_string(), serde_json::json!(tags));
+        if item.text("System.State") == Some("Removed") {
+            metadata.insert("archived".to_string(), serde_json::json!(true));
+        }
+
+        let content = TEXT_FIELDS
+            .iter()
+            .filter_map(|(field, heading)| {
+                let text = html2md::parse_html(item.text(field)?);
+                Some(if heading.is_empty() {
+                    text
+                } else {
+                    format!("## {}\n\n{}", heading, text)
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let created_at = item.date("System.CreatedDate").unwrap_or_else(Utc::now);
+        Resource {
+            id: format!("azuredevops_workitem_{}", item.id),
+            source: ResourceSource::Custom("azuredevops".to_string()),
+            title: item
+                .text("System.Title")
+                .map_or_else(|| format!("Work item {}", item.id), str::to_string),
+            content,
+            metadata,
+            created_at,
+            updated_at: item.date("System.ChangedDate").unwrap_or(created_at),
+            provenance: Some(Provenance {
+                provider: "azuredevops".to_string(),
+                fetched_at: Utc::now(),
+                api_version: Some(API_VERSION.to_string()),
+                request_id,
+                url: item
+                    .links
+                    .and_then(|links| links.html)
+                    .map(|link| link.href),
+            }),
+        }
+    }
+
+    fn page_to_resource(
+        &self,
+        wiki: &Wiki,
+        page: WikiPage,
+        dates: &HashMap<String, DateTime<Utc>>,
+        request_id: Option<String>,
+    ) -> Resource {
+        let mut metadata = HashMap::new();
+
+        metadata.insert("kind".to_string(), serde_json::json!("page"));
+        metadata.insert("wiki".to_string(), serde_json::json!(wiki.name));
+        metadata.insert("wiki_id".to_string(), serde_json::json!(wiki.id));
+        metadata.insert("path".to_string(), serde_json::json!(page.path));
+
+        // Only the file's last change is known, so that stands for both.
+        let date = page_date(&page, dates).unwrap_or_else(Utc::now);
+        let title = page
+            .path
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&wiki.name)
+            .to_string();
+        Resource {
+            id: format!(
+                "azuredevops_page_{}_{}_{}",
+                wiki.project_id,
+                wiki.id,
+                page.id.unwrap_or_default()
+            ),
+            source: ResourceSource::Custom("azuredevops".to_string()),
+            title,
+            content: page.content.unwrap_or_default(),
+            metadata,
+            created_at: date,
+            updated_at: date,
+            provenance: Some(Provenance {
+                provider: "azuredevops".to_string(),
+                fetched_at: Utc::now(),
+                api_version: Some(API_VERSION.to_string()),
+                request_id,
+                url: page.remote_url,
+            }),
+        }
+    }
+}
+
+/// The project, wiki and page IDs in the ID of a wiki page's resource,
+/// without its `azuredevops_` prefix.
+fn page_key(id: &str) -> Option<(&str, &str, u64)> {
+    let mut parts = id.strip_prefix("page_")?.split('_');
+    let (project_id, wiki_id, page_id) = (parts.next()?, parts.next()?, parts.next()?);
+    Some((project_id, wiki_id, page_id.parse().ok()?))
+}
+
+/// The WIQL query for the work items matching the `project`, `state`,
+/// `type` and `wiql` filters, with IDs above `after` if given, in `order`;
+/// or the `wiql` filter itself if it's a whole query.
+fn wiql(filters: &HashMap<String, String>, after: Option<u64>, order: &str) -> String {
+    if let Some(select) = whole_query(filters) {
+        return select.clone();
+    }
+
+    let mut conditions = Vec::new();
+    for (filter, field) in [
+        ("project", "System.TeamProject"),
+        ("state", "System.State"),
+        ("type", "System.WorkItemType"),
+    ] {
+        if let Some(value) = filters.get(filter) {
+            conditions.push(format!("[{}] = {}", field, quote(value)));
+        }
+    }
+    if let Some(wiql) = filters.get("wiql") {
+        conditions.push(format!("({})", wiql));
+    }
+    if let Some(after) = after {
+        conditions.push(format!("[System.Id] > {}", after));
+    }
+    let mut query = "SELECT [System.Id] FROM WorkItems".to_string();
+    if !conditions.is_empty() {
+        query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+    }
+    query.push_str(&format!(" ORDER BY {}", order));
+    query
+}
+
+/// The `wiql` filter if it's a whole `SELECT` query rather than a condition.
+fn whole_query(filters: &HashMap<String, String>) -> Option<&String> {
+    filters
+        .get("wiql")
+        .filter(|wiql| wiql.trim_start().to_lowercase().starts_with("select"))
+}
+
+/// When `page`'s file last changed, if known.
+fn page_date(page: &WikiPage, dates: &HashMap<String, DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    dates.get(page.git_item_path.as_deref()?).copied()
+}
+
+/// `value` as a WIQL string literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[async_trait]
+impl ResourceProvider for AzureDevOpsAdapter {
+    /// Lists work items and wiki pages, most recently changed first. `kind`
+    /// (`workitem` or `page`) lists only one; `project` limits both to a
+    /// project. Work items can be narrowed by `state`, `type` and `wiql`,
+    /// either a WIQL condition such as `[System.Tags] CONTAINS 'urgent'` or
+    /// a whole `SELECT` query; wiki pages by `wiki`, a wiki's name or ID.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let mut resources = Vec::new();
+        for kind in Kind::selected(query)? {
+            resources.extend(match kind {
+                Kind::WorkItem => self.list_work_items(&query.filters, limit).await?,
+                Kind::Page => self.list_pages(&query.filters, limit).await?,
+            });
+        }
+
+        resources.sort_by_key(|resource| std::cmp::Reverse(resource.updated_at));
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    /// Lists every work item in order of ID, a page at a time, then every
+    /// wiki's pages, a wiki at a time. Cursors are the kind being listed
+    /// and the last work item's ID or the next wiki's, empty to start on
+    /// it. Pages are listed without content, which `hydrate` fetches.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(WORK_ITEMS_PER_REQUEST)
+            .min(WORK_ITEMS_PER_REQUEST);
+        let kinds = Kind::selected(query)?;
+        let (kind, position) = match &cursor {
+            Some(cursor) => {
+                let (kind, position) = cursor
+                    .split_once(':')
+                    .and_then(|(kind, position)| Some((Kind::parse(kind)?, position)))
+                    .ok_or_else(|| {
+                        DomainError::InvalidQuery(format!(
+                            "Invalid Azure DevOps cursor: {}",
+                            cursor
+                        ))
+                    })?;
+                (kind, Some(position).filter(|position| !position.is_empty()))
+            }
+            None => match kinds.first() {
+                Some(kind) => (*kind, None),
+                None => return Ok(ListingPage::default()),
+            },
+        };
+
+        let (resources, next) = match kind {
+            Kind::WorkItem => {
+                let after = position
+                    .map(|after| {
+                        after.parse().map_err(|_| {
+                            DomainError::InvalidQuery(format!(
+                                "Invalid Azure DevOps cursor: {}",
+                                after
+                            ))
+                        })
+                    })
+                    .transpose()?;
+                let (resources, next) = self
+                    .work_items_page(&query.filters, page_size, after)
+                    .await?;
+                (resources, next.map(|id| id.to_string()))
+            }
+            Kind::Page => self.pages_page(&query.filters, position).await?,
+        };
+        let next_cursor = match next {
+            Some(next) => Some(format!("{}:{}", kind.name(), next)),
+            None => kinds
+                .iter()
+                .skip_while(|other| **other != kind)
+                .nth(1)
+                .map(|other| format!("{}:", other.name())),
+        };
+        Ok(ListingPage {
+            resources,
+            next_cursor,
+        })
+    }
+
+    /// Fetches the content of wiki pages, which are listed without it.
+    async fn hydrate(&self, mut resource: Resource) -> Result<Resource, DomainError> {
+        if !resource.content.is_empty()
+            || resource.metadata.get("kind") != Some(&serde_json::json!("page"))
+        {
+            return Ok(resource);
+        }
+        let id = resource
+            .id
+            .strip_prefix("azuredevops_")
+            .unwrap_or(&resource.id);
+        let Some((project_id, wiki_id, page_id)) = page_key(id) else {
+            return Ok(resource);
+        };
+
+        let url = self.url(
+            Some(project_id),
+            &format!("_apis/wiki/wikis/{}/pages/{}", wiki_id, page_id),
+        )?;
+        let (page, request_id): (WikiPage, _) =
+            self.get_json(url, &[("includeContent", "true")]).await?;
+        resource.content = page.content.unwrap_or_default();
+        if let Some(provenance) = &mut resource.provenance {
+            provenance.request_id = request_id;
+        }
+        Ok(resource)
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let id = id.strip_prefix("azuredevops_").unwrap_or(id);
+        if let Some(item_id) = id.strip_prefix("workitem_") {
+            let item_id: u64 = item_id.parse().map_err(|_| {
+                DomainError::ResourceNotFound(format!("Not an Azure DevOps work item: {}", id))
+            })?;
+            return self.work_items(&[item_id]).await?.pop().ok_or_else(|| {
+                DomainError::ResourceNotFound(format!(
+                    "Azure DevOps work item not found: {}",
+                    item_id
+                ))
+            });
+        }
+
+        let (project_id, wiki_id, page_id) = page_key(id).ok_or_else(|| {
+            DomainError::ResourceNotFound(format!(
+                "Not an Azure DevOps work item or wiki page: {}",
+                id
+            ))
+        })?;
+
+        let url = self.url(Some(project_id), &format!("_apis/wiki/wikis/{}", wiki_id))?;
+        let (wiki, _): (Wiki, _) = self.get_json(url, &[]).await?;
+        let dates = self.page_dates(&wiki).await;
+        self.page(&wiki, page_id, &dates)
+            .await
+            .map_err(|e| match e {
+                DomainError::ResourceNotFound(_) => DomainError::ResourceNotFound(format!(
+                    "Azure DevOps wiki page not found: {}",
+                    page_id
+                )),
+                e => e,
+            })
+    }
+
+    /// Searches work item titles and descriptions with WIQL, then wiki
+    /// pages if the Search extension is installed.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let wiql = format!(
+            "SELECT [System.Id] FROM WorkItems \
+             WHERE [System.Title] CONTAINS {0} OR [System.Description] CONTAINS WORDS {0} \
+             ORDER BY [System.ChangedDate] DESC",
+            quote(query)
+        );
+        let ids = self.query_work_items(None, &wiql, Some(50)).await?;
+        let mut resources = self.work_items(&ids).await?;
+
+        match self.search_pages(query).await {
+            Ok(pages) => resources.extend(pages),
+            Err(e) => tracing::debug!("Not searching Azure DevOps wikis: {}", e),
+        }
+        Ok(resources)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "AzureDevOps"
+    }
+
+    /// Lists the projects and their wikis.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct Project {
+            id: String,
+            name: String,
+        }
+
+        let url = self.url(None, "_apis/projects")?;
+        let (projects, _): (List<Project>, _) = self.get_json(url, &[("$top", "500")]).await?;
+        let mut items: Vec<DiscoveredItem> = projects
+            .value
+            .into_iter()
+            .map(|project| DiscoveredItem {
+                url: Some(format!(
+                    "{}/{}",
+                    self.base_url,
+                    project.name.replace(' ', "%20")
+                )),
+                id: project.id,
+                kind: "project".to_string(),
+                title: project.name,
+                parent_id: None,
+            })
+            .collect();
+
+        items.extend(
+            self.wikis(None)
+                .await?
+                .into_iter()
+                .map(|wiki| DiscoveredItem {
+                    id: wiki.id,
+                    kind: "wiki".to_string(),
+                    title: wiki.name,
+                    parent_id: Some(wiki.project_id),
+                    url: None,
+                }),
+        );
+        Ok(items)
+    }
+}
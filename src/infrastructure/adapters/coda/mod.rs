@@ -0,0 +1,978 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    domain::{
+        DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource, ResourceSource,
+    },
+    infrastructure::http::{self, HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
+};
+
+const CODA_API: &str = "https://coda.io/apis/v1";
+const CODA_API_VERSION: &str = "v1";
+
+/// Most items Coda returns per page.
+const CODA_MAX_PAGE_SIZE: usize = 100;
+
+/// Docs whose pages, tables or rows are fetched at once.
+const DOC_CONCURRENCY: usize = 4;
+
+/// Pages exported at once.
+const EXPORT_CONCURRENCY: usize = 4;
+
+/// How often a page export is checked on, and how many times before
+/// giving up on it.
+const EXPORT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const EXPORT_MAX_POLLS: usize = 30;
+
+/// Recently updated docs whose page names are searched.
+const SEARCH_DOCS: usize = 20;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct List<T> {
+    items: Vec<T>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Doc {
+    id: String,
+    name: String,
+    browser_link: String,
+    /// The owner's email address.
+    owner: Option<String>,
+    owner_name: Option<String>,
+    workspace: Option<Reference>,
+    folder: Option<Reference>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+/// Another object, as Coda refers to it from the one it returned.
+#[derive(Debug, Deserialize)]
+struct Reference {
+    id: String,
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page {
+    id: String,
+    name: String,
+    subtitle: Option<String>,
+    browser_link: String,
+    /// `canvas` for pages written in Coda, `embed` or `syncPage` for those
+    /// showing something else.
+    content_type: Option<String>,
+    #[serde(default)]
+    is_hidden: bool,
+    parent: Option<Reference>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Table {
+    id: String,
+    name: String,
+    /// `table`, or `view` for a view of another table.
+    table_type: String,
+    browser_link: String,
+    /// The page the table is on.
+    parent: Option<Reference>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Row {
+    id: String,
+    name: String,
+    index: Option<u64>,
+    browser_link: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    /// Cell values by column name.
+    #[serde(default)]
+    values: Map<String, Value>,
+}
+
+/// Where a listing is up to, kept in `list_page` cursors: at a doc's
+/// pages, or with `table` set, at a page of its rows.
+#[derive(Debug, Serialize, Deserialize)]
+struct Position {
+    doc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    table: Option<String>,
+    /// Coda's token for the page of rows, unset for the first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+impl Position {
+    fn doc(doc: &Doc) -> Self {
+        Self {
+            doc: doc.id.clone(),
+            table: None,
+            token: None,
+        }
+    }
+}
+
+/// A page's content being exported, which Coda does in the background.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    id: String,
+    /// `inProgress`, `complete` or `failed`.
+    status: String,
+    download_link: Option<String>,
+    error: Option<String>,
+}
+
+/// What a resource is in Coda, which its ID starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Doc,
+    Page,
+    Row,
+}
+
+impl Kind {
+    /// The kinds the `kind` filter selects, all of them by default.
+    /// `table_id` and `row_query` only apply to rows, so they leave out the
+    /// rest, and rows are only listed from docs named by `doc_id`.
+    fn selected(query: &Query) -> Result<Vec<Self>, DomainError> {
+        let mut kinds = match query.filters.get("kind").map(|kind| kind.as_str()) {
+            None => vec![Kind::Doc, Kind::Page, Kind::Row],
+            Some("doc") => vec![Kind::Doc],
+            Some("page") => vec![Kind::Page],
+            Some("row") => vec![Kind::Row],
+            Some(other) => {
+                return Err(DomainError::InvalidQuery(format!(
+                    "Unsupported kind filter: {} (expected \"doc\", \"page\" or \"row\")",
+                    other
+                )))
+            }
+        };
+        let rows_only =
+            query.filters.contains_key("table_id") || query.filters.contains_key("row_query");
+        if rows_only {
+            kinds.retain(|kind| *kind == Kind::Row);
+        }
+        if !query.filters.contains_key("doc_id") && kinds.contains(&Kind::Row) {
+            if rows_only || kinds == [Kind::Row] {
+                return Err(DomainError::InvalidQuery(
+                    "Listing Coda rows needs a doc_id filter".to_string(),
+                ));
+            }
+            kinds.retain(|kind| *kind != Kind::Row);
+        }
+        Ok(kinds)
+    }
+}
+
+pub struct CodaAdapter {
+    http: HttpClient,
+    /// Fetches exported pages from the links Coda gives out, which are
+    /// signed themselves and mustn't get the API token.
+    downloads: HttpClient,
+}
+
+impl CodaAdapter {
+    /// `api_key` is a Coda API token.
+    pub fn new(api_key: String) -> Result<Self, DomainError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?,
+        );
+
+        let http = HttpClient::new("Coda", headers, vec![api_key])?
+            .with_retry_policy(Self::default_retry_policy());
+        let downloads = HttpClient::new("Coda", HeaderMap::new(), Vec::new())?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self { http, downloads })
+    }
+
+    /// Coda allows a hundred reads every six seconds and says how long to
+    /// wait when they run out.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![429, 500, 502, 503, 504],
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry.clone());
+        self.downloads = self.downloads.with_retry_policy(retry);
+        self
+    }
+
+    /// Sends `request` for `path`, returning the response and Coda's ID for
+    /// the request.
+    async fn json<T: for<'de> Deserialize<'de>>(
+        &self,
+        request: RequestBuilder,
+        path: &str,
+    ) -> Result<(T, Option<String>), DomainError> {
+        let response = self.http.send(request).await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(match status {
+                StatusCode::NOT_FOUND => {
+                    DomainError::ResourceNotFound(format!("Coda has no {}", path))
+                }
+                // Such as a row query naming a column the table lacks.
+                StatusCode::BAD_REQUEST => {
+                    DomainError::InvalidQuery(format!("Coda rejected the query: {}", error_text))
+                }
+                _ => DomainError::ProviderError(format!("Coda API error: {}", error_text)),
+            });
+        }
+
+        let data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((data, request_id))
+    }
+
+    /// Fetches `path` with the `params` given.
+    async fn get_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(T, Option<String>), DomainError> {
+        let url = format!("{}{}", CODA_API, path);
+        self.json(self.http.get(&url).query(params), path).await
+    }
+
+    /// Up to `limit` items of the paginated list at `path`, with Coda's ID
+    /// for the last request.
+    async fn get_list<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+        limit: usize,
+    ) -> Result<(Vec<T>, Option<String>), DomainError> {
+        let page_size = limit.clamp(1, CODA_MAX_PAGE_SIZE);
+        let mut items = Vec::new();
+        let mut page_token: Option<String> = None;
+        let mut request_id = None;
+        loop {
+            let (list, id): (List<T>, _) = self
+                .get_page(path, params, page_size, page_token.as_deref())
+                .await?;
+            items.extend(list.items);
+            request_id = id.or(request_id);
+
+            match list.next_page_token {
+                Some(next) if items.len() < limit => page_token = Some(next),
+                _ => break,
+            }
+        }
+        items.truncate(limit);
+        Ok((items, request_id))
+    }
+
+    /// The page of `page_size` items of the paginated list at `path` that
+    /// `page_token` points to, or the first, with Coda's ID for the request.
+    async fn get_page<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+        page_size: usize,
+        page_token: Option<&str>,
+    ) -> Result<(List<T>, Option<String>), DomainError> {
+        let page_size = page_size.to_string();
+        let mut page_params = params.to_vec();
+        page_params.push(("limit", &page_size));
+        page_params.extend(page_token.map(|token| ("pageToken", token)));
+        self.get_json(path, &page_params).await
+    }
+
+    /// The docs with the IDs given, or else the `limit` most recently
+    /// updated docs.
+    async fn docs(&self, ids: Option<&[&str]>, limit: usize) -> Result<Vec<Doc>, DomainError> {
+        if let Some(ids) = ids {
+            let mut docs = Vec::new();
+            for batch in ids.chunks(DOC_CONCURRENCY) {
+                let fetched = future::join_all(batch.iter().map(|id| self.doc(id))).await;
+                for doc in fetched {
+                    docs.push(doc?);
+                }
+            }
+            return Ok(docs);
+        }
+
+        let (mut docs, _): (Vec<Doc>, _) = self.get_list("/docs", &[], usize::MAX).await?;
+        docs.sort_by_key(|doc| std::cmp::Reverse(doc.updated_at));
+        docs.truncate(limit);
+        Ok(docs)
+    }
+
+    async fn doc(&self, doc_id: &str) -> Result<Doc, DomainError> {
+        let (doc, _): (Doc, _) = self
+            .get_json(&format!("/docs/{}", doc_id), &[])
+            .await
+            .map_err(|e| not_found(e, "doc", doc_id))?;
+        Ok(doc)
+    }
+
+    /// Every page in a doc, subpages included, in the doc's order.
+    async fn pages(&self, doc_id: &str) -> Result<Vec<Page>, DomainError> {
+        let (pages, _) = self
+            .get_list(&format!("/docs/{}/pages", doc_id), &[], usize::MAX)
+            .await?;
+        Ok(pages)
+    }
+
+    /// The tables in a doc, views of other tables aside.
+    async fn tables(&self, doc_id: &str) -> Result<Vec<Table>, DomainError> {
+        let (tables, _): (Vec<Table>, _) = self
+            .get_list(&format!("/docs/{}/tables", doc_id), &[], usize::MAX)
+            .await?;
+        Ok(tables
+            .into_iter()
+            .filter(|table| table.table_type == "table")
+            .collect())
+    }
+
+    /// The tables in a doc, or only the table `table_id` (an ID or name),
+    /// views of other tables aside.
+    async fn selected_tables(
+        &self,
+        doc_id: &str,
+        table_id: Option<&str>,
+    ) -> Result<Vec<Table>, DomainError> {
+        let mut tables = self.tables(doc_id).await?;
+        tables.retain(|table| table_id.is_none_or(|id| table.id == id || table.name == id));
+        Ok(tables)
+    }
+
+    /// Up to `limit` rows of a table in the table's order, as resources.
+    /// `row_query` (`<column>:<value>`) selects the rows whose cell in the
+    /// column holds the value.
+    async fn rows(
+        &self,
+        doc: &Doc,
+        table: &Table,
+        row_query: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let (rows, request_id): (Vec<Row>, _) = self
+            .get_list(
+                &format!("/docs/{}/tables/{}/rows", doc.id, table.id),
+                &row_params(row_query),
+                limit,
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| row_to_resource(doc, table, row, request_id.clone()))
+            .collect())
+    }
+
+    /// The page of `page_size` rows of a table that `page_token` points
+    /// to, or the first, as resources, with the token of the next page.
+    async fn rows_page(
+        &self,
+        doc: &Doc,
+        table: &Table,
+        row_query: Option<&str>,
+        page_size: usize,
+        page_token: Option<&str>,
+    ) -> Result<(Vec<Resource>, Option<String>), DomainError> {
+        let (rows, request_id): (List<Row>, _) = self
+            .get_page(
+                &format!("/docs/{}/tables/{}/rows", doc.id, table.id),
+                &row_params(row_query),
+                page_size,
+                page_token,
+            )
+            .await?;
+        let resources = rows
+            .items
+            .into_iter()
+            .map(|row| row_to_resource(doc, table, row, request_id.clone()))
+            .collect();
+        Ok((resources, rows.next_page_token))
+    }
+
+    /// The resources of the `kinds` given in one doc: the doc itself, its
+    /// pages without their content, and up to `limit` rows of each table,
+    /// or only of the table `table_id`.
+    async fn doc_resources(
+        &self,
+        doc: &Doc,
+        kinds: &[Kind],
+        table_id: Option<&str>,
+        row_query: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::new();
+        if kinds.contains(&Kind::Doc) || kinds.contains(&Kind::Page) {
+            let pages = self.pages(&doc.id).await?;
+            if kinds.contains(&Kind::Doc) {
+                resources.push(doc_to_resource(doc, &pages));
+            }
+            if kinds.contains(&Kind::Page) {
+                resources.extend(pages.iter().map(|page| page_to_resource(doc, page)));
+            }
+        }
+        if kinds.contains(&Kind::Row) {
+            for table in self.selected_tables(&doc.id, table_id).await? {
+                resources.extend(self.rows(doc, &table, row_query, limit).await?);
+            }
+        }
+        Ok(resources)
+    }
+
+    /// A page's content as Markdown. Coda exports it in the background, so
+    /// the export is started, then checked on until it's ready.
+    async fn page_markdown(&self, doc_id: &str, page_id: &str) -> Result<String, DomainError> {
+        let path = format!("/docs/{}/pages/{}/export", doc_id, page_id);
+        let url = format!("{}{}", CODA_API, path);
+        let request = self
+            .http
+            .post(&url)
+            .json(&serde_json::json!({ "outputFormat": "markdown" }));
+        let (mut export, _): (Export, _) = self
+            .json(request, &path)
+            .await
+            .map_err(|e| not_found(e, "page", page_id))?;
+
+        for _ in 0..EXPORT_MAX_POLLS {
+            match export.status.as_str() {
+                "complete" => {
+                    let link = export.download_link.ok_or_else(|| {
+                        DomainError::ProviderError(format!(
+                            "Coda exported page {} without a link to it",
+                            page_id
+                        ))
+                    })?;
+                    let blob = self
+                        .downloads
+                        .download(&link, &format!("{}.md", page_id))
+                        .await?;
+                    return Ok(String::from_utf8_lossy(&blob.data).trim().to_string());
+                }
+                "failed" => {
+                    return Err(DomainError::ProviderError(format!(
+                        "Coda couldn't export page {}: {}",
+                        page_id,
+                        export.error.as_deref().unwrap_or("no reason given")
+                    )))
+                }
+                _ => {
+                    tokio::time::sleep(EXPORT_POLL_INTERVAL).await;
+                    (export, _) = self
+                        .get_json(&format!("{}/{}", path, export.id), &[])
+                        .await?;
+                }
+            }
+        }
+        Err(DomainError::ProviderError(format!(
+            "Coda took longer than {}s to export page {}",
+            EXPORT_MAX_POLLS as u64 * EXPORT_POLL_INTERVAL.as_secs(),
+            page_id
+        )))
+    }
+
+    /// Fills in the content of the pages among `resources`, exporting them.
+    async fn export_pages(&self, resources: &mut [Resource]) -> Result<(), DomainError> {
+        let mut pages: Vec<&mut Resource> = resources
+            .iter_mut()
+            .filter(|resource| resource.id.starts_with("coda_page_"))
+            .collect();
+        let total = pages.len();
+        let mut exported = 0;
+        for batch in pages.chunks_mut(EXPORT_CONCURRENCY) {
+            let contents = future::join_all(batch.iter().map(|resource| {
+                let (doc_id, page_id) = page_ids(&resource.id).unwrap_or_default();
+                self.page_markdown(doc_id, page_id)
+            }))
+            .await;
+            for (resource, content) in batch.iter_mut().zip(contents) {
+                resource.content = content?;
+            }
+            exported += batch.len();
+            report_progress(|| format!("Exported {}/{} Coda pages", exported, total));
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of a request for rows, selected by `row_query` if given.
+fn row_params(row_query: Option<&str>) -> Vec<(&str, &str)> {
+    let mut params = vec![
+        ("useColumnNames", "true"),
+        ("valueFormat", "simpleWithArrays"),
+    ];
+    params.extend(row_query.map(|row_query| ("query", row_query)));
+    params
+}
+
+/// The doc IDs in `query`'s comma-separated `doc_id` filter, if it has one.
+fn doc_ids(query: &Query) -> Option<Vec<&str>> {
+    query.filters.get("doc_id").map(|ids| {
+        ids.split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .collect()
+    })
+}
+
+/// The doc and page IDs in a page's resource ID.
+fn page_ids(id: &str) -> Option<(&str, &str)> {
+    id.strip_prefix("coda_page_")?.split_once('.')
+}
+
+/// The name of the object `reference` refers to, or else its ID.
+fn name(reference: &Reference) -> &str {
+    reference.name.as_deref().unwrap_or(&reference.id)
+}
+
+/// A doc as a resource holding an outline of its pages.
+fn doc_to_resource(doc: &Doc, pages: &[Page]) -> Resource {
+    let mut metadata = HashMap::new();
+
+    metadata.insert("kind".to_string(), serde_json::json!("doc"));
+    if let Some(owner) = doc.owner_name.as_ref().or(doc.owner.as_ref()) {
+        metadata.insert("owner".to_string(), serde_json::json!(owner));
+    }
+    if let Some(workspace) = &doc.workspace {
+        metadata.insert("workspace".to_string(), serde_json::json!(name(workspace)));
+    }
+    if let Some(folder) = &doc.folder {
+        metadata.insert("folder".to_string(), serde_json::json!(name(folder)));
+    }
+    metadata.insert("page_count".to_string(), serde_json::json!(pages.len()));
+
+    Resource {
+        id: format!("coda_doc_{}", doc.id),
+        source: ResourceSource::Custom("coda".to_string()),
+        title: doc.name.clone(),
+        content: outline(pages),
+        metadata,
+        created_at: doc.created_at,
+        updated_at: doc.updated_at,
+        provenance: Some(Provenance {
+            provider: "coda".to_string(),
+            fetched_at: Utc::now(),
+            api_version: Some(CODA_API_VERSION.to_string()),
+            request_id: None,
+            url: Some(doc.browser_link.clone()),
+        }),
+    }
+}
+
+/// `pages` as a nested Markdown list of links, subpages under their
+/// parents.
+fn outline(pages: &[Page]) -> String {
+    fn add(outline: &mut String, pages: &[Page], parent: Option<&str>, depth: usize) {
+        for page in pages
+            .iter()
+            .filter(|page| page.parent.as_ref().map(|p| p.id.as_str()) == parent)
+        {
+            outline.push_str(&format!(
+                "{}- [{}]({})\n",
+                "  ".repeat(depth),
+                page.name,
+                page.browser_link
+            ));
+            add(outline, pages, Some(&page.id), depth + 1);
+        }
+    }
+
+    if pages.is_empty() {
+        return String::new();
+    }
+    let mut outline = "## Pages\n\n".to_string();
+    add(&mut outline, pages, None, 0);
+    outline
+}
+
+/// A page as a resource, without its content, which is exported
+/// separately. Pages Coda doesn't date take their doc's dates.
+fn page_to_resource(doc: &Doc, page: &Page) -> Resource {
+    let mut metadata = HashMap::new();
+
+    metadata.insert("kind".to_string(), serde_json::json!("page"));
+    metadata.insert("doc_id".to_string(), serde_json::json!(doc.id));
+    metadata.insert("doc".to_string(), serde_json::json!(doc.name));
+    if let Some(parent) = &page.parent {
+        metadata.insert(
+            "parent_id".to_string(),
+            serde_json::json!(format!("coda_page_{}.{}", doc.id, parent.id)),
+        );
+    }
+    if let Some(subtitle) = page.subtitle.as_ref().filter(|s| !s.is_empty()) {
+        metadata.insert("subtitle".to_string(), serde_json::json!(subtitle));
+    }
+    if let Some(content_type) = &page.content_type {
+        metadata.insert("content_type".to_string(), serde_json::json!(content_type));
+    }
+    if page.is_hidden {
+        metadata.insert("hidden".to_string(), serde_json::json!(true));
+    }
+
+    Resource {
+        id: format!("coda_page_{}.{}", doc.id, page.id),
+        source: ResourceSource::Custom("coda".to_string()),
+        title: page.name.clone(),
+        content: String::new(),
+        metadata,
+        created_at: page.created_at.unwrap_or(doc.created_at),
+        updated_at: page.updated_at.unwrap_or(doc.updated_at),
+        provenance: Some(Provenance {
+            provider: "coda".to_string(),
+            fetched_at: Utc::now(),
+            api_version: Some(CODA_API_VERSION.to_string()),
+            request_id: None,
+            url: Some(page.browser_link.clone()),
+        }),
+    }
+}
+
+/// A row as a resource listing its cells, which are also in its metadata
+/// under `values`.
+fn row_to_resource(doc: &Doc, table: &Table, row: Row, request_id: Option<String>) -> Resource {
+    let mut metadata = HashMap::new();
+
+    metadata.insert("kind".to_string(), serde_json::json!("row"));
+    metadata.insert("doc_id".to_string(), serde_json::json!(doc.id));
+    metadata.insert("doc".to_string(), serde_json::json!(doc.name));
+    metadata.insert("table_id".to_string(), serde_json::json!(table.id));
+    metadata.insert("table".to_string(), serde_json::json!(table.name));
+    if let Some(page) = &table.parent {
+        metadata.insert(
+            "page_id".to_string(),
+            serde_json::json!(format!("coda_page_{}.{}", doc.id, page.id)),
+        );
+    }
+    if let Some(index) = row.index {
+        metadata.insert("index".to_string(), serde_json::json!(index));
+    }
+
+    let content = row
+        .values
+        .iter()
+        .filter_map(|(column, value)| Some(format!("**{}**: {}", column, cell(value)?)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    metadata.insert("values".to_string(), Value::Object(row.values));
+
+    Resource {
+        id: format!("coda_row_{}.{}.{}", doc.id, table.id, row.id),
+        source: ResourceSource::Custom("coda".to_string()),
+        title: row.name,
+        content,
+        metadata,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+        provenance: Some(Provenance {
+            provider: "coda".to_string(),
+            fetched_at: Utc::now(),
+            api_version: Some(CODA_API_VERSION.to_string()),
+            request_id,
+            url: Some(row.browser_link),
+        }),
+    }
+}
+
+/// A cell's value as text, or nothing if it's empty.
+fn cell(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::Null => return None,
+        Value::String(text) => text.trim().to_string(),
+        Value::Array(values) => values
+            .iter()
+            .filter_map(cell)
+            .collect::<Vec<_>>()
+            .join(", "),
+        value => value.to_string(),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Names the missing `kind` of object in a not found error.
+fn not_found(error: DomainError, kind: &str, id: &str) -> DomainError {
+    match error {
+        DomainError::ResourceNotFound(_) => {
+            DomainError::ResourceNotFound(format!("Coda {} not found: {}", kind, id))
+        }
+        e => e,
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for CodaAdapter {
+    /// Lists docs, their pages with the content exported as Markdown, and
+    /// the rows of their tables. `kind` (`doc`, `page` or `row`) lists only
+    /// one. `doc_id` takes one or more comma-separated doc IDs; without it
+    /// the most recently updated docs are listed, without their rows.
+    /// `table_id` (an ID or name) lists the rows of one table, and
+    /// `row_query` (`<column>:<value>`) the rows whose cell holds a value.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let table_id = query.filters.get("table_id").map(String::as_str);
+        let row_query = query.filters.get("row_query").map(String::as_str);
+        let doc_ids = doc_ids(query);
+        let kinds = Kind::selected(query)?;
+
+        let docs = self.docs(doc_ids.as_deref(), limit).await?;
+        let mut resources = Vec::new();
+        for batch in docs.chunks(DOC_CONCURRENCY) {
+            let fetched = future::join_all(
+                batch
+                    .iter()
+                    .map(|doc| self.doc_resources(doc, &kinds, table_id, row_query, limit)),
+            )
+            .await;
+            for doc_resources in fetched {
+                resources.extend(doc_resources?);
+            }
+            report_progress(|| format!("Fetched {} Coda resources", resources.len()));
+        }
+
+        resources.sort_by_key(|resource| std::cmp::Reverse(resource.updated_at));
+        resources.truncate(limit);
+        self.export_pages(&mut resources).await?;
+        Ok(resources)
+    }
+
+    /// Lists each doc with its pages, then its tables' rows a page at a
+    /// time. Docs go in order of ID, so one updated meanwhile keeps its
+    /// place. Pages are listed without content, which `hydrate` exports.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(CODA_MAX_PAGE_SIZE)
+            .min(CODA_MAX_PAGE_SIZE);
+        let table_id = query.filters.get("table_id").map(String::as_str);
+        let row_query = query.filters.get("row_query").map(String::as_str);
+        let kinds = Kind::selected(query)?;
+        let invalid = || DomainError::InvalidQuery(format!("Invalid Coda cursor: {:?}", cursor));
+
+        let mut docs = self.docs(doc_ids(query).as_deref(), usize::MAX).await?;
+        docs.sort_by(|a, b| a.id.cmp(&b.id));
+        let position = match &cursor {
+            Some(cursor) => serde_json::from_str(cursor).map_err(|_| invalid())?,
+            None => match docs.first() {
+                Some(doc) => Position::doc(doc),
+                None => return Ok(ListingPage::default()),
+            },
+        };
+        let index = docs
+            .iter()
+            .position(|doc| doc.id == position.doc)
+            .ok_or_else(invalid)?;
+        let doc = &docs[index];
+        let next_doc = docs.get(index + 1).map(Position::doc);
+        let tables = if kinds.contains(&Kind::Row) {
+            self.selected_tables(&doc.id, table_id).await?
+        } else {
+            Vec::new()
+        };
+        let at_table = |table: &Table, token| Position {
+            doc: doc.id.clone(),
+            table: Some(table.id.clone()),
+            token,
+        };
+
+        let (resources, next) = match &position.table {
+            None => {
+                let kinds: Vec<Kind> = kinds
+                    .into_iter()
+                    .filter(|kind| *kind != Kind::Row)
+                    .collect();
+                let resources = self
+                    .doc_resources(doc, &kinds, None, None, usize::MAX)
+                    .await?;
+                let next = tables.first().map(|table| at_table(table, None));
+                (resources, next.or(next_doc))
+            }
+            Some(table_id) => {
+                let table_index = tables
+                    .iter()
+                    .position(|table| table.id == *table_id)
+                    .ok_or_else(invalid)?;
+                let table = &tables[table_index];
+                let (resources, token) = self
+                    .rows_page(doc, table, row_query, page_size, position.token.as_deref())
+                    .await?;
+                let next = match token {
+                    Some(token) => Some(at_table(table, Some(token))),
+                    None => tables
+                        .get(table_index + 1)
+                        .map(|table| at_table(table, None))
+                        .or(next_doc),
+                };
+                (resources, next)
+            }
+        };
+        let next_cursor = next
+            .map(|next| serde_json::to_string(&next))
+            .transpose()
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok(ListingPage {
+            resources,
+            next_cursor,
+        })
+    }
+
+    /// Exports the content of pages, which are listed without it.
+    async fn hydrate(&self, mut resource: Resource) -> Result<Resource, DomainError> {
+        if !resource.content.is_empty() {
+            return Ok(resource);
+        }
+        if let Some((doc_id, page_id)) = page_ids(&resource.id) {
+            resource.content = self.page_markdown(doc_id, page_id).await?;
+        }
+        Ok(resource)
+    }
+
+    /// A doc with its outline, a page with its content, or a row.
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let id = id.strip_prefix("coda_").unwrap_or(id);
+        let not_coda =
+            || DomainError::ResourceNotFound(format!("Not a Coda doc, page or row: {}", id));
+
+        if let Some(doc_id) = id.strip_prefix("doc_") {
+            let doc = self.doc(doc_id).await?;
+            let pages = self.pages(doc_id).await?;
+            return Ok(doc_to_resource(&doc, &pages));
+        }
+        if let Some(ids) = id.strip_prefix("page_") {
+            let (doc_id, page_id) = ids.split_once('.').ok_or_else(not_coda)?;
+            let doc = self.doc(doc_id).await?;
+            let (page, _): (Page, _) = self
+                .get_json(&format!("/docs/{}/pages/{}", doc_id, page_id), &[])
+                .await
+                .map_err(|e| not_found(e, "page", page_id))?;
+            let mut resource = page_to_resource(&doc, &page);
+            resource.content = self.page_markdown(doc_id, page_id).await?;
+            return Ok(resource);
+        }
+
+        let mut ids = id.strip_prefix("row_").ok_or_else(not_coda)?.split('.');
+        let (Some(doc_id), Some(table_id), Some(row_id), None) =
+            (ids.next(), ids.next(), ids.next(), ids.next())
+        else {
+            return Err(not_coda());
+        };
+        let doc = self.doc(doc_id).await?;
+        let (table, _): (Table, _) = self
+            .get_json(&format!("/docs/{}/tables/{}", doc_id, table_id), &[])
+            .await
+            .map_err(|e| not_found(e, "table", table_id))?;
+        let (row, request_id): (Row, _) = self
+            .get_json(
+                &format!("/docs/{}/tables/{}/rows/{}", doc_id, table_id, row_id),
+                &[
+                    ("useColumnNames", "true"),
+                    ("valueFormat", "simpleWithArrays"),
+                ],
+            )
+            .await
+            .map_err(|e| not_found(e, "row", row_id))?;
+        Ok(row_to_resource(&doc, &table, row, request_id))
+    }
+
+    /// Docs, and pages of the most recently updated docs, whose name
+    /// contains every word of `query`, or for pages their subtitle. Coda
+    /// has no search of page content.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        let matches = |text: String| {
+            let text = text.to_lowercase();
+            !words.is_empty() && words.iter().all(|word| text.contains(word))
+        };
+        let docs = self.docs(None, usize::MAX).await?;
+
+        let mut resources = Vec::new();
+        for batch in docs[..docs.len().min(SEARCH_DOCS)].chunks(DOC_CONCURRENCY) {
+            let fetched = future::join_all(batch.iter().map(|doc| self.pages(&doc.id))).await;
+            for (doc, pages) in batch.iter().zip(fetched) {
+                resources.extend(
+                    pages?
+                        .iter()
+                        .filter(|page| {
+                            let subtitle = page.subtitle.as_deref().unwrap_or_default();
+                            matches(format!("{} {}", page.name, subtitle))
+                        })
+                        .map(|page| page_to_resource(doc, page)),
+                );
+            }
+        }
+        self.export_pages(&mut resources).await?;
+
+        for doc in docs.iter().filter(|doc| matches(doc.name.clone())) {
+            let pages = self.pages(&doc.id).await?;
+            resources.push(doc_to_resource(doc, &pages));
+        }
+        Ok(resources)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "Coda"
+    }
+
+    /// Lists docs and their tables, for the `doc_id` and `table_id`
+    /// filters.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        let docs = self.docs(None, usize::MAX).await?;
+        let mut items = Vec::new();
+        for batch in docs.chunks(DOC_CONCURRENCY) {
+            let fetched = future::join_all(batch.iter().map(|doc| self.tables(&doc.id))).await;
+            for (doc, tables) in batch.iter().zip(fetched) {
+                items.push(DiscoveredItem {
+                    id: doc.id.clone(),
+                    kind: "doc".to_string(),
+                    title: doc.name.clone(),
+                    parent_id: None,
+                    url: Some(doc.browser_link.clone()),
+                });
+                items.extend(tables?.into_iter().map(|table| DiscoveredItem {
+                    id: table.id,
+                    kind: "table".to_string(),
+                    title: table.name,
+                    parent_id: Some(doc.id.clone()),
+                    url: Some(table.browser_link),
+                }));
+            }
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,310 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::{StatusCode, Url};
+use serde_json::{json, Value};
+
+use super::{
+    percent_encode,
+    rest::{self, Fields},
+};
+use crate::{
+    domain::{DomainError, ListingPage, Query, Resource},
+    infrastructure::{
+        config::ElasticsearchConfig,
+        http::{self, HttpClient, RetryPolicy},
+    },
+    ports::{is_listing_filter, report_progress, ResourceProvider},
+};
+
+/// Hits read at once when paging through a listing.
+const PAGE_SIZE: usize = 100;
+
+/// Hits a search returns at most.
+const SEARCH_LIMIT: usize = 50;
+
+/// How deep `from` can page, the clusters' default `max_result_window`.
+const MAX_RESULT_WINDOW: usize = 10_000;
+
+/// An Elasticsearch or OpenSearch index served as a provider, as an
+/// `[elasticsearch.<name>]` entry describes it. Searches are the cluster's,
+/// so resources come back in its order of relevance.
+pub struct ElasticsearchAdapter {
+    name: &'static str,
+    http: HttpClient,
+    /// The index's `_search` endpoint.
+    search_url: String,
+    filter: Option<Value>,
+    sort: Option<Value>,
+    query: Option<Value>,
+    search_fields: Vec<String>,
+    fields: Fields,
+}
+
+impl ElasticsearchAdapter {
+    pub fn new(name: &'static str, config: &ElasticsearchConfig) -> Result<Self, DomainError> {
+        let url = Url::parse(config.url.trim()).map_err(|e| {
+            DomainError::InvalidQuery(format!(
+                "Invalid elasticsearch.{}.url {}: {}",
+                name, config.url, e
+            ))
+        })?;
+        let index = config.index.trim();
+        if index.is_empty() {
+            return Err(DomainError::InvalidQuery(format!(
+                "elasticsearch.{}.index is empty",
+                name
+            )));
+        }
+        let search_url = format!(
+            "{}/{}/_search",
+            url.as_str().trim_end_matches('/'),
+            index
+                .split(',')
+                .map(|index| percent_encode(index.trim()).replace("%2A", "*"))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        let (mut headers, mut secrets) =
+            rest::headers("elasticsearch", name, None, &config.headers)?;
+        if let Some(api_key) = &config.api_key {
+            let authorization = match api_key.split_once(':') {
+                Some((username, password)) => {
+                    secrets.push(password.to_string());
+                    format!(
+                        "Basic {}",
+                        BASE64.encode(format!("{}:{}", username, password))
+                    )
+                }
+                None => format!("ApiKey {}", api_key),
+            };
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&authorization)
+                    .map_err(|e| DomainError::InvalidQuery(e.to_string()))?,
+            );
+            secrets.push(api_key.clone());
+        }
+        let http = HttpClient::new(name, headers, secrets)?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self {
+            name,
+            http,
+            search_url,
+            filter: config.filter.clone(),
+            sort: config.sort.clone(),
+            query: config.query.clone(),
+            search_fields: config.search_fields.clone(),
+            fields: Fields::new("elasticsearch", name, "fields", &config.fields)?,
+        })
+    }
+
+    /// Clusters answer 429 when their queues fill, so the common defaults.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// The hits of `body` sent to the index's `_search` endpoint.
+    async fn hits(&self, body: &Value) -> Result<Vec<Resource>, DomainError> {
+        let response = self
+            .http
+            .send(self.http.post(&self.search_url).json(body))
+            .await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            let reason = serde_json::from_str::<Value>(&error_text)
+                .ok()
+                .and_then(|error| error["error"]["reason"].as_str().map(str::to_string))
+                .unwrap_or(error_text);
+            return Err(match status {
+                StatusCode::BAD_REQUEST => DomainError::InvalidQuery(format!(
+                    "{} rejected the query: {}",
+                    self.name, reason
+                )),
+                _ => DomainError::ProviderError(format!(
+                    "{} error ({}): {}",
+                    self.name, status, reason
+                )),
+            });
+        }
+
+        let data: Value = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(format!("{}: {}", self.name, e)))?;
+        Ok(data["hits"]["hits"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|hit| self.fields.to_resource(self.name, hit, request_id.clone()))
+            .collect())
+    }
+
+    /// `query` within the configured filter and `terms`.
+    fn restrict(&self, query: Value, terms: Vec<Value>) -> Value {
+        let filter: Vec<Value> = self.filter.iter().cloned().chain(terms).collect();
+        if filter.is_empty() {
+            return query;
+        }
+        json!({ "bool": { "must": [query], "filter": filter } })
+    }
+
+    /// The query of a search for `text`.
+    fn search_query(&self, text: &str) -> Value {
+        match &self.query {
+            Some(query) => fill(query, text),
+            None if self.search_fields.is_empty() => json!({ "multi_match": { "query": text } }),
+            None => json!({
+                "multi_match": { "query": text, "fields": self.search_fields }
+            }),
+        }
+    }
+
+    /// A `term` query for each of `query`'s filters. Listing filters,
+    /// such as `page_size`, aren't fields.
+    fn terms(query: &Query) -> Vec<Value> {
+        let mut filters: Vec<(&String, &String)> = query
+            .filters
+            .iter()
+            .filter(|(field, _)| !is_listing_filter(field))
+            .collect();
+        filters.sort();
+        filters
+            .into_iter()
+            .map(|(field, value)| json!({ "term": { field: value } }))
+            .collect()
+    }
+
+    fn page_size(query: &Query) -> Result<usize, DomainError> {
+        match query.filters.get("page_size") {
+            Some(size) => match size.trim().parse() {
+                Ok(size) if size > 0 => Ok(size),
+                _ => Err(DomainError::InvalidQuery(format!(
+                    "page_size must be a positive number, not {:?}",
+                    size
+                ))),
+            },
+            None => Ok(PAGE_SIZE),
+        }
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for ElasticsearchAdapter {
+    /// Lists the index's documents. Each filter keeps those whose field of
+    /// the same name has its value.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let mut resources = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.list_page(query, cursor).await?;
+            resources.extend(page.resources);
+            report_progress(|| format!("Fetched {} {} documents", resources.len(), self.name));
+            match page.next_cursor {
+                Some(next) if resources.len() < limit => cursor = Some(next),
+                _ => break,
+            }
+        }
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let document_id = id
+            .strip_prefix(self.name)
+            .and_then(|id| id.strip_prefix('_'))
+            .unwrap_or(id);
+        let query = self.restrict(json!({ "ids": { "values": [document_id] } }), Vec::new());
+        self.hits(&json!({ "query": query, "size": 1 }))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DomainError::ResourceNotFound(format!("{} has no {}", self.name, id)))
+    }
+
+    /// Searches the index with the configured query, most relevant first.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = self.restrict(self.search_query(query), Vec::new());
+        self.hits(&json!({ "query": query, "size": SEARCH_LIMIT }))
+            .await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Pages are `page_size` documents (100 by default); the cursor is the
+    /// offset of the next. Listings end `max_result_window` deep.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let offset: usize = match cursor {
+            Some(cursor) => cursor.parse().map_err(|_| {
+                DomainError::InvalidQuery(format!("Invalid {} cursor {:?}", self.name, cursor))
+            })?,
+            None => 0,
+        };
+        let mut page_size = Self::page_size(query)?;
+        if let Some(limit) = query.limit {
+            page_size = page_size.min(limit.max(1));
+        }
+        page_size = page_size.min(MAX_RESULT_WINDOW.saturating_sub(offset));
+        if page_size == 0 {
+            return Ok(ListingPage {
+                resources: Vec::new(),
+                next_cursor: None,
+            });
+        }
+
+        let mut body = json!({
+            "query": self.restrict(json!({ "match_all": {} }), Self::terms(query)),
+            "from": offset,
+            "size": page_size,
+        });
+        if let Some(sort) = &self.sort {
+            body["sort"] = sort.clone();
+        }
+        let resources = self.hits(&body).await?;
+        let next_cursor =
+            (resources.len() == page_size).then(|| (offset + resources.len()).to_string());
+        Ok(ListingPage {
+            resources,
+            next_cursor,
+        })
+    }
+}
+
+/// `query` with `{query}` in its strings replaced by `text`.
+fn fill(query: &Value, text: &str) -> Value {
+    match query {
+        Value::String(template) => Value::String(template.replace("{query}", text)),
+        Value::Array(items) => Value::Array(items.iter().map(|item| fill(item, text)).collect()),
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), fill(value, text)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
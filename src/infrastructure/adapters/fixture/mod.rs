@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use std::path::Path;
+
+use crate::{
+    domain::{DomainError, Query, Resource},
+    ports::ResourceProvider,
+};
+
+/// Serves resources recorded to a JSON file instead of calling a live API,
+/// so benchmarks and experiments can be replayed offline.
+pub struct FixtureAdapter {
+    resources: Vec<Resource>,
+}
+
+impl FixtureAdapter {
+    pub fn new(resources: Vec<Resource>) -> Self {
+        Self { resources }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, DomainError> {
+        Ok(Self::new(Self::load(path)?))
+    }
+
+    /// The resources recorded to `path`.
+    pub fn load(path: &Path) -> Result<Vec<Resource>, DomainError> {
+        let data = std::fs::read_to_string(path).map_err(|e| {
+            DomainError::ProviderError(format!("Failed to read fixtures {}: {}", path.display(), e))
+        })?;
+
+        serde_json::from_str(&data).map_err(|e| {
+            DomainError::ProviderError(format!("Invalid fixtures {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn save(path: &Path, resources: &[Resource]) -> Result<(), DomainError> {
+        let data = serde_json::to_string_pretty(resources)
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        std::fs::write(path, data).map_err(|e| {
+            DomainError::ProviderError(format!(
+                "Failed to write fixtures {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for FixtureAdapter {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(self.resources.len());
+        Ok(self.resources.iter().take(limit).cloned().collect())
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        self.resources
+            .iter()
+            .find(|resource| resource.id == id)
+            .cloned()
+            .ok_or_else(|| DomainError::ResourceNotFound(format!("Fixture not found: {}", id)))
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let needle = query.to_lowercase();
+        Ok(self
+            .resources
+            .iter()
+            .filter(|resource| {
+                resource.title.to_lowercase().contains(&needle)
+                    || resource.content.to_lowercase().contains(&needle)
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "Fixture"
+    }
+}
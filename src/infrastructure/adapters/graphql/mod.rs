@@ -0,0 +1,327 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use super::rest::{self, jsonpath::JsonPath, Fields};
+use crate::{
+    domain::{DomainError, ListingPage, Query, Resource},
+    infrastructure::{
+        config::{GraphqlConfig, GraphqlQuery},
+        http::{HttpClient, RetryPolicy},
+    },
+    ports::{report_progress, ResourceProvider},
+};
+
+/// Pages followed for one listing, however many more the API offers.
+const MAX_PAGES: usize = 100;
+
+/// Most items asked for at once, the most GraphQL APIs commonly allow.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Items looked through for one by ID, or for matches, when the API has
+/// no query to ask.
+const SCAN_LIMIT: usize = 1000;
+
+/// `$name` in a query.
+static VARIABLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$([A-Za-z_][A-Za-z0-9_]*)").unwrap());
+
+/// A query as configured, its JSONPaths parsed.
+struct Operation {
+    query: String,
+    /// The variables the query declares, the only ones sent.
+    declared: HashSet<String>,
+    variables: HashMap<String, Value>,
+    items: Option<JsonPath>,
+    next: Option<JsonPath>,
+    has_next: Option<JsonPath>,
+}
+
+impl Operation {
+    fn new(name: &str, operation: &str, config: &GraphqlQuery) -> Result<Self, DomainError> {
+        let path = |field: &str, path: &Option<String>| {
+            path.as_deref()
+                .map(|path| {
+                    rest::parse_path("graphql", name, &format!("{}.{}", operation, field), path)
+                })
+                .transpose()
+        };
+        Ok(Self {
+            query: config.query.clone(),
+            declared: VARIABLE
+                .captures_iter(&config.query)
+                .map(|captures| captures[1].to_string())
+                .collect(),
+            variables: config
+                .variables
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            items: path("items", &config.items)?,
+            next: path("next", &config.next)?,
+            has_next: path("has_next", &config.has_next)?,
+        })
+    }
+
+    /// Whether the query can be asked for the page after one, passing back
+    /// the cursor at `next` as `$cursor`.
+    fn pages(&self) -> bool {
+        self.declared.contains("cursor") && self.next.is_some()
+    }
+
+    /// The query's own variables and `vars`, which take precedence, those
+    /// it doesn't declare left out.
+    fn variables(&self, vars: &HashMap<String, Value>) -> HashMap<String, Value> {
+        self.variables
+            .iter()
+            .chain(vars)
+            .filter(|(name, _)| self.declared.contains(*name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// A GraphQL API served as a provider, as a `[graphql.<name>]` entry
+/// describes it: which queries list, fetch and search its items, and where
+/// in each item a resource's fields are.
+pub struct GraphqlAdapter {
+    name: &'static str,
+    http: HttpClient,
+    url: String,
+    list: Operation,
+    get: Option<Operation>,
+    search: Option<Operation>,
+    fields: Fields,
+}
+
+impl GraphqlAdapter {
+    pub fn new(name: &'static str, config: &GraphqlConfig) -> Result<Self, DomainError> {
+        let url = reqwest::Url::parse(config.url.trim()).map_err(|e| {
+            DomainError::InvalidQuery(format!(
+                "Invalid graphql.{}.url {}: {}",
+                name, config.url, e
+            ))
+        })?;
+        let (headers, secrets) =
+            rest::headers("graphql", name, config.token.as_deref(), &config.headers)?;
+        let http = HttpClient::new(name, headers, secrets)?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self {
+            name,
+            http,
+            url: url.into(),
+            list: Operation::new(name, "list", &config.list)?,
+            get: config
+                .get
+                .as_ref()
+                .map(|get| Operation::new(name, "get", get))
+                .transpose()?,
+            search: config
+                .search
+                .as_ref()
+                .map(|search| Operation::new(name, "search", search))
+                .transpose()?,
+            fields: Fields::new("graphql", name, "fields", &config.fields)?,
+        })
+    }
+
+    /// Nothing is known of the API's throttling, so the common defaults.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// Up to `limit` items `operation` returns with the variables `vars`,
+    /// following its pages, with the API's ID for the last request.
+    async fn items(
+        &self,
+        operation: &Operation,
+        vars: &HashMap<String, Value>,
+        limit: usize,
+    ) -> Result<(Vec<Value>, Option<String>), DomainError> {
+        let mut items = Vec::new();
+        let mut request_id = None;
+        let mut cursor = None;
+        for _ in 0..MAX_PAGES {
+            let page_size = (limit - items.len()).clamp(1, MAX_PAGE_SIZE);
+            let (page_items, id, next) =
+                self.page(operation, vars, page_size, cursor.take()).await?;
+            request_id = id.or(request_id);
+            items.extend(page_items);
+            report_progress(|| format!("Fetched {} {} items", items.len(), self.name));
+            match next {
+                Some(next) if items.len() < limit => cursor = Some(next),
+                _ => break,
+            }
+        }
+        items.truncate(limit);
+        Ok((items, request_id))
+    }
+
+    /// The page of `page_size` items `operation` returns with the variables
+    /// `vars` and `cursor`, if given, with the API's ID for the request and
+    /// the cursor of the next page, if there is one.
+    async fn page(
+        &self,
+        operation: &Operation,
+        vars: &HashMap<String, Value>,
+        page_size: usize,
+        cursor: Option<String>,
+    ) -> Result<(Vec<Value>, Option<String>, Option<String>), DomainError> {
+        let mut vars = vars.clone();
+        vars.insert("limit".to_string(), page_size.into());
+        if let Some(cursor) = cursor {
+            vars.insert("cursor".to_string(), cursor.into());
+        }
+
+        let (data, request_id): (Value, _) = self
+            .http
+            .graphql(
+                &self.url,
+                &operation.query,
+                Some(operation.variables(&vars)),
+            )
+            .await?;
+        let selected = match &operation.items {
+            Some(path) => path.select(&data),
+            None => vec![&data],
+        };
+        // A list selected alone, e.g. by `$.issues.nodes`, holds the items.
+        let items: Vec<Value> = match selected.as_slice() {
+            [Value::Array(array)] => array.clone(),
+            selected => selected.iter().map(|item| (*item).clone()).collect(),
+        };
+        if items.is_empty() || !operation.pages() {
+            return Ok((items, request_id, None));
+        }
+
+        let more = match &operation.has_next {
+            Some(path) => path.first(&data).and_then(Value::as_bool).unwrap_or(false),
+            None => true,
+        };
+        let next = operation
+            .next
+            .as_ref()
+            .and_then(|path| path.first(&data))
+            .map(rest::text)
+            .filter(|next| more && !next.is_empty());
+        Ok((items, request_id, next))
+    }
+
+    fn to_resources(&self, items: &[Value], request_id: Option<String>) -> Vec<Resource> {
+        items
+            .iter()
+            .filter_map(|item| self.fields.to_resource(self.name, item, request_id.clone()))
+            .collect()
+    }
+
+    /// The listing's variables: `--filter`s given, as strings.
+    fn vars(query: &Query) -> HashMap<String, Value> {
+        query
+            .filters
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for GraphqlAdapter {
+    /// Lists what the `list` query returns. Filters are passed as the
+    /// variables of the same name.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let (items, request_id) = self.items(&self.list, &Self::vars(query), limit).await?;
+        Ok(self.to_resources(&items, request_id))
+    }
+
+    /// Fetches the item with the `get` query, or finds it by listing.
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let item_id = id
+            .strip_prefix(self.name)
+            .and_then(|id| id.strip_prefix('_'))
+            .unwrap_or(id);
+        let not_found = || DomainError::ResourceNotFound(format!("{} has no {}", self.name, id));
+
+        let Some(get) = &self.get else {
+            let (items, request_id) = self.items(&self.list, &HashMap::new(), SCAN_LIMIT).await?;
+            return self
+                .to_resources(&items, request_id)
+                .into_iter()
+                .find(|resource| resource.id == format!("{}_{}", self.name, item_id))
+                .ok_or_else(not_found);
+        };
+        let vars = HashMap::from([("id".to_string(), Value::String(item_id.to_string()))]);
+        let (items, request_id) = self.items(get, &vars, 1).await?;
+        items
+            .first()
+            .and_then(|item| self.fields.to_resource(self.name, item, request_id))
+            .ok_or_else(not_found)
+    }
+
+    /// Asks the `search` query, or else lists items and keeps those whose
+    /// title or content contains every word of `query`.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        if let Some(search) = &self.search {
+            let vars =
+                HashMap::from([("query".to_string(), Value::String(query.trim().to_string()))]);
+            let (items, request_id) = self.items(search, &vars, 50).await?;
+            return Ok(self.to_resources(&items, request_id));
+        }
+
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        let (items, request_id) = self.items(&self.list, &HashMap::new(), SCAN_LIMIT).await?;
+        Ok(self
+            .to_resources(&items, request_id)
+            .into_iter()
+            .filter(|resource| {
+                let text = format!("{} {}", resource.title, resource.content).to_lowercase();
+                !words.is_empty() && words.iter().all(|word| text.contains(word))
+            })
+            .collect())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Pages are the `list` query's, asking for `page_size` items (100 at
+    /// most); cursors are the API's own. A query that takes `$limit` but
+    /// can't be paged fails on a full page rather than pass it off as
+    /// everything there is.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(MAX_PAGE_SIZE)
+            .min(MAX_PAGE_SIZE);
+        let (items, request_id, next_cursor) = self
+            .page(&self.list, &Self::vars(query), page_size, cursor)
+            .await?;
+        if !self.list.pages() && self.list.declared.contains("limit") && items.len() >= page_size {
+            return Err(DomainError::ProviderError(format!(
+                "{} lists more than {} items, but its list query can't be paged: declare \
+                 $cursor in it and set graphql.{}.list.next (and has_next) to the next \
+                 page's cursor, e.g. from pageInfo {{ endCursor hasNextPage }}",
+                self.name, page_size, self.name
+            )));
+        }
+        Ok(ListingPage {
+            resources: self.to_resources(&items, request_id),
+            next_cursor,
+        })
+    }
+}
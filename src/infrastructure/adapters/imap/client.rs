@@ -0,0 +1,561 @@
+use base64::{engine::general_purpose::STANDARD_NO_PAD as BASE64, Engine};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_native_tls::native_tls;
+
+use crate::{domain::DomainError, ports::charge_request};
+
+/// How long the server gets to answer each line before the session is
+/// given up on.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest literal accepted from the server, bounding what a message can
+/// make us hold in memory.
+const MAX_LITERAL_BYTES: usize = 50 * 1024 * 1024;
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// A logged-in IMAP4rev1 session. Only reads are sent: mailboxes are
+/// opened with `EXAMINE` and messages fetched with `BODY.PEEK`, so nothing
+/// is marked read.
+pub struct Session {
+    stream: BufReader<Box<dyn Io>>,
+    tag: u32,
+}
+
+/// An item of a server response.
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// Atoms and numbers, and section names such as `BODY[HEADER]`.
+    Atom(String),
+    /// Quoted strings and literals.
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Nil,
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<String> {
+        match self {
+            Value::Atom(atom) => Some(atom.clone()),
+            Value::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        }
+    }
+
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            Value::Atom(atom) => Some(atom.into_bytes()),
+            _ => None,
+        }
+    }
+}
+
+/// An argument of a command, sent as is, or quoted or as a literal as its
+/// contents need.
+#[derive(Clone, Copy)]
+pub enum Arg<'a> {
+    Atom(&'a str),
+    Text(&'a str),
+}
+
+/// What `EXAMINE` reports about a mailbox.
+#[derive(Debug, Clone, Copy)]
+pub struct Mailbox {
+    /// Changes when the server renumbers the mailbox's messages, making
+    /// UIDs from before it meaningless.
+    pub uid_validity: u64,
+}
+
+impl Session {
+    /// Connects to `host`, with TLS unless `tls` is unset, and logs in.
+    pub async fn connect(
+        host: &str,
+        port: u16,
+        tls: bool,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, DomainError> {
+        let tcp = tokio::time::timeout(READ_TIMEOUT, TcpStream::connect((host, port)))
+            .await
+            .map_err(|_| unavailable(format!("connecting to {}:{} timed out", host, port)))?
+            .map_err(|e| unavailable(format!("connecting to {}:{}: {}", host, port, e)))?;
+        let stream: Box<dyn Io> = if tls {
+            let connector = native_tls::TlsConnector::new().map_err(imap_error)?;
+            let connector = tokio_native_tls::TlsConnector::from(connector);
+            Box::new(
+                connector
+                    .connect(host, tcp)
+                    .await
+                    .map_err(|e| unavailable(format!("TLS with {}: {}", host, e)))?,
+            )
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut session = Self {
+            stream: BufReader::new(stream),
+            tag: 0,
+        };
+        let greeting = session.read_line().await?;
+        if !greeting.starts_with(b"* OK") && !greeting.starts_with(b"* PREAUTH") {
+            return Err(imap_error(format!(
+                "unexpected greeting: {}",
+                String::from_utf8_lossy(&greeting).trim_end()
+            )));
+        }
+        if !greeting.starts_with(b"* PREAUTH") {
+            session
+                .command(&[Arg::Atom("LOGIN"), Arg::Text(username), Arg::Text(password)])
+                .await
+                .map_err(|e| match e {
+                    DomainError::ProviderError(message) => {
+                        DomainError::ProviderError(format!("{} (logging in)", message))
+                    }
+                    e => e,
+                })?;
+        }
+        Ok(session)
+    }
+
+    /// Opens `mailbox` read-only.
+    pub async fn examine(&mut self, mailbox: &str) -> Result<Mailbox, DomainError> {
+        let encoded = encode_mailbox(mailbox);
+        let responses = self
+            .command(&[Arg::Atom("EXAMINE"), Arg::Text(&encoded)])
+            .await
+            .map_err(|e| match e {
+                DomainError::ProviderError(message) => {
+                    DomainError::ResourceNotFound(format!("IMAP mailbox {}: {}", mailbox, message))
+                }
+                e => e,
+            })?;
+        let uid_validity = responses
+            .iter()
+            .find_map(|response| {
+                let text = String::from_utf8_lossy(response);
+                let rest = text.split("[UIDVALIDITY ").nth(1)?;
+                rest.split(']').next()?.trim().parse().ok()
+            })
+            .unwrap_or(0);
+        Ok(Mailbox { uid_validity })
+    }
+
+    /// UIDs of the messages in the open mailbox matching `criteria`, e.g.
+    /// `[Atom("ALL")]`, in ascending order.
+    pub async fn search(&mut self, criteria: &[Arg<'_>]) -> Result<Vec<u32>, DomainError> {
+        let mut args = vec![Arg::Atom("UID"), Arg::Atom("SEARCH")];
+        if criteria
+            .iter()
+            .any(|arg| matches!(arg, Arg::Text(text) if !text.is_ascii()))
+        {
+            args.extend([Arg::Atom("CHARSET"), Arg::Atom("UTF-8")]);
+        }
+        args.extend(criteria.iter().copied());
+        let mut uids: Vec<u32> = self
+            .command(&args)
+            .await?
+            .iter()
+            .filter_map(|response| response.strip_prefix(b"* SEARCH"))
+            .flat_map(|numbers| {
+                String::from_utf8_lossy(numbers)
+                    .split_whitespace()
+                    .filter_map(|uid| uid.parse().ok())
+                    .collect::<Vec<u32>>()
+            })
+            .collect();
+        uids.sort_unstable();
+        Ok(uids)
+    }
+
+    /// `items`, e.g. `(UID FLAGS BODY.PEEK[])`, of the messages with `uids`
+    /// in the open mailbox, each as its list of names and values.
+    pub async fn fetch(
+        &mut self,
+        uids: &[u32],
+        items: &str,
+    ) -> Result<Vec<Vec<(String, Value)>>, DomainError> {
+        if uids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let set = uids
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let responses = self
+            .command(&[
+                Arg::Atom("UID"),
+                Arg::Atom("FETCH"),
+                Arg::Atom(&set),
+                Arg::Atom(items),
+            ])
+            .await?;
+
+        let mut messages = Vec::new();
+        for response in responses {
+            // * <n> FETCH (<name> <value> ...)
+            let mut values = parse(&response)?.into_iter().skip(2);
+            let (Some(Value::Atom(kind)), Some(Value::List(fields))) =
+                (values.next(), values.next())
+            else {
+                continue;
+            };
+            if !kind.eq_ignore_ascii_case("FETCH") {
+                continue;
+            }
+            let mut fields = fields.into_iter();
+            let mut message = Vec::new();
+            while let (Some(Value::Atom(name)), Some(value)) = (fields.next(), fields.next()) {
+                message.push((name.to_uppercase(), value));
+            }
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    /// Every mailbox that can be opened, by name, with its hierarchy
+    /// delimiter.
+    pub async fn list(&mut self) -> Result<Vec<(String, Option<String>)>, DomainError> {
+        let responses = self
+            .command(&[Arg::Atom("LIST"), Arg::Text(""), Arg::Text("*")])
+            .await?;
+        let mut mailboxes = Vec::new();
+        for response in responses {
+            let values = parse(&response)?;
+            // * LIST (<flags>) <delimiter> <name>
+            let [_, Value::Atom(kind), Value::List(flags), delimiter, name] = values.as_slice()
+            else {
+                continue;
+            };
+            let selectable = !flags.iter().any(
+                |flag| matches!(flag, Value::Atom(flag) if flag.eq_ignore_ascii_case("\\Noselect")),
+            );
+            if !kind.eq_ignore_ascii_case("LIST") || !selectable {
+                continue;
+            }
+            if let Some(name) = name.as_str() {
+                mailboxes.push((decode_mailbox(&name), delimiter.as_str()));
+            }
+        }
+        Ok(mailboxes)
+    }
+
+    pub async fn logout(mut self) {
+        let _ = self.command(&[Arg::Atom("LOGOUT")]).await;
+    }
+
+    /// Sends a command and reads its responses up to the tagged one,
+    /// returning the untagged ones, or an error for `NO` and `BAD`.
+    async fn command(&mut self, args: &[Arg<'_>]) -> Result<Vec<Vec<u8>>, DomainError> {
+        charge_request("IMAP")?;
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+
+        let mut line = tag.clone().into_bytes();
+        for arg in args {
+            line.push(b' ');
+            match arg {
+                Arg::Atom(atom) => line.extend_from_slice(atom.as_bytes()),
+                Arg::Text(text) if is_quotable(text) => {
+                    line.push(b'"');
+                    for byte in text.bytes() {
+                        if byte == b'"' || byte == b'\\' {
+                            line.push(b'\\');
+                        }
+                        line.push(byte);
+                    }
+                    line.push(b'"');
+                }
+                Arg::Text(text) => {
+                    // Literals wait for the server to ask for them.
+                    line.extend_from_slice(format!("{{{}}}\r\n", text.len()).as_bytes());
+                    self.write(&line).await?;
+                    line.clear();
+                    let reply = self.read_line().await?;
+                    if !reply.starts_with(b"+") {
+                        return Err(imap_error(String::from_utf8_lossy(&reply).trim_end()));
+                    }
+                    line.extend_from_slice(text.as_bytes());
+                }
+            }
+        }
+        line.extend_from_slice(b"\r\n");
+        self.write(&line).await?;
+
+        let mut responses = Vec::new();
+        loop {
+            let response = self.read_response().await?;
+            if let Some(status) = response.strip_prefix(format!("{} ", tag).as_bytes()) {
+                let status = String::from_utf8_lossy(status).trim_end().to_string();
+                if status.starts_with("OK") {
+                    return Ok(responses);
+                }
+                return Err(imap_error(status));
+            }
+            if response.starts_with(b"* BYE") && args.first().is_none_or(|arg| !is_logout(arg)) {
+                return Err(imap_error(String::from_utf8_lossy(&response).trim_end()));
+            }
+            responses.push(response);
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), DomainError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(bytes).await.map_err(unavailable)?;
+        stream.flush().await.map_err(unavailable)
+    }
+
+    /// One response: a line, and the literals it announces with the rest of
+    /// the line after each, as sent.
+    async fn read_response(&mut self) -> Result<Vec<u8>, DomainError> {
+        let mut response = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            let literal = literal_length(&line);
+            response.extend_from_slice(&line);
+            let Some(length) = literal else {
+                return Ok(response);
+            };
+            if length > MAX_LITERAL_BYTES {
+                return Err(imap_error(format!(
+                    "the server sent {} bytes at once, more than the {} allowed",
+                    length, MAX_LITERAL_BYTES
+                )));
+            }
+            let start = response.len();
+            response.resize(start + length, 0);
+            tokio::time::timeout(READ_TIMEOUT, self.stream.read_exact(&mut response[start..]))
+                .await
+                .map_err(|_| unavailable("the server stopped answering"))?
+                .map_err(unavailable)?;
+        }
+    }
+
+    async fn read_line(&mut self) -> Result<Vec<u8>, DomainError> {
+        let mut line = Vec::new();
+        let read = tokio::time::timeout(READ_TIMEOUT, self.stream.read_until(b'\n', &mut line))
+            .await
+            .map_err(|_| unavailable("the server stopped answering"))?
+            .map_err(unavailable)?;
+        if read == 0 {
+            return Err(unavailable("the server closed the connection"));
+        }
+        Ok(line)
+    }
+}
+
+fn is_logout(arg: &Arg) -> bool {
+    matches!(arg, Arg::Atom(atom) if atom.eq_ignore_ascii_case("LOGOUT"))
+}
+
+fn is_quotable(text: &str) -> bool {
+    text.is_ascii() && !text.contains(['\r', '\n', '\0'])
+}
+
+/// The length of the literal a line ends by announcing, `{<n>}\r\n`.
+fn literal_length(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"\r\n").or(line.strip_suffix(b"\n"))?;
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|byte| *byte == b'{')?;
+    std::str::from_utf8(&line[start + 1..]).ok()?.parse().ok()
+}
+
+/// The items of a response, literals included.
+pub fn parse(response: &[u8]) -> Result<Vec<Value>, DomainError> {
+    let mut parser = Parser {
+        input: response,
+        at: 0,
+    };
+    let mut values = Vec::new();
+    while let Some(value) = parser.value()? {
+        values.push(value);
+    }
+    Ok(values)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Option<Value>, DomainError> {
+        while self
+            .input
+            .get(self.at)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.at += 1;
+        }
+        let Some(&byte) = self.input.get(self.at) else {
+            return Ok(None);
+        };
+        match byte {
+            b'(' => {
+                self.at += 1;
+                let mut items = Vec::new();
+                loop {
+                    while self
+                        .input
+                        .get(self.at)
+                        .is_some_and(|byte| byte.is_ascii_whitespace())
+                    {
+                        self.at += 1;
+                    }
+                    match self.input.get(self.at) {
+                        Some(b')') => {
+                            self.at += 1;
+                            return Ok(Some(Value::List(items)));
+                        }
+                        None => return Err(imap_error("unterminated list in response")),
+                        _ => items.extend(self.value()?),
+                    }
+                }
+            }
+            b')' => Err(imap_error("unexpected ) in response")),
+            b'"' => {
+                self.at += 1;
+                let mut text = Vec::new();
+                loop {
+                    match self.input.get(self.at) {
+                        Some(b'"') => {
+                            self.at += 1;
+                            return Ok(Some(Value::Bytes(text)));
+                        }
+                        Some(b'\\') => {
+                            text.extend(self.input.get(self.at + 1));
+                            self.at += 2;
+                        }
+                        Some(&byte) => {
+                            text.push(byte);
+                            self.at += 1;
+                        }
+                        None => return Err(imap_error("unterminated string in response")),
+                    }
+                }
+            }
+            b'{' => {
+                let close = self.input[self.at..]
+                    .iter()
+                    .position(|byte| *byte == b'}')
+                    .map(|offset| self.at + offset)
+                    .ok_or_else(|| imap_error("unterminated literal in response"))?;
+                let length: usize = std::str::from_utf8(&self.input[self.at + 1..close])
+                    .ok()
+                    .and_then(|length| length.parse().ok())
+                    .ok_or_else(|| imap_error("invalid literal in response"))?;
+                let mut start = close + 1;
+                if self.input.get(start) == Some(&b'\r') {
+                    start += 1;
+                }
+                if self.input.get(start) == Some(&b'\n') {
+                    start += 1;
+                }
+                let end = start + length;
+                let bytes = self
+                    .input
+                    .get(start..end)
+                    .ok_or_else(|| imap_error("truncated literal in response"))?;
+                self.at = end;
+                Ok(Some(Value::Bytes(bytes.to_vec())))
+            }
+            _ => {
+                // Atoms run to a space or parenthesis, except within the
+                // brackets of section names like `BODY[HEADER.FIELDS (FROM)]`.
+                let start = self.at;
+                let mut depth = 0;
+                while let Some(&byte) = self.input.get(self.at) {
+                    match byte {
+                        b'[' => depth += 1,
+                        b']' => depth -= 1,
+                        b' ' | b'(' | b')' | b'\r' | b'\n' if depth == 0 => break,
+                        _ => {}
+                    }
+                    self.at += 1;
+                }
+                let atom = String::from_utf8_lossy(&self.input[start..self.at]).into_owned();
+                Ok(Some(if atom.eq_ignore_ascii_case("NIL") {
+                    Value::Nil
+                } else {
+                    Value::Atom(atom)
+                }))
+            }
+        }
+    }
+}
+
+/// A mailbox name as IMAP writes it, in modified UTF-7: non-ASCII runs are
+/// base64 of their UTF-16 between `&` and `-`, with `,` for `/`.
+pub fn encode_mailbox(name: &str) -> String {
+    let mut encoded = String::new();
+    let mut run: Vec<u16> = Vec::new();
+    let flush = |run: &mut Vec<u16>, encoded: &mut String| {
+        if run.is_empty() {
+            return;
+        }
+        let bytes: Vec<u8> = run.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+        encoded.push('&');
+        encoded.push_str(&BASE64.encode(bytes).replace('/', ","));
+        encoded.push('-');
+        run.clear();
+    };
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush(&mut run, &mut encoded);
+            match c {
+                '&' => encoded.push_str("&-"),
+                c => encoded.push(c),
+            }
+        } else {
+            run.extend(c.encode_utf16(&mut [0; 2]).iter());
+        }
+    }
+    flush(&mut run, &mut encoded);
+    encoded
+}
+
+/// The reverse of `encode_mailbox`. Names that aren't valid modified
+/// UTF-7 are returned as they are.
+pub fn decode_mailbox(name: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('-').map(|end| start + end) else {
+            return name.to_string();
+        };
+        let run = &rest[start + 1..end];
+        if run.is_empty() {
+            decoded.push('&');
+        } else {
+            let Ok(bytes) = BASE64.decode(run.replace(',', "/")) else {
+                return name.to_string();
+            };
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            decoded.push_str(&String::from_utf16_lossy(&units));
+        }
+        rest = &rest[end + 1..];
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn imap_error(error: impl std::fmt::Display) -> DomainError {
+    DomainError::ProviderError(format!("IMAP error: {}", error))
+}
+
+/// A failure reaching the server, which may pass.
+fn unavailable(error: impl std::fmt::Display) -> DomainError {
+    DomainError::Unavailable {
+        provider: "imap".to_string(),
+        message: error.to_string(),
+    }
+}
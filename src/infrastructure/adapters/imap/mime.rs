@@ -0,0 +1,600 @@
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Base64 as mail is written, with padding or without and any bits left
+/// over ignored.
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+/// A message's headers, unfolded, by lowercase name in the order given.
+#[derive(Debug, Default)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    pub fn parse(raw: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(raw);
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in text.lines() {
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+        Self(headers)
+    }
+
+    /// The first `name` header as written.
+    pub fn raw(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The first `name` header with its encoded words decoded.
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.raw(name).map(decode_words)
+    }
+
+    /// The value of a header such as `Content-Type`, lowercased, and its
+    /// parameters by lowercase name.
+    fn with_params(&self, name: &str) -> Option<(String, BTreeMap<String, String>)> {
+        let raw = self.raw(name)?;
+        let mut parts = split_outside_quotes(raw, ';').into_iter();
+        let value = parts.next()?.trim().to_lowercase();
+        Some((value, params(parts)))
+    }
+}
+
+/// A mailbox in an address header.
+#[derive(Debug, Clone)]
+pub struct Address {
+    pub name: Option<String>,
+    pub email: String,
+}
+
+impl Address {
+    /// `Name <email>`, or the address alone.
+    pub fn display(&self) -> String {
+        match &self.name {
+            // Quoted when it would otherwise read as several addresses.
+            Some(name) if name.contains([',', ';']) => format!("\"{}\" <{}>", name, self.email),
+            Some(name) => format!("{} <{}>", name, self.email),
+            None => self.email.clone(),
+        }
+    }
+}
+
+/// The addresses in a header such as `From` or `To`.
+pub fn addresses(raw: &str) -> Vec<Address> {
+    split_outside_quotes(raw, ',')
+        .into_iter()
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            // Groups (`team: a@b, c@d;`) are flattened.
+            let entry = entry
+                .split_once(':')
+                .filter(|(name, _)| !name.contains(['<', '"', '@']))
+                .map_or(entry, |(_, rest)| rest)
+                .trim()
+                .trim_end_matches(';');
+            match (entry.rfind('<'), entry.rfind('>')) {
+                (Some(start), Some(end)) if start < end => {
+                    let name = decode_words(entry[..start].trim().trim_matches('"').trim());
+                    Some(Address {
+                        name: (!name.is_empty()).then_some(name),
+                        email: entry[start + 1..end].trim().to_string(),
+                    })
+                }
+                _ if entry.contains('@') => Some(Address {
+                    name: None,
+                    email: entry.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// A file attached to a message.
+#[derive(Debug, Clone)]
+pub struct AttachedFile {
+    pub name: String,
+    pub mime_type: String,
+    /// The part's IMAP section number, e.g. `2` or `1.3`.
+    pub section: String,
+}
+
+/// A parsed message: its headers, its body as plain text, and the files
+/// attached to it.
+#[derive(Debug)]
+pub struct Message {
+    pub headers: Headers,
+    pub text: String,
+    pub attachments: Vec<AttachedFile>,
+}
+
+impl Message {
+    pub fn parse(raw: &[u8]) -> Self {
+        let entity = Entity::parse(raw, String::new());
+        let mut attachments = Vec::new();
+        entity.collect_attachments(&mut attachments);
+        Self {
+            text: entity.text().unwrap_or_default().trim().to_string(),
+            attachments,
+            headers: entity.headers,
+        }
+    }
+}
+
+/// The decoded contents of the part of `raw` numbered `section`, with its
+/// MIME type.
+pub fn part(raw: &[u8], section: &str) -> Option<(String, Vec<u8>)> {
+    let entity = Entity::parse(raw, String::new());
+    let part = entity.find(section)?;
+    Some((part.content_type().0, part.decoded()))
+}
+
+/// One part of a MIME message, or the message itself.
+struct Entity<'a> {
+    headers: Headers,
+    body: &'a [u8],
+    section: String,
+    children: Vec<Entity<'a>>,
+}
+
+impl<'a> Entity<'a> {
+    /// Parses `raw` as the part numbered `section`, the empty string for
+    /// the whole message.
+    fn parse(raw: &'a [u8], section: String) -> Self {
+        let (header, body) = split_header(raw);
+        let headers = Headers::parse(header);
+        let mut entity = Self {
+            headers,
+            body,
+            section,
+            children: Vec::new(),
+        };
+        let (content_type, params) = entity.content_type();
+        match params.get("boundary") {
+            Some(boundary) if content_type.starts_with("multipart/") => {
+                entity.children = split_multipart(body, boundary)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, part)| {
+                        let section = match entity.section.as_str() {
+                            "" => (index + 1).to_string(),
+                            parent => format!("{}.{}", parent, index + 1),
+                        };
+                        Entity::parse(part, section)
+                    })
+                    .collect();
+            }
+            // A message that isn't multipart is its own part 1.
+            _ if entity.section.is_empty() => entity.section = "1".to_string(),
+            _ => {}
+        }
+        entity
+    }
+
+    fn content_type(&self) -> (String, BTreeMap<String, String>) {
+        self.headers
+            .with_params("content-type")
+            .unwrap_or_else(|| ("text/plain".to_string(), BTreeMap::new()))
+    }
+
+    /// The file name the part gives, if any.
+    fn file_name(&self) -> Option<String> {
+        let disposition = self.headers.with_params("content-disposition");
+        disposition
+            .as_ref()
+            .and_then(|(_, params)| params.get("filename").cloned())
+            .or_else(|| self.content_type().1.get("name").cloned())
+            .filter(|name| !name.trim().is_empty())
+    }
+
+    fn is_attachment(&self) -> bool {
+        let disposition = self.headers.with_params("content-disposition");
+        disposition.is_some_and(|(value, _)| value == "attachment") || self.file_name().is_some()
+    }
+
+    /// The body with its transfer encoding undone.
+    fn decoded(&self) -> Vec<u8> {
+        let encoding = self
+            .headers
+            .raw("content-transfer-encoding")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        match encoding.as_str() {
+            "base64" => {
+                let data: Vec<u8> = self
+                    .body
+                    .iter()
+                    .copied()
+                    .filter(|byte| !byte.is_ascii_whitespace())
+                    .collect();
+                LENIENT_BASE64.decode(data).unwrap_or_default()
+            }
+            "quoted-printable" => decode_quoted_printable(self.body),
+            _ => self.body.to_vec(),
+        }
+    }
+
+    /// The readable text of the part: plain text parts as they are, HTML
+    /// without its markup, and of alternatives the plain text one.
+    fn text(&self) -> Option<String> {
+        let (content_type, params) = self.content_type();
+        if content_type == "multipart/alternative" {
+            return self
+                .children
+                .iter()
+                .find(|child| child.content_type().0 == "text/plain" && !child.is_attachment())
+                .or_else(|| self.children.iter().find(|child| child.text().is_some()))
+                .and_then(Entity::text);
+        }
+        if content_type.starts_with("multipart/") {
+            let texts: Vec<String> = self
+                .children
+                .iter()
+                .filter(|child| !child.is_attachment())
+                .filter_map(Entity::text)
+                .filter(|text| !text.trim().is_empty())
+                .collect();
+            return (!texts.is_empty()).then(|| texts.join("\n\n"));
+        }
+        if self.is_attachment() {
+            return None;
+        }
+        let charset = params.get("charset").map(String::as_str);
+        match content_type.as_str() {
+            "text/plain" => Some(decode_charset(&self.decoded(), charset)),
+            "text/html" => Some(html_to_text(&decode_charset(&self.decoded(), charset))),
+            _ => None,
+        }
+    }
+
+    fn collect_attachments(&self, attachments: &mut Vec<AttachedFile>) {
+        if !self.children.is_empty() {
+            for child in &self.children {
+                child.collect_attachments(attachments);
+            }
+            return;
+        }
+        let content_type = self.content_type().0;
+        let readable = content_type == "text/plain" || content_type == "text/html";
+        if !self.is_attachment() && (readable || content_type.starts_with("multipart/")) {
+            return;
+        }
+        let name = self.file_name().unwrap_or_else(|| {
+            if content_type == "message/rfc822" {
+                format!("message-{}.eml", self.section)
+            } else {
+                format!("part-{}", self.section)
+            }
+        });
+        attachments.push(AttachedFile {
+            name,
+            mime_type: content_type,
+            section: self.section.clone(),
+        });
+    }
+
+    fn find(&self, section: &str) -> Option<&Entity<'a>> {
+        if self.section == section {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(section))
+    }
+}
+
+/// The header and body of a message or part, split at the first empty line.
+fn split_header(raw: &[u8]) -> (&[u8], &[u8]) {
+    if raw.starts_with(b"\r\n") {
+        return (&[], &raw[2..]);
+    }
+    if raw.starts_with(b"\n") {
+        return (&[], &raw[1..]);
+    }
+    let crlf = raw.windows(4).position(|window| window == b"\r\n\r\n");
+    let lf = raw.windows(2).position(|window| window == b"\n\n");
+    match (crlf, lf) {
+        (Some(crlf), Some(lf)) if lf < crlf => (&raw[..lf], &raw[lf + 2..]),
+        (Some(crlf), _) => (&raw[..crlf], &raw[crlf + 4..]),
+        (None, Some(lf)) => (&raw[..lf], &raw[lf + 2..]),
+        (None, None) => (raw, &[]),
+    }
+}
+
+/// The parts of a multipart body between its `--<boundary>` lines.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut at = 0;
+    while at < body.len() {
+        let end = body[at..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(body.len(), |offset| at + offset + 1);
+        let line = body[at..end].trim_ascii_end();
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            if rest.is_empty() || rest == b"--" {
+                if let Some(start) = start {
+                    // The line break before a delimiter belongs to it.
+                    let part = &body[start..at];
+                    let part = part
+                        .strip_suffix(b"\r\n")
+                        .or(part.strip_suffix(b"\n"))
+                        .unwrap_or(part);
+                    parts.push(part);
+                }
+                if rest == b"--" {
+                    return parts;
+                }
+                start = Some(end);
+            }
+        }
+        at = end;
+    }
+    // A message cut short keeps what there is of its last part.
+    if let Some(start) = start.filter(|start| *start < body.len()) {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+/// Splits `text` at `separator`s that aren't within quotes, angle brackets
+/// or comments.
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut quoted, mut depth, mut escaped) = (false, 0, false);
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' | '(' if !quoted => depth += 1,
+            '>' | ')' if !quoted && depth > 0 => depth -= 1,
+            c if c == separator && !quoted && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Parameters such as `charset="utf-8"`, by lowercase name, with RFC 2231
+/// continuations (`name*0=`) joined and encoded values (`name*=utf-8''`)
+/// decoded.
+fn params<'a>(parts: impl Iterator<Item = &'a str>) -> BTreeMap<String, String> {
+    let mut plain = BTreeMap::new();
+    // Pieces of each extended parameter, by section number, and whether
+    // they are percent-encoded.
+    let mut extended: BTreeMap<String, BTreeMap<u32, (String, bool)>> = BTreeMap::new();
+    for part in parts {
+        let Some((name, value)) = part.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        let value = value.trim();
+        let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+            None => value.to_string(),
+        };
+        let (name, encoded) = match name.strip_suffix('*') {
+            Some(name) => (name.to_string(), true),
+            None => (name, false),
+        };
+        match name.split_once('*') {
+            Some((base, index)) => {
+                if let Ok(index) = index.parse() {
+                    extended
+                        .entry(base.to_string())
+                        .or_default()
+                        .insert(index, (value, encoded));
+                }
+            }
+            None if encoded => {
+                extended.entry(name).or_default().insert(0, (value, true));
+            }
+            None => {
+                plain.insert(name, decode_words(&value));
+            }
+        }
+    }
+
+    for (name, pieces) in extended {
+        let mut charset = None;
+        let mut bytes = Vec::new();
+        for (index, (value, encoded)) in pieces {
+            if !encoded {
+                bytes.extend_from_slice(value.as_bytes());
+                continue;
+            }
+            let mut value = value.as_str();
+            // The first encoded piece starts `<charset>'<language>'`.
+            if index == 0 {
+                if let Some((set, rest)) = value.split_once('\'') {
+                    charset = Some(set.to_string());
+                    value = rest.split_once('\'').map_or(rest, |(_, rest)| rest);
+                }
+            }
+            bytes.extend(decode_percent(value));
+        }
+        plain.insert(name, decode_charset(&bytes, charset.as_deref()));
+    }
+    plain
+}
+
+pub fn decode_percent(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], hex_byte(bytes.get(i + 1..i + 3))) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        if body[i] != b'=' {
+            decoded.push(body[i]);
+            i += 1;
+        } else if body[i + 1..].starts_with(b"\r\n") {
+            i += 3;
+        } else if body[i + 1..].starts_with(b"\n") {
+            i += 2;
+        } else if let Some(byte) = hex_byte(body.get(i + 1..i + 3)) {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(b'=');
+            i += 1;
+        }
+    }
+    decoded
+}
+
+fn hex_byte(digits: Option<&[u8]>) -> Option<u8> {
+    u8::from_str_radix(std::str::from_utf8(digits?).ok()?, 16).ok()
+}
+
+/// `bytes` as text in `charset`, UTF-8 if it's unknown.
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    match charset.and_then(|charset| encoding_rs::Encoding::for_label(charset.trim().as_bytes())) {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decodes the encoded words (`=?utf-8?B?...?=`) in a header. Words next to
+/// each other are joined, as the whitespace between them doesn't count and
+/// a character may be split across them.
+pub fn decode_words(text: &str) -> String {
+    static WORD: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"=\?([^?\s]+)\?([BbQq])\?([^?\s]*)\?=").unwrap());
+
+    let mut decoded = String::new();
+    // The bytes of the adjacent words decoded so far, in their charset.
+    let mut pending: Option<(String, Vec<u8>)> = None;
+    let mut last = 0;
+    for word in WORD.captures_iter(text) {
+        let whole = word.get(0).unwrap();
+        let gap = &text[last..whole.start()];
+        if !(pending.is_some() && gap.trim().is_empty()) {
+            if let Some((charset, bytes)) = pending.take() {
+                decoded.push_str(&decode_charset(&bytes, Some(&charset)));
+            }
+            decoded.push_str(gap);
+        }
+        last = whole.end();
+
+        // `utf-8*en` names a language too.
+        let charset = word[1].split('*').next().unwrap_or_default().to_lowercase();
+        let bytes = if word[2].eq_ignore_ascii_case("b") {
+            LENIENT_BASE64.decode(&word[3]).unwrap_or_default()
+        } else {
+            decode_quoted_printable(word[3].replace('_', " ").as_bytes())
+        };
+        match &mut pending {
+            Some((pending_charset, pending_bytes)) if *pending_charset == charset => {
+                pending_bytes.extend(bytes)
+            }
+            _ => {
+                if let Some((charset, bytes)) = pending.take() {
+                    decoded.push_str(&decode_charset(&bytes, Some(&charset)));
+                }
+                pending = Some((charset, bytes));
+            }
+        }
+    }
+    if let Some((charset, bytes)) = pending {
+        decoded.push_str(&decode_charset(&bytes, Some(&charset)));
+    }
+    decoded.push_str(&text[last..]);
+    decoded
+}
+
+/// The text of an HTML body, with block elements on lines of their own.
+fn html_to_text(html: &str) -> String {
+    static HIDDEN: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?is)<!--.*?-->|<(?:script|style|head)\b.*?</(?:script|style|head)\s*>")
+            .unwrap()
+    });
+    static SPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+    static BREAK: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)<br\s*/?>|</?(?:p|div|tr|h[1-6]|blockquote|pre|table|ul|ol)\b[^>]*>")
+            .unwrap()
+    });
+    static ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<li\b[^>]*>").unwrap());
+    static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+    static ENTITY: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap());
+
+    let text = HIDDEN.replace_all(html, "");
+    let text = SPACE.replace_all(&text, " ");
+    let text = BREAK.replace_all(&text, "\n");
+    let text = ITEM.replace_all(&text, "\n- ");
+    let text = TAG.replace_all(&text, "");
+    let text = ENTITY.replace_all(&text, |entity: &regex::Captures| {
+        let name = &entity[1];
+        let code = match name.strip_prefix('#') {
+            Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok(),
+            Some(decimal) => decimal.parse().ok(),
+            None => None,
+        };
+        if let Some(c) = code.and_then(char::from_u32) {
+            return c.to_string();
+        }
+        match name {
+            "nbsp" => " ",
+            "amp" => "&",
+            "lt" => "<",
+            "gt" => ">",
+            "quot" => "\"",
+            "apos" => "'",
+            _ => return entity[0].to_string(),
+        }
+        .to_string()
+    });
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        // One blank line at most between paragraphs.
+        if !line.is_empty() || lines.last().is_some_and(|last| !last.is_empty()) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
@@ -0,0 +1,717 @@
+mod client;
+mod mime;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use super::percent_encode;
+use crate::{
+    domain::{
+        Attachment, Blob, DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource,
+        ResourceSource,
+    },
+    infrastructure::sandbox,
+    ports::{report_progress, ResourceProvider},
+};
+use client::{Arg, Session, Value};
+use mime::{Address, Message};
+
+const IMAP_API_VERSION: &str = "IMAP4rev1";
+const DEFAULT_MAILBOX: &str = "INBOX";
+
+/// Messages larger than this are listed by their headers alone; fetching
+/// one by ID still reads it whole.
+const MAX_LISTED_MESSAGE_BYTES: u64 = 1024 * 1024;
+
+/// Newest matches kept from each mailbox searched.
+const SEARCH_LIMIT: usize = 20;
+
+/// Messages listed a page, unless `page_size` says.
+const PAGE_SIZE: usize = 50;
+
+/// What is fetched of each message listed: everything but its body.
+const LIST_ITEMS: &str = "(UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[HEADER])";
+
+/// What is fetched of a message read whole.
+const MESSAGE_ITEMS: &str = "(UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[])";
+
+/// A message's place on the server. UIDs only mean something within a
+/// mailbox's UID validity, so resource IDs carry all three.
+#[derive(Debug, Clone, PartialEq)]
+struct Location {
+    mailbox: String,
+    uid_validity: u64,
+    uid: u32,
+}
+
+impl Location {
+    /// `imap_<uid validity>_<uid>_<mailbox>`, the mailbox percent-encoded.
+    fn id(&self) -> String {
+        format!(
+            "imap_{}_{}_{}",
+            self.uid_validity,
+            self.uid,
+            percent_encode(&self.mailbox)
+        )
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        let mut parts = id.strip_prefix("imap_").unwrap_or(id).splitn(3, '_');
+        let uid_validity = parts.next()?.parse().ok()?;
+        let uid = parts.next()?.parse().ok()?;
+        let mailbox = String::from_utf8(mime::decode_percent(parts.next()?)).ok()?;
+        Some(Self {
+            mailbox,
+            uid_validity,
+            uid,
+        })
+    }
+
+    /// The message's IMAP URL (RFC 5092), e.g.
+    /// `imaps://mail.example.com/INBOX;UIDVALIDITY=385759045/;UID=20`.
+    fn url(&self, server: &Url) -> String {
+        format!(
+            "{}://{}/{};UIDVALIDITY={}/;UID={}",
+            server.scheme(),
+            server.authority(),
+            percent_encode(&self.mailbox).replace("%2F", "/"),
+            self.uid_validity,
+            self.uid
+        )
+    }
+
+    /// The location and section of the part an attachment URL made by
+    /// `url` names.
+    fn from_url(url: &str) -> Option<(Self, String)> {
+        let (_, path) = url.split_once("://")?.1.split_once('/')?;
+        let (mailbox, rest) = path.split_once(";UIDVALIDITY=")?;
+        let (uid_validity, rest) = rest.split_once("/;UID=")?;
+        let (uid, section) = rest.split_once("/;SECTION=")?;
+        let location = Self {
+            mailbox: String::from_utf8(mime::decode_percent(mailbox)).ok()?,
+            uid_validity: uid_validity.parse().ok()?,
+            uid: uid.parse().ok()?,
+        };
+        Some((location, section.to_string()))
+    }
+}
+
+/// Messages in the mailboxes of an IMAP account, read-only. Each request
+/// logs in afresh, so nothing is held open between them.
+pub struct ImapAdapter {
+    server: Url,
+    host: String,
+    port: u16,
+    tls: bool,
+    username: String,
+    password: String,
+    mailboxes: Vec<String>,
+}
+
+impl ImapAdapter {
+    /// `url` is the server, e.g. `imaps://imap.example.com`, `api_key` is
+    /// `<username>:<password>` (an app password with most providers), and
+    /// `mailboxes` those listed, the inbox if none.
+    pub fn new(url: &str, api_key: String, mailboxes: Vec<String>) -> Result<Self, DomainError> {
+        let url = url.trim();
+        let url = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("imaps://{}", url)
+        };
+        let server = Url::parse(&url)
+            .map_err(|e| DomainError::InvalidQuery(format!("Invalid IMAP url {}: {}", url, e)))?;
+        let host = server
+            .host_str()
+            .ok_or_else(|| DomainError::InvalidQuery(format!("IMAP url {} has no host", url)))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let tls = match server.scheme() {
+            "imaps" => true,
+            // Local bridges such as Proton Mail Bridge's speak plain IMAP;
+            // anything further away must use TLS.
+            "imap" if is_loopback(&host) => false,
+            "imap" => {
+                return Err(DomainError::InvalidQuery(format!(
+                    "IMAP without TLS is only allowed to this machine; use imaps://{}",
+                    host
+                )))
+            }
+            scheme => {
+                return Err(DomainError::InvalidQuery(format!(
+                    "Unsupported IMAP url scheme: {} (expected imaps)",
+                    scheme
+                )))
+            }
+        };
+        let port = server.port().unwrap_or(if tls { 993 } else { 143 });
+
+        let Some((username, password)) = api_key.split_once(':') else {
+            return Err(DomainError::InvalidQuery(
+                "IMAP credentials must be <username>:<password>".to_string(),
+            ));
+        };
+        let mailboxes = if mailboxes.is_empty() {
+            vec![DEFAULT_MAILBOX.to_string()]
+        } else {
+            mailboxes
+        };
+
+        Ok(Self {
+            server,
+            host,
+            port,
+            tls,
+            username: username.to_string(),
+            password: password.to_string(),
+            mailboxes,
+        })
+    }
+
+    async fn connect(&self) -> Result<Session, DomainError> {
+        sandbox::check_url(&self.server)?;
+        Session::connect(
+            &self.host,
+            self.port,
+            self.tls,
+            &self.username,
+            &self.password,
+        )
+        .await
+    }
+
+    /// The mailboxes the `mailbox` filter names, comma-separated, else
+    /// those configured.
+    fn mailboxes(&self, query: &Query) -> Vec<String> {
+        match query.filters.get("mailbox") {
+            Some(mailboxes) => mailboxes
+                .split(',')
+                .map(str::trim)
+                .filter(|mailbox| !mailbox.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => self.mailboxes.clone(),
+        }
+    }
+
+    /// The newest `limit` messages in each of `mailboxes` matching
+    /// `criteria`, newest first.
+    async fn list(
+        &self,
+        session: &mut Session,
+        mailboxes: &[String],
+        criteria: &[Arg<'_>],
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::new();
+        for mailbox in mailboxes {
+            let uid_validity = session.examine(mailbox).await?.uid_validity;
+            let uids = session.search(criteria).await?;
+            let newest = &uids[uids.len().saturating_sub(limit)..];
+            resources.extend(
+                self.messages(session, mailbox, uid_validity, newest)
+                    .await?,
+            );
+            report_progress(|| format!("Fetched {} IMAP messages", resources.len()));
+        }
+        resources.sort_by_key(|resource| std::cmp::Reverse(resource.updated_at));
+        Ok(resources)
+    }
+
+    /// Up to `page_size` messages in `mailbox` matching `criteria`, in
+    /// order of UID from the one after `after`, with the last one's UID if
+    /// there are more. `uid_validity` is the mailbox's when the listing
+    /// started, if it has: UIDs from before a renumbering mean nothing.
+    async fn list_after(
+        &self,
+        session: &mut Session,
+        mailbox: &str,
+        uid_validity: Option<u64>,
+        criteria: &[Arg<'_>],
+        after: u32,
+        page_size: usize,
+    ) -> Result<(Vec<Resource>, Option<u32>, u64), DomainError> {
+        let current = session.examine(mailbox).await?.uid_validity;
+        if uid_validity.is_some_and(|uid_validity| uid_validity != current) {
+            return Err(DomainError::ProviderError(format!(
+                "IMAP mailbox {} was renumbered while being listed",
+                mailbox
+            )));
+        }
+
+        let uids = session.search(criteria).await?;
+        let mut page: Vec<u32> = uids
+            .into_iter()
+            .filter(|uid| *uid > after)
+            .take(page_size + 1)
+            .collect();
+        let more = page.len() > page_size;
+        page.truncate(page_size);
+        let next = page.last().copied().filter(|_| more);
+        let resources = self.messages(session, mailbox, current, &page).await?;
+        Ok((resources, next, current))
+    }
+
+    /// The messages with `uids` in `mailbox`, open in `session`, whole if
+    /// small enough and else by their headers.
+    async fn messages(
+        &self,
+        session: &mut Session,
+        mailbox: &str,
+        uid_validity: u64,
+        uids: &[u32],
+    ) -> Result<Vec<Resource>, DomainError> {
+        // Headers and sizes first, then the bodies of those small enough
+        // to list whole.
+        let mut messages = session.fetch(uids, LIST_ITEMS).await?;
+        let small: Vec<u32> = messages
+            .iter()
+            .filter(|fields| {
+                number(fields, "RFC822.SIZE").is_some_and(|size| size <= MAX_LISTED_MESSAGE_BYTES)
+            })
+            .filter_map(|fields| number(fields, "UID"))
+            .map(|uid| uid as u32)
+            .collect();
+        let whole = session.fetch(&small, MESSAGE_ITEMS).await?;
+        messages.retain(|fields| {
+            number(fields, "UID").is_none_or(|uid| !small.contains(&(uid as u32)))
+        });
+        messages.extend(whole);
+
+        Ok(messages
+            .into_iter()
+            .filter_map(|fields| self.to_resource(mailbox, uid_validity, fields))
+            .collect())
+    }
+
+    /// The raw message at `location`, checking the mailbox hasn't been
+    /// renumbered since its ID was made.
+    async fn fetch_raw(
+        &self,
+        session: &mut Session,
+        location: &Location,
+    ) -> Result<Vec<(String, Value)>, DomainError> {
+        let not_found = || {
+            DomainError::ResourceNotFound(format!(
+                "IMAP message {} not found in {}",
+                location.uid, location.mailbox
+            ))
+        };
+        let mailbox = session.examine(&location.mailbox).await?;
+        if mailbox.uid_validity != location.uid_validity {
+            return Err(not_found());
+        }
+        session
+            .fetch(&[location.uid], MESSAGE_ITEMS)
+            .await?
+            .into_iter()
+            .find(|fields| number(fields, "UID") == Some(location.uid as u64))
+            .ok_or_else(not_found)
+    }
+
+    /// A fetched message as a resource, or `None` if the server left out
+    /// its UID.
+    fn to_resource(
+        &self,
+        mailbox: &str,
+        uid_validity: u64,
+        fields: Vec<(String, Value)>,
+    ) -> Option<Resource> {
+        let location = Location {
+            mailbox: mailbox.to_string(),
+            uid_validity,
+            uid: number(&fields, "UID")? as u32,
+        };
+        let size = number(&fields, "RFC822.SIZE");
+        let mut flags = Vec::new();
+        let mut internal_date = None;
+        let mut raw = None;
+        let mut whole = false;
+        for (name, value) in fields {
+            match (name.as_str(), value) {
+                ("FLAGS", Value::List(list)) => {
+                    flags = list.iter().filter_map(Value::as_str).collect()
+                }
+                ("INTERNALDATE", value) => {
+                    internal_date = value.as_str().and_then(|date| parse_internal_date(&date))
+                }
+                ("BODY[]", value) => {
+                    raw = value.into_bytes();
+                    whole = true;
+                }
+                ("BODY[HEADER]", value) if raw.is_none() => raw = value.into_bytes(),
+                _ => {}
+            }
+        }
+        let message = Message::parse(&raw.unwrap_or_default());
+        let headers = &message.headers;
+
+        let from = headers.raw("from").map(mime::addresses).unwrap_or_default();
+        let to = headers.raw("to").map(mime::addresses).unwrap_or_default();
+        let cc = headers.raw("cc").map(mime::addresses).unwrap_or_default();
+        let subject = headers.text("subject").filter(|s| !s.trim().is_empty());
+        let date = headers
+            .raw("date")
+            .and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok())
+            .map(|date| date.with_timezone(&Utc))
+            .or(internal_date)
+            .unwrap_or_default();
+        let message_id = headers.raw("message-id").and_then(first_message_id);
+        let in_reply_to = headers.raw("in-reply-to").and_then(first_message_id);
+        // The thread's first message, as the references list it, names the
+        // thread.
+        let thread_id = headers
+            .raw("references")
+            .and_then(first_message_id)
+            .or_else(|| in_reply_to.clone())
+            .or_else(|| message_id.clone());
+        let unread = !flags.iter().any(|flag| flag.eq_ignore_ascii_case("\\Seen"));
+
+        let url = location.url(&self.server);
+        let attachments: Vec<Attachment> = message
+            .attachments
+            .iter()
+            .map(|file| Attachment {
+                name: file.name.clone(),
+                url: format!("{}/;SECTION={}", url, file.section),
+                mime_type: Some(file.mime_type.clone()),
+            })
+            .collect();
+
+        let mut metadata = HashMap::new();
+        metadata.insert("kind".to_string(), serde_json::json!("message"));
+        metadata.insert("mailbox".to_string(), serde_json::json!(mailbox));
+        if let Some(sender) = from.first() {
+            metadata.insert("from".to_string(), address_json(sender));
+        }
+        metadata.insert(
+            "to".to_string(),
+            serde_json::json!(to.iter().map(address_json).collect::<Vec<_>>()),
+        );
+        if !cc.is_empty() {
+            metadata.insert(
+                "cc".to_string(),
+                serde_json::json!(cc.iter().map(address_json).collect::<Vec<_>>()),
+            );
+        }
+        if let Some(subject) = &subject {
+            metadata.insert("subject".to_string(), serde_json::json!(subject));
+        }
+        metadata.insert("date".to_string(), serde_json::json!(date.to_rfc3339()));
+        if let Some(message_id) = &message_id {
+            metadata.insert("message_id".to_string(), serde_json::json!(message_id));
+        }
+        if let Some(in_reply_to) = &in_reply_to {
+            metadata.insert("in_reply_to".to_string(), serde_json::json!(in_reply_to));
+        }
+        if let Some(thread_id) = &thread_id {
+            metadata.insert("thread_id".to_string(), serde_json::json!(thread_id));
+        }
+        metadata.insert("flags".to_string(), serde_json::json!(flags));
+        metadata.insert("unread".to_string(), serde_json::json!(unread));
+        if let Some(size) = size {
+            metadata.insert("size".to_string(), serde_json::json!(size));
+        }
+        if !attachments.is_empty() {
+            metadata.insert("attachments".to_string(), serde_json::json!(attachments));
+        }
+
+        let mut content = String::new();
+        for (label, addresses) in [("From", &from), ("To", &to), ("Cc", &cc)] {
+            if !addresses.is_empty() {
+                let addresses: Vec<String> = addresses.iter().map(Address::display).collect();
+                content.push_str(&format!("{}: {}\n", label, addresses.join(", ")));
+            }
+        }
+        content.push_str(&format!("Date: {}\n\n", date.to_rfc2822()));
+        if whole {
+            content.push_str(&message.text);
+        } else {
+            content.push_str(&format!(
+                "(The body, {} bytes, is too large to list; fetch the message by ID to read it.)",
+                size.unwrap_or_default()
+            ));
+        }
+
+        Some(Resource {
+            id: location.id(),
+            source: ResourceSource::Custom("imap".to_string()),
+            title: subject.unwrap_or_else(|| "(no subject)".to_string()),
+            content,
+            metadata,
+            created_at: date,
+            updated_at: date,
+            provenance: Some(Provenance {
+                provider: "imap".to_string(),
+                fetched_at: Utc::now(),
+                api_version: Some(IMAP_API_VERSION.to_string()),
+                request_id: None,
+                url: Some(url),
+            }),
+        })
+    }
+}
+
+/// The date `query`'s `since` filter, or `updated_after` as sync sets it,
+/// gives, as `SEARCH` takes it.
+fn since(query: &Query) -> Result<Option<String>, DomainError> {
+    query
+        .filters
+        .get("since")
+        .or(query.filters.get("updated_after"))
+        .map(|date| search_date(date))
+        .transpose()
+}
+
+/// The `SEARCH` criteria of `query`'s filters, with `since` from `since`.
+fn criteria<'a>(query: &'a Query, since: Option<&'a str>) -> Result<Vec<Arg<'a>>, DomainError> {
+    let mut criteria = vec![Arg::Atom("ALL")];
+    if let Some(since) = since {
+        criteria.extend([Arg::Atom("SINCE"), Arg::Atom(since)]);
+    }
+    for (filter, key) in [("from", "FROM"), ("subject", "SUBJECT")] {
+        if let Some(text) = query.filters.get(filter) {
+            criteria.extend([Arg::Atom(key), Arg::Text(text)]);
+        }
+    }
+    match query.filters.get("unseen").map(String::as_str) {
+        None | Some("false") => {}
+        Some("true") => criteria.push(Arg::Atom("UNSEEN")),
+        Some(other) => {
+            return Err(DomainError::InvalidQuery(format!(
+                "Invalid unseen filter: {} (expected true or false)",
+                other
+            )))
+        }
+    }
+    Ok(criteria)
+}
+
+/// The value of the numeric item `name`, e.g. `UID`.
+fn number(fields: &[(String, Value)], name: &str) -> Option<u64> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .and_then(|(_, value)| value.as_str()?.parse().ok())
+}
+
+/// Parses an `INTERNALDATE` such as ` 7-Jul-2024 09:15:00 +0200`.
+fn parse_internal_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(date.trim(), "%d-%b-%Y %H:%M:%S %z")
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// The first `<message-id>` in a header such as `References`.
+fn first_message_id(header: &str) -> Option<String> {
+    let start = header.find('<')?;
+    let end = start + header[start..].find('>')?;
+    Some(header[start..=end].to_string())
+}
+
+fn address_json(address: &Address) -> serde_json::Value {
+    serde_json::json!({ "name": address.name, "email": address.email })
+}
+
+/// `date`, an RFC 3339 time or a date, as an IMAP search date such as
+/// `7-Jul-2024`.
+fn search_date(date: &str) -> Result<String, DomainError> {
+    let day = DateTime::parse_from_rfc3339(date)
+        .map(|time| time.with_timezone(&Utc).date_naive())
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .map_err(|_| {
+            DomainError::InvalidQuery(format!(
+                "Invalid date {:?}: expected YYYY-MM-DD or an RFC 3339 time",
+                date
+            ))
+        })?;
+    Ok(day.format("%-d-%b-%Y").to_string())
+}
+
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+#[async_trait]
+impl ResourceProvider for ImapAdapter {
+    /// Lists the newest messages of each mailbox. `mailbox` names the
+    /// mailboxes to list instead of those configured, comma-separated;
+    /// `from` and `subject` match text in those headers; `unseen=true`
+    /// lists unread messages only; and `since` (or `updated_after`, as
+    /// sync sets it) a date they must have arrived on or after.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let since = since(query)?;
+        let criteria = criteria(query, since.as_deref())?;
+
+        let mut session = self.connect().await?;
+        let listed = self
+            .list(&mut session, &self.mailboxes(query), &criteria, limit)
+            .await;
+        session.logout().await;
+        let mut resources = listed?;
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    /// A message, read whole however large.
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let location = Location::from_id(id)
+            .ok_or_else(|| DomainError::ResourceNotFound(format!("Not an IMAP message: {}", id)))?;
+        let mut session = self.connect().await?;
+        let fields = self.fetch_raw(&mut session, &location).await;
+        session.logout().await;
+        self.to_resource(&location.mailbox, location.uid_validity, fields?)
+            .ok_or_else(|| DomainError::ResourceNotFound(format!("IMAP message {}", id)))
+    }
+
+    /// Messages whose headers or body contain `query`, as the server's
+    /// `SEARCH TEXT` finds them, newest first.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let mut session = self.connect().await?;
+        let found = self
+            .list(
+                &mut session,
+                &self.mailboxes,
+                &[Arg::Atom("TEXT"), Arg::Text(query.trim())],
+                SEARCH_LIMIT,
+            )
+            .await;
+        session.logout().await;
+        found
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "IMAP"
+    }
+
+    /// Lists every message of each mailbox in order of UID, a page at a
+    /// time. Cursors are `<uid validity>:<uid>:<mailbox>`, for the messages
+    /// after that UID, both empty to start on the mailbox.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query.page_size()?.unwrap_or(PAGE_SIZE).max(1);
+        let since = since(query)?;
+        let criteria = criteria(query, since.as_deref())?;
+        let mailboxes = self.mailboxes(query);
+
+        let (mailbox, uid_validity, after) = match &cursor {
+            Some(cursor) => {
+                let invalid =
+                    || DomainError::InvalidQuery(format!("Invalid IMAP cursor: {}", cursor));
+                let mut parts = cursor.splitn(3, ':');
+                let (Some(uid_validity), Some(after), Some(mailbox)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(invalid());
+                };
+                let uid_validity = match uid_validity {
+                    "" => None,
+                    uid_validity => Some(uid_validity.parse().map_err(|_| invalid())?),
+                };
+                let after = match after {
+                    "" => 0,
+                    after => after.parse().map_err(|_| invalid())?,
+                };
+                (mailbox.to_string(), uid_validity, after)
+            }
+            None => match mailboxes.first() {
+                Some(mailbox) => (mailbox.clone(), None, 0),
+                None => return Ok(ListingPage::default()),
+            },
+        };
+        let index = mailboxes
+            .iter()
+            .position(|other| *other == mailbox)
+            .ok_or_else(|| {
+                DomainError::InvalidQuery(format!("IMAP mailbox {} isn't being listed", mailbox))
+            })?;
+
+        let mut session = self.connect().await?;
+        let listed = self
+            .list_after(
+                &mut session,
+                &mailbox,
+                uid_validity,
+                &criteria,
+                after,
+                page_size,
+            )
+            .await;
+        session.logout().await;
+        let (resources, next, uid_validity) = listed?;
+        let next_cursor = match next {
+            Some(uid) => Some(format!("{}:{}:{}", uid_validity, uid, mailbox)),
+            None => mailboxes
+                .get(index + 1)
+                .map(|mailbox| format!("::{}", mailbox)),
+        };
+        Ok(ListingPage {
+            resources,
+            next_cursor,
+        })
+    }
+
+    /// Lists the account's mailboxes, for the `mailbox` filter and the
+    /// `mailboxes` setting.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        let mut session = self.connect().await?;
+        let listed = session.list().await;
+        session.logout().await;
+        Ok(listed?
+            .into_iter()
+            .map(|(name, delimiter)| {
+                let parent_id = delimiter
+                    .filter(|delimiter| !delimiter.is_empty())
+                    .and_then(|delimiter| {
+                        name.rsplit_once(delimiter.as_str())
+                            .map(|(parent, _)| parent.to_string())
+                    });
+                DiscoveredItem {
+                    title: name.rsplit(['/', '.']).next().unwrap_or(&name).to_string(),
+                    id: name,
+                    kind: "mailbox".to_string(),
+                    parent_id,
+                    url: None,
+                }
+            })
+            .collect())
+    }
+
+    /// Fetches the message again and decodes the attached part.
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        let Some((location, section)) = Location::from_url(&attachment.url).filter(|_| {
+            attachment
+                .url
+                .contains(&format!("//{}/", self.server.authority()))
+        }) else {
+            return Err(DomainError::InvalidQuery(format!(
+                "{} is not a part of a message on this IMAP server",
+                attachment.url
+            )));
+        };
+        let mut session = self.connect().await?;
+        let fields = self.fetch_raw(&mut session, &location).await;
+        session.logout().await;
+        let raw = fields?
+            .into_iter()
+            .find(|(name, _)| name == "BODY[]")
+            .and_then(|(_, value)| value.into_bytes())
+            .unwrap_or_default();
+        let (mime_type, data) = mime::part(&raw, &section).ok_or_else(|| {
+            DomainError::ResourceNotFound(format!(
+                "IMAP message {} has no part {}",
+                location.uid, section
+            ))
+        })?;
+        Ok(Blob { mime_type, data })
+    }
+}
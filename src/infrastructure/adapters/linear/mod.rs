@@ -1,31 +1,20 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::OnceCell;
 
 use crate::{
-    domain::{DomainError, Query, Resource, ResourceSource},
-    ports::ResourceProvider,
+    domain::{
+        Attachment, Blob, Change, DiscoveredItem, DomainError, ListingPage, Provenance, Query,
+        Resource, ResourceSource,
+    },
+    infrastructure::http::{HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
 };
 
-#[derive(Debug, Serialize)]
-struct GraphQLRequest {
-    query: String,
-    variables: Option<HashMap<String, serde_json::Value>>,
-}
-
-#[derive(Debug, Deserialize)]
-struct GraphQLResponse<T> {
-    data: Option<T>,
-    errors: Option<Vec<GraphQLError>>,
-}
-
-#[derive(Debug, Deserialize)]
-struct GraphQLError {
-    message: String,
-}
-
 #[derive(Debug, Deserialize)]
 struct IssuesData {
     issues: IssuesConnection,
@@ -49,15 +38,23 @@ struct PageInfo {
 #[derive(Debug, Deserialize)]
 struct Issue {
     id: String,
+    /// Team key and number, e.g. `ENG-123`.
+    identifier: String,
     title: String,
     description: Option<String>,
     #[serde(rename = "createdAt")]
     created_at: DateTime<Utc>,
     #[serde(rename = "updatedAt")]
     updated_at: DateTime<Utc>,
+    url: Option<String>,
+    #[serde(rename = "archivedAt")]
+    archived_at: Option<DateTime<Utc>>,
+    trashed: Option<bool>,
     state: IssueState,
     assignee: Option<User>,
     labels: Labels,
+    #[serde(default)]
+    attachments: Attachments,
     project: Option<Project>,
 }
 
@@ -82,15 +79,34 @@ struct Label {
     name: String,
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct Attachments {
+    nodes: Vec<IssueAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueAttachment {
+    title: String,
+    url: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct Project {
     id: String,
     name: String,
 }
 
+/// Largest page size accepted by the Linear API.
+const LINEAR_MAX_PAGE_SIZE: usize = 250;
+
+/// Where files uploaded to Linear are kept. Only these are downloaded, as
+/// they're the only links the API key is any good for.
+const LINEAR_UPLOADS: &str = "https://uploads.linear.app/";
+
 pub struct LinearAdapter {
-    client: reqwest::Client,
-    api_key: String,
+    http: HttpClient,
+    /// ID of the user the API key belongs to, looked up on first use.
+    viewer_id: OnceCell<String>,
 }
 
 impl LinearAdapter {
@@ -103,19 +119,139 @@ impl LinearAdapter {
         );
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        let http = HttpClient::new("Linear", headers, vec![api_key])?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self {
+            http,
+            viewer_id: OnceCell::new(),
+        })
+    }
 
-        Ok(Self { client, api_key })
+    /// Linear budgets requests per hour, so once throttled, quick retries
+    /// rarely help; retry transient server errors a couple of times only.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            retry_on: vec![429, 502, 503, 504],
+        }
     }
 
-    fn issue_to_resource(&self, issue: Issue) -> Resource {
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    async fn viewer_id(&self) -> Result<&str, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct ViewerData {
+            viewer: Viewer,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Viewer {
+            id: String,
+        }
+
+        self.viewer_id
+            .get_or_try_init(|| async {
+                let (data, _): (ViewerData, _) = self
+                    .execute_graphql("query { viewer { id } }", None)
+                    .await?;
+                Ok(data.viewer.id)
+            })
+            .await
+            .map(|id| id.as_str())
+    }
+
+    /// Translates the `assignee`, `cycle`, `project`, `team` and
+    /// `updated_after` query filters into a Linear `IssueFilter`.
+    /// `assignee=me` resolves to the API key's user; any other value matches
+    /// a user's email, name, or display name. `project` takes a project ID
+    /// or name, `team` a team key, and `updated_after` an RFC 3339 time.
+    async fn issue_filter(&self, query: &Query) -> Result<Option<serde_json::Value>, DomainError> {
+        let mut filter = serde_json::Map::new();
+
+        match query.filters.get("assignee").map(|a| a.as_str()) {
+            None => {}
+            Some("me") => {
+                let viewer_id = self.viewer_id().await?;
+                filter.insert(
+                    "assignee".to_string(),
+                    serde_json::json!({ "id": { "eq": viewer_id } }),
+                );
+            }
+            Some(user) => {
+                filter.insert(
+                    "assignee".to_string(),
+                    serde_json::json!({
+                        "or": [
+                            { "email": { "eqIgnoreCase": user } },
+                            { "name": { "eqIgnoreCase": user } },
+                            { "displayName": { "eqIgnoreCase": user } },
+                        ]
+                    }),
+                );
+            }
+        }
+
+        match query.filters.get("cycle").map(|c| c.as_str()) {
+            None => {}
+            Some("current") => {
+                filter.insert(
+                    "cycle".to_string(),
+                    serde_json::json!({ "isActive": { "eq": true } }),
+                );
+            }
+            Some(other) => {
+                return Err(DomainError::InvalidQuery(format!(
+                    "Unsupported cycle filter: {} (expected \"current\")",
+                    other
+                )))
+            }
+        }
+
+        if let Some(project) = query.filters.get("project") {
+            let project_filter = if uuid::Uuid::parse_str(project).is_ok() {
+                serde_json::json!({ "id": { "eq": project } })
+            } else {
+                serde_json::json!({ "name": { "eqIgnoreCase": project } })
+            };
+            filter.insert("project".to_string(), project_filter);
+        }
+
+        if let Some(team) = query.filters.get("team") {
+            filter.insert(
+                "team".to_string(),
+                serde_json::json!({ "key": { "eqIgnoreCase": team } }),
+            );
+        }
+
+        if let Some(updated_after) = query.filters.get("updated_after") {
+            filter.insert(
+                "updatedAt".to_string(),
+                serde_json::json!({ "gte": updated_after }),
+            );
+        }
+
+        Ok((!filter.is_empty()).then_some(serde_json::Value::Object(filter)))
+    }
+
+    fn issue_to_resource(&self, issue: Issue, request_id: Option<String>) -> Resource {
         let mut metadata = HashMap::new();
 
+        metadata.insert(
+            "identifier".to_string(),
+            serde_json::json!(issue.identifier),
+        );
         metadata.insert("state".to_string(), serde_json::json!(issue.state.name));
 
+        if issue.archived_at.is_some() || issue.trashed == Some(true) {
+            metadata.insert("archived".to_string(), serde_json::json!(true));
+        }
+
         if let Some(assignee) = &issue.assignee {
             metadata.insert(
                 "assignee".to_string(),
@@ -126,9 +262,14 @@ impl LinearAdapter {
             );
         }
 
+        let attachments = uploads(&issue);
         let labels: Vec<String> = issue.labels.nodes.into_iter().map(|l| l.name).collect();
         metadata.insert("labels".to_string(), serde_json::json!(labels));
 
+        if !attachments.is_empty() {
+            metadata.insert("attachments".to_string(), serde_json::json!(attachments));
+        }
+
         if let Some(project) = &issue.project {
             metadata.insert(
                 "project".to_string(),
@@ -150,69 +291,47 @@ impl LinearAdapter {
             metadata,
             created_at: issue.created_at,
             updated_at: issue.updated_at,
+            provenance: Some(Provenance {
+                provider: "linear".to_string(),
+                fetched_at: Utc::now(),
+                api_version: None,
+                request_id,
+                url: issue.url,
+            }),
         }
     }
 
-    async fn execute_graphql<T: for<'de> Deserialize<'de>>(
+    /// Fetches up to `first` issues matching `query`, starting after the
+    /// `after` cursor.
+    async fn issues_page(
         &self,
-        query: &str,
-        variables: Option<HashMap<String, serde_json::Value>>,
-    ) -> Result<T, DomainError> {
-        let request = GraphQLRequest {
-            query: query.to_string(),
-            variables,
-        };
-
-        let response = self
-            .client
-            .post("https://api.linear.app/graphql")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
-
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
-            return Err(DomainError::ProviderError(format!(
-                "Linear API error: {}",
-                error_text
-            )));
-        }
-
-        let graphql_response: GraphQLResponse<T> = response
-            .json()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
-
-        if let Some(errors) = graphql_response.errors {
-            let error_messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
-            return Err(DomainError::ProviderError(format!(
-                "GraphQL errors: {}",
-                error_messages.join(", ")
-            )));
-        }
-
-        graphql_response
-            .data
-            .ok_or_else(|| DomainError::ProviderError("No data in response".to_string()))
-    }
-}
-
-#[async_trait]
-impl ResourceProvider for LinearAdapter {
-    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        query: &Query,
+        first: usize,
+        after: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
         let graphql_query = r#"
-            query GetIssues($first: Int!, $after: String) {
-                issues(first: $first, after: $after) {
+            query GetIssues(
+                $first: Int!
+                $after: String
+                $filter: IssueFilter
+                $includeArchived: Boolean
+            ) {
+                issues(
+                    first: $first
+                    after: $after
+                    filter: $filter
+                    includeArchived: $includeArchived
+                ) {
                     nodes {
                         id
+                        identifier
                         title
                         description
                         createdAt
                         updatedAt
+                        url
+                        archivedAt
+                        trashed
                         state {
                             name
                         }
@@ -225,6 +344,12 @@ impl ResourceProvider for LinearAdapter {
                                 name
                             }
                         }
+                        attachments {
+                            nodes {
+                                title
+                                url
+                            }
+                        }
                         project {
                             id
                             name
@@ -238,22 +363,352 @@ impl ResourceProvider for LinearAdapter {
             }
         "#;
 
-        let limit = query.limit.unwrap_or(50).min(250) as i32;
+        let filter = self.issue_filter(query).await?;
+        let include_archived = query
+            .filters
+            .get("include_archived")
+            .is_some_and(|value| value == "true");
+
         let mut variables = HashMap::new();
-        variables.insert("first".to_string(), serde_json::json!(limit));
+        variables.insert("first".to_string(), serde_json::json!(first));
+        if let Some(filter) = filter {
+            variables.insert("filter".to_string(), filter);
+        }
+        if include_archived {
+            variables.insert("includeArchived".to_string(), serde_json::json!(true));
+        }
+        if let Some(cursor) = after {
+            variables.insert("after".to_string(), serde_json::json!(cursor));
+        }
 
-        let issues_data: IssuesData = self.execute_graphql(graphql_query, Some(variables)).await?;
+        let (issues_data, request_id): (IssuesData, _) =
+            self.execute_graphql(graphql_query, Some(variables)).await?;
 
-        let resources: Vec<Resource> = issues_data
+        let page_info = issues_data.issues.page_info;
+        let resources = issues_data
             .issues
             .nodes
             .into_iter()
-            .map(|issue| self.issue_to_resource(issue))
+            .map(|issue| self.issue_to_resource(issue, request_id.clone()))
             .collect();
 
+        Ok(ListingPage {
+            resources,
+            next_cursor: page_info.end_cursor.filter(|_| page_info.has_next_page),
+        })
+    }
+
+    /// Creates an issue in `team` (its key, name or ID), tagged with the
+    /// labels named `labels`, which must belong to the team or the whole
+    /// workspace.
+    async fn create_issue(
+        &self,
+        team: &str,
+        title: &str,
+        description: &str,
+        labels: &[String],
+    ) -> Result<Resource, DomainError> {
+        let graphql_query = r#"
+            query CreateIssueContext {
+                teams {
+                    nodes {
+                        id
+                        key
+                        name
+                    }
+                }
+                issueLabels(first: 250) {
+                    nodes {
+                        id
+                        name
+                        team {
+                            id
+                        }
+                    }
+                }
+            }
+        "#;
+
+        #[derive(Debug, Deserialize)]
+        struct ContextData {
+            teams: Nodes<Team>,
+            #[serde(rename = "issueLabels")]
+            issue_labels: Nodes<IssueLabel>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Nodes<T> {
+            nodes: Vec<T>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Team {
+            id: String,
+            key: String,
+            name: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct IssueLabel {
+            id: String,
+            name: String,
+            team: Option<TeamRef>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct TeamRef {
+            id: String,
+        }
+
+        let (context, _): (ContextData, _) = self.execute_graphql(graphql_query, None).await?;
+        let team = context
+            .teams
+            .nodes
+            .into_iter()
+            .find(|candidate| {
+                candidate.id == team
+                    || candidate.key.eq_ignore_ascii_case(team)
+                    || candidate.name.eq_ignore_ascii_case(team)
+            })
+            .ok_or_else(|| DomainError::InvalidQuery(format!("Unknown Linear team: {}", team)))?;
+
+        let mut label_ids = Vec::new();
+        let mut unknown = Vec::new();
+        for name in labels {
+            let label = context.issue_labels.nodes.iter().find(|label| {
+                label.name.eq_ignore_ascii_case(name)
+                    && label.team.as_ref().is_none_or(|owner| owner.id == team.id)
+            });
+            match label {
+                Some(label) => label_ids.push(label.id.clone()),
+                None => unknown.push(name.as_str()),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(DomainError::InvalidQuery(format!(
+                "Unknown labels for Linear team {}: {}",
+                team.key,
+                unknown.join(", ")
+            )));
+        }
+
+        let graphql_query = r#"
+            mutation CreateIssue($input: IssueCreateInput!) {
+                issueCreate(input: $input) {
+                    success
+                    issue {
+                        id
+                        identifier
+                        title
+                        description
+                        createdAt
+                        updatedAt
+                        url
+                        archivedAt
+                        trashed
+                        state {
+                            name
+                        }
+                        assignee {
+                            name
+                            email
+                        }
+                        labels {
+                            nodes {
+                                name
+                            }
+                        }
+                        attachments {
+                            nodes {
+                                title
+                                url
+                            }
+                        }
+                        project {
+                            id
+                            name
+                        }
+                    }
+                }
+            }
+        "#;
+
+        let mut input = serde_json::json!({
+            "teamId": team.id,
+            "title": title,
+            "labelIds": label_ids,
+        });
+        if !description.is_empty() {
+            input["description"] = serde_json::json!(description);
+        }
+        let mut variables = HashMap::new();
+        variables.insert("input".to_string(), input);
+
+        #[derive(Debug, Deserialize)]
+        struct CreateData {
+            #[serde(rename = "issueCreate")]
+            issue_create: IssuePayload,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct IssuePayload {
+            success: bool,
+            issue: Option<Issue>,
+        }
+
+        let (data, request_id): (CreateData, _) =
+            self.execute_graphql(graphql_query, Some(variables)).await?;
+        let issue = data
+            .issue_create
+            .issue
+            .filter(|_| data.issue_create.success)
+            .ok_or_else(|| {
+                DomainError::ProviderError("Linear did not create the issue".to_string())
+            })?;
+
+        Ok(self.issue_to_resource(issue, request_id))
+    }
+
+    /// Sets an issue's title and/or description.
+    async fn update_issue(
+        &self,
+        id: &str,
+        title: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<Resource, DomainError> {
+        let issue_id = id.strip_prefix("linear_").unwrap_or(id);
+        let graphql_query = r#"
+            mutation UpdateIssue($id: String!, $input: IssueUpdateInput!) {
+                issueUpdate(id: $id, input: $input) {
+                    success
+                    issue {
+                        id
+                        identifier
+                        title
+                        description
+                        createdAt
+                        updatedAt
+                        url
+                        archivedAt
+                        trashed
+                        state {
+                            name
+                        }
+                        assignee {
+                            name
+                            email
+                        }
+                        labels {
+                            nodes {
+                                name
+                            }
+                        }
+                        attachments {
+                            nodes {
+                                title
+                                url
+                            }
+                        }
+                        project {
+                            id
+                            name
+                        }
+                    }
+                }
+            }
+        "#;
+
+        let mut input = serde_json::json!({});
+        if let Some(title) = title {
+            input["title"] = serde_json::json!(title);
+        }
+        if let Some(description) = description {
+            input["description"] = serde_json::json!(description);
+        }
+        let mut variables = HashMap::new();
+        variables.insert("id".to_string(), serde_json::json!(issue_id));
+        variables.insert("input".to_string(), input);
+
+        #[derive(Debug, Deserialize)]
+        struct UpdateData {
+            #[serde(rename = "issueUpdate")]
+            issue_update: IssuePayload,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct IssuePayload {
+            success: bool,
+            issue: Option<Issue>,
+        }
+
+        let (data, request_id): (UpdateData, _) =
+            self.execute_graphql(graphql_query, Some(variables)).await?;
+        let issue = data
+            .issue_update
+            .issue
+            .filter(|_| data.issue_update.success)
+            .ok_or_else(|| {
+                DomainError::ProviderError(format!("Linear did not update issue {}", issue_id))
+            })?;
+
+        Ok(self.issue_to_resource(issue, request_id))
+    }
+
+    /// Runs a GraphQL query, returning its data and Linear's ID for the
+    /// request.
+    async fn execute_graphql<T: for<'de> Deserialize<'de>>(
+        &self,
+        query: &str,
+        variables: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(T, Option<String>), DomainError> {
+        self.http
+            .graphql("https://api.linear.app/graphql", query, variables)
+            .await
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for LinearAdapter {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let page_size = query
+            .page_size()?
+            .unwrap_or(LINEAR_MAX_PAGE_SIZE)
+            .min(LINEAR_MAX_PAGE_SIZE)
+            .min(limit.max(1));
+
+        let mut resources = Vec::new();
+        let mut after: Option<String> = None;
+
+        while resources.len() < limit {
+            let page = self
+                .issues_page(query, page_size.min(limit - resources.len()), after.take())
+                .await?;
+            resources.extend(page.resources);
+            report_progress(|| format!("Fetched {} Linear issues", resources.len()));
+
+            after = page.next_cursor;
+            if after.is_none() {
+                break;
+            }
+        }
+        resources.truncate(limit);
+
         Ok(resources)
     }
 
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(LINEAR_MAX_PAGE_SIZE)
+            .min(LINEAR_MAX_PAGE_SIZE);
+        self.issues_page(query, page_size, cursor).await
+    }
+
     async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
         let issue_id = id.strip_prefix("linear_").unwrap_or(id);
 
@@ -261,10 +716,14 @@ impl ResourceProvider for LinearAdapter {
             query GetIssue($id: String!) {
                 issue(id: $id) {
                     id
+                    identifier
                     title
                     description
                     createdAt
                     updatedAt
+                    url
+                    archivedAt
+                    trashed
                     state {
                         name
                     }
@@ -277,6 +736,12 @@ impl ResourceProvider for LinearAdapter {
                             name
                         }
                     }
+                    attachments {
+                        nodes {
+                            title
+                            url
+                        }
+                    }
                     project {
                         id
                         name
@@ -293,13 +758,14 @@ impl ResourceProvider for LinearAdapter {
             issue: Option<Issue>,
         }
 
-        let issue_data: IssueData = self.execute_graphql(graphql_query, Some(variables)).await?;
+        let (issue_data, request_id): (IssueData, _) =
+            self.execute_graphql(graphql_query, Some(variables)).await?;
 
         let issue = issue_data.issue.ok_or_else(|| {
             DomainError::ResourceNotFound(format!("Linear issue not found: {}", issue_id))
         })?;
 
-        Ok(self.issue_to_resource(issue))
+        Ok(self.issue_to_resource(issue, request_id))
     }
 
     async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
@@ -308,10 +774,14 @@ impl ResourceProvider for LinearAdapter {
                 issueSearch(query: $query) {
                     nodes {
                         id
+                        identifier
                         title
                         description
                         createdAt
                         updatedAt
+                        url
+                        archivedAt
+                        trashed
                         state {
                             name
                         }
@@ -324,11 +794,21 @@ impl ResourceProvider for LinearAdapter {
                                 name
                             }
                         }
+                        attachments {
+                            nodes {
+                                title
+                                url
+                            }
+                        }
                         project {
                             id
                             name
                         }
                     }
+                    pageInfo {
+                        hasNextPage
+                        endCursor
+                    }
                 }
             }
         "#;
@@ -342,13 +822,14 @@ impl ResourceProvider for LinearAdapter {
             issue_search: IssuesConnection,
         }
 
-        let search_data: SearchData = self.execute_graphql(graphql_query, Some(variables)).await?;
+        let (search_data, request_id): (SearchData, _) =
+            self.execute_graphql(graphql_query, Some(variables)).await?;
 
         let resources: Vec<Resource> = search_data
             .issue_search
             .nodes
             .into_iter()
-            .map(|issue| self.issue_to_resource(issue))
+            .map(|issue| self.issue_to_resource(issue, request_id.clone()))
             .collect();
 
         Ok(resources)
@@ -357,4 +838,167 @@ impl ResourceProvider for LinearAdapter {
     fn provider_name(&self) -> &'static str {
         "Linear"
     }
+
+    /// Creates issues; `parent` names the team.
+    /// Downloads files uploaded to Linear, whether attached to issues or
+    /// embedded in their descriptions.
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        if !attachment.url.starts_with(LINEAR_UPLOADS) {
+            return Err(DomainError::InvalidQuery(format!(
+                "{} is not a Linear upload",
+                attachment.url
+            )));
+        }
+        self.http.download(&attachment.url, &attachment.name).await
+    }
+
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        match change {
+            Change::Create {
+                parent,
+                title,
+                content,
+                labels,
+            } => self.create_issue(parent, title, content, labels).await,
+            Change::Update { id, title, content } => {
+                self.update_issue(id, title.as_deref(), content.as_deref())
+                    .await
+            }
+            _ => Err(DomainError::InvalidQuery(format!(
+                "Linear does not support {}",
+                change.kind()
+            ))),
+        }
+    }
+
+    /// Lists the teams and projects the API key can see.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        let graphql_query = r#"
+            query Discover($after: String) {
+                teams {
+                    nodes {
+                        id
+                        key
+                        name
+                    }
+                }
+                projects(first: 250, after: $after) {
+                    nodes {
+                        id
+                        name
+                        url
+                    }
+                    pageInfo {
+                        hasNextPage
+                        endCursor
+                    }
+                }
+            }
+        "#;
+
+        #[derive(Debug, Deserialize)]
+        struct DiscoverData {
+            teams: Nodes<Team>,
+            projects: ProjectsConnection,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Nodes<T> {
+            nodes: Vec<T>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Team {
+            id: String,
+            key: String,
+            name: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct ProjectsConnection {
+            nodes: Vec<DiscoveredProject>,
+            #[serde(rename = "pageInfo")]
+            page_info: PageInfo,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct DiscoveredProject {
+            id: String,
+            name: String,
+            url: Option<String>,
+        }
+
+        let mut items = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let mut variables = HashMap::new();
+            variables.insert("after".to_string(), serde_json::json!(after));
+            let (data, _): (DiscoverData, _) =
+                self.execute_graphql(graphql_query, Some(variables)).await?;
+
+            // Teams aren't paginated, so only take them once.
+            if after.is_none() {
+                items.extend(data.teams.nodes.into_iter().map(|team| DiscoveredItem {
+                    id: team.id,
+                    kind: "team".to_string(),
+                    title: format!("{} ({})", team.name, team.key),
+                    parent_id: None,
+                    url: None,
+                }));
+            }
+            items.extend(
+                data.projects
+                    .nodes
+                    .into_iter()
+                    .map(|project| DiscoveredItem {
+                        id: project.id,
+                        kind: "project".to_string(),
+                        title: project.name,
+                        parent_id: None,
+                        url: project.url,
+                    }),
+            );
+
+            after = data.projects.page_info.end_cursor;
+            if !data.projects.page_info.has_next_page || after.is_none() {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+}
+
+/// Files uploaded to Linear for `issue`: its attachments, then images and
+/// links in its description, each once.
+fn uploads(issue: &Issue) -> Vec<Attachment> {
+    let attached = issue
+        .attachments
+        .nodes
+        .iter()
+        .map(|attachment| (attachment.title.as_str(), attachment.url.as_str()));
+    let description = issue.description.as_deref().unwrap_or_default();
+    let embedded = description.match_indices("](").filter_map(|(end, _)| {
+        let name = description[..end].rsplit('[').next()?;
+        let url = description[end + 2..].split([')', ' ']).next()?;
+        Some((name, url))
+    });
+
+    let mut attachments: Vec<Attachment> = Vec::new();
+    for (name, url) in attached.chain(embedded) {
+        if !url.starts_with(LINEAR_UPLOADS) || attachments.iter().any(|a| a.url == url) {
+            continue;
+        }
+        let name = if name.trim().is_empty() {
+            url.rsplit('/').next().unwrap_or(url)
+        } else {
+            name.trim()
+        };
+        attachments.push(Attachment {
+            name: name.to_string(),
+            url: url.to_string(),
+            mime_type: None,
+        });
+    }
+    attachments
 }
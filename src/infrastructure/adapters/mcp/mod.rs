@@ -0,0 +1,331 @@
+pub mod transport;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::OnceCell;
+
+use self::transport::{HttpTransport, StdioTransport, Transport};
+use super::provider_name;
+use crate::{
+    domain::{
+        DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource, ResourceSource,
+    },
+    infrastructure::{http::RetryPolicy, mcp::protocol::PROTOCOL_VERSION},
+    ports::{report_progress, ResourceProvider},
+};
+
+/// Resource contents read from the server at once.
+const CONCURRENT_READS: usize = 4;
+
+/// How to reach a downstream MCP server.
+#[derive(Debug, Clone)]
+pub enum Upstream {
+    /// Run `command` and speak to it over stdin and stdout.
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: BTreeMap<String, String>,
+    },
+    /// POST to a streamable HTTP endpoint, with `token` as a bearer token.
+    Http { url: String, token: Option<String> },
+}
+
+/// Serves the resources of another MCP server as a provider named after
+/// it, so mcp-rs can aggregate several servers behind one endpoint.
+///
+/// Resource IDs are the provider name and the server's resource URI, e.g.
+/// `docs_file:///handbook/onboarding.md`. The server is started, or
+/// connected to, on first use.
+pub struct McpClientAdapter {
+    name: &'static str,
+    upstream: Upstream,
+    retry: RetryPolicy,
+    connection: OnceCell<Box<dyn Transport>>,
+}
+
+impl McpClientAdapter {
+    /// `name` becomes the provider name and ID prefix; see
+    /// [`provider_name`].
+    pub fn new(name: &str, upstream: Upstream) -> Result<Self, DomainError> {
+        Ok(Self {
+            name: provider_name("upstream", name)?,
+            upstream,
+            retry: Self::default_retry_policy(),
+            connection: OnceCell::new(),
+        })
+    }
+
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// Applies to HTTP upstreams only.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The connection to the server, completing the initialize handshake
+    /// the first time.
+    async fn connection(&self) -> Result<&dyn Transport, DomainError> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| async {
+                let transport: Box<dyn Transport> = match &self.upstream {
+                    Upstream::Stdio { command, args, env } => {
+                        Box::new(StdioTransport::spawn(self.name, command, args, env)?)
+                    }
+                    Upstream::Http { url, token } => Box::new(HttpTransport::new(
+                        self.name,
+                        url.clone(),
+                        token.clone(),
+                        self.retry.clone(),
+                    )?),
+                };
+
+                let result = transport
+                    .request(
+                        "initialize",
+                        json!({
+                            "protocolVersion": PROTOCOL_VERSION,
+                            "capabilities": {},
+                            "clientInfo": { "name": "mcp-rs", "version": env!("CARGO_PKG_VERSION") },
+                        }),
+                    )
+                    .await?;
+                if result.pointer("/capabilities/resources").is_none() {
+                    return Err(DomainError::ProviderError(format!(
+                        "{} doesn't serve resources",
+                        self.name
+                    )));
+                }
+                transport
+                    .notify("notifications/initialized", json!({}))
+                    .await?;
+                tracing::info!(
+                    "Connected to {} ({})",
+                    self.name,
+                    result["serverInfo"]["name"].as_str().unwrap_or("unknown server")
+                );
+                Ok(transport)
+            })
+            .await?;
+        Ok(connection.as_ref())
+    }
+
+    /// One page of the server's resource list.
+    async fn list(
+        &self,
+        cursor: Option<String>,
+    ) -> Result<(Vec<Value>, Option<String>), DomainError> {
+        let params = match cursor {
+            Some(cursor) => json!({ "cursor": cursor }),
+            None => json!({}),
+        };
+        let mut result = self
+            .connection()
+            .await?
+            .request("resources/list", params)
+            .await?;
+
+        let resources = match result["resources"].take() {
+            Value::Array(resources) => resources,
+            _ => Vec::new(),
+        };
+        let next_cursor = result["nextCursor"].as_str().map(str::to_string);
+        report_progress(|| format!("Listed {} resources from {}", resources.len(), self.name));
+        Ok((resources, next_cursor))
+    }
+
+    /// The server's resource list, up to `limit` entries.
+    async fn list_all(&self, limit: Option<usize>) -> Result<Vec<Value>, DomainError> {
+        let mut listed = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (resources, next_cursor) = self.list(cursor).await?;
+            listed.extend(resources);
+            if limit.is_some_and(|limit| listed.len() >= limit) {
+                listed.truncate(limit.unwrap_or(usize::MAX));
+                return Ok(listed);
+            }
+            match next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(listed),
+            }
+        }
+    }
+
+    /// Reads the contents of the resource at `uri`, taking its title and
+    /// other details from `listed` when it came from a listing.
+    async fn read(&self, uri: &str, listed: Option<&Value>) -> Result<Resource, DomainError> {
+        let result = self
+            .connection()
+            .await?
+            .request("resources/read", json!({ "uri": uri }))
+            .await?;
+        let contents = result["contents"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let mut resource = self.to_resource(listed.unwrap_or(&json!({ "uri": uri })));
+        resource.content = contents
+            .iter()
+            .filter_map(|content| content["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if !resource.metadata.contains_key("mimeType") {
+            if let Some(mime_type) = contents.first().and_then(|content| content.get("mimeType")) {
+                resource
+                    .metadata
+                    .insert("mimeType".to_string(), mime_type.clone());
+            }
+        }
+        Ok(resource)
+    }
+
+    /// Converts an entry of the server's resource list, without content.
+    fn to_resource(&self, listed: &Value) -> Resource {
+        let uri = listed["uri"].as_str().unwrap_or_default();
+        let title = listed["title"]
+            .as_str()
+            .or_else(|| listed["name"].as_str())
+            .unwrap_or(uri);
+        let updated_at = listed["annotations"]["lastModified"]
+            .as_str()
+            .and_then(|modified| DateTime::parse_from_rfc3339(modified).ok())
+            .map_or_else(Utc::now, |modified| modified.with_timezone(&Utc));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("uri".to_string(), json!(uri));
+        for key in ["description", "mimeType", "size"] {
+            if let Some(value) = listed.get(key).filter(|value| !value.is_null()) {
+                metadata.insert(key.to_string(), value.clone());
+            }
+        }
+
+        Resource {
+            id: format!("{}_{}", self.name, uri),
+            source: ResourceSource::Custom(self.name.to_string()),
+            title: title.to_string(),
+            content: String::new(),
+            metadata,
+            created_at: updated_at,
+            updated_at,
+            provenance: Some(Provenance {
+                provider: self.name.to_string(),
+                fetched_at: Utc::now(),
+                api_version: None,
+                request_id: None,
+                url: uri.starts_with("http").then(|| uri.to_string()),
+            }),
+        }
+    }
+
+    /// Reads each listed resource, a few at a time, keeping their order.
+    async fn read_all(&self, listed: &[Value]) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::with_capacity(listed.len());
+        for chunk in listed.chunks(CONCURRENT_READS) {
+            let reads = chunk
+                .iter()
+                .map(|listed| self.read(listed["uri"].as_str().unwrap_or_default(), Some(listed)));
+            resources.extend(futures::future::try_join_all(reads).await?);
+        }
+        Ok(resources)
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for McpClientAdapter {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let listed = self.list_all(query.limit).await?;
+        self.read_all(&listed).await
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let uri = id
+            .strip_prefix(self.name)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .ok_or_else(|| {
+                DomainError::ResourceNotFound(format!("Not a {} resource: {}", self.name, id))
+            })?;
+        self.read(uri, None).await
+    }
+
+    /// Matches the query against the names, titles, descriptions and URIs
+    /// the server lists; servers don't offer search over content.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let needle = query.to_lowercase();
+        let listed: Vec<Value> = self
+            .list_all(None)
+            .await?
+            .into_iter()
+            .filter(|listed| {
+                ["name", "title", "description", "uri"].iter().any(|key| {
+                    listed[key]
+                        .as_str()
+                        .is_some_and(|text| text.to_lowercase().contains(&needle))
+                })
+            })
+            .collect();
+        self.read_all(&listed).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn list_page(
+        &self,
+        _query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let (listed, next_cursor) = self.list(cursor).await?;
+        Ok(ListingPage {
+            resources: listed
+                .iter()
+                .map(|listed| self.to_resource(listed))
+                .collect(),
+            next_cursor,
+        })
+    }
+
+    async fn hydrate(&self, resource: Resource) -> Result<Resource, DomainError> {
+        let uri = resource
+            .metadata
+            .get("uri")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let mut hydrated = self.read(uri, None).await?;
+        hydrated.title = resource.title;
+        hydrated.metadata.extend(resource.metadata);
+        hydrated.created_at = resource.created_at;
+        hydrated.updated_at = resource.updated_at;
+        Ok(hydrated)
+    }
+
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        Ok(self
+            .list_all(None)
+            .await?
+            .iter()
+            .map(|listed| {
+                let resource = self.to_resource(listed);
+                DiscoveredItem {
+                    id: resource.id,
+                    kind: "resource".to_string(),
+                    title: resource.title,
+                    parent_id: None,
+                    url: listed["uri"].as_str().map(str::to_string),
+                }
+            })
+            .collect())
+    }
+
+    /// Starts the server, or connects to it, and completes the handshake.
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        self.connection().await.map(|_| ())
+    }
+}
@@ -0,0 +1,333 @@
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+
+use crate::{
+    domain::DomainError,
+    infrastructure::http::{HttpClient, RetryPolicy},
+    ports::cancellable,
+};
+
+/// How long a downstream server gets to answer one request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Header carrying the streamable HTTP transport's session ID.
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// JSON-RPC error code MCP servers use for unknown resources.
+const RESOURCE_NOT_FOUND: i64 = -32002;
+
+/// A connection to a downstream MCP server.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Sends a request and waits for its result.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, DomainError>;
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), DomainError>;
+}
+
+/// Unwraps a JSON-RPC response into its result.
+fn into_result(server: &str, response: Value) -> Result<Value, DomainError> {
+    match response.get("error") {
+        Some(error) => {
+            let message = error["message"].as_str().unwrap_or("unknown error");
+            Err(match error["code"].as_i64() {
+                Some(RESOURCE_NOT_FOUND) => DomainError::ResourceNotFound(message.to_string()),
+                _ => DomainError::ProviderError(format!("{}: {}", server, message)),
+            })
+        }
+        None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// A server run as a child process, spoken to over its stdin and stdout.
+/// The process is killed when the transport is dropped.
+pub struct StdioTransport {
+    server: &'static str,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    _child: Child,
+}
+
+impl StdioTransport {
+    pub fn spawn(
+        server: &'static str,
+        command: &str,
+        args: &[String],
+        env: &BTreeMap<String, String>,
+    ) -> Result<Self, DomainError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                DomainError::ProviderError(format!("Failed to start {}: {}", command, e))
+            })?;
+
+        let stdin = Arc::new(tokio::sync::Mutex::new(
+            child.stdin.take().expect("stdin is piped"),
+        ));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let pending = Pending::default();
+        tokio::spawn(read_messages(
+            server,
+            BufReader::new(stdout),
+            stdin.clone(),
+            pending.clone(),
+        ));
+
+        Ok(Self {
+            server,
+            stdin,
+            pending,
+            next_id: AtomicU64::new(1),
+            _child: child,
+        })
+    }
+
+    async fn write(&self, message: &Value) -> Result<(), DomainError> {
+        write_line(&self.stdin, message).await.map_err(|e| {
+            DomainError::ProviderError(format!("{} connection failed: {}", self.server, e))
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for StdioTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, DomainError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let call = async {
+            self.write(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+                .await?;
+            match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+                Ok(Ok(response)) => Ok(response),
+                Ok(Err(_)) => Err(DomainError::ProviderError(format!(
+                    "{} closed the connection",
+                    self.server
+                ))),
+                Err(_) => Err(DomainError::ProviderError(format!(
+                    "{} didn't answer {} in time",
+                    self.server, method
+                ))),
+            }
+        };
+        let response = cancellable(call).await;
+        // Nothing is waiting for a response that didn't arrive.
+        self.pending.lock().unwrap().remove(&id);
+        into_result(self.server, response?)
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), DomainError> {
+        self.write(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+}
+
+async fn write_line(
+    stdin: &tokio::sync::Mutex<ChildStdin>,
+    message: &Value,
+) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&line).await?;
+    stdin.flush().await
+}
+
+/// Routes the server's responses to the requests awaiting them until it
+/// exits. Requests from the server are answered: `ping` with an empty
+/// result, anything else as unsupported.
+async fn read_messages(
+    server: &'static str,
+    stdout: BufReader<tokio::process::ChildStdout>,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+) {
+    let mut lines = stdout.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            tracing::debug!("Ignoring non-JSON output from {}: {}", server, line);
+            continue;
+        };
+
+        match (
+            message.get("id"),
+            message.get("method").and_then(Value::as_str),
+        ) {
+            (Some(id), None) => {
+                let sender = id
+                    .as_u64()
+                    .and_then(|id| pending.lock().unwrap().remove(&id));
+                if let Some(sender) = sender {
+                    let _ = sender.send(message);
+                }
+            }
+            (Some(id), Some(method)) => {
+                let reply = if method == "ping" {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+                    })
+                };
+                let _ = write_line(&stdin, &reply).await;
+            }
+            (None, Some(method)) => tracing::debug!("{} sent {}", server, method),
+            (None, None) => {}
+        }
+    }
+
+    tracing::debug!("{} exited", server);
+    // Dropping the senders fails the requests still waiting.
+    pending.lock().unwrap().clear();
+}
+
+/// A server reached over the streamable HTTP transport.
+pub struct HttpTransport {
+    server: &'static str,
+    http: HttpClient,
+    url: String,
+    /// Assigned by the server in its answer to `initialize`.
+    session_id: Mutex<Option<String>>,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    pub fn new(
+        server: &'static str,
+        url: String,
+        token: Option<String>,
+        retry: RetryPolicy,
+    ) -> Result<Self, DomainError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/json, text/event-stream"),
+        );
+        if let Some(token) = &token {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token))
+                    .map_err(|e| DomainError::ProviderError(e.to_string()))?,
+            );
+        }
+
+        let http =
+            HttpClient::new(server, headers, token.into_iter().collect())?.with_retry_policy(retry);
+        Ok(Self {
+            server,
+            http,
+            url,
+            session_id: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response, DomainError> {
+        let mut request = self
+            .http
+            .post(&self.url)
+            .json(message)
+            .timeout(REQUEST_TIMEOUT);
+        if let Some(session_id) = self.session_id.lock().unwrap().clone() {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+
+        let response = self.http.send(request).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(DomainError::ProviderError(format!(
+                "{} returned {}: {}",
+                self.server, status, body
+            )));
+        }
+
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            *self.session_id.lock().unwrap() = Some(session_id.to_string());
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, DomainError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let response = self
+            .post(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+
+        let streamed = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        let body = response
+            .text()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+
+        // A stream may carry notifications and requests ahead of the
+        // response.
+        let messages: Vec<Value> = if streamed {
+            event_data(&body)
+                .filter_map(|data| serde_json::from_str(&data).ok())
+                .collect()
+        } else {
+            vec![serde_json::from_str(&body).map_err(|e| {
+                DomainError::ProviderError(format!("{} sent invalid JSON: {}", self.server, e))
+            })?]
+        };
+        let response = messages
+            .into_iter()
+            .find(|message| message["id"] == id && message.get("method").is_none())
+            .ok_or_else(|| {
+                DomainError::ProviderError(format!("{} didn't answer {}", self.server, method))
+            })?;
+        into_result(self.server, response)
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), DomainError> {
+        self.post(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// The data of each event in a Server-Sent Events body.
+fn event_data(body: &str) -> impl Iterator<Item = String> + '_ {
+    body.split("\n\n").filter_map(|event| {
+        let data: Vec<&str> = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        (!data.is_empty()).then(|| data.join("\n"))
+    })
+}
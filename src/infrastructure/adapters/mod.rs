@@ -1,2 +1,64 @@
+pub mod azuredevops;
+pub mod coda;
+pub mod elasticsearch;
+pub mod fixture;
+pub mod graphql;
+pub mod imap;
 pub mod linear;
+pub mod mcp;
 pub mod notion;
+pub mod rest;
+#[cfg(feature = "sql")]
+pub mod sql;
+pub mod todoist;
+pub mod trello;
+pub mod vault;
+pub mod zendesk;
+
+use crate::domain::DomainError;
+
+/// Provider names the built-in adapters already use.
+const RESERVED_NAMES: [&str; 9] = [
+    "notion",
+    "linear",
+    "trello",
+    "zendesk",
+    "azuredevops",
+    "todoist",
+    "coda",
+    "imap",
+    "fixture",
+];
+
+/// Checks the name a `kind` of provider, such as an upstream, is configured
+/// under. It becomes the provider name and ID prefix, so it may only hold
+/// lowercase letters, digits and `-`, and must not be a built-in provider's.
+pub fn provider_name(kind: &str, name: &str) -> Result<&'static str, DomainError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid || RESERVED_NAMES.contains(&name) {
+        return Err(DomainError::InvalidQuery(format!(
+            "Invalid {} name {:?}: use lowercase letters, digits and '-', other than {}",
+            kind,
+            name,
+            RESERVED_NAMES.join(", ")
+        )));
+    }
+    // Provider names are static; these providers are only configured at
+    // startup and when the config file changes.
+    Ok(Box::leak(name.to_string().into_boxed_str()))
+}
+
+/// `text` with everything but unreserved URI characters percent-encoded.
+pub fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,284 @@
+use serde_json::{json, Value};
+
+/// Longest text Notion accepts in one rich text object.
+const MAX_TEXT_CHARS: usize = 2000;
+
+/// Code block languages Notion knows, under the names Markdown fences
+/// commonly use for them.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("bash", "bash"),
+    ("c", "c"),
+    ("cpp", "c++"),
+    ("c++", "c++"),
+    ("cs", "c#"),
+    ("csharp", "c#"),
+    ("css", "css"),
+    ("diff", "diff"),
+    ("dockerfile", "docker"),
+    ("go", "go"),
+    ("graphql", "graphql"),
+    ("html", "html"),
+    ("java", "java"),
+    ("javascript", "javascript"),
+    ("js", "javascript"),
+    ("json", "json"),
+    ("kotlin", "kotlin"),
+    ("makefile", "makefile"),
+    ("markdown", "markdown"),
+    ("md", "markdown"),
+    ("mermaid", "mermaid"),
+    ("nix", "nix"),
+    ("php", "php"),
+    ("powershell", "powershell"),
+    ("py", "python"),
+    ("python", "python"),
+    ("rb", "ruby"),
+    ("ruby", "ruby"),
+    ("rs", "rust"),
+    ("rust", "rust"),
+    ("sh", "shell"),
+    ("shell", "shell"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("ts", "typescript"),
+    ("typescript", "typescript"),
+    ("xml", "xml"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+];
+
+/// Converts Markdown to Notion blocks: headings, paragraphs, bulleted,
+/// numbered and to-do list items, quotes, fenced code and dividers, with
+/// bold, italic, strikethrough, inline code and links within them. Nested
+/// list items are appended flat. Items marked `•`, as page content shows
+/// list items, are bulleted too.
+pub fn from_markdown(markdown: &str) -> Vec<Value> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = markdown.lines();
+
+    let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Value>| {
+        if !paragraph.is_empty() {
+            blocks.push(text_block("paragraph", &paragraph.join("\n")));
+            paragraph.clear();
+        }
+    };
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks);
+            continue;
+        }
+
+        if let Some(language) = trimmed.strip_prefix("```") {
+            flush(&mut paragraph, &mut blocks);
+            let code: Vec<&str> = lines
+                .by_ref()
+                .take_while(|line| !line.trim_start().starts_with("```"))
+                .collect();
+            blocks.push(code_block(&code.join("\n"), language.trim()));
+            continue;
+        }
+
+        let block = if let Some((level, text)) = heading(trimmed) {
+            text_block(&format!("heading_{}", level), text)
+        } else if matches!(trimmed, "---" | "***" | "___") {
+            json!({ "object": "block", "type": "divider", "divider": {} })
+        } else if let Some(text) = trimmed.strip_prefix("> ").or(trimmed.strip_prefix('>')) {
+            text_block("quote", text.trim_start())
+        } else if let Some((checked, text)) = to_do(trimmed) {
+            let mut block = text_block("to_do", text);
+            block["to_do"]["checked"] = json!(checked);
+            block
+        } else if let Some(text) = ["- ", "* ", "+ ", "• "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            text_block("bulleted_list_item", text)
+        } else if let Some(text) = numbered(trimmed) {
+            text_block("numbered_list_item", text)
+        } else {
+            paragraph.push(trimmed);
+            continue;
+        };
+        flush(&mut paragraph, &mut blocks);
+        blocks.push(block);
+    }
+    flush(&mut paragraph, &mut blocks);
+    blocks
+}
+
+/// Level (1 to 3; deeper headings count as 3) and text of a `#` heading.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6)
+        .contains(&level)
+        .then(|| (level.min(3), text.trim()))
+}
+
+/// Whether a `- [ ]` or `- [x]` item is checked, and its text.
+fn to_do(line: &str) -> Option<(bool, &str)> {
+    let item = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))?;
+    if let Some(text) = item.strip_prefix("[ ] ") {
+        Some((false, text))
+    } else {
+        let text = item.strip_prefix("[x] ").or(item.strip_prefix("[X] "))?;
+        Some((true, text))
+    }
+}
+
+/// Text of a `1.` or `1)` list item.
+fn numbered(line: &str) -> Option<&str> {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    line[digits..]
+        .strip_prefix(". ")
+        .or(line[digits..].strip_prefix(") "))
+}
+
+fn text_block(block_type: &str, text: &str) -> Value {
+    json!({
+        "object": "block",
+        "type": block_type,
+        block_type: { "rich_text": rich_text(text) },
+    })
+}
+
+fn code_block(code: &str, language: &str) -> Value {
+    let language = LANGUAGES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(language))
+        .map_or("plain text", |(_, notion)| notion);
+    json!({
+        "object": "block",
+        "type": "code",
+        "code": {
+            "rich_text": spans(code, &Style::default(), None),
+            "language": language,
+        },
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    strikethrough: bool,
+    code: bool,
+}
+
+impl Style {
+    /// The annotation an emphasis marker turns on and off.
+    fn flag(&mut self, marker: &str) -> &mut bool {
+        match marker {
+            "**" | "__" => &mut self.bold,
+            "~~" => &mut self.strikethrough,
+            _ => &mut self.italic,
+        }
+    }
+}
+
+/// Inline Markdown as Notion rich text.
+fn rich_text(text: &str) -> Vec<Value> {
+    let mut rich_text = Vec::new();
+    let mut style = Style::default();
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut previous: Option<char> = None;
+
+    'scan: while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                rich_text.extend(spans(&std::mem::take(&mut plain), &style, None));
+                let code = Style {
+                    code: true,
+                    ..style
+                };
+                rich_text.extend(spans(&after[..end], &code, None));
+                rest = &after[end + 1..];
+                previous = Some('`');
+                continue;
+            }
+        }
+
+        if let Some((label, url, after)) = link(rest) {
+            rich_text.extend(spans(&std::mem::take(&mut plain), &style, None));
+            rich_text.extend(spans(label, &style, Some(url)));
+            rest = after;
+            previous = Some(')');
+            continue;
+        }
+
+        for marker in ["**", "__", "~~", "*", "_"] {
+            let Some(after) = rest.strip_prefix(marker) else {
+                continue;
+            };
+            let open = *style.flag(marker);
+            // Emphasis hugs its text, as in `*this*` but not `2 * 3 * 4`,
+            // and underscores inside words, as in snake_case, aren't
+            // emphasis at all.
+            let (inner, outer) = if open {
+                (previous, after.chars().next())
+            } else {
+                (after.chars().next(), previous)
+            };
+            let flanking = inner.is_some_and(|c| !c.is_whitespace())
+                && (!marker.starts_with('_') || outer.is_none_or(|c| !c.is_alphanumeric()));
+            if !flanking {
+                continue;
+            }
+            if open || after.contains(marker) {
+                rich_text.extend(spans(&std::mem::take(&mut plain), &style, None));
+                let flag = style.flag(marker);
+                *flag = !*flag;
+                rest = after;
+                previous = marker.chars().last();
+                continue 'scan;
+            }
+        }
+
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+        previous = Some(c);
+    }
+    rich_text.extend(spans(&plain, &style, None));
+    rich_text
+}
+
+/// The label and URL of a `[label](url)` link at the start of `text`, and
+/// what follows it.
+fn link(text: &str) -> Option<(&str, &str, &str)> {
+    let text = text.strip_prefix('[')?;
+    let (label, rest) = text.split_once("](")?;
+    let (url, rest) = rest.split_once(')')?;
+    (!label.contains(']') && !url.contains(char::is_whitespace)).then_some((label, url, rest))
+}
+
+/// Rich text objects for `text`, split to stay within Notion's limit.
+fn spans(text: &str, style: &Style, url: Option<&str>) -> Vec<Value> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .chunks(MAX_TEXT_CHARS)
+        .map(|chunk| {
+            json!({
+                "type": "text",
+                "text": {
+                    "content": chunk.iter().collect::<String>(),
+                    "link": url.map(|url| json!({ "url": url })),
+                },
+                "annotations": {
+                    "bold": style.bold,
+                    "italic": style.italic,
+                    "strikethrough": style.strikethrough,
+                    "code": style.code,
+                },
+            })
+        })
+        .collect()
+}
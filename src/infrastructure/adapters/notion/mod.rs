@@ -1,12 +1,19 @@
+mod blocks;
+
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
 
 use crate::{
-    domain::{DomainError, Query, Resource, ResourceSource},
-    ports::ResourceProvider,
+    domain::{
+        Attachment, Blob, Change, DiscoveredItem, DomainError, ListingPage, Provenance, Query,
+        Resource, ResourceSource, SyncEstimate,
+    },
+    infrastructure::http::{self, HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,7 +37,6 @@ struct NotionQueryResponse {
 
 #[derive(Debug, Deserialize)]
 struct NotionBlock {
-    id: String,
     #[serde(rename = "type")]
     block_type: String,
     #[serde(flatten)]
@@ -44,9 +50,29 @@ struct NotionBlocksResponse {
     next_cursor: Option<String>,
 }
 
+/// Notion API version requested via the `Notion-Version` header.
+const NOTION_VERSION: &str = "2022-06-28";
+
+/// Largest page size accepted by the Notion API.
+const NOTION_MAX_PAGE_SIZE: usize = 100;
+
+/// Most blocks Notion appends in one request.
+const NOTION_MAX_APPENDED_BLOCKS: usize = 100;
+
+/// Requests a second Notion allows on average.
+const NOTION_REQUESTS_PER_SECOND: f64 = 3.0;
+
+/// Pages whose blocks are fetched to estimate what the rest take.
+const ESTIMATE_SAMPLE_PAGES: usize = 5;
+
+/// Block types that hold a file, uploaded or linked.
+const FILE_BLOCK_TYPES: [&str; 5] = ["file", "image", "pdf", "video", "audio"];
+
 pub struct NotionAdapter {
-    client: reqwest::Client,
-    api_key: String,
+    http: HttpClient,
+    /// Downloads uploaded files, which Notion hosts elsewhere under signed
+    /// URLs; the API key isn't sent along.
+    files: HttpClient,
 }
 
 impl NotionAdapter {
@@ -58,14 +84,29 @@ impl NotionAdapter {
                 .map_err(|e| DomainError::ProviderError(e.to_string()))?,
         );
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        headers.insert("Notion-Version", HeaderValue::from_static("2022-06-28"));
+        headers.insert("Notion-Version", HeaderValue::from_static(NOTION_VERSION));
 
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        let http = HttpClient::new("Notion", headers, vec![api_key])?
+            .with_retry_policy(Self::default_retry_policy());
+        let files = HttpClient::new("Notion files", HeaderMap::new(), Vec::new())?;
 
-        Ok(Self { client, api_key })
+        Ok(Self { http, files })
+    }
+
+    /// Notion allows ~3 requests/second and answers bursts with 429 and a
+    /// `Retry-After` header, so retry generously.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![409, 429, 500, 502, 503, 504],
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
     }
 
     async fn get_page_blocks(&self, page_id: &str) -> Result<Vec<NotionBlock>, DomainError> {
@@ -74,16 +115,13 @@ impl NotionAdapter {
         let mut start_cursor: Option<String> = None;
 
         loop {
-            let mut request = self.client.get(&url);
+            let mut request = self.http.get(&url);
 
             if let Some(cursor) = &start_cursor {
                 request = request.query(&[("start_cursor", cursor)]);
             }
 
-            let response = request
-                .send()
-                .await
-                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            let response = self.http.send(request).await?;
 
             if !response.status().is_success() {
                 let error_text = response
@@ -102,6 +140,13 @@ impl NotionAdapter {
                 .map_err(|e| DomainError::ProviderError(e.to_string()))?;
 
             all_blocks.extend(blocks_response.results);
+            report_progress(|| {
+                format!(
+                    "Fetched {} blocks of Notion page {}",
+                    all_blocks.len(),
+                    page_id
+                )
+            });
 
             if !blocks_response.has_more {
                 break;
@@ -113,12 +158,247 @@ impl NotionAdapter {
         Ok(all_blocks)
     }
 
+    /// Appends `markdown`, converted to blocks, to a page in as many
+    /// requests as it takes: after the block `after`, or else at the end.
+    async fn append_blocks(
+        &self,
+        page_id: &str,
+        markdown: &str,
+        after: Option<&str>,
+    ) -> Result<(), DomainError> {
+        let url = format!("https://api.notion.com/v1/blocks/{}/children", page_id);
+        let children = blocks::from_markdown(markdown);
+        if children.is_empty() {
+            return Err(DomainError::InvalidQuery(
+                "Nothing to append to the Notion page".to_string(),
+            ));
+        }
+
+        let mut after = after.map(|id| id.to_string());
+        for batch in children.chunks(NOTION_MAX_APPENDED_BLOCKS) {
+            let mut body = serde_json::json!({ "children": batch });
+            if let Some(after) = &after {
+                body["after"] = serde_json::json!(after);
+            }
+            let response = self.http.send(self.http.patch(&url).json(&body)).await?;
+            if !response.status().is_success() {
+                let error_text = response
+                    .text()
+                    .await
+                    .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+                return Err(DomainError::ProviderError(format!(
+                    "Notion API error: {}",
+                    error_text
+                )));
+            }
+            // The next batch goes after the last block of this one.
+            if after.is_some() {
+                let appended: NotionBlocksResponse = response
+                    .json()
+                    .await
+                    .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+                after = appended
+                    .results
+                    .last()
+                    .and_then(block_id)
+                    .map(str::to_string);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces a page's content with `markdown`. Blocks at the start and
+    /// end whose text is unchanged are kept, formatting and all, as are
+    /// blocks the content doesn't show, such as images and child pages;
+    /// only the blocks from the first changed line to the last are
+    /// replaced. Notion can't insert before a page's first block, so a
+    /// change there replaces everything the content shows.
+    async fn replace_content(&self, page_id: &str, markdown: &str) -> Result<(), DomainError> {
+        let blocks = self.get_page_blocks(page_id).await?;
+        let shown: Vec<String> = blocks
+            .iter()
+            .map(|block| self.extract_text_from_blocks(std::slice::from_ref(block)))
+            .collect();
+        let lines: Vec<&str> = markdown.lines().collect();
+        let shows = |text: &str, lines: &[&str]| {
+            let text: Vec<&str> = text.lines().collect();
+            text.len() <= lines.len()
+                && text
+                    .iter()
+                    .zip(lines)
+                    .all(|(shown, line)| shown.trim_end() == line.trim_end())
+        };
+
+        // Blocks kept at the start, and the first line not shown by them.
+        let (mut start, mut first) = (0, 0);
+        while start < blocks.len() {
+            if !shown[start].is_empty() {
+                let at = first
+                    + lines[first..]
+                        .iter()
+                        .take_while(|line| line.trim().is_empty())
+                        .count();
+                if !shows(&shown[start], &lines[at..]) {
+                    break;
+                }
+                first = at + shown[start].lines().count();
+            }
+            start += 1;
+        }
+        // Blocks kept at the end, and the line after the last not shown by
+        // them. Without a block to insert after, there are none.
+        let (mut end, mut last) = (blocks.len(), lines.len());
+        while start > 0 && end > start {
+            if !shown[end - 1].is_empty() {
+                let at = last
+                    - lines[first..last]
+                        .iter()
+                        .rev()
+                        .take_while(|line| line.trim().is_empty())
+                        .count();
+                let count = shown[end - 1].lines().count();
+                if at - first < count || !shows(&shown[end - 1], &lines[at - count..at]) {
+                    break;
+                }
+                last = at - count;
+            }
+            end -= 1;
+        }
+
+        let replaced: Vec<&NotionBlock> = blocks[start..end]
+            .iter()
+            .zip(&shown[start..end])
+            .filter(|(_, shown)| !shown.is_empty())
+            .map(|(block, _)| block)
+            .collect();
+        if replaced.iter().any(|block| {
+            block
+                .content
+                .get("has_children")
+                .and_then(|children| children.as_bool())
+                .unwrap_or(false)
+        }) {
+            return Err(DomainError::InvalidQuery(format!(
+                "Changing Notion page {} there would delete nested blocks the content doesn't show; edit it in Notion instead",
+                page_id
+            )));
+        }
+
+        // The old blocks go first, so a failure can't leave the page showing
+        // both versions; one after the page changed says so.
+        let partly = |changed: bool, e: DomainError| {
+            if changed {
+                DomainError::ProviderError(format!(
+                    "Notion page {} was only partly updated; try again or fix it in Notion: {}",
+                    page_id, e
+                ))
+            } else {
+                e
+            }
+        };
+        let mut changed = false;
+        for block in replaced {
+            let Some(id) = block_id(block) else {
+                continue;
+            };
+            let url = format!("https://api.notion.com/v1/blocks/{}", id);
+            let response = self
+                .http
+                .send(self.http.delete(&url))
+                .await
+                .map_err(|e| partly(changed, e))?;
+            if !response.status().is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                return Err(partly(
+                    changed,
+                    DomainError::ProviderError(format!("Notion API error: {}", error_text)),
+                ));
+            }
+            changed = true;
+        }
+        let added = lines[first..last].join("\n");
+        if !blocks::from_markdown(&added).is_empty() {
+            let after = start.checked_sub(1).and_then(|i| block_id(&blocks[i]));
+            self.append_blocks(page_id, &added, after)
+                .await
+                .map_err(|e| partly(changed, e))?;
+        }
+        Ok(())
+    }
+
+    /// Renames a page through its title property, whatever it is called.
+    async fn set_title(&self, page_id: &str, title: &str) -> Result<(), DomainError> {
+        let (page_data, _) = self.get_page(page_id).await?;
+        let property = page_data
+            .get("properties")
+            .and_then(|properties| properties.as_object())
+            .and_then(|properties| {
+                properties.iter().find(|(_, property)| {
+                    property.get("type").and_then(|t| t.as_str()) == Some("title")
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| "title".to_string());
+
+        let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+        let body = serde_json::json!({
+            "properties": {
+                property: { "title": [{ "type": "text", "text": { "content": title } }] }
+            }
+        });
+        let response = self.http.send(self.http.patch(&url).json(&body)).await?;
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(DomainError::ProviderError(format!(
+                "Notion API error: {}",
+                error_text
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fills in `resource`'s content and attachments from its blocks.
+    fn fill_from_blocks(&self, resource: &mut Resource, blocks: &[NotionBlock]) {
+        resource.content = self.extract_text_from_blocks(blocks);
+        let attachments = attachments_from_blocks(blocks);
+        if !attachments.is_empty() {
+            resource
+                .metadata
+                .insert("attachments".to_string(), serde_json::json!(attachments));
+        }
+    }
+
     fn extract_text_from_blocks(&self, blocks: &[NotionBlock]) -> String {
         let mut text = String::new();
 
         for block in blocks {
             match block.block_type.as_str() {
-                "paragraph" | "heading_1" | "heading_2" | "heading_3" => {
+                // Headings are marked up as in Markdown, so content can be
+                // split into sections.
+                "heading_1" | "heading_2" | "heading_3" => {
+                    if let Some(rich_text_array) = block
+                        .content
+                        .get(&block.block_type)
+                        .and_then(|content| content.get("rich_text"))
+                        .and_then(|rt| rt.as_array())
+                    {
+                        let level = &block.block_type["heading_".len()..];
+                        text.push_str(&"#".repeat(level.parse().unwrap_or(1)));
+                        text.push(' ');
+                        for rich_text in rich_text_array {
+                            if let Some(plain_text) =
+                                rich_text.get("plain_text").and_then(|pt| pt.as_str())
+                            {
+                                text.push_str(plain_text);
+                            }
+                        }
+                        text.push('\n');
+                    }
+                }
+                "paragraph" => {
                     if let Some(content) = block.content.get(&block.block_type) {
                         if let Some(rich_text_array) =
                             content.get("rich_text").and_then(|rt| rt.as_array())
@@ -158,9 +438,187 @@ impl NotionAdapter {
         text
     }
 
+    /// Queries one page of a database's rows, returning it with the
+    /// request ID of the response.
+    async fn query_database(
+        &self,
+        database_id: &str,
+        page_size: usize,
+        start_cursor: Option<String>,
+    ) -> Result<(NotionQueryResponse, Option<String>), DomainError> {
+        let url = format!("https://api.notion.com/v1/databases/{}/query", database_id);
+        let notion_query = NotionDatabaseQuery {
+            filter: None,
+            sorts: None,
+            start_cursor,
+            page_size: Some(page_size as u32),
+        };
+
+        let response = self
+            .http
+            .send(self.http.post(&url).json(&notion_query))
+            .await?;
+        let request_id = http::request_id(&response);
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(DomainError::ProviderError(format!(
+                "Notion API error: {}",
+                error_text
+            )));
+        }
+
+        let query_response = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((query_response, request_id))
+    }
+
+    /// Lists one page of the pages shared with the integration, most
+    /// recently edited first.
+    async fn search_pages(
+        &self,
+        page_size: usize,
+        start_cursor: Option<String>,
+    ) -> Result<(NotionQueryResponse, Option<String>), DomainError> {
+        let mut body = serde_json::json!({
+            "filter": { "property": "object", "value": "page" },
+            "sort": { "direction": "descending", "timestamp": "last_edited_time" },
+            "page_size": page_size,
+        });
+        if let Some(cursor) = start_cursor {
+            body["start_cursor"] = serde_json::Value::String(cursor);
+        }
+
+        let response = self
+            .http
+            .send(
+                self.http
+                    .post("https://api.notion.com/v1/search")
+                    .json(&body),
+            )
+            .await?;
+        let request_id = http::request_id(&response);
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(DomainError::ProviderError(format!(
+                "Notion search error: {}",
+                error_text
+            )));
+        }
+
+        let query_response = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((query_response, request_id))
+    }
+
+    /// Retrieves a page's properties, returning them with the request ID of
+    /// the response.
+    async fn get_page(
+        &self,
+        page_id: &str,
+    ) -> Result<(serde_json::Value, Option<String>), DomainError> {
+        let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+
+        let response = self.http.send(self.http.get(&url)).await?;
+        let request_id = http::request_id(&response);
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(DomainError::ResourceNotFound(format!(
+                "Notion page not found: {}",
+                error_text
+            )));
+        }
+
+        let page_data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((page_data, request_id))
+    }
+
+    /// Fetches `root` and the pages nested under it, breadth first, up to
+    /// `limit` pages. Child pages are found among each page's blocks, so
+    /// every page costs two requests. Pages that fail below the root are
+    /// skipped.
+    async fn fetch_page_tree(
+        &self,
+        root: &str,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::new();
+        let mut queue = VecDeque::from([root.to_string()]);
+        let mut seen = HashSet::new();
+
+        while let Some(page_id) = queue.pop_front() {
+            if resources.len() >= limit {
+                break;
+            }
+            if !seen.insert(page_id.replace('-', "")) {
+                continue;
+            }
+
+            let page = match self.get_page(&page_id).await {
+                Ok(page) => page,
+                Err(e) if !resources.is_empty() => {
+                    tracing::warn!("Skipping Notion page {}: {}", page_id, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let (page_data, request_id) = page;
+            let mut resource = self.page_to_stub(&page_data, false, request_id)?;
+
+            let blocks = self.get_page_blocks(&page_id).await?;
+            self.fill_from_blocks(&mut resource, &blocks);
+            queue.extend(
+                blocks
+                    .iter()
+                    .filter(|block| block.block_type == "child_page")
+                    .filter_map(|block| block.content.get("id")?.as_str())
+                    .map(|id| id.to_string()),
+            );
+
+            resources.push(resource);
+        }
+
+        Ok(resources)
+    }
+
+    /// Converts a page to a resource, fetching its blocks as the content.
     async fn page_to_resource(
         &self,
         page_data: &serde_json::Value,
+        records: bool,
+        request_id: Option<String>,
+    ) -> Result<Resource, DomainError> {
+        let resource = self.page_to_stub(page_data, records, request_id)?;
+        self.hydrate(resource).await
+    }
+
+    /// Converts a page to a resource without fetching its blocks. In records
+    /// mode the page's properties are parsed into a `record` metadata entry
+    /// and rendered as the content; otherwise the content is left for
+    /// `hydrate` to fill in.
+    fn page_to_stub(
+        &self,
+        page_data: &serde_json::Value,
+        records: bool,
+        request_id: Option<String>,
     ) -> Result<Resource, DomainError> {
         let page_id = page_data
             .get("id")
@@ -169,9 +627,6 @@ impl NotionAdapter {
 
         let title = self.extract_title_from_page(page_data);
 
-        let blocks = self.get_page_blocks(page_id).await?;
-        let content = self.extract_text_from_blocks(&blocks);
-
         let created_at = page_data
             .get("created_time")
             .and_then(|ct| ct.as_str())
@@ -186,29 +641,119 @@ impl NotionAdapter {
             .map(|dt| dt.with_timezone(&Utc))
             .unwrap_or_else(Utc::now);
 
+        let database_id = page_data
+            .get("parent")
+            .and_then(|parent| parent.get("database_id"))
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string());
+
         let mut metadata = HashMap::new();
         if let Some(props) = page_data.get("properties") {
             metadata.insert("properties".to_string(), props.clone());
         }
 
+        let archived = ["archived", "in_trash"]
+            .iter()
+            .any(|flag| page_data.get(flag).and_then(|v| v.as_bool()) == Some(true));
+        if archived {
+            metadata.insert("archived".to_string(), serde_json::json!(true));
+        }
+
+        let content = if records {
+            let record = page_data
+                .get("properties")
+                .and_then(|props| props.as_object())
+                .map(|props| {
+                    props
+                        .iter()
+                        .map(|(name, property)| (name.clone(), property_value(property)))
+                        .collect::<serde_json::Map<_, _>>()
+                })
+                .unwrap_or_default();
+
+            let content = record
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, display_value(value)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            metadata.insert("record".to_string(), serde_json::Value::Object(record));
+            content
+        } else {
+            String::new()
+        };
+
         Ok(Resource {
             id: format!("notion_{}", page_id),
             source: ResourceSource::Notion {
                 page_id: page_id.to_string(),
-                database_id: None,
+                database_id,
             },
             title,
             content,
             metadata,
             created_at,
             updated_at,
+            provenance: Some(Provenance {
+                provider: "notion".to_string(),
+                fetched_at: Utc::now(),
+                api_version: Some(NOTION_VERSION.to_string()),
+                request_id,
+                url: page_data
+                    .get("url")
+                    .and_then(|u| u.as_str())
+                    .map(|u| u.to_string()),
+            }),
+        })
+    }
+
+    /// Summarizes a page or database returned by the search endpoint.
+    fn object_to_item(&self, object: &serde_json::Value) -> Option<DiscoveredItem> {
+        let id = object.get("id")?.as_str()?.to_string();
+        let kind = object.get("object")?.as_str()?.to_string();
+
+        let title = if kind == "database" {
+            object
+                .get("title")
+                .and_then(|t| t.as_array())
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .filter_map(|part| part.get("plain_text").and_then(|t| t.as_str()))
+                        .collect::<String>()
+                })
+                .filter(|title| !title.is_empty())
+                .unwrap_or_else(|| "Untitled".to_string())
+        } else {
+            self.extract_title_from_page(object)
+        };
+
+        // `parent` is `{"type": "page_id", "page_id": "..."}`, or
+        // `{"type": "workspace", "workspace": true}` for top-level items.
+        let parent_id = object.get("parent").and_then(|parent| {
+            let parent_type = parent.get("type")?.as_str()?;
+            parent.get(parent_type)?.as_str().map(|id| id.to_string())
+        });
+
+        Some(DiscoveredItem {
+            id,
+            kind,
+            title,
+            parent_id,
+            url: object
+                .get("url")
+                .and_then(|u| u.as_str())
+                .map(|u| u.to_string()),
         })
     }
 
     fn extract_title_from_page(&self, page_data: &serde_json::Value) -> String {
         if let Some(properties) = page_data.get("properties") {
             // Try to find a title property
-            for (key, value) in properties.as_object().unwrap_or(&serde_json::Map::new()) {
+            for value in properties
+                .as_object()
+                .unwrap_or(&serde_json::Map::new())
+                .values()
+            {
                 if let Some(title_array) = value.get("title").and_then(|t| t.as_array()) {
                     if let Some(first_title) = title_array.first() {
                         if let Some(plain_text) =
@@ -227,47 +772,63 @@ impl NotionAdapter {
 #[async_trait]
 impl ResourceProvider for NotionAdapter {
     async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(NOTION_MAX_PAGE_SIZE);
+        if let Some(root) = query.filters.get("page_tree") {
+            return self.fetch_page_tree(root, limit).await;
+        }
+
         // For now, we'll need a database_id from the query filters
         let database_id = query.filters.get("database_id").ok_or_else(|| {
-            DomainError::InvalidQuery("database_id required for Notion queries".to_string())
+            DomainError::InvalidQuery(
+                "database_id or page_tree required for Notion queries".to_string(),
+            )
         })?;
 
-        let url = format!("https://api.notion.com/v1/databases/{}/query", database_id);
+        let page_size = query
+            .page_size()?
+            .unwrap_or(NOTION_MAX_PAGE_SIZE)
+            .min(NOTION_MAX_PAGE_SIZE)
+            .min(limit.max(1));
 
-        let notion_query = NotionDatabaseQuery {
-            filter: None,
-            sorts: None,
-            start_cursor: None,
-            page_size: query.limit.map(|l| l as u32),
-        };
+        let mut results = Vec::new();
+        let mut start_cursor: Option<String> = None;
 
-        let response = self
-            .client
-            .post(&url)
-            .json(&notion_query)
-            .send()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        while results.len() < limit {
+            let (query_response, request_id) = self
+                .query_database(
+                    database_id,
+                    page_size.min(limit - results.len()),
+                    start_cursor.take(),
+                )
+                .await?;
 
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
-            return Err(DomainError::ProviderError(format!(
-                "Notion API error: {}",
-                error_text
-            )));
+            results.extend(
+                query_response
+                    .results
+                    .into_iter()
+                    .map(|page| (page, request_id.clone())),
+            );
+            report_progress(|| format!("Listed {} Notion database rows", results.len()));
+
+            if !query_response.has_more {
+                break;
+            }
+
+            start_cursor = query_response.next_cursor;
+            if start_cursor.is_none() {
+                break;
+            }
         }
+        results.truncate(limit);
 
-        let query_response: NotionQueryResponse = response
-            .json()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        let records = query
+            .filters
+            .get("records")
+            .is_some_and(|value| value == "true");
 
         let mut resources = Vec::new();
-        for page_data in query_response.results {
-            match self.page_to_resource(&page_data).await {
+        for (page_data, request_id) in results {
+            match self.page_to_resource(&page_data, records, request_id).await {
                 Ok(resource) => resources.push(resource),
                 Err(e) => tracing::warn!("Failed to convert page to resource: {}", e),
             }
@@ -276,36 +837,73 @@ impl ResourceProvider for NotionAdapter {
         Ok(resources)
     }
 
-    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
-        // Remove the "notion_" prefix if present
-        let page_id = id.strip_prefix("notion_").unwrap_or(id);
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        // Page trees are walked in one go, content included.
+        if let Some(root) = query.filters.get("page_tree") {
+            return Ok(ListingPage {
+                resources: self
+                    .fetch_page_tree(root, query.limit.unwrap_or(usize::MAX))
+                    .await?,
+                next_cursor: None,
+            });
+        }
 
-        let url = format!("https://api.notion.com/v1/pages/{}", page_id);
+        let page_size = query
+            .page_size()?
+            .unwrap_or(NOTION_MAX_PAGE_SIZE)
+            .min(NOTION_MAX_PAGE_SIZE);
+        let records = query
+            .filters
+            .get("records")
+            .is_some_and(|value| value == "true");
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        // Without a database, list every page shared with the integration.
+        let (query_response, request_id) = match query.filters.get("database_id") {
+            Some(database_id) => self.query_database(database_id, page_size, cursor).await?,
+            None => self.search_pages(page_size, cursor).await?,
+        };
 
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
-            return Err(DomainError::ResourceNotFound(format!(
-                "Notion page not found: {}",
-                error_text
-            )));
+        let mut resources = Vec::new();
+        for page_data in &query_response.results {
+            match self.page_to_stub(page_data, records, request_id.clone()) {
+                Ok(resource) => resources.push(resource),
+                Err(e) => tracing::warn!("Failed to convert page to resource: {}", e),
+            }
         }
 
-        let page_data: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok(ListingPage {
+            resources,
+            next_cursor: query_response
+                .next_cursor
+                .filter(|_| query_response.has_more),
+        })
+    }
+
+    async fn hydrate(&self, mut resource: Resource) -> Result<Resource, DomainError> {
+        // Records are rendered from their properties when listed, and page
+        // tree pages are listed with their content.
+        if resource.metadata.contains_key("record") || !resource.content.is_empty() {
+            return Ok(resource);
+        }
+        let ResourceSource::Notion { page_id, .. } = &resource.source else {
+            return Ok(resource);
+        };
+
+        let blocks = self.get_page_blocks(page_id).await?;
+        self.fill_from_blocks(&mut resource, &blocks);
+        Ok(resource)
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        // Remove the "notion_" prefix if present
+        let page_id = id.strip_prefix("notion_").unwrap_or(id);
 
-        self.page_to_resource(&page_data).await
+        let (page_data, request_id) = self.get_page(page_id).await?;
+        self.page_to_resource(&page_data, false, request_id).await
     }
 
     async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
@@ -320,12 +918,10 @@ impl ResourceProvider for NotionAdapter {
         });
 
         let response = self
-            .client
-            .post(url)
-            .json(&search_body)
-            .send()
-            .await
-            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            .http
+            .send(self.http.post(url).json(&search_body))
+            .await?;
+        let request_id = http::request_id(&response);
 
         if !response.status().is_success() {
             let error_text = response
@@ -345,7 +941,10 @@ impl ResourceProvider for NotionAdapter {
 
         let mut resources = Vec::new();
         for page_data in search_response.results {
-            match self.page_to_resource(&page_data).await {
+            match self
+                .page_to_resource(&page_data, false, request_id.clone())
+                .await
+            {
                 Ok(resource) => resources.push(resource),
                 Err(e) => tracing::warn!("Failed to convert search result to resource: {}", e),
             }
@@ -357,4 +956,278 @@ impl ResourceProvider for NotionAdapter {
     fn provider_name(&self) -> &'static str {
         "Notion"
     }
+
+    /// Lists what a sync would, counting the requests it takes, then
+    /// fetches the blocks of a few pages spread through the listing to see
+    /// how many pages of blocks the rest are likely to have. Records are
+    /// listed complete, and page trees fetched in one go, so a sync of
+    /// records costs only the listing and one of a page tree isn't
+    /// estimated.
+    async fn estimate_sync(&self, query: &Query) -> Result<Option<SyncEstimate>, DomainError> {
+        if query.filters.contains_key("page_tree") {
+            return Ok(None);
+        }
+        let records = query
+            .filters
+            .get("records")
+            .is_some_and(|value| value == "true");
+        let updated_after = query
+            .filters
+            .get("updated_after")
+            .and_then(|after| DateTime::parse_from_rfc3339(after).ok());
+        let limit = query.limit.unwrap_or(usize::MAX);
+
+        let mut estimate = SyncEstimate {
+            requests_per_second: NOTION_REQUESTS_PER_SECOND,
+            ..SyncEstimate::default()
+        };
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let listing = self.list_page(query, cursor.take()).await?;
+            estimate.listing_calls += 1;
+            pages.extend(
+                listing
+                    .resources
+                    .into_iter()
+                    .filter(|page| updated_after.is_none_or(|after| page.updated_at >= after)),
+            );
+            report_progress(|| format!("Counted {} Notion pages", pages.len()));
+
+            cursor = listing.next_cursor;
+            if cursor.is_none() || pages.len() >= limit {
+                break;
+            }
+        }
+        pages.truncate(limit);
+        estimate.resources = pages.len();
+        if records || pages.is_empty() {
+            return Ok(Some(estimate));
+        }
+
+        let step = (pages.len() / ESTIMATE_SAMPLE_PAGES).max(1);
+        let mut sampled = 0;
+        let mut block_pages = 0;
+        for page in pages.iter().step_by(step).take(ESTIMATE_SAMPLE_PAGES) {
+            let ResourceSource::Notion { page_id, .. } = &page.source else {
+                continue;
+            };
+            let blocks = self.get_page_blocks(page_id).await?;
+            block_pages += blocks.len().div_ceil(NOTION_MAX_PAGE_SIZE).max(1);
+            sampled += 1;
+        }
+        estimate.content_calls =
+            (block_pages as f64 / sampled.max(1) as f64 * pages.len() as f64).ceil() as usize;
+        Ok(Some(estimate))
+    }
+
+    /// Downloads files from file, image, PDF, video and audio blocks. Links
+    /// to uploads expire after an hour, so fetch the page again first if
+    /// it was fetched long ago.
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        self.files.download(&attachment.url, &attachment.name).await
+    }
+
+    /// Appends to and updates pages, returning the page as it is now.
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        match change {
+            Change::Append { id, content } => {
+                let page_id = id.strip_prefix("notion_").unwrap_or(id);
+                self.append_blocks(page_id, content, None).await?;
+                self.fetch_resource_by_id(page_id).await
+            }
+            Change::Update { id, title, content } => {
+                let page_id = id.strip_prefix("notion_").unwrap_or(id);
+                if let Some(title) = title {
+                    self.set_title(page_id, title).await?;
+                }
+                if let Some(content) = content {
+                    self.replace_content(page_id, content).await?;
+                }
+                self.fetch_resource_by_id(page_id).await
+            }
+            _ => Err(DomainError::InvalidQuery(format!(
+                "Notion does not support {}",
+                change.kind()
+            ))),
+        }
+    }
+
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        let url = "https://api.notion.com/v1/search";
+        let mut items = Vec::new();
+        let mut start_cursor: Option<String> = None;
+
+        loop {
+            let mut body = serde_json::json!({ "page_size": NOTION_MAX_PAGE_SIZE });
+            if let Some(cursor) = start_cursor.take() {
+                body["start_cursor"] = serde_json::Value::String(cursor);
+            }
+
+            let response = self.http.send(self.http.post(url).json(&body)).await?;
+
+            if !response.status().is_success() {
+                let error_text = response
+                    .text()
+                    .await
+                    .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+                return Err(DomainError::ProviderError(format!(
+                    "Notion search error: {}",
+                    error_text
+                )));
+            }
+
+            let search_response: NotionQueryResponse = response
+                .json()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+
+            items.extend(
+                search_response
+                    .results
+                    .iter()
+                    .filter_map(|object| self.object_to_item(object)),
+            );
+
+            if !search_response.has_more {
+                break;
+            }
+            start_cursor = search_response.next_cursor;
+            if start_cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+}
+
+fn block_id(block: &NotionBlock) -> Option<&str> {
+    block.content.get("id")?.as_str()
+}
+
+/// Simplifies a Notion property value to plain JSON: text as strings, numbers
+/// as numbers, selects as their option names, relations as page IDs, and dates
+/// as ISO 8601 strings (`start/end` for ranges).
+fn property_value(property: &serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    let Some(kind) = property.get("type").and_then(|t| t.as_str()) else {
+        return Value::Null;
+    };
+    let value = property.get(kind).unwrap_or(&Value::Null);
+    let name = |v: &Value| v.get("name").cloned().unwrap_or(Value::Null);
+
+    match kind {
+        "title" | "rich_text" => Value::String(
+            value
+                .as_array()
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .filter_map(|part| part.get("plain_text").and_then(|t| t.as_str()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
+        "number" | "checkbox" | "url" | "email" | "phone_number" | "created_time"
+        | "last_edited_time" => value.clone(),
+        "select" | "status" | "created_by" | "last_edited_by" => name(value),
+        "multi_select" | "people" | "files" => Value::Array(
+            value
+                .as_array()
+                .map(|items| items.iter().map(name).collect())
+                .unwrap_or_default(),
+        ),
+        "relation" => Value::Array(
+            value
+                .as_array()
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| item.get("id").cloned())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
+        "date" => match (
+            value.get("start").and_then(|s| s.as_str()),
+            value.get("end").and_then(|e| e.as_str()),
+        ) {
+            (Some(start), Some(end)) => Value::String(format!("{}/{}", start, end)),
+            (Some(start), None) => Value::String(start.to_string()),
+            _ => Value::Null,
+        },
+        "unique_id" => match value.get("prefix").and_then(|p| p.as_str()) {
+            Some(prefix) => Value::String(format!(
+                "{}-{}",
+                prefix,
+                value.get("number").unwrap_or(&Value::Null)
+            )),
+            None => value.get("number").cloned().unwrap_or(Value::Null),
+        },
+        // Formulas and rollups wrap another typed value.
+        "formula" | "rollup" => match value.get("type").and_then(|t| t.as_str()) {
+            Some("array") => Value::Array(
+                value
+                    .get("array")
+                    .and_then(|items| items.as_array())
+                    .map(|items| items.iter().map(property_value).collect())
+                    .unwrap_or_default(),
+            ),
+            Some(_) => property_value(value),
+            None => Value::Null,
+        },
+        _ => Value::Null,
+    }
+}
+
+fn display_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Files held by file, image, PDF, video and audio blocks, named by their
+/// name, caption or URL, in that order of preference.
+fn attachments_from_blocks(blocks: &[NotionBlock]) -> Vec<Attachment> {
+    blocks
+        .iter()
+        .filter(|block| FILE_BLOCK_TYPES.contains(&block.block_type.as_str()))
+        .filter_map(|block| {
+            let file = block.content.get(&block.block_type)?;
+            let url = ["file", "external"]
+                .iter()
+                .find_map(|kind| file.get(kind)?.get("url")?.as_str())?;
+            let caption: String = file
+                .get("caption")
+                .and_then(|caption| caption.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|text| text.get("plain_text")?.as_str())
+                .collect();
+            let name = file
+                .get("name")
+                .and_then(|name| name.as_str())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .or_else(|| (!caption.trim().is_empty()).then(|| caption.trim().to_string()))
+                .unwrap_or_else(|| {
+                    let path = url.split(['?', '#']).next().unwrap_or(url);
+                    path.rsplit('/').next().unwrap_or(path).to_string()
+                });
+            Some(Attachment {
+                name,
+                url: url.to_string(),
+                mime_type: None,
+            })
+        })
+        .collect()
 }
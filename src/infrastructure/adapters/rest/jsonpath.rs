@@ -0,0 +1,121 @@
+use serde_json::Value;
+
+/// A JSONPath of the common kind: `$`, then any of `.name`, `['name']`,
+/// `[0]` (negative counts from the end), `[*]` and `.*`. The leading `$.`
+/// may be left out. Filters and recursive descent aren't supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let path = path.trim();
+        let mut rest = match path.strip_prefix('$') {
+            Some(rest) => rest,
+            None if path.starts_with('[') => path,
+            None => return Self::parse(&format!("$.{}", path)),
+        };
+
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                let key = &after[..end];
+                match key {
+                    "" => return Err(format!("empty name in {}", path)),
+                    "*" => steps.push(Step::Wildcard),
+                    key => steps.push(Step::Key(key.to_string())),
+                }
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let quote = after.chars().next().filter(|c| *c == '\'' || *c == '"');
+                let (step, end) = match quote {
+                    Some(quote) => {
+                        let close = after[1..]
+                            .find(quote)
+                            .ok_or_else(|| format!("unclosed quote in {}", path))?;
+                        (Step::Key(after[1..close + 1].to_string()), close + 2)
+                    }
+                    None => {
+                        let close = after
+                            .find(']')
+                            .ok_or_else(|| format!("unclosed [ in {}", path))?;
+                        let step = match after[..close].trim() {
+                            "*" => Step::Wildcard,
+                            index => Step::Index(
+                                index
+                                    .parse()
+                                    .map_err(|_| format!("invalid index {} in {}", index, path))?,
+                            ),
+                        };
+                        (step, close)
+                    }
+                };
+                rest = after[end..]
+                    .strip_prefix(']')
+                    .ok_or_else(|| format!("expected ] in {}", path))?;
+                steps.push(step);
+            } else {
+                return Err(format!("unexpected {:?} in {}", rest, path));
+            }
+        }
+        Ok(Self { steps })
+    }
+
+    /// Every value the path selects in `value`, in document order.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut selected = vec![value];
+        for step in &self.steps {
+            selected = selected
+                .into_iter()
+                .flat_map(|value| -> Vec<&Value> {
+                    match (step, value) {
+                        (Step::Key(key), Value::Object(object)) => {
+                            object.get(key).into_iter().collect()
+                        }
+                        (Step::Index(index), Value::Array(array)) => {
+                            let index = if *index < 0 {
+                                array.len() as i64 + index
+                            } else {
+                                *index
+                            };
+                            usize::try_from(index)
+                                .ok()
+                                .and_then(|index| array.get(index))
+                                .into_iter()
+                                .collect()
+                        }
+                        (Step::Wildcard, Value::Array(array)) => array.iter().collect(),
+                        (Step::Wildcard, Value::Object(object)) => object.values().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        selected
+    }
+
+    /// The name the path starts with, e.g. `body` of `$.body.text`.
+    #[cfg(feature = "sql")]
+    pub fn first_key(&self) -> Option<&str> {
+        match self.steps.first() {
+            Some(Step::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The first value the path selects that isn't null.
+    pub fn first<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.select(value)
+            .into_iter()
+            .find(|value| !value.is_null())
+    }
+}
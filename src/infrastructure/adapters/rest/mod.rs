@@ -0,0 +1,800 @@
+pub mod jsonpath;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::{Captures, Regex};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+use super::percent_encode;
+use crate::{
+    domain::{DomainError, ListingPage, Provenance, Query, Resource, ResourceSource},
+    infrastructure::{
+        config::{self, RestConfig, RestEndpoint, RestFields},
+        http::{self, HttpClient, RetryPolicy},
+    },
+    ports::{report_progress, ResourceProvider},
+};
+use jsonpath::JsonPath;
+
+/// Pages followed for one fetch, however many more the API offers.
+const MAX_PAGES: usize = 100;
+
+/// Items asked for per page of a listing, unless `page_size` says.
+const PAGE_SIZE: usize = 100;
+
+/// Pages of a listing after which it's taken for one that never ends,
+/// e.g. of an API ignoring `{page}`, and given up on.
+const MAX_LISTING_PAGES: usize = 10_000;
+
+/// Items looked through for one by ID, or for matches, when the API has
+/// no endpoint to ask.
+const SCAN_LIMIT: usize = 1000;
+
+/// `{name}` in an endpoint's path or parameters.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{([A-Za-z_][A-Za-z0-9_.]*)\}").unwrap());
+
+/// An endpoint as configured, its JSONPaths parsed.
+struct Endpoint {
+    path: String,
+    params: Vec<(String, String)>,
+    items: Option<JsonPath>,
+    next: Option<JsonPath>,
+    cursor_param: Option<String>,
+}
+
+impl Endpoint {
+    fn new(name: &str, endpoint: &str, config: &RestEndpoint) -> Result<Self, DomainError> {
+        let path = |field: &str, path: &Option<String>| {
+            path.as_deref()
+                .map(|path| parse_path("rest", name, &format!("{}.{}", endpoint, field), path))
+                .transpose()
+        };
+        Ok(Self {
+            path: config.path.clone(),
+            params: config
+                .params
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            items: path("items", &config.items)?,
+            next: path("next", &config.next)?,
+            cursor_param: config.cursor_param.clone(),
+        })
+    }
+
+    /// Whether it's paged by `{page}` or `{offset}`.
+    fn numbered_pages(&self) -> bool {
+        std::iter::once(&self.path)
+            .chain(self.params.iter().map(|(_, value)| value))
+            .any(|text| text.contains("{page}") || text.contains("{offset}"))
+    }
+}
+
+/// Where a listing of an endpoint is up to, kept in `list_page` cursors.
+#[derive(Debug, Serialize, Deserialize)]
+struct Position {
+    /// Number of the next page, from 1.
+    page: usize,
+    /// Items listed before the next page.
+    offset: usize,
+    /// The next page's link, if the API gave one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    /// What to send as the endpoint's `cursor_param` for the next page, if
+    /// the API gave it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>,
+}
+
+impl Position {
+    fn start() -> Self {
+        Self {
+            page: 1,
+            offset: 0,
+            url: None,
+            cursor: None,
+        }
+    }
+}
+
+/// An item's fields, as JSONPaths within it.
+pub struct Fields {
+    id: JsonPath,
+    title: JsonPath,
+    content: Option<JsonPath>,
+    url: Option<JsonPath>,
+    created_at: Option<JsonPath>,
+    updated_at: Option<JsonPath>,
+    metadata: Vec<(String, JsonPath)>,
+}
+
+impl Fields {
+    /// The fields of the `[<section>.<name>]` provider, configured in its
+    /// `table`, e.g. `fields`.
+    pub fn new(
+        section: &str,
+        name: &str,
+        table: &str,
+        fields: &RestFields,
+    ) -> Result<Self, DomainError> {
+        let path = |field: &str, path: &str| {
+            parse_path(section, name, &format!("{}.{}", table, field), path)
+        };
+        let optional = |field: &str, path: &Option<String>| {
+            path.as_deref()
+                .map(|path| parse_path(section, name, &format!("{}.{}", table, field), path))
+                .transpose()
+        };
+        Ok(Self {
+            id: path("id", &fields.id)?,
+            title: path("title", &fields.title)?,
+            content: optional("content", &fields.content)?,
+            url: optional("url", &fields.url)?,
+            created_at: optional("created_at", &fields.created_at)?,
+            updated_at: optional("updated_at", &fields.updated_at)?,
+            metadata: fields
+                .metadata
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), path(&format!("metadata.{}", key), value)?)))
+                .collect::<Result<_, DomainError>>()?,
+        })
+    }
+
+    /// An item of `provider` as a resource, or `None` if it has no ID.
+    pub fn to_resource(
+        &self,
+        provider: &str,
+        item: &Value,
+        request_id: Option<String>,
+    ) -> Option<Resource> {
+        let id = self.id.first(item).map(text).filter(|id| !id.is_empty())?;
+        let title = self
+            .title
+            .first(item)
+            .map(text)
+            .unwrap_or_else(|| id.clone());
+        let content = match &self.content {
+            Some(path) => path.first(item).map(text).unwrap_or_default(),
+            None => serde_json::to_string_pretty(item).unwrap_or_default(),
+        };
+        let time = |path: &Option<JsonPath>| path.as_ref()?.first(item).and_then(timestamp);
+        let created_at = time(&self.created_at);
+        let updated_at = time(&self.updated_at);
+        let created_at = created_at.or(updated_at).unwrap_or_default();
+        let updated_at = updated_at.unwrap_or(created_at);
+
+        let mut metadata = HashMap::new();
+        for (key, path) in &self.metadata {
+            let mut values = path.select(item);
+            let value = match values.len() {
+                0 => continue,
+                1 => values.remove(0).clone(),
+                _ => Value::Array(values.into_iter().cloned().collect()),
+            };
+            metadata.insert(key.clone(), value);
+        }
+
+        Some(Resource {
+            id: format!("{}_{}", provider, id),
+            source: ResourceSource::Custom(provider.to_string()),
+            title,
+            content,
+            metadata,
+            created_at,
+            updated_at,
+            provenance: Some(Provenance {
+                provider: provider.to_string(),
+                fetched_at: Utc::now(),
+                api_version: None,
+                request_id,
+                url: self
+                    .url
+                    .as_ref()
+                    .and_then(|path| path.first(item))
+                    .map(text),
+            }),
+        })
+    }
+}
+
+/// A JSON API served as a provider, as a `[rest.<name>]` entry describes
+/// it: which endpoints list, fetch and search its items, and where in each
+/// item a resource's fields are.
+pub struct RestAdapter {
+    name: &'static str,
+    http: HttpClient,
+    base_url: Url,
+    list: Endpoint,
+    get: Option<Endpoint>,
+    search: Option<Endpoint>,
+    fields: Fields,
+}
+
+impl RestAdapter {
+    pub fn new(name: &'static str, config: &RestConfig) -> Result<Self, DomainError> {
+        let base_url = Url::parse(config.base_url.trim()).map_err(|e| {
+            DomainError::InvalidQuery(format!(
+                "Invalid rest.{}.base_url {}: {}",
+                name, config.base_url, e
+            ))
+        })?;
+
+        let (headers, secrets) = headers("rest", name, config.token.as_deref(), &config.headers)?;
+        let http = HttpClient::new(name, headers, secrets)?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self {
+            name,
+            http,
+            base_url,
+            list: Endpoint::new(name, "list", &config.list)?,
+            get: config
+                .get
+                .as_ref()
+                .map(|get| Endpoint::new(name, "get", get))
+                .transpose()?,
+            search: config
+                .search
+                .as_ref()
+                .map(|search| Endpoint::new(name, "search", search))
+                .transpose()?,
+            fields: Fields::new("rest", name, "fields", &config.fields)?,
+        })
+    }
+
+    /// Nothing is known of the API's throttling, so the common defaults.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// Fetches `url`, returning the response and the API's ID for the
+    /// request.
+    async fn get_json(
+        &self,
+        url: &str,
+        params: &[(String, String)],
+    ) -> Result<(Value, Option<String>), DomainError> {
+        let response = self.http.send(self.http.get(url).query(params)).await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(match status {
+                StatusCode::NOT_FOUND => {
+                    DomainError::ResourceNotFound(format!("{} has no {}", self.name, url))
+                }
+                StatusCode::BAD_REQUEST => DomainError::InvalidQuery(format!(
+                    "{} rejected the query: {}",
+                    self.name, error_text
+                )),
+                _ => DomainError::ProviderError(format!(
+                    "{} API error ({}): {}",
+                    self.name, status, error_text
+                )),
+            });
+        }
+
+        let data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(format!("{}: {}", self.name, e)))?;
+        Ok((data, request_id))
+    }
+
+    /// Up to `limit` items `endpoint` returns with the placeholders in
+    /// `vars`, following its pages, with the API's ID for the last request.
+    async fn items(
+        &self,
+        endpoint: &Endpoint,
+        vars: &HashMap<String, String>,
+        limit: usize,
+    ) -> Result<(Vec<Value>, Option<String>), DomainError> {
+        let mut items = Vec::new();
+        let mut request_id = None;
+        let mut position = Position::start();
+        for _ in 0..MAX_PAGES {
+            let (page_items, id, next) = self.page(endpoint, vars, limit, &position).await?;
+            request_id = id.or(request_id);
+            items.extend(page_items);
+            report_progress(|| format!("Fetched {} {} items", items.len(), self.name));
+            match next {
+                Some(next) if items.len() < limit => position = next,
+                _ => break,
+            }
+        }
+        items.truncate(limit);
+        Ok((items, request_id))
+    }
+
+    /// The page of `endpoint` at `position`, asking for `size` items with
+    /// the placeholders in `vars`, with the API's ID for the request and
+    /// where the next page is, if there is one.
+    async fn page(
+        &self,
+        endpoint: &Endpoint,
+        vars: &HashMap<String, String>,
+        size: usize,
+        position: &Position,
+    ) -> Result<(Vec<Value>, Option<String>, Option<Position>), DomainError> {
+        let mut vars = vars.clone();
+        vars.insert("limit".to_string(), size.to_string());
+        vars.insert("page".to_string(), position.page.to_string());
+        vars.insert("offset".to_string(), position.offset.to_string());
+        let (url, mut params) = match &position.url {
+            // Next page links carry their own parameters.
+            Some(url) => (url.clone(), Vec::new()),
+            None => (
+                self.url(&endpoint.path, &vars)?,
+                fill_params(&endpoint.params, &vars),
+            ),
+        };
+        if let (Some(param), Some(cursor)) = (&endpoint.cursor_param, &position.cursor) {
+            params.push((param.clone(), cursor.clone()));
+        }
+
+        let (response, request_id) = self.get_json(&url, &params).await?;
+        let selected = match &endpoint.items {
+            Some(path) => path.select(&response),
+            None => vec![&response],
+        };
+        // An array selected alone, e.g. by `$.data`, holds the items.
+        let items: Vec<Value> = match selected.as_slice() {
+            [Value::Array(array)] => array.clone(),
+            selected => selected.iter().map(|item| (*item).clone()).collect(),
+        };
+        if items.is_empty() {
+            return Ok((items, request_id, None));
+        }
+
+        let after = |url, cursor| Position {
+            page: position.page + 1,
+            offset: position.offset + items.len(),
+            url,
+            cursor,
+        };
+        let next = endpoint
+            .next
+            .as_ref()
+            .and_then(|path| path.first(&response))
+            .map(text)
+            .filter(|next| !next.is_empty());
+        let next = match (next, &endpoint.cursor_param) {
+            (Some(next), Some(_)) => Some(after(None, Some(next))),
+            (Some(next), None) => Some(after(Some(self.next_url(&url, &next)?), None)),
+            (None, _) if endpoint.numbered_pages() && endpoint.next.is_none() => {
+                Some(after(None, None))
+            }
+            (None, _) => None,
+        };
+        Ok((items, request_id, next))
+    }
+
+    /// `path` with its placeholders filled in, against `base_url`.
+    fn url(&self, path: &str, vars: &HashMap<String, String>) -> Result<String, DomainError> {
+        let mut missing = None;
+        let path =
+            PLACEHOLDER.replace_all(path, |captures: &Captures| match vars.get(&captures[1]) {
+                Some(value) => percent_encode(value),
+                None => {
+                    missing.get_or_insert_with(|| captures[1].to_string());
+                    String::new()
+                }
+            });
+        if let Some(missing) = missing {
+            return Err(DomainError::InvalidQuery(format!(
+                "{} needs {{{}}}: pass --filter {}=<value>",
+                self.name, missing, missing
+            )));
+        }
+        if path.contains("://") {
+            return Ok(path.into_owned());
+        }
+        Ok(format!(
+            "{}/{}",
+            self.base_url.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        ))
+    }
+
+    /// The next page's URL, which must be on the API's own host, as the
+    /// credentials go with it.
+    fn next_url(&self, current: &str, next: &str) -> Result<String, DomainError> {
+        let url = Url::parse(current)
+            .and_then(|current| current.join(next))
+            .map_err(|e| {
+                DomainError::ProviderError(format!("{}: next page {}: {}", self.name, next, e))
+            })?;
+        if url.origin() != self.base_url.origin() {
+            return Err(DomainError::ProviderError(format!(
+                "{} links its next page to {}, off {}",
+                self.name,
+                url,
+                self.base_url.origin().ascii_serialization()
+            )));
+        }
+        Ok(url.into())
+    }
+
+    fn to_resources(&self, items: &[Value], request_id: Option<String>) -> Vec<Resource> {
+        items
+            .iter()
+            .filter_map(|item| self.fields.to_resource(self.name, item, request_id.clone()))
+            .collect()
+    }
+
+    /// The listing's placeholders: `--filter`s given.
+    fn vars(query: &Query) -> HashMap<String, String> {
+        query
+            .filters
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// The headers sent with every request of the `[<section>.<name>]`
+/// provider: the bearer `token` and the `headers` configured, their
+/// `${NAME}`s expanded. Returns them with the secrets among them, for
+/// redaction.
+pub fn headers(
+    section: &str,
+    name: &str,
+    token: Option<&str>,
+    configured: &BTreeMap<String, String>,
+) -> Result<(HeaderMap, Vec<String>), DomainError> {
+    let mut headers = HeaderMap::new();
+    let mut secrets = Vec::new();
+    if let Some(token) = token {
+        headers.insert(
+            AUTHORIZATION,
+            header_value(name, &format!("Bearer {}", token))?,
+        );
+        secrets.push(token.to_string());
+    }
+    for (header, value) in configured {
+        let value = config::expand_env(value).map_err(|e| match e {
+            DomainError::InvalidQuery(message) => DomainError::InvalidQuery(format!(
+                "{}.{}.headers.{}: {}",
+                section, name, header, message
+            )),
+            e => e,
+        })?;
+        let header_name = HeaderName::from_bytes(header.as_bytes()).map_err(|e| {
+            DomainError::InvalidQuery(format!("Invalid header {} for {}: {}", header, name, e))
+        })?;
+        headers.insert(header_name, header_value(name, &value)?);
+        secrets.push(value);
+    }
+    Ok((headers, secrets))
+}
+
+pub fn parse_path(
+    section: &str,
+    name: &str,
+    field: &str,
+    path: &str,
+) -> Result<JsonPath, DomainError> {
+    JsonPath::parse(path).map_err(|e| {
+        DomainError::InvalidQuery(format!(
+            "Invalid JSONPath {}.{}.{}: {}",
+            section, name, field, e
+        ))
+    })
+}
+
+fn header_value(name: &str, value: &str) -> Result<HeaderValue, DomainError> {
+    HeaderValue::from_str(value)
+        .map_err(|e| DomainError::InvalidQuery(format!("Invalid header for {}: {}", name, e)))
+}
+
+/// `params` with their placeholders filled in; those with any unset are
+/// left out.
+fn fill_params(
+    params: &[(String, String)],
+    vars: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    params
+        .iter()
+        .filter_map(|(name, template)| {
+            let mut complete = true;
+            let value = PLACEHOLDER.replace_all(template, |captures: &Captures| {
+                vars.get(&captures[1]).cloned().unwrap_or_else(|| {
+                    complete = false;
+                    String::new()
+                })
+            });
+            complete.then(|| (name.clone(), value.into_owned()))
+        })
+        .collect()
+}
+
+/// A field's value as text: strings as they are, anything else as JSON.
+pub fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        Value::Number(_) | Value::Bool(_) => value.to_string(),
+        _ => serde_json::to_string_pretty(value).unwrap_or_default(),
+    }
+}
+
+/// An RFC 3339 time, a date, or seconds or milliseconds since 1970.
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::Number(number) => {
+            let number = number.as_i64()?;
+            // Milliseconds, unless that would be before 1971.
+            if number.abs() >= 100_000_000_000 {
+                Utc.timestamp_millis_opt(number).single()
+            } else {
+                Utc.timestamp_opt(number, 0).single()
+            }
+        }
+        Value::String(text) => {
+            let text = text.trim();
+            DateTime::parse_from_rfc3339(text)
+                .map(|time| time.with_timezone(&Utc))
+                .ok()
+                .or_else(|| {
+                    NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f"))
+                        .ok()
+                        .map(|time| time.and_utc())
+                })
+                .or_else(|| {
+                    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                        .map(|time| time.and_utc())
+                })
+                .or_else(|| text.parse::<i64>().ok().and_then(|n| timestamp(&n.into())))
+        }
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for RestAdapter {
+    /// Lists what the `list` endpoint returns. Filters fill the
+    /// placeholders of the same name in its path and parameters.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let (items, request_id) = self.items(&self.list, &Self::vars(query), limit).await?;
+        Ok(self.to_resources(&items, request_id))
+    }
+
+    /// Fetches the item from the `get` endpoint, or finds it by listing.
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let item_id = id
+            .strip_prefix(self.name)
+            .and_then(|id| id.strip_prefix('_'))
+            .unwrap_or(id);
+        let not_found = || DomainError::ResourceNotFound(format!("{} has no {}", self.name, id));
+
+        let Some(get) = &self.get else {
+            let (items, request_id) = self.items(&self.list, &HashMap::new(), SCAN_LIMIT).await?;
+            return self
+                .to_resources(&items, request_id)
+                .into_iter()
+                .find(|resource| resource.id == format!("{}_{}", self.name, item_id))
+                .ok_or_else(not_found);
+        };
+        let vars = HashMap::from([("id".to_string(), item_id.to_string())]);
+        let (items, request_id) = self.items(get, &vars, 1).await?;
+        items
+            .first()
+            .and_then(|item| self.fields.to_resource(self.name, item, request_id))
+            .ok_or_else(not_found)
+    }
+
+    /// Asks the `search` endpoint, or else lists items and keeps those
+    /// whose title or content contains every word of `query`.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        if let Some(search) = &self.search {
+            let vars = HashMap::from([("query".to_string(), query.trim().to_string())]);
+            let (items, request_id) = self.items(search, &vars, 50).await?;
+            return Ok(self.to_resources(&items, request_id));
+        }
+
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        let (items, request_id) = self.items(&self.list, &HashMap::new(), SCAN_LIMIT).await?;
+        Ok(self
+            .to_resources(&items, request_id)
+            .into_iter()
+            .filter(|resource| {
+                let text = format!("{} {}", resource.title, resource.content).to_lowercase();
+                !words.is_empty() && words.iter().all(|word| text.contains(word))
+            })
+            .collect())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Pages are the `list` endpoint's, asking for `page_size` items (100
+    /// by default); the cursor says where the next one is.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let position = match cursor {
+            Some(cursor) => {
+                let position: Position = serde_json::from_str(&cursor).map_err(|_| {
+                    DomainError::InvalidQuery(format!("Invalid {} cursor {:?}", self.name, cursor))
+                })?;
+                // Cursors come back from clients, and the credentials go
+                // wherever a next page link points.
+                if let Some(url) = &position.url {
+                    self.next_url(self.base_url.as_str(), url)?;
+                }
+                position
+            }
+            None => Position::start(),
+        };
+        if position.page > MAX_LISTING_PAGES {
+            return Err(DomainError::ProviderError(format!(
+                "{} listed more than {} pages without reaching the end",
+                self.name, MAX_LISTING_PAGES
+            )));
+        }
+
+        let mut size = query.page_size()?.unwrap_or(PAGE_SIZE);
+        if let Some(limit) = query.limit {
+            size = size.min(limit.max(1));
+        }
+        let (items, request_id, next) = self
+            .page(&self.list, &Self::vars(query), size, &position)
+            .await?;
+        let next_cursor = next
+            .map(|next| serde_json::to_string(&next))
+            .transpose()
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok(ListingPage {
+            resources: self.to_resources(&items, request_id),
+            next_cursor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        application::{sync::SyncLimits, ResourceService},
+        domain::QuerySource,
+        infrastructure::repository::SqliteRepository,
+        ports::{ResourceProvider, ResourceRepository},
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const ITEMS: usize = 120;
+    /// Most items the server returns in a page, whatever is asked for.
+    const SERVER_PAGE: usize = 40;
+
+    /// Serves `/items?page=N&per_page=M` over `ITEMS` items, in pages of
+    /// at most `SERVER_PAGE`, until the test ends.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|end| end == b"\r\n\r\n") {
+                    match stream.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let target = request.split_whitespace().nth(1).unwrap_or("/");
+                let url = Url::parse(&format!("http://localhost{}", target)).unwrap();
+                let param = |name: &str| {
+                    url.query_pairs()
+                        .find(|(key, _)| key == name)
+                        .and_then(|(_, value)| value.parse::<usize>().ok())
+                };
+                let page = param("page").unwrap_or(1).max(1);
+                let size = param("per_page").unwrap_or(SERVER_PAGE).min(SERVER_PAGE);
+                let items: Vec<Value> = ((page - 1) * size..(page * size).min(ITEMS))
+                    .map(|i| serde_json::json!({ "id": i.to_string(), "title": format!("Item {}", i) }))
+                    .collect();
+                let body = serde_json::json!({ "items": items }).to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn adapter(base_url: String) -> RestAdapter {
+        let config = RestConfig {
+            base_url,
+            token: None,
+            headers: BTreeMap::new(),
+            list: RestEndpoint {
+                path: "/items".to_string(),
+                params: BTreeMap::from([
+                    ("page".to_string(), "{page}".to_string()),
+                    ("per_page".to_string(), "{limit}".to_string()),
+                ]),
+                items: Some("$.items".to_string()),
+                ..RestEndpoint::default()
+            },
+            get: None,
+            search: None,
+            fields: RestFields {
+                id: "$.id".to_string(),
+                title: "$.title".to_string(),
+                content: None,
+                url: None,
+                created_at: None,
+                updated_at: None,
+                metadata: BTreeMap::new(),
+            },
+            retry: Default::default(),
+        };
+        RestAdapter::new("rest", &config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sync_lists_past_the_first_page_without_tombstoning() {
+        let dir = std::env::temp_dir().join(format!("mcp-rs-rest-test-{}", std::process::id()));
+        let repository = Arc::new(SqliteRepository::open(&dir.join("cache.db")).unwrap());
+        let mut service = ResourceService::new();
+        service.set_cache(repository.clone(), Duration::from_secs(3600), true);
+        let provider: Arc<dyn ResourceProvider> = Arc::new(adapter(serve().await));
+        service.add_provider(provider).unwrap();
+        let query = Query {
+            source: QuerySource::Named("rest".to_string()),
+            filters: HashMap::new(),
+            limit: None,
+        };
+
+        // The second sync finds every item the first cached still listed.
+        for _ in 0..2 {
+            let reports = service
+                .sync(&query, |_| SyncLimits::default())
+                .await
+                .unwrap();
+            let report = &reports[0];
+            assert_eq!(report.error, None);
+            assert_eq!(report.fetched, ITEMS);
+            assert_eq!(report.tombstoned, 0);
+        }
+        let live = repository.list_entries(Some("rest"), false).await.unwrap();
+        assert_eq!(live.len(), ITEMS);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,509 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{
+    mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions, MySqlRow},
+    postgres::{PgConnectOptions, PgPool, PgPoolOptions, PgRow},
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Column, ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Row, Type,
+};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::rest::{self, Fields};
+use crate::{
+    domain::{DomainError, ListingPage, Query, Resource},
+    infrastructure::{config::SqlConfig, sandbox},
+    ports::{is_listing_filter, report_progress, ResourceProvider},
+};
+
+/// Rows read at once when paging through a listing.
+const PAGE_SIZE: usize = 100;
+
+/// Rows a search returns at most.
+const SEARCH_LIMIT: usize = 50;
+
+/// Connections held open to one database.
+const MAX_CONNECTIONS: u32 = 4;
+
+/// How long a query waits to connect before failing.
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// SQLSTATEs of refused logins and missing privileges, which Postgres and
+/// MySQL share.
+const UNAUTHORIZED_STATES: [&str; 3] = ["28000", "28P01", "42501"];
+
+/// Connections to whichever database a URL names.
+enum Pool {
+    Postgres(PgPool),
+    MySql(MySqlPool),
+    Sqlite(SqlitePool),
+}
+
+impl Pool {
+    /// A pool for the `[sql.<name>]` database, which connects when first
+    /// used. Postgres and SQLite connections are read-only.
+    fn new(name: &str, config: &SqlConfig) -> Result<Self, DomainError> {
+        let url = config.url.trim();
+        let invalid =
+            |e: sqlx::Error| DomainError::InvalidQuery(format!("Invalid sql.{}.url: {}", name, e));
+        let scheme = url.split_once(':').map_or("", |(scheme, _)| scheme);
+        if scheme != "sqlite" {
+            let parsed = reqwest::Url::parse(url).map_err(|e| {
+                DomainError::InvalidQuery(format!("Invalid sql.{}.url: {}", name, e))
+            })?;
+            sandbox::check_url(&parsed)?;
+        }
+
+        Ok(match scheme {
+            "postgres" | "postgresql" => {
+                let mut options = PgConnectOptions::from_str(url)
+                    .map_err(invalid)?
+                    .options([("default_transaction_read_only", "on")]);
+                if let Some(password) = &config.password {
+                    options = options.password(password);
+                }
+                Self::Postgres(
+                    PgPoolOptions::new()
+                        .max_connections(MAX_CONNECTIONS)
+                        .acquire_timeout(ACQUIRE_TIMEOUT)
+                        .connect_lazy_with(options),
+                )
+            }
+            "mysql" | "mariadb" => {
+                let mut options = MySqlConnectOptions::from_str(url).map_err(invalid)?;
+                if let Some(password) = &config.password {
+                    options = options.password(password);
+                }
+                Self::MySql(
+                    MySqlPoolOptions::new()
+                        .max_connections(MAX_CONNECTIONS)
+                        .acquire_timeout(ACQUIRE_TIMEOUT)
+                        .connect_lazy_with(options),
+                )
+            }
+            "sqlite" => {
+                if sandbox::active().is_some() {
+                    return Err(DomainError::InvalidQuery(format!(
+                        "sql.{} is a local file, which the sandbox doesn't read",
+                        name
+                    )));
+                }
+                let options = SqliteConnectOptions::from_str(url)
+                    .map_err(invalid)?
+                    .read_only(true);
+                Self::Sqlite(
+                    SqlitePoolOptions::new()
+                        .max_connections(MAX_CONNECTIONS)
+                        .acquire_timeout(ACQUIRE_TIMEOUT)
+                        .connect_lazy_with(options),
+                )
+            }
+            scheme => {
+                return Err(DomainError::InvalidQuery(format!(
+                    "Unsupported sql.{}.url scheme {:?} (expected postgres, mysql or sqlite)",
+                    name, scheme
+                )))
+            }
+        })
+    }
+
+    /// The `n`th parameter of a statement, counting from 1.
+    fn placeholder(&self, n: usize) -> String {
+        match self {
+            Self::Postgres(_) => format!("${}", n),
+            Self::MySql(_) | Self::Sqlite(_) => "?".to_string(),
+        }
+    }
+
+    /// `column` quoted as an identifier.
+    fn quote(&self, column: &str) -> String {
+        match self {
+            Self::MySql(_) => format!("`{}`", column.replace('`', "``")),
+            Self::Postgres(_) | Self::Sqlite(_) => format!("\"{}\"", column.replace('"', "\"\"")),
+        }
+    }
+
+    /// `column`'s value as text, to compare with text parameters whatever
+    /// its type.
+    fn text(&self, column: &str) -> String {
+        let text = match self {
+            Self::MySql(_) => "CHAR",
+            Self::Postgres(_) | Self::Sqlite(_) => "TEXT",
+        };
+        format!("CAST({} AS {})", self.quote(column), text)
+    }
+
+    /// The rows `sql` returns with `params` bound, each as an object of
+    /// its columns.
+    async fn rows(&self, sql: &str, params: &[String]) -> Result<Vec<Value>, sqlx::Error> {
+        match self {
+            Self::Postgres(pool) => fetch_all(pool, sql, params, pg_value).await,
+            Self::MySql(pool) => fetch_all(pool, sql, params, mysql_value).await,
+            Self::Sqlite(pool) => fetch_all(pool, sql, params, sqlite_value).await,
+        }
+    }
+}
+
+/// Rows of a SQL database served as a provider, as a `[sql.<name>]` entry
+/// describes them: the queries that list, fetch and search them, and which
+/// columns hold a resource's fields.
+pub struct SqlAdapter {
+    name: &'static str,
+    pool: Pool,
+    list: String,
+    get: Option<String>,
+    search: Option<String>,
+    fields: Fields,
+    /// The columns of the ID, title and content, to look rows up by.
+    id_column: String,
+    title_column: Option<String>,
+    content_column: Option<String>,
+}
+
+impl SqlAdapter {
+    pub fn new(name: &'static str, config: &SqlConfig) -> Result<Self, DomainError> {
+        let fields = Fields::new("sql", name, "columns", &config.columns)?;
+        let column = |field: &str, path: &str| {
+            rest::parse_path("sql", name, &format!("columns.{}", field), path)
+                .map(|path| path.first_key().map(str::to_string))
+        };
+        let id_column = column("id", &config.columns.id)?.ok_or_else(|| {
+            DomainError::InvalidQuery(format!("sql.{}.columns.id names no column", name))
+        })?;
+        let content_column = match &config.columns.content {
+            Some(content) => column("content", content)?,
+            None => None,
+        };
+
+        Ok(Self {
+            name,
+            pool: Pool::new(name, config)?,
+            list: statement(&config.list),
+            get: config.get.as_deref().map(statement),
+            search: config.search.as_deref().map(statement),
+            fields,
+            id_column,
+            title_column: column("title", &config.columns.title)?,
+            content_column,
+        })
+    }
+
+    async fn rows(&self, sql: &str, params: &[String]) -> Result<Vec<Value>, DomainError> {
+        tracing::debug!("{} query: {}", self.name, sql);
+        self.pool.rows(sql, params).await.map_err(|e| match &e {
+            sqlx::Error::Database(error)
+                if error
+                    .code()
+                    .is_some_and(|code| UNAUTHORIZED_STATES.contains(&code.as_ref())) =>
+            {
+                DomainError::Unauthorized {
+                    provider: self.name.to_string(),
+                    missing_scope: None,
+                    message: error.message().to_string(),
+                }
+            }
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut => {
+                DomainError::Unavailable {
+                    provider: self.name.to_string(),
+                    message: e.to_string(),
+                }
+            }
+            _ => DomainError::ProviderError(format!("{} query failed: {}", self.name, e)),
+        })
+    }
+
+    /// Up to `limit` rows from `offset` of `from`, the rows of the listing
+    /// unless given, that meet every condition of `conditions`.
+    fn select(
+        &self,
+        from: Option<&str>,
+        conditions: &[String],
+        limit: usize,
+        offset: usize,
+    ) -> String {
+        let mut sql = format!("SELECT * FROM ({}) AS listed", from.unwrap_or(&self.list));
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(&format!(" LIMIT {}", limit));
+        if offset > 0 {
+            sql.push_str(&format!(" OFFSET {}", offset));
+        }
+        sql
+    }
+
+    /// The conditions of `query`'s filters, each keeping rows whose column
+    /// has the value, pushing the values onto `params`. Listing filters,
+    /// such as `page_size`, aren't columns.
+    fn conditions(&self, query: &Query, params: &mut Vec<String>) -> Vec<String> {
+        let mut filters: Vec<(&String, &String)> = query
+            .filters
+            .iter()
+            .filter(|(column, _)| !is_listing_filter(column))
+            .collect();
+        filters.sort();
+        filters
+            .into_iter()
+            .map(|(column, value)| {
+                params.push(value.clone());
+                format!(
+                    "{} = {}",
+                    self.pool.text(column),
+                    self.pool.placeholder(params.len())
+                )
+            })
+            .collect()
+    }
+
+    fn page_size(query: &Query) -> Result<usize, DomainError> {
+        match query.filters.get("page_size") {
+            Some(size) => match size.trim().parse() {
+                Ok(size) if size > 0 => Ok(size),
+                _ => Err(DomainError::InvalidQuery(format!(
+                    "page_size must be a positive number, not {:?}",
+                    size
+                ))),
+            },
+            None => Ok(PAGE_SIZE),
+        }
+    }
+
+    fn to_resources(&self, rows: &[Value]) -> Vec<Resource> {
+        rows.iter()
+            .filter_map(|row| self.fields.to_resource(self.name, row, None))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for SqlAdapter {
+    /// Lists the rows of the `list` query. Each filter keeps the rows whose
+    /// column of the same name has its value.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let mut resources = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.list_page(query, cursor).await?;
+            resources.extend(page.resources);
+            report_progress(|| format!("Fetched {} {} rows", resources.len(), self.name));
+            match page.next_cursor {
+                Some(next) if resources.len() < limit => cursor = Some(next),
+                _ => break,
+            }
+        }
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    /// Fetches the row with the `get` query, or looks it up among the
+    /// listed rows.
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let row_id = id
+            .strip_prefix(self.name)
+            .and_then(|id| id.strip_prefix('_'))
+            .unwrap_or(id);
+        let params = [row_id.to_string()];
+        let rows = match &self.get {
+            Some(get) => self.rows(get, &params).await?,
+            None => {
+                let condition = format!(
+                    "{} = {}",
+                    self.pool.text(&self.id_column),
+                    self.pool.placeholder(1)
+                );
+                let sql = self.select(None, &[condition], 1, 0);
+                self.rows(&sql, &params).await?
+            }
+        };
+        self.to_resources(&rows)
+            .into_iter()
+            .next()
+            .ok_or_else(|| DomainError::ResourceNotFound(format!("{} has no {}", self.name, id)))
+    }
+
+    /// Runs the `search` query, or else finds the listed rows whose title
+    /// or content contains every word of `query`, ignoring case.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        if let Some(search) = &self.search {
+            let sql = self.select(Some(search), &[], SEARCH_LIMIT, 0);
+            let rows = self.rows(&sql, &[query.trim().to_string()]).await?;
+            return Ok(self.to_resources(&rows));
+        }
+
+        let columns: Vec<&String> = self
+            .title_column
+            .iter()
+            .chain(&self.content_column)
+            .collect();
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() || columns.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut params = Vec::new();
+        let conditions: Vec<String> = words
+            .iter()
+            .map(|word| {
+                // `!` escapes LIKE's wildcards in every dialect, unlike `\`.
+                let pattern = word
+                    .replace('!', "!!")
+                    .replace('%', "!%")
+                    .replace('_', "!_");
+                let matches: Vec<String> = columns
+                    .iter()
+                    .map(|column| {
+                        params.push(format!("%{}%", pattern));
+                        format!(
+                            "LOWER({}) LIKE {} ESCAPE '!'",
+                            self.pool.text(column),
+                            self.pool.placeholder(params.len())
+                        )
+                    })
+                    .collect();
+                format!("({})", matches.join(" OR "))
+            })
+            .collect();
+        let sql = self.select(None, &conditions, SEARCH_LIMIT, 0);
+        let rows = self.rows(&sql, &params).await?;
+        Ok(self.to_resources(&rows))
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Pages are `page_size` rows (100 by default); the cursor is the
+    /// offset of the next.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let offset = match cursor {
+            Some(cursor) => cursor.parse().map_err(|_| {
+                DomainError::InvalidQuery(format!("Invalid {} cursor {:?}", self.name, cursor))
+            })?,
+            None => 0,
+        };
+        let mut page_size = Self::page_size(query)?;
+        if let Some(limit) = query.limit {
+            page_size = page_size.min(limit.max(1));
+        }
+        let mut params = Vec::new();
+        let conditions = self.conditions(query, &mut params);
+        let sql = self.select(None, &conditions, page_size, offset);
+        let rows = self.rows(&sql, &params).await?;
+
+        let next_cursor = (rows.len() == page_size).then(|| (offset + rows.len()).to_string());
+        Ok(ListingPage {
+            resources: self.to_resources(&rows),
+            next_cursor,
+        })
+    }
+
+    /// Connects, so the first query doesn't wait for it.
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        self.rows("SELECT 1", &[]).await.map(|_| ())
+    }
+}
+
+/// `sql` without the trailing `;` that can't be part of a subquery.
+fn statement(sql: &str) -> String {
+    sql.trim().trim_end_matches(';').trim_end().to_string()
+}
+
+async fn fetch_all<DB>(
+    pool: &sqlx::Pool<DB>,
+    sql: &str,
+    params: &[String],
+    value: fn(&DB::Row, usize) -> Value,
+) -> Result<Vec<Value>, sqlx::Error>
+where
+    DB: Database,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    let mut query = sqlx::query::<DB>(sql);
+    for param in params {
+        query = query.bind(param.clone());
+    }
+    let rows = query.fetch_all(pool).await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            Value::Object(
+                row.columns()
+                    .iter()
+                    .map(|column| (column.name().to_string(), value(row, column.ordinal())))
+                    .collect(),
+            )
+        })
+        .collect())
+}
+
+/// Column `index` of `row` as JSON, if it holds a `T`.
+fn column<'r, R, T>(row: &'r R, index: usize) -> Option<Value>
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    T: Decode<'r, R::Database> + Type<R::Database> + Serialize,
+{
+    let value: Option<T> = row.try_get(index).ok()?;
+    serde_json::to_value(value).ok()
+}
+
+// Values of types without a JSON counterpart, such as Postgres' NUMERIC,
+// come out null; queries can cast them to text.
+
+fn pg_value(row: &PgRow, index: usize) -> Value {
+    column::<_, String>(row, index)
+        .or_else(|| column::<_, i64>(row, index))
+        .or_else(|| column::<_, i32>(row, index))
+        .or_else(|| column::<_, i16>(row, index))
+        .or_else(|| column::<_, f64>(row, index))
+        .or_else(|| column::<_, f32>(row, index))
+        .or_else(|| column::<_, bool>(row, index))
+        .or_else(|| column::<_, DateTime<Utc>>(row, index))
+        .or_else(|| column::<_, NaiveDateTime>(row, index))
+        .or_else(|| column::<_, NaiveDate>(row, index))
+        .or_else(|| column::<_, Value>(row, index))
+        .or_else(|| column::<_, Vec<String>>(row, index))
+        .or_else(|| column::<_, Vec<i64>>(row, index))
+        .or_else(|| column::<_, Vec<i32>>(row, index))
+        .unwrap_or(Value::Null)
+}
+
+fn mysql_value(row: &MySqlRow, index: usize) -> Value {
+    column::<_, String>(row, index)
+        .or_else(|| column::<_, bool>(row, index))
+        .or_else(|| column::<_, i64>(row, index))
+        .or_else(|| column::<_, i32>(row, index))
+        .or_else(|| column::<_, i16>(row, index))
+        .or_else(|| column::<_, i8>(row, index))
+        .or_else(|| column::<_, u64>(row, index))
+        .or_else(|| column::<_, u32>(row, index))
+        .or_else(|| column::<_, u16>(row, index))
+        .or_else(|| column::<_, u8>(row, index))
+        .or_else(|| column::<_, f64>(row, index))
+        .or_else(|| column::<_, f32>(row, index))
+        .or_else(|| column::<_, DateTime<Utc>>(row, index))
+        .or_else(|| column::<_, NaiveDateTime>(row, index))
+        .or_else(|| column::<_, NaiveDate>(row, index))
+        .or_else(|| column::<_, Value>(row, index))
+        .unwrap_or(Value::Null)
+}
+
+fn sqlite_value(row: &SqliteRow, index: usize) -> Value {
+    column::<_, String>(row, index)
+        .or_else(|| column::<_, i64>(row, index))
+        .or_else(|| column::<_, f64>(row, index))
+        .or_else(|| column::<_, bool>(row, index))
+        .or_else(|| column::<_, NaiveDateTime>(row, index))
+        .unwrap_or(Value::Null)
+}
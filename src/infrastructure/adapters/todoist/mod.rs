@@ -0,0 +1,758 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    domain::{
+        DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource, ResourceSource,
+    },
+    infrastructure::http::{self, HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
+};
+
+const TODOIST_API: &str = "https://api.todoist.com/api/v1";
+const TODOIST_APP: &str = "https://app.todoist.com/app";
+const TODOIST_API_VERSION: &str = "v1";
+
+/// Most items Todoist returns per page.
+const TODOIST_MAX_PAGE_SIZE: usize = 200;
+
+/// Projects whose notes are fetched at once.
+const NOTE_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    results: Vec<T>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Task {
+    id: String,
+    /// The task's title, in Todoist's Markdown.
+    content: String,
+    #[serde(default)]
+    description: String,
+    project_id: String,
+    section_id: Option<String>,
+    parent_id: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    /// 1 (normal) to 4 (urgent), the reverse of the app's p1 to p4.
+    #[serde(default = "normal_priority")]
+    priority: u8,
+    due: Option<Due>,
+    deadline: Option<Deadline>,
+    #[serde(default)]
+    checked: bool,
+    responsible_uid: Option<String>,
+    #[serde(default)]
+    note_count: usize,
+    added_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+fn normal_priority() -> u8 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+struct Due {
+    /// A date, or a date and time when the task is due at one.
+    date: String,
+    /// How it was written, e.g. `every monday`.
+    string: Option<String>,
+    #[serde(default)]
+    is_recurring: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Deadline {
+    date: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Project {
+    id: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    parent_id: Option<String>,
+    #[serde(default)]
+    is_archived: bool,
+    #[serde(default)]
+    is_shared: bool,
+    #[serde(default)]
+    is_favorite: bool,
+    #[serde(default)]
+    inbox_project: bool,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// A comment on a task, or a note on a project.
+#[derive(Debug, Deserialize)]
+struct Comment {
+    #[serde(default)]
+    content: String,
+    posted_at: DateTime<Utc>,
+    file_attachment: Option<FileAttachment>,
+    #[serde(default)]
+    is_deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileAttachment {
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    id: String,
+    name: String,
+    project_id: Option<String>,
+}
+
+/// What a resource is in Todoist, which its ID starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Task,
+    Project,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Task => "task",
+            Kind::Project => "project",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "task" => Some(Kind::Task),
+            "project" => Some(Kind::Project),
+            _ => None,
+        }
+    }
+
+    /// The kinds the `kind` filter selects, both by default. `filter`,
+    /// `section_id` and `label` only apply to tasks, so they leave out
+    /// projects.
+    fn selected(query: &Query) -> Result<Vec<Self>, DomainError> {
+        let mut kinds = match query.filters.get("kind") {
+            None => vec![Kind::Task, Kind::Project],
+            Some(kind) => vec![Self::parse(kind).ok_or_else(|| {
+                DomainError::InvalidQuery(format!(
+                    "Unsupported kind filter: {} (expected \"task\" or \"project\")",
+                    kind
+                ))
+            })?],
+        };
+        if ["filter", "section_id", "label"]
+            .iter()
+            .any(|key| query.filters.contains_key(*key))
+        {
+            kinds.retain(|kind| *kind == Kind::Task);
+        }
+        Ok(kinds)
+    }
+}
+
+pub struct TodoistAdapter {
+    http: HttpClient,
+}
+
+impl TodoistAdapter {
+    /// `api_key` is a Todoist API token.
+    pub fn new(api_key: String) -> Result<Self, DomainError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?,
+        );
+
+        let http = HttpClient::new("Todoist", headers, vec![api_key])?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self { http })
+    }
+
+    /// Todoist allows a thousand requests every fifteen minutes and says
+    /// how long to wait when they run out.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![429, 500, 502, 503, 504],
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// Fetches `path` with the `params` given, returning the response and
+    /// Todoist's ID for the request.
+    async fn get_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(T, Option<String>), DomainError> {
+        let url = format!("{}{}", TODOIST_API, path);
+        let response = self.http.send(self.http.get(&url).query(params)).await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(match status {
+                StatusCode::NOT_FOUND => {
+                    DomainError::ResourceNotFound(format!("Todoist has no {}", path))
+                }
+                // Such as a filter it can't parse.
+                StatusCode::BAD_REQUEST => {
+                    DomainError::InvalidQuery(format!("Todoist rejected the query: {}", error_text))
+                }
+                _ => DomainError::ProviderError(format!("Todoist API error: {}", error_text)),
+            });
+        }
+
+        let data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((data, request_id))
+    }
+
+    /// Up to `limit` items of the paginated list at `path`, with Todoist's
+    /// ID for the last request.
+    async fn get_pages<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+        limit: usize,
+    ) -> Result<(Vec<T>, Option<String>), DomainError> {
+        let page_size = limit.clamp(1, TODOIST_MAX_PAGE_SIZE);
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut request_id = None;
+        loop {
+            let (page, id): (Page<T>, _) = self
+                .get_page(path, params, page_size, cursor.as_deref())
+                .await?;
+            items.extend(page.results);
+            request_id = id.or(request_id);
+
+            match page.next_cursor {
+                Some(next) if items.len() < limit => cursor = Some(next),
+                _ => break,
+            }
+        }
+        items.truncate(limit);
+        Ok((items, request_id))
+    }
+
+    /// The page of `page_size` items of the paginated list at `path` that
+    /// `cursor` points to, or the first, with Todoist's ID for the request.
+    async fn get_page<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+        page_size: usize,
+        cursor: Option<&str>,
+    ) -> Result<(Page<T>, Option<String>), DomainError> {
+        let page_size = page_size.to_string();
+        let mut page_params = params.to_vec();
+        page_params.push(("limit", &page_size));
+        page_params.extend(cursor.map(|cursor| ("cursor", cursor)));
+        self.get_json(path, &page_params).await
+    }
+
+    /// Every project, archived ones aside, by ID.
+    async fn projects(&self) -> Result<HashMap<String, Project>, DomainError> {
+        let (projects, _): (Vec<Project>, _) = self.get_pages("/projects", &[], usize::MAX).await?;
+        Ok(projects
+            .into_iter()
+            .map(|project| (project.id.clone(), project))
+            .collect())
+    }
+
+    /// Up to `limit` active tasks: those matching the Todoist filter
+    /// `filter` if given, else those `params` select.
+    async fn tasks(
+        &self,
+        filter: Option<&str>,
+        params: &[(&str, &str)],
+        limit: usize,
+        projects: &HashMap<String, Project>,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let (tasks, request_id): (Vec<Task>, _) = match filter {
+            Some(filter) => {
+                self.get_pages("/tasks/filter", &[("query", filter)], limit)
+                    .await?
+            }
+            None => self.get_pages("/tasks", params, limit).await?,
+        };
+        let resources: Vec<Resource> = tasks
+            .into_iter()
+            .map(|task| {
+                let project = projects.get(&task.project_id);
+                task_to_resource(task, project, String::new(), request_id.clone())
+            })
+            .collect();
+        report_progress(|| format!("Fetched {} Todoist tasks", resources.len()));
+        Ok(resources)
+    }
+
+    /// The page of `page_size` active tasks `cursor` points to, or the
+    /// first, of those matching the Todoist filter `filter` if given, else
+    /// those `params` select. Returns the cursor of the next page too.
+    async fn tasks_page(
+        &self,
+        filter: Option<&str>,
+        params: &[(&str, &str)],
+        page_size: usize,
+        cursor: Option<&str>,
+        projects: &HashMap<String, Project>,
+    ) -> Result<(Vec<Resource>, Option<String>), DomainError> {
+        let (page, request_id): (Page<Task>, _) = match filter {
+            Some(filter) => {
+                self.get_page("/tasks/filter", &[("query", filter)], page_size, cursor)
+                    .await?
+            }
+            None => self.get_page("/tasks", params, page_size, cursor).await?,
+        };
+        let resources = page
+            .results
+            .into_iter()
+            .map(|task| {
+                let project = projects.get(&task.project_id);
+                task_to_resource(task, project, String::new(), request_id.clone())
+            })
+            .collect();
+        Ok((resources, page.next_cursor))
+    }
+
+    /// The comments on a task, or with `project` set the notes on a
+    /// project, oldest first.
+    async fn comments(&self, id: &str, project: bool) -> Result<Vec<Comment>, DomainError> {
+        let parent = if project { "project_id" } else { "task_id" };
+        let (comments, _): (Vec<Comment>, _) = self
+            .get_pages("/comments", &[(parent, id)], usize::MAX)
+            .await?;
+        Ok(comments
+            .into_iter()
+            .filter(|comment| !comment.is_deleted)
+            .collect())
+    }
+
+    /// `projects` as resources, each with its notes.
+    async fn projects_with_notes(
+        &self,
+        projects: Vec<&Project>,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::new();
+        for batch in projects.chunks(NOTE_CONCURRENCY) {
+            let notes = batch.iter().map(|project| self.comments(&project.id, true));
+            for (project, notes) in batch.iter().zip(future::join_all(notes).await) {
+                resources.push(project_to_resource(project, &notes?));
+            }
+            report_progress(|| format!("Fetched {} Todoist projects", resources.len()));
+        }
+        Ok(resources)
+    }
+}
+
+/// The parameters selecting the tasks `query`'s filters ask for.
+fn task_params(query: &Query) -> Vec<(&'static str, &str)> {
+    ["project_id", "section_id", "label"]
+        .into_iter()
+        .filter_map(|key| Some((key, query.filters.get(key)?.as_str())))
+        .collect()
+}
+
+/// `comments` as Markdown under a heading, or nothing if there are none.
+fn thread(heading: &str, comments: &[Comment]) -> String {
+    if comments.is_empty() {
+        return String::new();
+    }
+    let mut thread = format!("## {}\n", heading);
+    for comment in comments {
+        thread.push_str(&format!(
+            "\n**{}**\n\n",
+            comment.posted_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+        if !comment.content.trim().is_empty() {
+            thread.push_str(comment.content.trim());
+            thread.push('\n');
+        }
+        if let Some(name) = comment
+            .file_attachment
+            .as_ref()
+            .and_then(|file| file.file_name.as_deref())
+        {
+            thread.push_str(&format!("(attached: {})\n", name));
+        }
+    }
+    thread
+}
+
+/// Joins a description and a comment thread into a resource's content.
+fn content(description: &str, thread: String) -> String {
+    match (description.trim(), thread) {
+        (description, thread) if thread.is_empty() => description.to_string(),
+        ("", thread) => thread,
+        (description, thread) => format!("{}\n\n{}", description, thread),
+    }
+}
+
+/// `thread` is the task's comments, rendered by `thread`, when they were
+/// fetched with it.
+fn task_to_resource(
+    task: Task,
+    project: Option<&Project>,
+    thread: String,
+    request_id: Option<String>,
+) -> Resource {
+    let mut metadata = HashMap::new();
+
+    metadata.insert("kind".to_string(), serde_json::json!("task"));
+    metadata.insert("project_id".to_string(), serde_json::json!(task.project_id));
+    if let Some(project) = project {
+        metadata.insert("project".to_string(), serde_json::json!(project.name));
+    }
+    if let Some(section_id) = &task.section_id {
+        metadata.insert("section_id".to_string(), serde_json::json!(section_id));
+    }
+    if let Some(parent_id) = &task.parent_id {
+        metadata.insert(
+            "parent_id".to_string(),
+            serde_json::json!(format!("todoist_task_{}", parent_id)),
+        );
+    }
+    metadata.insert("labels".to_string(), serde_json::json!(task.labels));
+    // As the app shows it: p1 is the most urgent.
+    metadata.insert(
+        "priority".to_string(),
+        serde_json::json!(format!("p{}", 5 - task.priority.clamp(1, 4))),
+    );
+    if let Some(due) = &task.due {
+        metadata.insert("due".to_string(), serde_json::json!(due.date));
+        if let Some(string) = &due.string {
+            metadata.insert("due_string".to_string(), serde_json::json!(string));
+        }
+        metadata.insert("recurring".to_string(), serde_json::json!(due.is_recurring));
+    }
+    if let Some(deadline) = &task.deadline {
+        metadata.insert("deadline".to_string(), serde_json::json!(deadline.date));
+    }
+    if let Some(assignee) = &task.responsible_uid {
+        metadata.insert("assignee_id".to_string(), serde_json::json!(assignee));
+    }
+    metadata.insert("completed".to_string(), serde_json::json!(task.checked));
+    metadata.insert(
+        "comment_count".to_string(),
+        serde_json::json!(task.note_count),
+    );
+
+    Resource {
+        id: format!("todoist_task_{}", task.id),
+        source: ResourceSource::Custom("todoist".to_string()),
+        title: task.content,
+        content: content(&task.description, thread),
+        metadata,
+        created_at: task.added_at,
+        updated_at: task.updated_at.unwrap_or(task.added_at),
+        provenance: Some(Provenance {
+            provider: "todoist".to_string(),
+            fetched_at: Utc::now(),
+            api_version: Some(TODOIST_API_VERSION.to_string()),
+            request_id,
+            url: Some(format!("{}/task/{}", TODOIST_APP, task.id)),
+        }),
+    }
+}
+
+/// A project as a resource holding its description and notes. Its date is
+/// its last change or note, whichever is later.
+fn project_to_resource(project: &Project, notes: &[Comment]) -> Resource {
+    let mut metadata = HashMap::new();
+
+    metadata.insert("kind".to_string(), serde_json::json!("project"));
+    if let Some(parent_id) = &project.parent_id {
+        metadata.insert(
+            "parent_id".to_string(),
+            serde_json::json!(format!("todoist_project_{}", parent_id)),
+        );
+    }
+    metadata.insert("shared".to_string(), serde_json::json!(project.is_shared));
+    metadata.insert(
+        "favorite".to_string(),
+        serde_json::json!(project.is_favorite),
+    );
+    if project.inbox_project {
+        metadata.insert("inbox".to_string(), serde_json::json!(true));
+    }
+    metadata.insert("note_count".to_string(), serde_json::json!(notes.len()));
+    if project.is_archived {
+        metadata.insert("archived".to_string(), serde_json::json!(true));
+    }
+
+    let created_at = project.created_at.unwrap_or_default();
+    let updated_at = notes
+        .iter()
+        .map(|note| note.posted_at)
+        .chain(project.updated_at)
+        .max()
+        .unwrap_or(created_at);
+
+    Resource {
+        id: format!("todoist_project_{}", project.id),
+        source: ResourceSource::Custom("todoist".to_string()),
+        title: project.name.clone(),
+        content: content(&project.description, thread("Notes", notes)),
+        metadata,
+        created_at,
+        updated_at,
+        provenance: Some(Provenance {
+            provider: "todoist".to_string(),
+            fetched_at: Utc::now(),
+            api_version: Some(TODOIST_API_VERSION.to_string()),
+            request_id: None,
+            url: Some(format!("{}/project/{}", TODOIST_APP, project.id)),
+        }),
+    }
+}
+
+/// Names the missing `kind` of object in a not found error.
+fn not_found(error: DomainError, kind: &str, id: &str) -> DomainError {
+    match error {
+        DomainError::ResourceNotFound(_) => {
+            DomainError::ResourceNotFound(format!("Todoist {} not found: {}", kind, id))
+        }
+        e => e,
+    }
+}
+
+/// `query` as a Todoist filter matching tasks that contain it, with the
+/// characters filters give meaning to escaped.
+fn search_filter(query: &str) -> String {
+    let mut escaped = String::new();
+    for c in query.trim().chars() {
+        if "&|!(),\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    format!("search: {}", escaped)
+}
+
+#[async_trait]
+impl ResourceProvider for TodoistAdapter {
+    /// Lists active tasks and projects with their notes. `kind` (`task` or
+    /// `project`) lists only one. `filter` takes a Todoist filter such as
+    /// `today | overdue` or `#Work & p1` and lists the tasks it matches;
+    /// otherwise `project_id`, `section_id` and `label` narrow the tasks,
+    /// and `project_id` the projects.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let filter = query.filters.get("filter").map(String::as_str);
+        let project_id = query.filters.get("project_id");
+        let projects = self.projects().await?;
+        let mut resources = Vec::new();
+        for kind in Kind::selected(query)? {
+            match kind {
+                Kind::Task => {
+                    let params = task_params(query);
+                    resources.extend(self.tasks(filter, &params, limit, &projects).await?);
+                }
+                Kind::Project => {
+                    let selected: Vec<&Project> = projects
+                        .values()
+                        .filter(|project| project_id.is_none_or(|id| project.id == *id))
+                        .collect();
+                    resources.extend(self.projects_with_notes(selected).await?);
+                }
+            }
+        }
+
+        resources.sort_by_key(|resource| std::cmp::Reverse(resource.updated_at));
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    /// A task with its comments, or a project with its notes.
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let id = id.strip_prefix("todoist_").unwrap_or(id);
+        if let Some(project_id) = id.strip_prefix("project_") {
+            let (project, _): (Project, _) = self
+                .get_json(&format!("/projects/{}", project_id), &[])
+                .await
+                .map_err(|e| not_found(e, "project", project_id))?;
+            let notes = self.comments(project_id, true).await?;
+            return Ok(project_to_resource(&project, &notes));
+        }
+        let Some(task_id) = id.strip_prefix("task_") else {
+            return Err(DomainError::ResourceNotFound(format!(
+                "Not a Todoist task or project: {}",
+                id
+            )));
+        };
+
+        let (task, request_id): (Task, _) = self
+            .get_json(&format!("/tasks/{}", task_id), &[])
+            .await
+            .map_err(|e| not_found(e, "task", task_id))?;
+        let project = self
+            .get_json::<Project>(&format!("/projects/{}", task.project_id), &[])
+            .await
+            .ok()
+            .map(|(project, _)| project);
+        let comments = self.comments(task_id, false).await?;
+        Ok(task_to_resource(
+            task,
+            project.as_ref(),
+            thread("Comments", &comments),
+            request_id,
+        ))
+    }
+
+    /// Active tasks containing `query`, found by Todoist, and projects whose
+    /// name or description contains every word of it.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let projects = self.projects().await?;
+        let mut resources = self
+            .tasks(
+                Some(&search_filter(query)),
+                &[],
+                TODOIST_MAX_PAGE_SIZE,
+                &projects,
+            )
+            .await?;
+
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect();
+        let matching: Vec<&Project> = projects
+            .values()
+            .filter(|project| {
+                let text = format!("{} {}", project.name, project.description).to_lowercase();
+                !words.is_empty() && words.iter().all(|word| text.contains(word))
+            })
+            .collect();
+        resources.extend(self.projects_with_notes(matching).await?);
+        Ok(resources)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "Todoist"
+    }
+
+    /// Lists every active task a page at a time, then every project with
+    /// its notes in one. Cursors are the kind being listed and Todoist's
+    /// cursor for its next page, empty to start on it.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(TODOIST_MAX_PAGE_SIZE)
+            .min(TODOIST_MAX_PAGE_SIZE);
+        let kinds = Kind::selected(query)?;
+        let (kind, position) = match &cursor {
+            Some(cursor) => {
+                let (kind, position) = cursor
+                    .split_once(':')
+                    .and_then(|(kind, position)| Some((Kind::parse(kind)?, position)))
+                    .ok_or_else(|| {
+                        DomainError::InvalidQuery(format!("Invalid Todoist cursor: {}", cursor))
+                    })?;
+                (kind, Some(position).filter(|position| !position.is_empty()))
+            }
+            None => match kinds.first() {
+                Some(kind) => (*kind, None),
+                None => return Ok(ListingPage::default()),
+            },
+        };
+
+        let projects = self.projects().await?;
+        let (resources, next) = match kind {
+            Kind::Task => {
+                let filter = query.filters.get("filter").map(String::as_str);
+                let params = task_params(query);
+                self.tasks_page(filter, &params, page_size, position, &projects)
+                    .await?
+            }
+            Kind::Project => {
+                let project_id = query.filters.get("project_id");
+                let selected: Vec<&Project> = projects
+                    .values()
+                    .filter(|project| project_id.is_none_or(|id| project.id == *id))
+                    .collect();
+                (self.projects_with_notes(selected).await?, None)
+            }
+        };
+        let next_cursor = match next {
+            Some(next) => Some(format!("{}:{}", kind.name(), next)),
+            None => kinds
+                .iter()
+                .skip_while(|other| **other != kind)
+                .nth(1)
+                .map(|other| format!("{}:", other.name())),
+        };
+        Ok(ListingPage {
+            resources,
+            next_cursor,
+        })
+    }
+
+    /// Lists projects, their sections, and labels, for the `project_id`,
+    /// `section_id` and `label` filters.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        let projects = self.projects().await?;
+        let (sections, _): (Vec<Named>, _) = self.get_pages("/sections", &[], usize::MAX).await?;
+        let (labels, _): (Vec<Named>, _) = self.get_pages("/labels", &[], usize::MAX).await?;
+
+        let mut items: Vec<DiscoveredItem> = projects
+            .into_values()
+            .map(|project| DiscoveredItem {
+                url: Some(format!("{}/project/{}", TODOIST_APP, project.id)),
+                id: project.id,
+                kind: "project".to_string(),
+                title: project.name,
+                parent_id: project.parent_id,
+            })
+            .collect();
+        items.sort_by(|a, b| a.title.cmp(&b.title));
+        items.extend(sections.into_iter().map(|section| DiscoveredItem {
+            id: section.id,
+            kind: "section".to_string(),
+            title: section.name,
+            parent_id: section.project_id,
+            url: None,
+        }));
+        items.extend(labels.into_iter().map(|label| DiscoveredItem {
+            id: label.id,
+            kind: "label".to_string(),
+            title: label.name,
+            parent_id: None,
+            url: None,
+        }));
+        Ok(items)
+    }
+}
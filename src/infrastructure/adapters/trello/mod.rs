@@ -0,0 +1,508 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    domain::{
+        DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource, ResourceSource,
+    },
+    infrastructure::http::{self, HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
+};
+
+const TRELLO_API: &str = "https://api.trello.com/1";
+
+/// Card fields every request asks for, matching `Card`.
+const CARD_FIELDS: &str =
+    "name,desc,idBoard,idList,shortLink,url,dateLastActivity,due,dueComplete,closed,labels";
+
+/// Most cards Trello returns for one search.
+const TRELLO_MAX_SEARCH_RESULTS: usize = 1000;
+
+/// Most cards Trello returns for one request of a board's.
+const TRELLO_MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct Card {
+    id: String,
+    name: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(rename = "idBoard")]
+    board_id: String,
+    #[serde(rename = "idList")]
+    list_id: String,
+    /// The short ID in the card's URL, e.g. `aBcD1234`.
+    #[serde(rename = "shortLink")]
+    short_link: String,
+    url: Option<String>,
+    #[serde(rename = "dateLastActivity")]
+    last_activity: DateTime<Utc>,
+    due: Option<DateTime<Utc>>,
+    #[serde(rename = "dueComplete", default)]
+    due_complete: bool,
+    #[serde(default)]
+    closed: bool,
+    #[serde(default)]
+    labels: Vec<Label>,
+    /// Only there when asked for alongside the card.
+    board: Option<Named>,
+    list: Option<Named>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    #[serde(default)]
+    name: String,
+    color: Option<String>,
+}
+
+/// A board or list, by ID and name.
+#[derive(Debug, Deserialize)]
+struct Named {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Board {
+    name: String,
+    #[serde(default)]
+    lists: Vec<Named>,
+}
+
+pub struct TrelloAdapter {
+    http: HttpClient,
+}
+
+impl TrelloAdapter {
+    /// `api_key` is a Trello API key and a token it issued, written
+    /// `<key>:<token>`.
+    pub fn new(api_key: String) -> Result<Self, DomainError> {
+        let (key, token) = api_key
+            .split_once(':')
+            .filter(|(key, token)| !key.is_empty() && !token.is_empty())
+            .ok_or_else(|| {
+                DomainError::ProviderError(
+                    "Trello API key must be written <key>:<token>".to_string(),
+                )
+            })?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!(
+                "OAuth oauth_consumer_key=\"{}\", oauth_token=\"{}\"",
+                key, token
+            ))
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?,
+        );
+
+        let http = HttpClient::new("Trello", headers, vec![key.to_string(), token.to_string()])?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self { http })
+    }
+
+    /// Trello's rate limits reset every ten seconds, so throttled requests
+    /// are worth retrying a few times after a short wait.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            retry_on: vec![429, 500, 502, 503, 504],
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// Fetches `path` with the `params` given, returning the response and
+    /// Trello's ID for the request.
+    async fn get_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(T, Option<String>), DomainError> {
+        let url = format!("{}{}", TRELLO_API, path);
+        let response = self.http.send(self.http.get(&url).query(params)).await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(if status == StatusCode::NOT_FOUND {
+                DomainError::ResourceNotFound(format!("Trello has no {}", path))
+            } else {
+                DomainError::ProviderError(format!("Trello API error: {}", error_text))
+            });
+        }
+
+        let data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((data, request_id))
+    }
+
+    /// The IDs of the boards `query` lists cards from: those in its
+    /// comma-separated `board_id` filter, else every open board the token's
+    /// user is a member of.
+    async fn board_ids(&self, query: &Query) -> Result<Vec<String>, DomainError> {
+        if let Some(board_ids) = query.filters.get("board_id") {
+            let board_ids: Vec<String> = board_ids
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string)
+                .collect();
+            if board_ids.is_empty() {
+                return Err(DomainError::InvalidQuery(
+                    "Empty board_id filter".to_string(),
+                ));
+            }
+            return Ok(board_ids);
+        }
+
+        let (boards, _): (Vec<Named>, _) = self
+            .get_json(
+                "/members/me/boards",
+                &[("filter", "open"), ("fields", "name")],
+            )
+            .await?;
+        Ok(boards.into_iter().map(|board| board.id).collect())
+    }
+
+    /// The cards on board `board_id`, with its lists, as resources.
+    async fn board_cards(
+        &self,
+        board_id: &str,
+        include_archived: bool,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let mut resources = Vec::new();
+        let mut before = None;
+        loop {
+            let (cards, next) = self
+                .board_cards_page(
+                    board_id,
+                    include_archived,
+                    TRELLO_MAX_PAGE_SIZE,
+                    before.as_deref(),
+                )
+                .await?;
+            resources.extend(cards);
+            match next {
+                Some(next) => before = Some(next),
+                None => return Ok(resources),
+            }
+        }
+    }
+
+    /// Up to `page_size` cards on board `board_id`, with its lists, as
+    /// resources, newest first from the one before card `before` if given.
+    /// Returns the ID to go on before too, if there may be more.
+    async fn board_cards_page(
+        &self,
+        board_id: &str,
+        include_archived: bool,
+        page_size: usize,
+        before: Option<&str>,
+    ) -> Result<(Vec<Resource>, Option<String>), DomainError> {
+        let (board, _): (Board, _) = self
+            .get_json(
+                &format!("/boards/{}", board_id),
+                &[
+                    ("fields", "name"),
+                    ("lists", "all"),
+                    ("list_fields", "name"),
+                ],
+            )
+            .await?;
+
+        let filter = if include_archived { "all" } else { "open" };
+        let limit = page_size.to_string();
+        let mut params = vec![("fields", CARD_FIELDS), ("limit", limit.as_str())];
+        params.extend(before.map(|before| ("before", before)));
+        let (cards, request_id): (Vec<Card>, _) = self
+            .get_json(&format!("/boards/{}/cards/{}", board_id, filter), &params)
+            .await?;
+        // IDs grow with time, so the oldest card's is where the next starts.
+        let next = cards
+            .iter()
+            .map(|card| card.id.as_str())
+            .min()
+            .filter(|_| cards.len() >= page_size)
+            .map(str::to_string);
+
+        let Board { name, lists } = board;
+        let lists: HashMap<&str, &str> = lists
+            .iter()
+            .map(|list| (list.id.as_str(), list.name.as_str()))
+            .collect();
+        let resources = cards
+            .into_iter()
+            .map(|card| {
+                let list = lists.get(card.list_id.as_str()).copied();
+                self.card_to_resource(card, Some(&name), list, request_id.clone())
+            })
+            .collect();
+        Ok((resources, next))
+    }
+
+    /// `board` and `list` name the card's board and list when it wasn't
+    /// fetched with them.
+    fn card_to_resource(
+        &self,
+        card: Card,
+        board: Option<&str>,
+        list: Option<&str>,
+        request_id: Option<String>,
+    ) -> Resource {
+        let mut metadata = HashMap::new();
+
+        metadata.insert("identifier".to_string(), serde_json::json!(card.short_link));
+        metadata.insert("board_id".to_string(), serde_json::json!(card.board_id));
+        metadata.insert("list_id".to_string(), serde_json::json!(card.list_id));
+        if let Some(board) = card.board.as_ref().map(|b| b.name.as_str()).or(board) {
+            metadata.insert("board".to_string(), serde_json::json!(board));
+        }
+        if let Some(list) = card.list.as_ref().map(|l| l.name.as_str()).or(list) {
+            metadata.insert("list".to_string(), serde_json::json!(list));
+        }
+
+        // Labels needn't have names, in which case Trello shows their color.
+        let labels: Vec<String> = card
+            .labels
+            .into_iter()
+            .filter_map(|label| Some(label.name).filter(|n| !n.is_empty()).or(label.color))
+            .collect();
+        metadata.insert("labels".to_string(), serde_json::json!(labels));
+
+        if let Some(due) = card.due {
+            metadata.insert("due".to_string(), serde_json::json!(due));
+            metadata.insert(
+                "due_complete".to_string(),
+                serde_json::json!(card.due_complete),
+            );
+        }
+
+        if card.closed {
+            metadata.insert("archived".to_string(), serde_json::json!(true));
+        }
+
+        Resource {
+            id: format!("trello_{}", card.id),
+            source: ResourceSource::Custom("trello".to_string()),
+            title: card.name,
+            content: card.desc,
+            metadata,
+            created_at: created_at(&card.id).unwrap_or(card.last_activity),
+            updated_at: card.last_activity,
+            provenance: Some(Provenance {
+                provider: "trello".to_string(),
+                fetched_at: Utc::now(),
+                api_version: None,
+                request_id,
+                url: card.url,
+            }),
+        }
+    }
+}
+
+/// When the object with Trello ID `id` was created, which its first eight
+/// hex digits hold as a Unix timestamp.
+fn created_at(id: &str) -> Option<DateTime<Utc>> {
+    let seconds = i64::from_str_radix(id.get(..8)?, 16).ok()?;
+    Utc.timestamp_opt(seconds, 0).single()
+}
+
+#[async_trait]
+impl ResourceProvider for TrelloAdapter {
+    /// Lists cards, most recently active first. `board_id` takes one or more
+    /// comma-separated board IDs, `list_id` keeps the cards in one list, and
+    /// `include_archived=true` adds archived cards.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let include_archived = query
+            .filters
+            .get("include_archived")
+            .is_some_and(|value| value == "true");
+        let list_id = query.filters.get("list_id");
+
+        let mut resources = Vec::new();
+        for board_id in self.board_ids(query).await? {
+            let cards = self.board_cards(&board_id, include_archived).await?;
+            resources.extend(cards.into_iter().filter(|card| {
+                list_id.is_none_or(|list_id| card.metadata["list_id"] == list_id.as_str())
+            }));
+            report_progress(|| format!("Fetched {} Trello cards", resources.len()));
+        }
+
+        resources.sort_by_key(|resource| std::cmp::Reverse(resource.updated_at));
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let card_id = id.strip_prefix("trello_").unwrap_or(id);
+        let (card, request_id): (Card, _) = self
+            .get_json(
+                &format!("/cards/{}", card_id),
+                &[
+                    ("fields", CARD_FIELDS),
+                    ("board", "true"),
+                    ("board_fields", "name"),
+                    ("list", "true"),
+                ],
+            )
+            .await
+            .map_err(|e| match e {
+                DomainError::ResourceNotFound(_) => {
+                    DomainError::ResourceNotFound(format!("Trello card not found: {}", card_id))
+                }
+                e => e,
+            })?;
+        Ok(self.card_to_resource(card, None, None, request_id))
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct SearchData {
+            cards: Vec<Card>,
+        }
+
+        let limit = TRELLO_MAX_SEARCH_RESULTS.to_string();
+        let (data, request_id): (SearchData, _) = self
+            .get_json(
+                "/search",
+                &[
+                    ("query", query),
+                    ("modelTypes", "cards"),
+                    ("partial", "true"),
+                    ("cards_limit", &limit),
+                    ("card_fields", CARD_FIELDS),
+                    ("card_board", "true"),
+                    ("card_list", "true"),
+                ],
+            )
+            .await?;
+
+        Ok(data
+            .cards
+            .into_iter()
+            .map(|card| self.card_to_resource(card, None, None, request_id.clone()))
+            .collect())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "Trello"
+    }
+
+    /// Lists each board's cards, newest first, a page at a time. Cursors
+    /// are the board being listed and the card to go on before, empty to
+    /// start on the board.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(TRELLO_MAX_PAGE_SIZE)
+            .min(TRELLO_MAX_PAGE_SIZE);
+        let include_archived = query
+            .filters
+            .get("include_archived")
+            .is_some_and(|value| value == "true");
+        let list_id = query.filters.get("list_id");
+
+        let board_ids = self.board_ids(query).await?;
+        let (index, before) = match &cursor {
+            Some(cursor) => {
+                let (board_id, before) = cursor.split_once(':').unwrap_or((cursor, ""));
+                let index = board_ids
+                    .iter()
+                    .position(|id| id == board_id)
+                    .ok_or_else(|| {
+                        DomainError::InvalidQuery(format!("Invalid Trello cursor: {}", cursor))
+                    })?;
+                (index, Some(before).filter(|before| !before.is_empty()))
+            }
+            None => (0, None),
+        };
+        let Some(board_id) = board_ids.get(index) else {
+            return Ok(ListingPage::default());
+        };
+
+        let (cards, next) = self
+            .board_cards_page(board_id, include_archived, page_size, before)
+            .await?;
+        let next_cursor = match next {
+            Some(next) => Some(format!("{}:{}", board_id, next)),
+            None => board_ids.get(index + 1).map(|next| format!("{}:", next)),
+        };
+        Ok(ListingPage {
+            resources: cards
+                .into_iter()
+                .filter(|card| {
+                    list_id.is_none_or(|list_id| card.metadata["list_id"] == list_id.as_str())
+                })
+                .collect(),
+            next_cursor,
+        })
+    }
+
+    /// Lists the open boards the token's user is a member of, and their
+    /// open lists.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct DiscoveredBoard {
+            id: String,
+            name: String,
+            url: Option<String>,
+            #[serde(default)]
+            lists: Vec<Named>,
+        }
+
+        let (boards, _): (Vec<DiscoveredBoard>, _) = self
+            .get_json(
+                "/members/me/boards",
+                &[
+                    ("filter", "open"),
+                    ("fields", "name,url"),
+                    ("lists", "open"),
+                    ("list_fields", "name"),
+                ],
+            )
+            .await?;
+
+        let mut items = Vec::new();
+        for board in boards {
+            items.push(DiscoveredItem {
+                id: board.id.clone(),
+                kind: "board".to_string(),
+                title: board.name,
+                parent_id: None,
+                url: board.url,
+            });
+            items.extend(board.lists.into_iter().map(|list| DiscoveredItem {
+                id: list.id,
+                kind: "list".to_string(),
+                title: list.name,
+                parent_id: Some(board.id.clone()),
+                url: None,
+            }));
+        }
+        Ok(items)
+    }
+}
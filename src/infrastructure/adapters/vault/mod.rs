@@ -0,0 +1,421 @@
+pub mod obsidian;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use self::obsidian::{Excluded, NoteInfo};
+use super::{percent_encode, provider_name};
+use crate::{
+    domain::{DomainError, Provenance, Query, Resource, ResourceSource},
+    ports::ResourceProvider,
+};
+
+/// Extensions of the files served as notes.
+const NOTE_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// Serves the Markdown notes in a folder as a provider named after it. In
+/// Obsidian mode, `[[wikilinks]]` are resolved to the notes they link, each
+/// note lists the notes linking to it, tags and aliases are read, and files
+/// Obsidian excludes are left out.
+///
+/// Resource IDs are the provider name and the note's path in the vault,
+/// e.g. `notes_Projects/Roadmap.md`. Notes are read from disk on every
+/// request, so edits show up at once.
+pub struct VaultAdapter {
+    name: &'static str,
+    root: Arc<PathBuf>,
+    obsidian: bool,
+}
+
+/// A note as read from disk.
+struct Note {
+    /// Relative to the vault, with `/` between folders.
+    path: String,
+    content: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    info: NoteInfo,
+}
+
+impl Note {
+    /// The file name without its extension, which Obsidian titles and links
+    /// notes by.
+    fn name(&self) -> &str {
+        let file = self.path.rsplit('/').next().unwrap_or(&self.path);
+        file.rsplit_once('.').map_or(file, |(name, _)| name)
+    }
+
+    fn folder(&self) -> &str {
+        self.path.rsplit_once('/').map_or("", |(folder, _)| folder)
+    }
+}
+
+/// Every note in a vault, with their links resolved.
+struct Vault {
+    notes: Vec<Note>,
+    /// For each note, the note each of its links resolves to, if any.
+    links: Vec<Vec<Option<usize>>>,
+    /// For each note, the notes linking to it.
+    backlinks: Vec<Vec<usize>>,
+}
+
+impl VaultAdapter {
+    /// `name` becomes the provider name and ID prefix; see
+    /// [`provider_name`]. Obsidian mode is on when `obsidian` says so, or
+    /// by default when the folder has Obsidian's `.obsidian` settings.
+    pub fn new(name: &str, root: PathBuf, obsidian: Option<bool>) -> Result<Self, DomainError> {
+        let name = provider_name("vault", name)?;
+        if !root.is_dir() {
+            return Err(DomainError::ProviderError(format!(
+                "Vault {} is not a folder",
+                root.display()
+            )));
+        }
+        let obsidian = obsidian.unwrap_or_else(|| root.join(".obsidian").is_dir());
+        Ok(Self {
+            name,
+            root: Arc::new(root),
+            obsidian,
+        })
+    }
+
+    /// Reads the whole vault, off the async workers.
+    async fn load(&self) -> Result<Vault, DomainError> {
+        let root = self.root.clone();
+        let obsidian = self.obsidian;
+        tokio::task::spawn_blocking(move || Vault::load(&root, obsidian))
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?
+    }
+
+    fn id(&self, path: &str) -> String {
+        format!("{}_{}", self.name, path)
+    }
+
+    fn to_resource(&self, vault: &Vault, index: usize) -> Resource {
+        let note = &vault.notes[index];
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            "uri".to_string(),
+            json!(format!("file://{}", self.root.join(&note.path).display())),
+        );
+        metadata.insert("path".to_string(), json!(note.path));
+        if self.obsidian {
+            if !note.info.tags.is_empty() {
+                metadata.insert("tags".to_string(), json!(note.info.tags));
+            }
+            if !note.info.aliases.is_empty() {
+                metadata.insert("aliases".to_string(), json!(note.info.aliases));
+            }
+            let links: Vec<Value> = note
+                .info
+                .links
+                .iter()
+                .zip(&vault.links[index])
+                .map(|(target, resolved)| {
+                    json!({
+                        "target": target,
+                        "id": resolved.map(|linked| self.id(&vault.notes[linked].path)),
+                    })
+                })
+                .collect();
+            if !links.is_empty() {
+                metadata.insert("wikilinks".to_string(), json!(links));
+            }
+            let backlinks: Vec<Value> = vault.backlinks[index]
+                .iter()
+                .map(|&linking| {
+                    let linking = &vault.notes[linking];
+                    json!({ "id": self.id(&linking.path), "title": linking.name() })
+                })
+                .collect();
+            if !backlinks.is_empty() {
+                metadata.insert("backlinks".to_string(), json!(backlinks));
+            }
+        }
+
+        Resource {
+            id: self.id(&note.path),
+            source: ResourceSource::Custom(self.name.to_string()),
+            title: note.name().to_string(),
+            content: note.content.clone(),
+            metadata,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+            provenance: Some(Provenance {
+                provider: self.name.to_string(),
+                fetched_at: Utc::now(),
+                api_version: None,
+                request_id: None,
+                url: self.obsidian.then(|| self.open_url(note)),
+            }),
+        }
+    }
+
+    /// Link opening `note` in Obsidian.
+    fn open_url(&self, note: &Note) -> String {
+        let vault = self
+            .root
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let file = note
+            .path
+            .rsplit_once('.')
+            .map_or(note.path.as_str(), |(file, _)| file);
+        format!(
+            "obsidian://open?vault={}&file={}",
+            percent_encode(&vault),
+            percent_encode(file)
+        )
+    }
+
+    /// Notes matching the `folder` and `tag` filters, most recently changed
+    /// first. A tag filter also matches nested tags, e.g. `project` matches
+    /// `project/alpha`.
+    fn select(&self, vault: &Vault, filters: &HashMap<String, String>) -> Vec<usize> {
+        let folder = filters
+            .get("folder")
+            .map(|folder| format!("{}/", folder.trim_matches('/')));
+        let tag = filters
+            .get("tag")
+            .map(|tag| tag.trim_start_matches('#').to_lowercase());
+        let mut selected: Vec<usize> = (0..vault.notes.len())
+            .filter(|&index| {
+                let note = &vault.notes[index];
+                folder
+                    .as_deref()
+                    .is_none_or(|folder| folder == "/" || note.path.starts_with(folder))
+                    && tag.as_deref().is_none_or(|wanted| {
+                        note.info.tags.iter().any(|tag| {
+                            let tag = tag.to_lowercase();
+                            tag == wanted || tag.starts_with(&format!("{}/", wanted))
+                        })
+                    })
+            })
+            .collect();
+        selected.sort_by_key(|&index| std::cmp::Reverse(vault.notes[index].updated_at));
+        selected
+    }
+}
+
+impl Vault {
+    fn load(root: &Path, obsidian: bool) -> Result<Self, DomainError> {
+        let excluded = if obsidian {
+            Excluded::load(root)
+        } else {
+            Excluded::default()
+        };
+        let mut paths = Vec::new();
+        find_notes(root, "", &mut paths).map_err(|e| {
+            DomainError::ProviderError(format!("Failed to read {}: {}", root.display(), e))
+        })?;
+        let notes: Vec<Note> = paths
+            .into_iter()
+            .filter(|path| !excluded.contains(path))
+            .filter_map(|path| match read_note(root, path) {
+                Ok(note) => Some(note),
+                Err((path, e)) => {
+                    tracing::warn!("Skipping {}: {}", path, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut vault = Self {
+            links: Vec::new(),
+            backlinks: vec![Vec::new(); notes.len()],
+            notes,
+        };
+        if obsidian {
+            vault.resolve_links();
+        } else {
+            vault.links = vec![Vec::new(); vault.notes.len()];
+        }
+        Ok(vault)
+    }
+
+    /// Resolves each note's links the way Obsidian does: a target with
+    /// folders names the note at that path, one without names the notes
+    /// called that, or failing that those with that alias. When several
+    /// match, the one in the linking note's folder wins, then the one with
+    /// the shortest path. Links to attachments are dropped.
+    fn resolve_links(&mut self) {
+        let mut names: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut aliases: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, note) in self.notes.iter().enumerate() {
+            names
+                .entry(note.name().to_lowercase())
+                .or_default()
+                .push(index);
+            for alias in &note.info.aliases {
+                aliases.entry(alias.to_lowercase()).or_default().push(index);
+            }
+        }
+
+        for from in 0..self.notes.len() {
+            let mut targets = std::mem::take(&mut self.notes[from].info.links);
+            let mut resolved = Vec::with_capacity(targets.len());
+            targets.retain(|target| {
+                let target = target.to_lowercase();
+                let stem = NOTE_EXTENSIONS
+                    .iter()
+                    .find_map(|extension| target.strip_suffix(&format!(".{}", extension)))
+                    .unwrap_or(&target);
+                let candidates: Vec<usize> = if stem.contains('/') {
+                    let stem = stem.trim_start_matches('/');
+                    (0..self.notes.len())
+                        .filter(|&index| {
+                            let path = self.notes[index].path.to_lowercase();
+                            let path = path
+                                .rsplit_once('.')
+                                .map_or(path.as_str(), |(path, _)| path);
+                            path == stem || path.ends_with(&format!("/{}", stem))
+                        })
+                        .collect()
+                } else {
+                    names
+                        .get(stem)
+                        .or_else(|| aliases.get(stem))
+                        .cloned()
+                        .unwrap_or_default()
+                };
+                let folder = self.notes[from].folder();
+                let best = candidates.into_iter().min_by_key(|&index| {
+                    let note = &self.notes[index];
+                    (note.folder() != folder, note.path.len(), note.path.as_str())
+                });
+                // Unresolved targets with another extension are attachments,
+                // such as embedded images.
+                let attachment = best.is_none()
+                    && target.rsplit_once('.').is_some_and(|(_, extension)| {
+                        !extension.contains([' ', '/']) && !NOTE_EXTENSIONS.contains(&extension)
+                    });
+                if !attachment {
+                    resolved.push(best);
+                }
+                !attachment
+            });
+            for &to in resolved.iter().flatten() {
+                if to != from && !self.backlinks[to].contains(&from) {
+                    self.backlinks[to].push(from);
+                }
+            }
+            self.notes[from].info.links = targets;
+            self.links.push(resolved);
+        }
+    }
+
+    fn find(&self, path: &str) -> Option<usize> {
+        self.notes.iter().position(|note| note.path == path)
+    }
+}
+
+/// Collects the paths of the notes under `folder`, relative to `root`.
+/// Hidden files and folders, such as `.obsidian` and `.trash`, are skipped,
+/// and symbolic links to folders aren't followed.
+fn find_notes(root: &Path, folder: &str, paths: &mut Vec<String>) -> std::io::Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(root.join(folder))?
+        .filter_map(Result::ok)
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = if folder.is_empty() {
+            name
+        } else {
+            format!("{}/{}", folder, name)
+        };
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if let Err(e) = find_notes(root, &path, paths) {
+                tracing::warn!("Skipping {}: {}", path, e);
+            }
+        } else if Path::new(&path)
+            .extension()
+            .is_some_and(|extension| NOTE_EXTENSIONS.iter().any(|note| extension == *note))
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_note(root: &Path, path: String) -> Result<Note, (String, std::io::Error)> {
+    let file = root.join(&path);
+    let read = || -> std::io::Result<Note> {
+        let content = std::fs::read_to_string(&file)?;
+        let metadata = std::fs::metadata(&file)?;
+        let updated_at: DateTime<Utc> = metadata.modified()?.into();
+        let created_at = metadata.created().map_or(updated_at, DateTime::from);
+        Ok(Note {
+            info: NoteInfo::parse(&content),
+            path: path.clone(),
+            content,
+            created_at,
+            updated_at,
+        })
+    };
+    read().map_err(|e| (path.clone(), e))
+}
+
+#[async_trait]
+impl ResourceProvider for VaultAdapter {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let vault = self.load().await?;
+        Ok(self
+            .select(&vault, &query.filters)
+            .into_iter()
+            .take(query.limit.unwrap_or(usize::MAX))
+            .map(|index| self.to_resource(&vault, index))
+            .collect())
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let not_found =
+            || DomainError::ResourceNotFound(format!("No note {} in {}", id, self.name));
+        let path = id
+            .strip_prefix(self.name)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .ok_or_else(not_found)?;
+        let vault = self.load().await?;
+        let index = vault.find(path).ok_or_else(not_found)?;
+        Ok(self.to_resource(&vault, index))
+    }
+
+    /// Matches the query against the notes' names, paths, aliases, tags and
+    /// text, most recently changed first.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let needle = query.trim_start_matches('#').to_lowercase();
+        let vault = self.load().await?;
+        Ok(self
+            .select(&vault, &HashMap::new())
+            .into_iter()
+            .filter(|&index| {
+                let note = &vault.notes[index];
+                note.path.to_lowercase().contains(&needle)
+                    || note.content.to_lowercase().contains(&needle)
+                    || note
+                        .info
+                        .aliases
+                        .iter()
+                        .chain(&note.info.tags)
+                        .any(|name| name.to_lowercase().contains(&needle))
+            })
+            .map(|index| self.to_resource(&vault, index))
+            .collect())
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+}
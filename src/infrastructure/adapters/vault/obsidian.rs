@@ -0,0 +1,189 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// `[[target]]`, `[[target#heading|label]]` and `![[embed]]`, capturing the
+/// target.
+static WIKILINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!?\[\[([^\[\]|#^]*)[^\[\]]*\]\]").unwrap());
+
+/// `#tag` and `#nested/tag` at the start of a line or after whitespace, so
+/// headings and anchors in URLs aren't tags.
+static TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)(?:^|\s)#([\p{L}\p{N}_/-]+)").unwrap());
+
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`\n]*`").unwrap());
+
+/// What a note says about itself, in its front matter and text.
+#[derive(Debug, Default)]
+pub struct NoteInfo {
+    /// Without the `#`, in the order they first appear.
+    pub tags: Vec<String>,
+    /// Other names the note can be linked to by.
+    pub aliases: Vec<String>,
+    /// The targets of its wikilinks as written, e.g. `Projects/Roadmap`.
+    pub links: Vec<String>,
+}
+
+impl NoteInfo {
+    pub fn parse(content: &str) -> Self {
+        let (front_matter, body) = split_front_matter(content);
+        let front_matter = front_matter.unwrap_or_default();
+        let prose = prose(body);
+
+        let mut tags = Vec::new();
+        let tagged = list_field(front_matter, &["tags", "tag"])
+            .into_iter()
+            .map(|tag| tag.trim_start_matches('#').to_string());
+        let inline = TAG
+            .captures_iter(&prose)
+            .map(|captures| captures[1].trim_end_matches('/').to_string())
+            // Obsidian doesn't take numbers such as `#1` for tags.
+            .filter(|tag| !tag.chars().all(|c| c.is_ascii_digit() || c == '/'));
+        for tag in tagged.chain(inline) {
+            if !tag.is_empty() && !tags.iter().any(|t: &String| t.eq_ignore_ascii_case(&tag)) {
+                tags.push(tag);
+            }
+        }
+
+        let mut links = Vec::new();
+        for captures in WIKILINK.captures_iter(&prose) {
+            let target = captures[1].trim().to_string();
+            // `[[#Heading]]` links within the note.
+            if !target.is_empty() && !links.contains(&target) {
+                links.push(target);
+            }
+        }
+
+        Self {
+            tags,
+            aliases: list_field(front_matter, &["aliases", "alias"]),
+            links,
+        }
+    }
+}
+
+/// The files excluded under Obsidian's "Excluded files" setting, kept in
+/// `.obsidian/app.json` as path prefixes and `/regex/` patterns.
+#[derive(Debug, Default)]
+pub struct Excluded {
+    prefixes: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Excluded {
+    /// The settings of the vault at `root`; nothing is excluded if they
+    /// can't be read.
+    pub fn load(root: &Path) -> Self {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct AppSettings {
+            #[serde(default)]
+            user_ignore_filters: Vec<String>,
+        }
+
+        let path = root.join(".obsidian").join("app.json");
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        let settings: AppSettings = match serde_json::from_str(&text) {
+            Ok(settings) => settings,
+            Err(e) => {
+                tracing::warn!("Ignoring {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+
+        let mut excluded = Self::default();
+        for filter in settings.user_ignore_filters {
+            let pattern = filter
+                .strip_prefix('/')
+                .and_then(|filter| filter.strip_suffix('/'))
+                .filter(|pattern| !pattern.is_empty());
+            match pattern.map(Regex::new) {
+                Some(Ok(pattern)) => excluded.patterns.push(pattern),
+                Some(Err(e)) => tracing::warn!("Ignoring excluded files {}: {}", filter, e),
+                None => excluded.prefixes.push(filter.to_lowercase()),
+            }
+        }
+        excluded
+    }
+
+    /// Whether the note at `path`, relative to the vault, is excluded.
+    pub fn contains(&self, path: &str) -> bool {
+        let lowercase = path.to_lowercase();
+        self.prefixes
+            .iter()
+            .any(|prefix| lowercase.starts_with(prefix.as_str()))
+            || self.patterns.iter().any(|pattern| pattern.is_match(path))
+    }
+}
+
+/// The YAML front matter of a note, if it has any, and the rest of it.
+fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+/// The values of the first of `keys` in `front_matter`, written as
+/// `key: [a, b]`, `key: a, b` or one `- a` line per value.
+fn list_field(front_matter: &str, keys: &[&str]) -> Vec<String> {
+    let unquote = |value: &str| value.trim().trim_matches(['"', '\'']).to_string();
+    let mut lines = front_matter.lines();
+    while let Some(line) = lines.next() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if !keys.contains(&key.trim_end()) {
+            continue;
+        }
+        let value = value.trim();
+        let values: Vec<String> = if value.is_empty() {
+            lines
+                .map_while(|line| line.trim_start().strip_prefix('-'))
+                .map(unquote)
+                .collect()
+        } else {
+            let value = value
+                .strip_prefix('[')
+                .and_then(|value| value.strip_suffix(']'))
+                .unwrap_or(value);
+            value.split(',').map(unquote).collect()
+        };
+        return values
+            .into_iter()
+            .filter(|value| !value.is_empty())
+            .collect();
+    }
+    Vec::new()
+}
+
+/// `body` with code blanked out, since `#` and `[[` in code are neither tags
+/// nor links.
+fn prose(body: &str) -> String {
+    let mut prose = String::with_capacity(body.len());
+    let mut fenced = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fenced = !fenced;
+        } else if !fenced {
+            prose.push_str(&INLINE_CODE.replace_all(line, " "));
+        }
+        prose.push('\n');
+    }
+    prose
+}
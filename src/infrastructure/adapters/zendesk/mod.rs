@@ -0,0 +1,830 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    domain::{
+        DiscoveredItem, DomainError, ListingPage, Provenance, Query, Resource, ResourceSource,
+    },
+    infrastructure::http::{self, HttpClient, RetryPolicy},
+    ports::{report_progress, ResourceProvider},
+};
+
+/// Largest page size the Zendesk APIs accept.
+const ZENDESK_MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct Ticket {
+    id: u64,
+    subject: Option<String>,
+    description: Option<String>,
+    status: String,
+    priority: Option<String>,
+    #[serde(rename = "type")]
+    ticket_type: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    requester_id: Option<u64>,
+    assignee_id: Option<u64>,
+    organization_id: Option<u64>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    id: u64,
+    name: String,
+    email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Organization {
+    id: u64,
+    name: String,
+}
+
+/// Users and organizations sideloaded with tickets, by ID.
+#[derive(Debug, Default)]
+struct People {
+    users: HashMap<u64, User>,
+    organizations: HashMap<u64, Organization>,
+}
+
+impl People {
+    fn new(users: Vec<User>, organizations: Vec<Organization>) -> Self {
+        Self {
+            users: users.into_iter().map(|user| (user.id, user)).collect(),
+            organizations: organizations
+                .into_iter()
+                .map(|organization| (organization.id, organization))
+                .collect(),
+        }
+    }
+
+    fn user(&self, id: Option<u64>) -> Option<serde_json::Value> {
+        let user = self.users.get(&id?)?;
+        Some(serde_json::json!({ "name": user.name, "email": user.email }))
+    }
+}
+
+/// Where the page after a listed one is: the `next_page` link of offset
+/// pagination, or with `page[size]` asked for, the `links.next` one of
+/// cursor pagination.
+#[derive(Debug, Deserialize)]
+struct Paging {
+    next_page: Option<String>,
+    #[serde(default)]
+    links: Links,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Links {
+    next: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Meta {
+    #[serde(default)]
+    has_more: bool,
+}
+
+impl Paging {
+    fn next(self) -> Option<String> {
+        self.next_page
+            .or(self.links.next.filter(|_| self.meta.has_more))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TicketsPage {
+    #[serde(default)]
+    tickets: Vec<Ticket>,
+    /// What the search API returns instead of `tickets`.
+    #[serde(default)]
+    results: Vec<Ticket>,
+    #[serde(default)]
+    users: Vec<User>,
+    #[serde(default)]
+    organizations: Vec<Organization>,
+    #[serde(flatten)]
+    paging: Paging,
+}
+
+#[derive(Debug, Deserialize)]
+struct Comment {
+    author_id: Option<u64>,
+    #[serde(default)]
+    body: String,
+    /// Internal notes aren't.
+    public: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Article {
+    id: u64,
+    title: String,
+    /// HTML.
+    body: Option<String>,
+    html_url: Option<String>,
+    section_id: Option<u64>,
+    locale: Option<String>,
+    #[serde(default)]
+    label_names: Vec<String>,
+    #[serde(default)]
+    draft: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArticlesPage {
+    #[serde(default)]
+    articles: Vec<Article>,
+    /// What the search API returns instead of `articles`.
+    #[serde(default)]
+    results: Vec<Article>,
+    #[serde(flatten)]
+    paging: Paging,
+}
+
+/// What a resource is in Zendesk, which its ID starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ticket,
+    Article,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Ticket => "ticket",
+            Kind::Article => "article",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ticket" => Some(Kind::Ticket),
+            "article" => Some(Kind::Article),
+            _ => None,
+        }
+    }
+
+    /// The kinds the `kind` filter selects, both by default. `status` only
+    /// applies to tickets and `section_id` to articles, so either leaves
+    /// out the other kind.
+    fn selected(query: &Query) -> Result<Vec<Self>, DomainError> {
+        let mut kinds = match query.filters.get("kind") {
+            None => vec![Kind::Ticket, Kind::Article],
+            Some(kind) => vec![Self::parse(kind).ok_or_else(|| {
+                DomainError::InvalidQuery(format!(
+                    "Unsupported kind filter: {} (expected \"ticket\" or \"article\")",
+                    kind
+                ))
+            })?],
+        };
+        if query.filters.contains_key("status") {
+            kinds.retain(|kind| *kind == Kind::Ticket);
+        }
+        if query.filters.contains_key("section_id") {
+            kinds.retain(|kind| *kind == Kind::Article);
+        }
+        Ok(kinds)
+    }
+}
+
+pub struct ZendeskAdapter {
+    http: HttpClient,
+    /// E.g. `https://acme.zendesk.com`, without a trailing `/`.
+    base_url: String,
+}
+
+impl ZendeskAdapter {
+    /// `url` is the account's address, e.g. `https://acme.zendesk.com`, or
+    /// just its subdomain. `api_key` is an agent's email address and an API
+    /// token, written `<email>:<token>`, or an OAuth access token.
+    pub fn new(url: &str, api_key: String) -> Result<Self, DomainError> {
+        let url = url.trim().trim_end_matches('/');
+        let base_url = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("https://{}.zendesk.com", url)
+        };
+
+        let (authorization, secrets) = match api_key.split_once(':') {
+            Some((email, token)) => (
+                format!(
+                    "Basic {}",
+                    BASE64.encode(format!("{}/token:{}", email, token))
+                ),
+                vec![token.to_string()],
+            ),
+            None => (format!("Bearer {}", api_key), vec![api_key.clone()]),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&authorization)
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?,
+        );
+
+        let http = HttpClient::new("Zendesk", headers, secrets)?
+            .with_retry_policy(Self::default_retry_policy());
+
+        Ok(Self { http, base_url })
+    }
+
+    /// Zendesk budgets requests per minute and says how long to wait when
+    /// throttled, which the client honors.
+    pub fn default_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![429, 500, 502, 503, 504],
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http = self.http.with_retry_policy(retry);
+        self
+    }
+
+    /// Fetches `url`, a path under the account or a `next_page` link,
+    /// returning the response and Zendesk's ID for the request.
+    async fn get_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        url: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(T, Option<String>), DomainError> {
+        let url = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("{}{}", self.base_url, url)
+        };
+        let response = self.http.send(self.http.get(&url).query(params)).await?;
+        let request_id = http::request_id(&response);
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(if status == StatusCode::NOT_FOUND {
+                DomainError::ResourceNotFound(format!("Zendesk has no {}", url))
+            } else {
+                DomainError::ProviderError(format!("Zendesk API error: {}", error_text))
+            });
+        }
+
+        let data = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        Ok((data, request_id))
+    }
+
+    /// Up to `limit` tickets, most recently updated first, with `status` if
+    /// given.
+    async fn tickets(
+        &self,
+        status: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let page_size = limit.clamp(1, ZENDESK_MAX_PAGE_SIZE);
+        let mut resources = Vec::new();
+        let mut next = None;
+        loop {
+            let (page, next_page) = self.tickets_page(status, page_size, next.take()).await?;
+            resources.extend(page);
+            report_progress(|| format!("Fetched {} Zendesk tickets", resources.len()));
+
+            match next_page {
+                Some(next_page) if resources.len() < limit => next = Some(next_page),
+                _ => break,
+            }
+        }
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    /// A page of `page_size` tickets, most recently updated first, with
+    /// `status` if given: the one `next` links to, or the first. Returns
+    /// the link to the page after it too, if there is one.
+    async fn tickets_page(
+        &self,
+        status: Option<&str>,
+        page_size: usize,
+        next: Option<String>,
+    ) -> Result<(Vec<Resource>, Option<String>), DomainError> {
+        let page_size = page_size.to_string();
+        let search = status.map(|status| format!("type:ticket status:{}", status));
+        let (url, params) = match (next, &search) {
+            // The link carries the parameters.
+            (Some(next), _) => (next, Vec::new()),
+            // Only the search API filters tickets by status, and it only
+            // pages by offset.
+            (None, Some(search)) => (
+                "/api/v2/search.json".to_string(),
+                vec![
+                    ("sort_by", "updated_at"),
+                    ("sort_order", "desc"),
+                    ("per_page", page_size.as_str()),
+                    ("query", search.as_str()),
+                    ("include", "tickets(users,organizations)"),
+                ],
+            ),
+            // Offset pagination stops at 10,000 tickets; cursors don't.
+            (None, None) => (
+                "/api/v2/tickets.json".to_string(),
+                vec![
+                    ("sort", "-updated_at"),
+                    ("page[size]", page_size.as_str()),
+                    ("include", "users,organizations"),
+                ],
+            ),
+        };
+
+        let (page, request_id): (TicketsPage, _) = self.get_json(&url, &params).await?;
+        let people = People::new(page.users, page.organizations);
+        let resources = page
+            .tickets
+            .into_iter()
+            .chain(page.results)
+            .map(|ticket| self.ticket_to_resource(ticket, &people, request_id.clone()))
+            .collect();
+        Ok((resources, page.paging.next()))
+    }
+
+    /// Up to `limit` help center articles, most recently updated first,
+    /// from `section_id` if given.
+    async fn articles(
+        &self,
+        section_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Resource>, DomainError> {
+        let page_size = limit.clamp(1, ZENDESK_MAX_PAGE_SIZE);
+        let mut resources = Vec::new();
+        let mut next = None;
+        loop {
+            let (page, next_page) = self
+                .articles_page(section_id, page_size, next.take())
+                .await?;
+            resources.extend(page);
+            report_progress(|| format!("Fetched {} Zendesk articles", resources.len()));
+
+            match next_page {
+                Some(next_page) if resources.len() < limit => next = Some(next_page),
+                _ => break,
+            }
+        }
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    /// A page of `page_size` help center articles, most recently updated
+    /// first, from `section_id` if given: the one `next` links to, or the
+    /// first. Returns the link to the page after it too, if there is one.
+    async fn articles_page(
+        &self,
+        section_id: Option<&str>,
+        page_size: usize,
+        next: Option<String>,
+    ) -> Result<(Vec<Resource>, Option<String>), DomainError> {
+        let page_size = page_size.to_string();
+        let (url, params) = match next {
+            Some(next) => (next, Vec::new()),
+            None => (
+                match section_id {
+                    Some(section_id) => {
+                        format!("/api/v2/help_center/sections/{}/articles.json", section_id)
+                    }
+                    None => "/api/v2/help_center/articles.json".to_string(),
+                },
+                vec![
+                    ("sort_by", "updated_at"),
+                    ("sort_order", "desc"),
+                    ("page[size]", page_size.as_str()),
+                ],
+            ),
+        };
+
+        let (page, request_id): (ArticlesPage, _) = self.get_json(&url, &params).await?;
+        let resources = page
+            .articles
+            .into_iter()
+            .chain(page.results)
+            .map(|article| self.article_to_resource(article, request_id.clone()))
+            .collect();
+        Ok((resources, page.paging.next()))
+    }
+
+    /// A ticket with its whole conversation, internal notes included.
+    async fn ticket(&self, ticket_id: &str) -> Result<Resource, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct TicketData {
+            ticket: Ticket,
+            #[serde(default)]
+            users: Vec<User>,
+            #[serde(default)]
+            organizations: Vec<Organization>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct CommentsPage {
+            comments: Vec<Comment>,
+            #[serde(default)]
+            users: Vec<User>,
+            next_page: Option<String>,
+        }
+
+        let (data, request_id): (TicketData, _) = self
+            .get_json(
+                &format!("/api/v2/tickets/{}.json", ticket_id),
+                &[("include", "users,organizations")],
+            )
+            .await?;
+        let mut people = People::new(data.users, data.organizations);
+
+        let mut comments = Vec::new();
+        let mut url = format!("/api/v2/tickets/{}/comments.json", ticket_id);
+        let mut params = vec![("include", "users")];
+        loop {
+            let (page, _): (CommentsPage, _) = self.get_json(&url, &params).await?;
+            comments.extend(page.comments);
+            people
+                .users
+                .extend(page.users.into_iter().map(|user| (user.id, user)));
+            match page.next_page {
+                Some(next_page) => {
+                    url = next_page;
+                    params.clear();
+                }
+                None => break,
+            }
+        }
+
+        let mut resource = self.ticket_to_resource(data.ticket, &people, request_id);
+        if !comments.is_empty() {
+            resource.content = conversation(&comments, &people);
+        }
+        Ok(resource)
+    }
+
+    fn ticket_to_resource(
+        &self,
+        ticket: Ticket,
+        people: &People,
+        request_id: Option<String>,
+    ) -> Resource {
+        let mut metadata = HashMap::new();
+
+        metadata.insert("kind".to_string(), serde_json::json!("ticket"));
+        metadata.insert("status".to_string(), serde_json::json!(ticket.status));
+        if let Some(priority) = &ticket.priority {
+            metadata.insert("priority".to_string(), serde_json::json!(priority));
+        }
+        if let Some(ticket_type) = &ticket.ticket_type {
+            metadata.insert("type".to_string(), serde_json::json!(ticket_type));
+        }
+        metadata.insert("tags".to_string(), serde_json::json!(ticket.tags));
+        if let Some(requester) = people.user(ticket.requester_id) {
+            metadata.insert("requester".to_string(), requester);
+        }
+        if let Some(assignee) = people.user(ticket.assignee_id) {
+            metadata.insert("assignee".to_string(), assignee);
+        }
+        if let Some(organization) = ticket
+            .organization_id
+            .and_then(|id| people.organizations.get(&id))
+        {
+            metadata.insert(
+                "organization".to_string(),
+                serde_json::json!({ "id": organization.id, "name": organization.name }),
+            );
+        }
+        // Closed tickets can't be reopened, only followed up.
+        if ticket.status == "closed" || ticket.status == "deleted" {
+            metadata.insert("archived".to_string(), serde_json::json!(true));
+        }
+
+        Resource {
+            id: format!("zendesk_ticket_{}", ticket.id),
+            source: ResourceSource::Custom("zendesk".to_string()),
+            title: ticket
+                .subject
+                .filter(|subject| !subject.is_empty())
+                .unwrap_or_else(|| format!("Ticket #{}", ticket.id)),
+            content: ticket.description.unwrap_or_default(),
+            metadata,
+            created_at: ticket.created_at,
+            updated_at: ticket.updated_at,
+            provenance: Some(Provenance {
+                provider: "zendesk".to_string(),
+                fetched_at: Utc::now(),
+                api_version: Some("v2".to_string()),
+                request_id,
+                url: Some(format!("{}/agent/tickets/{}", self.base_url, ticket.id)),
+            }),
+        }
+    }
+
+    fn article_to_resource(&self, article: Article, request_id: Option<String>) -> Resource {
+        let mut metadata = HashMap::new();
+
+        metadata.insert("kind".to_string(), serde_json::json!("article"));
+        if let Some(section_id) = article.section_id {
+            metadata.insert("section_id".to_string(), serde_json::json!(section_id));
+        }
+        if let Some(locale) = &article.locale {
+            metadata.insert("locale".to_string(), serde_json::json!(locale));
+        }
+        metadata.insert("labels".to_string(), serde_json::json!(article.label_names));
+        if article.draft {
+            metadata.insert("draft".to_string(), serde_json::json!(true));
+        }
+
+        Resource {
+            id: format!("zendesk_article_{}", article.id),
+            source: ResourceSource::Custom("zendesk".to_string()),
+            title: article.title,
+            content: article
+                .body
+                .map(|body| html2md::parse_html(&body))
+                .unwrap_or_default(),
+            metadata,
+            created_at: article.created_at,
+            updated_at: article.updated_at,
+            provenance: Some(Provenance {
+                provider: "zendesk".to_string(),
+                fetched_at: Utc::now(),
+                api_version: Some("v2".to_string()),
+                request_id,
+                url: article.html_url,
+            }),
+        }
+    }
+}
+
+/// A ticket's comments as Markdown, each under its author and time.
+fn conversation(comments: &[Comment], people: &People) -> String {
+    comments
+        .iter()
+        .map(|comment| {
+            let author = comment
+                .author_id
+                .and_then(|id| people.users.get(&id))
+                .map_or("Unknown", |user| user.name.as_str());
+            let note = if comment.public {
+                ""
+            } else {
+                ", internal note"
+            };
+            format!(
+                "**{}** ({}{})\n\n{}",
+                author,
+                comment.created_at.format("%Y-%m-%d %H:%M UTC"),
+                note,
+                comment.body.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n")
+}
+
+#[async_trait]
+impl ResourceProvider for ZendeskAdapter {
+    /// Lists tickets and help center articles, most recently updated first.
+    /// `kind` (`ticket` or `article`) lists only one, `status` (e.g. `open`)
+    /// only tickets in that status, and `section_id` only articles in that
+    /// help center section. Listed tickets hold their description only.
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        let limit = query.limit.unwrap_or(50);
+        let mut resources = Vec::new();
+        for kind in Kind::selected(query)? {
+            resources.extend(match kind {
+                Kind::Ticket => {
+                    let status = query.filters.get("status").map(String::as_str);
+                    self.tickets(status, limit).await?
+                }
+                Kind::Article => {
+                    let section_id = query.filters.get("section_id").map(String::as_str);
+                    self.articles(section_id, limit).await?
+                }
+            });
+        }
+
+        resources.sort_by_key(|resource| std::cmp::Reverse(resource.updated_at));
+        resources.truncate(limit);
+        Ok(resources)
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        let id = id.strip_prefix("zendesk_").unwrap_or(id);
+        if let Some(ticket_id) = id.strip_prefix("ticket_") {
+            return self.ticket(ticket_id).await.map_err(|e| match e {
+                DomainError::ResourceNotFound(_) => DomainError::ResourceNotFound(format!(
+                    "Zendesk ticket not found: {}",
+                    ticket_id
+                )),
+                e => e,
+            });
+        }
+        let Some(article_id) = id.strip_prefix("article_") else {
+            return Err(DomainError::ResourceNotFound(format!(
+                "Not a Zendesk ticket or article: {}",
+                id
+            )));
+        };
+
+        #[derive(Debug, Deserialize)]
+        struct ArticleData {
+            article: Article,
+        }
+
+        let (data, request_id): (ArticleData, _) = self
+            .get_json(
+                &format!("/api/v2/help_center/articles/{}.json", article_id),
+                &[],
+            )
+            .await
+            .map_err(|e| match e {
+                DomainError::ResourceNotFound(_) => DomainError::ResourceNotFound(format!(
+                    "Zendesk article not found: {}",
+                    article_id
+                )),
+                e => e,
+            })?;
+        Ok(self.article_to_resource(data.article, request_id))
+    }
+
+    /// Searches tickets, then help center articles.
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        let (tickets, request_id): (TicketsPage, _) = self
+            .get_json(
+                "/api/v2/search.json",
+                &[
+                    ("query", &format!("type:ticket {}", query)),
+                    ("include", "tickets(users,organizations)"),
+                ],
+            )
+            .await?;
+        let people = People::new(tickets.users, tickets.organizations);
+        let mut resources: Vec<Resource> = tickets
+            .results
+            .into_iter()
+            .map(|ticket| self.ticket_to_resource(ticket, &people, request_id.clone()))
+            .collect();
+
+        let (articles, request_id): (ArticlesPage, _) = self
+            .get_json(
+                "/api/v2/help_center/articles/search.json",
+                &[("query", query)],
+            )
+            .await?;
+        resources.extend(
+            articles
+                .results
+                .into_iter()
+                .map(|article| self.article_to_resource(article, request_id.clone())),
+        );
+        Ok(resources)
+    }
+
+    fn provider_name(&self) -> &'static str {
+        "Zendesk"
+    }
+
+    /// Lists every ticket, then every article, a page at a time. Cursors
+    /// are the kind being listed and the link to its next page, empty to
+    /// start on it.
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        let page_size = query
+            .page_size()?
+            .unwrap_or(ZENDESK_MAX_PAGE_SIZE)
+            .min(ZENDESK_MAX_PAGE_SIZE);
+        let kinds = Kind::selected(query)?;
+        let (kind, next) = match &cursor {
+            Some(cursor) => {
+                let invalid =
+                    || DomainError::InvalidQuery(format!("Invalid Zendesk cursor: {}", cursor));
+                let (kind, next) = cursor.split_once(':').ok_or_else(invalid)?;
+                let kind = Kind::parse(kind).ok_or_else(invalid)?;
+                // Cursors come back from clients, and the credentials go
+                // wherever the link points.
+                if !next.is_empty() && !next.starts_with(&format!("{}/", self.base_url)) {
+                    return Err(invalid());
+                }
+                (kind, Some(next.to_string()).filter(|next| !next.is_empty()))
+            }
+            None => match kinds.first() {
+                Some(kind) => (*kind, None),
+                None => return Ok(ListingPage::default()),
+            },
+        };
+
+        let (resources, next) = match kind {
+            Kind::Ticket => {
+                let status = query.filters.get("status").map(String::as_str);
+                self.tickets_page(status, page_size, next).await?
+            }
+            Kind::Article => {
+                let section_id = query.filters.get("section_id").map(String::as_str);
+                self.articles_page(section_id, page_size, next).await?
+            }
+        };
+        let next_cursor = match next {
+            Some(next) => Some(format!("{}:{}", kind.name(), next)),
+            None => kinds
+                .iter()
+                .skip_while(|other| **other != kind)
+                .nth(1)
+                .map(|other| format!("{}:", other.name())),
+        };
+        Ok(ListingPage {
+            resources,
+            next_cursor,
+        })
+    }
+
+    /// Lists the help center's categories and their sections.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        #[derive(Debug, Deserialize)]
+        struct CategoriesPage {
+            categories: Vec<Group>,
+            next_page: Option<String>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct SectionsPage {
+            sections: Vec<Group>,
+            next_page: Option<String>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Group {
+            id: u64,
+            name: String,
+            category_id: Option<u64>,
+            html_url: Option<String>,
+        }
+
+        let per_page = ZENDESK_MAX_PAGE_SIZE.to_string();
+        let mut items = Vec::new();
+        let mut url = "/api/v2/help_center/categories.json".to_string();
+        let mut params = vec![("per_page", per_page.as_str())];
+        loop {
+            let (page, _): (CategoriesPage, _) = self.get_json(&url, &params).await?;
+            items.extend(page.categories.into_iter().map(|category| DiscoveredItem {
+                id: category.id.to_string(),
+                kind: "category".to_string(),
+                title: category.name,
+                parent_id: None,
+                url: category.html_url,
+            }));
+            match page.next_page {
+                Some(next_page) => {
+                    url = next_page;
+                    params.clear();
+                }
+                None => break,
+            }
+        }
+
+        let mut url = "/api/v2/help_center/sections.json".to_string();
+        let mut params = vec![("per_page", per_page.as_str())];
+        loop {
+            let (page, _): (SectionsPage, _) = self.get_json(&url, &params).await?;
+            items.extend(page.sections.into_iter().map(|section| DiscoveredItem {
+                id: section.id.to_string(),
+                kind: "section".to_string(),
+                title: section.name,
+                parent_id: section.category_id.map(|id| id.to_string()),
+                url: section.html_url,
+            }));
+            match page.next_page {
+                Some(next_page) => {
+                    url = next_page;
+                    params.clear();
+                }
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+}
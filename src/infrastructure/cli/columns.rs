@@ -0,0 +1,170 @@
+use serde_json::Value;
+use std::str::FromStr;
+
+use super::{dates::Dates, output::cell};
+use crate::domain::Resource;
+
+/// How a column's cells line up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// A column of `--columns`: a resource field, or a dotted path into its
+/// metadata, optionally followed by `:` and an alignment (`<`, `>` or `^`)
+/// and a width, e.g. `metadata.assignee.name:20` or `points:>6`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSpec {
+    /// What the column shows, e.g. `metadata.state`.
+    pub field: String,
+    /// Widest the column gets before its cells are cut, if set.
+    pub width: Option<usize>,
+    /// Numbers line up on the right and everything else on the left if
+    /// unset.
+    pub align: Option<Align>,
+}
+
+/// The columns `--columns` asks tables for, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns(pub Vec<ColumnSpec>);
+
+impl FromStr for Columns {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let columns = text
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(ColumnSpec::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        if columns.is_empty() {
+            return Err("no columns given".to_string());
+        }
+        Ok(Self(columns))
+    }
+}
+
+impl FromStr for ColumnSpec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (field, format) = match text.rsplit_once(':') {
+            Some((field, format)) => (field.trim(), format.trim()),
+            None => (text.trim(), ""),
+        };
+        if field.is_empty() {
+            return Err(format!("column {:?} names no field", text));
+        }
+        let (align, width) = match format.chars().next() {
+            Some('<') => (Some(Align::Left), &format[1..]),
+            Some('>') => (Some(Align::Right), &format[1..]),
+            Some('^') => (Some(Align::Center), &format[1..]),
+            _ => (None, format),
+        };
+        let width = match width {
+            "" => None,
+            width => match width.parse() {
+                Ok(width) if width > 0 => Some(width),
+                _ => {
+                    return Err(format!(
+                        "invalid format {:?} of column {}; expected an alignment \
+                         (<, > or ^) and a width, e.g. {}:>10",
+                        format, field, field
+                    ))
+                }
+            },
+        };
+        Ok(Self {
+            field: field.to_string(),
+            width,
+            align,
+        })
+    }
+}
+
+impl ColumnSpec {
+    /// The column's heading: its field, without `metadata.`.
+    pub fn heading(&self) -> String {
+        self.field
+            .strip_prefix("metadata.")
+            .unwrap_or(&self.field)
+            .to_string()
+    }
+
+    /// `text` on one line, cut to the column's width if it has one, with
+    /// `...` marking the cut where there's room for it.
+    pub fn fit(&self, text: &str) -> String {
+        let text = text.replace('\n', " ");
+        match self.width {
+            Some(width) if text.chars().count() > width => {
+                let kept = if width > 3 { width - 3 } else { width };
+                let mut cut: String = text.chars().take(kept).collect();
+                if width > 3 {
+                    cut.push_str("...");
+                }
+                cut
+            }
+            _ => text,
+        }
+    }
+
+    /// The column's cell for `resource`. Fields other than the resource's
+    /// own are looked up in its metadata, a dotted path descending into
+    /// objects and across lists; cells of missing ones are empty.
+    pub fn cell(&self, resource: &Resource, dates: Dates) -> String {
+        match self.field.as_str() {
+            "id" => resource.id.clone(),
+            "short_id" => resource.short_id().unwrap_or_default().to_string(),
+            "title" => resource.title.clone(),
+            "content" => resource.content.clone(),
+            "source" | "provider" => resource.source.provider_name().to_string(),
+            "uri" => resource.uri(),
+            "created_at" => dates.date(resource.created_at),
+            "updated_at" => dates.date(resource.updated_at),
+            "url" => resource
+                .provenance
+                .as_ref()
+                .and_then(|provenance| provenance.url.clone())
+                .unwrap_or_default(),
+            field => {
+                let path = field.strip_prefix("metadata.").unwrap_or(field);
+                let mut keys = path.split('.');
+                let first = keys.next().unwrap_or_default();
+                resource
+                    .metadata
+                    .get(first)
+                    .map(|value| cell(&lookup(value, keys.collect::<Vec<_>>().as_slice())))
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// What `keys` lead to in `value`: into objects by key and lists by
+/// index, or into each item of a list by key.
+fn lookup(value: &Value, keys: &[&str]) -> Value {
+    let Some((key, rest)) = keys.split_first() else {
+        return value.clone();
+    };
+    match value {
+        Value::Object(object) => object
+            .get(*key)
+            .map_or(Value::Null, |value| lookup(value, rest)),
+        Value::Array(items) => match key.parse::<usize>() {
+            Ok(index) => items
+                .get(index)
+                .map_or(Value::Null, |value| lookup(value, rest)),
+            Err(_) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| lookup(item, keys))
+                    .filter(|value| !value.is_null())
+                    .collect(),
+            ),
+        },
+        _ => Value::Null,
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+
+/// How a locale writes dates and times, as `strftime` patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LocalePattern {
+    date: &'static str,
+    time: &'static str,
+}
+
+/// Year first, for the C locale and wherever the locale isn't known.
+const ISO_PATTERN: LocalePattern = LocalePattern {
+    date: "%Y-%m-%d",
+    time: "%H:%M",
+};
+
+/// How human output renders times, chosen with `--utc` and `--iso`: local
+/// time written the way the locale writes it, followed by how long ago it
+/// was. JSON output always has RFC 3339.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dates {
+    /// UTC instead of the local time zone.
+    utc: bool,
+    /// RFC 3339 instead of the locale's format, and no relative time.
+    iso: bool,
+    pattern: LocalePattern,
+}
+
+impl Dates {
+    /// The style for the locale in `LC_ALL`, `LC_TIME` or `LANG`.
+    pub fn from_env(utc: bool, iso: bool) -> Self {
+        let locale = ["LC_ALL", "LC_TIME", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        Self {
+            utc,
+            iso,
+            pattern: locale_pattern(&locale),
+        }
+    }
+
+    /// A full timestamp, e.g. `16.10.2026 15:34 (3 days ago)`.
+    pub fn timestamp(&self, time: DateTime<Utc>) -> String {
+        if self.iso {
+            return self.iso_timestamp(time);
+        }
+        let pattern = format!("{} {}", self.pattern.date, self.pattern.time);
+        format!("{} ({})", self.absolute(time, &pattern), relative(time))
+    }
+
+    /// Only the date, e.g. `16.10.2026`.
+    pub fn date(&self, time: DateTime<Utc>) -> String {
+        if self.iso {
+            return self.absolute(time, "%Y-%m-%d");
+        }
+        self.absolute(time, self.pattern.date)
+    }
+
+    /// How long ago `time` was, compactly enough for a table column, e.g.
+    /// `5m ago`. With `--iso`, the time itself instead.
+    pub fn age(&self, time: DateTime<Utc>) -> String {
+        if self.iso {
+            return self.iso_timestamp(time);
+        }
+        let secs = (Utc::now() - time).num_seconds().max(0);
+        match secs {
+            0..=59 => format!("{}s ago", secs),
+            60..=3599 => format!("{}m ago", secs / 60),
+            3600..=86399 => format!("{}h ago", secs / 3600),
+            _ => format!("{}d ago", secs / 86400),
+        }
+    }
+
+    fn iso_timestamp(&self, time: DateTime<Utc>) -> String {
+        if self.utc {
+            time.to_rfc3339_opts(SecondsFormat::Secs, true)
+        } else {
+            time.with_timezone(&Local)
+                .to_rfc3339_opts(SecondsFormat::Secs, false)
+        }
+    }
+
+    fn absolute(&self, time: DateTime<Utc>, pattern: &str) -> String {
+        if self.utc {
+            format!("{} UTC", time.format(pattern))
+        } else {
+            time.with_timezone(&Local).format(pattern).to_string()
+        }
+    }
+}
+
+/// The pattern for a POSIX locale name such as `de_DE.UTF-8`.
+fn locale_pattern(locale: &str) -> LocalePattern {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let (language, region) = name.split_once(['_', '-']).unwrap_or((name, ""));
+    let (date, time) = match (language, region) {
+        ("en", "US" | "PH") => ("%b %-d, %Y", "%-I:%M %p"),
+        ("en" | "fr", "CA") => return ISO_PATTERN,
+        ("en", "") => return ISO_PATTERN,
+        ("en", _) => ("%-d %b %Y", "%H:%M"),
+        ("de" | "da" | "fi" | "nb" | "nn" | "no" | "pl" | "ru" | "cs" | "sk" | "tr" | "uk", _) => {
+            ("%d.%m.%Y", "%H:%M")
+        }
+        ("fr" | "es" | "it" | "pt" | "el" | "ca", _) => ("%d/%m/%Y", "%H:%M"),
+        ("nl", _) => ("%d-%m-%Y", "%H:%M"),
+        ("ja" | "zh" | "ko", _) => ("%Y/%m/%d", "%H:%M"),
+        ("hu", _) => ("%Y.%m.%d.", "%H:%M"),
+        _ => return ISO_PATTERN,
+    };
+    LocalePattern { date, time }
+}
+
+/// How long ago `time` was in words, e.g. `3 days ago`, or how far off
+/// for times in the future.
+fn relative(time: DateTime<Utc>) -> String {
+    let secs = (Utc::now() - time).num_seconds();
+    let (amount, unit) = match secs.unsigned_abs() {
+        0..=44 => return "just now".to_string(),
+        secs @ 45..=3599 => ((secs + 30) / 60, "minute"),
+        secs @ 3600..=86399 => ((secs + 1800) / 3600, "hour"),
+        secs @ 86400..=2_591_999 => ((secs + 43200) / 86400, "day"),
+        secs @ 2_592_000..=31_535_999 => (secs / 2_592_000, "month"),
+        secs => (secs / 31_536_000, "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    if secs < 0 {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
@@ -1,5 +1,46 @@
-use clap::{Parser, Subcommand};
+pub mod columns;
+pub mod dates;
+pub mod output;
+#[cfg(feature = "tui")]
+pub mod picker;
+/// Stands in for the fuzzy finder in builds without the `tui` feature.
+#[cfg(not(feature = "tui"))]
+pub mod picker {
+    use super::dates::Dates;
+    use crate::{domain::Resource, infrastructure::features};
+
+    pub fn pick(_: &[Resource], _: Dates) -> std::io::Result<Option<usize>> {
+        Err(std::io::Error::other(features::missing("tui")))
+    }
+}
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use clap::{parser::ValueSource, ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
 use std::collections::HashMap;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use self::columns::Columns;
+use self::dates::Dates;
+use self::output::OutputFormat;
+use crate::application::condition::Condition;
+use crate::domain::Classification;
+use crate::infrastructure::mcp::server::DEFAULT_MAX_READ_CHARS;
+
+/// MCP server transports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Transport {
+    /// Newline-delimited JSON-RPC over stdin/stdout
+    Stdio,
+    /// HTTP with Server-Sent Events
+    Sse,
+    /// Streamable HTTP on a single endpoint (also serves the sse endpoints)
+    Http,
+    /// WebSocket on /ws, one session per connection
+    Ws,
+}
 
 #[derive(Parser)]
 #[command(name = "mcp-rs")]
@@ -11,13 +52,85 @@ pub struct Cli {
 
     #[arg(short, long, global = true)]
     pub verbose: bool,
+
+    /// Log HTTP requests and responses to providers (credentials are redacted)
+    #[arg(long, global = true)]
+    pub trace_http: bool,
+
+    /// Include request and response bodies in HTTP traces (implies --trace-http)
+    #[arg(long, global = true)]
+    pub trace_http_bodies: bool,
+
+    /// API key for this invocation only (repeatable), e.g. --api-key linear=lin_api_...
+    #[arg(
+        long,
+        visible_alias = "token",
+        global = true,
+        value_name = "PROVIDER=KEY"
+    )]
+    pub api_key: Vec<String>,
+
+    /// Always fetch from providers instead of serving cached resources
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Refuse every change to upstream data (also `read_only` in the config file)
+    #[arg(long, global = true)]
+    pub read_only: bool,
+
+    /// Harden for config files from less trusted users: no hooks, no
+    /// upstream servers run as commands, no vaults, no REST, GraphQL or
+    /// Elasticsearch providers, and requests only to the built-in
+    /// providers' hosts and those given with --allow-host
+    #[arg(long, global = true, env = "MCP_RS_SANDBOX")]
+    pub sandbox: bool,
+
+    /// Host the sandbox lets providers reach (repeatable), e.g.
+    /// tfs.example.com or *.example.com
+    #[arg(
+        long,
+        global = true,
+        value_name = "HOST",
+        requires = "sandbox",
+        env = "MCP_RS_ALLOWED_HOSTS",
+        value_delimiter = ','
+    )]
+    pub allow_host: Vec<String>,
+
+    /// Path to the config file
+    #[arg(long, global = true, env = "MCP_RS_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Output format for fetch, get, search and grep
+    #[arg(short, long, global = true, value_enum, default_value = "text")]
+    pub output: OutputFormat,
+
+    /// Columns of table output, e.g. "title,metadata.state:20,points:>";
+    /// implies `-o table` unless JSON is asked for
+    #[arg(long, global = true)]
+    pub columns: Option<Columns>,
+
+    /// Show times in UTC instead of local time
+    #[arg(long, global = true)]
+    pub utc: bool,
+
+    /// Show times as RFC 3339 timestamps instead of the locale's date format
+    #[arg(long, global = true)]
+    pub iso: bool,
+}
+
+impl Cli {
+    /// How human output renders times, per `--utc`, `--iso` and the locale.
+    pub fn dates(&self) -> Dates {
+        Dates::from_env(self.utc, self.iso)
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Fetch resources from configured providers
     Fetch {
-        /// Source provider (notion, linear, all)
+        /// Source provider or group (notion, linear, all, or a name from [sources])
         #[arg(short, long, default_value = "all")]
         source: String,
 
@@ -28,41 +141,537 @@ pub enum Commands {
         /// Additional filters (key=value pairs)
         #[arg(short, long)]
         filter: Vec<String>,
+
+        /// Return Notion database rows as records with typed property
+        /// columns instead of page content (pairs well with `-o table`)
+        #[arg(long)]
+        records: bool,
+
+        /// Linear issues assigned to you
+        #[arg(long, conflicts_with = "assigned_to")]
+        mine: bool,
+
+        /// Linear issues assigned to a user (email, name, or display name)
+        #[arg(long, value_name = "USER")]
+        assigned_to: Option<String>,
+
+        /// Linear issues in an active cycle
+        #[arg(long)]
+        current_cycle: bool,
+
+        /// Only keep results matching this condition, checked here rather
+        /// than by the provider, e.g. 'metadata.state != "Done" &&
+        /// updated_at > now() - 7d'
+        #[arg(long = "where", value_name = "EXPR")]
+        condition: Option<Condition>,
     },
 
     /// Get a specific resource by ID
     Get {
-        /// Resource ID
+        /// Resource ID, URI, Linear issue identifier (e.g. ENG-123), or alias
+        id: String,
+
+        /// Print the resource as the cache held it at this time instead of
+        /// fetching it: a date (2026-09-01, midnight UTC), an RFC 3339
+        /// timestamp, or how long ago (e.g. 2w)
+        #[arg(long, value_name = "TIME", value_parser = parse_time)]
+        as_of: Option<DateTime<Utc>>,
+    },
+
+    /// Edit a resource's content as Markdown in $VISUAL or $EDITOR and save
+    /// it back upstream (Notion pages and Linear issue descriptions)
+    Edit {
+        /// Resource ID, URI, Linear issue identifier (e.g. ENG-123), or alias
         id: String,
     },
 
+    /// Print the lines of one resource matching a pattern, with context
+    Grep {
+        /// Resource ID, URI, Linear issue identifier, or alias
+        id: String,
+
+        /// Regular expression to look for
+        pattern: String,
+
+        /// Lines of context around each match
+        #[arg(short = 'C', long, default_value_t = 2)]
+        context: usize,
+
+        /// Match case-insensitively
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Treat the pattern as a literal string
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+
+        /// Stop after this many matching lines
+        #[arg(short, long)]
+        max_count: Option<usize>,
+    },
+
     /// Search for resources
     Search {
         /// Search query
-        query: String,
+        #[arg(required_unless_present = "batch")]
+        query: Option<String>,
 
-        /// Source providers to search (notion, linear, all)
-        #[arg(short, long, default_value = "all")]
+        /// Run every query in FILE, one per line (- for stdin), and print
+        /// the results grouped by query. Blank lines and lines starting
+        /// with # are skipped
+        #[arg(long, value_name = "FILE", conflicts_with = "query")]
+        batch: Option<PathBuf>,
+
+        /// Queries of a batch searched at once
+        #[arg(long, default_value_t = 4, requires = "batch")]
+        concurrency: usize,
+
+        /// Source providers or groups to search (notion, linear, all, or a
+        /// name from [sources]). Without one, the query is routed: an issue
+        /// identifier, ID or link is fetched, and a query about docs,
+        /// tickets or mail searches their providers first
+        #[arg(short, long)]
         source: Vec<String>,
 
-        /// Limit number of results
+        /// Limit number of results (per query in a batch)
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Include resources archived or deleted upstream
+        #[arg(long)]
+        include_archived: bool,
+
+        /// Accept results up to this old (e.g. 15m, 1h) from the local index
+        /// or an earlier identical search before searching live; 0s always
+        /// searches live
+        #[arg(long, value_parser = humantime::parse_duration)]
+        max_staleness: Option<Duration>,
+
+        /// Print the passages of the results that match best, with the
+        /// headings they're under and where they are, instead of whole
+        /// resources; --limit counts passages
+        #[arg(long, conflicts_with = "batch")]
+        chunks: bool,
+
+        /// Only keep results matching this condition, checked here rather
+        /// than by the provider, e.g. 'metadata.state != "Done" &&
+        /// updated_at > now() - 7d'
+        #[arg(long = "where", value_name = "EXPR")]
+        condition: Option<Condition>,
+
+        /// Search the resources as the cache held them at this time instead
+        /// of searching providers: a date (2026-09-01, midnight UTC), an
+        /// RFC 3339 timestamp, or how long ago (e.g. 2w)
+        #[arg(
+            long,
+            value_name = "TIME",
+            value_parser = parse_time,
+            conflicts_with_all = ["batch", "max_staleness"]
+        )]
+        as_of: Option<DateTime<Utc>>,
+    },
+
+    /// Choose a resource with a fuzzy finder and print its ID, e.g.
+    /// `mcp-rs get $(mcp-rs pick roadmap)`
+    Pick {
+        /// Pick among the results of this search instead of everything the
+        /// source lists
+        query: Option<String>,
+
+        /// Source provider or group (notion, linear, all, or a name from [sources])
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Print the picked resource's full content instead of its ID
+        #[arg(long)]
+        content: bool,
+    },
+
+    /// Fetch everything from providers into the local cache, tombstoning
+    /// resources that were archived or deleted upstream
+    Sync {
+        /// Source provider or group (notion, linear, all, or a name from [sources])
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Additional filters (key=value pairs; Notion needs database_id)
+        #[arg(short, long)]
+        filter: Vec<String>,
+
+        /// Stop after this many resources per provider (skips deletion
+        /// detection, which needs a complete listing)
         #[arg(short, long)]
         limit: Option<usize>,
+
+        /// Content fetches to run in parallel per provider (overrides
+        /// [providers.<name>.sync] workers)
+        #[arg(short, long)]
+        workers: Option<usize>,
+
+        /// Stop after this many API requests in all, retries and the
+        /// estimate beforehand included (skips deletion detection)
+        #[arg(long)]
+        max_calls: Option<usize>,
     },
 
     /// List configured providers
     Providers,
 
+    /// Run as an MCP server
+    Serve {
+        /// How clients connect: stdio (spawned as a subprocess), http
+        /// (streamable HTTP), sse (HTTP with Server-Sent Events) or ws
+        /// (WebSocket)
+        #[arg(short, long, value_enum, default_value_t = Transport::Stdio)]
+        transport: Transport,
+
+        /// Address to listen on for network transports
+        #[arg(long, default_value = "127.0.0.1")]
+        host: IpAddr,
+
+        /// Port to listen on for network transports
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// Serve on a Unix domain socket at this path instead, speaking
+        /// newline-delimited JSON-RPC as over stdio
+        #[cfg(unix)]
+        #[arg(long, value_name = "PATH", conflicts_with = "transport")]
+        socket: Option<PathBuf>,
+
+        /// Browser origin allowed to call network transports besides
+        /// localhost (repeatable), e.g. https://app.example.com; * allows any
+        #[arg(long, value_name = "ORIGIN")]
+        allow_origin: Vec<String>,
+
+        /// End HTTP sessions after this long without activity, e.g. 30m
+        #[arg(long, default_value = "30m", value_parser = humantime::parse_duration)]
+        session_idle_timeout: Duration,
+
+        /// Characters of content a resources/read returns at once; longer
+        /// content comes in parts, each ending with the URI of the next. 0
+        /// returns everything at once
+        #[arg(long, value_name = "CHARS", default_value_t = DEFAULT_MAX_READ_CHARS)]
+        max_read_chars: usize,
+
+        /// Ping clients on event streams and WebSockets this often, e.g. 30s,
+        /// ending sessions that stop answering
+        #[arg(long, value_parser = humantime::parse_duration)]
+        ping_interval: Option<Duration>,
+
+        /// How long a client gets to answer a ping
+        #[arg(long, default_value = "10s", value_parser = humantime::parse_duration, requires = "ping_interval")]
+        ping_timeout: Duration,
+
+        /// Expose only the slice of resources configured under
+        /// [scopes.<SCOPE>]
+        #[arg(long)]
+        scope: Option<String>,
+
+        /// TOML file limiting the tools and resources each client may see
+        #[arg(long, env = "MCP_RS_POLICY")]
+        policy: Option<PathBuf>,
+
+        /// Expose only this tool to every client (repeatable)
+        #[arg(long, value_name = "TOOL")]
+        allow_tool: Vec<String>,
+
+        /// Hide this tool from every client (repeatable)
+        #[arg(long, value_name = "TOOL")]
+        deny_tool: Vec<String>,
+
+        /// Expose only this provider's resources to every client
+        /// (repeatable)
+        #[arg(long, value_name = "PROVIDER")]
+        allow_provider: Vec<String>,
+
+        /// Hide this provider's resources from every client (repeatable)
+        #[arg(long, value_name = "PROVIDER")]
+        deny_provider: Vec<String>,
+
+        /// Hide resources classified above this (public, internal or
+        /// confidential) from every client
+        #[arg(long, value_name = "LEVEL")]
+        max_classification: Option<Classification>,
+
+        /// Offer tools that create and change data upstream, such as
+        /// create_linear_issue
+        #[arg(long)]
+        allow_writes: bool,
+
+        /// Set up every provider and connect to upstream servers in the
+        /// background on startup, instead of on first use
+        #[arg(long)]
+        warm_up: bool,
+    },
+
+    /// Enumerate everything a provider's credentials can see
+    Discover {
+        /// Provider to explore (notion)
+        #[arg(short, long, default_value = "notion")]
+        source: String,
+    },
+
+    /// Benchmark provider latency and throughput
+    Bench {
+        /// Source provider to benchmark (notion, linear)
+        #[arg(short, long)]
+        source: String,
+
+        /// Operations to benchmark (fetch, search, get)
+        #[arg(long, value_delimiter = ',', default_value = "fetch,search,get")]
+        ops: Vec<String>,
+
+        /// Number of calls per operation
+        #[arg(short = 'n', long, default_value_t = 10)]
+        iterations: usize,
+
+        /// Number of calls in flight at once
+        #[arg(short, long, default_value_t = 1)]
+        concurrency: usize,
+
+        /// Search query used by the search benchmark
+        #[arg(short, long, default_value = "test")]
+        query: String,
+
+        /// Resource ID used by the get benchmark (defaults to the first fetched result)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Limit number of results per fetch
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Additional fetch filters (key=value pairs, e.g. page_size=25)
+        #[arg(short, long)]
+        filter: Vec<String>,
+
+        /// Replay resources from a fixtures file instead of the live provider
+        #[arg(long, conflicts_with = "record")]
+        fixtures: Option<PathBuf>,
+
+        /// Record fetched resources to a fixtures file for later replay
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+
+    /// Check a provider against the contracts adapters are expected to keep
+    Conformance {
+        /// Source provider to check (notion, linear, or an upstream name)
+        #[arg(short, long)]
+        source: String,
+
+        /// Search query used by the search check
+        #[arg(short, long, default_value = "test")]
+        query: String,
+
+        /// Page size used to walk pages
+        #[arg(long, default_value_t = 2)]
+        page_size: usize,
+
+        /// Pages walked at most
+        #[arg(long, default_value_t = 3)]
+        max_pages: usize,
+
+        /// Resources fetched by ID and hydrated
+        #[arg(long, default_value_t = 3)]
+        samples: usize,
+
+        /// Additional filters for listing (key=value pairs, e.g. database_id=...)
+        #[arg(short, long)]
+        filter: Vec<String>,
+    },
+
+    /// Check the configuration, then take fixture resources through the
+    /// cache, sync, index, output formats and an MCP session
+    Selftest {
+        /// Resources recorded with `bench --record` to use instead of the
+        /// built-in samples
+        #[arg(long)]
+        fixtures: Option<PathBuf>,
+    },
+
+    /// Show resources grouped by the rules in [projects.<name>]
+    Project {
+        #[command(subcommand)]
+        action: ProjectAction,
+    },
+
+    /// Run multi-step workflows defined in YAML files
+    Pipeline {
+        #[command(subcommand)]
+        action: PipelineAction,
+    },
+
+    /// Name resources so they can be fetched without their IDs
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+
+    /// Seed the cache and its index from a teammate's snapshot or a
+    /// running server instead of a first full sync
+    Bootstrap {
+        /// Snapshot file written by `cache export`, or the URL of a server
+        /// started with `serve --transport http` (or sse or ws)
+        #[arg(long)]
+        from: String,
+
+        /// Bearer token to present to the server, picking its client policy
+        #[arg(long, env = "MCP_RS_BOOTSTRAP_TOKEN")]
+        bearer_token: Option<String>,
+    },
+
+    /// Inspect and evict locally cached resources
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Check or rebuild the full-text index searches of the cache use
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+
+    /// Show cache statistics, or with --usage what the cache has saved
+    Stats {
+        /// Show local usage statistics: commands run, provider calls, and
+        /// calls avoided by serving from the cache. They never leave this
+        /// machine.
+        #[arg(long)]
+        usage: bool,
+    },
+
     /// Configure API credentials
     Config {
         #[command(subcommand)]
         action: ConfigAction,
     },
+
+    /// Run `serve` in the background from login on, as a systemd user
+    /// service or a Windows scheduled task
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProjectAction {
+    /// List configured projects
+    List,
+
+    /// Show a project's status and resources across providers
+    Show {
+        /// Project name
+        name: String,
+
+        /// Print a Markdown context bundle of the project instead
+        #[arg(long)]
+        bundle: bool,
+
+        /// Keep the bundle under this many characters, leaving out the
+        /// lowest ranked resources
+        #[arg(long, requires = "bundle")]
+        max_chars: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PipelineAction {
+    /// Run a pipeline's steps in order, printing its result unless a step
+    /// exports or posts it
+    Run {
+        /// Pipeline file
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AliasAction {
+    /// Point an alias at a resource, e.g. `alias set notion:roadmap notion_<page_id>`
+    Set {
+        /// Alias name
+        alias: String,
+
+        /// Resource ID, URI, Linear issue identifier, or another alias
+        id: String,
+    },
+
+    /// Remove an alias
+    Rm {
+        /// Alias name
+        alias: String,
+    },
+
+    /// List aliases, including Linear issue identifiers seen so far
+    Ls,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Show cache size and freshness per provider
+    Stats,
+
+    /// List cached resources
+    Ls {
+        /// Only list resources from this provider
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Only list entries older than the cache TTL
+        #[arg(long)]
+        stale: bool,
+
+        /// Include resources tombstoned by sync
+        #[arg(long)]
+        include_archived: bool,
+    },
+
+    /// Remove cached resources
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "source", "stale", "all"])))]
+    Invalidate {
+        /// Resource ID to remove
+        id: Option<String>,
+
+        /// Remove every resource from this provider
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Remove entries older than the cache TTL
+        #[arg(long)]
+        stale: bool,
+
+        /// Remove everything
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Reclaim space: forget downloaded attachments of resources that have
+    /// changed or left the cache, and delete stored files nothing uses
+    Prune,
+
+    /// Write the cached resources to a snapshot file another install can
+    /// bootstrap from
+    Export {
+        /// File to write the snapshot to
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IndexAction {
+    /// Compare the index with the cached resources
+    Status,
+
+    /// Index the cached resources afresh
+    Rebuild,
 }
 
 #[derive(Subcommand)]
 pub enum ConfigAction {
-    /// Set an API key
+    /// Store an API key in the OS keyring
     Set {
         /// Provider name (notion, linear)
         provider: String,
@@ -78,6 +687,42 @@ pub enum ConfigAction {
         /// Provider to test (optional, tests all if not specified)
         provider: Option<String>,
     },
+
+    /// Encrypt plaintext API keys in the config file
+    Encrypt {
+        /// age recipient to encrypt to (defaults to the configured identity file)
+        #[arg(long, conflicts_with_all = ["passphrase", "gpg"])]
+        recipient: Vec<String>,
+
+        /// Encrypt with the passphrase held in the keyring
+        #[arg(long, conflicts_with = "gpg")]
+        passphrase: bool,
+
+        /// Encrypt to a GPG key ID using the local gpg binary
+        #[arg(long)]
+        gpg: Option<String>,
+    },
+
+    /// Store the config encryption passphrase in the OS keyring
+    Passphrase,
+}
+
+#[derive(Subcommand)]
+pub enum DaemonAction {
+    /// Register and start the daemon, replacing any installed before, e.g.
+    /// `daemon install -- --transport http --port 8931`
+    Install {
+        /// Print what would be installed instead of installing it
+        #[arg(long)]
+        print: bool,
+
+        /// Arguments for `serve`; the transport defaults to http
+        #[arg(last = true)]
+        serve_args: Vec<String>,
+    },
+
+    /// Stop the daemon and unregister it
+    Uninstall,
 }
 
 pub fn parse_filters(filters: Vec<String>) -> HashMap<String, String> {
@@ -92,12 +737,148 @@ pub fn parse_filters(filters: Vec<String>) -> HashMap<String, String> {
 
 pub fn parse_sources(sources: Vec<String>) -> Vec<crate::domain::QuerySource> {
     sources
-        .into_iter()
-        .map(|s| match s.to_lowercase().as_str() {
-            "notion" => crate::domain::QuerySource::Notion,
-            "linear" => crate::domain::QuerySource::Linear,
-            "all" => crate::domain::QuerySource::All,
-            _ => crate::domain::QuerySource::All,
-        })
+        .iter()
+        .map(|source| crate::domain::QuerySource::parse(source))
         .collect()
 }
+
+/// A point in time for `--as-of`: an RFC 3339 timestamp, a date (midnight
+/// UTC), or a duration before now.
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(ago) = humantime::parse_duration(value) {
+        return chrono::Duration::from_std(ago)
+            .ok()
+            .and_then(|ago| Utc::now().checked_sub_signed(ago))
+            .ok_or_else(|| format!("{} is too long ago", value));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN).and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| {
+            "expected a date such as 2026-09-01, an RFC 3339 timestamp, or a duration such as 2w"
+                .to_string()
+        })
+}
+
+/// Queries for `search --batch`: one per line of the file at `path`, or of
+/// stdin for `-`, leaving out blank lines, `#` comments and repeats.
+pub fn read_queries(path: &Path) -> std::io::Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    let mut queries: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if !line.is_empty() && !line.starts_with('#') && !queries.iter().any(|q| q == line) {
+            queries.push(line.to_string());
+        }
+    }
+    Ok(queries)
+}
+
+/// The `serve` arguments a daemon runs with: `args`, serving HTTP unless
+/// they pick a transport or socket. A daemon has to listen for clients,
+/// and keys passed with `--api-key` would be written into its definition
+/// in plain text, so both are refused.
+pub fn daemon_serve_args(args: Vec<String>) -> Result<Vec<String>, String> {
+    let argv = ["mcp-rs", "serve"]
+        .into_iter()
+        .map(String::from)
+        .chain(args.clone());
+    let matches = Cli::command()
+        .try_get_matches_from(argv)
+        .map_err(|e| e.to_string())?;
+    let serve = matches
+        .subcommand_matches("serve")
+        .ok_or("Expected serve arguments")?;
+    if serve
+        .get_many::<String>("api_key")
+        .is_some_and(|keys| keys.len() > 0)
+    {
+        return Err(
+            "Store API keys with `config set` or in the config file rather than passing --api-key to the daemon"
+                .to_string(),
+        );
+    }
+
+    let socket = serve
+        .try_get_one::<PathBuf>("socket")
+        .is_ok_and(|socket| socket.is_some());
+    if socket {
+        return Ok(args);
+    }
+    match serve.value_source("transport") {
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable) => {
+            if serve.get_one::<Transport>("transport") == Some(&Transport::Stdio) {
+                return Err(
+                    "The daemon needs a network transport, e.g. --transport http".to_string(),
+                );
+            }
+        }
+        _ => {
+            let http = ["--transport".to_string(), "http".to_string()];
+            return Ok(http.into_iter().chain(args).collect());
+        }
+    }
+    Ok(args)
+}
+
+/// Opens `path` in the user's editor, `$VISUAL` or `$EDITOR` (which may
+/// carry arguments, e.g. `code --wait`), or else vi or Notepad, and waits
+/// for it to exit.
+pub fn edit_file(path: &Path) -> std::io::Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "{} exited with {}",
+            editor, status
+        )));
+    }
+    Ok(())
+}
+
+/// Writes `content` to a new file in the temp directory, readable only by
+/// the user and named `mcp-rs-<stem>-<random>.md` so no one can prepare
+/// the path beforehand.
+pub fn write_private_temp(stem: &str, content: &str) -> std::io::Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!(
+        "mcp-rs-{}-{}.md",
+        stem,
+        uuid::Uuid::new_v4().simple()
+    ));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)?.write_all(content.as_bytes())?;
+    Ok(path)
+}
+
+/// Renders a byte count with a binary unit, e.g. `1.2 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
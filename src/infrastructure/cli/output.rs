@@ -0,0 +1,684 @@
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashMap};
+
+use super::{
+    columns::{Align, Columns},
+    dates::Dates,
+};
+use crate::{
+    application::{grep::Section, project::ProjectReport},
+    domain::{Chunk, DiscoveredItem, DomainError, QueryResult, Resource, Route, SearchTier},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    /// One row per resource, with a column per record property.
+    Table,
+}
+
+/// Widest a table cell is allowed to get before it is truncated.
+const MAX_CELL_WIDTH: usize = 40;
+
+/// Prints the result of `fetch`. In text mode each resource's content is
+/// truncated to `preview` characters; tables have `columns` if given.
+pub fn print_results(
+    result: &QueryResult,
+    format: OutputFormat,
+    dates: Dates,
+    preview: usize,
+    columns: Option<&Columns>,
+) {
+    print!(
+        "{}",
+        render_results(result, format, dates, preview, columns)
+    );
+    if format != OutputFormat::Json {
+        print_problems(result);
+    }
+}
+
+/// What `print_results` prints to stdout.
+pub fn render_results(
+    result: &QueryResult,
+    format: OutputFormat,
+    dates: Dates,
+    preview: usize,
+    columns: Option<&Columns>,
+) -> String {
+    match format {
+        OutputFormat::Json => json(result),
+        OutputFormat::Text => {
+            let mut text = format!("Found {} resources:\n", result.resources.len());
+            for resource in &result.resources {
+                text.push_str(&summary(resource, dates, preview));
+            }
+            text
+        }
+        OutputFormat::Table => table(&result.resources, columns, dates),
+    }
+}
+
+/// Prints the result of `search`, showing at most `limit` resources.
+pub fn print_search_results(
+    result: &QueryResult,
+    format: OutputFormat,
+    dates: Dates,
+    limit: Option<usize>,
+    preview: usize,
+    columns: Option<&Columns>,
+) {
+    let shown = limit
+        .unwrap_or(result.resources.len())
+        .min(result.resources.len());
+
+    match format {
+        OutputFormat::Json => print_json(&QueryResult {
+            resources: result.resources[..shown].to_vec(),
+            errors: result.errors.clone(),
+            warnings: result.warnings.clone(),
+            origins: result.origins.clone(),
+            route: result.route.clone(),
+        }),
+        OutputFormat::Text => {
+            println!(
+                "Found {} resources (showing first {}):",
+                result.resources.len(),
+                shown
+            );
+            for resource in &result.resources[..shown] {
+                print_summary(resource, dates, preview);
+            }
+            print_origins(result, dates);
+            print_problems(result);
+        }
+        OutputFormat::Table => {
+            print_table(&result.resources[..shown], columns, dates);
+            print_origins(result, dates);
+            print_problems(result);
+        }
+    }
+}
+
+/// Prints the passages `search --chunks` found in `result`, most relevant
+/// first, in full.
+pub fn print_chunks(result: &QueryResult, chunks: &[Chunk], format: OutputFormat, dates: Dates) {
+    match format {
+        OutputFormat::Json => print_json(&serde_json::json!({
+            "chunks": chunks,
+            "errors": result.errors,
+            "warnings": result.warnings,
+            "origins": result.origins,
+            "route": result.route,
+        })),
+        OutputFormat::Text => {
+            println!(
+                "Found {} passages in {} resources:",
+                chunks.len(),
+                result.resources.len()
+            );
+            for chunk in chunks {
+                println!("\n--- {} ---", chunk_heading(chunk));
+                println!(
+                    "ID: {} (characters {}-{})",
+                    chunk.id, chunk.start, chunk.end
+                );
+                println!("{}", chunk.text);
+            }
+            print_origins(result, dates);
+            print_problems(result);
+        }
+        OutputFormat::Table => {
+            let header = ["id", "section", "start", "end", "text"].map(str::to_string);
+            let rows: Vec<Vec<String>> = chunks
+                .iter()
+                .map(|chunk| {
+                    vec![
+                        chunk.id.clone(),
+                        chunk_heading(chunk),
+                        chunk.start.to_string(),
+                        chunk.end.to_string(),
+                        chunk.text.clone(),
+                    ]
+                })
+                .collect();
+            print_grid(&header, &rows);
+            print_origins(result, dates);
+            print_problems(result);
+        }
+    }
+}
+
+/// The passage's resource title and the headings it is under.
+fn chunk_heading(chunk: &Chunk) -> String {
+    std::iter::once(chunk.title.as_str())
+        .chain(chunk.section.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
+/// Prints the results of `search --batch`, grouped by query, in the order
+/// the queries were given. JSON output is one array with an entry per query.
+pub fn print_batch_results(
+    queries: &[String],
+    results: &[Result<QueryResult, DomainError>],
+    format: OutputFormat,
+    dates: Dates,
+    limit: Option<usize>,
+    preview: usize,
+    columns: Option<&Columns>,
+) {
+    if format == OutputFormat::Json {
+        let entries: Vec<serde_json::Value> = queries
+            .iter()
+            .zip(results)
+            .map(|(query, result)| match result {
+                Ok(result) => {
+                    let shown = limit.unwrap_or(usize::MAX);
+                    serde_json::json!({
+                        "query": query,
+                        "resources": result.resources.iter().take(shown).collect::<Vec<_>>(),
+                        "errors": result.errors,
+                        "warnings": result.warnings,
+                        "origins": result.origins,
+                    })
+                }
+                Err(e) => serde_json::json!({ "query": query, "error": e.to_string() }),
+            })
+            .collect();
+        print_json(&entries);
+        return;
+    }
+
+    for (i, (query, result)) in queries.iter().zip(results).enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("=== {} ===", query);
+        match result {
+            Ok(result) => print_search_results(result, format, dates, limit, preview, columns),
+            Err(e) => eprintln!("Error searching for {:?}: {}", query, e),
+        }
+    }
+}
+
+pub fn print_resource(resource: &Resource, format: OutputFormat, dates: Dates) {
+    match format {
+        OutputFormat::Json => print_json(resource),
+        OutputFormat::Table => {
+            let mut rows = vec![
+                vec!["id".to_string(), resource.id.clone()],
+                vec!["title".to_string(), resource.title.clone()],
+                vec![
+                    "source".to_string(),
+                    resource.source.provider_name().to_string(),
+                ],
+                vec!["updated".to_string(), dates.timestamp(resource.updated_at)],
+            ];
+            if let Some(record) = record(resource) {
+                rows.extend(
+                    record
+                        .iter()
+                        .map(|(name, value)| vec![name.clone(), cell(value)]),
+                );
+            }
+            print_grid(&["field".to_string(), "value".to_string()], &rows);
+        }
+        OutputFormat::Text => {
+            println!("Resource: {}", resource.title);
+            println!("ID: {}", display_id(resource));
+            println!("Source: {:?}", resource.source);
+            println!("Created: {}", dates.timestamp(resource.created_at));
+            println!("Updated: {}", dates.timestamp(resource.updated_at));
+            if let Some(provenance) = &resource.provenance {
+                print!(
+                    "Fetched: {} from {}",
+                    dates.timestamp(provenance.fetched_at),
+                    provenance.provider
+                );
+                if let Some(version) = &provenance.api_version {
+                    print!(" API {}", version);
+                }
+                match &provenance.request_id {
+                    Some(request_id) => println!(" (request {})", request_id),
+                    None => println!(),
+                }
+                if let Some(url) = &provenance.url {
+                    println!("URL: {}", url);
+                }
+            }
+            println!("\nContent:\n{}", resource.content);
+
+            if !resource.metadata.is_empty() {
+                println!("\nMetadata:");
+                for (key, value) in &resource.metadata {
+                    println!("  {}: {}", key, value);
+                }
+            }
+        }
+    }
+}
+
+/// Prints `grep` sections like `grep -n -C`: matching lines as `N:`,
+/// context lines as `N-`, and `--` between sections.
+pub fn print_sections(resource: &Resource, sections: &[Section], format: OutputFormat) {
+    match format {
+        OutputFormat::Json => print_json(&serde_json::json!({
+            "id": resource.id,
+            "title": resource.title,
+            "sections": sections,
+        })),
+        OutputFormat::Text | OutputFormat::Table => {
+            if sections.is_empty() {
+                println!("No matches in {}", resource.title);
+                return;
+            }
+
+            println!("--- {} ({}) ---", resource.title, resource.id);
+            for (i, section) in sections.iter().enumerate() {
+                if i > 0 {
+                    println!("--");
+                }
+                for (offset, line) in section.lines.iter().enumerate() {
+                    let number = section.start_line + offset;
+                    let separator = if section.is_match(number) { ':' } else { '-' };
+                    println!("{}{}{}", number, separator, line);
+                }
+            }
+        }
+    }
+}
+
+/// Prints the result of `discover`: counts per kind, the top-level items
+/// (those whose parent isn't visible) with how much sits beneath them, and
+/// every database ID.
+pub fn print_project(
+    report: &ProjectReport,
+    format: OutputFormat,
+    dates: Dates,
+    columns: Option<&Columns>,
+) {
+    match format {
+        OutputFormat::Json => print_json(report),
+        OutputFormat::Table => print_table(&report.resources, columns, dates),
+        OutputFormat::Text => {
+            match &report.description {
+                Some(description) => println!("Project: {} ({})", report.name, description),
+                None => println!("Project: {}", report.name),
+            }
+
+            let status = &report.status;
+            let counts = |counts: &BTreeMap<String, usize>| {
+                counts
+                    .iter()
+                    .map(|(name, count)| format!("{} {}", name, count))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            match counts(&status.providers) {
+                by_provider if by_provider.is_empty() => println!("Resources: 0"),
+                by_provider => println!("Resources: {} ({})", report.resources.len(), by_provider),
+            }
+            if !status.issue_states.is_empty() {
+                println!("Issues: {}", counts(&status.issue_states));
+            }
+            if let Some(last_updated) = status.last_updated {
+                println!("Last updated: {}", dates.timestamp(last_updated));
+            }
+
+            if !report.resources.is_empty() {
+                println!("\nMost recently updated first:");
+            }
+            for resource in &report.resources {
+                let state = resource
+                    .metadata
+                    .get("state")
+                    .and_then(|s| s.as_str())
+                    .map(|s| format!(" [{}]", s))
+                    .unwrap_or_default();
+                println!(
+                    "  {}  {}{}  ({})",
+                    dates.date(resource.updated_at),
+                    truncate(&resource.title, 60),
+                    state,
+                    resource.id
+                );
+            }
+        }
+    }
+
+    for failure in &report.errors {
+        eprintln!(
+            "Warning: {} rule failed: {}",
+            failure.provider, failure.error
+        );
+    }
+    for warning in &report.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+pub fn print_discovery(items: &[DiscoveredItem], format: OutputFormat) {
+    match format {
+        OutputFormat::Json => print_json(&items),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = items
+                .iter()
+                .map(|item| {
+                    vec![
+                        item.kind.clone(),
+                        item.id.clone(),
+                        item.title.clone(),
+                        item.parent_id.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            print_grid(&["kind", "id", "title", "parent"].map(String::from), &rows);
+        }
+        OutputFormat::Text => {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            let mut children: HashMap<&str, Vec<&DiscoveredItem>> = HashMap::new();
+            for item in items {
+                *counts.entry(&item.kind).or_default() += 1;
+                if let Some(parent) = &item.parent_id {
+                    children.entry(parent).or_default().push(item);
+                }
+            }
+
+            println!(
+                "Discovered {} objects: {}",
+                items.len(),
+                counts
+                    .iter()
+                    .map(|(kind, count)| format!("{} {}s", count, kind))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+
+            let by_id: HashMap<&str, &DiscoveredItem> =
+                items.iter().map(|item| (item.id.as_str(), item)).collect();
+            let top_level: Vec<&DiscoveredItem> = items
+                .iter()
+                .filter(|item| {
+                    item.parent_id
+                        .as_deref()
+                        .is_none_or(|parent| !by_id.contains_key(parent))
+                })
+                .collect();
+
+            println!("\nTop level:");
+            for item in &top_level {
+                let mut descendants = 0;
+                let mut stack = vec![item.id.as_str()];
+                while let Some(id) = stack.pop() {
+                    for child in children.get(id).into_iter().flatten() {
+                        descendants += 1;
+                        stack.push(&child.id);
+                    }
+                }
+                println!(
+                    "  [{}] {} ({} beneath)  {}",
+                    item.kind, item.title, descendants, item.id
+                );
+            }
+
+            let databases: Vec<&DiscoveredItem> = items
+                .iter()
+                .filter(|item| item.kind == "database")
+                .collect();
+            if !databases.is_empty() {
+                println!("\nDatabases (use with --filter database_id=<id>):");
+                for database in databases {
+                    let parent = database
+                        .parent_id
+                        .as_deref()
+                        .and_then(|parent| by_id.get(parent))
+                        .map(|parent| format!("  (in {})", parent.title))
+                        .unwrap_or_default();
+                    println!("  {}  {}{}", database.id, database.title, parent);
+                }
+            }
+        }
+    }
+}
+
+fn print_summary(resource: &Resource, dates: Dates, preview: usize) {
+    print!("{}", summary(resource, dates, preview));
+}
+
+fn summary(resource: &Resource, dates: Dates, preview: usize) -> String {
+    format!(
+        "\n--- {} ---\nID: {}\nSource: {:?}\nCreated: {}\nContent: {}\n",
+        resource.title,
+        display_id(resource),
+        resource.source,
+        dates.timestamp(resource.created_at),
+        resource.excerpt(preview)
+    )
+}
+
+/// The resource's ID, followed by its short ID if it has one.
+fn display_id(resource: &Resource) -> String {
+    match resource.short_id() {
+        Some(short_id) => format!("{} ({})", resource.id, short_id),
+        None => resource.id.clone(),
+    }
+}
+
+/// Notes providers whose results weren't fetched live.
+fn print_origins(result: &QueryResult, dates: Dates) {
+    if let Some(route) = result.route.as_ref().filter(|route| **route != Route::All) {
+        eprintln!("({}; --source all searches everything)", route);
+    }
+    for (provider, origin) in &result.origins {
+        if origin.tier != SearchTier::Live {
+            eprintln!(
+                "({} results from {}, as of {})",
+                provider,
+                origin.tier,
+                dates.timestamp(origin.as_of)
+            );
+        }
+    }
+}
+
+/// Reports provider failures and warnings on stderr so that piped stdout
+/// only carries results.
+pub fn print_problems(result: &QueryResult) {
+    if result.is_partial() {
+        eprintln!(
+            "\nWarning: results are incomplete ({} of the requested providers failed)",
+            result.errors.len()
+        );
+    }
+    for failure in &result.errors {
+        eprintln!("  {}: {}", failure.provider, failure.error);
+    }
+    for warning in &result.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// Prints resources as a grid: `columns` if given, else ID and title,
+/// then one column for every record property seen across the resources
+/// (Notion database rows fetched with `--records`).
+fn print_table(resources: &[Resource], columns: Option<&Columns>, dates: Dates) {
+    print!("{}", table(resources, columns, dates));
+}
+
+fn table(resources: &[Resource], columns: Option<&Columns>, dates: Dates) -> String {
+    if let Some(Columns(columns)) = columns {
+        let header: Vec<String> = columns
+            .iter()
+            .map(|column| column.fit(&column.heading()))
+            .collect();
+        let rows: Vec<Vec<String>> = resources
+            .iter()
+            .map(|resource| {
+                columns
+                    .iter()
+                    .map(|column| column.fit(&column.cell(resource, dates)))
+                    .collect()
+            })
+            .collect();
+        // Columns with a width are already cut to it.
+        let layout: Vec<(usize, Option<Align>)> = columns
+            .iter()
+            .map(|column| {
+                let max = column.width.map_or(MAX_CELL_WIDTH, |_| usize::MAX);
+                (max, column.align)
+            })
+            .collect();
+        return laid_out_grid(&header, &rows, &layout);
+    }
+
+    let mut columns: Vec<String> = Vec::new();
+    for resource in resources {
+        for name in record(resource).into_iter().flat_map(|r| r.keys()) {
+            if !columns.contains(name) {
+                columns.push(name.clone());
+            }
+        }
+    }
+
+    let rows: Vec<Vec<String>> = resources
+        .iter()
+        .map(|resource| {
+            let record = record(resource);
+            let mut row = vec![resource.id.clone(), resource.title.clone()];
+            row.extend(columns.iter().map(|column| {
+                record
+                    .and_then(|r| r.get(column))
+                    .map(cell)
+                    .unwrap_or_default()
+            }));
+            row
+        })
+        .collect();
+
+    let mut header = vec!["id".to_string(), "title".to_string()];
+    header.extend(columns);
+    grid(&header, &rows)
+}
+
+fn print_grid(header: &[String], rows: &[Vec<String>]) {
+    print!("{}", grid(header, rows));
+}
+
+fn grid(header: &[String], rows: &[Vec<String>]) -> String {
+    let layout = vec![(MAX_CELL_WIDTH, Some(Align::Left)); header.len()];
+    laid_out_grid(header, rows, &layout)
+}
+
+/// A grid whose columns are cut to their width in `layout` and aligned as
+/// it says, or, where it doesn't, numbers on the right.
+fn laid_out_grid(
+    header: &[String],
+    rows: &[Vec<String>],
+    layout: &[(usize, Option<Align>)],
+) -> String {
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(layout)
+                .map(|(value, (max, _))| truncate(&value.replace('\n', " "), *max))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(header[i].chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let aligns: Vec<Align> = layout
+        .iter()
+        .enumerate()
+        .map(|(i, (_, align))| {
+            align.unwrap_or_else(|| {
+                let mut cells = rows
+                    .iter()
+                    .map(|row| &row[i])
+                    .filter(|cell| !cell.is_empty());
+                let numeric =
+                    cells.clone().next().is_some() && cells.all(|cell| cell.parse::<f64>().is_ok());
+                if numeric {
+                    Align::Right
+                } else {
+                    Align::Left
+                }
+            })
+        })
+        .collect();
+
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(widths.iter().zip(&aligns))
+            .map(|(cell, (width, align))| match align {
+                Align::Left => format!("{:<width$}", cell, width = width),
+                Align::Right => format!("{:>width$}", cell, width = width),
+                Align::Center => format!("{:^width$}", cell, width = width),
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut grid = format!("{}\n", line(header));
+    grid.push_str(
+        &widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-"),
+    );
+    grid.push('\n');
+    for row in &rows {
+        grid.push_str(&line(row));
+        grid.push('\n');
+    }
+    grid
+}
+
+fn record(resource: &Resource) -> Option<&serde_json::Map<String, serde_json::Value>> {
+    resource.metadata.get("record").and_then(|r| r.as_object())
+}
+
+pub(super) fn cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) {
+    print!("{}", json(value));
+}
+
+fn json<T: serde::Serialize>(value: &T) -> String {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => format!("{}\n", json),
+        Err(e) => {
+            eprintln!("Error serializing output: {}", e);
+            String::new()
+        }
+    }
+}
+
+/// Shortens `text` to at most `max` characters, appending `...` if cut.
+pub fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((i, _)) => format!("{}...", &text[..i]),
+        None => text.to_string(),
+    }
+}
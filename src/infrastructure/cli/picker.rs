@@ -0,0 +1,268 @@
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use std::io::{self, IsTerminal, Write};
+
+use super::dates::Dates;
+use crate::domain::Resource;
+
+/// Narrowest terminal that still gets a preview pane beside the list.
+const MIN_PREVIEW_WIDTH: usize = 80;
+
+/// Lets the user narrow `resources` down by typing a fuzzy pattern and
+/// choose one, previewing the highlighted resource's content beside the
+/// list. Returns the index of the chosen resource, or `None` if the user
+/// gave up. The picker draws on stderr, so stdout stays free for whatever
+/// the caller prints about the choice.
+pub fn pick(resources: &[Resource], dates: Dates) -> io::Result<Option<usize>> {
+    if !io::stderr().is_terminal() {
+        return Err(io::Error::other("picking a resource needs a terminal"));
+    }
+    let mut picker = Picker::new(resources, dates);
+    let _screen = Screen::enter()?;
+    let mut out = io::stderr();
+
+    loop {
+        picker.draw(&mut out)?;
+        // Anything else, such as a resize, only needs a redraw.
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c' | 'g') if ctrl => return Ok(None),
+            KeyCode::Enter => return Ok(picker.selection()),
+            KeyCode::Up => picker.move_by(-1),
+            KeyCode::Char('p' | 'k') if ctrl => picker.move_by(-1),
+            KeyCode::Down => picker.move_by(1),
+            KeyCode::Char('n' | 'j') if ctrl => picker.move_by(1),
+            KeyCode::PageUp => picker.move_by(-picker.page()),
+            KeyCode::PageDown => picker.move_by(picker.page()),
+            KeyCode::Char('u') if ctrl => picker.set_pattern(String::new()),
+            KeyCode::Backspace => {
+                let mut pattern = picker.pattern.clone();
+                pattern.pop();
+                picker.set_pattern(pattern);
+            }
+            KeyCode::Char(c) if !ctrl => {
+                let pattern = format!("{}{}", picker.pattern, c);
+                picker.set_pattern(pattern);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, restored on drop.
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(io::stderr(), EnterAlternateScreen) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Picker<'a> {
+    resources: &'a [Resource],
+    dates: Dates,
+    /// What each resource is matched on: its title and IDs.
+    haystacks: Vec<String>,
+    matcher: SkimMatcherV2,
+    pattern: String,
+    /// Indices of the resources matching `pattern`, best match first.
+    matches: Vec<usize>,
+    /// Position in `matches` of the highlighted resource.
+    selected: usize,
+    /// Position in `matches` of the first resource on screen.
+    offset: usize,
+    /// Rows the list had when last drawn.
+    rows: usize,
+}
+
+impl<'a> Picker<'a> {
+    fn new(resources: &'a [Resource], dates: Dates) -> Self {
+        let haystacks = resources
+            .iter()
+            .map(|resource| match resource.short_id() {
+                Some(short_id) => format!("{} {} {}", resource.title, short_id, resource.id),
+                None => format!("{} {}", resource.title, resource.id),
+            })
+            .collect();
+        Self {
+            resources,
+            dates,
+            haystacks,
+            matcher: SkimMatcherV2::default(),
+            pattern: String::new(),
+            matches: (0..resources.len()).collect(),
+            selected: 0,
+            offset: 0,
+            rows: 1,
+        }
+    }
+
+    /// Matches the resources against `pattern` afresh. Equally good
+    /// matches keep the order the resources came in, i.e. their ranking.
+    fn set_pattern(&mut self, pattern: String) {
+        let mut scored: Vec<(usize, i64)> = self
+            .haystacks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, haystack)| {
+                let score = self.matcher.fuzzy_match(haystack, &pattern)?;
+                Some((i, score))
+            })
+            .collect();
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(i, _)| i).collect();
+        self.pattern = pattern;
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    fn selection(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    fn move_by(&mut self, rows: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(rows).min(last);
+    }
+
+    fn page(&self) -> isize {
+        self.rows.max(1) as isize
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        self.rows = height.saturating_sub(2).max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.rows {
+            self.offset = self.selected + 1 - self.rows;
+        }
+        let list_width = if width >= MIN_PREVIEW_WIDTH {
+            width / 2
+        } else {
+            width
+        };
+
+        queue!(
+            out,
+            Clear(ClearType::All),
+            MoveTo(0, 1),
+            SetAttribute(Attribute::Dim),
+            Print(format!("  {}/{}", self.matches.len(), self.resources.len())),
+            SetAttribute(Attribute::Reset),
+        )?;
+        for (position, &index) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.rows)
+        {
+            let resource = &self.resources[index];
+            let selected = position == self.selected;
+            let line = format!(
+                "{} {:<8} {}",
+                if selected { '>' } else { ' ' },
+                resource.source.provider_name(),
+                resource.title
+            );
+            queue!(out, MoveTo(0, (position - self.offset + 2) as u16))?;
+            if selected {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                out,
+                Print(clip(&line, list_width.saturating_sub(1))),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+
+        if list_width < width {
+            let preview_width = width - list_width - 2;
+            let lines = self
+                .selection()
+                .map(|index| self.preview(&self.resources[index], preview_width))
+                .unwrap_or_default();
+            for row in 2..height {
+                queue!(out, MoveTo(list_width as u16, row as u16), Print('│'))?;
+                if let Some(line) = lines.get(row - 2) {
+                    queue!(out, Print(' '), Print(line))?;
+                }
+            }
+        }
+
+        let prompt = clip(&self.pattern, width.saturating_sub(3));
+        queue!(
+            out,
+            MoveTo(0, 0),
+            Print("> "),
+            Print(&prompt),
+            MoveTo((2 + prompt.chars().count()) as u16, 0)
+        )?;
+        out.flush()
+    }
+
+    /// The preview of `resource`: a short header, then its content wrapped
+    /// to `width`.
+    fn preview(&self, resource: &Resource, width: usize) -> Vec<String> {
+        let mut lines = vec![
+            clip(&resource.title, width),
+            clip(resource.short_id().unwrap_or(&resource.id), width),
+            clip(
+                &format!("Updated: {}", self.dates.timestamp(resource.updated_at)),
+                width,
+            ),
+            String::new(),
+        ];
+        if resource.content.is_empty() {
+            lines.push("(no content fetched)".to_string());
+        }
+        for line in resource.content.lines() {
+            let chars: Vec<char> = clip(line, usize::MAX).chars().collect();
+            if chars.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend(
+                chars
+                    .chunks(width.max(1))
+                    .map(|chunk| chunk.iter().collect()),
+            );
+        }
+        lines
+    }
+}
+
+/// `text` cut to `width` characters, with control characters such as tabs
+/// blanked so they can't move the cursor.
+fn clip(text: &str, width: usize) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(width)
+        .collect()
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Config, ConfigError, KEYRING_SERVICE};
+
+/// Built-in providers that take an API key.
+pub const PROVIDERS: [&str; 8] = [
+    "notion",
+    "linear",
+    "trello",
+    "zendesk",
+    "azuredevops",
+    "todoist",
+    "coda",
+    "imap",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialSource {
+    Flag,
+    Environment,
+    Keyring,
+    ConfigFile,
+}
+
+impl fmt::Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flag => write!(f, "--api-key flag"),
+            Self::Environment => write!(f, "environment"),
+            Self::Keyring => write!(f, "keyring"),
+            Self::ConfigFile => write!(f, "config file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Credential {
+    pub value: String,
+    pub source: CredentialSource,
+}
+
+/// Resolves provider credentials from every supported location, in order of
+/// precedence:
+///
+/// 1. `--api-key <provider>=<key>` flags for this invocation
+/// 2. `<PROVIDER>_API_KEY` environment variables
+/// 3. the OS keyring (`config set`)
+/// 4. `providers.<provider>.api_key` in the config file
+///
+/// Keyring lookups block, so call `resolve` off the async workers.
+pub struct CredentialResolver {
+    overrides: HashMap<String, String>,
+    config: Arc<Config>,
+}
+
+impl CredentialResolver {
+    pub fn new(overrides: HashMap<String, String>, config: Arc<Config>) -> Self {
+        let overrides = overrides
+            .into_iter()
+            .map(|(provider, key)| (provider.to_lowercase(), key))
+            .collect();
+        Self { overrides, config }
+    }
+
+    /// Environment variable holding the API key for `provider`.
+    pub fn env_var(provider: &str) -> String {
+        format!("{}_API_KEY", provider.to_uppercase())
+    }
+
+    /// Resolves the credentials of every built-in provider that has one,
+    /// off the async workers.
+    pub async fn resolve_all(self) -> HashMap<&'static str, Credential> {
+        tokio::task::spawn_blocking(move || {
+            PROVIDERS
+                .into_iter()
+                .filter_map(|provider| Some((provider, self.resolve(provider)?)))
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    pub fn resolve(&self, provider: &str) -> Option<Credential> {
+        let provider = provider.to_lowercase();
+
+        if let Some(value) = self.overrides.get(&provider) {
+            return Some(Credential {
+                value: value.clone(),
+                source: CredentialSource::Flag,
+            });
+        }
+
+        if let Ok(value) = std::env::var(Self::env_var(&provider)) {
+            return Some(Credential {
+                value,
+                source: CredentialSource::Environment,
+            });
+        }
+
+        match keyring_entry(&provider).and_then(|entry| entry.get_password()) {
+            Ok(value) => {
+                return Some(Credential {
+                    value,
+                    source: CredentialSource::Keyring,
+                })
+            }
+            Err(keyring::Error::NoEntry) => {}
+            Err(e) => tracing::debug!("Keyring lookup for {} failed: {}", provider, e),
+        }
+
+        self.config
+            .provider(&provider)
+            .and_then(|p| p.api_key.clone())
+            .map(|value| Credential {
+                value,
+                source: CredentialSource::ConfigFile,
+            })
+    }
+}
+
+/// Stores an API key for `provider` in the OS keyring.
+pub fn store_in_keyring(provider: &str, api_key: &str) -> Result<(), ConfigError> {
+    keyring_entry(&provider.to_lowercase())
+        .and_then(|entry| entry.set_password(api_key))
+        .map_err(|e| ConfigError::Secret(format!("Failed to store API key in keyring: {}", e)))
+}
+
+fn keyring_entry(provider: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("{}-api-key", provider))
+}
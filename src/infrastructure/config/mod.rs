@@ -0,0 +1,931 @@
+pub mod credentials;
+pub mod secrets;
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use self::secrets::{EncryptionTarget, SecretCipher};
+use super::sandbox;
+use crate::domain::{
+    Classification, ClassificationRule, DomainError, Guardrails, ProjectDefinition,
+    ScopeDefinition, ScoringWeights, SyncScope,
+};
+
+/// Service name for everything mcp-rs stores in the OS keyring.
+const KEYRING_SERVICE: &str = "mcp-rs";
+
+/// `${NAME}` in configured URLs or headers, replaced by the environment
+/// variable so tokens stay out of the file.
+static ENV_VAR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config {path}: {message}")]
+    Read { path: PathBuf, message: String },
+
+    #[error("Failed to write config {path}: {message}")]
+    Write { path: PathBuf, message: String },
+
+    #[error("Invalid config {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+
+    #[error("Secret error: {0}")]
+    Secret(String),
+}
+
+/// Contents of the `config.toml` file.
+///
+/// ```toml
+/// [encryption]
+/// identity_file = "~/.config/mcp-rs/identity.txt"
+///
+/// [providers.notion]
+/// api_key = "enc:age:YWdlLWVuY3J5cHRpb24..."
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Refuse every change to upstream data, like `--read-only`.
+    #[serde(default)]
+    pub read_only: bool,
+
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    #[serde(default)]
+    pub search: SearchConfig,
+
+    #[serde(default)]
+    pub prompts: PromptsConfig,
+
+    #[serde(default)]
+    pub subscriptions: SubscriptionsConfig,
+
+    #[serde(default)]
+    pub links: LinksConfig,
+
+    #[serde(default)]
+    pub providers: BTreeMap<String, ProviderConfig>,
+
+    /// Other MCP servers whose resources are served as providers named
+    /// after them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub upstreams: BTreeMap<String, UpstreamConfig>,
+
+    /// Folders of Markdown notes, such as Obsidian vaults, served as
+    /// providers named after them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vaults: BTreeMap<String, VaultConfig>,
+
+    /// JSON APIs served as providers named after them, their responses
+    /// mapped to resources as configured.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rest: BTreeMap<String, RestConfig>,
+
+    /// GraphQL APIs served as providers named after them, queried and
+    /// mapped to resources as configured.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub graphql: BTreeMap<String, GraphqlConfig>,
+
+    /// SQL databases served as providers named after them, the rows of
+    /// their queries mapped to resources as configured.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sql: BTreeMap<String, SqlConfig>,
+
+    /// Elasticsearch and OpenSearch indices served as providers named after
+    /// them, their hits mapped to resources as configured.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub elasticsearch: BTreeMap<String, ElasticsearchConfig>,
+
+    /// Limits protecting shared API quotas from runaway callers, e.g.
+    ///
+    /// ```toml
+    /// [guardrails]
+    /// max_api_calls = 200
+    /// max_resources = 500
+    /// max_bundle_tokens = 50000
+    /// ```
+    #[serde(default)]
+    pub guardrails: Guardrails,
+
+    /// Named lists of providers, usable anywhere a source is, e.g.
+    /// `search --source docs`. Groups may include other groups and `all`:
+    ///
+    /// ```toml
+    /// [sources]
+    /// docs = ["notion", "handbook"]
+    /// tickets = ["linear", "jira"]
+    /// work = ["docs", "tickets"]
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, Vec<String>>,
+
+    /// Resources grouped by initiative, e.g.
+    ///
+    /// ```toml
+    /// [projects.checkout]
+    /// description = "Checkout redesign"
+    /// linear_projects = ["Checkout Redesign"]
+    /// notion_pages = ["8a1f2c3d4e5f60718293a4b5c6d7e8f9"]
+    /// ```
+    #[serde(default)]
+    pub projects: BTreeMap<String, ProjectDefinition>,
+
+    /// Slices of the knowledge base a server can be limited to with
+    /// `serve --scope`, e.g.
+    ///
+    /// ```toml
+    /// [scopes.payments]
+    /// notion_databases = ["8a1f2c3d4e5f60718293a4b5c6d7e8f9"]
+    /// linear_teams = ["PAY"]
+    /// directories = ["~/notes/payments"]
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scopes: BTreeMap<String, ScopeDefinition>,
+
+    /// How sensitive each source's resources are, tagged in their metadata
+    /// for policies to act on, e.g.
+    ///
+    /// ```toml
+    /// [classification]
+    /// default = "internal"
+    ///
+    /// [[classification.rules]]
+    /// classification = "confidential"
+    /// notion_databases = ["8a1f2c3d4e5f60718293a4b5c6d7e8f9"]
+    ///
+    /// [[classification.rules]]
+    /// classification = "public"
+    /// providers = ["docs"]
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<ClassificationConfig>,
+
+    /// Ordering of search results and project bundles; providers' own
+    /// order when omitted, e.g.
+    ///
+    /// ```toml
+    /// [scoring]
+    /// recency = 1.0
+    /// pins = ["ENG-123", "notion:roadmap"]
+    ///
+    /// [scoring.providers]
+    /// linear = 1.0
+    /// notion = 0.5
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scoring: Option<ScoringWeights>,
+
+    /// Scripts that fetch, get and search results pass through, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookConfig>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// age identity file used to decrypt values encrypted to its recipient.
+    pub identity_file: Option<PathBuf>,
+}
+
+/// Local resource cache.
+///
+/// ```toml
+/// [cache]
+/// path = "~/.local/share/mcp-rs/cache.db"
+/// ttl_secs = 3600
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub path: Option<PathBuf>,
+    /// How long a cached resource is served by `get` before refetching.
+    pub ttl_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+            ttl_secs: 3600,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn path(&self) -> PathBuf {
+        match &self.path {
+            Some(path) => secrets::expand_home(path),
+            None => dirs::data_local_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("mcp-rs")
+                .join("cache.db"),
+        }
+    }
+}
+
+/// Search freshness.
+///
+/// ```toml
+/// [search]
+/// max_staleness_secs = 900
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SearchConfig {
+    /// Default for `search --max-staleness` and the MCP search tool. Unset
+    /// means always search live.
+    pub max_staleness_secs: Option<u64>,
+}
+
+/// Classification of resources. The first rule covering a resource
+/// classifies it; resources no rule covers get `default`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ClassificationConfig {
+    #[serde(default)]
+    pub default: Classification,
+    #[serde(default)]
+    pub rules: Vec<ClassificationRule>,
+}
+
+/// Prompt templates served over MCP.
+///
+/// ```toml
+/// [prompts]
+/// dir = "~/.config/mcp-rs/prompts"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PromptsConfig {
+    pub dir: Option<PathBuf>,
+}
+
+impl PromptsConfig {
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => secrets::expand_home(dir),
+            None => dirs::config_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("mcp-rs")
+                .join("prompts"),
+        }
+    }
+}
+
+/// A Rhai script filtering, transforming or ranking results.
+///
+/// ```toml
+/// [[hooks]]
+/// script = "~/.config/mcp-rs/hooks/triage.rhai"
+/// providers = ["linear"]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookConfig {
+    pub script: PathBuf,
+    /// Providers whose resources the script sees; all if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<String>,
+}
+
+#[cfg(feature = "scripting")]
+impl HookConfig {
+    pub fn script(&self) -> PathBuf {
+        secrets::expand_home(&self.script)
+    }
+}
+
+/// Resource subscriptions of MCP clients.
+///
+/// ```toml
+/// [subscriptions]
+/// poll_interval_secs = 60
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubscriptionsConfig {
+    /// How often subscribed resources are refetched to look for changes.
+    pub poll_interval_secs: u64,
+}
+
+impl Default for SubscriptionsConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 60,
+        }
+    }
+}
+
+/// Titles of bare links in resource content, looked up on the web.
+///
+/// ```toml
+/// [links]
+/// resolve_titles = true
+/// max_per_resource = 20
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinksConfig {
+    /// Turn bare links into Markdown links titled after their pages. Each
+    /// page is fetched once a day at most.
+    pub resolve_titles: bool,
+    /// Links looked up per resource; the rest are left bare.
+    pub max_per_resource: usize,
+}
+
+impl Default for LinksConfig {
+    fn default() -> Self {
+        Self {
+            resolve_titles: false,
+            max_per_resource: 20,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    /// API key, either plaintext or an `enc:age:`/`enc:gpg:` encrypted value.
+    pub api_key: Option<String>,
+
+    /// Where the provider's account is, for providers hosted per account,
+    /// e.g. `https://acme.zendesk.com` for Zendesk or
+    /// `https://tfs.acme.com/tfs/DefaultCollection` for Azure DevOps Server.
+    pub url: Option<String>,
+
+    /// Overrides for the provider's default retry policy.
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Concurrency of `sync` for this provider.
+    #[serde(default)]
+    pub sync: SyncConfig,
+
+    /// Mailboxes listed and searched, for IMAP; the inbox if none are set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mailboxes: Vec<String>,
+}
+
+/// A downstream MCP server, either a command to run or a streamable HTTP
+/// endpoint.
+///
+/// ```toml
+/// [upstreams.docs]
+/// command = "docs-mcp-server"
+/// args = ["--root", "~/handbook"]
+///
+/// [upstreams.wiki]
+/// url = "https://wiki.example.com/mcp"
+/// token = "enc:age:YWdlLWVuY3J5cHRpb24..."
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamConfig {
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Environment variables set for `command`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    pub url: Option<String>,
+    /// Bearer token for `url`, either plaintext or encrypted like `api_key`.
+    pub token: Option<String>,
+
+    /// Overrides for the retry policy of `url` requests.
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// A folder of Markdown notes.
+///
+/// ```toml
+/// [vaults.notes]
+/// path = "~/Documents/Notes"
+/// obsidian = true
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultConfig {
+    pub path: PathBuf,
+    /// Resolve wikilinks, read tags and aliases, and leave out the files
+    /// Obsidian excludes. On by default when the folder has a `.obsidian`
+    /// folder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obsidian: Option<bool>,
+}
+
+impl VaultConfig {
+    pub fn path(&self) -> PathBuf {
+        secrets::expand_home(&self.path)
+    }
+}
+
+/// A JSON API served as a provider. Endpoints' paths and parameters may
+/// use `{limit}`, `{id}` (for `get`), `{query}` (for `search`), `{page}` or
+/// `{offset}` to page, and `{<filter>}` for a `--filter` given; parameters
+/// whose placeholders aren't set are left out. Responses are mapped to
+/// resources by JSONPath (`$.data[*].name`).
+///
+/// ```toml
+/// [rest.helpdesk]
+/// base_url = "https://helpdesk.example.com/api/v2"
+/// token = "enc:age:YWdlLWVuY3J5cHRpb24..."
+///
+/// [rest.helpdesk.list]
+/// path = "/tickets"
+/// params = { per_page = "{limit}", status = "{status}" }
+/// items = "$.tickets[*]"
+/// next = "$.next_page"
+///
+/// [rest.helpdesk.get]
+/// path = "/tickets/{id}"
+/// items = "$.ticket"
+///
+/// [rest.helpdesk.fields]
+/// id = "$.id"
+/// title = "$.subject"
+/// content = "$.description"
+/// updated_at = "$.updated_at"
+/// metadata = { status = "$.status", tags = "$.tags[*]" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestConfig {
+    pub base_url: String,
+    /// Bearer token, either plaintext or encrypted like `api_key`.
+    pub token: Option<String>,
+    /// Headers sent with every request, e.g. an API key; `${NAME}` in a
+    /// value is replaced by the environment variable `NAME`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    pub list: RestEndpoint,
+    /// Fetches one item by `{id}`. Without it, items are found by listing.
+    pub get: Option<RestEndpoint>,
+    /// Searches by `{query}`. Without it, listed items are searched.
+    pub search: Option<RestEndpoint>,
+
+    pub fields: RestFields,
+
+    /// Overrides for the retry policy of the API's requests.
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// One endpoint of a `[rest.<name>]` API.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestEndpoint {
+    /// Relative to `base_url`, or a whole URL.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+    /// JSONPath of the items in a response (for `get`, the item), the
+    /// whole response if unset.
+    pub items: Option<String>,
+    /// JSONPath of the next page's URL in a response, or with
+    /// `cursor_param` set, of the cursor passed back as that parameter.
+    pub next: Option<String>,
+    pub cursor_param: Option<String>,
+}
+
+/// A GraphQL API served as a provider. Each query is sent with whichever
+/// of these variables it declares: `$limit` (the page size, at most 100)
+/// and `$cursor` to page, `$id` (for `get`), `$query` (for `search`), a
+/// `$<filter>` for each `--filter` given, and its own `variables`. Items
+/// are found in the response's `data`, and mapped to resources, by
+/// JSONPath like those of `[rest.<name>]`.
+///
+/// ```toml
+/// [graphql.github]
+/// url = "https://api.github.com/graphql"
+/// token = "enc:age:YWdlLWVuY3J5cHRpb24..."
+///
+/// [graphql.github.list]
+/// query = '''
+/// query($owner: String!, $limit: Int!, $cursor: String) {
+///   repository(owner: $owner, name: "web") {
+///     discussions(first: $limit, after: $cursor) {
+///       nodes { id title body url createdAt updatedAt }
+///       pageInfo { hasNextPage endCursor }
+///     }
+///   }
+/// }'''
+/// variables = { owner = "acme" }
+/// items = "$.repository.discussions.nodes[*]"
+/// next = "$.repository.discussions.pageInfo.endCursor"
+/// has_next = "$.repository.discussions.pageInfo.hasNextPage"
+///
+/// [graphql.github.get]
+/// query = "query($id: ID!) { node(id: $id) { ... on Discussion { id title body url createdAt updatedAt } } }"
+/// items = "$.node"
+///
+/// [graphql.github.fields]
+/// id = "$.id"
+/// title = "$.title"
+/// content = "$.body"
+/// url = "$.url"
+/// created_at = "$.createdAt"
+/// updated_at = "$.updatedAt"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphqlConfig {
+    /// The endpoint queries are posted to.
+    pub url: String,
+    /// Bearer token, either plaintext or encrypted like `api_key`.
+    pub token: Option<String>,
+    /// Headers sent with every request; `${NAME}` in a value is replaced
+    /// by the environment variable `NAME`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    pub list: GraphqlQuery,
+    /// Fetches one item by `$id`. Without it, items are found by listing.
+    pub get: Option<GraphqlQuery>,
+    /// Searches by `$query`. Without it, listed items are searched.
+    pub search: Option<GraphqlQuery>,
+
+    pub fields: RestFields,
+
+    /// Overrides for the retry policy of the API's requests.
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// One query of a `[graphql.<name>]` API.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphqlQuery {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, serde_json::Value>,
+    /// JSONPath of the items in the response's `data` (for `get`, the
+    /// item), all of `data` if unset.
+    pub items: Option<String>,
+    /// JSONPath of the cursor for the next page, passed back as `$cursor`.
+    pub next: Option<String>,
+    /// JSONPath of whether there is a next page, for APIs that give a
+    /// cursor on the last page too.
+    pub has_next: Option<String>,
+}
+
+/// A SQL database served as a provider: Postgres, MySQL or SQLite, by the
+/// scheme of its `url`. Resources are the rows `list` returns, their
+/// columns mapped to resource fields by `columns`, JSONPaths into the row
+/// like those of `[rest.<name>]` whose first key is the column's name. Each
+/// `--filter column=value` keeps rows where the column is that value, and
+/// `page_size` sets how many rows are read at once.
+///
+/// `get` and `search` take one parameter, the item's ID and the search
+/// text, written as the database writes them: `$1` in Postgres, `?` in
+/// MySQL and SQLite.
+///
+/// ```toml
+/// [sql.kb]
+/// url = "postgres://reader@db.internal/kb"
+/// password = "enc:age:YWdlLWVuY3J5cHRpb24..."
+/// list = "SELECT id, title, body, author, tags, updated_at FROM articles WHERE published ORDER BY id"
+/// search = "SELECT id, title, body, author, tags, updated_at FROM articles WHERE published AND search @@ plainto_tsquery($1)"
+///
+/// [sql.kb.columns]
+/// id = "id"
+/// title = "title"
+/// content = "body"
+/// updated_at = "updated_at"
+/// metadata = { author = "author", tags = "tags[*]" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SqlConfig {
+    /// `postgres://`, `mysql://` or `sqlite://` connection URL.
+    pub url: String,
+    /// The database password, either plaintext or encrypted like
+    /// `api_key`, if it isn't in `url`.
+    pub password: Option<String>,
+
+    /// Selects the rows served, in a stable order so they can be paged.
+    pub list: String,
+    /// Selects the row with the given ID. Without it, the listed row with
+    /// that ID is looked up.
+    pub get: Option<String>,
+    /// Selects the rows matching the given text. Without it, listed rows
+    /// whose title or content contains every word are found.
+    pub search: Option<String>,
+
+    pub columns: RestFields,
+}
+
+/// An Elasticsearch or OpenSearch index served as a provider, so searches
+/// are ranked by the cluster's own full-text relevance. Resources are the
+/// index's hits, mapped by JSONPaths within each hit like those of
+/// `[rest.<name>]`: the document is under `_source`, its ID is `_id` and
+/// its relevance `_score`. Each `--filter field=value` keeps documents
+/// where the field is the value exactly (a `term` query), and `page_size`
+/// sets how many are read at once.
+///
+/// ```toml
+/// [elasticsearch.kb]
+/// url = "https://search.internal:9200"
+/// index = "articles"
+/// api_key = "enc:age:YWdlLWVuY3J5cHRpb24..."
+/// filter = { term = { published = true } }
+/// sort = [{ updated_at = "desc" }]
+/// search_fields = ["title^3", "body"]
+///
+/// [elasticsearch.kb.fields]
+/// id = "$._id"
+/// title = "$._source.title"
+/// content = "$._source.body"
+/// url = "$._source.link"
+/// updated_at = "$._source.updated_at"
+/// metadata = { author = "$._source.author", score = "$._score" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElasticsearchConfig {
+    /// The cluster's address.
+    pub url: String,
+    /// The index searched, or an alias, pattern or comma-separated list.
+    pub index: String,
+    /// An API key, or `<username>:<password>` for basic authentication,
+    /// either plaintext or encrypted like `api_key` of a provider.
+    pub api_key: Option<String>,
+    /// Headers sent with every request; `${NAME}` in a value is replaced
+    /// by the environment variable `NAME`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Query DSL every listing and search is restricted to.
+    pub filter: Option<serde_json::Value>,
+    /// Sort of listings, in the index's order if unset. Searches are
+    /// ranked by relevance.
+    pub sort: Option<serde_json::Value>,
+    /// Query DSL of searches, `{query}` in its strings replaced by the
+    /// search text. Without it, a `multi_match` of `search_fields`.
+    pub query: Option<serde_json::Value>,
+    /// Fields searched, with optional boosts such as `title^3`; those the
+    /// index searches by default if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub search_fields: Vec<String>,
+
+    pub fields: RestFields,
+
+    /// Overrides for the retry policy of the cluster's requests.
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// JSONPaths of a `[rest.<name>]` or `[graphql.<name>]` item's fields,
+/// within the item, of an `[elasticsearch.<name>]` hit's, within the hit,
+/// or of a `[sql.<name>]` row's, within the row.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestFields {
+    pub id: String,
+    pub title: String,
+    /// The whole item as JSON if unset.
+    pub content: Option<String>,
+    /// Link to the item for people.
+    pub url: Option<String>,
+    /// RFC 3339 times, or seconds or milliseconds since 1970.
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// Further fields, put in the resource's metadata under these names.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Retry settings for one provider. Unset fields keep the adapter's default.
+///
+/// ```toml
+/// [providers.notion.retry]
+/// max_retries = 5
+/// base_delay_ms = 500
+/// max_delay_ms = 30000
+/// retry_on = [429, 502, 503]
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    pub max_retries: Option<u32>,
+    pub base_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+    pub retry_on: Option<Vec<u16>>,
+}
+
+/// Sync settings for one provider. Unset fields keep the defaults, and
+/// without scopes `sync` mirrors everything the provider lists.
+///
+/// ```toml
+/// [providers.notion.sync]
+/// workers = 3
+/// queue_size = 200
+///
+/// [[providers.notion.sync.scopes]]
+/// database_id = "0123456789abcdef0123456789abcdef"
+///
+/// [[providers.linear.sync.scopes]]
+/// team = "ENG"
+/// since = "90d"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    pub workers: Option<usize>,
+    pub queue_size: Option<usize>,
+    /// The slices `sync` mirrors instead of everything.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<SyncScope>,
+}
+
+impl Config {
+    /// Config location: `$MCP_RS_CONFIG`, else `<config dir>/mcp-rs/config.toml`.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os("MCP_RS_CONFIG") {
+            return PathBuf::from(path);
+        }
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("mcp-rs")
+            .join("config.toml")
+    }
+
+    /// Loads the config file, returning the defaults if it does not exist.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(ConfigError::Read {
+                    path: path.to_path_buf(),
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        toml::from_str(&data).map_err(|e| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&ProviderConfig> {
+        self.providers.get(&name.to_lowercase())
+    }
+
+    /// The sync scopes of each provider that has any.
+    pub fn sync_scopes(&self) -> HashMap<String, Vec<SyncScope>> {
+        self.providers
+            .iter()
+            .filter(|(_, provider)| !provider.sync.scopes.is_empty())
+            .map(|(name, provider)| (name.to_lowercase(), provider.sync.scopes.clone()))
+            .collect()
+    }
+
+    /// The scope called `name`, with `~` in its directories expanded.
+    pub fn scope(&self, name: &str) -> Option<ScopeDefinition> {
+        let mut scope = self.scopes.get(name)?.clone();
+        for directory in &mut scope.directories {
+            *directory = secrets::expand_home(directory);
+        }
+        Some(scope)
+    }
+
+    /// The classification rules, with `~` in their directories expanded.
+    pub fn classification(&self) -> Option<ClassificationConfig> {
+        let mut classification = self.classification.clone()?;
+        for rule in &mut classification.rules {
+            for directory in &mut rule.sources.directories {
+                *directory = secrets::expand_home(directory);
+            }
+        }
+        Some(classification)
+    }
+
+    /// Replaces every encrypted secret with its plaintext. Values that fail to
+    /// decrypt are dropped and reported, so one bad key doesn't block startup.
+    pub fn decrypt_secrets(&mut self, cipher: &SecretCipher) -> Vec<(String, ConfigError)> {
+        let mut failures = Vec::new();
+
+        let mut decrypt = |name: String, secret: &mut Option<String>| {
+            let Some(value) = secret.take() else {
+                return;
+            };
+
+            if !secrets::is_encrypted(&value) {
+                *secret = Some(value);
+                return;
+            }
+
+            match cipher.decrypt(&value) {
+                Ok(plaintext) => *secret = Some(plaintext),
+                Err(e) => failures.push((name, e)),
+            }
+        };
+
+        for (name, provider) in self.providers.iter_mut() {
+            decrypt(name.clone(), &mut provider.api_key);
+        }
+        for (name, upstream) in self.upstreams.iter_mut() {
+            decrypt(format!("upstream {}", name), &mut upstream.token);
+        }
+        for (name, rest) in self.rest.iter_mut() {
+            decrypt(format!("rest {}", name), &mut rest.token);
+        }
+        for (name, graphql) in self.graphql.iter_mut() {
+            decrypt(format!("graphql {}", name), &mut graphql.token);
+        }
+        for (name, sql) in self.sql.iter_mut() {
+            decrypt(format!("sql {}", name), &mut sql.password);
+        }
+        for (name, elasticsearch) in self.elasticsearch.iter_mut() {
+            decrypt(
+                format!("elasticsearch {}", name),
+                &mut elasticsearch.api_key,
+            );
+        }
+
+        failures
+    }
+}
+
+/// Encrypts every plaintext `api_key` in the config file in place, keeping
+/// the rest of the file (comments, ordering) untouched. Returns the names of
+/// the providers whose keys were encrypted.
+pub fn encrypt_file(
+    path: &Path,
+    cipher: &SecretCipher,
+    target: &EncryptionTarget,
+) -> Result<Vec<String>, ConfigError> {
+    let data = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let mut document: toml_edit::DocumentMut =
+        data.parse()
+            .map_err(|e: toml_edit::TomlError| ConfigError::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+    let mut encrypted = Vec::new();
+    if let Some(providers) = document
+        .get_mut("providers")
+        .and_then(|providers| providers.as_table_like_mut())
+    {
+        for (name, provider) in providers.iter_mut() {
+            let Some(api_key) = provider
+                .get_mut("api_key")
+                .and_then(|api_key| api_key.as_value_mut())
+            else {
+                continue;
+            };
+
+            let Some(plaintext) = api_key.as_str().filter(|v| !secrets::is_encrypted(v)) else {
+                continue;
+            };
+
+            let ciphertext = cipher.encrypt(plaintext, target)?;
+            *api_key = toml_edit::Value::from(ciphertext).decorated(
+                api_key
+                    .decor()
+                    .prefix()
+                    .and_then(|p| p.as_str())
+                    .unwrap_or(" "),
+                api_key
+                    .decor()
+                    .suffix()
+                    .and_then(|s| s.as_str())
+                    .unwrap_or(""),
+            );
+            encrypted.push(name.to_string());
+        }
+    }
+
+    std::fs::write(path, document.to_string()).map_err(|e| ConfigError::Write {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    Ok(encrypted)
+}
+
+/// `text` with each `${NAME}` replaced by the environment variable `NAME`.
+/// Refused in the sandbox, where the config may come from anyone and the
+/// environment holds secrets.
+pub fn expand_env(text: &str) -> Result<String, DomainError> {
+    if sandbox::active().is_some() && ENV_VAR.is_match(text) {
+        return Err(DomainError::InvalidQuery(
+            "the sandbox expands no environment variables".to_string(),
+        ));
+    }
+    let mut missing = None;
+    let expanded = ENV_VAR.replace_all(text, |captures: &Captures| {
+        std::env::var(&captures[1]).unwrap_or_else(|_| {
+            missing.get_or_insert_with(|| captures[1].to_string());
+            String::new()
+        })
+    });
+    match missing {
+        Some(name) => Err(DomainError::InvalidQuery(format!(
+            "environment variable {} isn't set",
+            name
+        ))),
+        None => Ok(expanded.into_owned()),
+    }
+}
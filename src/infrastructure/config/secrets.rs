@@ -0,0 +1,253 @@
+use age::secrecy::SecretString;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use super::{ConfigError, KEYRING_SERVICE};
+
+/// Prefix for values encrypted with age, followed by the base64 ciphertext.
+pub const AGE_PREFIX: &str = "enc:age:";
+
+/// Prefix for values encrypted with GPG, followed by the base64 ciphertext.
+pub const GPG_PREFIX: &str = "enc:gpg:";
+
+/// Environment variable that can supply the config passphrase in place of
+/// the keyring (e.g. in CI).
+pub const PASSPHRASE_ENV: &str = "MCP_RS_CONFIG_PASSPHRASE";
+
+const PASSPHRASE_ACCOUNT: &str = "config-passphrase";
+
+/// scrypt work factor for passphrase-encrypted values. Lower than age's
+/// interactive default since every value is decrypted on each startup.
+const SCRYPT_WORK_FACTOR: u8 = 15;
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(AGE_PREFIX) || value.starts_with(GPG_PREFIX)
+}
+
+/// How `config encrypt` should protect plaintext values.
+pub enum EncryptionTarget {
+    /// age X25519 recipients (`age1...`).
+    Recipients(Vec<String>),
+    /// age scrypt encryption with the keyring-held passphrase.
+    Passphrase,
+    /// A GPG key ID, encrypted with the local `gpg` binary.
+    Gpg(String),
+}
+
+/// Decrypts and encrypts config secrets. The passphrase is only looked up
+/// (and the keyring only touched) when a passphrase-encrypted value is seen.
+pub struct SecretCipher {
+    identity_file: Option<PathBuf>,
+    passphrase: OnceLock<Result<SecretString, String>>,
+}
+
+impl SecretCipher {
+    pub fn new(identity_file: Option<PathBuf>) -> Self {
+        Self {
+            identity_file: identity_file.map(|path| expand_home(&path)),
+            passphrase: OnceLock::new(),
+        }
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, ConfigError> {
+        if let Some(payload) = value.strip_prefix(AGE_PREFIX) {
+            self.decrypt_age(&decode(payload)?)
+        } else if let Some(payload) = value.strip_prefix(GPG_PREFIX) {
+            run_gpg(&["--batch", "--quiet", "--decrypt"], &decode(payload)?).and_then(into_string)
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    pub fn encrypt(&self, value: &str, target: &EncryptionTarget) -> Result<String, ConfigError> {
+        let ciphertext = match target {
+            EncryptionTarget::Recipients(recipients) => {
+                let recipients = if recipients.is_empty() {
+                    self.identity_recipients()?
+                } else {
+                    recipients
+                        .iter()
+                        .map(|r| {
+                            age::x25519::Recipient::from_str(r)
+                                .map(|r| Box::new(r) as Box<dyn age::Recipient + Send>)
+                                .map_err(|e| {
+                                    ConfigError::Secret(format!("Invalid recipient {}: {}", r, e))
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?
+                };
+                age_encrypt(
+                    recipients.iter().map(|r| r.as_ref() as &dyn age::Recipient),
+                    value,
+                )?
+            }
+            EncryptionTarget::Passphrase => {
+                let mut recipient = age::scrypt::Recipient::new(self.passphrase()?);
+                recipient.set_work_factor(SCRYPT_WORK_FACTOR);
+                age_encrypt(std::iter::once(&recipient as &dyn age::Recipient), value)?
+            }
+            EncryptionTarget::Gpg(key_id) => {
+                let ciphertext = run_gpg(
+                    &["--batch", "--yes", "--encrypt", "--recipient", key_id],
+                    value.as_bytes(),
+                )?;
+                return Ok(format!("{}{}", GPG_PREFIX, BASE64.encode(ciphertext)));
+            }
+        };
+
+        Ok(format!("{}{}", AGE_PREFIX, BASE64.encode(ciphertext)))
+    }
+
+    fn decrypt_age(&self, ciphertext: &[u8]) -> Result<String, ConfigError> {
+        let decryptor = age::Decryptor::new(ciphertext).map_err(age_error)?;
+
+        let mut reader = if decryptor.is_scrypt() {
+            let identity = age::scrypt::Identity::new(self.passphrase()?);
+            decryptor
+                .decrypt(std::iter::once(&identity as &dyn age::Identity))
+                .map_err(age_error)?
+        } else {
+            let identities = self.identities()?;
+            decryptor
+                .decrypt(identities.iter().map(|i| i.as_ref() as &dyn age::Identity))
+                .map_err(age_error)?
+        };
+
+        let mut plaintext = Vec::new();
+        reader
+            .read_to_end(&mut plaintext)
+            .map_err(|e| ConfigError::Secret(e.to_string()))?;
+        into_string(plaintext)
+    }
+
+    fn identity_file(&self) -> Result<age::IdentityFile<age::NoCallbacks>, ConfigError> {
+        let path = self.identity_file.as_ref().ok_or_else(|| {
+            ConfigError::Secret(
+                "No identity file configured (set encryption.identity_file)".to_string(),
+            )
+        })?;
+
+        age::IdentityFile::from_file(path.to_string_lossy().into_owned()).map_err(|e| {
+            ConfigError::Secret(format!(
+                "Failed to read identity file {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    fn identities(&self) -> Result<Vec<Box<dyn age::Identity>>, ConfigError> {
+        self.identity_file()?.into_identities().map_err(age_error)
+    }
+
+    fn identity_recipients(&self) -> Result<Vec<Box<dyn age::Recipient + Send>>, ConfigError> {
+        self.identity_file()?
+            .to_recipients()
+            .map_err(|e| ConfigError::Secret(e.to_string()))
+    }
+
+    fn passphrase(&self) -> Result<SecretString, ConfigError> {
+        self.passphrase
+            .get_or_init(load_passphrase)
+            .clone()
+            .map_err(ConfigError::Secret)
+    }
+}
+
+/// Stores the config passphrase in the OS keyring.
+pub fn store_passphrase(passphrase: &str) -> Result<(), ConfigError> {
+    keyring::Entry::new(KEYRING_SERVICE, PASSPHRASE_ACCOUNT)
+        .and_then(|entry| entry.set_password(passphrase))
+        .map_err(|e| ConfigError::Secret(format!("Failed to store passphrase: {}", e)))
+}
+
+fn load_passphrase() -> Result<SecretString, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(SecretString::from(passphrase));
+    }
+
+    keyring::Entry::new(KEYRING_SERVICE, PASSPHRASE_ACCOUNT)
+        .and_then(|entry| entry.get_password())
+        .map(SecretString::from)
+        .map_err(|e| {
+            format!(
+                "No config passphrase available (set {} or run `config passphrase`): {}",
+                PASSPHRASE_ENV, e
+            )
+        })
+}
+
+fn age_encrypt<'a>(
+    recipients: impl Iterator<Item = &'a dyn age::Recipient>,
+    value: &str,
+) -> Result<Vec<u8>, ConfigError> {
+    let encryptor = age::Encryptor::with_recipients(recipients)
+        .map_err(|e| ConfigError::Secret(e.to_string()))?;
+
+    let mut ciphertext = Vec::new();
+    let mut writer = encryptor
+        .wrap_output(&mut ciphertext)
+        .map_err(|e| ConfigError::Secret(e.to_string()))?;
+    writer
+        .write_all(value.as_bytes())
+        .and_then(|_| writer.finish())
+        .map_err(|e| ConfigError::Secret(e.to_string()))?;
+
+    Ok(ciphertext)
+}
+
+fn run_gpg(args: &[&str], input: &[u8]) -> Result<Vec<u8>, ConfigError> {
+    let mut child = Command::new("gpg")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| ConfigError::Secret(format!("Failed to run gpg: {}", e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .map_err(|e| ConfigError::Secret(format!("Failed to write to gpg: {}", e)))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| ConfigError::Secret(format!("Failed to run gpg: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(ConfigError::Secret(format!(
+            "gpg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+fn decode(payload: &str) -> Result<Vec<u8>, ConfigError> {
+    BASE64
+        .decode(payload.trim())
+        .map_err(|e| ConfigError::Secret(format!("Invalid encrypted value: {}", e)))
+}
+
+fn into_string(plaintext: Vec<u8>) -> Result<String, ConfigError> {
+    String::from_utf8(plaintext)
+        .map_err(|_| ConfigError::Secret("Decrypted value is not valid UTF-8".to_string()))
+}
+
+fn age_error(e: age::DecryptError) -> ConfigError {
+    ConfigError::Secret(format!("age decryption failed: {}", e))
+}
+
+/// Expands a leading `~/` so paths in the config file can be home-relative.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the systemd unit and the Windows scheduled task.
+const SERVICE_NAME: &str = "mcp-rs";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DaemonError {
+    #[error("Failed to {action}: {message}")]
+    Io { action: String, message: String },
+
+    #[error("{command} failed: {message}")]
+    Command { command: String, message: String },
+
+    #[error("Installing a daemon isn't supported on this platform")]
+    Unsupported,
+}
+
+/// What the daemon runs: `mcp-rs serve` with `args`, reading the config
+/// file at `config`.
+#[derive(Debug, Clone)]
+pub struct DaemonSpec {
+    pub executable: PathBuf,
+    pub config: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl DaemonSpec {
+    /// The daemon's command line, the executable first.
+    pub fn command_line(&self) -> Vec<String> {
+        let mut line = vec![
+            self.executable.display().to_string(),
+            "--config".to_string(),
+            self.config.display().to_string(),
+            "serve".to_string(),
+        ];
+        line.extend(self.args.iter().cloned());
+        line
+    }
+
+    /// The systemd user unit running the daemon. It runs in the user's
+    /// session bus, where the Secret Service keyring lives, and restarts
+    /// after failures.
+    pub fn systemd_unit(&self) -> String {
+        let exec_start: Vec<String> = self
+            .command_line()
+            .iter()
+            .map(|arg| systemd_quote(arg))
+            .collect();
+        format!(
+            "[Unit]\n\
+             Description=mcp-rs MCP server\n\
+             \n\
+             [Service]\n\
+             ExecStart={}\n\
+             Environment=DBUS_SESSION_BUS_ADDRESS=unix:path=%t/bus\n\
+             Restart=on-failure\n\
+             RestartSec=5\n\
+             \n\
+             [Install]\n\
+             WantedBy=default.target\n",
+            exec_start.join(" ")
+        )
+    }
+}
+
+/// Where the systemd user unit is installed.
+pub fn systemd_unit_path() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join("systemd")
+            .join("user")
+            .join(format!("{}.service", SERVICE_NAME)),
+    )
+}
+
+/// Registers the daemon to start at login, and starts it: as a systemd
+/// user service on Linux, and as a scheduled task run at logon on Windows.
+/// A task rather than a Windows service, since services run outside the
+/// user's logon session and can't read keys from their Credential Manager.
+/// Returns a description of what was installed.
+pub fn install(spec: &DaemonSpec) -> Result<String, DaemonError> {
+    if cfg!(target_os = "linux") {
+        let path = systemd_unit_path().ok_or(DaemonError::Unsupported)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| io_error("create", dir, e))?;
+        }
+        std::fs::write(&path, spec.systemd_unit()).map_err(|e| io_error("write", &path, e))?;
+        let unit = format!("{}.service", SERVICE_NAME);
+        let started = run("systemctl", &["--user", "daemon-reload"])
+            .and_then(|()| run("systemctl", &["--user", "enable", "--now", &unit]));
+        if let Err(e) = started {
+            // A unit systemd never took would only confuse `uninstall`.
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+        Ok(format!("systemd user service {}", path.display()))
+    } else if cfg!(windows) {
+        let command_line: Vec<String> = spec
+            .command_line()
+            .iter()
+            .map(|arg| windows_quote(arg))
+            .collect();
+        run(
+            "schtasks",
+            &[
+                "/Create",
+                "/TN",
+                SERVICE_NAME,
+                "/TR",
+                &command_line.join(" "),
+                "/SC",
+                "ONLOGON",
+                "/RL",
+                "LIMITED",
+                "/F",
+            ],
+        )?;
+        run("schtasks", &["/Run", "/TN", SERVICE_NAME])?;
+        Ok(format!("scheduled task {}", SERVICE_NAME))
+    } else {
+        Err(DaemonError::Unsupported)
+    }
+}
+
+/// Stops the daemon and removes what `install` registered. Returns false
+/// if nothing was installed.
+pub fn uninstall() -> Result<bool, DaemonError> {
+    if cfg!(target_os = "linux") {
+        let path = systemd_unit_path().ok_or(DaemonError::Unsupported)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        run(
+            "systemctl",
+            &[
+                "--user",
+                "disable",
+                "--now",
+                &format!("{}.service", SERVICE_NAME),
+            ],
+        )?;
+        std::fs::remove_file(&path).map_err(|e| io_error("remove", &path, e))?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+        Ok(true)
+    } else if cfg!(windows) {
+        if run("schtasks", &["/Query", "/TN", SERVICE_NAME]).is_err() {
+            return Ok(false);
+        }
+        // Not running is fine.
+        let _ = run("schtasks", &["/End", "/TN", SERVICE_NAME]);
+        run("schtasks", &["/Delete", "/TN", SERVICE_NAME, "/F"])?;
+        Ok(true)
+    } else {
+        Err(DaemonError::Unsupported)
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), DaemonError> {
+    let command = format!("{} {}", program, args.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| DaemonError::Command {
+            command: command.clone(),
+            message: e.to_string(),
+        })?;
+    if !output.status.success() {
+        return Err(DaemonError::Command {
+            command,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn io_error(action: &str, path: &Path, error: std::io::Error) -> DaemonError {
+    DaemonError::Io {
+        action: format!("{} {}", action, path.display()),
+        message: error.to_string(),
+    }
+}
+
+/// `arg` quoted for an `ExecStart=` line, where `%` starts a specifier and
+/// `$` a variable.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
+
+/// `arg` quoted for a Windows command line, if it needs to be.
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
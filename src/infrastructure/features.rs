@@ -0,0 +1,79 @@
+use std::sync::LazyLock;
+
+use super::cli::{Commands, Transport};
+
+/// A part of mcp-rs that builds can leave out, named after the cargo
+/// feature that compiles it in.
+pub struct Feature {
+    pub name: &'static str,
+    /// What it provides, e.g. for `--version`.
+    pub provides: &'static str,
+    pub enabled: bool,
+}
+
+/// The optional parts, all of which default builds include. Minimal builds
+/// such as `cargo build --no-default-features` leave them out and say so
+/// when they're asked for.
+pub const FEATURES: [Feature; 4] = [
+    Feature {
+        name: "http-server",
+        provides: "serving MCP over HTTP, SSE and WebSocket",
+        enabled: cfg!(feature = "http-server"),
+    },
+    Feature {
+        name: "tui",
+        provides: "the interactive picker of `mcp-rs pick`",
+        enabled: cfg!(feature = "tui"),
+    },
+    Feature {
+        name: "scripting",
+        provides: "Rhai result hooks",
+        enabled: cfg!(feature = "scripting"),
+    },
+    Feature {
+        name: "sql",
+        provides: "providers serving rows of SQL databases",
+        enabled: cfg!(feature = "sql"),
+    },
+];
+
+/// The `--version` text, listing which optional parts this build has.
+pub static LONG_VERSION: LazyLock<String> = LazyLock::new(|| {
+    let mut version = env!("CARGO_PKG_VERSION").to_string();
+    for feature in &FEATURES {
+        let mark = if feature.enabled { '+' } else { '-' };
+        version.push_str(&format!(
+            "\n{}{} ({})",
+            mark, feature.name, feature.provides
+        ));
+    }
+    version
+});
+
+/// Why `name` can't be used in this build, and how to get a build that has
+/// it.
+pub fn missing(name: &str) -> String {
+    let provides = FEATURES
+        .iter()
+        .find(|feature| feature.name == name)
+        .map_or(name, |feature| feature.provides);
+    format!(
+        "mcp-rs was compiled without the {} feature, needed for {}; \
+         reinstall with `cargo install mcp-rs --features {}`",
+        name, provides, name
+    )
+}
+
+/// Checks that this build has what `command` needs before anything starts,
+/// so a minimal build fails up front rather than after connecting.
+pub fn check(command: &Commands) -> Result<(), String> {
+    let needed = match command {
+        Commands::Pick { .. } => Some("tui"),
+        Commands::Serve { transport, .. } if *transport != Transport::Stdio => Some("http-server"),
+        _ => None,
+    };
+    match needed {
+        Some(name) if !FEATURES.iter().any(|f| f.name == name && f.enabled) => Err(missing(name)),
+        _ => Ok(()),
+    }
+}
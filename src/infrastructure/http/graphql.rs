@@ -0,0 +1,69 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{request_id, HttpClient};
+use crate::domain::DomainError;
+
+#[derive(Debug, Serialize)]
+struct GraphQLRequest<'a> {
+    query: &'a str,
+    variables: Option<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    errors: Option<Vec<GraphQLError>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLError {
+    message: String,
+}
+
+impl HttpClient {
+    /// Runs a GraphQL query against `url`, returning its data and the
+    /// provider's ID for the request. A response with errors fails, even
+    /// if it has some data too.
+    pub async fn graphql<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &str,
+        variables: Option<HashMap<String, serde_json::Value>>,
+    ) -> Result<(T, Option<String>), DomainError> {
+        let request = GraphQLRequest { query, variables };
+
+        let response = self.send(self.post(url).json(&request)).await?;
+        let request_id = request_id(&response);
+
+        if !response.status().is_success() {
+            let error_text = response
+                .text()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+            return Err(DomainError::ProviderError(format!(
+                "{} API error: {}",
+                self.provider, error_text
+            )));
+        }
+
+        let graphql_response: GraphQLResponse<T> = response
+            .json()
+            .await
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+
+        if let Some(errors) = graphql_response.errors {
+            let error_messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+            return Err(DomainError::ProviderError(format!(
+                "GraphQL errors: {}",
+                error_messages.join(", ")
+            )));
+        }
+
+        let data = graphql_response
+            .data
+            .ok_or_else(|| DomainError::ProviderError("No data in response".to_string()))?;
+        Ok((data, request_id))
+    }
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE, USER_AGENT};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use super::{HttpClient, RetryPolicy};
+use crate::{domain::DomainError, ports::LinkResolver};
+
+/// How long one page gets to answer.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bytes of a page read looking for its title, which belongs in `<head>`.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+static TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+/// Looks up a page's title in its HTML `<title>`. Links are looked up
+/// without credentials, so private pages only give their login page's
+/// title, if any.
+pub struct WebLinkResolver {
+    http: HttpClient,
+}
+
+impl WebLinkResolver {
+    pub fn new() -> Result<Self, DomainError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("text/html"));
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static(concat!("mcp-rs/", env!("CARGO_PKG_VERSION"))),
+        );
+        // A page that fails is left untitled; retrying only slows down the
+        // resource it's linked from.
+        let retry = RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        };
+        Ok(Self {
+            http: HttpClient::new("links", headers, Vec::new())?.with_retry_policy(retry),
+        })
+    }
+}
+
+#[async_trait]
+impl LinkResolver for WebLinkResolver {
+    async fn title(&self, url: &str) -> Result<Option<String>, DomainError> {
+        let request = self.http.get(url).timeout(LOOKUP_TIMEOUT);
+        let mut response = self.http.send(request).await?;
+        let html = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if !response.status().is_success() || !html {
+            return Ok(None);
+        }
+
+        let mut head = Vec::new();
+        while head.len() < MAX_HEAD_BYTES {
+            match response
+                .chunk()
+                .await
+                .map_err(|e| DomainError::ProviderError(e.to_string()))?
+            {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+
+        let head = String::from_utf8_lossy(&head);
+        Ok(TITLE
+            .captures(&head)
+            .map(|captures| decode_entities(&captures[1]))
+            .map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|title| !title.is_empty()))
+    }
+}
+
+/// Decodes the HTML entities titles commonly contain.
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
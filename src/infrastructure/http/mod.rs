@@ -0,0 +1,512 @@
+pub mod graphql;
+pub mod links;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Method, Request, RequestBuilder, Response, StatusCode};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+use crate::{
+    domain::{Blob, DomainError},
+    infrastructure::{config::RetryConfig, sandbox},
+    ports::{cancellable, charge_request},
+};
+
+/// Tracing target for HTTP request/response logs. Enabled at `debug` for
+/// request lines and at `trace` for bodies (see `--trace-http`).
+pub const TRACE_TARGET: &str = "mcp_rs::http";
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Redirects followed per request, as many as reqwest follows by default.
+const MAX_REDIRECTS: usize = 10;
+
+/// Characters of a refusal's body quoted in the error.
+const MAX_ERROR_CHARS: usize = 300;
+
+/// Largest file `download` fetches, so one attachment can't exhaust memory.
+const MAX_DOWNLOAD_BYTES: usize = 20 * 1024 * 1024;
+
+/// MIME types of common file extensions, for files served as plain bytes.
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("csv", "text/csv"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("gif", "image/gif"),
+    ("html", "text/html"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("json", "application/json"),
+    ("md", "text/markdown"),
+    ("mov", "video/quicktime"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("svg", "image/svg+xml"),
+    ("txt", "text/plain"),
+    ("wav", "audio/wav"),
+    ("webp", "image/webp"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("zip", "application/zip"),
+];
+
+/// How failed requests are retried. Each adapter supplies defaults suited to
+/// its API's throttling, which `[providers.<name>.retry]` can override.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_on: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            retry_on: vec![429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_overrides(mut self, overrides: &RetryConfig) -> Self {
+        if let Some(max_retries) = overrides.max_retries {
+            self.max_retries = max_retries;
+        }
+        if let Some(base_delay_ms) = overrides.base_delay_ms {
+            self.base_delay = Duration::from_millis(base_delay_ms);
+        }
+        if let Some(max_delay_ms) = overrides.max_delay_ms {
+            self.max_delay = Duration::from_millis(max_delay_ms);
+        }
+        if let Some(retry_on) = &overrides.retry_on {
+            self.retry_on = retry_on.clone();
+        }
+        self
+    }
+
+    fn is_retryable(&self, status: StatusCode) -> bool {
+        self.retry_on.contains(&status.as_u16())
+    }
+
+    /// Exponential backoff for the given retry attempt (0-based), capped at
+    /// `max_delay`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+}
+
+/// Shared HTTP client used by the API adapters. Wraps `reqwest::Client` so
+/// cross-cutting concerns like debug tracing and retries live in one place.
+pub struct HttpClient {
+    client: reqwest::Client,
+    provider: &'static str,
+    default_headers: HeaderMap,
+    secrets: Vec<String>,
+    retry: RetryPolicy,
+}
+
+impl HttpClient {
+    /// `secrets` are scrubbed from any URL or body that gets logged.
+    pub fn new(
+        provider: &'static str,
+        default_headers: HeaderMap,
+        secrets: Vec<String>,
+    ) -> Result<Self, DomainError> {
+        // Redirects are checked against the sandbox like the requests
+        // themselves.
+        let redirect = reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(e) = sandbox::check_url(attempt.url()) {
+                attempt.error(e)
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .default_headers(default_headers.clone())
+            .redirect(redirect)
+            .build()
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+
+        Ok(Self {
+            client,
+            provider,
+            default_headers,
+            secrets: secrets.into_iter().filter(|s| !s.is_empty()).collect(),
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.request(Method::POST, url)
+    }
+
+    pub fn patch(&self, url: &str) -> RequestBuilder {
+        self.client.request(Method::PATCH, url)
+    }
+
+    pub fn delete(&self, url: &str) -> RequestBuilder {
+        self.client.request(Method::DELETE, url)
+    }
+
+    /// Downloads the file `name` at `url`, taking its MIME type from the
+    /// response, or from the name when the response only says it's bytes.
+    pub async fn download(&self, url: &str, name: &str) -> Result<Blob, DomainError> {
+        let mut response = self.send(self.get(url)).await?;
+        if !response.status().is_success() {
+            return Err(DomainError::ProviderError(format!(
+                "{} answered {} for {}",
+                self.provider,
+                response.status(),
+                name
+            )));
+        }
+        let too_large = || {
+            DomainError::InvalidQuery(format!(
+                "{} is larger than {} MB",
+                name,
+                MAX_DOWNLOAD_BYTES / (1024 * 1024)
+            ))
+        };
+        if response
+            .content_length()
+            .is_some_and(|length| length > MAX_DOWNLOAD_BYTES as u64)
+        {
+            return Err(too_large());
+        }
+
+        let mime_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && !value.ends_with("/octet-stream"))
+            .map_or_else(|| guess_mime_type(name), str::to_string);
+
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| self.unavailable(e))? {
+            data.extend_from_slice(&chunk);
+            if data.len() > MAX_DOWNLOAD_BYTES {
+                return Err(too_large());
+            }
+        }
+        Ok(Blob { mime_type, data })
+    }
+
+    /// Sends a request, retrying throttled and transient failures according
+    /// to the retry policy. Requests with streaming bodies are sent once.
+    /// Cancelling the surrounding `ports::with_cancellation` scope aborts it.
+    /// Throttling that outlasts the retries, refused credentials and server
+    /// errors fail as `RateLimited`, `Unauthorized` and `Unavailable`; other
+    /// responses are returned whatever their status.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, DomainError> {
+        let response = cancellable(self.send_with_retries(request)).await?;
+        self.check_access(response).await
+    }
+
+    async fn check_access(&self, response: Response) -> Result<Response, DomainError> {
+        match response.status() {
+            StatusCode::TOO_MANY_REQUESTS => Err(DomainError::RateLimited {
+                provider: self.provider.to_lowercase(),
+                retry_after_secs: retry_after(&response)
+                    .map(|delay| delay.min(self.retry.max_delay))
+                    .map(|delay| delay.as_secs_f64().ceil() as u64),
+            }),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                let status = response.status();
+                let missing_scope = missing_scope(&response);
+                let body = response.text().await.unwrap_or_default();
+                let body = body.trim();
+                let message = if body.is_empty() {
+                    status.to_string()
+                } else {
+                    self.redact(&body.chars().take(MAX_ERROR_CHARS).collect::<String>())
+                };
+                Err(DomainError::Unauthorized {
+                    provider: self.provider.to_lowercase(),
+                    missing_scope,
+                    message,
+                })
+            }
+            status if status.is_server_error() => {
+                let body = response.text().await.unwrap_or_default();
+                let body = body.trim();
+                Err(self.unavailable(if body.is_empty() {
+                    status.to_string()
+                } else {
+                    format!(
+                        "{}: {}",
+                        status,
+                        self.redact(&body.chars().take(MAX_ERROR_CHARS).collect::<String>())
+                    )
+                }))
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// A failure reaching the provider, or on its side, which may pass.
+    fn unavailable(&self, error: impl std::fmt::Display) -> DomainError {
+        DomainError::Unavailable {
+            provider: self.provider.to_lowercase(),
+            message: self.redact(&error.to_string()),
+        }
+    }
+
+    async fn send_with_retries(&self, request: RequestBuilder) -> Result<Response, DomainError> {
+        let request = request
+            .build()
+            .map_err(|e| DomainError::ProviderError(e.to_string()))?;
+        sandbox::check_url(request.url())?;
+
+        let mut attempt = 0;
+        loop {
+            charge_request(self.provider)?;
+            let retry_request = if attempt < self.retry.max_retries {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            let Some(next_request) = retry_request else {
+                return self.execute(request).await.map_err(|e| self.unavailable(e));
+            };
+
+            let (delay, throttled) = match self.execute(next_request).await {
+                Ok(response) if self.retry.is_retryable(response.status()) => (
+                    retry_after(&response)
+                        .map(|delay| delay.min(self.retry.max_delay))
+                        .unwrap_or_else(|| self.retry.backoff(attempt)),
+                    response.status() == StatusCode::TOO_MANY_REQUESTS,
+                ),
+                Err(e) if e.is_timeout() || e.is_connect() => (self.retry.backoff(attempt), false),
+                result => return result.map_err(|e| self.unavailable(e)),
+            };
+
+            attempt += 1;
+            // Rate limiting is worth surfacing (e.g. to MCP clients), as it
+            // slows everything down until the quota recovers.
+            if throttled {
+                tracing::warn!(
+                    "{} rate limited requests to {}, retrying in {}ms (attempt {}/{})",
+                    self.provider,
+                    self.redact(request.url().as_str()),
+                    delay.as_millis(),
+                    attempt,
+                    self.retry.max_retries
+                );
+            } else {
+                tracing::debug!(
+                    "{} request to {} failed, retrying in {}ms (attempt {}/{})",
+                    self.provider,
+                    self.redact(request.url().as_str()),
+                    delay.as_millis(),
+                    attempt,
+                    self.retry.max_retries
+                );
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn execute(&self, request: Request) -> Result<Response, reqwest::Error> {
+        if !tracing::enabled!(target: TRACE_TARGET, Level::DEBUG) {
+            return self.client.execute(request).await;
+        }
+
+        let method = request.method().clone();
+        let url = self.redact(request.url().as_str());
+
+        let mut headers = self.default_headers.clone();
+        headers.extend(request.headers().clone());
+        tracing::debug!(
+            target: TRACE_TARGET,
+            provider = self.provider,
+            "--> {} {} {}",
+            method,
+            url,
+            self.format_headers(&headers)
+        );
+
+        if tracing::enabled!(target: TRACE_TARGET, Level::TRACE) {
+            if let Some(body) = request.body().and_then(|b| b.as_bytes()) {
+                tracing::trace!(
+                    target: TRACE_TARGET,
+                    provider = self.provider,
+                    "--> body: {}",
+                    self.redact(&String::from_utf8_lossy(body))
+                );
+            }
+        }
+
+        let started = Instant::now();
+        let result = self.client.execute(request).await;
+        let elapsed = started.elapsed();
+
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::debug!(
+                    target: TRACE_TARGET,
+                    provider = self.provider,
+                    "<-- {} {} failed after {}ms: {}",
+                    method,
+                    url,
+                    elapsed.as_millis(),
+                    self.redact(&e.to_string())
+                );
+                return Err(e);
+            }
+        };
+
+        tracing::debug!(
+            target: TRACE_TARGET,
+            provider = self.provider,
+            "<-- {} {} {} ({}ms) {}",
+            response.status(),
+            method,
+            url,
+            elapsed.as_millis(),
+            self.format_headers(response.headers())
+        );
+
+        if !tracing::enabled!(target: TRACE_TARGET, Level::TRACE) {
+            return Ok(response);
+        }
+
+        // Reading the body consumes the response, so rebuild one from the
+        // buffered bytes for the caller.
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+
+        tracing::trace!(
+            target: TRACE_TARGET,
+            provider = self.provider,
+            "<-- body: {}",
+            self.redact(&String::from_utf8_lossy(&body))
+        );
+
+        let mut rebuilt = http::Response::new(body.to_vec());
+        *rebuilt.status_mut() = status;
+        *rebuilt.version_mut() = version;
+        *rebuilt.headers_mut() = headers;
+
+        Ok(Response::from(rebuilt))
+    }
+
+    fn format_headers(&self, headers: &HeaderMap) -> String {
+        let formatted: Vec<String> = headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, self.redact_header(name, value)))
+            .collect();
+        format!("[{}]", formatted.join(", "))
+    }
+
+    fn redact_header(&self, name: &HeaderName, value: &HeaderValue) -> String {
+        let name = name.as_str();
+        if is_sensitive_header(name) {
+            return REDACTED.to_string();
+        }
+        self.redact(&String::from_utf8_lossy(value.as_bytes()))
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+    }
+}
+
+/// The provider's ID for a request, if it sent one, for citing where a
+/// resource came from and for support requests.
+pub fn request_id(response: &Response) -> Option<String> {
+    ["x-request-id", "request-id", "activityid"]
+        .iter()
+        .find_map(|name| response.headers().get(*name))
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// MIME type of a file named `name`, by its extension.
+pub fn guess_mime_type(name: &str) -> String {
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    MIME_TYPES
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map_or("application/octet-stream", |(_, mime_type)| mime_type)
+        .to_string()
+}
+
+/// Delay requested by a `Retry-After` header, in seconds.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+/// The scope a refused request needed: from an RFC 6750
+/// `WWW-Authenticate: Bearer error="insufficient_scope", scope="..."`, or
+/// GitHub's `X-Accepted-OAuth-Scopes`.
+fn missing_scope(response: &Response) -> Option<String> {
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    if let Some(challenge) = header("www-authenticate").filter(|c| c.contains("insufficient_scope"))
+    {
+        let scope = challenge.split_once("scope=\"").map(|(_, rest)| rest);
+        if let Some((scope, _)) = scope.and_then(|scope| scope.split_once('"')) {
+            return Some(scope.to_string());
+        }
+    }
+    header("x-accepted-oauth-scopes").map(str::to_string)
+}
+
+fn is_sensitive_header(name: &str) -> bool {
+    matches!(
+        name,
+        "authorization" | "proxy-authorization" | "cookie" | "set-cookie"
+    ) || ["key", "token", "secret"]
+        .iter()
+        .any(|needle| name.contains(needle))
+}
@@ -0,0 +1,222 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::policy::ClientPolicy;
+use super::prompts::Prompts;
+use super::protocol::{parse_params, RpcError};
+use super::templates;
+use super::tools;
+use crate::{application::ResourceService, domain::DiscoveredItem};
+
+/// How long discovered databases and projects are reused for, so typing
+/// doesn't call the provider on every keystroke.
+const DISCOVERY_TTL: Duration = Duration::from_secs(300);
+
+/// Most values returned per completion, as the protocol allows.
+const MAX_VALUES: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct CompleteParams {
+    #[serde(rename = "ref")]
+    reference: Reference,
+    argument: Argument,
+    #[serde(default)]
+    context: Context,
+}
+
+/// Arguments the client has already filled in.
+#[derive(Debug, Default, Deserialize)]
+struct Context {
+    #[serde(default)]
+    arguments: HashMap<String, String>,
+}
+
+/// What is being filled in: a prompt's arguments, a resource template's,
+/// or (beyond the protocol) a tool's.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Reference {
+    #[serde(rename = "ref/prompt")]
+    Prompt { name: String },
+    #[serde(rename = "ref/resource")]
+    Resource { uri: String },
+    #[serde(rename = "ref/tool")]
+    Tool { name: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct Argument {
+    name: String,
+    #[serde(default)]
+    value: String,
+}
+
+/// Answers `completion/complete` for arguments whose name says what they
+/// hold: providers (`source`, `sources`, `provider`), Notion databases
+/// (`database`, `database_id`), Linear projects (`project`), Linear team
+/// keys (`team`, `team_key`), and cached resources: Notion pages
+/// (`page_id`), Linear issues (`issue_id`), and the `id` of a resource of
+/// the `provider` given in the context.
+pub struct Completer {
+    service: Arc<ResourceService>,
+    discovered: Mutex<HashMap<String, (Instant, Vec<DiscoveredItem>)>>,
+}
+
+impl Completer {
+    pub fn new(service: Arc<ResourceService>) -> Self {
+        Self {
+            service,
+            discovered: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn complete(
+        &self,
+        prompts: &Prompts,
+        policy: &ClientPolicy,
+        writes: bool,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        let params: CompleteParams = parse_params(params)?;
+        let known = match &params.reference {
+            Reference::Prompt { name } => prompts.contains(name),
+            Reference::Tool { name } => tools::exists(policy, writes, name),
+            Reference::Resource { uri } => templates::exists(uri),
+        };
+
+        let mut values = if known {
+            self.candidates(policy, &params.argument, &params.context.arguments)
+                .await
+        } else {
+            Vec::new()
+        };
+        values.sort();
+        values.dedup();
+        let total = values.len();
+        values.truncate(MAX_VALUES);
+
+        Ok(json!({
+            "completion": {
+                "values": values,
+                "total": total,
+                "hasMore": total > MAX_VALUES,
+            },
+        }))
+    }
+
+    async fn candidates(
+        &self,
+        policy: &ClientPolicy,
+        argument: &Argument,
+        context: &HashMap<String, String>,
+    ) -> Vec<String> {
+        let typed = argument.value.to_lowercase();
+        let matches = |text: &str| text.to_lowercase().contains(&typed);
+
+        match argument.name.to_lowercase().as_str() {
+            "source" | "sources" | "provider" => self
+                .service
+                .list_providers()
+                .into_iter()
+                .filter(|provider| policy.allows_provider(provider))
+                .map(str::to_lowercase)
+                .filter(|provider| provider.starts_with(&typed))
+                .collect(),
+            "database" | "database_id" if policy.allows_provider("notion") => self
+                .discover("notion")
+                .await
+                .into_iter()
+                .filter(|item| item.kind == "database")
+                .filter(|item| item.id.starts_with(&typed) || matches(&item.title))
+                .map(|item| item.id)
+                .collect(),
+            "project" if policy.allows_provider("linear") => self
+                .discover("linear")
+                .await
+                .into_iter()
+                .filter(|item| item.kind == "project" && matches(&item.title))
+                .map(|item| item.title)
+                .collect(),
+            "team" | "team_key" if policy.allows_provider("linear") => self
+                .discover("linear")
+                .await
+                .into_iter()
+                .filter(|item| item.kind == "team" && matches(&item.title))
+                .filter_map(|item| team_key(&item.title))
+                .collect(),
+            "page_id" if policy.allows_provider("notion") => {
+                self.cached_ids(policy, "notion", &typed).await
+            }
+            // Issues by identifier, such as ENG-123, which reads better than
+            // their IDs and works as well.
+            "issue_id" if policy.allows_provider("linear") => self
+                .service
+                .cached_resources("linear")
+                .await
+                .into_iter()
+                .filter(|resource| policy.allows_resource(resource))
+                .filter_map(|resource| resource.short_id().map(str::to_string))
+                .filter(|identifier| identifier.to_lowercase().starts_with(&typed))
+                .collect(),
+            "id" => match context.get("provider") {
+                Some(provider) if policy.allows_provider(provider) => {
+                    self.cached_ids(policy, &provider.to_lowercase(), &typed)
+                        .await
+                }
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// The provider's own IDs of `provider`'s cached resources, without the
+    /// `<provider>_` prefix, matching `typed` at the start or in the title.
+    async fn cached_ids(&self, policy: &ClientPolicy, provider: &str, typed: &str) -> Vec<String> {
+        let prefix = format!("{}_", provider);
+        self.service
+            .cached_resources(provider)
+            .await
+            .into_iter()
+            .filter(|resource| policy.allows_resource(resource))
+            .filter_map(|resource| {
+                let id = resource.id.strip_prefix(&prefix)?;
+                (id.to_lowercase().starts_with(typed)
+                    || resource.title.to_lowercase().contains(typed))
+                .then(|| id.to_string())
+            })
+            .collect()
+    }
+
+    /// What `provider` can see, from a recent discovery if there was one.
+    /// Failures complete nothing.
+    async fn discover(&self, provider: &str) -> Vec<DiscoveredItem> {
+        if let Some((discovered_at, items)) = self.discovered.lock().unwrap().get(provider) {
+            if discovered_at.elapsed() < DISCOVERY_TTL {
+                return items.clone();
+            }
+        }
+
+        match self.service.discover(provider).await {
+            Ok(items) => {
+                self.discovered
+                    .lock()
+                    .unwrap()
+                    .insert(provider.to_string(), (Instant::now(), items.clone()));
+                items
+            }
+            Err(e) => {
+                tracing::debug!("No completions from {}: {}", provider, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// The key of a team discovered as `Name (KEY)`.
+fn team_key(title: &str) -> Option<String> {
+    let (_, key) = title.rsplit_once(" (")?;
+    key.strip_suffix(')').map(str::to_string)
+}
@@ -0,0 +1,42 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::protocol::RpcError;
+
+/// Where `resources/list` left off: the provider being listed and that
+/// provider's own cursor, or `None` to start at its first page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListCursor {
+    pub provider: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl ListCursor {
+    pub fn start(provider: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            cursor: None,
+        }
+    }
+}
+
+/// Position in a tool's results: how many were already returned.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct OffsetCursor {
+    pub offset: usize,
+}
+
+/// A position as sent to clients, base64-encoded so they treat it as
+/// opaque.
+pub fn encode(position: &impl Serialize) -> String {
+    BASE64.encode(serde_json::to_vec(position).unwrap_or_default())
+}
+
+pub fn decode<T: DeserializeOwned>(cursor: &str) -> Result<T, RpcError> {
+    BASE64
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| RpcError::invalid_params(format!("Invalid cursor: {}", cursor)))
+}
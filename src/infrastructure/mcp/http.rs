@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Header carrying the streamable HTTP transport's session ID.
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// The token of an `Authorization: Bearer <token>` header, which picks the
+/// client's policy.
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+/// Browser origins allowed to call the server. Requests from other origins
+/// are refused, so web pages can't reach a local server through the
+/// visitor's browser (including via DNS rebinding). Requests without an
+/// `Origin` header don't come from a browser and are always allowed.
+#[derive(Debug, Clone, Default)]
+pub struct OriginPolicy {
+    /// Origins allowed besides localhost, e.g. `https://app.example.com`;
+    /// `*` allows any.
+    pub allowed: Vec<String>,
+}
+
+impl OriginPolicy {
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        if self
+            .allowed
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.trim_end_matches('/') == origin)
+        {
+            return true;
+        }
+
+        origin
+            .parse::<Uri>()
+            .ok()
+            .and_then(|uri| uri.host().map(|host| host.to_string()))
+            .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"))
+    }
+}
+
+/// Serves an MCP router on `addr`, enforcing `origins`.
+pub async fn serve(router: Router, addr: SocketAddr, origins: OriginPolicy) -> std::io::Result<()> {
+    let origins = Arc::new(origins);
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate({
+            let origins = origins.clone();
+            move |origin, _| origins.allows(origin)
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::HeaderName::from_static(SESSION_HEADER),
+            header::HeaderName::from_static("mcp-protocol-version"),
+            header::HeaderName::from_static("last-event-id"),
+        ])
+        .expose_headers([header::HeaderName::from_static(SESSION_HEADER)]);
+
+    let app = router
+        .layer(middleware::from_fn_with_state(origins, check_origin))
+        .layer(cors);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Serving MCP over HTTP on http://{}", listener.local_addr()?);
+    axum::serve(listener, app).await
+}
+
+async fn check_origin(
+    State(origins): State<Arc<OriginPolicy>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match request.headers().get(header::ORIGIN) {
+        Some(origin) if !origins.allows(origin) => {
+            tracing::warn!("Refused request from origin {:?}", origin);
+            (StatusCode::FORBIDDEN, "Origin not allowed").into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
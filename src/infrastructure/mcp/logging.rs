@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Log messages buffered for each session; sessions that fall further
+/// behind miss the oldest.
+const MESSAGE_CAPACITY: usize = 256;
+
+/// Severity of an MCP log message, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::TRACE | Level::DEBUG => LogLevel::Debug,
+            Level::INFO => LogLevel::Info,
+            Level::WARN => LogLevel::Warning,
+            Level::ERROR => LogLevel::Error,
+        }
+    }
+}
+
+/// One `tracing` event, as sent in `notifications/message`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogMessage {
+    pub level: LogLevel,
+    /// The event's target, e.g. `mcp_rs::infrastructure::http`.
+    pub logger: String,
+    pub data: String,
+}
+
+/// A `tracing` layer that passes events to MCP sessions, which forward
+/// those at or above the level their client set. Events the global filter
+/// drops never get here.
+#[derive(Clone)]
+pub struct LogForwarder {
+    messages: broadcast::Sender<LogMessage>,
+}
+
+impl LogForwarder {
+    pub fn new() -> Self {
+        let (messages, _) = broadcast::channel(MESSAGE_CAPACITY);
+        Self { messages }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LogMessage> {
+        self.messages.subscribe()
+    }
+}
+
+impl Default for LogForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogForwarder {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Formatting is wasted when no session is connected.
+        if self.messages.receiver_count() == 0 {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let _ = self.messages.send(LogMessage {
+            level: (*event.metadata().level()).into(),
+            logger: event.metadata().target().to_string(),
+            data: format!("{}{}", visitor.message, visitor.fields),
+        });
+    }
+}
+
+/// Renders an event's message followed by its other fields as `key=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::domain::Resource;
+
+/// Resources remembered per session; reading another forgets the oldest.
+const REMEMBERED: usize = 20;
+
+/// Searches memoized per session; another forgets the oldest.
+const MEMOIZED: usize = 10;
+
+/// How long a session's search results are reused for.
+const MEMO_TTL: Duration = Duration::from_secs(60);
+
+/// The resources a session's client read most recently, so an agent can
+/// get them back with `recall_context` instead of fetching them again.
+#[derive(Default)]
+pub struct ContextMemory {
+    /// Most recently read first, at most one entry per resource ID.
+    recent: Mutex<VecDeque<Resource>>,
+}
+
+impl ContextMemory {
+    /// Records a read. Reading a resource again moves it to the front with
+    /// its new content.
+    pub fn remember(&self, resource: &Resource) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|remembered| remembered.id != resource.id);
+        recent.push_front(resource.clone());
+        recent.truncate(REMEMBERED);
+    }
+
+    /// Up to `limit` remembered resources, most recently read first.
+    pub fn recall(&self, limit: usize) -> Vec<Resource> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The results of a session's recent searches, by tool and arguments, so an
+/// agent that repeats a search while reasoning gets the answer at once.
+/// Forgotten after writes and syncs, and when the client's roots change.
+#[derive(Default)]
+pub struct SearchMemo {
+    /// Most recent first, at most one entry per search.
+    recent: Mutex<VecDeque<Memoized>>,
+}
+
+struct Memoized {
+    key: String,
+    result: Value,
+    at: Instant,
+    searched_at: DateTime<Utc>,
+}
+
+impl SearchMemo {
+    /// The result memoized for `key`, if it's younger than a minute and
+    /// than `max_age`, annotated with when it was searched for.
+    pub fn recall(&self, key: &str, max_age: Option<Duration>) -> Option<Value> {
+        let max_age = max_age.map_or(MEMO_TTL, |max_age| max_age.min(MEMO_TTL));
+        let recent = self.recent.lock().unwrap();
+        let memoized = recent
+            .iter()
+            .find(|memoized| memoized.key == key)
+            .filter(|memoized| memoized.at.elapsed() < max_age)?;
+        let mut result = memoized.result.clone();
+        result["memoized"] = json!({
+            "searched_at": memoized.searched_at,
+            "age_secs": memoized.at.elapsed().as_secs(),
+        });
+        Some(result)
+    }
+
+    pub fn remember(&self, key: String, result: &Value) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|memoized| memoized.key != key);
+        recent.push_front(Memoized {
+            key,
+            result: result.clone(),
+            at: Instant::now(),
+            searched_at: Utc::now(),
+        });
+        recent.truncate(MEMOIZED);
+    }
+
+    pub fn forget(&self) {
+        self.recent.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,25 @@
+pub mod completion;
+pub mod cursor;
+#[cfg(feature = "http-server")]
+pub mod http;
+pub mod logging;
+pub mod memory;
+pub mod policy;
+pub mod prompts;
+pub mod protocol;
+pub mod roots;
+pub mod server;
+pub mod session;
+#[cfg(feature = "http-server")]
+pub mod snapshot;
+#[cfg(unix)]
+pub mod socket;
+#[cfg(feature = "http-server")]
+pub mod sse;
+pub mod stdio;
+#[cfg(feature = "http-server")]
+pub mod streamable;
+pub mod templates;
+pub mod tools;
+#[cfg(feature = "http-server")]
+pub mod websocket;
@@ -0,0 +1,192 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::domain::{Classification, DomainError, Resource};
+
+/// Which tools and resources each client may see, loaded from a TOML file:
+///
+/// ```toml
+/// [default]
+/// tools = ["search_resources", "fetch_resource"]
+///
+/// [[clients]]
+/// name = "research-agent"
+/// hide_providers = ["linear"]
+///
+/// [[clients]]
+/// token = "s3cret"
+/// hide_tools = ["list_providers"]
+///
+/// [[clients]]
+/// name = "untrusted-agent"
+/// read_only = true
+/// max_classification = "public"
+/// ```
+///
+/// Clients are matched by the `clientInfo.name` they send in `initialize`
+/// and, over HTTP, the bearer token they present; the first matching entry
+/// applies, and `default` covers the rest. Without a policy, clients see
+/// everything.
+#[derive(Debug, Default, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    default: ClientPolicy,
+    #[serde(default)]
+    clients: Vec<ClientRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientRule {
+    name: Option<String>,
+    token: Option<String>,
+    #[serde(flatten)]
+    policy: ClientPolicy,
+}
+
+/// What one client may see. Hidden tools and resources look to the client
+/// as if they don't exist.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClientPolicy {
+    /// Tools exposed; all when omitted.
+    tools: Option<Vec<String>>,
+    #[serde(default)]
+    hide_tools: Vec<String>,
+    /// Providers whose resources are exposed; all when omitted.
+    providers: Option<Vec<String>>,
+    #[serde(default)]
+    hide_providers: Vec<String>,
+    /// Hide the tools that change data upstream, even on servers that
+    /// allow writes.
+    #[serde(default)]
+    read_only: bool,
+    /// The most sensitive classification of resources exposed; all when
+    /// omitted.
+    max_classification: Option<Classification>,
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid policy {}: {}", path.display(), e))
+    }
+
+    /// Applies `restriction` to every client on top of what the file says:
+    /// only tools and providers both allow are exposed, and anything either
+    /// hides is hidden.
+    pub fn restrict(&mut self, restriction: &ClientPolicy) {
+        self.default.narrow(restriction);
+        for rule in &mut self.clients {
+            rule.policy.narrow(restriction);
+        }
+    }
+
+    /// The policy for a client called `name` that presented `token`.
+    pub fn for_client(&self, name: Option<&str>, token: Option<&str>) -> Arc<ClientPolicy> {
+        let policy = self
+            .clients
+            .iter()
+            .find(|rule| {
+                rule.name.as_deref().is_none_or(|rule| Some(rule) == name)
+                    && rule.token.as_deref().is_none_or(|rule| Some(rule) == token)
+            })
+            .map_or(&self.default, |rule| &rule.policy);
+        Arc::new(policy.clone())
+    }
+}
+
+impl ClientPolicy {
+    /// A policy exposing only `tools` and `providers` (everything when
+    /// empty), minus those hidden, and resources classified no higher than
+    /// `max_classification`.
+    pub fn new(
+        tools: Vec<String>,
+        hide_tools: Vec<String>,
+        providers: Vec<String>,
+        hide_providers: Vec<String>,
+        max_classification: Option<Classification>,
+    ) -> Self {
+        Self {
+            tools: (!tools.is_empty()).then_some(tools),
+            hide_tools,
+            providers: (!providers.is_empty()).then_some(providers),
+            hide_providers,
+            read_only: false,
+            max_classification,
+        }
+    }
+
+    fn narrow(&mut self, other: &ClientPolicy) {
+        let intersect = |mine: &mut Option<Vec<String>>, theirs: &Option<Vec<String>>| {
+            if let Some(theirs) = theirs {
+                *mine = Some(match mine.take() {
+                    Some(mine) => mine
+                        .into_iter()
+                        .filter(|item| theirs.iter().any(|t| t.eq_ignore_ascii_case(item)))
+                        .collect(),
+                    None => theirs.clone(),
+                });
+            }
+        };
+        intersect(&mut self.tools, &other.tools);
+        intersect(&mut self.providers, &other.providers);
+        self.hide_tools.extend(other.hide_tools.iter().cloned());
+        self.hide_providers
+            .extend(other.hide_providers.iter().cloned());
+        self.read_only |= other.read_only;
+        self.max_classification = match (self.max_classification, other.max_classification) {
+            (Some(mine), Some(theirs)) => Some(mine.min(theirs)),
+            (mine, theirs) => mine.or(theirs),
+        };
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        let listed = |tools: &[String]| tools.iter().any(|listed| listed == tool);
+        self.tools.as_deref().is_none_or(listed) && !listed(&self.hide_tools)
+    }
+
+    pub fn allows_provider(&self, provider: &str) -> bool {
+        let listed = |providers: &[String]| {
+            providers
+                .iter()
+                .any(|listed| listed.eq_ignore_ascii_case(provider))
+        };
+        self.providers.as_deref().is_none_or(listed) && !listed(&self.hide_providers)
+    }
+
+    /// Whether `resource` is exposed: its provider is, and it's classified
+    /// no higher than allowed.
+    pub fn allows_resource(&self, resource: &Resource) -> bool {
+        self.allows_provider(resource.source.provider_name())
+            && self
+                .max_classification
+                .is_none_or(|max| Classification::of(resource) <= max)
+    }
+
+    /// Checks that the resource `id` (e.g. `linear_<issue_id>`) is exposed,
+    /// judging by the provider its prefix names, reporting it as not found
+    /// otherwise.
+    pub fn check_resource(&self, id: &str) -> Result<(), DomainError> {
+        let provider = id.split_once('_').map_or(id, |(provider, _)| provider);
+        if self.allows_provider(provider) {
+            Ok(())
+        } else {
+            Err(DomainError::ResourceNotFound(id.to_string()))
+        }
+    }
+
+    /// Passes `resource` through if it's exposed, and reports it as not
+    /// found otherwise.
+    pub fn expose(&self, resource: Resource) -> Result<Resource, DomainError> {
+        if self.allows_resource(&resource) {
+            Ok(resource)
+        } else {
+            Err(DomainError::ResourceNotFound(resource.id))
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use super::policy::ClientPolicy;
+use super::protocol::{parse_params, RpcError};
+use crate::{application::ResourceService, domain::Resource};
+
+/// Templates available without a prompts directory. A file with the same
+/// name in the directory replaces them.
+const BUILTIN_PROMPTS: [(&str, &str); 2] = [
+    (
+        "summarize_linear_issue",
+        r#"
+description = "Summarize a Linear issue"
+
+template = """
+Summarize the Linear issue "{{issue.title}}" ({{issue.uri}}) attached above. \
+Cover the problem, its current status, and any open questions or next steps.
+"""
+
+[[arguments]]
+name = "issue"
+description = "Issue ID (linear_<issue_id>) or URI (linear://issue/<issue_id>)"
+required = true
+resource = true
+"#,
+    ),
+    (
+        "summarize_notion_page",
+        r#"
+description = "Summarize a Notion page"
+
+template = """
+Summarize the Notion page "{{page.title}}" ({{page.uri}}) attached above. \
+Lead with its purpose, then list the key points and any decisions or action items.
+"""
+
+[[arguments]]
+name = "page"
+description = "Page ID (notion_<page_id>) or URI (notion://page/<page_id>)"
+required = true
+resource = true
+"#,
+    ),
+];
+
+/// A prompt template, loaded from `<name>.toml` in the prompts directory.
+///
+/// ```toml
+/// description = "Summarize a Linear issue"
+/// template = "Summarize {{issue.title}} ({{issue.uri}})."
+///
+/// [[arguments]]
+/// name = "issue"
+/// required = true
+/// resource = true
+/// ```
+///
+/// `{{<arg>}}` is replaced with the argument's value. Resource arguments
+/// also provide `{{<arg>.title}}` and `{{<arg>.uri}}`, and the resource's
+/// content is attached to the prompt.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptTemplate {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+    pub template: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    /// The value is a resource ID or URI to fetch and attach.
+    #[serde(default, skip_serializing)]
+    pub resource: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetPromptParams {
+    name: String,
+    #[serde(default)]
+    arguments: HashMap<String, String>,
+}
+
+/// The prompts served by `prompts/list` and `prompts/get`, keyed by name.
+#[derive(Debug, Clone, Default)]
+pub struct Prompts {
+    templates: BTreeMap<String, PromptTemplate>,
+}
+
+impl Prompts {
+    /// Loads the built-in templates and every `*.toml` file in `dir`. A
+    /// missing directory is not an error; invalid files are skipped with a
+    /// warning.
+    pub fn load(dir: &Path) -> Self {
+        let mut templates = BTreeMap::new();
+        for (name, source) in BUILTIN_PROMPTS {
+            match toml::from_str(source) {
+                Ok(template) => {
+                    templates.insert(name.to_string(), template);
+                }
+                Err(e) => tracing::warn!("Invalid built-in prompt {}: {}", name, e),
+            }
+        }
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Failed to read prompts from {}: {}", dir.display(), e);
+                }
+                return Self { templates };
+            }
+        };
+
+        for path in entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let template = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| toml::from_str(&data).map_err(|e| e.to_string()));
+            match template {
+                Ok(template) => {
+                    templates.insert(name.to_string(), template);
+                }
+                Err(e) => tracing::warn!("Skipping prompt {}: {}", path.display(), e),
+            }
+        }
+
+        Self { templates }
+    }
+
+    pub fn list(&self) -> Value {
+        let prompts: Vec<Value> = self
+            .templates
+            .iter()
+            .map(|(name, template)| {
+                json!({
+                    "name": name,
+                    "description": template.description,
+                    "arguments": template.arguments,
+                })
+            })
+            .collect();
+
+        json!({ "prompts": prompts })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    /// Renders a prompt. Resource arguments are fetched and attached as
+    /// embedded resources ahead of the rendered text, if `policy` exposes
+    /// them.
+    pub async fn get(
+        &self,
+        service: &ResourceService,
+        policy: &ClientPolicy,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        let params: GetPromptParams = parse_params(params)?;
+        let template = self
+            .templates
+            .get(&params.name)
+            .ok_or_else(|| RpcError::invalid_params(format!("Unknown prompt: {}", params.name)))?;
+
+        let mut text = template.template.clone();
+        let mut messages = Vec::new();
+
+        for argument in &template.arguments {
+            let value = match params.arguments.get(&argument.name) {
+                Some(value) => value.as_str(),
+                None if argument.required => {
+                    return Err(RpcError::invalid_params(format!(
+                        "Missing required argument: {}",
+                        argument.name
+                    )))
+                }
+                None => "",
+            };
+
+            if argument.resource && !value.is_empty() {
+                let id = Resource::id_from_uri(value).unwrap_or_else(|| value.to_string());
+                let resource = policy.expose(service.fetch_resource_by_id(&id).await?)?;
+                let uri = resource.uri();
+
+                text = fill(&text, &argument.name, Some("title"), &resource.title);
+                text = fill(&text, &argument.name, Some("uri"), &uri);
+                messages.push(json!({
+                    "role": "user",
+                    "content": {
+                        "type": "resource",
+                        "resource": {
+                            "uri": uri,
+                            "mimeType": "text/plain",
+                            "text": resource.content,
+                        },
+                    },
+                }));
+            } else if argument.resource {
+                text = fill(&text, &argument.name, Some("title"), "");
+                text = fill(&text, &argument.name, Some("uri"), "");
+            }
+
+            text = fill(&text, &argument.name, None, value);
+        }
+
+        messages.push(json!({
+            "role": "user",
+            "content": { "type": "text", "text": text.trim() },
+        }));
+
+        Ok(json!({
+            "description": template.description,
+            "messages": messages,
+        }))
+    }
+}
+
+/// Replaces `{{name}}`, or `{{name.field}}` when `field` is given.
+fn fill(text: &str, name: &str, field: Option<&str>, value: &str) -> String {
+    let placeholder = match field {
+        Some(field) => format!("{{{{{}.{}}}}}", name, field),
+        None => format!("{{{{{}}}}}", name),
+    };
+    text.replace(&placeholder, value)
+}
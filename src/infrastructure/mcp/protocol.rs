@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::domain::DomainError;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Latest MCP protocol revision implemented by the server.
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Revisions the server can speak, newest first. `initialize` agrees to the
+/// client's revision if it is listed, and offers the latest otherwise.
+pub const SUPPORTED_PROTOCOL_VERSIONS: [&str; 2] = [PROTOCOL_VERSION, "2024-11-05"];
+
+/// An incoming JSON-RPC request, or a notification when `id` is absent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION,
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION,
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// A client's answer to a request the server sent it.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub id: Value,
+    pub outcome: Result<Value, RpcError>,
+}
+
+/// A message from the server that expects no reply.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub jsonrpc: &'static str,
+    pub method: String,
+    pub params: Value,
+}
+
+impl Notification {
+    pub fn new(method: impl Into<String>, params: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION,
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// MCP's code for reads of unknown resources.
+    pub const RESOURCE_NOT_FOUND: i64 = -32002;
+    /// A provider refused its credentials; `data.missing_scope` says what
+    /// they lack, if the provider said.
+    pub const UNAUTHORIZED: i64 = -32003;
+    /// A provider is throttling requests; `data.retry_after_secs` says how
+    /// long to wait, if the provider said.
+    pub const RATE_LIMITED: i64 = -32029;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            Self::METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )
+    }
+
+    pub fn invalid_params(message: impl std::fmt::Display) -> Self {
+        Self::new(Self::INVALID_PARAMS, format!("Invalid params: {}", message))
+    }
+}
+
+impl From<DomainError> for RpcError {
+    /// Codes by kind of error, with the error's details as `data` so
+    /// clients can tell when and how to retry.
+    fn from(error: DomainError) -> Self {
+        let code = match error {
+            DomainError::ResourceNotFound(_) | DomainError::NotConfigured { .. } => {
+                Self::RESOURCE_NOT_FOUND
+            }
+            DomainError::InvalidQuery(_) => Self::INVALID_PARAMS,
+            DomainError::ReadOnly(_) | DomainError::LimitExceeded(_) | DomainError::Conflict(_) => {
+                Self::INVALID_REQUEST
+            }
+            DomainError::RateLimited { .. } => Self::RATE_LIMITED,
+            DomainError::Unauthorized { .. } => Self::UNAUTHORIZED,
+            DomainError::ProviderError(_)
+            | DomainError::Unavailable { .. }
+            | DomainError::StorageError(_)
+            | DomainError::DuplicateProvider(_) => Self::INTERNAL_ERROR,
+        };
+        let mut data = serde_json::to_value(error.details()).unwrap_or_default();
+        if let (DomainError::Conflict(conflict), Value::Object(data)) = (&error, &mut data) {
+            // The versions let a client tell what it raced with.
+            if let Ok(Value::Object(versions)) = serde_json::to_value(conflict) {
+                data.extend(versions);
+            }
+        }
+        Self {
+            data: Some(data),
+            ..Self::new(code, error.to_string())
+        }
+    }
+}
+
+/// Parses a request's params, reporting failures as `INVALID_PARAMS`.
+pub fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(RpcError::invalid_params)
+}
+
+/// A JSON-RPC message: one request, or a batch of them. Batch entries that
+/// aren't valid requests are replaced by the error response to send back.
+pub enum Message {
+    Single(Request),
+    Batch(Vec<Result<Request, Box<Response>>>),
+    /// The client's reply to a request the server sent.
+    Reply(Reply),
+}
+
+/// Parses one JSON-RPC message, or returns the error response to send
+/// instead.
+#[cfg(feature = "http-server")]
+pub fn parse_request(message: &str) -> Result<Request, Box<Response>> {
+    let value: Value = serde_json::from_str(message).map_err(|e| Box::new(parse_error(e)))?;
+    request_from_value(value)
+}
+
+/// Like `parse_request`, but also accepts batches.
+pub fn parse_message(message: &str) -> Result<Message, Box<Response>> {
+    let value: Value = serde_json::from_str(message).map_err(|e| Box::new(parse_error(e)))?;
+    match value {
+        Value::Array(items) if items.is_empty() => Err(Box::new(Response::failure(
+            Value::Null,
+            RpcError::new(RpcError::INVALID_REQUEST, "Invalid request: empty batch"),
+        ))),
+        Value::Array(items) => Ok(Message::Batch(
+            items.into_iter().map(request_from_value).collect(),
+        )),
+        value => match reply_from_value(&value) {
+            Some(reply) => Ok(Message::Reply(reply)),
+            None => request_from_value(value).map(Message::Single),
+        },
+    }
+}
+
+/// `message` as a reply, if it's a reply rather than a request.
+#[cfg(feature = "http-server")]
+pub fn parse_reply(message: &str) -> Option<Reply> {
+    reply_from_value(&serde_json::from_str(message).ok()?)
+}
+
+/// A reply has a result or error where a request has a method.
+fn reply_from_value(value: &Value) -> Option<Reply> {
+    if value.get("method").is_some() {
+        return None;
+    }
+    let id = value.get("id").cloned()?;
+    let outcome = match (value.get("result"), value.get("error")) {
+        (Some(result), None) => Ok(result.clone()),
+        (_, Some(error)) => Err(serde_json::from_value(error.clone()).unwrap_or_else(|_| {
+            RpcError::new(
+                RpcError::INTERNAL_ERROR,
+                format!("Malformed error: {}", error),
+            )
+        })),
+        (None, None) => return None,
+    };
+    Some(Reply { id, outcome })
+}
+
+fn parse_error(error: serde_json::Error) -> Response {
+    Response::failure(
+        Value::Null,
+        RpcError::new(RpcError::PARSE_ERROR, format!("Parse error: {}", error)),
+    )
+}
+
+fn request_from_value(value: Value) -> Result<Request, Box<Response>> {
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let invalid = |message: String| {
+        Box::new(Response::failure(
+            id.clone(),
+            RpcError::new(RpcError::INVALID_REQUEST, message),
+        ))
+    };
+
+    let request: Request =
+        serde_json::from_value(value).map_err(|e| invalid(format!("Invalid request: {}", e)))?;
+    if request.jsonrpc != JSONRPC_VERSION {
+        return Err(invalid(format!(
+            "Unsupported JSON-RPC version: {}",
+            request.jsonrpc
+        )));
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn data(error: DomainError) -> Value {
+        RpcError::from(error).data.unwrap_or_default()
+    }
+
+    #[test]
+    fn error_data_says_which_failures_to_retry() {
+        assert_eq!(
+            data(DomainError::NotConfigured {
+                provider: "notion".to_string(),
+            }),
+            json!({ "kind": "not_configured", "retryable": false, "provider": "notion" })
+        );
+        assert_eq!(
+            data(DomainError::ProviderError("bad filter".to_string())),
+            json!({ "kind": "provider_error", "retryable": false })
+        );
+        assert_eq!(
+            data(DomainError::Unavailable {
+                provider: "jira".to_string(),
+                message: "503 Service Unavailable".to_string(),
+            }),
+            json!({ "kind": "unavailable", "retryable": true, "provider": "jira" })
+        );
+        assert_eq!(
+            data(DomainError::RateLimited {
+                provider: "github".to_string(),
+                retry_after_secs: Some(30),
+            }),
+            json!({
+                "kind": "rate_limited",
+                "retryable": true,
+                "provider": "github",
+                "retry_after_secs": 30,
+            })
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::domain::{Resource, ResourceSource};
+
+#[derive(Debug, Deserialize)]
+struct ListRootsResult {
+    roots: Vec<Root>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Root {
+    uri: String,
+}
+
+/// The roots a client gave in answer to `roots/list`, which narrow what it
+/// is shown. Roots only restrict resources of their own scheme, so an
+/// editor's `file://` workspace root hides no Notion pages:
+///
+/// - `notion://` and `linear://` allow the whole provider,
+/// - `notion://database/<database_id>` the pages of a database,
+/// - `linear://team/<team_key>` a team's issues,
+/// - `notion://page/<page_id>` and `linear://issue/<issue_id>` one resource,
+/// - any other URI, such as `file:///home/ada/handbook`, the upstream
+///   resources under it.
+#[derive(Debug, Clone, Default)]
+pub struct Roots {
+    uris: Vec<String>,
+}
+
+impl Roots {
+    /// Parses a `roots/list` result.
+    pub fn from_result(result: Value) -> Option<Self> {
+        let result: ListRootsResult = serde_json::from_value(result).ok()?;
+        Some(Self {
+            uris: result.roots.into_iter().map(|root| root.uri).collect(),
+        })
+    }
+
+    pub fn uris(&self) -> &[String] {
+        &self.uris
+    }
+
+    /// Whether `resource` is under one of the roots of its scheme, or
+    /// there are none.
+    pub fn contains(&self, resource: &Resource) -> bool {
+        let Some(uri) = scheme_uri(resource) else {
+            return true;
+        };
+        let Some((scheme, _)) = uri.split_once("://") else {
+            return true;
+        };
+        let mut roots = self
+            .uris
+            .iter()
+            .filter_map(|root| root.split_once("://"))
+            .filter(|(root_scheme, _)| root_scheme.eq_ignore_ascii_case(scheme))
+            .map(|(_, path)| path.trim_end_matches('/'))
+            .peekable();
+        if roots.peek().is_none() {
+            return true;
+        }
+        roots.any(|root| root.is_empty() || covers(root, resource, uri))
+    }
+}
+
+/// The URI `resource` is matched by: its own, or for upstream resources
+/// the URI the upstream server gave it.
+fn scheme_uri(resource: &Resource) -> Option<&str> {
+    match resource.source {
+        ResourceSource::Notion { .. } => Some("notion://"),
+        ResourceSource::Linear { .. } => Some("linear://"),
+        ResourceSource::Custom(_) => resource.metadata.get("uri")?.as_str(),
+    }
+}
+
+/// Whether the root `notion://<root>`, `linear://<root>` or, for upstream
+/// resources, `<scheme>://<root>` covers `resource`.
+fn covers(root: &str, resource: &Resource, uri: &str) -> bool {
+    let same_id = |a: &str, b: &str| a.replace('-', "").eq_ignore_ascii_case(&b.replace('-', ""));
+    match &resource.source {
+        ResourceSource::Notion {
+            page_id,
+            database_id,
+        } => match root.split_once('/') {
+            Some(("page", id)) => same_id(id, page_id),
+            Some(("database", id)) => database_id
+                .as_deref()
+                .is_some_and(|database_id| same_id(id, database_id)),
+            _ => false,
+        },
+        ResourceSource::Linear { issue_id, .. } => match root.split_once('/') {
+            Some(("issue", id)) => {
+                id == issue_id
+                    || resource
+                        .short_id()
+                        .is_some_and(|short| short.eq_ignore_ascii_case(id))
+            }
+            // Issue identifiers start with their team's key, e.g. `ENG-123`.
+            Some(("team", team)) => {
+                let team = team.trim_end_matches("/issues");
+                resource
+                    .short_id()
+                    .and_then(|identifier| identifier.rsplit_once('-'))
+                    .is_some_and(|(key, _)| key.eq_ignore_ascii_case(team))
+            }
+            _ => false,
+        },
+        ResourceSource::Custom(_) => {
+            let path = uri.split_once("://").map_or(uri, |(_, path)| path);
+            if uri.starts_with("file://") {
+                Path::new(path).starts_with(root)
+            } else {
+                path.strip_prefix(root)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }
+        }
+    }
+}
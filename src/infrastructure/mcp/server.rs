@@ -0,0 +1,649 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::completion::Completer;
+use super::cursor::{self, ListCursor, OffsetCursor};
+use super::logging::{LogForwarder, LogLevel};
+use super::policy::Policy;
+use super::prompts::Prompts;
+use super::protocol::{
+    parse_params, Message, Notification, Request, Response, RpcError, PROTOCOL_VERSION,
+    SUPPORTED_PROTOCOL_VERSIONS,
+};
+use super::session::{Negotiated, Phase, Session};
+use super::templates::{self, Collection};
+use super::tools;
+#[cfg(feature = "http-server")]
+use crate::domain::Snapshot;
+use crate::{
+    application::{
+        shape::{self, shape, ContentFormat, ShapeOptions},
+        watch::ResourceWatcher,
+        ResourceService,
+    },
+    domain::{DomainError, Resource},
+    ports::{with_call_budget, with_cancellation, with_progress, ProgressReporter},
+};
+
+/// Params of `resources/list`.
+#[derive(Debug, Default, Deserialize)]
+struct ListParams {
+    cursor: Option<String>,
+}
+
+/// Params of `resources/read`, `resources/subscribe` and
+/// `resources/unsubscribe`.
+#[derive(Debug, Deserialize)]
+struct ResourceParams {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetLevelParams {
+    level: LogLevel,
+}
+
+#[derive(Debug, Deserialize)]
+struct CancelledParams {
+    #[serde(rename = "requestId")]
+    request_id: Value,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct InitializeParams {
+    #[serde(rename = "protocolVersion")]
+    protocol_version: Option<String>,
+    #[serde(default)]
+    capabilities: Value,
+    #[serde(rename = "clientInfo")]
+    client_info: Option<ClientInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientInfo {
+    name: String,
+}
+
+/// How long network sessions may sit idle unless configured otherwise.
+const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Characters of content `resources/read` returns at once unless configured
+/// otherwise, about 25,000 tokens.
+pub const DEFAULT_MAX_READ_CHARS: usize = 100_000;
+
+/// Sends progress reported while handling a request to its client as
+/// `notifications/progress`.
+struct ProgressNotifier {
+    token: Value,
+    outbox: mpsc::UnboundedSender<Value>,
+}
+
+impl ProgressReporter for ProgressNotifier {
+    fn report(&self, progress: u64, message: &str) {
+        let notification = Notification::new(
+            "notifications/progress",
+            json!({
+                "progressToken": self.token,
+                "progress": progress,
+                "message": message,
+            }),
+        );
+        let _ = self.outbox.send(json!(notification));
+    }
+}
+
+/// Routes MCP requests to the resource service. Transports parse messages
+/// and hand them to `handle`, so every transport shares the same behavior.
+pub struct McpServer {
+    service: Arc<ResourceService>,
+    prompts: Prompts,
+    watcher: Arc<ResourceWatcher>,
+    policy: Policy,
+    logs: LogForwarder,
+    completer: Completer,
+    session_idle_timeout: Duration,
+    /// Characters of content `resources/read` returns at once, or `None`
+    /// for no limit.
+    max_read_chars: Option<usize>,
+    /// Whether tools that change data upstream are offered.
+    writes: bool,
+    /// How often event stream and WebSocket sessions are pinged, and how
+    /// long they get to answer.
+    keepalive: Option<(Duration, Duration)>,
+}
+
+impl McpServer {
+    pub fn new(
+        service: Arc<ResourceService>,
+        prompts: Prompts,
+        watcher: Arc<ResourceWatcher>,
+    ) -> Self {
+        Self {
+            completer: Completer::new(service.clone()),
+            service,
+            prompts,
+            watcher,
+            policy: Policy::default(),
+            logs: LogForwarder::new(),
+            session_idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
+            max_read_chars: Some(DEFAULT_MAX_READ_CHARS),
+            writes: false,
+            keepalive: None,
+        }
+    }
+
+    /// Limits what each client sees.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Lets clients receive the log messages `logs` collects.
+    pub fn with_logs(mut self, logs: LogForwarder) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    /// Ends network sessions after `timeout` without activity.
+    pub fn with_session_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.session_idle_timeout = timeout;
+        self
+    }
+
+    /// Returns at most `max_chars` characters of content per
+    /// `resources/read`, with a URI to read the rest from, or everything
+    /// at once if `None`.
+    pub fn with_max_read_chars(mut self, max_chars: Option<usize>) -> Self {
+        self.max_read_chars = max_chars;
+        self
+    }
+
+    /// Offers the tools that create and change data upstream, such as
+    /// `create_linear_issue`. Without this they don't exist.
+    pub fn with_writes(mut self) -> Self {
+        self.writes = true;
+        self
+    }
+
+    /// Pings clients with a stream open every `interval`, ending sessions
+    /// that take longer than `timeout` to answer.
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some((interval, timeout));
+        self
+    }
+
+    /// Starts pinging `session`, if keepalive pings are configured.
+    #[cfg(feature = "http-server")]
+    pub fn keep_alive(&self, session: &Arc<Session>) {
+        if let Some((interval, timeout)) = self.keepalive {
+            session.keep_alive(interval, timeout);
+        }
+    }
+
+    #[cfg(feature = "http-server")]
+    pub fn session_idle_timeout(&self) -> Duration {
+        self.session_idle_timeout
+    }
+
+    /// Starts a session for a newly connected client; see `Session::new`.
+    pub fn session(
+        &self,
+        outbox: Option<mpsc::UnboundedSender<Value>>,
+        token: Option<String>,
+    ) -> Arc<Session> {
+        Session::new(self.watcher.clone(), self.logs.subscribe(), outbox, token)
+    }
+
+    /// The cached resources a client presenting `token` may read, for
+    /// another install to start its cache from.
+    #[cfg(feature = "http-server")]
+    pub async fn snapshot(&self, token: Option<&str>) -> Result<Snapshot, DomainError> {
+        let policy = self.policy.for_client(None, token);
+        self.service
+            .export_snapshot(|resource| policy.allows_resource(resource))
+            .await
+    }
+
+    /// Handles one message from `session`'s client, returning the response
+    /// to send back, or `None` for notifications.
+    pub async fn handle(&self, session: &Session, request: Request) -> Option<Response> {
+        self.handle_via(session, None, request).await
+    }
+
+    /// Like `handle`, but sends notifications about the request, such as
+    /// progress, to `outbox` rather than the session's stream.
+    pub async fn handle_via(
+        &self,
+        session: &Session,
+        outbox: Option<&mpsc::UnboundedSender<Value>>,
+        request: Request,
+    ) -> Option<Response> {
+        let Some(id) = request.id.clone() else {
+            tracing::debug!("MCP notification: {}", request.method);
+            match request.method.as_str() {
+                "notifications/initialized" => session.ready(),
+                "notifications/cancelled" => cancel(session, request.params),
+                "notifications/roots/list_changed" => session.roots_changed(),
+                _ => {}
+            }
+            return None;
+        };
+
+        let progress_token = request
+            .params
+            .get("_meta")
+            .and_then(|meta| meta.get("progressToken"))
+            .cloned();
+        let outbox = outbox.cloned().or_else(|| session.outbox());
+        let cancellation = session.start(&id);
+        let dispatch = self.dispatch(session, request);
+        let dispatch = async {
+            match self.service.request_call_budget() {
+                Some(budget) => with_call_budget(budget, dispatch).await,
+                None => dispatch.await,
+            }
+        };
+        let dispatch = with_cancellation(cancellation.clone(), dispatch);
+        let result = match progress_token.zip(outbox) {
+            Some((token, outbox)) => {
+                let reporter = Arc::new(ProgressNotifier { token, outbox });
+                with_progress(reporter, dispatch).await
+            }
+            None => dispatch.await,
+        };
+        session.finish(&id);
+
+        // The client has stopped waiting for cancelled requests.
+        if cancellation.is_cancelled() {
+            tracing::debug!("MCP request {} cancelled", id);
+            return None;
+        }
+
+        Some(match result {
+            Ok(result) => Response::success(id, result),
+            Err(error) => Response::failure(id, error),
+        })
+    }
+
+    async fn dispatch(&self, session: &Session, request: Request) -> Result<Value, RpcError> {
+        match (request.method.as_str(), session.phase()) {
+            ("initialize", _) => initialize(session, &self.policy, request.params),
+            ("ping", _) => Ok(json!({})),
+            (method, Phase::AwaitingInitialize) => Err(RpcError::new(
+                RpcError::INVALID_REQUEST,
+                format!("Received {} before initialize", method),
+            )),
+            ("resources/list", _) => self.list_resources(session, request.params).await,
+            ("resources/read", _) => self.read_resource(session, request.params).await,
+            ("resources/templates/list", _) => {
+                Ok(templates::list(&self.service, &session.policy()))
+            }
+            ("resources/subscribe", _) => subscribe(session, request.params).await,
+            ("resources/unsubscribe", _) => unsubscribe(session, request.params),
+            ("tools/list", _) => Ok(json!({
+                "tools": tools::definitions(&session.policy(), self.writes)
+            })),
+            ("tools/call", _) => {
+                tools::call(&self.service, session, self.writes, request.params).await
+            }
+            ("completion/complete", _) => {
+                let policy = session.policy();
+                let params = request.params;
+                self.completer
+                    .complete(&self.prompts, &policy, self.writes, params)
+                    .await
+            }
+            ("logging/setLevel", _) => set_log_level(session, request.params),
+            ("prompts/list", _) => Ok(self.prompts.list()),
+            ("prompts/get", _) => {
+                let policy = session.policy();
+                self.prompts
+                    .get(&self.service, &policy, request.params)
+                    .await
+            }
+            (method, _) => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    /// Handles a batch in order, returning the responses to send back, with
+    /// entries that failed to parse answered by their error responses.
+    pub async fn handle_batch(
+        &self,
+        session: &Session,
+        requests: Vec<Result<Request, Box<Response>>>,
+    ) -> Vec<Response> {
+        let mut responses = Vec::new();
+        for request in requests {
+            match request {
+                Ok(request) => responses.extend(self.handle(session, request).await),
+                Err(response) => responses.push(*response),
+            }
+        }
+        responses
+    }
+
+    /// Handles a message on a connection that carries one session, as stdio
+    /// and WebSocket do, returning what to send back: a response, an array
+    /// of responses for a batch, or nothing for notifications.
+    pub async fn handle_message(&self, session: &Session, message: Message) -> Option<Value> {
+        match message {
+            Message::Single(request) => self
+                .handle(session, request)
+                .await
+                .map(|response| json!(response)),
+            Message::Batch(requests) => {
+                let responses = self.handle_batch(session, requests).await;
+                (!responses.is_empty()).then(|| json!(responses))
+            }
+            Message::Reply(reply) => {
+                session.replied(reply);
+                None
+            }
+        }
+    }
+
+    /// Lists one page of resources from the providers the client may see,
+    /// in name order, following each provider's own pagination, leaving
+    /// out resources outside the client's roots or classified above what it
+    /// may see. Providers that fail are
+    /// skipped, and so are empty pages, so a page is only empty when
+    /// nothing is left.
+    async fn list_resources(&self, session: &Session, params: Value) -> Result<Value, RpcError> {
+        let params: ListParams = parse_params::<Option<_>>(params)?.unwrap_or_default();
+        let policy = session.policy();
+        let roots = session.roots().await;
+        let mut providers: Vec<String> = self
+            .service
+            .list_providers()
+            .into_iter()
+            .map(str::to_lowercase)
+            .filter(|provider| policy.allows_provider(provider))
+            .collect();
+        providers.sort();
+
+        let mut position = match &params.cursor {
+            Some(cursor) => Some(cursor::decode(cursor)?),
+            None => providers
+                .first()
+                .map(|provider| ListCursor::start(provider)),
+        };
+        while let Some(ListCursor { provider, cursor }) = position.take() {
+            let index = providers
+                .iter()
+                .position(|name| *name == provider)
+                .ok_or_else(|| {
+                    RpcError::invalid_params(format!("Unknown provider {}", provider))
+                })?;
+            let mut page = match self.service.list_page(&provider, cursor).await {
+                Ok(page) => page,
+                Err(e) => {
+                    tracing::warn!("Skipping {} in resources/list: {}", provider, e);
+                    Default::default()
+                }
+            };
+
+            position = match page.next_cursor {
+                Some(cursor) => Some(ListCursor {
+                    provider,
+                    cursor: Some(cursor),
+                }),
+                None => providers.get(index + 1).map(|next| ListCursor::start(next)),
+            };
+            page.resources.retain(|resource| {
+                policy.allows_resource(resource)
+                    && roots.as_ref().is_none_or(|roots| roots.contains(resource))
+            });
+            if !page.resources.is_empty() {
+                let resources: Vec<Value> = page.resources.iter().map(listed_resource).collect();
+                let mut result = json!({ "resources": resources });
+                if let Some(next) = &position {
+                    result["nextCursor"] = json!(cursor::encode(next));
+                }
+                return Ok(result);
+            }
+        }
+
+        Ok(json!({ "resources": [] }))
+    }
+
+    /// Reads a resource, or lists a collection such as
+    /// `notion://database/<database_id>`, shaped by the `section`, `format`
+    /// and `max_tokens` query parameters of its URI, e.g.
+    /// `notion://page/<page_id>?section=Decision&max_tokens=2000`. Content
+    /// over the size limit is returned in parts, each ending with the URI
+    /// of the next, which adds a `cursor` parameter. Files attached to
+    /// resources are read as base64 blobs under URIs such as
+    /// `notion://page/<page_id>/attachments/0`.
+    async fn read_resource(&self, session: &Session, params: Value) -> Result<Value, RpcError> {
+        let params: ResourceParams = parse_params(params)?;
+        let (uri, options, offset) = read_options(&params.uri)?;
+        let policy = session.policy();
+        if let Some(collection) = Collection::parse(uri) {
+            if !policy.allows_provider(collection.provider()) {
+                return Err(DomainError::ResourceNotFound(uri.to_string()).into());
+            }
+            let content = collection.read(&self.service, &policy).await?;
+            let (text, next) = self.page(&params.uri, &shape(&content, &options)?, offset);
+            return Ok(json!({
+                "contents": [{
+                    "uri": params.uri,
+                    "mimeType": options.format.map_or("text/markdown", ContentFormat::mime_type),
+                    "text": text,
+                    "_meta": {
+                        "nextUri": next,
+                    },
+                }],
+            }));
+        }
+
+        if let Some((id, index)) = Resource::id_from_attachment_uri(uri) {
+            policy.check_resource(&id)?;
+            policy.expose(self.service.fetch_resource_by_id(&id).await?)?;
+            let (attachment, blob) = self.service.attachment(&id, index).await?;
+            return Ok(json!({
+                "contents": [{
+                    "uri": uri,
+                    "mimeType": attachment.mime_type.unwrap_or(blob.mime_type),
+                    "blob": BASE64.encode(&blob.data),
+                    "_meta": {
+                        "name": attachment.name,
+                    },
+                }],
+            }));
+        }
+
+        let id = templates::resource_id(&self.service, &policy, uri)?;
+        let resource = policy.expose(self.service.fetch_resource_by_id(&id).await?)?;
+        session.memory().remember(&resource);
+        let attachments: Vec<Value> = resource
+            .attachments()
+            .into_iter()
+            .enumerate()
+            .map(|(index, attachment)| {
+                let mut listed = json!(attachment);
+                listed["uri"] = json!(resource.attachment_uri(index));
+                listed
+            })
+            .collect();
+        let mime_type = options
+            .format
+            .map_or("text/plain", ContentFormat::mime_type);
+        let (text, next) = self.page(&params.uri, &shape(&resource.content, &options)?, offset);
+        Ok(json!({
+            "contents": [{
+                "uri": params.uri,
+                "mimeType": mime_type,
+                "text": text,
+                "_meta": {
+                    "provenance": resource.provenance,
+                    "version": resource.version(),
+                    "attachments": attachments,
+                    "nextUri": next,
+                },
+            }],
+        }))
+    }
+
+    /// The part of `content` read from `offset` characters in, and if it
+    /// doesn't fit in one read, the URI to read the rest from, which is
+    /// `uri` with a `cursor` parameter. The part ends noting that URI, so
+    /// models given only the text know there's more.
+    fn page(&self, uri: &str, content: &str, offset: usize) -> (String, Option<String>) {
+        let max_chars = self.max_read_chars.unwrap_or(usize::MAX);
+        let (text, next) = shape::page(content, offset, max_chars);
+        let Some(next) = next else {
+            return (text.to_string(), None);
+        };
+        let next_uri = continuation_uri(uri, next);
+        let total = content.chars().count();
+        let text = format!(
+            "{}\n\n[Characters {}-{} of {}; continued at {}]",
+            text.trim_end(),
+            offset + 1,
+            next,
+            total,
+            next_uri
+        );
+        (text, Some(next_uri))
+    }
+}
+
+/// Answers the handshake with the agreed protocol revision (the client's
+/// if supported, else the latest) and what the server supports: resources
+/// with subscriptions, tools and prompts, none of whose lists change while
+/// it runs, argument completion, and logging. The client's name and token
+/// pick its policy.
+fn initialize(session: &Session, policy: &Policy, params: Value) -> Result<Value, RpcError> {
+    let params: InitializeParams = serde_json::from_value(params).unwrap_or_default();
+    let protocol_version = params
+        .protocol_version
+        .filter(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(&version.as_str()))
+        .unwrap_or_else(|| PROTOCOL_VERSION.to_string());
+
+    let client_name = params.client_info.map(|info| info.name);
+    let negotiated = Negotiated {
+        protocol_version: protocol_version.clone(),
+        client_name: client_name.clone(),
+        capabilities: params.capabilities,
+    };
+    tracing::debug!("MCP session initialized for {}", negotiated);
+
+    if !session.initialized(negotiated) {
+        return Err(RpcError::new(
+            RpcError::INVALID_REQUEST,
+            "Session is already initialized",
+        ));
+    }
+    session.set_policy(policy.for_client(client_name.as_deref(), session.token()));
+
+    Ok(json!({
+        "protocolVersion": protocol_version,
+        "capabilities": {
+            "resources": { "subscribe": true, "listChanged": false },
+            "tools": { "listChanged": false },
+            "prompts": { "listChanged": false },
+            "completions": {},
+            "logging": {},
+        },
+        "serverInfo": {
+            "name": "mcp-rs",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "instructions": "resources/list pages through every provider's resources; \
+            resources/read fetches any resource by URI, \
+            and resources/templates/list shows the URIs it accepts.",
+    }))
+}
+
+/// An entry of `resources/list`.
+fn listed_resource(resource: &Resource) -> Value {
+    json!({
+        "uri": resource.uri(),
+        "name": resource.title,
+        "mimeType": "text/plain",
+        "annotations": {
+            "lastModified": resource.updated_at,
+        },
+        "_meta": {
+            "provenance": resource.provenance,
+        },
+    })
+}
+
+/// Starts sending `notifications/resources/updated` to the session when the
+/// resource changes upstream.
+async fn subscribe(session: &Session, params: Value) -> Result<Value, RpcError> {
+    let params: ResourceParams = parse_params(params)?;
+    let id = resource_id(&params.uri)?;
+    session.policy().check_resource(&id)?;
+    session.subscribe(&id).await?;
+    Ok(json!({}))
+}
+
+fn unsubscribe(session: &Session, params: Value) -> Result<Value, RpcError> {
+    let params: ResourceParams = parse_params(params)?;
+    session.unsubscribe(&resource_id(&params.uri)?);
+    Ok(json!({}))
+}
+
+fn set_log_level(session: &Session, params: Value) -> Result<Value, RpcError> {
+    let params: SetLevelParams = parse_params(params)?;
+    session.set_log_level(params.level);
+    Ok(json!({}))
+}
+
+/// Aborts the request a `notifications/cancelled` names, if still running.
+fn cancel(session: &Session, params: Value) {
+    let Ok(params) = serde_json::from_value::<CancelledParams>(params) else {
+        return;
+    };
+    tracing::debug!(
+        "Client cancelled MCP request {}: {}",
+        params.request_id,
+        params.reason.as_deref().unwrap_or("no reason given")
+    );
+    session.cancel(&params.request_id);
+}
+
+/// Splits the shaping options and the `cursor` position off a resource
+/// URI's query.
+fn read_options(uri: &str) -> Result<(&str, ShapeOptions, usize), RpcError> {
+    let mut options = ShapeOptions::default();
+    let mut offset = 0;
+    let Some((base, _)) = uri.split_once('?') else {
+        return Ok((uri, options, offset));
+    };
+    let url = reqwest::Url::parse(uri)
+        .map_err(|e| DomainError::InvalidQuery(format!("Invalid URI {}: {}", uri, e)))?;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "cursor" => offset = cursor::decode::<OffsetCursor>(&value)?.offset,
+            _ => options.set(&key, &value)?,
+        }
+    }
+    Ok((base, options, offset))
+}
+
+/// `uri` with its `cursor` parameter, if any, replaced by one starting at
+/// `offset`.
+fn continuation_uri(uri: &str, offset: usize) -> String {
+    let cursor = cursor::encode(&OffsetCursor { offset });
+    let (base, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let mut parameters: Vec<&str> = query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty() && !parameter.starts_with("cursor="))
+        .collect();
+    let cursor = format!("cursor={}", cursor);
+    parameters.push(&cursor);
+    format!("{}?{}", base, parameters.join("&"))
+}
+
+fn resource_id(uri: &str) -> Result<String, DomainError> {
+    Resource::id_from_uri(uri)
+        .ok_or_else(|| DomainError::ResourceNotFound(format!("Unknown resource URI: {}", uri)))
+}
@@ -0,0 +1,608 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use super::logging::{LogLevel, LogMessage};
+use super::memory::{ContextMemory, SearchMemo};
+use super::policy::ClientPolicy;
+use super::protocol::{Notification, Reply, RpcError, JSONRPC_VERSION};
+use super::roots::Roots;
+use crate::application::watch::{ResourceUpdate, ResourceWatcher};
+use crate::domain::DomainError;
+
+/// How long a client gets to answer `roots/list`.
+const ROOTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where a session is in the initialize handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Only `initialize` and `ping` are accepted.
+    AwaitingInitialize,
+    /// `initialize` was answered; waiting for `notifications/initialized`.
+    AwaitingInitialized,
+    Ready,
+}
+
+/// What a client agreed to in `initialize`.
+#[derive(Debug, Clone)]
+pub struct Negotiated {
+    pub protocol_version: String,
+    pub client_name: Option<String>,
+    /// The client's `capabilities`, e.g. `{"sampling": {}}`.
+    pub capabilities: Value,
+}
+
+impl fmt::Display for Negotiated {
+    /// E.g. `claude-ai with protocol 2025-03-26 (roots, sampling)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let capabilities: Vec<&str> = self
+            .capabilities
+            .as_object()
+            .map(|capabilities| capabilities.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        write!(
+            f,
+            "{} with protocol {} ({})",
+            self.client_name.as_deref().unwrap_or("unnamed client"),
+            self.protocol_version,
+            capabilities.join(", ")
+        )
+    }
+}
+
+/// One client's connection: its handshake, what it may see, its requests in
+/// flight, what it subscribed to and read, and where messages the server
+/// sends unprompted go. Subscriptions end when the session is dropped.
+pub struct Session {
+    phase: Mutex<Phase>,
+    /// Set once `initialize` is answered.
+    negotiated: Mutex<Option<Negotiated>>,
+    /// When the client last sent a message.
+    last_active: Mutex<Instant>,
+    /// Bearer token the client authenticated with, if any.
+    token: Option<String>,
+    /// Chosen when the client identifies itself in `initialize`.
+    policy: Mutex<Arc<ClientPolicy>>,
+    /// `None` while the client has no stream open to receive them, in which
+    /// case they are dropped.
+    outbox: Mutex<Option<mpsc::UnboundedSender<Value>>>,
+    /// Least severe log messages the client wants, or `None` for none.
+    log_level: Mutex<Option<LogLevel>>,
+    /// Requests being handled, by ID, so the client can cancel them.
+    requests: Mutex<HashMap<String, CancellationToken>>,
+    /// Resource IDs subscribed to.
+    subscriptions: Mutex<HashSet<String>>,
+    memory: ContextMemory,
+    searches: SearchMemo,
+    watcher: Arc<ResourceWatcher>,
+    /// Requests sent to the client, such as pings, not yet answered, by
+    /// request ID.
+    pending: Mutex<HashMap<String, oneshot::Sender<Result<Value, RpcError>>>>,
+    sent_requests: AtomicU64,
+    /// The client's roots, asked for when first needed after they change.
+    roots: tokio::sync::Mutex<Option<Arc<Roots>>>,
+    roots_stale: AtomicBool,
+    /// Cancelled when the session is closed or dropped, which stops
+    /// forwarding updates and log messages.
+    closed: CancellationToken,
+}
+
+impl Session {
+    /// Starts a session, forwarding updates to the resources it subscribes
+    /// to, and `logs` once it sets a log level, into `outbox`.
+    pub fn new(
+        watcher: Arc<ResourceWatcher>,
+        logs: broadcast::Receiver<LogMessage>,
+        outbox: Option<mpsc::UnboundedSender<Value>>,
+        token: Option<String>,
+    ) -> Arc<Self> {
+        let closed = CancellationToken::new();
+        let session = Arc::new(Self {
+            phase: Mutex::new(Phase::AwaitingInitialize),
+            negotiated: Mutex::default(),
+            last_active: Mutex::new(Instant::now()),
+            token,
+            policy: Mutex::default(),
+            log_level: Mutex::default(),
+            requests: Mutex::default(),
+            outbox: Mutex::new(outbox),
+            subscriptions: Mutex::new(HashSet::new()),
+            memory: ContextMemory::default(),
+            searches: SearchMemo::default(),
+            watcher: watcher.clone(),
+            pending: Mutex::default(),
+            sent_requests: AtomicU64::new(0),
+            roots: tokio::sync::Mutex::default(),
+            roots_stale: AtomicBool::new(true),
+            closed: closed.clone(),
+        });
+        tokio::spawn(forward_updates(
+            Arc::downgrade(&session),
+            watcher.updates(),
+            closed.clone(),
+        ));
+        tokio::spawn(forward_logs(Arc::downgrade(&session), logs, closed));
+        session
+    }
+
+    pub fn phase(&self) -> Phase {
+        *self.phase.lock().unwrap()
+    }
+
+    /// Records the answered `initialize`. Returns false, changing nothing,
+    /// if the session was already initialized.
+    pub fn initialized(&self, negotiated: Negotiated) -> bool {
+        let mut phase = self.phase.lock().unwrap();
+        if *phase != Phase::AwaitingInitialize {
+            return false;
+        }
+        *phase = Phase::AwaitingInitialized;
+        *self.negotiated.lock().unwrap() = Some(negotiated);
+        true
+    }
+
+    /// What `initialize` agreed to, or `None` before it.
+    #[cfg(feature = "http-server")]
+    pub fn negotiated(&self) -> Option<Negotiated> {
+        self.negotiated.lock().unwrap().clone()
+    }
+
+    /// Records activity from the client, postponing expiry.
+    pub fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// Whether the client has been silent for `timeout`, with no request in
+    /// flight and no stream open to receive notifications.
+    #[cfg(feature = "http-server")]
+    pub fn is_idle(&self, timeout: Duration) -> bool {
+        let streaming = self
+            .outbox
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|outbox| !outbox.is_closed());
+        !streaming
+            && self.requests.lock().unwrap().is_empty()
+            && self.last_active.lock().unwrap().elapsed() >= timeout
+    }
+
+    /// Completes the handshake on `notifications/initialized`.
+    pub fn ready(&self) {
+        let mut phase = self.phase.lock().unwrap();
+        if *phase == Phase::AwaitingInitialized {
+            *phase = Phase::Ready;
+        }
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn policy(&self) -> Arc<ClientPolicy> {
+        self.policy.lock().unwrap().clone()
+    }
+
+    pub fn set_policy(&self, policy: Arc<ClientPolicy>) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Resources the client read recently.
+    pub fn memory(&self) -> &ContextMemory {
+        &self.memory
+    }
+
+    /// Results of the client's recent searches.
+    pub fn searches(&self) -> &SearchMemo {
+        &self.searches
+    }
+
+    /// Sends later messages to `outbox`, e.g. when a client opens a new
+    /// stream.
+    #[cfg(feature = "http-server")]
+    pub fn connect(&self, outbox: mpsc::UnboundedSender<Value>) {
+        *self.outbox.lock().unwrap() = Some(outbox);
+    }
+
+    /// Where messages to the client currently go.
+    pub fn outbox(&self) -> Option<mpsc::UnboundedSender<Value>> {
+        self.outbox.lock().unwrap().clone()
+    }
+
+    /// Queues a message for the client. Returns false if it has no open
+    /// stream.
+    pub fn send(&self, message: impl Serialize) -> bool {
+        let Ok(message) = serde_json::to_value(message) else {
+            return false;
+        };
+        match self.outbox.lock().unwrap().as_ref() {
+            Some(outbox) => outbox.send(message).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Sends log messages at least as severe as `level` from now on.
+    pub fn set_log_level(&self, level: LogLevel) {
+        *self.log_level.lock().unwrap() = Some(level);
+    }
+
+    /// Tracks a request until `finish`, returning the token that `cancel`
+    /// cancels.
+    pub fn start(&self, id: &Value) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.requests
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), token.clone());
+        token
+    }
+
+    pub fn finish(&self, id: &Value) {
+        self.requests.lock().unwrap().remove(&id.to_string());
+    }
+
+    /// Cancels a request in flight. Requests already finished, or never
+    /// seen, are ignored.
+    pub fn cancel(&self, id: &Value) {
+        if let Some(token) = self.requests.lock().unwrap().remove(&id.to_string()) {
+            token.cancel();
+        }
+    }
+
+    /// Subscribes to updates of a resource. Subscribing twice is a no-op.
+    pub async fn subscribe(&self, id: &str) -> Result<(), DomainError> {
+        if self.subscriptions.lock().unwrap().contains(id) {
+            return Ok(());
+        }
+        self.watcher.watch(id).await?;
+        if !self.subscriptions.lock().unwrap().insert(id.to_string()) {
+            // A concurrent subscribe got there first.
+            self.watcher.unwatch(id);
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, id: &str) {
+        if self.subscriptions.lock().unwrap().remove(id) {
+            self.watcher.unwatch(id);
+        }
+    }
+
+    /// Every resource found changed from now on, whoever watches it.
+    pub fn updates(&self) -> broadcast::Receiver<ResourceUpdate> {
+        self.watcher.updates()
+    }
+
+    /// Pings the client every `interval` from now on, closing the session
+    /// once a ping goes unanswered for `timeout`.
+    #[cfg(feature = "http-server")]
+    pub fn keep_alive(self: &Arc<Self>, interval: Duration, timeout: Duration) {
+        tokio::spawn(ping(
+            Arc::downgrade(self),
+            interval,
+            timeout,
+            self.closed.clone(),
+        ));
+    }
+
+    /// Whether the client declared `capability`, e.g. `sampling`, in
+    /// `initialize`.
+    pub fn supports(&self, capability: &str) -> bool {
+        self.negotiated
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|negotiated| negotiated.capabilities.get(capability).is_some())
+    }
+
+    /// Sends the client a request, returning where its reply will arrive,
+    /// or `None` if the client has no stream open to receive it.
+    fn send_request(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Option<(String, oneshot::Receiver<Result<Value, RpcError>>)> {
+        let number = self.sent_requests.fetch_add(1, Ordering::Relaxed);
+        let id = json!(format!("{}-{}", method.replace('/', "-"), number));
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.to_string(), tx);
+        let mut request = json!({ "jsonrpc": JSONRPC_VERSION, "id": id, "method": method });
+        if let Some(params) = params {
+            request["params"] = params;
+        }
+        if !self.send(request) {
+            self.pending.lock().unwrap().remove(&id.to_string());
+            return None;
+        }
+        Some((id.to_string(), rx))
+    }
+
+    /// Sends the client a request, such as `sampling/createMessage`, and
+    /// waits up to `timeout` for its reply.
+    pub async fn request(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcError> {
+        let Some((id, reply)) = self.send_request(method, Some(params)) else {
+            return Err(RpcError::new(
+                RpcError::INTERNAL_ERROR,
+                "The client has no stream open to receive requests",
+            ));
+        };
+        match tokio::time::timeout(timeout, reply).await {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(_)) => Err(RpcError::new(RpcError::INTERNAL_ERROR, "Session closed")),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(RpcError::new(
+                    RpcError::INTERNAL_ERROR,
+                    format!(
+                        "The client didn't answer {} within {}",
+                        method,
+                        humantime::format_duration(timeout)
+                    ),
+                ))
+            }
+        }
+    }
+
+    /// Asks the client for its roots again when they're next needed, on
+    /// `notifications/roots/list_changed`.
+    pub fn roots_changed(&self) {
+        self.roots_stale.store(true, Ordering::Relaxed);
+        // Results were restricted to the old roots.
+        self.searches.forget();
+    }
+
+    /// The roots the client wants to be shown, or `None` if it has none or
+    /// doesn't support them. Asked for with `roots/list` when first needed,
+    /// and again after they change; clients that can't be asked count as
+    /// having none.
+    pub async fn roots(&self) -> Option<Arc<Roots>> {
+        if !self.supports("roots") {
+            return None;
+        }
+        let mut roots = self.roots.lock().await;
+        if self.roots_stale.swap(false, Ordering::Relaxed) {
+            *roots = match self.request("roots/list", json!({}), ROOTS_TIMEOUT).await {
+                Ok(result) => Roots::from_result(result).map(Arc::new),
+                Err(e) => {
+                    tracing::debug!("Failed to list the client's roots: {}", e.message);
+                    None
+                }
+            };
+            if let Some(listed) = roots.as_ref() {
+                tracing::debug!("Client roots: {}", listed.uris().join(", "));
+            }
+        }
+        roots.clone()
+    }
+
+    /// Records the client's reply to a request the server sent, such as a
+    /// ping.
+    pub fn replied(&self, reply: Reply) {
+        self.touch();
+        if let Some(answered) = self.pending.lock().unwrap().remove(&reply.id.to_string()) {
+            let _ = answered.send(reply.outcome);
+        }
+    }
+
+    /// Ends the session from the server's side: its stream is closed and
+    /// nothing more is sent. Transports drop closed sessions.
+    #[cfg(feature = "http-server")]
+    pub fn close(&self) {
+        *self.outbox.lock().unwrap() = None;
+        self.closed.cancel();
+    }
+
+    #[cfg(feature = "http-server")]
+    pub fn is_closed(&self) -> bool {
+        self.closed.is_cancelled()
+    }
+
+    /// Waits until the session is closed.
+    #[cfg(feature = "http-server")]
+    pub async fn wait_closed(&self) {
+        self.closed.cancelled().await
+    }
+}
+
+/// Sessions of a network transport by ID. Sessions idle for longer than
+/// the timeout are ended, for clients that go away without saying so.
+#[cfg(feature = "http-server")]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    idle_timeout: Duration,
+}
+
+#[cfg(feature = "http-server")]
+impl SessionStore {
+    /// Starts a store, and a task ending its idle sessions until the store
+    /// is dropped.
+    pub fn new(idle_timeout: Duration) -> Arc<Self> {
+        let store = Arc::new(Self {
+            sessions: Mutex::new(HashMap::new()),
+            idle_timeout,
+        });
+        tokio::spawn(expire_sessions(Arc::downgrade(&store)));
+        store
+    }
+
+    pub fn insert(&self, id: &str, session: Arc<Session>) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), session);
+    }
+
+    /// The session called `id`, marked as active.
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let session = self.sessions.lock().unwrap().get(id).cloned()?;
+        session.touch();
+        Some(session)
+    }
+
+    /// Ends a session, returning whether it existed.
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().remove(id).is_some()
+    }
+}
+
+/// Ends the store's idle sessions, checking a few times per timeout.
+#[cfg(feature = "http-server")]
+async fn expire_sessions(store: Weak<SessionStore>) {
+    let Some(timeout) = store.upgrade().map(|store| store.idle_timeout) else {
+        return;
+    };
+    let mut interval =
+        tokio::time::interval((timeout / 4).clamp(Duration::from_secs(1), Duration::from_secs(60)));
+    loop {
+        interval.tick().await;
+        let Some(store) = store.upgrade() else {
+            return;
+        };
+        store.sessions.lock().unwrap().retain(|id, session| {
+            let idle = session.is_idle(timeout);
+            if idle {
+                tracing::debug!("Expired idle MCP session {}", id);
+            }
+            !idle && !session.is_closed()
+        });
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.closed.cancel();
+        for id in self.subscriptions.get_mut().unwrap().drain() {
+            self.watcher.unwatch(&id);
+        }
+    }
+}
+
+/// Sends the session's client a `ping` every `interval` until the session
+/// is closed or dropped, closing it when a ping isn't answered in time.
+#[cfg(feature = "http-server")]
+async fn ping(
+    session: Weak<Session>,
+    interval: Duration,
+    timeout: Duration,
+    closed: CancellationToken,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    // The first tick is immediate; the client just connected.
+    ticks.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = closed.cancelled() => return,
+        }
+        let answered = {
+            let Some(session) = session.upgrade() else {
+                return;
+            };
+            match session.send_request("ping", None) {
+                Some((_, reply)) => reply,
+                // Nowhere to send it; the transport notices on its own.
+                None => continue,
+            }
+        };
+
+        let answered = tokio::select! {
+            answered = tokio::time::timeout(timeout, answered) => answered,
+            _ = closed.cancelled() => return,
+        };
+        // Any reply will do, even an error.
+        if !matches!(answered, Ok(Ok(_))) {
+            if let Some(session) = session.upgrade() {
+                tracing::info!(
+                    "Closing MCP session: ping unanswered after {}",
+                    humantime::format_duration(timeout)
+                );
+                session.close();
+            }
+            return;
+        }
+    }
+}
+
+/// Tells the session's client about updates to resources it subscribed to,
+/// until the session is dropped.
+async fn forward_updates(
+    session: Weak<Session>,
+    mut updates: broadcast::Receiver<ResourceUpdate>,
+    closed: CancellationToken,
+) {
+    loop {
+        let update = tokio::select! {
+            update = updates.recv() => update,
+            _ = closed.cancelled() => return,
+        };
+        let update = match update {
+            Ok(update) => update,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!("Dropped {} resource updates for a slow session", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+
+        // What changed goes along in `_meta`, so clients can tell whether
+        // it matters without reading the resource again.
+        if session
+            .subscriptions
+            .lock()
+            .unwrap()
+            .contains(&update.resource.id)
+        {
+            session.send(Notification::new(
+                "notifications/resources/updated",
+                json!({
+                    "uri": update.resource.uri(),
+                    "title": update.resource.title,
+                    "_meta": { "changes": update.changes },
+                }),
+            ));
+        }
+    }
+}
+
+/// Sends the session's client the log messages at or above its level, until
+/// the session is dropped.
+async fn forward_logs(
+    session: Weak<Session>,
+    mut logs: broadcast::Receiver<LogMessage>,
+    closed: CancellationToken,
+) {
+    loop {
+        let message = tokio::select! {
+            message = logs.recv() => message,
+            _ = closed.cancelled() => return,
+        };
+        let message = match message {
+            Ok(message) => message,
+            // Logging the loss would only add to the backlog.
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+
+        let level = *session.log_level.lock().unwrap();
+        if level.is_some_and(|level| message.level >= level) {
+            session.send(Notification::new("notifications/message", json!(message)));
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response as HttpResponse},
+    routing::get,
+    Json, Router,
+};
+use std::sync::Arc;
+
+use super::http::bearer_token;
+use super::server::McpServer;
+use crate::domain::DomainError;
+
+/// Path a snapshot of the server's cache is downloaded from.
+pub const SNAPSHOT_PATH: &str = "/snapshot";
+
+/// Routes for `GET /snapshot`, which returns the server's cached resources
+/// as a snapshot for `bootstrap --from` to start another install's cache
+/// from. It holds what the bearer token's policy lets the client read.
+pub fn router(server: Arc<McpServer>) -> Router {
+    Router::new()
+        .route(SNAPSHOT_PATH, get(snapshot))
+        .with_state(server)
+}
+
+async fn snapshot(State(server): State<Arc<McpServer>>, headers: HeaderMap) -> HttpResponse {
+    let token = bearer_token(&headers);
+    match server.snapshot(token.as_deref()).await {
+        Ok(snapshot) => {
+            tracing::info!(
+                "Served a snapshot of {} resources",
+                snapshot.resources.len()
+            );
+            Json(snapshot).into_response()
+        }
+        Err(e @ DomainError::StorageError(_)) => {
+            (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,49 @@
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::{UnixListener, UnixStream};
+
+use super::server::McpServer;
+use super::stdio;
+
+/// Serves MCP on a Unix domain socket at `path`, speaking newline-delimited
+/// JSON-RPC as over stdio, one session per connection. The socket is only
+/// accessible to the current user; loosen its permissions to share it.
+pub async fn serve(server: Arc<McpServer>, path: &Path) -> std::io::Result<()> {
+    remove_stale(path).await?;
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    tracing::info!("Serving MCP on unix socket {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            let (input, output) = stream.into_split();
+            if let Err(e) = stdio::serve_lines(server, input, output).await {
+                tracing::debug!("Unix socket connection failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Removes a socket left behind by a server that no longer runs. Refuses
+/// to replace anything else, including a socket still being served.
+async fn remove_stale(path: &Path) -> std::io::Result<()> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AddrInUse,
+            format!("{} is already being served", path.display()),
+        ));
+    }
+    std::fs::remove_file(path)
+}
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response as HttpResponse,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, Stream, StreamExt};
+
+use super::http::bearer_token;
+use super::protocol::{parse_reply, parse_request};
+use super::server::McpServer;
+use super::session::SessionStore;
+
+/// Path clients open the event stream on.
+const SSE_PATH: &str = "/sse";
+
+/// Path clients post messages to, with their session ID as a query parameter.
+const MESSAGE_PATH: &str = "/message";
+
+#[derive(Clone)]
+struct AppState {
+    server: Arc<McpServer>,
+    /// Open event streams by session ID.
+    sessions: Arc<SessionStore>,
+}
+
+/// Ends a session when its event stream is dropped, i.e. when the client
+/// disconnects.
+struct SessionGuard {
+    id: String,
+    sessions: Arc<SessionStore>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.remove(&self.id);
+        tracing::debug!("Closed SSE session {}", self.id);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageParams {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+/// Routes for MCP over HTTP with Server-Sent Events, the transport of
+/// protocol revision 2024-11-05. Each `GET /sse` opens a session: the first
+/// event names the URL to post requests to, and responses arrive on the
+/// stream as `message` events, as do notifications.
+pub fn router(server: Arc<McpServer>) -> Router {
+    let state = AppState {
+        sessions: SessionStore::new(server.session_idle_timeout()),
+        server,
+    };
+
+    Router::new()
+        .route(SSE_PATH, get(open_stream))
+        .route(MESSAGE_PATH, post(post_message))
+        .with_state(state)
+}
+
+async fn open_stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let session_id = uuid::Uuid::new_v4().simple().to_string();
+    let (tx, rx) = mpsc::unbounded_channel();
+    let session = state.server.session(Some(tx), bearer_token(&headers));
+    state.sessions.insert(&session_id, session.clone());
+    state.server.keep_alive(&session);
+    tracing::debug!("Opened SSE session {}", session_id);
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("{}?sessionId={}", MESSAGE_PATH, session_id));
+    let guard = SessionGuard {
+        id: session_id,
+        sessions: state.sessions.clone(),
+    };
+    let messages = UnboundedReceiverStream::new(rx).map(move |message| {
+        let _session = &guard;
+        let data = serde_json::to_string(&message).unwrap_or_default();
+        Event::default().event("message").data(data)
+    });
+
+    let stream = tokio_stream::once(endpoint).chain(messages).map(Ok);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Accepts one JSON-RPC message for a session. The response is sent on the
+/// session's event stream, so this only acknowledges receipt.
+async fn post_message(
+    State(state): State<AppState>,
+    Query(params): Query<MessageParams>,
+    body: String,
+) -> HttpResponse {
+    let Some(session) = state.sessions.get(&params.session_id) else {
+        return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+    };
+
+    if let Some(reply) = parse_reply(&body) {
+        session.replied(reply);
+        return StatusCode::ACCEPTED.into_response();
+    }
+    let request = match parse_request(&body) {
+        Ok(request) => request,
+        Err(response) => return (StatusCode::BAD_REQUEST, Json(*response)).into_response(),
+    };
+
+    let server = state.server.clone();
+    tokio::spawn(async move {
+        if let Some(response) = server.handle(&session, request).await {
+            session.send(response);
+        }
+    });
+
+    StatusCode::ACCEPTED.into_response()
+}
@@ -0,0 +1,69 @@
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+use super::protocol::parse_message;
+use super::server::McpServer;
+
+/// Serves MCP over newline-delimited JSON-RPC on stdin/stdout until stdin
+/// closes; see `serve_lines`.
+pub async fn serve(server: Arc<McpServer>) -> std::io::Result<()> {
+    serve_lines(server, tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// Serves MCP over newline-delimited JSON-RPC until `input` closes.
+/// Requests are handled concurrently; responses are written to `output` as
+/// they complete, and batches are answered with one array. The client is a
+/// single session, whose notifications are written alongside.
+pub async fn serve_lines<R, W>(
+    server: Arc<McpServer>,
+    input: R,
+    mut output: W,
+) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+
+    let writer = tokio::spawn(async move {
+        while let Some(response) = rx.recv().await {
+            let mut line = serde_json::to_vec(&response)?;
+            line.push(b'\n');
+            output.write_all(&line).await?;
+            output.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+
+    let session = server.session(Some(tx.clone()), None);
+    let mut lines = BufReader::new(input).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message = match parse_message(&line) {
+            Ok(message) => message,
+            Err(response) => {
+                let _ = tx.send(json!(response));
+                continue;
+            }
+        };
+
+        let server = server.clone();
+        let session = session.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            if let Some(reply) = server.handle_message(&session, message).await {
+                let _ = tx.send(reply);
+            }
+        });
+    }
+
+    // The writer finishes once every in-flight request has replied.
+    drop(session);
+    drop(tx);
+    writer.await.map_err(std::io::Error::other)?
+}
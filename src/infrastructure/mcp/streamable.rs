@@ -0,0 +1,221 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response as HttpResponse,
+    },
+    routing::get,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
+
+use super::http::{bearer_token, SESSION_HEADER};
+use super::protocol::{parse_message, Message, Response, RpcError};
+use super::server::McpServer;
+use super::session::{Session, SessionStore};
+
+/// The transport's single endpoint.
+const MCP_PATH: &str = "/mcp";
+
+/// Header clients send the negotiated protocol revision in.
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+#[derive(Clone)]
+struct AppState {
+    server: Arc<McpServer>,
+    sessions: Arc<SessionStore>,
+}
+
+/// Routes for the streamable HTTP transport of protocol revision 2025-03-26.
+/// Clients POST messages to `/mcp` and get the responses back as JSON, or
+/// streamed as events if they accept `text/event-stream`. `initialize` starts
+/// a session whose ID is returned in the `Mcp-Session-Id` header and must
+/// accompany every later request; `GET /mcp` opens a stream of the
+/// session's notifications, and `DELETE /mcp` ends it. Sessions also end
+/// once idle for the server's session idle timeout.
+pub fn router(server: Arc<McpServer>) -> Router {
+    let state = AppState {
+        sessions: SessionStore::new(server.session_idle_timeout()),
+        server,
+    };
+
+    Router::new()
+        .route(
+            MCP_PATH,
+            get(open_stream).post(post_message).delete(end_session),
+        )
+        .with_state(state)
+}
+
+async fn post_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> HttpResponse {
+    let (requests, batch) = match parse_message(&body) {
+        Ok(Message::Single(request)) => (vec![Ok(request)], false),
+        Ok(Message::Batch(requests)) => (requests, true),
+        Ok(Message::Reply(reply)) => {
+            return match check_session(&state, &headers) {
+                Ok((_, session)) => {
+                    session.replied(reply);
+                    StatusCode::ACCEPTED.into_response()
+                }
+                Err(rejection) => *rejection,
+            };
+        }
+        Err(response) => return (StatusCode::BAD_REQUEST, Json(*response)).into_response(),
+    };
+
+    let initializing = requests
+        .iter()
+        .any(|request| matches!(request, Ok(request) if request.method == "initialize"));
+    let (session, new_session) = if initializing {
+        if batch {
+            let error = RpcError::new(
+                RpcError::INVALID_REQUEST,
+                "initialize can't be part of a batch",
+            );
+            let response = Response::failure(serde_json::Value::Null, error);
+            return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+        }
+        let session_id = uuid::Uuid::new_v4().simple().to_string();
+        let session = state.server.session(None, bearer_token(&headers));
+        state.sessions.insert(&session_id, session.clone());
+        (session, Some(session_id))
+    } else {
+        match check_session(&state, &headers) {
+            Ok((_, session)) => (session, None),
+            Err(rejection) => return *rejection,
+        }
+    };
+
+    // Notifications get no reply; acknowledge them and handle them in the
+    // background.
+    if requests
+        .iter()
+        .all(|request| matches!(request, Ok(request) if request.id.is_none()))
+    {
+        let server = state.server.clone();
+        tokio::spawn(async move { server.handle_batch(&session, requests).await });
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    let accepts_stream = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+
+    let mut response = if accepts_stream {
+        // Each response is sent as soon as it is ready, after any progress
+        // notifications for it, and the stream ends after the last one.
+        let (tx, rx) = mpsc::unbounded_channel::<Value>();
+        let server = state.server.clone();
+        tokio::spawn(async move {
+            for request in requests {
+                let response = match request {
+                    Ok(request) => server.handle_via(&session, Some(&tx), request).await,
+                    Err(response) => Some(*response),
+                };
+                if let Some(response) = response {
+                    if tx.send(json!(response)).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let events = UnboundedReceiverStream::new(rx).map(|response| {
+            let data = serde_json::to_string(&response).unwrap_or_default();
+            Ok::<_, Infallible>(Event::default().event("message").data(data))
+        });
+        Sse::new(events).into_response()
+    } else {
+        let mut responses = state.server.handle_batch(&session, requests).await;
+        match (batch, responses.pop()) {
+            (false, Some(response)) => Json(response).into_response(),
+            (true, Some(last)) => {
+                responses.push(last);
+                Json(responses).into_response()
+            }
+            (_, None) => StatusCode::ACCEPTED.into_response(),
+        }
+    };
+
+    if let Some(session_id) = new_session {
+        if let Ok(value) = HeaderValue::from_str(&session_id) {
+            response.headers_mut().insert(SESSION_HEADER, value);
+        }
+    }
+    response
+}
+
+/// Streams the session's notifications, replacing any stream opened
+/// earlier.
+async fn open_stream(State(state): State<AppState>, headers: HeaderMap) -> HttpResponse {
+    let session = match check_session(&state, &headers) {
+        Ok((_, session)) => session,
+        Err(rejection) => return *rejection,
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel::<Value>();
+    session.connect(tx);
+    let events = UnboundedReceiverStream::new(rx).map(|message| {
+        let data = serde_json::to_string(&message).unwrap_or_default();
+        Ok::<_, Infallible>(Event::default().event("message").data(data))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn end_session(State(state): State<AppState>, headers: HeaderMap) -> HttpResponse {
+    match check_session(&state, &headers) {
+        Ok((session_id, _)) => {
+            state.sessions.remove(&session_id);
+            tracing::debug!("Ended MCP session {}", session_id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(rejection) => *rejection,
+    }
+}
+
+/// The request's session and its ID, or the response rejecting it: 400
+/// without a session or with a protocol version header other than the one
+/// the session negotiated, 404 for unknown, ended or expired sessions so
+/// clients start a new one.
+fn check_session(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(String, Arc<Session>), Box<HttpResponse>> {
+    let session_id = headers
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| {
+            Box::new((StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response())
+        })?;
+
+    let session = state
+        .sessions
+        .get(session_id)
+        .ok_or_else(|| Box::new((StatusCode::NOT_FOUND, "Unknown session").into_response()))?;
+
+    let requested = headers
+        .get(PROTOCOL_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if let (Some(requested), Some(negotiated)) = (requested, session.negotiated()) {
+        if requested != negotiated.protocol_version {
+            let message = format!(
+                "Protocol version {} doesn't match the session's {}",
+                requested, negotiated.protocol_version
+            );
+            return Err(Box::new((StatusCode::BAD_REQUEST, message).into_response()));
+        }
+    }
+    Ok((session_id.to_string(), session))
+}
@@ -0,0 +1,252 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::policy::ClientPolicy;
+use crate::{
+    application::ResourceService,
+    domain::{DomainError, Query, QuerySource, Resource},
+};
+
+/// Most resources listed when a collection is read.
+const COLLECTION_LIMIT: usize = 100;
+
+/// The template's provider when any configured one fills it in.
+const ANY_PROVIDER: &str = "*";
+
+struct Template {
+    uri_template: &'static str,
+    name: &'static str,
+    description: &'static str,
+    provider: &'static str,
+}
+
+const TEMPLATES: [Template; 5] = [
+    Template {
+        uri_template: "notion://page/{page_id}",
+        name: "notion_page",
+        description: "Content of a Notion page",
+        provider: "notion",
+    },
+    Template {
+        uri_template: "notion://database/{database_id}",
+        name: "notion_database",
+        description: "Rows of a Notion database, each linking to its page",
+        provider: "notion",
+    },
+    Template {
+        uri_template: "linear://issue/{issue_id}",
+        name: "linear_issue",
+        description: "A Linear issue, by ID or identifier such as ENG-123",
+        provider: "linear",
+    },
+    Template {
+        uri_template: "linear://team/{team_key}/issues",
+        name: "linear_team_issues",
+        description: "Most recently updated issues of a Linear team, each linking to the issue",
+        provider: "linear",
+    },
+    Template {
+        uri_template: "mcp-rs://resource/{provider}_{id}",
+        name: "resource",
+        description: "A resource of any provider, by the provider's ID for it, e.g. \
+                      mcp-rs://resource/zendesk_ticket_123",
+        provider: ANY_PROVIDER,
+    },
+];
+
+/// The `resources/templates/list` result: templates of the configured
+/// providers `policy` exposes.
+pub fn list(service: &ResourceService, policy: &ClientPolicy) -> Value {
+    let providers: Vec<&str> = service
+        .list_providers()
+        .into_iter()
+        .filter(|provider| policy.allows_provider(provider))
+        .collect();
+    let templates: Vec<Value> = TEMPLATES
+        .iter()
+        .filter(|template| {
+            providers.iter().any(|provider| {
+                template.provider == ANY_PROVIDER
+                    || provider.eq_ignore_ascii_case(template.provider)
+            })
+        })
+        .map(|template| {
+            json!({
+                "uriTemplate": template.uri_template,
+                "name": template.name,
+                "description": template.description,
+                "mimeType": "text/markdown",
+            })
+        })
+        .collect();
+    json!({ "resourceTemplates": templates })
+}
+
+pub fn exists(uri_template: &str) -> bool {
+    TEMPLATES
+        .iter()
+        .any(|template| template.uri_template == uri_template)
+}
+
+/// The ID of the resource `uri` names, if it fits a template: it has to
+/// name a resource of a configured provider `policy` exposes. Unknown
+/// providers are reported as invalid, and hidden ones as not found.
+pub fn resource_id(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    uri: &str,
+) -> Result<String, DomainError> {
+    let id = Resource::id_from_uri(uri)
+        .ok_or_else(|| DomainError::ResourceNotFound(format!("Unknown resource URI: {}", uri)))?;
+    policy.check_resource(&id)?;
+    match id.split_once('_') {
+        Some((provider, rest)) if !rest.is_empty() && service.get_provider(provider).is_some() => {
+            Ok(id)
+        }
+        Some((provider, rest)) if !rest.is_empty() => Err(DomainError::InvalidQuery(format!(
+            "{} names a resource of {}, which isn't configured; configured providers: {}",
+            uri,
+            provider,
+            configured(service, policy)
+        ))),
+        _ => Err(DomainError::InvalidQuery(format!(
+            "{} doesn't fit a resource template; resources/templates/list shows the URIs \
+             this server reads",
+            uri
+        ))),
+    }
+}
+
+/// The configured providers `policy` exposes, for error messages.
+fn configured(service: &ResourceService, policy: &ClientPolicy) -> String {
+    let mut providers: Vec<String> = service
+        .list_providers()
+        .into_iter()
+        .filter(|provider| policy.allows_provider(provider))
+        .map(str::to_lowercase)
+        .collect();
+    providers.sort();
+    if providers.is_empty() {
+        "none".to_string()
+    } else {
+        providers.join(", ")
+    }
+}
+
+/// A URI naming a set of resources rather than one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Collection {
+    NotionDatabase(String),
+    LinearTeam(String),
+}
+
+impl Collection {
+    pub fn parse(uri: &str) -> Option<Self> {
+        if let Some(database_id) = uri.strip_prefix("notion://database/") {
+            Some(Collection::NotionDatabase(database_id.to_string()))
+        } else {
+            uri.strip_prefix("linear://team/")
+                .and_then(|rest| rest.strip_suffix("/issues"))
+                .map(|key| Collection::LinearTeam(key.to_uppercase()))
+        }
+        .filter(|collection| !collection.key().is_empty() && !collection.key().contains('/'))
+    }
+
+    pub fn provider(&self) -> &'static str {
+        match self {
+            Collection::NotionDatabase(_) => "notion",
+            Collection::LinearTeam(_) => "linear",
+        }
+    }
+
+    fn key(&self) -> &str {
+        match self {
+            Collection::NotionDatabase(key) | Collection::LinearTeam(key) => key,
+        }
+    }
+
+    fn query(&self) -> Query {
+        let (source, filters) = match self {
+            // Rows are rendered from their properties, without fetching
+            // every page's content.
+            Collection::NotionDatabase(database_id) => (
+                QuerySource::Notion,
+                HashMap::from([
+                    ("database_id".to_string(), database_id.clone()),
+                    ("records".to_string(), "true".to_string()),
+                ]),
+            ),
+            Collection::LinearTeam(key) => (
+                QuerySource::Linear,
+                HashMap::from([("team".to_string(), key.clone())]),
+            ),
+        };
+        Query {
+            source,
+            filters,
+            limit: Some(COLLECTION_LIMIT),
+        }
+    }
+
+    /// Lists the collection as Markdown, one line per resource `policy`
+    /// exposes linking to its URI.
+    pub async fn read(
+        &self,
+        service: &ResourceService,
+        policy: &ClientPolicy,
+    ) -> Result<String, DomainError> {
+        if service.get_provider(self.provider()).is_none() {
+            return Err(DomainError::NotConfigured {
+                provider: self.provider().to_string(),
+            });
+        }
+        let mut result = service.fetch_resources(&self.query()).await?;
+        if let (Some(failure), true) = (result.errors.first(), result.resources.is_empty()) {
+            return Err(DomainError::ProviderError(format!(
+                "{} unavailable: {}",
+                failure.provider, failure.error
+            )));
+        }
+
+        let truncated = result.resources.len() >= COLLECTION_LIMIT;
+        result
+            .resources
+            .retain(|resource| policy.allows_resource(resource));
+
+        let mut content = match self {
+            Collection::NotionDatabase(id) => format!("# Notion database {}\n\n", id),
+            Collection::LinearTeam(key) => format!("# Linear team {} issues\n\n", key),
+        };
+        if result.resources.is_empty() {
+            content.push_str("No resources.\n");
+        }
+        for resource in &result.resources {
+            let _ = writeln!(content, "{}", entry(resource));
+        }
+        if truncated {
+            let _ = writeln!(content, "\nOnly the first {} are listed.", COLLECTION_LIMIT);
+        }
+        Ok(content)
+    }
+}
+
+fn entry(resource: &Resource) -> String {
+    let label = match resource.short_id() {
+        Some(short_id) => format!("{}: {}", short_id, resource.title),
+        None => resource.title.clone(),
+    };
+    let state = resource
+        .metadata
+        .get("state")
+        .and_then(Value::as_str)
+        .map(|state| format!("{}, ", state))
+        .unwrap_or_default();
+    format!(
+        "- [{}]({}) ({}updated {})",
+        label,
+        resource.uri(),
+        state,
+        resource.updated_at.format("%Y-%m-%d")
+    )
+}
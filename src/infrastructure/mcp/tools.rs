@@ -0,0 +1,1211 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use schemars::{generate::SchemaSettings, JsonSchema};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::cursor::{self, OffsetCursor};
+use super::memory::{ContextMemory, SearchMemo};
+use super::policy::ClientPolicy;
+use super::protocol::{parse_params, RpcError};
+use super::roots::Roots;
+use super::session::Session;
+use crate::{
+    application::{
+        chunks::{self, DEFAULT_CHUNKS},
+        search::SearchOptions,
+        shape::{shape, ShapeOptions, CHARS_PER_TOKEN},
+        sync::SyncLimits,
+        ResourceService,
+    },
+    domain::{Change, DomainError, Query, QueryResult, QuerySource, Resource},
+};
+
+/// Characters of content included per search result.
+const EXCERPT_CHARS: usize = 300;
+
+/// Results per `advanced_search` page unless the caller asks otherwise.
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Resources listed per provider when `advanced_search` has to see more
+/// than one page's worth to filter by date or sort.
+const MAX_CANDIDATES: usize = 200;
+
+/// Tokens of content handed to the client's model to summarize.
+const MAX_SUMMARIZED_TOKENS: usize = 8000;
+
+/// Summary length unless the caller asks otherwise, in tokens.
+const DEFAULT_SUMMARY_TOKENS: usize = 400;
+
+/// Passages `answer_question` answers from unless the caller asks otherwise,
+/// and at most.
+const DEFAULT_PASSAGES: usize = 8;
+const MAX_PASSAGES: usize = 20;
+
+/// Terms of a question searched for, each on its own, and how many
+/// searches run at once.
+const MAX_SEARCHED_TERMS: usize = 6;
+const SEARCH_CONCURRENCY: usize = 4;
+
+/// Tokens of passages handed to the client's model to answer from.
+const MAX_ANSWER_CONTEXT_TOKENS: usize = 8000;
+
+/// Answer length unless the caller asks otherwise, in tokens.
+const DEFAULT_ANSWER_TOKENS: usize = 600;
+
+/// `[2]` or `[1, 3]` in an answer.
+static CITATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[(\d+(?:\s*,\s*\d+)*)\]").unwrap());
+
+/// How long the client gets to answer a sampling request, which may wait
+/// for the user to approve it.
+const SAMPLING_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Longest `watch_resource` waits for a change.
+const MAX_WATCH_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// Search the configured providers for resources matching a text query.
+/// The same search repeated within a minute returns the same results,
+/// marked with when they were `memoized`.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SearchResourcesArgs {
+    /// Text to search for.
+    query: String,
+    /// Providers or source groups to search, `all` for every configured
+    /// provider. Without them the query is routed: an issue identifier, ID
+    /// or link is fetched, and a query about docs, tickets or mail goes to
+    /// the providers holding those first. `route` in the result says where
+    /// it went.
+    #[serde(default)]
+    sources: Vec<QuerySource>,
+    /// Maximum number of results to return.
+    limit: Option<usize>,
+    /// Include resources archived or deleted upstream.
+    #[serde(default)]
+    include_archived: bool,
+    /// Oldest results acceptable, e.g. `15m` or `1h`. Fresh enough local,
+    /// cached or memoized results are returned without calling the
+    /// provider; `0s` always searches live.
+    max_staleness: Option<String>,
+    /// Return the passages of the results that match best instead of
+    /// whole resources, each with its resource's URI, the headings it is
+    /// under and where it is in the content, in characters. `limit` then
+    /// counts passages (default 10).
+    #[serde(default)]
+    chunks: bool,
+}
+
+/// Fetch the content and metadata of one resource, optionally just one
+/// section, as Markdown or plain text, within a token budget.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct FetchResourceArgs {
+    /// Resource ID (e.g. `linear_<issue_id>`), URI (e.g. `notion://page/<page_id>`),
+    /// Linear issue identifier (e.g. `ENG-123`), or alias.
+    id: String,
+    #[serde(flatten)]
+    shape: ShapeOptions,
+}
+
+/// Search or list resources with a structured query. Without `query`,
+/// resources are listed using `filters`; date ranges and sorting are then
+/// applied to at most 200 resources per provider. The same search repeated
+/// within a minute returns the same results, marked with when they were
+/// `memoized`.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct AdvancedSearchArgs {
+    /// Text to search for. Omit to list resources instead.
+    query: Option<String>,
+    /// Providers or source groups to search. Defaults to all configured
+    /// providers.
+    #[serde(default)]
+    sources: Vec<QuerySource>,
+    /// Provider filters for listing, e.g. `database_id` (Notion), or
+    /// `assignee` (`me` or a user), `cycle` (`current`) and `project`
+    /// (Linear).
+    #[serde(default)]
+    filters: HashMap<String, String>,
+    /// Only resources updated at or after this time (RFC 3339 or
+    /// `YYYY-MM-DD`).
+    updated_after: Option<String>,
+    /// Only resources updated before this time.
+    updated_before: Option<String>,
+    /// Only resources created at or after this time.
+    created_after: Option<String>,
+    /// Only resources created before this time.
+    created_before: Option<String>,
+    /// Order of the results. Defaults to the providers' own order.
+    #[serde(default)]
+    sort: SortField,
+    /// Defaults to newest first for dates and A to Z for titles.
+    order: Option<SortOrder>,
+    /// Results per page (default 20).
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page, to continue from it.
+    cursor: Option<String>,
+    /// Include resources archived or deleted upstream.
+    #[serde(default)]
+    include_archived: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum SortField {
+    /// As the providers returned them, e.g. by relevance for searches.
+    #[default]
+    Relevance,
+    Updated,
+    Created,
+    Title,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Pull resources into the local cache so later searches can be answered
+/// from it, tombstoning those gone upstream. This can take minutes; progress
+/// is reported per batch.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SyncResourcesArgs {
+    /// Provider or source group to sync. Defaults to all configured
+    /// providers.
+    source: Option<QuerySource>,
+    /// Provider filters, e.g. `database_id` for Notion or `team` for Linear.
+    #[serde(default)]
+    filters: HashMap<String, String>,
+    /// Stop after this many resources per provider. Capped syncs don't
+    /// tombstone resources missing from the listing.
+    limit: Option<usize>,
+}
+
+/// Return the resources read earlier in this session, most recent first,
+/// to reuse them instead of fetching them again.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct RecallContextArgs {
+    /// Maximum number of resources to return.
+    limit: Option<usize>,
+    /// Return an excerpt of each resource instead of its full content.
+    #[serde(default)]
+    summarize: bool,
+}
+
+/// Create a Linear issue and return it. This changes data in Linear.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct CreateLinearIssueArgs {
+    /// Issue title.
+    title: String,
+    /// Issue description, in Markdown.
+    #[serde(default)]
+    description: String,
+    /// Team to create the issue in: its key (e.g. `ENG`), name or ID.
+    team: String,
+    /// Names of existing team or workspace labels to add.
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+/// Append Markdown to the end of a Notion page, e.g. meeting notes, and
+/// return the page. This changes data in Notion.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct AppendToNotionPageArgs {
+    /// Page ID (e.g. `notion_<page_id>`), URI, Notion link or alias.
+    page: String,
+    /// Markdown to append: headings, paragraphs, lists, to-dos, quotes,
+    /// code blocks and dividers, with bold, italic, code and links.
+    content: String,
+    /// The page's `version` as last fetched. If given, nothing is appended
+    /// when the page has changed since, and the result is a
+    /// `version_conflict` error.
+    version: Option<String>,
+}
+
+/// Replace the title and/or content of a Notion page and return the page.
+/// The change is made only if the page is still at the `version` it was
+/// read at; if someone else changed it since, the result is a
+/// `version_conflict` error with the current version, and the page should
+/// be fetched again and the change redone on what it now says. This
+/// changes data in Notion.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct UpdateNotionPageArgs {
+    /// Page ID (e.g. `notion_<page_id>`), URI, Notion link or alias.
+    page: String,
+    /// New title.
+    title: Option<String>,
+    /// New content, in Markdown, replacing all of it.
+    content: Option<String>,
+    /// The page's `version`, as returned by `fetch_resource` or a write.
+    version: String,
+}
+
+/// Summarize one resource using the client's own model, through MCP
+/// sampling; the client may ask the user to approve the request. Only
+/// works with clients that support sampling.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SummarizeResourceArgs {
+    /// Resource ID, URI, Linear issue identifier (e.g. `ENG-123`), or alias.
+    id: String,
+    /// Summarize only the section under this heading.
+    section: Option<String>,
+    /// What the summary should concentrate on, e.g. `decisions and open
+    /// questions`.
+    focus: Option<String>,
+    /// Longest summary, in tokens (default 400).
+    max_tokens: Option<usize>,
+}
+
+/// Answer a question from what the configured providers hold, citing the
+/// resources the answer comes from. The passages most relevant to the
+/// question are looked up, and the client's own model answers from them
+/// through MCP sampling; the client may ask the user to approve the
+/// request. Only works with clients that support sampling.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct AnswerQuestionArgs {
+    /// The question, e.g. `What did we decide about the checkout redesign?`.
+    question: String,
+    /// Providers or source groups to search. Defaults to all configured
+    /// providers.
+    #[serde(default)]
+    sources: Vec<QuerySource>,
+    /// Passages to answer from (default 8, at most 20).
+    max_passages: Option<usize>,
+    /// Longest answer, in tokens (default 600).
+    max_tokens: Option<usize>,
+}
+
+/// Watch resources for changes upstream, e.g. the issues an agent acts on.
+/// Watched resources are polled, and the client is sent
+/// `notifications/resources/updated` when one changes, until the session
+/// ends or `unwatch_resource` is called. With `wait_secs`, the call also
+/// waits for a change and returns the resources that changed, each with a
+/// compact summary and diff of what changed.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WatchResourceArgs {
+    /// Resource IDs, URIs, Linear issue identifiers (e.g. `ENG-123`), or
+    /// aliases.
+    ids: Vec<String>,
+    /// Seconds to wait for one of the resources to change, at most 300.
+    /// Without it, the call returns as soon as they are watched.
+    wait_secs: Option<u64>,
+}
+
+/// Stop watching resources watched with `watch_resource`.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct UnwatchResourceArgs {
+    /// Resource IDs, URIs, Linear issue identifiers, or aliases.
+    ids: Vec<String>,
+}
+
+/// List the providers this server can reach.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ListProvidersArgs {}
+
+#[derive(Debug, Deserialize)]
+struct CallParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+/// Tools that change data upstream, which exist only on servers started
+/// with `--allow-writes`.
+const WRITE_TOOLS: [&str; 3] = [
+    "create_linear_issue",
+    "append_to_notion_page",
+    "update_notion_page",
+];
+
+/// The `tools/list` entries `policy` exposes, including the write tools
+/// when `writes` is set. Input schemas are generated from the argument
+/// types, and descriptions from their doc comments.
+pub fn definitions(policy: &ClientPolicy, writes: bool) -> Vec<Value> {
+    [
+        definition::<SearchResourcesArgs>("search_resources"),
+        definition::<AdvancedSearchArgs>("advanced_search"),
+        definition::<FetchResourceArgs>("fetch_resource"),
+        definition::<SyncResourcesArgs>("sync_resources"),
+        definition::<RecallContextArgs>("recall_context"),
+        definition::<SummarizeResourceArgs>("summarize_resource"),
+        definition::<AnswerQuestionArgs>("answer_question"),
+        definition::<WatchResourceArgs>("watch_resource"),
+        definition::<UnwatchResourceArgs>("unwatch_resource"),
+        definition::<CreateLinearIssueArgs>("create_linear_issue"),
+        definition::<AppendToNotionPageArgs>("append_to_notion_page"),
+        definition::<UpdateNotionPageArgs>("update_notion_page"),
+        definition::<ListProvidersArgs>("list_providers"),
+    ]
+    .into_iter()
+    .filter(|tool| allowed(policy, writes, tool["name"].as_str().unwrap_or_default()))
+    .collect()
+}
+
+pub fn exists(policy: &ClientPolicy, writes: bool, name: &str) -> bool {
+    definitions(policy, writes)
+        .iter()
+        .any(|tool| tool["name"] == name)
+}
+
+fn definition<T: JsonSchema>(name: &str) -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator();
+    let mut schema = generator.into_root_schema_for::<T>().to_value();
+
+    let description = schema
+        .as_object_mut()
+        .and_then(|schema| {
+            schema.remove("$schema");
+            schema.remove("title");
+            schema.remove("description")
+        })
+        .unwrap_or(Value::Null);
+
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": schema,
+    })
+}
+
+/// Write tools need both the server to allow writes and the client not to
+/// be held to read-only.
+fn allowed(policy: &ClientPolicy, writes: bool, tool: &str) -> bool {
+    policy.allows_tool(tool) && (!WRITE_TOOLS.contains(&tool) || writes && !policy.read_only())
+}
+
+/// Runs a `tools/call` request for `session`, allowing the write tools if
+/// `writes` is set. Unknown tools, including those the session's policy
+/// hides, and malformed arguments are protocol errors; failures while
+/// running the tool are reported in the result with `isError` so the model
+/// can see them.
+pub async fn call(
+    service: &ResourceService,
+    session: &Session,
+    writes: bool,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let policy = &session.policy();
+    let params: CallParams = parse_params(params)?;
+    if !allowed(policy, writes, &params.name) {
+        return Err(RpcError::invalid_params(format!(
+            "Unknown tool: {}",
+            params.name
+        )));
+    }
+    let arguments = if params.arguments.is_null() {
+        json!({})
+    } else {
+        params.arguments
+    };
+
+    // Arguments are objects with sorted keys, so identical searches have
+    // identical keys.
+    let memo_key = format!("{} {}", params.name, arguments);
+    let result = match params.name.as_str() {
+        "search_resources" => {
+            let args = parse_params(arguments)?;
+            let roots = session.roots().await;
+            let memo = session.searches();
+            search_resources(service, policy, roots.as_deref(), memo, memo_key, args).await
+        }
+        "advanced_search" => {
+            let args = parse_params(arguments)?;
+            let roots = session.roots().await;
+            let memo = session.searches();
+            advanced_search(service, policy, roots.as_deref(), memo, memo_key, args).await
+        }
+        "fetch_resource" => {
+            let args = parse_params(arguments)?;
+            fetch_resource(service, policy, session.memory(), args).await
+        }
+        "sync_resources" => sync_resources(service, policy, parse_params(arguments)?).await,
+        "recall_context" => recall_context(session.memory(), parse_params(arguments)?),
+        "summarize_resource" => {
+            summarize_resource(service, session, parse_params(arguments)?).await
+        }
+        "answer_question" => answer_question(service, session, parse_params(arguments)?).await,
+        "watch_resource" => watch_resource(service, session, parse_params(arguments)?).await,
+        "unwatch_resource" => {
+            let args: UnwatchResourceArgs = parse_params(arguments)?;
+            for id in &args.ids {
+                session.unsubscribe(&service.resolve_id(id).await);
+            }
+            Ok(tool_result(&json!({ "unwatched": args.ids }), false))
+        }
+        "create_linear_issue" => {
+            create_linear_issue(service, policy, parse_params(arguments)?).await
+        }
+        "append_to_notion_page" => {
+            append_to_notion_page(service, policy, parse_params(arguments)?).await
+        }
+        "update_notion_page" => update_notion_page(service, policy, parse_params(arguments)?).await,
+        "list_providers" => {
+            let _: ListProvidersArgs = parse_params(arguments)?;
+            let mut providers = service.list_providers();
+            providers.retain(|provider| policy.allows_provider(provider));
+            Ok(tool_result(&json!({ "providers": providers }), false))
+        }
+        name => Err(RpcError::invalid_params(format!("Unknown tool: {}", name))),
+    };
+    // Searches may find something else after a write or sync.
+    if WRITE_TOOLS.contains(&params.name.as_str()) || params.name == "sync_resources" {
+        session.searches().forget();
+    }
+    result
+}
+
+async fn search_resources(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    roots: Option<&Roots>,
+    memo: &SearchMemo,
+    key: String,
+    args: SearchResourcesArgs,
+) -> Result<Value, RpcError> {
+    let max_staleness = match args.max_staleness.as_deref().map(humantime::parse_duration) {
+        Some(Ok(max_staleness)) => Some(max_staleness),
+        Some(Err(e)) => return Err(RpcError::invalid_params(format!("max_staleness: {}", e))),
+        None => None,
+    };
+    if let Some(memoized) = memo.recall(&key, max_staleness) {
+        return Ok(tool_result(&memoized, false));
+    }
+    let options = SearchOptions {
+        include_archived: args.include_archived,
+        max_staleness,
+    };
+
+    let result = if args.sources.is_empty() {
+        service.search_routed(&args.query, &options).await
+    } else {
+        service
+            .search(&args.query, Some(args.sources), &options)
+            .await
+    };
+    let mut result = match result {
+        Ok(result) => result,
+        Err(e) => return Ok(domain_error(&e)),
+    };
+    restrict(&mut result, policy, roots);
+
+    // Partial results still succeed; the errors tell the model which
+    // providers are missing from them.
+    let (value, failed) = if args.chunks {
+        let limit = args.limit.unwrap_or(DEFAULT_CHUNKS);
+        let passages = chunks::rank(&result.resources, &args.query, limit);
+        let failed = passages.is_empty() && result.is_partial();
+        let value = json!({
+            "chunks": passages,
+            "errors": result.errors,
+            "warnings": result.warnings,
+            "origins": result.origins,
+            "route": result.route,
+        });
+        (value, failed)
+    } else {
+        let limit = args.limit.unwrap_or(result.resources.len());
+        let resources: Vec<Value> = result.resources.iter().take(limit).map(summary).collect();
+        let failed = resources.is_empty() && result.is_partial();
+        let value = json!({
+            "resources": resources,
+            "errors": result.errors,
+            "warnings": result.warnings,
+            "origins": result.origins,
+            "route": result.route,
+        });
+        (value, failed)
+    };
+    // Partial results are searched for again in full.
+    if !result.is_partial() {
+        memo.remember(key, &value);
+    }
+    Ok(tool_result(&value, failed))
+}
+
+async fn advanced_search(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    roots: Option<&Roots>,
+    memo: &SearchMemo,
+    key: String,
+    args: AdvancedSearchArgs,
+) -> Result<Value, RpcError> {
+    if let Some(memoized) = memo.recall(&key, None) {
+        return Ok(tool_result(&memoized, false));
+    }
+    let updated = time_range("updated", &args.updated_after, &args.updated_before)?;
+    let created = time_range("created", &args.created_after, &args.created_before)?;
+    let offset = match &args.cursor {
+        Some(cursor) => cursor::decode::<OffsetCursor>(cursor)?.offset,
+        None => 0,
+    };
+    let limit = args.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let sources = if args.sources.is_empty() {
+        vec![QuerySource::All]
+    } else {
+        args.sources
+    };
+
+    let result = match &args.query {
+        Some(text) => {
+            let options = SearchOptions {
+                include_archived: args.include_archived,
+                max_staleness: None,
+            };
+            service.search(text, Some(sources), &options).await
+        }
+        None => {
+            // The providers' order is kept, so only what this page needs is
+            // listed unless it has to be filtered or sorted first.
+            let candidates =
+                if updated.is_none() && created.is_none() && args.sort == SortField::Relevance {
+                    offset + limit
+                } else {
+                    MAX_CANDIDATES
+                };
+            let mut result = QueryResult::default();
+            for source in sources {
+                let query = Query {
+                    source,
+                    filters: args.filters.clone(),
+                    limit: Some(candidates),
+                };
+                match service.fetch_resources(&query).await {
+                    Ok(mut fetched) => {
+                        result.resources.append(&mut fetched.resources);
+                        result.errors.append(&mut fetched.errors);
+                        result.warnings.append(&mut fetched.warnings);
+                    }
+                    Err(e) => return Ok(domain_error(&e)),
+                }
+            }
+            if !args.include_archived {
+                result.resources.retain(|resource| !resource.is_archived());
+            }
+            Ok(result)
+        }
+    };
+    let mut result = match result {
+        Ok(result) => result,
+        Err(e) => return Ok(domain_error(&e)),
+    };
+    restrict(&mut result, policy, roots);
+
+    let in_range = |time: DateTime<Utc>, range: Option<TimeRange>| {
+        range.is_none_or(|(after, before)| {
+            after.is_none_or(|after| time >= after) && before.is_none_or(|before| time < before)
+        })
+    };
+    result.resources.retain(|resource| {
+        in_range(resource.updated_at, updated) && in_range(resource.created_at, created)
+    });
+    sort(&mut result.resources, args.sort, args.order);
+
+    let total = result.resources.len();
+    let resources: Vec<Value> = result
+        .resources
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(summary)
+        .collect();
+    let next_cursor = (offset + limit < total).then(|| {
+        cursor::encode(&OffsetCursor {
+            offset: offset + limit,
+        })
+    });
+
+    let failed = resources.is_empty() && result.is_partial();
+    let value = json!({
+        "resources": resources,
+        "next_cursor": next_cursor,
+        "errors": result.errors,
+        "warnings": result.warnings,
+        "origins": result.origins,
+    });
+    if !result.is_partial() {
+        memo.remember(key, &value);
+    }
+    Ok(tool_result(&value, failed))
+}
+
+/// Inclusive start and exclusive end of a date range; either may be open.
+type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Parses the bounds of a date range, if either is given.
+fn time_range(
+    field: &str,
+    after: &Option<String>,
+    before: &Option<String>,
+) -> Result<Option<TimeRange>, RpcError> {
+    let parse = |bound: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|value| {
+                DateTime::parse_from_rfc3339(value)
+                    .map(|time| time.with_timezone(&Utc))
+                    .or_else(|_| {
+                        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                            .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+                    })
+                    .map_err(|_| {
+                        RpcError::invalid_params(format!(
+                            "{}_{}: expected RFC 3339 or YYYY-MM-DD, got {}",
+                            field, bound, value
+                        ))
+                    })
+            })
+            .transpose()
+    };
+    let range = (parse("after", after)?, parse("before", before)?);
+    Ok((range.0.is_some() || range.1.is_some()).then_some(range))
+}
+
+fn sort(resources: &mut [Resource], field: SortField, order: Option<SortOrder>) {
+    let compare: fn(&Resource, &Resource) -> Ordering = match field {
+        SortField::Relevance => return,
+        SortField::Updated => |a, b| a.updated_at.cmp(&b.updated_at),
+        SortField::Created => |a, b| a.created_at.cmp(&b.created_at),
+        SortField::Title => |a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+    };
+    let default = if field == SortField::Title {
+        SortOrder::Asc
+    } else {
+        SortOrder::Desc
+    };
+    match order.unwrap_or(default) {
+        SortOrder::Asc => resources.sort_by(compare),
+        SortOrder::Desc => resources.sort_by(|a, b| compare(b, a)),
+    }
+}
+
+/// Drops what `policy` hides from a search's resources, errors and origins,
+/// and resources outside the client's `roots`.
+fn restrict(result: &mut QueryResult, policy: &ClientPolicy, roots: Option<&Roots>) {
+    result.resources.retain(|resource| {
+        policy.allows_resource(resource) && roots.is_none_or(|roots| roots.contains(resource))
+    });
+    result
+        .errors
+        .retain(|failure| policy.allows_provider(&failure.provider));
+    result
+        .origins
+        .retain(|provider, _| policy.allows_provider(provider));
+}
+
+/// A search result as tools return it: an excerpt instead of the content.
+fn summary(resource: &Resource) -> Value {
+    json!({
+        "id": resource.id,
+        "uri": resource.uri(),
+        "title": resource.title,
+        "provider": resource.source.provider_name(),
+        "updated_at": resource.updated_at,
+        "excerpt": resource.excerpt(EXCERPT_CHARS),
+        "provenance": resource.provenance,
+    })
+}
+
+async fn fetch_resource(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    memory: &ContextMemory,
+    args: FetchResourceArgs,
+) -> Result<Value, RpcError> {
+    let resource = service
+        .fetch_resource_by_id(&args.id)
+        .await
+        .and_then(|resource| policy.expose(resource))
+        .inspect(|resource| memory.remember(resource))
+        .and_then(|mut resource| {
+            // The version is of the whole resource, not the part returned.
+            let version = resource.version();
+            resource.content = shape(&resource.content, &args.shape)?;
+            Ok((resource, version))
+        });
+    match resource {
+        Ok((resource, version)) => {
+            let mut value = json!(resource);
+            value["uri"] = json!(resource.uri());
+            value["version"] = json!(version);
+            Ok(tool_result(&value, false))
+        }
+        Err(e) => Ok(domain_error(&e)),
+    }
+}
+
+/// Resources are remembered whole, whatever part of them was read.
+fn recall_context(memory: &ContextMemory, args: RecallContextArgs) -> Result<Value, RpcError> {
+    let resources: Vec<Value> = memory
+        .recall(args.limit.unwrap_or(usize::MAX))
+        .iter()
+        .map(|resource| {
+            if args.summarize {
+                return summary(resource);
+            }
+            let mut value = json!(resource);
+            value["uri"] = json!(resource.uri());
+            value
+        })
+        .collect();
+    Ok(tool_result(&json!({ "resources": resources }), false))
+}
+
+/// Asks the client's model for the summary, so the server needs no model
+/// or API key of its own. The content sent is capped; the resource counts
+/// as read.
+async fn summarize_resource(
+    service: &ResourceService,
+    session: &Session,
+    args: SummarizeResourceArgs,
+) -> Result<Value, RpcError> {
+    if !session.supports("sampling") {
+        return Ok(tool_error(
+            &"This client doesn't support sampling; fetch the resource and summarize it instead",
+        ));
+    }
+    let options = ShapeOptions {
+        section: args.section,
+        format: None,
+        max_tokens: Some(MAX_SUMMARIZED_TOKENS),
+    };
+    let shaped = service
+        .fetch_resource_by_id(&args.id)
+        .await
+        .and_then(|resource| session.policy().expose(resource))
+        .inspect(|resource| session.memory().remember(resource))
+        .and_then(|resource| Ok((shape(&resource.content, &options)?, resource)));
+    let (content, resource) = match shaped {
+        Ok(shaped) => shaped,
+        Err(e) => return Ok(domain_error(&e)),
+    };
+
+    let mut prompt = format!(
+        "Summarize this {} resource titled \"{}\".",
+        resource.source.provider_name(),
+        resource.title
+    );
+    if let Some(focus) = &args.focus {
+        prompt.push_str(&format!(" Concentrate on {}.", focus));
+    }
+    prompt.push_str("\n\n");
+    prompt.push_str(&content);
+    let params = json!({
+        "messages": [{
+            "role": "user",
+            "content": { "type": "text", "text": prompt },
+        }],
+        "systemPrompt": "You summarize documents and issues accurately and concisely, \
+            keeping names, dates and decisions, and adding nothing that isn't in the text.",
+        "includeContext": "none",
+        "maxTokens": args.max_tokens.unwrap_or(DEFAULT_SUMMARY_TOKENS),
+    });
+
+    let reply = match session
+        .request("sampling/createMessage", params, SAMPLING_TIMEOUT)
+        .await
+    {
+        Ok(reply) => reply,
+        Err(e) => return Ok(tool_error(&format!("Sampling failed: {}", e.message))),
+    };
+    let Some(summary) = reply["content"]["text"].as_str() else {
+        return Ok(tool_error(&"The client's model didn't answer with text"));
+    };
+    Ok(tool_result(
+        &json!({
+            "id": resource.id,
+            "uri": resource.uri(),
+            "title": resource.title,
+            "summary": summary,
+            "model": reply["model"],
+        }),
+        false,
+    ))
+}
+
+/// Searches for each of the question's terms, since providers' searches
+/// mostly want all the words they are given, then numbers the best passages
+/// of what is found as sources for the client's model, asking it to cite them
+/// by number. The citations returned are the sources the answer cites, and
+/// the resources they are from count as read.
+async fn answer_question(
+    service: &ResourceService,
+    session: &Session,
+    args: AnswerQuestionArgs,
+) -> Result<Value, RpcError> {
+    if !session.supports("sampling") {
+        return Ok(tool_error(
+            &"This client doesn't support sampling; search the resources and answer from them instead",
+        ));
+    }
+    let policy = session.policy();
+    let roots = session.roots().await;
+    let sources = if args.sources.is_empty() {
+        vec![QuerySource::All]
+    } else {
+        args.sources
+    };
+    let terms: Vec<String> = chunks::search_terms(&args.question)
+        .split_whitespace()
+        .take(MAX_SEARCHED_TERMS)
+        .map(str::to_string)
+        .collect();
+    let options = SearchOptions::default();
+    let mut result = match service
+        .search_any(&terms, &sources, &options, SEARCH_CONCURRENCY)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => return Ok(domain_error(&e)),
+    };
+    restrict(&mut result, &policy, roots.as_deref());
+
+    let limit = args
+        .max_passages
+        .unwrap_or(DEFAULT_PASSAGES)
+        .clamp(1, MAX_PASSAGES);
+    let mut passages = chunks::rank(&result.resources, &args.question, limit);
+    let mut budget = MAX_ANSWER_CONTEXT_TOKENS * CHARS_PER_TOKEN;
+    passages.retain(|passage| {
+        let fits = passage.text.len() <= budget;
+        budget = budget.saturating_sub(passage.text.len());
+        fits
+    });
+    if passages.is_empty() {
+        let value = json!({
+            "answer": Value::Null,
+            "citations": [],
+            "errors": result.errors,
+            "warnings": result.warnings,
+        });
+        return Ok(tool_result(&value, true));
+    }
+
+    let mut prompt = format!(
+        "Answer this question using only the numbered sources below: {}\n\n\
+        Cite the sources each statement comes from by number, e.g. [2] or [1, 3]. \
+        If the sources don't answer the question, say so.",
+        args.question
+    );
+    for (number, passage) in passages.iter().enumerate() {
+        let mut heading = passage.title.clone();
+        for section in &passage.section {
+            heading.push_str(" > ");
+            heading.push_str(section);
+        }
+        prompt.push_str(&format!(
+            "\n\n[{}] {}\n{}",
+            number + 1,
+            heading,
+            passage.text
+        ));
+    }
+    let params = json!({
+        "messages": [{
+            "role": "user",
+            "content": { "type": "text", "text": prompt },
+        }],
+        "systemPrompt": "You answer questions about a team's documents and issues \
+            from the sources given, accurately and concisely, citing them by number \
+            and adding nothing that isn't in them.",
+        "includeContext": "none",
+        "maxTokens": args.max_tokens.unwrap_or(DEFAULT_ANSWER_TOKENS),
+    });
+
+    let reply = match session
+        .request("sampling/createMessage", params, SAMPLING_TIMEOUT)
+        .await
+    {
+        Ok(reply) => reply,
+        Err(e) => return Ok(tool_error(&format!("Sampling failed: {}", e.message))),
+    };
+    let Some(answer) = reply["content"]["text"].as_str() else {
+        return Ok(tool_error(&"The client's model didn't answer with text"));
+    };
+
+    let mut cited: Vec<usize> = Vec::new();
+    for captures in CITATION.captures_iter(answer) {
+        for number in captures[1].split(',') {
+            let number = number.trim().parse().unwrap_or(0);
+            if (1..=passages.len()).contains(&number) && !cited.contains(&number) {
+                cited.push(number);
+            }
+        }
+    }
+    let citations: Vec<Value> = cited
+        .iter()
+        .map(|&number| {
+            let passage = &passages[number - 1];
+            if let Some(resource) = result.resources.iter().find(|r| r.id == passage.id) {
+                session.memory().remember(resource);
+            }
+            json!({
+                "source": number,
+                "id": passage.id,
+                "uri": passage.uri,
+                "title": passage.title,
+                "section": passage.section,
+                "start": passage.start,
+                "end": passage.end,
+            })
+        })
+        .collect();
+
+    Ok(tool_result(
+        &json!({
+            "answer": answer,
+            "citations": citations,
+            "model": reply["model"],
+            "errors": result.errors,
+            "warnings": result.warnings,
+        }),
+        false,
+    ))
+}
+
+/// Subscribes the session to each resource, then waits for changes if
+/// asked to. Updates are listened for before subscribing, so none found in
+/// between is missed.
+async fn watch_resource(
+    service: &ResourceService,
+    session: &Session,
+    args: WatchResourceArgs,
+) -> Result<Value, RpcError> {
+    let policy = session.policy();
+    let mut updates = session.updates();
+    let mut watching = Vec::new();
+    for id in &args.ids {
+        let resource = service
+            .fetch_resource_by_id(id)
+            .await
+            .and_then(|resource| policy.expose(resource));
+        let subscribed = match resource {
+            Ok(resource) => session.subscribe(&resource.id).await.map(|()| resource),
+            Err(e) => Err(e),
+        };
+        match subscribed {
+            Ok(resource) => watching.push(resource),
+            Err(e) => {
+                let mut value = domain_error(&e);
+                value["content"][0]["text"] = json!(format!("{}: {}", id, e));
+                return Ok(value);
+            }
+        }
+    }
+
+    let mut changed: Vec<Value> = Vec::new();
+    if let Some(wait_secs) = args.wait_secs {
+        // The IDs not yet seen changed: each is returned once.
+        let mut ids: HashSet<&str> = watching.iter().map(|r| r.id.as_str()).collect();
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_secs(wait_secs).min(MAX_WATCH_WAIT);
+        loop {
+            let update = match tokio::time::timeout_at(deadline, updates.recv()).await {
+                Ok(Ok(update)) => update,
+                Ok(Err(RecvError::Lagged(_))) => continue,
+                Ok(Err(RecvError::Closed)) | Err(_) => break,
+            };
+            if ids.remove(update.resource.id.as_str()) {
+                if let Ok(resource) = policy.expose(update.resource) {
+                    let mut entry = summary(&resource);
+                    entry["changes"] = json!(update.changes);
+                    changed.push(entry);
+                }
+            }
+            // Changes already announced are returned together.
+            if !changed.is_empty() && updates.is_empty() {
+                break;
+            }
+        }
+    }
+
+    let watching: Vec<Value> = watching
+        .iter()
+        .map(|resource| {
+            json!({
+                "id": resource.id,
+                "uri": resource.uri(),
+                "title": resource.title,
+                "updated_at": resource.updated_at,
+            })
+        })
+        .collect();
+    Ok(tool_result(
+        &json!({ "watching": watching, "changed": changed }),
+        false,
+    ))
+}
+
+/// Providers the client may not see are synced along with the rest when
+/// no source is named, but left out of the reports.
+async fn sync_resources(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    args: SyncResourcesArgs,
+) -> Result<Value, RpcError> {
+    let source = args.source.unwrap_or(QuerySource::All);
+    let hidden = service
+        .expand_sources(std::slice::from_ref(&source))
+        .into_iter()
+        .find(|name| name != QuerySource::All.name() && !policy.allows_provider(name));
+    if let Some(provider) = hidden {
+        return Ok(tool_error(&format!("{} provider not configured", provider)));
+    }
+    let query = Query {
+        source,
+        filters: args.filters,
+        limit: args.limit,
+    };
+    let reports = match service.sync(&query, |_| SyncLimits::default()).await {
+        Ok(reports) => reports,
+        Err(e) => return Ok(domain_error(&e)),
+    };
+
+    let reports: Vec<Value> = reports
+        .iter()
+        .filter(|report| policy.allows_provider(&report.provider))
+        .map(|report| {
+            json!({
+                "provider": report.provider,
+                "fetched": report.fetched,
+                "tombstoned": report.tombstoned,
+                "skipped": report.skipped,
+                "error": report.error,
+            })
+        })
+        .collect();
+    let failed = !reports.is_empty() && reports.iter().all(|report| !report["error"].is_null());
+    Ok(tool_result(&json!({ "reports": reports }), failed))
+}
+
+async fn create_linear_issue(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    args: CreateLinearIssueArgs,
+) -> Result<Value, RpcError> {
+    if !policy.allows_provider("linear") {
+        return Ok(tool_error(&"linear provider not configured"));
+    }
+    let change = Change::Create {
+        parent: args.team,
+        title: args.title,
+        content: args.description,
+        labels: args.labels,
+    };
+    written(service.apply("linear", &change).await)
+}
+
+async fn append_to_notion_page(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    args: AppendToNotionPageArgs,
+) -> Result<Value, RpcError> {
+    let id = match notion_page(service, policy, &args.page).await {
+        Ok(id) => id,
+        Err(error) => return Ok(error),
+    };
+    let change = Change::Append {
+        id,
+        content: args.content,
+    };
+    written(
+        service
+            .apply_at("notion", &change, args.version.as_deref())
+            .await,
+    )
+}
+
+async fn update_notion_page(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    args: UpdateNotionPageArgs,
+) -> Result<Value, RpcError> {
+    if args.title.is_none() && args.content.is_none() {
+        return Err(RpcError::invalid_params(
+            "nothing to update: give a title or content",
+        ));
+    }
+    let id = match notion_page(service, policy, &args.page).await {
+        Ok(id) => id,
+        Err(error) => return Ok(error),
+    };
+    let change = Change::Update {
+        id,
+        title: args.title,
+        content: args.content,
+    };
+    written(
+        service
+            .apply_at("notion", &change, Some(&args.version))
+            .await,
+    )
+}
+
+/// The resource ID of the Notion page a write tool is given, or the tool
+/// result saying why it can't be written.
+async fn notion_page(
+    service: &ResourceService,
+    policy: &ClientPolicy,
+    page: &str,
+) -> Result<String, Value> {
+    let id = service.resolve_id(page).await;
+    if !id.starts_with("notion_") {
+        return Err(tool_error(&format!("{} is not a Notion page", page)));
+    }
+    policy.check_resource(&id).map_err(|e| domain_error(&e))?;
+    Ok(id)
+}
+
+/// The result of a write tool: the resource as written, with the version
+/// to pass to the next write, or why it failed. Conflicts are reported as
+/// JSON with both versions, so the model can tell what happened.
+fn written(result: Result<Resource, DomainError>) -> Result<Value, RpcError> {
+    match result {
+        Ok(resource) => {
+            let mut value = json!(resource);
+            value["uri"] = json!(resource.uri());
+            value["version"] = json!(resource.version());
+            Ok(tool_result(&value, false))
+        }
+        Err(DomainError::Conflict(conflict)) => {
+            let mut value = json!(conflict);
+            value["error"] = json!("version_conflict");
+            value["message"] = json!(conflict.to_string());
+            Ok(tool_result(&value, true))
+        }
+        Err(e) => Ok(domain_error(&e)),
+    }
+}
+
+fn tool_result(value: &Value, is_error: bool) -> Value {
+    json!({
+        "content": [{
+            "type": "text",
+            "text": serde_json::to_string_pretty(value).unwrap_or_default(),
+        }],
+        "isError": is_error,
+    })
+}
+
+fn tool_error(error: &dyn std::fmt::Display) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": error.to_string() }],
+        "isError": true,
+    })
+}
+
+/// A failed call's result: the message for the model, and the error's
+/// details in `structuredContent.error` for the client to act on, e.g.
+/// waiting out `retry_after_secs`.
+fn domain_error(error: &DomainError) -> Value {
+    let mut value = tool_error(error);
+    value["structuredContent"] = json!({ "error": error.details() });
+    value
+}
@@ -0,0 +1,105 @@
+use axum::{
+    extract::{
+        ws::{Message as Frame, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::HeaderMap,
+    response::Response as HttpResponse,
+    routing::get,
+    Router,
+};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use super::http::bearer_token;
+use super::protocol::parse_message;
+use super::server::McpServer;
+
+/// Path clients open WebSocket connections on.
+const WS_PATH: &str = "/ws";
+
+/// Routes for MCP over WebSocket. Each connection is a session; every text
+/// frame carries one JSON-RPC message or batch, in either direction, so
+/// nothing sits in a proxy's buffer the way an event stream can.
+pub fn router(server: Arc<McpServer>) -> Router {
+    Router::new()
+        .route(WS_PATH, get(upgrade))
+        .with_state(server)
+}
+
+async fn upgrade(
+    State(server): State<Arc<McpServer>>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> HttpResponse {
+    let token = bearer_token(&headers);
+    ws.on_upgrade(move |socket| serve_connection(server, socket, token))
+}
+
+/// Handles requests concurrently, like stdio, until the client closes the
+/// connection.
+async fn serve_connection(server: Arc<McpServer>, socket: WebSocket, token: Option<String>) {
+    let (mut sink, mut stream) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+
+    let writer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if sink
+                .send(Frame::Text(message.to_string().into()))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let session = server.session(Some(tx.clone()), token);
+    server.keep_alive(&session);
+    tracing::debug!("Opened WebSocket session");
+    loop {
+        let frame = tokio::select! {
+            frame = stream.next() => frame,
+            _ = session.wait_closed() => break,
+        };
+        let Some(Ok(frame)) = frame else {
+            break;
+        };
+        let text = match frame {
+            Frame::Text(text) => text.to_string(),
+            Frame::Binary(bytes) => match String::from_utf8(bytes.to_vec()) {
+                Ok(text) => text,
+                Err(_) => continue,
+            },
+            Frame::Close(_) => break,
+            // Pings are answered by the WebSocket layer.
+            Frame::Ping(_) | Frame::Pong(_) => continue,
+        };
+
+        let message = match parse_message(&text) {
+            Ok(message) => message,
+            Err(response) => {
+                let _ = tx.send(json!(response));
+                continue;
+            }
+        };
+        let server = server.clone();
+        let session = session.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            if let Some(reply) = server.handle_message(&session, message).await {
+                let _ = tx.send(reply);
+            }
+        });
+    }
+
+    tracing::debug!("Closed WebSocket session");
+    // Requests still in flight hold the session until they finish; their
+    // replies have nowhere to go.
+    drop(session);
+    drop(tx);
+    writer.abort();
+}
@@ -1,2 +1,15 @@
 pub mod adapters;
 pub mod cli;
+pub mod config;
+pub mod daemon;
+pub mod features;
+pub mod http;
+pub mod mcp;
+pub mod pipeline;
+pub mod reload;
+pub mod repository;
+pub mod sandbox;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod selftest;
+pub mod snapshot;
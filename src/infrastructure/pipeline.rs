@@ -0,0 +1,660 @@
+use chrono::{Local, Utc};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use super::{
+    cli::{output::truncate, parse_sources},
+    config,
+    http::HttpClient,
+};
+use crate::{
+    application::{chunks, condition::Condition, search::SearchOptions, ResourceService},
+    domain::{DomainError, Query, QueryResult, QuerySource, Resource},
+};
+
+/// Characters of each resource's best passage a summary includes unless
+/// asked for another number.
+const DEFAULT_PASSAGE_CHARS: usize = 300;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error("Failed to read {path}: {message}")]
+    Read { path: String, message: String },
+
+    #[error("Invalid pipeline {path}: {message}")]
+    Invalid { path: String, message: String },
+
+    #[error("Step {number} ({step}) failed: {source}")]
+    Step {
+        number: usize,
+        step: &'static str,
+        source: DomainError,
+    },
+}
+
+/// A recurring workflow read from a YAML file: steps run in order over one
+/// set of resources, which search and fetch steps add to and the others
+/// narrow down, summarize, or send somewhere.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    /// Titles the summary unless its step gives one.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Each step is a map with one key, its kind, as in `- limit: 10`.
+    #[serde(deserialize_with = "serde_yaml::with::singleton_map_recursive::deserialize")]
+    pub steps: Vec<Step>,
+    /// Where relative export paths start from: the pipeline file's
+    /// directory, so a pipeline writes to the same place wherever it's run
+    /// from.
+    #[serde(skip)]
+    base: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Search(#[serde(deserialize_with = "shorthand")] SearchStep),
+    Fetch(#[serde(deserialize_with = "shorthand")] FetchStep),
+    Where(#[serde(deserialize_with = "parsed")] Condition),
+    Sort(#[serde(deserialize_with = "parsed")] SortKey),
+    Limit(usize),
+    /// `summarize:` with nothing after it takes the defaults.
+    Summarize(Option<SummarizeStep>),
+    Export(#[serde(deserialize_with = "shorthand")] ExportStep),
+    Post(#[serde(deserialize_with = "shorthand")] PostStep),
+}
+
+/// Adds the results of a search. `search: <query>` searches every
+/// provider.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchStep {
+    pub query: String,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Oldest cached results to accept, e.g. `1h`; zero searches live.
+    #[serde(default, deserialize_with = "duration")]
+    pub max_staleness: Option<Duration>,
+}
+
+/// Adds the resources a provider lists. `fetch: <source>` lists them all.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FetchStep {
+    pub source: String,
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Orders the resources by a field, descending with a leading `-`.
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    field: SortField,
+    descending: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SortField {
+    Id,
+    Title,
+    Provider,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// Renders the resources as a Markdown digest: each one's title, source,
+/// and the passage most relevant to the pipeline's last search.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SummarizeStep {
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Picks the passages, instead of the last search's query.
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub passage_chars: Option<usize>,
+    /// Leaves out the last resources once the digest would grow past it.
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+}
+
+/// Writes the summary, or the resources as JSON, to a file.
+/// `{date}` in the path is replaced by today's date.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportStep {
+    pub path: String,
+    /// Taken from the path's extension when not given: JSON for `.json`,
+    /// Markdown otherwise.
+    #[serde(default)]
+    pub format: Option<Format>,
+}
+
+/// Sends the summary, or the resources as JSON, to a URL, e.g. a chat
+/// webhook.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostStep {
+    pub url: String,
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// The resources and errors as `fetch -o json` prints them, with the
+    /// Markdown summary under `text`, where chat webhooks look for it.
+    #[default]
+    Json,
+    Markdown,
+}
+
+/// What a pipeline ended with.
+#[derive(Debug, Default)]
+pub struct PipelineOutput {
+    pub result: QueryResult,
+    /// The Markdown digest of the last summarize step.
+    pub summary: Option<String>,
+    /// The last search's query, which picks the summary's passages.
+    pub query: Option<String>,
+    /// Whether an export or post step sent the output somewhere.
+    pub delivered: bool,
+}
+
+/// The JSON form of a pipeline's output.
+#[derive(Serialize)]
+struct Document<'a> {
+    text: String,
+    #[serde(flatten)]
+    result: &'a QueryResult,
+}
+
+impl Pipeline {
+    /// Reads and checks the pipeline at `path`, including its `where`
+    /// conditions, so a mistake stops it before any step runs.
+    pub fn load(path: &Path) -> Result<Self, PipelineError> {
+        let text = std::fs::read_to_string(path).map_err(|e| PipelineError::Read {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        let invalid = |message: String| PipelineError::Invalid {
+            path: path.display().to_string(),
+            message,
+        };
+        let mut pipeline: Pipeline =
+            serde_yaml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if pipeline.steps.is_empty() {
+            return Err(invalid("it has no steps".to_string()));
+        }
+        pipeline.base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(pipeline)
+    }
+
+    /// Runs the steps in order, stopping at the first that fails. Providers
+    /// that fail a search or fetch are reported in the output like a
+    /// partial `search`, without stopping the pipeline.
+    pub async fn run(&self, service: &ResourceService) -> Result<PipelineOutput, PipelineError> {
+        let mut output = PipelineOutput::default();
+        for (index, step) in self.steps.iter().enumerate() {
+            let done = self
+                .run_step(service, step, &mut output)
+                .await
+                .map_err(|source| PipelineError::Step {
+                    number: index + 1,
+                    step: step.name(),
+                    source,
+                })?;
+            eprintln!("[{}/{}] {}", index + 1, self.steps.len(), done);
+        }
+        Ok(output)
+    }
+
+    /// Runs one step, describing what it did.
+    async fn run_step(
+        &self,
+        service: &ResourceService,
+        step: &Step,
+        output: &mut PipelineOutput,
+    ) -> Result<String, DomainError> {
+        match step {
+            Step::Search(search) => {
+                let sources = if search.sources.is_empty() {
+                    vec![QuerySource::All]
+                } else {
+                    parse_sources(search.sources.clone())
+                };
+                let options = SearchOptions {
+                    include_archived: search.include_archived,
+                    max_staleness: search.max_staleness,
+                };
+                let result = service
+                    .search(&search.query, Some(sources), &options)
+                    .await?;
+                output.query = Some(search.query.clone());
+                let found = result.resources.len();
+                let added = merge(&mut output.result, result);
+                Ok(format!(
+                    "search \"{}\": {} resources, {} new",
+                    search.query, found, added
+                ))
+            }
+            Step::Fetch(fetch) => {
+                let query = Query {
+                    source: QuerySource::parse(&fetch.source),
+                    filters: fetch.filters.clone(),
+                    limit: fetch.limit,
+                };
+                let result = service.fetch_resources(&query).await?;
+                let found = result.resources.len();
+                let added = merge(&mut output.result, result);
+                Ok(format!(
+                    "fetch {}: {} resources, {} new",
+                    fetch.source, found, added
+                ))
+            }
+            Step::Where(condition) => {
+                let before = output.result.resources.len();
+                output
+                    .result
+                    .resources
+                    .retain(|resource| condition.matches(resource));
+                Ok(format!(
+                    "where {}: kept {} of {}",
+                    condition,
+                    output.result.resources.len(),
+                    before
+                ))
+            }
+            Step::Sort(key) => {
+                key.sort(&mut output.result.resources);
+                Ok(format!("sort by {}", key))
+            }
+            Step::Limit(limit) => {
+                output.result.resources.truncate(*limit);
+                Ok(format!(
+                    "limit {}: {} resources",
+                    limit,
+                    output.result.resources.len()
+                ))
+            }
+            Step::Summarize(summarize) => {
+                let default = SummarizeStep::default();
+                let summarize = summarize.as_ref().unwrap_or(&default);
+                let max_chars = service.bundle_limit(summarize.max_chars)?;
+                let summary = self.summarize(summarize, max_chars, output);
+                let done = format!("summarize: {} characters", summary.chars().count());
+                output.summary = Some(summary);
+                Ok(done)
+            }
+            Step::Export(export) => {
+                let path = self.base.join(
+                    export
+                        .path
+                        .replace("{date}", &Local::now().format("%Y-%m-%d").to_string()),
+                );
+                let format = export.format.unwrap_or_else(|| {
+                    match path.extension().and_then(|extension| extension.to_str()) {
+                        Some(extension) if extension.eq_ignore_ascii_case("json") => Format::Json,
+                        _ => Format::Markdown,
+                    }
+                });
+                let body = self.render(format, output);
+                if let Some(parent) = path
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                {
+                    std::fs::create_dir_all(parent).map_err(|e| storage_error(&path, e))?;
+                }
+                std::fs::write(&path, body).map_err(|e| storage_error(&path, e))?;
+                output.delivered = true;
+                Ok(format!("export: wrote {}", path.display()))
+            }
+            Step::Post(post) => {
+                let url = config::expand_env(&post.url)?;
+                let mut headers = HeaderMap::new();
+                // Webhook URLs are often secrets themselves.
+                let mut secrets = vec![url.clone()];
+                for (name, value) in &post.headers {
+                    let value = config::expand_env(value)?;
+                    let header = |e: String| {
+                        DomainError::InvalidQuery(format!("invalid header {}: {}", name, e))
+                    };
+                    headers.insert(
+                        HeaderName::from_str(name).map_err(|e| header(e.to_string()))?,
+                        HeaderValue::from_str(&value).map_err(|e| header(e.to_string()))?,
+                    );
+                    secrets.push(value);
+                }
+                let content_type = match post.format {
+                    Format::Json => "application/json",
+                    Format::Markdown => "text/markdown; charset=utf-8",
+                };
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+                let body = self.render(post.format, output);
+                let client = HttpClient::new("Pipeline", headers, secrets)?;
+                let response = client.send(client.post(&url).body(body)).await?;
+                if !response.status().is_success() {
+                    return Err(DomainError::ProviderError(format!(
+                        "{} answered {}",
+                        response.url().host_str().unwrap_or("the server"),
+                        response.status()
+                    )));
+                }
+                output.delivered = true;
+                Ok(format!("post: {}", response.status()))
+            }
+        }
+    }
+
+    /// The output in `format`: the summary, or a default one if no step
+    /// summarized, for Markdown.
+    pub fn render(&self, format: Format, output: &PipelineOutput) -> String {
+        let text = output
+            .summary
+            .clone()
+            .unwrap_or_else(|| self.summarize(&SummarizeStep::default(), None, output));
+        match format {
+            Format::Markdown => text,
+            Format::Json => serde_json::to_string_pretty(&Document {
+                text,
+                result: &output.result,
+            })
+            .unwrap_or_default(),
+        }
+    }
+
+    fn summarize(
+        &self,
+        step: &SummarizeStep,
+        max_chars: Option<usize>,
+        output: &PipelineOutput,
+    ) -> String {
+        let result = &output.result;
+        let title = step
+            .title
+            .as_deref()
+            .or(self.name.as_deref())
+            .unwrap_or("Pipeline results");
+        let query = step.query.as_deref().or(output.query.as_deref());
+        let passage_chars = step.passage_chars.unwrap_or(DEFAULT_PASSAGE_CHARS);
+
+        let providers: BTreeSet<String> = result
+            .resources
+            .iter()
+            .map(|resource| resource.source.provider_name().to_lowercase())
+            .collect();
+        let mut summary = format!("# {}\n\n", title);
+        summary.push_str(&format!(
+            "{} resources from {}, {}\n",
+            result.resources.len(),
+            if providers.is_empty() {
+                "no providers".to_string()
+            } else {
+                providers.into_iter().collect::<Vec<_>>().join(", ")
+            },
+            Utc::now().format("%Y-%m-%d %H:%M UTC")
+        ));
+        for failure in &result.errors {
+            summary.push_str(&format!(
+                "\n_{} unavailable: {}_\n",
+                failure.provider, failure.error
+            ));
+        }
+
+        for (included, resource) in result.resources.iter().enumerate() {
+            let section = section(resource, query, passage_chars);
+            if max_chars.is_some_and(|max| summary.len() + section.len() > max) {
+                summary.push_str(&format!(
+                    "\n_{} more resources left out._\n",
+                    result.resources.len() - included
+                ));
+                break;
+            }
+            summary.push_str(&section);
+        }
+        summary
+    }
+}
+
+impl Step {
+    fn name(&self) -> &'static str {
+        match self {
+            Step::Search(_) => "search",
+            Step::Fetch(_) => "fetch",
+            Step::Where(_) => "where",
+            Step::Sort(_) => "sort",
+            Step::Limit(_) => "limit",
+            Step::Summarize(_) => "summarize",
+            Step::Export(_) => "export",
+            Step::Post(_) => "post",
+        }
+    }
+}
+
+impl SortKey {
+    fn sort(&self, resources: &mut [Resource]) {
+        resources.sort_by(|a, b| {
+            let order = match self.field {
+                SortField::Id => a.id.cmp(&b.id),
+                SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+                SortField::Provider => a
+                    .source
+                    .provider_name()
+                    .to_lowercase()
+                    .cmp(&b.source.provider_name().to_lowercase()),
+                SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+                SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            };
+            if self.descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let (descending, name) = match key.trim().strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, key.trim()),
+        };
+        let field = match name {
+            "id" => SortField::Id,
+            "title" => SortField::Title,
+            "provider" => SortField::Provider,
+            "created_at" => SortField::CreatedAt,
+            "updated_at" => SortField::UpdatedAt,
+            _ => {
+                return Err(format!(
+                    "can't sort by {}; use id, title, provider, created_at or updated_at, \
+                     with a leading - for descending order",
+                    name
+                ))
+            }
+        };
+        Ok(Self { field, descending })
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.field {
+            SortField::Id => "id",
+            SortField::Title => "title",
+            SortField::Provider => "provider",
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+        };
+        if self.descending {
+            write!(f, "{} descending", name)
+        } else {
+            write!(f, "{}", name)
+        }
+    }
+}
+
+impl From<String> for SearchStep {
+    fn from(query: String) -> Self {
+        Self {
+            query,
+            sources: Vec::new(),
+            include_archived: false,
+            max_staleness: None,
+        }
+    }
+}
+
+impl From<String> for FetchStep {
+    fn from(source: String) -> Self {
+        Self {
+            source,
+            filters: HashMap::new(),
+            limit: None,
+        }
+    }
+}
+
+impl From<String> for ExportStep {
+    fn from(path: String) -> Self {
+        Self { path, format: None }
+    }
+}
+
+impl From<String> for PostStep {
+    fn from(url: String) -> Self {
+        Self {
+            url,
+            format: Format::default(),
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+/// Adds `result`'s resources not already in `into`, with its failures and
+/// warnings, returning how many were added.
+fn merge(into: &mut QueryResult, result: QueryResult) -> usize {
+    let mut added = 0;
+    for resource in result.resources {
+        if !into.resources.iter().any(|other| other.id == resource.id) {
+            into.resources.push(resource);
+            added += 1;
+        }
+    }
+    into.errors.extend(result.errors);
+    into.warnings.extend(result.warnings);
+    into.origins.extend(result.origins);
+    added
+}
+
+/// One resource's part of a summary.
+fn section(resource: &Resource, query: Option<&str>, passage_chars: usize) -> String {
+    let link = resource
+        .provenance
+        .as_ref()
+        .and_then(|provenance| provenance.url.clone())
+        .unwrap_or_else(|| resource.uri());
+    let mut section = format!("\n## [{}]({})\n\n", resource.title, link);
+    section.push_str(&format!(
+        "{} · updated {}",
+        resource.source.provider_name().to_lowercase(),
+        resource.updated_at.format("%Y-%m-%d")
+    ));
+    if let Some(state) = resource.metadata.get("state").and_then(|s| s.as_str()) {
+        section.push_str(&format!(" · {}", state));
+    }
+    section.push('\n');
+
+    let passage = query
+        .and_then(|query| {
+            chunks::rank(std::slice::from_ref(resource), query, 1)
+                .into_iter()
+                .next()
+        })
+        .map_or_else(|| resource.content.clone(), |chunk| chunk.text);
+    let passage = truncate(passage.trim(), passage_chars);
+    // Quoted, so the passage's own headings don't nest under the digest's.
+    if !passage.is_empty() {
+        section.push('\n');
+        for line in passage.lines() {
+            section.push_str(format!("> {}", line).trim_end());
+            section.push('\n');
+        }
+    }
+    section
+}
+
+fn storage_error(path: &Path, e: std::io::Error) -> DomainError {
+    DomainError::StorageError(format!("failed to write {}: {}", path.display(), e))
+}
+
+/// Deserializes a step given either in full or as the string its main
+/// field takes, e.g. `search: <query>`.
+fn shorthand<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de> + From<String>,
+    D: Deserializer<'de>,
+{
+    struct Shorthand<T>(PhantomData<T>);
+
+    impl<'de, T> de::Visitor<'de> for Shorthand<T>
+    where
+        T: Deserialize<'de> + From<String>,
+    {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a string or a map")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+            Ok(T::from(value.to_string()))
+        }
+
+        fn visit_map<M: de::MapAccess<'de>>(self, map: M) -> Result<T, M::Error> {
+            T::deserialize(de::value::MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(Shorthand(PhantomData))
+}
+
+/// Deserializes a value written as a string, such as a `where` condition.
+fn parsed<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+/// Deserializes a humantime duration such as `1h`.
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    humantime::parse_duration(&String::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
@@ -0,0 +1,562 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    application::ResourceService,
+    domain::DomainError,
+    infrastructure::{
+        adapters::{
+            self,
+            azuredevops::AzureDevOpsAdapter,
+            coda::CodaAdapter,
+            elasticsearch::ElasticsearchAdapter,
+            graphql::GraphqlAdapter,
+            imap::ImapAdapter,
+            linear::LinearAdapter,
+            mcp::{McpClientAdapter, Upstream},
+            notion::NotionAdapter,
+            rest::RestAdapter,
+            todoist::TodoistAdapter,
+            trello::TrelloAdapter,
+            vault::VaultAdapter,
+            zendesk::ZendeskAdapter,
+        },
+        config::{
+            credentials::{Credential, CredentialResolver},
+            secrets::SecretCipher,
+            Config, ConfigError, ElasticsearchConfig, GraphqlConfig, RestConfig, RetryConfig,
+            SqlConfig, UpstreamConfig, VaultConfig,
+        },
+        sandbox,
+    },
+    ports::{LazyProvider, ResourceProvider},
+};
+
+/// How often the config file is checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often credentials are resolved again, for keys changed in the
+/// keyring, where changes can't be watched.
+const CREDENTIAL_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What a provider is built from. Providers are rebuilt when theirs change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderSettings {
+    Notion {
+        api_key: String,
+        retry: RetryConfig,
+    },
+    Linear {
+        api_key: String,
+        retry: RetryConfig,
+    },
+    Trello {
+        api_key: String,
+        retry: RetryConfig,
+    },
+    Zendesk {
+        url: String,
+        api_key: String,
+        retry: RetryConfig,
+    },
+    AzureDevOps {
+        url: String,
+        api_key: String,
+        retry: RetryConfig,
+    },
+    Todoist {
+        api_key: String,
+        retry: RetryConfig,
+    },
+    Coda {
+        api_key: String,
+        retry: RetryConfig,
+    },
+    Imap {
+        url: String,
+        api_key: String,
+        mailboxes: Vec<String>,
+    },
+    Upstream(UpstreamConfig),
+    Vault(VaultConfig),
+    Rest(Box<RestConfig>),
+    Graphql(Box<GraphqlConfig>),
+    Sql(Box<SqlConfig>),
+    Elasticsearch(Box<ElasticsearchConfig>),
+}
+
+impl ProviderSettings {
+    /// Settings of every provider `config` and `credentials` set up, by
+    /// provider name.
+    pub fn from_config(
+        config: &Config,
+        credentials: &HashMap<&str, Credential>,
+    ) -> BTreeMap<String, Self> {
+        let retry = |provider: &str| {
+            config
+                .provider(provider)
+                .map(|provider| provider.retry.clone())
+                .unwrap_or_default()
+        };
+        let mut settings = BTreeMap::new();
+        if let Some(credential) = credentials.get("notion") {
+            let api_key = credential.value.clone();
+            let retry = retry("notion");
+            settings.insert("notion".to_string(), Self::Notion { api_key, retry });
+        }
+        if let Some(credential) = credentials.get("linear") {
+            let api_key = credential.value.clone();
+            let retry = retry("linear");
+            settings.insert("linear".to_string(), Self::Linear { api_key, retry });
+        }
+        if let Some(credential) = credentials.get("trello") {
+            let api_key = credential.value.clone();
+            let retry = retry("trello");
+            settings.insert("trello".to_string(), Self::Trello { api_key, retry });
+        }
+        if let Some(credential) = credentials.get("zendesk") {
+            match config.provider("zendesk").and_then(|p| p.url.clone()) {
+                Some(url) => {
+                    let api_key = credential.value.clone();
+                    let retry = retry("zendesk");
+                    let zendesk = Self::Zendesk {
+                        url,
+                        api_key,
+                        retry,
+                    };
+                    settings.insert("zendesk".to_string(), zendesk);
+                }
+                None => tracing::warn!("Skipping Zendesk: set providers.zendesk.url"),
+            }
+        }
+        if let Some(credential) = credentials.get("azuredevops") {
+            match config.provider("azuredevops").and_then(|p| p.url.clone()) {
+                Some(url) => {
+                    let api_key = credential.value.clone();
+                    let retry = retry("azuredevops");
+                    let azure_devops = Self::AzureDevOps {
+                        url,
+                        api_key,
+                        retry,
+                    };
+                    settings.insert("azuredevops".to_string(), azure_devops);
+                }
+                None => tracing::warn!("Skipping Azure DevOps: set providers.azuredevops.url"),
+            }
+        }
+        if let Some(credential) = credentials.get("todoist") {
+            let api_key = credential.value.clone();
+            let retry = retry("todoist");
+            settings.insert("todoist".to_string(), Self::Todoist { api_key, retry });
+        }
+        if let Some(credential) = credentials.get("coda") {
+            let api_key = credential.value.clone();
+            let retry = retry("coda");
+            settings.insert("coda".to_string(), Self::Coda { api_key, retry });
+        }
+        if let Some(credential) = credentials.get("imap") {
+            match config.provider("imap").filter(|p| p.url.is_some()) {
+                Some(provider) => {
+                    let imap = Self::Imap {
+                        url: provider.url.clone().unwrap_or_default(),
+                        api_key: credential.value.clone(),
+                        mailboxes: provider.mailboxes.clone(),
+                    };
+                    settings.insert("imap".to_string(), imap);
+                }
+                None => tracing::warn!("Skipping IMAP: set providers.imap.url"),
+            }
+        }
+        let sandboxed = sandbox::active().is_some();
+        for (name, upstream) in &config.upstreams {
+            if upstream.command.is_some() == upstream.url.is_some() {
+                tracing::warn!("Skipping upstream {}: set either command or url", name);
+                continue;
+            }
+            if sandboxed && upstream.command.is_some() {
+                tracing::warn!("Skipping upstream {}: the sandbox runs no commands", name);
+                continue;
+            }
+            settings.insert(name.clone(), Self::Upstream(upstream.clone()));
+        }
+        for (name, vault) in &config.vaults {
+            if sandboxed {
+                tracing::warn!("Skipping vault {}: the sandbox reads no local files", name);
+                continue;
+            }
+            if settings.contains_key(name) {
+                tracing::warn!("Skipping vault {}: an upstream has the same name", name);
+                continue;
+            }
+            settings.insert(name.clone(), Self::Vault(vault.clone()));
+        }
+        for (name, rest) in &config.rest {
+            if sandboxed {
+                tracing::warn!(
+                    "Skipping REST API {}: the sandbox allows no arbitrary APIs",
+                    name
+                );
+                continue;
+            }
+            if settings.contains_key(name) {
+                tracing::warn!(
+                    "Skipping REST API {}: an upstream or vault has the same name",
+                    name
+                );
+                continue;
+            }
+            settings.insert(name.clone(), Self::Rest(Box::new(rest.clone())));
+        }
+        for (name, graphql) in &config.graphql {
+            if sandboxed {
+                tracing::warn!(
+                    "Skipping GraphQL API {}: the sandbox allows no arbitrary APIs",
+                    name
+                );
+                continue;
+            }
+            if settings.contains_key(name) {
+                tracing::warn!(
+                    "Skipping GraphQL API {}: an upstream, vault or REST API has the same name",
+                    name
+                );
+                continue;
+            }
+            settings.insert(name.clone(), Self::Graphql(Box::new(graphql.clone())));
+        }
+        for (name, sql) in &config.sql {
+            if sandboxed && sql.url.trim().starts_with("sqlite:") {
+                tracing::warn!(
+                    "Skipping SQL database {}: the sandbox reads no local files",
+                    name
+                );
+                continue;
+            }
+            if settings.contains_key(name) {
+                tracing::warn!(
+                    "Skipping SQL database {}: an upstream, vault, REST or GraphQL API has the same name",
+                    name
+                );
+                continue;
+            }
+            settings.insert(name.clone(), Self::Sql(Box::new(sql.clone())));
+        }
+        for (name, elasticsearch) in &config.elasticsearch {
+            if sandboxed {
+                tracing::warn!(
+                    "Skipping Elasticsearch index {}: the sandbox allows no arbitrary APIs",
+                    name
+                );
+                continue;
+            }
+            if settings.contains_key(name) {
+                tracing::warn!(
+                    "Skipping Elasticsearch index {}: another configured provider has the same name",
+                    name
+                );
+                continue;
+            }
+            let elasticsearch = Self::Elasticsearch(Box::new(elasticsearch.clone()));
+            settings.insert(name.clone(), elasticsearch);
+        }
+        settings
+    }
+
+    /// A provider named `name` set up with these settings.
+    pub fn build(&self, name: &str) -> Result<Arc<dyn ResourceProvider>, DomainError> {
+        Ok(match self {
+            Self::Notion { api_key, retry } => {
+                let policy = NotionAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(NotionAdapter::new(api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::Linear { api_key, retry } => {
+                let policy = LinearAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(LinearAdapter::new(api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::Trello { api_key, retry } => {
+                let policy = TrelloAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(TrelloAdapter::new(api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::Zendesk {
+                url,
+                api_key,
+                retry,
+            } => {
+                let policy = ZendeskAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(ZendeskAdapter::new(url, api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::AzureDevOps {
+                url,
+                api_key,
+                retry,
+            } => {
+                let policy = AzureDevOpsAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(AzureDevOpsAdapter::new(url, api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::Todoist { api_key, retry } => {
+                let policy = TodoistAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(TodoistAdapter::new(api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::Coda { api_key, retry } => {
+                let policy = CodaAdapter::default_retry_policy().with_overrides(retry);
+                Arc::new(CodaAdapter::new(api_key.clone())?.with_retry_policy(policy))
+            }
+            Self::Imap {
+                url,
+                api_key,
+                mailboxes,
+            } => Arc::new(ImapAdapter::new(url, api_key.clone(), mailboxes.clone())?),
+            Self::Upstream(upstream) => {
+                let transport = match (&upstream.command, &upstream.url) {
+                    (Some(command), _) => Upstream::Stdio {
+                        command: command.clone(),
+                        args: upstream.args.clone(),
+                        env: upstream.env.clone(),
+                    },
+                    (None, url) => Upstream::Http {
+                        url: url.clone().unwrap_or_default(),
+                        token: upstream.token.clone(),
+                    },
+                };
+                let policy =
+                    McpClientAdapter::default_retry_policy().with_overrides(&upstream.retry);
+                Arc::new(McpClientAdapter::new(name, transport)?.with_retry_policy(policy))
+            }
+            Self::Vault(vault) => Arc::new(VaultAdapter::new(name, vault.path(), vault.obsidian)?),
+            Self::Rest(rest) => {
+                let policy = RestAdapter::default_retry_policy().with_overrides(&rest.retry);
+                let name = adapters::provider_name("REST API", name)?;
+                Arc::new(RestAdapter::new(name, rest)?.with_retry_policy(policy))
+            }
+            Self::Graphql(graphql) => {
+                let policy = GraphqlAdapter::default_retry_policy().with_overrides(&graphql.retry);
+                let name = adapters::provider_name("GraphQL API", name)?;
+                Arc::new(GraphqlAdapter::new(name, graphql)?.with_retry_policy(policy))
+            }
+            Self::Elasticsearch(elasticsearch) => {
+                let policy = ElasticsearchAdapter::default_retry_policy()
+                    .with_overrides(&elasticsearch.retry);
+                let name = adapters::provider_name("Elasticsearch index", name)?;
+                Arc::new(ElasticsearchAdapter::new(name, elasticsearch)?.with_retry_policy(policy))
+            }
+            #[cfg(feature = "sql")]
+            Self::Sql(sql) => {
+                let name = adapters::provider_name("SQL database", name)?;
+                Arc::new(adapters::sql::SqlAdapter::new(name, sql)?)
+            }
+            #[cfg(not(feature = "sql"))]
+            Self::Sql(_) => {
+                return Err(DomainError::InvalidQuery(
+                    crate::infrastructure::features::missing("sql"),
+                ))
+            }
+        })
+    }
+
+    /// A provider named `name` that is set up with these settings the
+    /// first time it's used.
+    pub fn lazy(&self, name: &str) -> Result<Arc<dyn ResourceProvider>, DomainError> {
+        let provider_name = match self {
+            Self::Notion { .. } => "Notion",
+            Self::Linear { .. } => "Linear",
+            Self::Trello { .. } => "Trello",
+            Self::Zendesk { .. } => "Zendesk",
+            Self::AzureDevOps { .. } => "AzureDevOps",
+            Self::Todoist { .. } => "Todoist",
+            Self::Coda { .. } => "Coda",
+            Self::Imap { .. } => "IMAP",
+            Self::Upstream(_) => adapters::provider_name("upstream", name)?,
+            Self::Vault(_) => adapters::provider_name("vault", name)?,
+            Self::Rest(_) => adapters::provider_name("REST API", name)?,
+            Self::Graphql(_) => adapters::provider_name("GraphQL API", name)?,
+            Self::Sql(_) => adapters::provider_name("SQL database", name)?,
+            Self::Elasticsearch(_) => adapters::provider_name("Elasticsearch index", name)?,
+        };
+        let settings = self.clone();
+        let name = name.to_string();
+        Ok(Arc::new(LazyProvider::new(provider_name, move || {
+            settings.build(&name)
+        })))
+    }
+
+    /// How providers with these settings are described in the log.
+    pub fn describe(&self, name: &str) -> String {
+        match self {
+            Self::Notion { .. } => "Notion provider".to_string(),
+            Self::Linear { .. } => "Linear provider".to_string(),
+            Self::Trello { .. } => "Trello provider".to_string(),
+            Self::Zendesk { .. } => "Zendesk provider".to_string(),
+            Self::AzureDevOps { .. } => "Azure DevOps provider".to_string(),
+            Self::Todoist { .. } => "Todoist provider".to_string(),
+            Self::Coda { .. } => "Coda provider".to_string(),
+            Self::Imap { .. } => "IMAP provider".to_string(),
+            Self::Upstream(_) => format!("Upstream {}", name),
+            Self::Vault(_) => format!("Vault {}", name),
+            Self::Rest(_) => format!("REST API {}", name),
+            Self::Graphql(_) => format!("GraphQL API {}", name),
+            Self::Sql(_) => format!("SQL database {}", name),
+            Self::Elasticsearch(_) => format!("Elasticsearch index {}", name),
+        }
+    }
+}
+
+/// Loads the config file at `path`, decrypting its secrets. Decryption may
+/// consult the OS keyring, which blocks, so it runs off the async workers;
+/// secrets that fail to decrypt are logged and left encrypted.
+pub async fn load_config(path: PathBuf) -> Result<Config, ConfigError> {
+    tokio::task::spawn_blocking(move || {
+        let mut config = Config::load(&path)?;
+        let cipher = SecretCipher::new(config.encryption.identity_file.clone());
+        for (provider, e) in config.decrypt_secrets(&cipher) {
+            tracing::warn!("Failed to decrypt {} secret: {}", provider, e);
+        }
+        Ok(config)
+    })
+    .await
+    .map_err(|e| ConfigError::Secret(e.to_string()))?
+}
+
+/// Applies changes to the config file, and to the credentials it names, to
+/// a running service: providers are added, removed and rebuilt with new
+/// keys or retry settings, and the scope served is redefined. Everything
+/// else in the file takes a restart.
+pub struct ConfigReloader {
+    path: PathBuf,
+    /// API keys given on the command line, which outrank the file.
+    api_keys: HashMap<String, String>,
+    service: Arc<ResourceService>,
+    /// Scope served, whose definition is reloaded too.
+    scope: Option<String>,
+    /// Settings the service's providers were built from.
+    settings: BTreeMap<String, ProviderSettings>,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigReloader {
+    /// `settings` are what the service's providers were built from at
+    /// startup.
+    pub fn new(
+        path: PathBuf,
+        api_keys: HashMap<String, String>,
+        service: Arc<ResourceService>,
+        settings: BTreeMap<String, ProviderSettings>,
+    ) -> Self {
+        Self {
+            modified: modified(&path),
+            path,
+            api_keys,
+            service,
+            scope: None,
+            settings,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: Option<String>) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Reloads whenever the config file changes, credentials are due for
+    /// another look, or, on Unix, the process gets `SIGHUP`. Runs forever.
+    pub async fn run(mut self) {
+        let mut file_checks = tokio::time::interval(CHECK_INTERVAL);
+        let mut credential_checks = tokio::time::interval(CREDENTIAL_CHECK_INTERVAL);
+        credential_checks.tick().await;
+        #[cfg(unix)]
+        let mut hangups =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+        loop {
+            #[cfg(unix)]
+            let hangup = async {
+                match hangups.as_mut() {
+                    Some(hangups) => hangups.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = file_checks.tick() => {
+                    let modified = modified(&self.path);
+                    if modified == self.modified {
+                        continue;
+                    }
+                    self.modified = modified;
+                    tracing::info!("Config file changed, reloading");
+                }
+                _ = credential_checks.tick() => {}
+                _ = hangup => tracing::info!("Got SIGHUP, reloading config"),
+            }
+            self.reload().await;
+        }
+    }
+
+    async fn reload(&mut self) {
+        let config = match load_config(self.path.clone()).await {
+            Ok(config) => Arc::new(config),
+            Err(e) => {
+                tracing::warn!("Keeping the current config: {}", e);
+                return;
+            }
+        };
+
+        if let Some(name) = &self.scope {
+            match config.scope(name) {
+                Some(scope) => self.service.update_scope(scope),
+                None => tracing::warn!("Scope {} left the config file; keeping it", name),
+            }
+        }
+
+        self.service.set_source_groups(config.sources.clone());
+        self.service.set_sync_scopes(config.sync_scopes());
+        let classification = config.classification();
+        if self.service.classifies() {
+            let classification = classification.unwrap_or_default();
+            self.service
+                .update_classification(classification.default, classification.rules);
+        } else if classification.is_some() {
+            tracing::warn!("Classification was added to the config file; restart to apply it");
+        }
+
+        let credentials = CredentialResolver::new(self.api_keys.clone(), config.clone())
+            .resolve_all()
+            .await;
+        let settings = ProviderSettings::from_config(&config, &credentials);
+        for (name, old) in &self.settings {
+            if !settings.contains_key(name) && self.service.remove_provider(name) {
+                tracing::info!("{} removed", old.describe(name));
+            }
+        }
+        for (name, new) in &settings {
+            let changed = self.settings.get(name) != Some(new);
+            // Providers a redefined scope covers again are added back.
+            let uncovered = self.scope.is_some()
+                && self.service.scope_includes(name)
+                && self.service.get_provider(name).is_none();
+            if !changed && !uncovered {
+                continue;
+            }
+            // Settings are by name, so a provider already registered under
+            // it is the one being rebuilt.
+            match new.lazy(name) {
+                Ok(provider) => {
+                    if self.service.replace_provider(provider) {
+                        tracing::info!("{} configured", new.describe(name));
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to configure {}: {}", new.describe(name), e),
+            }
+        }
+        self.settings = settings;
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
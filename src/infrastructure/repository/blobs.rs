@@ -0,0 +1,136 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::domain::DomainError;
+
+/// How long a blob is kept after it was written before garbage collection
+/// may remove it, even unreferenced: another process may have stored it
+/// and not yet written the row that references it.
+const GC_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Files stored by the SHA-256 of their contents, in a directory next to
+/// the cache database, so identical contents are stored once however many
+/// rows reference them. Blobs are only removed by `collect_garbage`.
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+/// What `collect_garbage` removed.
+#[derive(Debug, Default)]
+pub struct Collected {
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+impl BlobStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Stores `data`, returning its hash, by which it is read back.
+    pub fn put(&self, data: &[u8]) -> Result<String, DomainError> {
+        let hash: String = Sha256::digest(data)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let path = self.path(&hash);
+        if path.exists() {
+            return Ok(hash);
+        }
+        let parent = path.parent().unwrap_or(&self.dir);
+        std::fs::create_dir_all(parent).map_err(|e| blob_error(parent, e))?;
+        // Written aside and renamed into place, so a blob that exists is
+        // always whole.
+        let partial = parent.join(format!("{}.{}.partial", hash, uuid::Uuid::new_v4()));
+        std::fs::write(&partial, data)
+            .and_then(|_| std::fs::rename(&partial, &path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&partial);
+                blob_error(&path, e)
+            })?;
+        Ok(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>, DomainError> {
+        if !is_hash(hash) {
+            return Err(DomainError::StorageError(format!(
+                "Invalid blob reference: {}",
+                hash
+            )));
+        }
+        let path = self.path(hash);
+        std::fs::read(&path).map_err(|e| blob_error(&path, e))
+    }
+
+    /// How many blobs are stored, and their total size.
+    pub fn usage(&self) -> (usize, u64) {
+        self.blobs()
+            .fold((0, 0), |(count, bytes), (_, _, metadata)| {
+                (count + 1, bytes + metadata.len())
+            })
+    }
+
+    /// Removes every blob not in `referenced`, other than those written too
+    /// recently to tell. Leftovers of interrupted writes go too.
+    pub fn collect_garbage(&self, referenced: &HashSet<String>) -> Collected {
+        let now = SystemTime::now();
+        let mut collected = Collected::default();
+        for (hash, path, metadata) in self.blobs() {
+            if referenced.contains(&hash) {
+                continue;
+            }
+            let age = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok());
+            if age.is_none_or(|age| age < GC_GRACE) {
+                continue;
+            }
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    collected.blobs += 1;
+                    collected.bytes += metadata.len();
+                }
+                Err(e) => tracing::warn!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+        collected
+    }
+
+    /// Every file in the store, blob or not, with the name it's stored
+    /// under.
+    fn blobs(&self) -> impl Iterator<Item = (String, PathBuf, std::fs::Metadata)> {
+        std::fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|shard| std::fs::read_dir(shard.path()).ok())
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                Some((name, entry.path(), metadata))
+            })
+    }
+
+    /// Blobs are spread over directories named for their first two hex
+    /// digits, keeping each directory small.
+    fn path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+}
+
+fn is_hash(text: &str) -> bool {
+    text.len() == 64 && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn blob_error(path: &Path, error: std::io::Error) -> DomainError {
+    DomainError::StorageError(format!("Blob {}: {}", path.display(), error))
+}
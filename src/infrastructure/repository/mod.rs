@@ -0,0 +1,1001 @@
+mod blobs;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{
+    domain::{Alias, Blob, CachedResource, CachedSearch, DomainError, Resource, UsageEvent},
+    ports::ResourceRepository,
+};
+use blobs::BlobStore;
+
+/// Content longer than this, in bytes, is stored in the blob store rather
+/// than in the resource's row, so the versions kept of a large page that
+/// changed in other ways share one copy of it.
+const LARGE_BODY_BYTES: usize = 256 * 1024;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS resources (
+        id TEXT PRIMARY KEY,
+        provider TEXT NOT NULL,
+        data TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        cached_at TEXT NOT NULL,
+        deleted_at TEXT
+    );
+    CREATE INDEX IF NOT EXISTS resources_provider ON resources (provider);
+    CREATE TABLE IF NOT EXISTS syncs (
+        provider TEXT PRIMARY KEY,
+        synced_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS searches (
+        provider TEXT NOT NULL,
+        query TEXT NOT NULL,
+        ids TEXT NOT NULL,
+        searched_at TEXT NOT NULL,
+        PRIMARY KEY (provider, query)
+    );
+    CREATE TABLE IF NOT EXISTS aliases (
+        name TEXT PRIMARY KEY COLLATE NOCASE,
+        id TEXT NOT NULL,
+        automatic INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS usage (
+        event TEXT NOT NULL,
+        name TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        first_at TEXT NOT NULL,
+        last_at TEXT NOT NULL,
+        PRIMARY KEY (event, name)
+    );
+    CREATE TABLE IF NOT EXISTS attachments (
+        id TEXT NOT NULL,
+        idx INTEGER NOT NULL,
+        updated_at TEXT NOT NULL,
+        blob TEXT NOT NULL,
+        mime_type TEXT NOT NULL,
+        stored_at TEXT NOT NULL,
+        PRIMARY KEY (id, idx)
+    );
+";
+
+/// Full-text index of the live resources' titles and content, kept up to
+/// date by triggers as resources are stored, replaced, tombstoned and
+/// removed. Index rows share their resource's rowid and repeat its ID, so
+/// a row left behind by a write that skipped the triggers can't be taken
+/// for another resource.
+const INDEX_SCHEMA: &str = "
+    CREATE VIRTUAL TABLE resources_fts USING fts5(
+        id UNINDEXED,
+        title,
+        content,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    CREATE TRIGGER resources_fts_insert AFTER INSERT ON resources
+    WHEN new.deleted_at IS NULL BEGIN
+        INSERT INTO resources_fts (rowid, id, title, content)
+        VALUES (new.rowid, new.id, json_extract(new.data, '$.title'),
+                json_extract(new.data, '$.content'));
+    END;
+    CREATE TRIGGER resources_fts_update AFTER UPDATE ON resources BEGIN
+        DELETE FROM resources_fts WHERE rowid = old.rowid;
+        INSERT INTO resources_fts (rowid, id, title, content)
+        SELECT new.rowid, new.id, json_extract(new.data, '$.title'),
+               json_extract(new.data, '$.content')
+        WHERE new.deleted_at IS NULL;
+    END;
+    CREATE TRIGGER resources_fts_delete AFTER DELETE ON resources BEGIN
+        DELETE FROM resources_fts WHERE rowid = old.rowid;
+    END;
+";
+
+/// Every version of each resource the cache has stored, for reading the
+/// cache as it was at an earlier time. A version takes effect when the
+/// provider last updated it, or when it was tombstoned; storing a resource
+/// again only adds a version if either changed. The history outlives TTL
+/// eviction, so a resource fetched again later picks up where it left off.
+const HISTORY_SCHEMA: &str = "
+    CREATE TABLE resource_versions (
+        id TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        data TEXT NOT NULL,
+        valid_from TEXT NOT NULL,
+        deleted INTEGER NOT NULL
+    );
+    CREATE INDEX resource_versions_id ON resource_versions (id, valid_from);
+    CREATE INDEX resource_versions_provider ON resource_versions (provider);
+    CREATE TRIGGER resource_versions_insert AFTER INSERT ON resources
+    WHEN NOT EXISTS (
+        SELECT 1 FROM (
+            SELECT valid_from, deleted FROM resource_versions
+            WHERE id = new.id ORDER BY rowid DESC LIMIT 1
+        )
+        WHERE deleted = (new.deleted_at IS NOT NULL) AND (deleted OR valid_from = new.updated_at)
+    ) BEGIN
+        INSERT INTO resource_versions (id, provider, data, valid_from, deleted)
+        VALUES (new.id, new.provider, new.data, COALESCE(new.deleted_at, new.updated_at),
+                new.deleted_at IS NOT NULL);
+    END;
+    CREATE TRIGGER resource_versions_update AFTER UPDATE ON resources
+    WHEN new.updated_at IS NOT old.updated_at OR new.deleted_at IS NOT old.deleted_at BEGIN
+        INSERT INTO resource_versions (id, provider, data, valid_from, deleted)
+        VALUES (new.id, new.provider, new.data, COALESCE(new.deleted_at, new.updated_at),
+                new.deleted_at IS NOT NULL);
+    END;
+";
+
+/// The newest version of each resource in effect at `?2`, as a
+/// `resource_versions` alias `v`.
+const VERSION_AS_OF: &str = "v.valid_from <= ?2 AND v.rowid = (
+        SELECT w.rowid FROM resource_versions w
+        WHERE w.id = v.id AND w.valid_from <= ?2
+        ORDER BY w.valid_from DESC, w.rowid DESC LIMIT 1
+    )";
+
+/// How the full-text index compares to the cache it indexes.
+#[derive(Debug)]
+pub struct IndexStatus {
+    /// Live resources, which should all be indexed.
+    pub resources: usize,
+    pub indexed: usize,
+    /// Live resources the index lacks.
+    pub missing: usize,
+    /// Index rows for resources that are gone or tombstoned.
+    pub orphaned: usize,
+}
+
+impl IndexStatus {
+    pub fn is_current(&self) -> bool {
+        self.missing == 0 && self.orphaned == 0
+    }
+}
+
+/// Size and freshness summary for one provider's cached resources.
+#[derive(Debug)]
+pub struct ProviderStats {
+    pub provider: String,
+    pub entries: usize,
+    /// Entries stored before the staleness cutoff passed to `stats`.
+    pub stale: usize,
+    pub bytes: u64,
+    pub oldest: DateTime<Utc>,
+    pub newest: DateTime<Utc>,
+}
+
+/// How often one thing counted in the local usage statistics happened.
+#[derive(Debug)]
+pub struct UsageCount {
+    pub event: UsageEvent,
+    pub name: String,
+    pub count: u64,
+    pub first_at: DateTime<Utc>,
+    pub last_at: DateTime<Utc>,
+}
+
+/// What `prune` removed.
+#[derive(Debug)]
+pub struct PruneReport {
+    /// Stored attachments of resources that changed or are gone.
+    pub attachments: usize,
+    /// Blobs nothing referenced any more.
+    pub blobs: usize,
+    pub bytes: u64,
+}
+
+/// Where a resource whose content was moved to the blob store keeps the
+/// content's hash, in its stored JSON.
+#[derive(Deserialize)]
+struct StoredBody {
+    content_blob: Option<String>,
+}
+
+/// Local SQLite store for resources fetched from providers. Downloaded
+/// attachments and large content are kept in a blob store beside it.
+pub struct SqliteRepository {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+    blobs: BlobStore,
+}
+
+impl SqliteRepository {
+    pub fn open(path: &Path) -> Result<Self, DomainError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                DomainError::StorageError(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+
+        let conn = Connection::open(path).map_err(storage_error)?;
+        // Replacing a resource then fires the delete trigger for the row it
+        // replaces, which takes the old row out of the index.
+        conn.pragma_update(None, "recursive_triggers", true)
+            .map_err(storage_error)?;
+        conn.execute_batch(SCHEMA).map_err(storage_error)?;
+        let blobs = BlobStore::new(path.with_extension("blobs"));
+        Self::migrate(&conn, &blobs)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            conn: Mutex::new(conn),
+            blobs,
+        })
+    }
+
+    /// Brings caches created by older versions up to the current schema.
+    fn migrate(conn: &Connection, blobs: &BlobStore) -> Result<(), DomainError> {
+        let has_deleted_at = conn
+            .prepare("SELECT 1 FROM pragma_table_info('resources') WHERE name = 'deleted_at'")
+            .and_then(|mut statement| statement.exists([]))
+            .map_err(storage_error)?;
+
+        if !has_deleted_at {
+            conn.execute("ALTER TABLE resources ADD COLUMN deleted_at TEXT", [])
+                .map_err(storage_error)?;
+        }
+
+        let has_index = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE name = 'resources_fts'")
+            .and_then(|mut statement| statement.exists([]))
+            .map_err(storage_error)?;
+        if !has_index {
+            conn.execute_batch(INDEX_SCHEMA).map_err(storage_error)?;
+            Self::fill_index(conn, blobs)?;
+        }
+
+        // Caches from before history was kept start it with what they hold.
+        let has_history = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE name = 'resource_versions'")
+            .and_then(|mut statement| statement.exists([]))
+            .map_err(storage_error)?;
+        if !has_history {
+            conn.execute_batch(HISTORY_SCHEMA).map_err(storage_error)?;
+            conn.execute(
+                "INSERT INTO resource_versions (id, provider, data, valid_from, deleted)
+                 SELECT id, provider, data, COALESCE(deleted_at, updated_at), deleted_at IS NOT NULL
+                 FROM resources",
+                [],
+            )
+            .map_err(storage_error)?;
+        }
+        Ok(())
+    }
+
+    /// Indexes every live resource into an empty index.
+    fn fill_index(conn: &Connection, blobs: &BlobStore) -> Result<usize, DomainError> {
+        let indexed = conn
+            .execute(
+                "INSERT INTO resources_fts (rowid, id, title, content)
+                 SELECT rowid, id, json_extract(data, '$.title'), json_extract(data, '$.content')
+                 FROM resources WHERE deleted_at IS NULL",
+                [],
+            )
+            .map_err(storage_error)?;
+
+        // Content in the blob store is indexed from there.
+        let mut statement = conn
+            .prepare(
+                "SELECT rowid, json_extract(data, '$.content_blob') FROM resources
+                 WHERE deleted_at IS NULL AND json_extract(data, '$.content_blob') IS NOT NULL",
+            )
+            .map_err(storage_error)?;
+        let bodies: Vec<(i64, String)> = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(storage_error)?
+            .collect::<Result<_, _>>()
+            .map_err(storage_error)?;
+        for (rowid, hash) in bodies {
+            let content = String::from_utf8_lossy(&blobs.get(&hash)?).into_owned();
+            conn.execute(
+                "UPDATE resources_fts SET content = ?1 WHERE rowid = ?2",
+                params![content, rowid],
+            )
+            .map_err(storage_error)?;
+        }
+        Ok(indexed)
+    }
+
+    /// Compares the full-text index with the resources it should hold.
+    pub fn index_status(&self) -> Result<IndexStatus, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let count = |sql: &str| {
+            conn.query_row(sql, [], |row| row.get::<_, i64>(0))
+                .map(|count| count as usize)
+                .map_err(storage_error)
+        };
+        Ok(IndexStatus {
+            resources: count("SELECT COUNT(*) FROM resources WHERE deleted_at IS NULL")?,
+            indexed: count("SELECT COUNT(*) FROM resources_fts")?,
+            missing: count(
+                "SELECT COUNT(*) FROM resources r WHERE r.deleted_at IS NULL AND NOT EXISTS
+                 (SELECT 1 FROM resources_fts f WHERE f.rowid = r.rowid AND f.id = r.id)",
+            )?,
+            orphaned: count(
+                "SELECT COUNT(*) FROM resources_fts f WHERE NOT EXISTS
+                 (SELECT 1 FROM resources r
+                  WHERE r.rowid = f.rowid AND r.id = f.id AND r.deleted_at IS NULL)",
+            )?,
+        })
+    }
+
+    /// Indexes the live resources afresh, for an index that has drifted
+    /// from them, e.g. after the database was vacuumed or written by a
+    /// version without the index. Returns how many were indexed.
+    pub fn rebuild_index(&self) -> Result<usize, DomainError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        tx.execute("DELETE FROM resources_fts", [])
+            .map_err(storage_error)?;
+        let indexed = Self::fill_index(&tx, &self.blobs)?;
+        tx.execute(
+            "INSERT INTO resources_fts (resources_fts) VALUES ('optimize')",
+            [],
+        )
+        .map_err(storage_error)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(indexed)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size of the database file on disk.
+    pub fn file_size(&self) -> u64 {
+        std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0)
+    }
+
+    /// Where blobs are stored, how many there are, and their total size.
+    pub fn blob_usage(&self) -> (&Path, usize, u64) {
+        let (blobs, bytes) = self.blobs.usage();
+        (self.blobs.dir(), blobs, bytes)
+    }
+
+    /// Forgets attachments stored for resources that have since changed or
+    /// left the cache, then removes the blobs nothing references any more:
+    /// those attachments, and the content of evicted resources and of
+    /// versions no longer kept.
+    pub fn prune(&self) -> Result<PruneReport, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let attachments = conn
+            .execute(
+                "DELETE FROM attachments WHERE NOT EXISTS (
+                     SELECT 1 FROM resources r
+                     WHERE r.id = attachments.id AND r.updated_at = attachments.updated_at
+                           AND r.deleted_at IS NULL
+                 )",
+                [],
+            )
+            .map_err(storage_error)?;
+
+        let mut statement = conn
+            .prepare(
+                "SELECT blob FROM attachments
+                 UNION SELECT json_extract(data, '$.content_blob') FROM resources
+                 UNION SELECT json_extract(data, '$.content_blob') FROM resource_versions",
+            )
+            .map_err(storage_error)?;
+        let referenced: HashSet<String> = statement
+            .query_map([], |row| row.get::<_, Option<String>>(0))
+            .map_err(storage_error)?
+            .filter_map(Result::transpose)
+            .collect::<Result<_, _>>()
+            .map_err(storage_error)?;
+
+        // The lock is held throughout, so nothing this process stores is
+        // collected before the row referencing it is written.
+        let collected = self.blobs.collect_garbage(&referenced);
+        Ok(PruneReport {
+            attachments,
+            blobs: collected.blobs,
+            bytes: collected.bytes,
+        })
+    }
+
+    pub fn stats(&self, stale_before: DateTime<Utc>) -> Result<Vec<ProviderStats>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT provider, COUNT(*), SUM(cached_at < ?1), SUM(LENGTH(data)),
+                        MIN(cached_at), MAX(cached_at)
+                 FROM resources GROUP BY provider ORDER BY provider",
+            )
+            .map_err(storage_error)?;
+
+        let rows = statement
+            .query_map(params![stale_before], |row| {
+                Ok(ProviderStats {
+                    provider: row.get(0)?,
+                    entries: row.get::<_, i64>(1)? as usize,
+                    stale: row.get::<_, i64>(2)? as usize,
+                    bytes: row.get::<_, i64>(3)? as u64,
+                    oldest: row.get(4)?,
+                    newest: row.get(5)?,
+                })
+            })
+            .map_err(storage_error)?;
+
+        rows.collect::<Result<_, _>>().map_err(storage_error)
+    }
+
+    /// Everything counted by `record_usage`, most frequent first. Clearing
+    /// the cache leaves these alone.
+    pub fn usage(&self) -> Result<Vec<UsageCount>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT event, name, count, first_at, last_at
+                 FROM usage ORDER BY count DESC, name",
+            )
+            .map_err(storage_error)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .map_err(storage_error)?;
+
+        let mut counts = Vec::new();
+        for row in rows {
+            let (event, name, count, first_at, last_at) = row.map_err(storage_error)?;
+            // Events only a newer version counts are left out.
+            let Some(event) = UsageEvent::parse(&event) else {
+                continue;
+            };
+            counts.push(UsageCount {
+                event,
+                name,
+                count: count as u64,
+                first_at,
+                last_at,
+            });
+        }
+        Ok(counts)
+    }
+
+    fn insert(
+        conn: &Connection,
+        blobs: &BlobStore,
+        resource: &Resource,
+    ) -> Result<(), DomainError> {
+        let body = (resource.content.len() > LARGE_BODY_BYTES)
+            .then(|| blobs.put(resource.content.as_bytes()))
+            .transpose()?;
+        let data = match &body {
+            Some(hash) => serde_json::to_value(resource).map(|mut data| {
+                data["content"] = "".into();
+                data["content_blob"] = hash.as_str().into();
+                data.to_string()
+            }),
+            None => serde_json::to_string(resource),
+        }
+        .map_err(|e| DomainError::StorageError(e.to_string()))?;
+
+        let now = Utc::now();
+        conn.execute(
+            "INSERT OR REPLACE INTO resources (id, provider, data, updated_at, cached_at, deleted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                resource.id,
+                resource.source.provider_name(),
+                data,
+                resource.updated_at,
+                now,
+                resource.is_archived().then_some(now)
+            ],
+        )
+        .map_err(storage_error)?;
+        // The index trigger only sees what is in the row.
+        if body.is_some() && !resource.is_archived() {
+            conn.execute(
+                "UPDATE resources_fts SET content = ?1
+                 WHERE rowid = (SELECT rowid FROM resources WHERE id = ?2)",
+                params![resource.content, resource.id],
+            )
+            .map_err(storage_error)?;
+        }
+
+        // Aliases the user set take precedence over short IDs.
+        if let Some(short_id) = resource.short_id() {
+            conn.execute(
+                "INSERT INTO aliases (name, id, automatic) VALUES (?1, ?2, 1)
+                 ON CONFLICT (name) DO UPDATE SET id = excluded.id WHERE automatic = 1",
+                params![short_id, resource.id],
+            )
+            .map_err(storage_error)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ResourceRepository for SqliteRepository {
+    async fn save(&self, resource: &Resource) -> Result<(), DomainError> {
+        let conn = self.conn.lock().unwrap();
+        Self::insert(&conn, &self.blobs, resource)
+    }
+
+    async fn save_all(&self, resources: &[Resource]) -> Result<(), DomainError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        for resource in resources {
+            Self::insert(&tx, &self.blobs, resource)?;
+        }
+        tx.commit().map_err(storage_error)
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM resource_versions WHERE id = ?1", params![id])
+            .map_err(storage_error)?;
+        let removed = conn
+            .execute("DELETE FROM resources WHERE id = ?1", params![id])
+            .map_err(storage_error)?;
+
+        if removed == 0 {
+            return Err(DomainError::ResourceNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn find_entry(&self, id: &str) -> Result<Option<CachedResource>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT data, cached_at, deleted_at FROM resources WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(storage_error)?
+        .map(|(data, cached_at, deleted_at)| to_entry(&self.blobs, &data, cached_at, deleted_at))
+        .transpose()
+    }
+
+    async fn list_entries(
+        &self,
+        provider: Option<&str>,
+        include_tombstoned: bool,
+    ) -> Result<Vec<CachedResource>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT data, cached_at, deleted_at FROM resources
+                 WHERE (?1 IS NULL OR provider = ?1) AND (?2 OR deleted_at IS NULL)
+                 ORDER BY cached_at DESC",
+            )
+            .map_err(storage_error)?;
+
+        let rows = statement
+            .query_map(
+                params![provider.map(|p| p.to_lowercase()), include_tombstoned],
+                |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(storage_error)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (data, cached_at, deleted_at) = row.map_err(storage_error)?;
+            entries.push(to_entry(&self.blobs, &data, cached_at, deleted_at)?);
+        }
+        Ok(entries)
+    }
+
+    async fn tombstone(&self, ids: &[String]) -> Result<usize, DomainError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let now = Utc::now();
+        let mut tombstoned = 0;
+        for id in ids {
+            tombstoned += tx
+                .execute(
+                    "UPDATE resources SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                    params![now, id],
+                )
+                .map_err(storage_error)?;
+        }
+        tx.commit().map_err(storage_error)?;
+        Ok(tombstoned)
+    }
+
+    async fn tombstoned_ids(&self) -> Result<Vec<String>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT id FROM resources WHERE deleted_at IS NOT NULL")
+            .map_err(storage_error)?;
+        let rows = statement
+            .query_map([], |row| row.get(0))
+            .map_err(storage_error)?;
+        rows.collect::<Result<_, _>>().map_err(storage_error)
+    }
+
+    async fn search_entries(
+        &self,
+        provider: &str,
+        query: &str,
+    ) -> Result<Vec<CachedResource>, DomainError> {
+        // Every word, as a prefix, in the title or content.
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sql = "SELECT r.data, r.cached_at, r.deleted_at
+                   FROM resources_fts f JOIN resources r ON r.rowid = f.rowid AND r.id = f.id
+                   WHERE resources_fts MATCH ?1 AND r.provider = ?2 AND r.deleted_at IS NULL
+                   ORDER BY f.rank";
+
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(sql).map_err(storage_error)?;
+        let rows = statement
+            .query_map(params![terms.join(" "), provider.to_lowercase()], |row| {
+                Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(storage_error)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (data, cached_at, deleted_at) = row.map_err(storage_error)?;
+            entries.push(to_entry(&self.blobs, &data, cached_at, deleted_at)?);
+        }
+        Ok(entries)
+    }
+
+    async fn find_version(
+        &self,
+        id: &str,
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<Resource>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let version = conn
+            .query_row(
+                &format!(
+                    "SELECT v.data, v.deleted FROM resource_versions v WHERE v.id = ?1 AND {}",
+                    VERSION_AS_OF
+                ),
+                params![id, as_of],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)),
+            )
+            .optional()
+            .map_err(storage_error)?;
+        match version {
+            Some((data, false)) => parse_resource(&self.blobs, &data).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    async fn search_versions(
+        &self,
+        provider: &str,
+        query: &str,
+        as_of: DateTime<Utc>,
+        include_tombstoned: bool,
+    ) -> Result<Vec<Resource>, DomainError> {
+        // The index only covers current resources, so earlier versions are
+        // matched with one LIKE pair per word; LIKE is case-insensitive for
+        // ASCII. Content in the blob store is matched once read.
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let patterns: Vec<String> = query
+            .split_whitespace()
+            .map(|word| format!("%{}%", escape_like(word)))
+            .collect();
+        let mut sql = format!(
+            "SELECT v.data, json_extract(v.data, '$.content_blob') IS NOT NULL
+             FROM resource_versions v WHERE v.provider = ?1 AND {}",
+            VERSION_AS_OF
+        );
+        if !include_tombstoned {
+            sql.push_str(" AND NOT v.deleted");
+        }
+        for n in 3..patterns.len() + 3 {
+            sql.push_str(&format!(
+                " AND (json_extract(v.data, '$.title') LIKE ?{n} ESCAPE '\\'
+                      OR json_extract(v.data, '$.content') LIKE ?{n} ESCAPE '\\'
+                      OR json_extract(v.data, '$.content_blob') IS NOT NULL)"
+            ));
+        }
+        sql.push_str(" ORDER BY v.valid_from DESC");
+
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&sql).map_err(storage_error)?;
+        let provider = provider.to_lowercase();
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&provider, &as_of];
+        values.extend(
+            patterns
+                .iter()
+                .map(|pattern| pattern as &dyn rusqlite::ToSql),
+        );
+        let rows = statement
+            .query_map(values.as_slice(), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?))
+            })
+            .map_err(storage_error)?;
+
+        let mut resources = Vec::new();
+        for row in rows {
+            let (data, in_blob) = row.map_err(storage_error)?;
+            let resource = parse_resource(&self.blobs, &data)?;
+            if in_blob {
+                let title = resource.title.to_lowercase();
+                let content = resource.content.to_lowercase();
+                if !words
+                    .iter()
+                    .all(|word| title.contains(word) || content.contains(word))
+                {
+                    continue;
+                }
+            }
+            resources.push(resource);
+        }
+        Ok(resources)
+    }
+
+    async fn record_sync(
+        &self,
+        provider: &str,
+        synced_at: DateTime<Utc>,
+    ) -> Result<(), DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO syncs (provider, synced_at) VALUES (?1, ?2)",
+            params![provider.to_lowercase(), synced_at],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn last_sync(&self, provider: &str) -> Result<Option<DateTime<Utc>>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT synced_at FROM syncs WHERE provider = ?1",
+            params![provider.to_lowercase()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(storage_error)
+    }
+
+    async fn save_search(
+        &self,
+        provider: &str,
+        query: &str,
+        ids: &[String],
+    ) -> Result<(), DomainError> {
+        let ids =
+            serde_json::to_string(ids).map_err(|e| DomainError::StorageError(e.to_string()))?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO searches (provider, query, ids, searched_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![provider.to_lowercase(), query, ids, Utc::now()],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn find_search(
+        &self,
+        provider: &str,
+        query: &str,
+    ) -> Result<Option<CachedSearch>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT ids, searched_at FROM searches WHERE provider = ?1 AND query = ?2",
+            params![provider.to_lowercase(), query],
+            |row| Ok((row.get::<_, String>(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(storage_error)?
+        .map(|(ids, searched_at)| {
+            let ids = serde_json::from_str(&ids)
+                .map_err(|e| DomainError::StorageError(format!("Corrupt search entry: {}", e)))?;
+            Ok(CachedSearch { ids, searched_at })
+        })
+        .transpose()
+    }
+
+    async fn find_attachment(
+        &self,
+        id: &str,
+        index: usize,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Option<Blob>, DomainError> {
+        let stored = {
+            let conn = self.conn.lock().unwrap();
+            conn.query_row(
+                "SELECT blob, mime_type FROM attachments
+                 WHERE id = ?1 AND idx = ?2 AND updated_at = ?3",
+                params![id, index as i64, updated_at],
+                |row| Ok((row.get::<_, String>(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(storage_error)?
+        };
+        let Some((hash, mime_type)) = stored else {
+            return Ok(None);
+        };
+        Ok(Some(Blob {
+            mime_type,
+            data: self.blobs.get(&hash)?,
+        }))
+    }
+
+    async fn save_attachment(
+        &self,
+        id: &str,
+        index: usize,
+        updated_at: DateTime<Utc>,
+        blob: &Blob,
+    ) -> Result<(), DomainError> {
+        let hash = self.blobs.put(&blob.data)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO attachments (id, idx, updated_at, blob, mime_type, stored_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                index as i64,
+                updated_at,
+                hash,
+                blob.mime_type,
+                Utc::now()
+            ],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn record_usage(&self, event: UsageEvent, name: &str) -> Result<(), DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO usage (event, name, count, first_at, last_at) VALUES (?1, ?2, 1, ?3, ?3)
+             ON CONFLICT (event, name) DO UPDATE SET count = count + 1, last_at = excluded.last_at",
+            params![event.as_str(), name.to_lowercase(), Utc::now()],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn set_alias(&self, alias: &str, id: &str) -> Result<(), DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO aliases (name, id, automatic) VALUES (?1, ?2, 0)",
+            params![alias, id],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn remove_alias(&self, alias: &str) -> Result<bool, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn
+            .execute("DELETE FROM aliases WHERE name = ?1", params![alias])
+            .map_err(storage_error)?;
+        Ok(removed > 0)
+    }
+
+    async fn resolve_alias(&self, alias: &str) -> Result<Option<String>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id FROM aliases WHERE name = ?1",
+            params![alias],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(storage_error)
+    }
+
+    async fn list_aliases(&self) -> Result<Vec<Alias>, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT name, id, automatic FROM aliases ORDER BY name")
+            .map_err(storage_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(Alias {
+                    name: row.get(0)?,
+                    id: row.get(1)?,
+                    automatic: row.get(2)?,
+                })
+            })
+            .map_err(storage_error)?;
+        rows.collect::<Result<_, _>>().map_err(storage_error)
+    }
+
+    async fn delete_by_provider(&self, provider: &str) -> Result<usize, DomainError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let provider = provider.to_lowercase();
+        let removed = tx
+            .execute(
+                "DELETE FROM resources WHERE provider = ?1",
+                params![provider],
+            )
+            .map_err(storage_error)?;
+        tx.execute(
+            "DELETE FROM resource_versions WHERE provider = ?1",
+            params![provider],
+        )
+        .map_err(storage_error)?;
+        tx.execute("DELETE FROM syncs WHERE provider = ?1", params![provider])
+            .map_err(storage_error)?;
+        tx.execute(
+            "DELETE FROM searches WHERE provider = ?1",
+            params![provider],
+        )
+        .map_err(storage_error)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(removed)
+    }
+
+    async fn delete_stale(&self, cached_before: DateTime<Utc>) -> Result<usize, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM resources WHERE cached_at < ?1",
+            params![cached_before],
+        )
+        .map_err(storage_error)
+    }
+
+    async fn clear(&self) -> Result<usize, DomainError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(
+            "DELETE FROM syncs; DELETE FROM searches; DELETE FROM resource_versions;",
+        )
+        .map_err(storage_error)?;
+        conn.execute("DELETE FROM resources", [])
+            .map_err(storage_error)
+    }
+}
+
+fn to_entry(
+    blobs: &BlobStore,
+    data: &str,
+    cached_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+) -> Result<CachedResource, DomainError> {
+    Ok(CachedResource {
+        resource: parse_resource(blobs, data)?,
+        cached_at,
+        deleted_at,
+    })
+}
+
+/// A resource from its stored JSON, with its content read back from the
+/// blob store if it was moved there.
+fn parse_resource(blobs: &BlobStore, data: &str) -> Result<Resource, DomainError> {
+    let corrupt =
+        |e: serde_json::Error| DomainError::StorageError(format!("Corrupt cache entry: {}", e));
+    let mut resource: Resource = serde_json::from_str(data).map_err(corrupt)?;
+    if resource.content.is_empty() {
+        let body: StoredBody = serde_json::from_str(data).map_err(corrupt)?;
+        if let Some(hash) = body.content_blob {
+            resource.content = String::from_utf8(blobs.get(&hash)?)
+                .map_err(|e| DomainError::StorageError(format!("Corrupt blob {}: {}", hash, e)))?;
+        }
+    }
+    Ok(resource)
+}
+
+/// Escapes `%`, `_` and the escape character itself for a LIKE pattern.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn storage_error(error: rusqlite::Error) -> DomainError {
+    DomainError::StorageError(error.to_string())
+}
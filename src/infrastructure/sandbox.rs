@@ -0,0 +1,86 @@
+use reqwest::Url;
+use std::sync::OnceLock;
+
+use crate::domain::DomainError;
+
+/// Hosts the built-in providers talk to, allowed in every sandbox. `*.`
+/// allows any subdomain.
+pub const DEFAULT_ALLOWED_HOSTS: [&str; 12] = [
+    "api.notion.com",
+    // Where Notion keeps uploaded files.
+    "file.notion.so",
+    "prod-files-secure.s3.us-west-2.amazonaws.com",
+    "api.linear.app",
+    "uploads.linear.app",
+    "api.trello.com",
+    "*.zendesk.com",
+    "dev.azure.com",
+    "almsearch.dev.azure.com",
+    "*.visualstudio.com",
+    "api.todoist.com",
+    "coda.io",
+];
+
+static ACTIVE: OnceLock<Sandbox> = OnceLock::new();
+
+/// The hardened mode for serving configs from less trusted users: nothing
+/// in the config may run code or read local files, so hooks, upstream
+/// servers run as commands and vaults are left out, and requests only go
+/// to allowed hosts, so a provider can't be pointed elsewhere to collect
+/// the credentials it's given.
+///
+/// It's switched on from the command line, never by the config it guards.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    allowed_hosts: Vec<String>,
+}
+
+impl Sandbox {
+    /// A sandbox allowing `allowed_hosts` besides the built-in providers'.
+    pub fn new(allowed_hosts: &[String]) -> Self {
+        let allowed_hosts = DEFAULT_ALLOWED_HOSTS
+            .iter()
+            .map(|host| host.to_string())
+            .chain(allowed_hosts.iter().map(|host| host.trim().to_lowercase()))
+            .filter(|host| !host.is_empty())
+            .collect();
+        Self { allowed_hosts }
+    }
+
+    /// Applies the sandbox to the rest of the process. Only the first call
+    /// counts.
+    pub fn enable(self) {
+        let _ = ACTIVE.set(self);
+    }
+
+    pub fn allows_host(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.ends_with('.')),
+                None => host == *allowed,
+            })
+    }
+}
+
+/// The sandbox the process runs in, if any.
+pub fn active() -> Option<&'static Sandbox> {
+    ACTIVE.get()
+}
+
+/// Refuses requests to `url` when a sandbox doesn't allow its host.
+pub fn check_url(url: &Url) -> Result<(), DomainError> {
+    let Some(sandbox) = active() else {
+        return Ok(());
+    };
+    match url.host_str() {
+        Some(host) if sandbox.allows_host(host) => Ok(()),
+        host => Err(DomainError::ProviderError(format!(
+            "{} isn't an allowed host in the sandbox; allow it with --allow-host",
+            host.unwrap_or(url.as_str())
+        ))),
+    }
+}
@@ -0,0 +1,147 @@
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::path::Path;
+
+use crate::domain::{DomainError, Resource};
+use crate::ports::ResourceHook;
+
+/// Operations one script call may run before it is aborted, so a runaway
+/// loop can't hang a request.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A hook written in [Rhai](https://rhai.rs). The script defines any of:
+///
+/// ```rhai
+/// // Keep a resource only if this returns true.
+/// fn filter(resource) { resource.metadata.state != "Canceled" }
+///
+/// // Return the resource to use instead, e.g. with extra metadata.
+/// fn transform(resource) { resource.metadata.team = "payments"; resource }
+///
+/// // Score search results; higher ranks first.
+/// fn rank(resource, query) { if resource.title.contains(query) { 10 } else { 0 } }
+/// ```
+///
+/// Resources are passed as maps shaped like their JSON output.
+pub struct RhaiHook {
+    name: String,
+    providers: Vec<String>,
+    engine: Engine,
+    ast: AST,
+    filter: bool,
+    transform: bool,
+    rank: bool,
+}
+
+impl RhaiHook {
+    /// Compiles the script at `path`, applying it to `providers` (all if
+    /// empty).
+    pub fn load(path: &Path, providers: Vec<String>) -> Result<Self, DomainError> {
+        let name = path.display().to_string();
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        // stdout carries command output and the MCP protocol, so script
+        // output goes to the log instead.
+        let source = name.clone();
+        engine.on_print(move |text| tracing::info!("{}: {}", source, text));
+        let source = name.clone();
+        engine.on_debug(move |text, _, position| {
+            tracing::debug!("{} ({}): {}", source, position, text)
+        });
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| script_error(&name, e))?;
+        let defines = |function: &str, arity: usize| {
+            ast.iter_functions()
+                .any(|f| f.name == function && f.params.len() == arity)
+        };
+        let (filter, transform, rank) = (
+            defines("filter", 1),
+            defines("transform", 1),
+            defines("rank", 2),
+        );
+        if !(filter || transform || rank) {
+            return Err(DomainError::InvalidQuery(format!(
+                "Script {} defines none of filter(resource), transform(resource) or rank(resource, query)",
+                name
+            )));
+        }
+
+        Ok(Self {
+            name,
+            providers: providers.iter().map(|p| p.to_lowercase()).collect(),
+            engine,
+            ast,
+            filter,
+            transform,
+            rank,
+        })
+    }
+
+    fn call(&self, function: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, DomainError> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, function, args)
+            .map_err(|e| DomainError::InvalidQuery(e.to_string()))
+    }
+}
+
+impl ResourceHook for RhaiHook {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn applies_to(&self, provider: &str) -> bool {
+        self.providers.is_empty() || self.providers.iter().any(|p| p == provider)
+    }
+
+    fn transform(&self, resource: &Resource) -> Result<Option<Resource>, DomainError> {
+        if !(self.filter || self.transform) {
+            return Ok(Some(resource.clone()));
+        }
+        let value = to_dynamic(resource)?;
+
+        if self.filter {
+            let keep = self.call("filter", (value.clone(),))?;
+            let keep = keep.as_bool().map_err(|actual| {
+                DomainError::InvalidQuery(format!("filter returned {} instead of a bool", actual))
+            })?;
+            if !keep {
+                return Ok(None);
+            }
+        }
+
+        if !self.transform {
+            return Ok(Some(resource.clone()));
+        }
+        let value = self.call("transform", (value,))?;
+        rhai::serde::from_dynamic(&value).map(Some).map_err(|e| {
+            DomainError::InvalidQuery(format!("transform returned an invalid resource: {}", e))
+        })
+    }
+
+    fn rank(&self, resource: &Resource, query: &str) -> Result<Option<f64>, DomainError> {
+        if !self.rank {
+            return Ok(None);
+        }
+        let value = to_dynamic(resource)?;
+        let score = self.call("rank", (value, query.to_string()))?;
+
+        match (score.as_float(), score.as_int()) {
+            (Ok(score), _) => Ok(Some(score)),
+            (_, Ok(score)) => Ok(Some(score as f64)),
+            _ => Err(DomainError::InvalidQuery(format!(
+                "rank returned {} instead of a number",
+                score.type_name()
+            ))),
+        }
+    }
+}
+
+fn to_dynamic(resource: &Resource) -> Result<Dynamic, DomainError> {
+    rhai::serde::to_dynamic(resource).map_err(|e| DomainError::InvalidQuery(e.to_string()))
+}
+
+fn script_error(name: &str, error: Box<rhai::EvalAltResult>) -> DomainError {
+    DomainError::InvalidQuery(format!("Script {}: {}", name, error))
+}
@@ -0,0 +1,454 @@
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    application::{
+        search::SearchOptions,
+        sync::SyncLimits,
+        testing::{Check, Outcome},
+        watch::ResourceWatcher,
+        ResourceService,
+    },
+    domain::{Query, QueryResult, QuerySource, Resource, ResourceSource, SearchTier},
+    infrastructure::{
+        adapters::fixture::FixtureAdapter,
+        cli::{
+            dates::Dates,
+            output::{self, OutputFormat},
+        },
+        mcp::{prompts::Prompts, protocol::parse_message, server::McpServer},
+        reload::ProviderSettings,
+        repository::SqliteRepository,
+    },
+    ports::{ResourceProvider, ResourceRepository},
+};
+
+/// The provider resources are served from, under whose name they're
+/// re-homed.
+const PROVIDER: &str = "fixture";
+
+/// Runs the configured providers' setup, then takes `resources` (built-in
+/// samples if `None`) through everything between a provider and a client,
+/// without calling any live API: the service, a scratch cache, sync and its
+/// full-text index, each output format, and an MCP session. The stages
+/// after one that fails are skipped; `config` stands apart from them.
+pub async fn run(
+    settings: &BTreeMap<String, ProviderSettings>,
+    resources: Option<Vec<Resource>>,
+    dates: Dates,
+) -> Vec<Check> {
+    let mut checks = vec![check_config(settings)];
+
+    let dir = std::env::temp_dir().join(format!("mcp-rs-selftest-{}", std::process::id()));
+    let stages = match std::fs::create_dir_all(&dir) {
+        Ok(()) => {
+            let resources = rehome(resources.unwrap_or_else(samples));
+            run_stages(&dir, resources, dates).await
+        }
+        Err(e) => {
+            let mut matrix = Matrix(Vec::new());
+            matrix.record(
+                "provider",
+                Err(format!("creating {}: {}", dir.display(), e)),
+            );
+            matrix.finish()
+        }
+    };
+    checks.extend(stages);
+    let _ = std::fs::remove_dir_all(&dir);
+    checks
+}
+
+/// Builds every configured provider, as the first request to each would,
+/// which checks its settings without calling its API.
+fn check_config(settings: &BTreeMap<String, ProviderSettings>) -> Check {
+    if settings.is_empty() {
+        return check("config", Outcome::Skip, "no providers configured");
+    }
+    let problems: Vec<String> = settings
+        .iter()
+        .filter_map(|(name, settings)| {
+            settings
+                .build(name)
+                .err()
+                .map(|e| format!("{}: {}", settings.describe(name), e))
+        })
+        .collect();
+    if problems.is_empty() {
+        let names: Vec<&str> = settings.keys().map(String::as_str).collect();
+        check(
+            "config",
+            Outcome::Pass,
+            format!("set up {}", names.join(", ")),
+        )
+    } else {
+        check("config", Outcome::Fail, problems.join("; "))
+    }
+}
+
+/// The stages after `config`, in the order they run.
+const STAGES: [&str; 6] = ["provider", "cache", "sync", "index", "output", "mcp"];
+
+/// The checks made so far.
+struct Matrix(Vec<Check>);
+
+impl Matrix {
+    /// Records how `stage` went, returning whether it passed.
+    fn record(&mut self, stage: &'static str, result: Result<String, String>) -> bool {
+        let passed = result.is_ok();
+        self.0.push(match result {
+            Ok(detail) => check(stage, Outcome::Pass, detail),
+            Err(detail) => check(stage, Outcome::Fail, detail),
+        });
+        passed
+    }
+
+    /// The checks, with the stages not reached skipped.
+    fn finish(mut self) -> Vec<Check> {
+        let done = self.0.len();
+        self.0.extend(
+            STAGES[done..]
+                .iter()
+                .map(|stage| check(stage, Outcome::Skip, "an earlier stage failed")),
+        );
+        self.0
+    }
+}
+
+async fn run_stages(dir: &Path, resources: Vec<Resource>, dates: Dates) -> Vec<Check> {
+    let mut matrix = Matrix(Vec::new());
+    if resources.is_empty() {
+        matrix.record("provider", Err("no resources to serve".to_string()));
+        return matrix.finish();
+    }
+
+    let repository = match SqliteRepository::open(&dir.join("cache.db")) {
+        Ok(repository) => Arc::new(repository),
+        Err(e) => {
+            matrix.record("provider", Err(format!("opening the cache: {}", e)));
+            return matrix.finish();
+        }
+    };
+    let mut service = ResourceService::new();
+    service.set_cache(repository.clone(), Duration::from_secs(3600), true);
+    let provider: Arc<dyn ResourceProvider> = Arc::new(FixtureAdapter::new(resources.clone()));
+    if let Err(e) = service.add_provider(provider) {
+        matrix.record("provider", Err(e.to_string()));
+        return matrix.finish();
+    }
+    let service = Arc::new(service);
+    let query = Query {
+        source: QuerySource::Named(PROVIDER.to_string()),
+        filters: HashMap::new(),
+        limit: None,
+    };
+    let term = search_term(&resources);
+
+    let listed = match check_provider(&service, &query, resources.len()).await {
+        Ok(result) => {
+            let detail = format!("listed {} resources", result.resources.len());
+            matrix.record("provider", Ok(detail));
+            result
+        }
+        Err(detail) => {
+            matrix.record("provider", Err(detail));
+            return matrix.finish();
+        }
+    };
+    let _ = matrix.record(
+        "cache",
+        check_cache(&service, repository.as_ref(), &resources).await,
+    ) && matrix.record("sync", check_sync(&service, &query, resources.len()).await)
+        && matrix.record("index", check_index(&service, &repository, &term).await)
+        && matrix.record("output", check_output(&listed, dates))
+        && matrix.record(
+            "mcp",
+            check_mcp(service.clone(), dir, &resources[0], &term).await,
+        );
+    matrix.finish()
+}
+
+async fn check_provider(
+    service: &ResourceService,
+    query: &Query,
+    expected: usize,
+) -> Result<QueryResult, String> {
+    let result = service
+        .fetch_resources(query)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(failure) = result.errors.first() {
+        return Err(failure.error.clone());
+    }
+    if result.resources.len() != expected {
+        return Err(format!(
+            "listed {} of {} resources",
+            result.resources.len(),
+            expected
+        ));
+    }
+    Ok(result)
+}
+
+/// Listed resources were stored, and are served from the cache.
+async fn check_cache(
+    service: &ResourceService,
+    repository: &dyn ResourceRepository,
+    resources: &[Resource],
+) -> Result<String, String> {
+    let mut stored = 0;
+    for resource in resources {
+        match repository.find_entry(&resource.id).await {
+            Ok(Some(_)) => stored += 1,
+            Ok(None) => return Err(format!("{} wasn't stored", resource.id)),
+            Err(e) => return Err(format!("reading {}: {}", resource.id, e)),
+        }
+    }
+    let first = &resources[0];
+    let cached = service
+        .fetch_resource_by_id(&first.id)
+        .await
+        .map_err(|e| format!("fetching {}: {}", first.id, e))?;
+    if cached.content.trim() != first.content.trim() {
+        return Err(format!("{} came back changed", first.id));
+    }
+    Ok(format!("stored {} resources and read them back", stored))
+}
+
+async fn check_sync(
+    service: &ResourceService,
+    query: &Query,
+    expected: usize,
+) -> Result<String, String> {
+    let reports = service
+        .sync(query, |_| SyncLimits::default())
+        .await
+        .map_err(|e| e.to_string())?;
+    let report = reports
+        .iter()
+        .find(|report| report.provider.eq_ignore_ascii_case(PROVIDER))
+        .ok_or("the provider wasn't synced")?;
+    if let Some(error) = &report.error {
+        return Err(error.clone());
+    }
+    if report.fetched != expected {
+        return Err(format!(
+            "synced {} of {} resources",
+            report.fetched, expected
+        ));
+    }
+    Ok(format!("synced {} resources", report.fetched))
+}
+
+/// The index holds the synced resources, and searches are answered from it.
+async fn check_index(
+    service: &ResourceService,
+    repository: &SqliteRepository,
+    term: &str,
+) -> Result<String, String> {
+    let status = repository.index_status().map_err(|e| e.to_string())?;
+    if !status.is_current() {
+        return Err(format!(
+            "{} missing and {} orphaned of {} resources",
+            status.missing, status.orphaned, status.resources
+        ));
+    }
+    let options = SearchOptions {
+        include_archived: false,
+        max_staleness: Some(Duration::from_secs(3600)),
+    };
+    let sources = vec![QuerySource::Named(PROVIDER.to_string())];
+    let result = service
+        .search(term, Some(sources), &options)
+        .await
+        .map_err(|e| e.to_string())?;
+    let tier = result.origins.get(PROVIDER).map(|origin| origin.tier);
+    if tier != Some(SearchTier::LocalIndex) {
+        return Err(format!(
+            "searching {:?} wasn't answered from the index ({})",
+            term,
+            tier.map_or("no results".to_string(), |tier| tier.to_string())
+        ));
+    }
+    if result.resources.is_empty() {
+        return Err(format!("searching {:?} found nothing", term));
+    }
+    Ok(format!(
+        "indexed {} resources; {:?} found {}",
+        status.indexed,
+        term,
+        result.resources.len()
+    ))
+}
+
+/// Every output format renders every resource.
+fn check_output(result: &QueryResult, dates: Dates) -> Result<String, String> {
+    let formats = [OutputFormat::Text, OutputFormat::Json, OutputFormat::Table];
+    for format in formats {
+        let rendered = output::render_results(result, format, dates, 200, None);
+        let missing = result.resources.iter().find(|resource| {
+            let id = match format {
+                OutputFormat::Table => output::truncate(&resource.id, 40),
+                _ => resource.id.clone(),
+            };
+            !rendered.contains(id.trim_end_matches("..."))
+        });
+        if let Some(resource) = missing {
+            return Err(format!("{:?} output is missing {}", format, resource.id));
+        }
+        if format == OutputFormat::Json {
+            let parsed: Value = serde_json::from_str(&rendered)
+                .map_err(|e| format!("JSON output doesn't parse: {}", e))?;
+            serde_json::from_value::<Vec<Resource>>(parsed["resources"].clone())
+                .map_err(|e| format!("JSON output doesn't read back: {}", e))?;
+        }
+    }
+    Ok("text, json and table".to_string())
+}
+
+/// A client can connect, list tools, search and fetch.
+async fn check_mcp(
+    service: Arc<ResourceService>,
+    dir: &Path,
+    resource: &Resource,
+    term: &str,
+) -> Result<String, String> {
+    let watcher = Arc::new(ResourceWatcher::new(service.clone()));
+    let server = McpServer::new(service, Prompts::load(&dir.join("prompts")), watcher);
+    let session = server.session(None, None);
+    let request = |id: u64, method: &str, params: Value| json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    let messages = [
+        request(
+            1,
+            "initialize",
+            json!({
+                "protocolVersion": crate::infrastructure::mcp::protocol::PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "mcp-rs selftest", "version": env!("CARGO_PKG_VERSION") },
+            }),
+        ),
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        request(2, "tools/list", json!({})),
+        request(
+            3,
+            "tools/call",
+            json!({ "name": "search_resources", "arguments": { "query": term, "sources": [PROVIDER] } }),
+        ),
+        request(
+            4,
+            "tools/call",
+            json!({ "name": "fetch_resource", "arguments": { "id": resource.id } }),
+        ),
+    ];
+
+    let mut replies = Vec::new();
+    for message in messages {
+        let message = parse_message(&message.to_string()).map_err(|e| json!(e).to_string())?;
+        if let Some(reply) = server.handle_message(&session, message).await {
+            replies.push(reply);
+        }
+    }
+    let [initialized, tools, search, fetch] = replies.as_slice() else {
+        return Err(format!("expected 4 replies, got {}", replies.len()));
+    };
+    let result = |reply: &Value, what: &str| match reply.get("error") {
+        Some(error) => Err(format!("{}: {}", what, error["message"])),
+        None => Ok(reply["result"].clone()),
+    };
+
+    let initialized = result(initialized, "initialize")?;
+    let version = initialized["protocolVersion"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let tools = result(tools, "tools/list")?;
+    let count = tools["tools"].as_array().map_or(0, Vec::len);
+    if count == 0 {
+        return Err("tools/list offered no tools".to_string());
+    }
+    for (reply, tool) in [(search, "search_resources"), (fetch, "fetch_resource")] {
+        let called = result(reply, tool)?;
+        let text = called["content"][0]["text"].as_str().unwrap_or_default();
+        if called["isError"] == json!(true) {
+            return Err(format!("{} failed: {}", tool, text));
+        }
+        if !text.contains(&resource.id) {
+            return Err(format!("{} didn't return {}", tool, resource.id));
+        }
+    }
+    Ok(format!(
+        "protocol {}, {} tools, searched and fetched",
+        version, count
+    ))
+}
+
+fn check(stage: &'static str, outcome: Outcome, detail: impl Into<String>) -> Check {
+    Check {
+        contract: stage,
+        outcome,
+        detail: detail.into(),
+    }
+}
+
+/// `resources` as the fixture provider's own, whatever recorded them, so
+/// their IDs lead back to it.
+fn rehome(resources: Vec<Resource>) -> Vec<Resource> {
+    resources
+        .into_iter()
+        .map(|mut resource| {
+            if !resource.id.starts_with(&format!("{}_", PROVIDER)) {
+                resource.id = format!("{}_{}", PROVIDER, resource.id);
+            }
+            resource.source = ResourceSource::Custom(PROVIDER.to_string());
+            resource.provenance = None;
+            resource
+        })
+        .collect()
+}
+
+/// A word of the first resource's title to search for.
+fn search_term(resources: &[Resource]) -> String {
+    let title = &resources[0].title;
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .find(|word| word.chars().count() >= 3)
+        .unwrap_or(title)
+        .to_lowercase()
+}
+
+fn samples() -> Vec<Resource> {
+    let now = Utc::now();
+    [
+        (
+            "onboarding",
+            "Onboarding guide",
+            "# Onboarding\n\nSet up your laptop, then read the handbook.",
+        ),
+        (
+            "roadmap",
+            "Q3 roadmap",
+            "# Q3 roadmap\n\n- Checkout redesign\n- Faster search",
+        ),
+        (
+            "checkout-bug",
+            "Checkout fails on retry",
+            "Paying twice after a timeout shows an error page.",
+        ),
+    ]
+    .into_iter()
+    .map(|(id, title, content)| Resource {
+        id: format!("{}_{}", PROVIDER, id),
+        source: ResourceSource::Custom(PROVIDER.to_string()),
+        title: title.to_string(),
+        content: content.to_string(),
+        metadata: HashMap::new(),
+        created_at: now,
+        updated_at: now,
+        provenance: None,
+    })
+    .collect()
+}
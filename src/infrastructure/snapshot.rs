@@ -0,0 +1,79 @@
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Url;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::domain::{DomainError, Snapshot};
+use crate::infrastructure::http::HttpClient;
+
+/// Path of a running server's snapshot; see `mcp::snapshot`.
+const SNAPSHOT_PATH: &str = "/snapshot";
+
+/// Endpoints of the MCP transports, which a server URL may be given as.
+const TRANSPORT_PATHS: [&str; 3] = ["/mcp", "/sse", "/ws"];
+
+/// Reads the snapshot `from` names: an `http(s)://` URL of a running
+/// server, which is sent `token` as its bearer token, or a snapshot file.
+pub async fn load(from: &str, token: Option<&str>) -> Result<Snapshot, DomainError> {
+    if from.starts_with("http://") || from.starts_with("https://") {
+        return download(from, token).await;
+    }
+    let file = File::open(from)
+        .map_err(|e| DomainError::StorageError(format!("Failed to open {}: {}", from, e)))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| DomainError::InvalidQuery(format!("{} is not a snapshot: {}", from, e)))
+}
+
+/// Writes `snapshot` to the file at `path`.
+pub fn write(path: &Path, snapshot: &Snapshot) -> Result<(), DomainError> {
+    let error = |e: std::io::Error| {
+        DomainError::StorageError(format!("Failed to write {}: {}", path.display(), e))
+    };
+    let mut writer = BufWriter::new(File::create(path).map_err(error)?);
+    serde_json::to_writer(&mut writer, snapshot).map_err(|e| error(e.into()))?;
+    writer.flush().map_err(error)
+}
+
+/// Downloads the snapshot of the server at `url`, its root or one of its
+/// transports' endpoints.
+async fn download(url: &str, token: Option<&str>) -> Result<Snapshot, DomainError> {
+    let mut url = Url::parse(url)
+        .map_err(|e| DomainError::InvalidQuery(format!("Invalid server URL {}: {}", url, e)))?;
+    let path = url.path().trim_end_matches('/');
+    let path = TRANSPORT_PATHS
+        .iter()
+        .find_map(|transport| path.strip_suffix(transport))
+        .unwrap_or(path);
+    if !path.ends_with(SNAPSHOT_PATH) {
+        url.set_path(&format!("{}{}", path, SNAPSHOT_PATH));
+    }
+
+    let mut headers = HeaderMap::new();
+    let mut secrets = Vec::new();
+    if let Some(token) = token {
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|e| DomainError::InvalidQuery(e.to_string()))?,
+        );
+        secrets.push(token.to_string());
+    }
+    let http = HttpClient::new("bootstrap", headers, secrets)?;
+    let response = http.send(http.get(url.as_str())).await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(DomainError::ProviderError(format!(
+            "{} answered {}: {}",
+            url,
+            status,
+            error_text.trim()
+        )));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| DomainError::ProviderError(format!("{} sent no snapshot: {}", url, e)))
+}
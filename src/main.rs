@@ -4,76 +4,252 @@ mod infrastructure;
 mod ports;
 
 use anyhow::Result;
-use clap::Parser;
+use chrono::Utc;
+use clap::{CommandFactory, FromArgMatches};
 use dotenv::dotenv;
-use std::{env, sync::Arc};
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+#[cfg(feature = "scripting")]
+use crate::infrastructure::scripting::RhaiHook;
 use crate::{
-    application::ResourceService,
-    domain::{Query, QuerySource},
+    application::{
+        bench::{self, BenchOperation, BenchOptions, BenchReport},
+        chunks::{self, DEFAULT_CHUNKS},
+        condition::Condition,
+        grep::{self, GrepOptions},
+        links::LinkEnricher,
+        search::SearchOptions,
+        sync::SyncLimits,
+        testing::{self, ConformanceOptions, Outcome},
+        watch::ResourceWatcher,
+        ResourceService,
+    },
+    domain::{DomainError, Query, QueryResult, QuerySource, Resource, SyncEstimate, UsageEvent},
     infrastructure::{
-        adapters::{linear::LinearAdapter, notion::NotionAdapter},
-        cli::{parse_filters, parse_sources, Cli, Commands, ConfigAction},
+        adapters::fixture::FixtureAdapter,
+        cli::{
+            daemon_serve_args,
+            dates::Dates,
+            edit_file, format_bytes,
+            output::{self, OutputFormat},
+            parse_filters, parse_sources, picker, read_queries, write_private_temp, AliasAction,
+            CacheAction, Cli, Commands, ConfigAction, DaemonAction, IndexAction, PipelineAction,
+            ProjectAction, Transport,
+        },
+        config::{
+            self,
+            credentials::{self, CredentialResolver, CredentialSource},
+            secrets::{self, EncryptionTarget, SecretCipher},
+            Config,
+        },
+        daemon::{self, DaemonSpec},
+        features,
+        http::{self, links::WebLinkResolver},
+        mcp::{
+            self,
+            logging::LogForwarder,
+            policy::{ClientPolicy, Policy},
+            prompts::Prompts,
+            server::McpServer,
+        },
+        pipeline::{self, Pipeline},
+        reload::{self, ConfigReloader, ProviderSettings},
+        repository::SqliteRepository,
+        sandbox::Sandbox,
+        selftest, snapshot,
     },
+    ports::{with_request_budget, CallBudget, ResourceProvider, ResourceRepository},
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
 
-    let cli = Cli::parse();
+    let matches = Cli::command()
+        .long_version(features::LONG_VERSION.as_str())
+        .get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli.columns.is_some() && cli.output == OutputFormat::Text {
+        cli.output = OutputFormat::Table;
+    }
+    if let Err(e) = features::check(&cli.command) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 
     // Initialize tracing
-    let filter = if cli.verbose {
-        "mcp_rs=debug,info"
+    let mut filter = if cli.verbose {
+        "mcp_rs=debug,info".to_string()
     } else {
-        "mcp_rs=info,warn,error"
+        "mcp_rs=info,warn,error".to_string()
     };
 
+    if cli.trace_http_bodies {
+        filter.push_str(&format!(",{}=trace", http::TRACE_TARGET));
+    } else if cli.trace_http {
+        filter.push_str(&format!(",{}=debug", http::TRACE_TARGET));
+    }
+
+    // MCP clients that ask for logs get them too.
+    let logs = LogForwarder::new();
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(filter))
-        .with(tracing_subscriber::fmt::layer())
+        // Logs go to stderr: stdout carries command output, and in `serve`
+        // mode the MCP protocol itself.
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(logs.clone())
         .init();
 
+    if cli.sandbox {
+        Sandbox::new(&cli.allow_host).enable();
+        tracing::info!(
+            "Sandboxed: no hooks, commands, vaults, local databases or arbitrary APIs, and only allowed hosts"
+        );
+    }
+
+    // Load the config file, decrypting any encrypted secrets.
+    let config_path = cli.config.clone().unwrap_or_else(Config::default_path);
+    let config = match reload::load_config(config_path.clone()).await {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("Error loading config: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let cipher = Arc::new(SecretCipher::new(config.encryption.identity_file.clone()));
+
+    // Resolve credentials (flag > env > keyring > config).
+    let api_keys = parse_filters(cli.api_key.clone());
+    let credentials = CredentialResolver::new(api_keys.clone(), config.clone())
+        .resolve_all()
+        .await;
+
     // Initialize resource service
     let mut service = ResourceService::new();
+    if cli.read_only || config.read_only {
+        service.set_read_only();
+        tracing::info!("Read-only mode: changes to upstream data are refused");
+    }
 
-    // Configure providers based on environment variables
-    if let Ok(notion_key) = env::var("NOTION_API_KEY") {
-        match NotionAdapter::new(notion_key) {
-            Ok(adapter) => {
-                service.add_provider(Arc::new(adapter));
-                tracing::info!("Notion provider configured");
-            }
-            Err(e) => tracing::warn!("Failed to configure Notion provider: {}", e),
+    // Configure providers with resolved credentials
+    let provider_settings = ProviderSettings::from_config(&config, &credentials);
+    for (name, settings) in &provider_settings {
+        match settings
+            .lazy(name)
+            .and_then(|provider| service.add_provider(provider))
+        {
+            Ok(true) => tracing::info!("{} configured", settings.describe(name)),
+            Ok(false) => tracing::info!("{} skipped (out of scope)", settings.describe(name)),
+            Err(e) => tracing::warn!("Failed to configure {}: {}", settings.describe(name), e),
         }
     }
 
-    if let Ok(linear_key) = env::var("LINEAR_API_KEY") {
-        match LinearAdapter::new(linear_key) {
-            Ok(adapter) => {
-                service.add_provider(Arc::new(adapter));
-                tracing::info!("Linear provider configured");
+    // Load result hooks; the sandbox runs no scripts.
+    if cli.sandbox && !config.hooks.is_empty() {
+        tracing::warn!("Skipping hooks: the sandbox runs no scripts");
+    }
+    let hooks = if cli.sandbox {
+        &[]
+    } else {
+        config.hooks.as_slice()
+    };
+    #[cfg(feature = "scripting")]
+    for hook in hooks {
+        match RhaiHook::load(&hook.script(), hook.providers.clone()) {
+            Ok(script) => service.add_hook(Arc::new(script)),
+            Err(e) => tracing::warn!("Skipping hook: {}", e),
+        }
+    }
+    #[cfg(not(feature = "scripting"))]
+    if !hooks.is_empty() {
+        tracing::warn!("Skipping hooks: {}", features::missing("scripting"));
+    }
+
+    if config.links.resolve_titles && cli.sandbox {
+        tracing::warn!("Not resolving link titles: the sandbox only reaches allowed hosts");
+    } else if config.links.resolve_titles {
+        match WebLinkResolver::new() {
+            Ok(resolver) => service.set_link_enricher(LinkEnricher::new(
+                Arc::new(resolver),
+                config.links.max_per_resource,
+            )),
+            Err(e) => tracing::warn!("Not resolving link titles: {}", e),
+        }
+    }
+
+    // Open the local cache
+    service.set_max_staleness(config.search.max_staleness_secs.map(Duration::from_secs));
+
+    let cache_ttl = Duration::from_secs(config.cache.ttl_secs);
+    let repository = if config.cache.enabled {
+        match SqliteRepository::open(&config.cache.path()) {
+            Ok(repository) => Some(Arc::new(repository)),
+            Err(e) => {
+                tracing::warn!("Cache unavailable: {}", e);
+                None
             }
-            Err(e) => tracing::warn!("Failed to configure Linear provider: {}", e),
         }
+    } else {
+        None
+    };
+
+    if let Some(repository) = &repository {
+        service.set_cache(repository.clone(), cache_ttl, !cli.no_cache);
+        if let Some(command) = matches.subcommand_name() {
+            if let Err(e) = repository.record_usage(UsageEvent::Command, command).await {
+                tracing::debug!("Failed to record usage: {}", e);
+            }
+        }
+    }
+
+    service.set_source_groups(config.sources.clone());
+    service.set_sync_scopes(config.sync_scopes());
+    if let Some(classification) = config.classification() {
+        service.set_classification(classification.default, classification.rules);
+    }
+    service.set_guardrails(config.guardrails);
+    // A server budgets each request instead.
+    if !matches!(cli.command, Commands::Serve { .. }) {
+        service.budget_command_calls();
+    }
+
+    // Pins may name aliases, so scoring waits for the cache.
+    if let Some(scoring) = config.scoring.clone() {
+        service.set_scoring(scoring).await;
     }
 
+    let dates = cli.dates();
+
     // Handle commands
     match cli.command {
         Commands::Fetch {
             source,
             limit,
             filter,
+            records,
+            mine,
+            assigned_to,
+            current_cycle,
+            condition,
         } => {
-            let query_source = match source.to_lowercase().as_str() {
-                "notion" => QuerySource::Notion,
-                "linear" => QuerySource::Linear,
-                _ => QuerySource::All,
+            // The "my work" shortcuts only apply to Linear.
+            let linear_shortcut = mine || assigned_to.is_some() || current_cycle;
+            let query_source = match QuerySource::parse(&source) {
+                QuerySource::All if linear_shortcut => QuerySource::Linear,
+                source => source,
             };
 
-            let filters = parse_filters(filter);
+            let mut filters = parse_filters(filter);
+            if records {
+                filters.insert("records".to_string(), "true".to_string());
+            }
+            if let Some(assignee) = assigned_to.or(mine.then(|| "me".to_string())) {
+                filters.insert("assignee".to_string(), assignee);
+            }
+            if current_cycle {
+                filters.insert("cycle".to_string(), "current".to_string());
+            }
             let query = Query {
                 source: query_source,
                 filters,
@@ -81,21 +257,11 @@ async fn main() -> Result<()> {
             };
 
             match service.fetch_resources(&query).await {
-                Ok(resources) => {
-                    println!("Found {} resources:", resources.len());
-                    for resource in resources {
-                        println!("\n--- {} ---", resource.title);
-                        println!("ID: {}", resource.id);
-                        println!("Source: {:?}", resource.source);
-                        println!("Created: {}", resource.created_at);
-                        println!(
-                            "Content: {}",
-                            if resource.content.len() > 200 {
-                                format!("{}...", &resource.content[..200])
-                            } else {
-                                resource.content
-                            }
-                        );
+                Ok(mut result) => {
+                    keep_matching(&mut result, condition.as_ref());
+                    output::print_results(&result, cli.output, dates, 200, cli.columns.as_ref());
+                    if result.resources.is_empty() && result.is_partial() {
+                        std::process::exit(1);
                     }
                 }
                 Err(e) => {
@@ -105,66 +271,536 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Get { id } => match service.fetch_resource_by_id(&id).await {
-            Ok(resource) => {
-                println!("Resource: {}", resource.title);
-                println!("ID: {}", resource.id);
-                println!("Source: {:?}", resource.source);
-                println!("Created: {}", resource.created_at);
-                println!("Updated: {}", resource.updated_at);
-                println!("\nContent:\n{}", resource.content);
-
-                if !resource.metadata.is_empty() {
-                    println!("\nMetadata:");
-                    for (key, value) in resource.metadata {
-                        println!("  {}: {}", key, value);
-                    }
+        Commands::Get { id, as_of } => {
+            let resource = match as_of {
+                Some(as_of) => service.fetch_resource_as_of(&id, as_of).await,
+                None => service.fetch_resource_by_id(&id).await,
+            };
+            match resource {
+                Ok(resource) => output::print_resource(&resource, cli.output, dates),
+                Err(e) => {
+                    eprintln!("Error fetching resource: {}", e);
+                    std::process::exit(1);
                 }
             }
-            Err(e) => {
-                eprintln!("Error fetching resource: {}", e);
+        }
+
+        Commands::Edit { id } => {
+            if cli.read_only || config.read_only {
+                eprintln!("Error: resources can't be edited in read-only mode");
                 std::process::exit(1);
             }
-        },
+            let resource = match service.fetch_editable(&id).await {
+                Ok(resource) => resource,
+                Err(e) => {
+                    eprintln!("Error fetching resource: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let file_name: String = resource
+                .id
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect();
+            let path = match write_private_temp(&file_name, &resource.content) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Error editing {}: {}", resource.id, e);
+                    std::process::exit(1);
+                }
+            };
+            let edited = edit_file(&path).and_then(|_| std::fs::read_to_string(&path));
+            let edited = match edited {
+                Ok(edited) => edited,
+                Err(e) => {
+                    let _ = std::fs::remove_file(&path);
+                    eprintln!("Error editing {}: {}", resource.id, e);
+                    std::process::exit(1);
+                }
+            };
+            let edited = edited.trim_end();
+            if edited == resource.content.trim_end() {
+                let _ = std::fs::remove_file(&path);
+                println!("No changes to {}", resource.id);
+                return Ok(());
+            }
+
+            match service.update_content(&resource, edited.to_string()).await {
+                Ok(updated) => {
+                    let _ = std::fs::remove_file(&path);
+                    println!("Updated {}", updated.id);
+                }
+                Err(e) => {
+                    eprintln!("Error saving {}: {}", resource.id, e);
+                    eprintln!("Your edits are kept in {}", path.display());
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Grep {
+            id,
+            pattern,
+            context,
+            ignore_case,
+            fixed_strings,
+            max_count,
+        } => {
+            let options = GrepOptions {
+                context,
+                ignore_case,
+                fixed_strings,
+                max_matches: max_count,
+            };
+            let pattern = match grep::compile(&pattern, &options) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match service.fetch_resource_by_id(&id).await {
+                Ok(resource) => {
+                    let sections = grep::find_sections(&resource.content, &pattern, &options);
+                    output::print_sections(&resource, &sections, cli.output);
+                    if sections.is_empty() {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error fetching resource: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
 
         Commands::Search {
             query,
+            batch,
+            concurrency,
             source,
             limit,
+            include_archived,
+            max_staleness,
+            chunks,
+            condition,
+            as_of,
         } => {
-            let query_sources = parse_sources(source);
-
-            match service.search(&query, Some(query_sources)).await {
-                Ok(resources) => {
-                    let display_limit = limit.unwrap_or(resources.len());
-                    println!(
-                        "Found {} resources (showing first {}):",
-                        resources.len(),
-                        display_limit.min(resources.len())
+            let routed = source.is_empty();
+            let query_sources = if routed {
+                vec![QuerySource::All]
+            } else {
+                parse_sources(source)
+            };
+            let options = SearchOptions {
+                include_archived,
+                max_staleness,
+            };
+
+            if let Some(path) = batch {
+                let queries = match read_queries(&path) {
+                    Ok(queries) => queries,
+                    Err(e) => {
+                        eprintln!("Error reading queries from {}: {}", path.display(), e);
+                        std::process::exit(1);
+                    }
+                };
+                let mut results = service
+                    .search_batch(&queries, &query_sources, &options, concurrency)
+                    .await;
+                for result in results.iter_mut().flatten() {
+                    keep_matching(result, condition.as_ref());
+                }
+                output::print_batch_results(
+                    &queries,
+                    &results,
+                    cli.output,
+                    dates,
+                    limit,
+                    150,
+                    cli.columns.as_ref(),
+                );
+                let failed = results.iter().any(|result| match result {
+                    Ok(result) => result.resources.is_empty() && result.is_partial(),
+                    Err(_) => true,
+                });
+                if failed {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            let query = query.unwrap_or_default();
+
+            let result = match as_of {
+                Some(as_of) => {
+                    service
+                        .search_as_of(&query, Some(query_sources), &options, as_of)
+                        .await
+                }
+                None if routed => service.search_routed(&query, &options).await,
+                None => service.search(&query, Some(query_sources), &options).await,
+            };
+            match result.map(|mut result| {
+                keep_matching(&mut result, condition.as_ref());
+                result
+            }) {
+                Ok(result) if chunks => {
+                    let passages =
+                        chunks::rank(&result.resources, &query, limit.unwrap_or(DEFAULT_CHUNKS));
+                    output::print_chunks(&result, &passages, cli.output, dates);
+                    if passages.is_empty() && result.is_partial() {
+                        std::process::exit(1);
+                    }
+                }
+                Ok(result) => {
+                    output::print_search_results(
+                        &result,
+                        cli.output,
+                        dates,
+                        limit,
+                        150,
+                        cli.columns.as_ref(),
                     );
+                    if result.resources.is_empty() && result.is_partial() {
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error searching resources: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
 
-                    for resource in resources.into_iter().take(display_limit) {
-                        println!("\n--- {} ---", resource.title);
-                        println!("ID: {}", resource.id);
-                        println!("Source: {:?}", resource.source);
-                        println!(
-                            "Content: {}",
-                            if resource.content.len() > 150 {
-                                let truncated = resource
-                                    .content
-                                    .char_indices()
-                                    .nth(150)
-                                    .map(|(i, _)| &resource.content[..i])
-                                    .unwrap_or(&resource.content);
-                                format!("{}...", truncated)
-                            } else {
-                                resource.content
+        Commands::Pick {
+            query,
+            source,
+            content,
+        } => {
+            let source = QuerySource::parse(&source);
+            let result = match &query {
+                Some(query) => {
+                    service
+                        .search(query, Some(vec![source]), &SearchOptions::default())
+                        .await
+                }
+                None => {
+                    let query = Query {
+                        source,
+                        filters: Default::default(),
+                        limit: None,
+                    };
+                    service.fetch_resources(&query).await
+                }
+            };
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("Error listing resources: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if result.resources.is_empty() {
+                output::print_problems(&result);
+                eprintln!("No resources to pick from.");
+                std::process::exit(1);
+            }
+
+            let picked = picker::pick(&result.resources, dates);
+            output::print_problems(&result);
+            let resource = match picked {
+                Ok(Some(index)) => &result.resources[index],
+                // Like other fuzzy finders, exit with 130 when nothing was picked.
+                Ok(None) => std::process::exit(130),
+                Err(e) => {
+                    eprintln!("Error picking a resource: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if !content {
+                println!("{}", resource.id);
+                return Ok(());
+            }
+            match service.fetch_resource_by_id(&resource.id).await {
+                Ok(resource) => println!("{}", resource.content),
+                Err(e) => {
+                    eprintln!("Error fetching resource: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Bench {
+            source,
+            ops,
+            iterations,
+            concurrency,
+            query,
+            id,
+            limit,
+            filter,
+            fixtures,
+            record,
+        } => {
+            let provider = match &fixtures {
+                Some(path) => match FixtureAdapter::from_file(path) {
+                    Ok(adapter) => Arc::new(adapter) as Arc<dyn ResourceProvider>,
+                    Err(e) => {
+                        eprintln!("Error loading fixtures: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+                None => match service.get_provider(&source) {
+                    Some(provider) => provider,
+                    None => {
+                        eprintln!("Provider not configured: {}", source);
+                        std::process::exit(1);
+                    }
+                },
+            };
+
+            let operations = match ops
+                .iter()
+                .map(|op| BenchOperation::parse(op))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(operations) => operations,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let query_source = QuerySource::parse(&source);
+
+            let mut options = BenchOptions {
+                iterations,
+                concurrency,
+                query: Query {
+                    source: query_source,
+                    filters: parse_filters(filter),
+                    limit,
+                },
+                search_term: query,
+                resource_id: id,
+            };
+
+            println!(
+                "Benchmarking {} ({} calls per operation, concurrency {}):",
+                if fixtures.is_some() {
+                    "fixtures"
+                } else {
+                    provider.provider_name()
+                },
+                iterations,
+                concurrency
+            );
+            println!(
+                "\n{:<8} {:>6} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>8} {:>7}",
+                "op",
+                "calls",
+                "errors",
+                "min",
+                "p50",
+                "p90",
+                "p99",
+                "max",
+                "mean",
+                "req/s",
+                "items"
+            );
+
+            let mut recorded = Vec::new();
+            let mut failures = Vec::new();
+
+            for operation in operations {
+                if operation == BenchOperation::Get && options.resource_id.is_none() {
+                    options.resource_id = recorded.first().map(|r: &Resource| r.id.clone());
+                }
+
+                let report = match bench::run(provider.clone(), operation, &options).await {
+                    Ok(report) => report,
+                    Err(e) => {
+                        println!("{:<8} skipped: {}", operation.name(), e);
+                        continue;
+                    }
+                };
+
+                print_bench_report(&report);
+
+                if let Some(error) = report.errors.first() {
+                    failures.push(format!("{}: {}", operation.name(), error));
+                }
+                for resource in report.sample {
+                    if !recorded.iter().any(|r: &Resource| r.id == resource.id) {
+                        recorded.push(resource);
+                    }
+                }
+            }
+
+            if !failures.is_empty() {
+                println!("\nFirst error per operation:");
+                for failure in failures {
+                    println!("  {}", failure);
+                }
+            }
+
+            if let Some(path) = record {
+                match FixtureAdapter::save(&path, &recorded) {
+                    Ok(()) => println!(
+                        "\nRecorded {} resources to {}",
+                        recorded.len(),
+                        path.display()
+                    ),
+                    Err(e) => {
+                        eprintln!("Error recording fixtures: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+
+        Commands::Conformance {
+            source,
+            query,
+            page_size,
+            max_pages,
+            samples,
+            filter,
+        } => {
+            let Some(provider) = service.get_provider(&source) else {
+                eprintln!("Provider not configured: {}", source);
+                std::process::exit(1);
+            };
+
+            let options = ConformanceOptions {
+                query: Query {
+                    source: QuerySource::parse(&source),
+                    filters: parse_filters(filter),
+                    limit: None,
+                },
+                search_term: query,
+                page_size,
+                max_pages,
+                samples,
+            };
+
+            let report = testing::run(provider.as_ref(), &options).await;
+            println!("Conformance of {}:\n", report.provider);
+            for check in &report.checks {
+                println!(
+                    "{:<5} {:<15} {}",
+                    check.outcome.name(),
+                    check.contract,
+                    check.detail
+                );
+            }
+            if !report.passed() {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Selftest { fixtures } => {
+            let resources = match &fixtures {
+                Some(path) => match FixtureAdapter::load(path) {
+                    Ok(resources) => Some(resources),
+                    Err(e) => {
+                        eprintln!("Error loading fixtures: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
+
+            let checks = selftest::run(&provider_settings, resources, dates).await;
+            println!("Self-test:\n");
+            for check in &checks {
+                println!(
+                    "{:<5} {:<15} {}",
+                    check.outcome.name(),
+                    check.contract,
+                    check.detail
+                );
+            }
+            if checks.iter().any(|check| check.outcome == Outcome::Fail) {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Sync {
+            source,
+            filter,
+            limit,
+            workers,
+            max_calls,
+        } => {
+            let query = Query {
+                source: QuerySource::parse(&source),
+                filters: parse_filters(filter),
+                limit,
+            };
+
+            let limits = |provider: &str| {
+                let mut limits = SyncLimits::default();
+                if let Some(sync) = config.provider(provider).map(|p| &p.sync) {
+                    limits.workers = sync.workers.unwrap_or(limits.workers);
+                    limits.queue_size = sync.queue_size.unwrap_or(limits.queue_size);
+                }
+                limits.workers = workers.unwrap_or(limits.workers);
+                limits
+            };
+
+            let run = async {
+                let estimates = service.estimate_sync(&query).await;
+                warn_of_long_syncs(&estimates, max_calls);
+                service.sync(&query, limits).await
+            };
+            let result = match max_calls {
+                Some(max) => {
+                    let budget = Arc::new(CallBudget::new(max, "--max-calls"));
+                    with_request_budget(budget, run).await
+                }
+                None => run.await,
+            };
+
+            match result {
+                Ok(reports) if reports.is_empty() => {
+                    eprintln!("No providers configured");
+                    std::process::exit(1);
+                }
+                Ok(reports) => {
+                    let mut failed = false;
+                    for report in reports {
+                        match report.error {
+                            None if report.skipped > 0 => println!(
+                                "  {}: {} fetched, {} tombstoned, {} skipped (content fetch failed)",
+                                report.provider, report.fetched, report.tombstoned, report.skipped
+                            ),
+                            None => println!(
+                                "  {}: {} fetched, {} tombstoned",
+                                report.provider, report.fetched, report.tombstoned
+                            ),
+                            Some(error) if report.fetched > 0 => {
+                                failed = true;
+                                println!(
+                                    "  {}: ✗ Stopped after {} fetched ({})",
+                                    report.provider, report.fetched, error
+                                );
                             }
-                        );
+                            Some(error) => {
+                                failed = true;
+                                println!("  {}: ✗ Failed ({})", report.provider, error);
+                            }
+                        }
+                    }
+                    if failed {
+                        std::process::exit(1);
                     }
                 }
                 Err(e) => {
-                    eprintln!("Error searching resources: {}", e);
+                    eprintln!("Error syncing: {}", e);
                     std::process::exit(1);
                 }
             }
@@ -176,6 +812,14 @@ async fn main() -> Result<()> {
                 println!("No providers configured. Set API keys in environment variables:");
                 println!("  NOTION_API_KEY - for Notion integration");
                 println!("  LINEAR_API_KEY - for Linear integration");
+                println!("  TRELLO_API_KEY - for Trello integration (<key>:<token>)");
+                println!("  ZENDESK_API_KEY - for Zendesk integration (<email>:<token>)");
+                println!(
+                    "  AZUREDEVOPS_API_KEY - for Azure DevOps integration (personal access token)"
+                );
+                println!("  TODOIST_API_KEY - for Todoist integration");
+                println!("  CODA_API_KEY - for Coda integration");
+                println!("  IMAP_API_KEY - for IMAP mailboxes (<username>:<password>)");
             } else {
                 println!("Configured providers:");
                 for provider in providers {
@@ -184,35 +828,468 @@ async fn main() -> Result<()> {
             }
         }
 
+        Commands::Serve {
+            transport,
+            host,
+            port,
+            #[cfg(unix)]
+            socket,
+            allow_origin,
+            session_idle_timeout,
+            max_read_chars,
+            ping_interval,
+            ping_timeout,
+            scope,
+            policy,
+            allow_tool,
+            deny_tool,
+            allow_provider,
+            deny_provider,
+            max_classification,
+            allow_writes,
+            warm_up,
+        } => {
+            if allow_writes && (cli.read_only || config.read_only) {
+                eprintln!("Error: --allow-writes can't be used in read-only mode");
+                std::process::exit(1);
+            }
+            let mut policy = match policy.as_deref().map(Policy::load).transpose() {
+                Ok(policy) => policy.unwrap_or_default(),
+                Err(e) => {
+                    eprintln!("Error loading policy: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            policy.restrict(&ClientPolicy::new(
+                allow_tool,
+                deny_tool,
+                allow_provider,
+                deny_provider,
+                max_classification,
+            ));
+            if let Some(name) = &scope {
+                match config.scope(name) {
+                    Some(scope) => {
+                        service.set_scope(name, scope);
+                        tracing::info!("Serving scope {}", name);
+                    }
+                    None => {
+                        eprintln!("Error: no scope named {} in the config file", name);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let prompts = Prompts::load(&config.prompts.dir());
+            let service = Arc::new(service);
+            if warm_up {
+                tokio::spawn({
+                    let service = service.clone();
+                    async move { service.warm_up().await }
+                });
+            }
+            // Sessions stay open while providers come and go.
+            let reloader =
+                ConfigReloader::new(config_path, api_keys, service.clone(), provider_settings)
+                    .with_scope(scope);
+            tokio::spawn(reloader.run());
+            let watcher = Arc::new(ResourceWatcher::new(service.clone()));
+            let poll_interval = Duration::from_secs(config.subscriptions.poll_interval_secs.max(1));
+            tokio::spawn({
+                let watcher = watcher.clone();
+                async move { watcher.run(poll_interval).await }
+            });
+            let mut server = McpServer::new(service, prompts, watcher)
+                .with_policy(policy)
+                .with_logs(logs)
+                .with_session_idle_timeout(session_idle_timeout)
+                .with_max_read_chars((max_read_chars > 0).then_some(max_read_chars));
+            if let Some(interval) = ping_interval {
+                server = server.with_keepalive(interval, ping_timeout);
+            }
+            if allow_writes {
+                server = server.with_writes();
+                tracing::warn!("Write tools enabled: clients can change data upstream");
+            }
+            let server = Arc::new(server);
+            #[cfg(unix)]
+            if let Some(path) = socket {
+                mcp::socket::serve(server, &path).await?;
+                return Ok(());
+            }
+            match transport {
+                Transport::Stdio => {
+                    tracing::info!("Serving MCP over stdio");
+                    mcp::stdio::serve(server).await?;
+                }
+                Transport::Sse | Transport::Http | Transport::Ws => {
+                    let addr = SocketAddr::new(host, port);
+                    serve_network(server, transport, addr, allow_origin).await?;
+                }
+            }
+        }
+
+        Commands::Discover { source } => match service.discover(&source).await {
+            Ok(items) => output::print_discovery(&items, cli.output),
+            Err(e) => {
+                eprintln!("Error discovering {}: {}", source, e);
+                std::process::exit(1);
+            }
+        },
+
+        Commands::Project { action } => match action {
+            ProjectAction::List => {
+                if config.projects.is_empty() {
+                    println!("No projects configured (see [projects.<name>] in the config file)");
+                }
+                for (name, project) in &config.projects {
+                    match &project.description {
+                        Some(description) => println!("  {} - {}", name, description),
+                        None => println!("  {}", name),
+                    }
+                }
+            }
+            ProjectAction::Show {
+                name,
+                bundle,
+                max_chars,
+            } => {
+                let Some(definition) = config.projects.get(&name) else {
+                    eprintln!("Unknown project: {}", name);
+                    std::process::exit(1);
+                };
+
+                let max_chars = match service.bundle_limit(max_chars) {
+                    Ok(max_chars) => max_chars,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                match service.project(&name, definition).await {
+                    Ok(report) if bundle => print!("{}", report.bundle(max_chars)),
+                    Ok(report) => {
+                        output::print_project(&report, cli.output, dates, cli.columns.as_ref());
+                        if report.resources.is_empty() && !report.errors.is_empty() {
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error gathering project {}: {}", name, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        },
+
+        Commands::Pipeline { action } => match action {
+            PipelineAction::Run { file } => {
+                let pipeline = match Pipeline::load(&file) {
+                    Ok(pipeline) => pipeline,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let outcome = match pipeline.run(&service).await {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                if outcome.delivered {
+                    output::print_problems(&outcome.result);
+                } else {
+                    match (&outcome.summary, cli.output) {
+                        (_, OutputFormat::Json) => {
+                            println!("{}", pipeline.render(pipeline::Format::Json, &outcome))
+                        }
+                        (Some(summary), OutputFormat::Text) => {
+                            print!("{}", summary);
+                            output::print_problems(&outcome.result);
+                        }
+                        _ => output::print_results(
+                            &outcome.result,
+                            cli.output,
+                            dates,
+                            200,
+                            cli.columns.as_ref(),
+                        ),
+                    }
+                }
+                if outcome.result.resources.is_empty() && outcome.result.is_partial() {
+                    std::process::exit(1);
+                }
+            }
+        },
+
+        Commands::Alias { action } => match action {
+            AliasAction::Set { alias, id } => match service.set_alias(&alias, &id).await {
+                Ok(id) => println!("{} -> {}", alias, id),
+                Err(e) => {
+                    eprintln!("Error setting alias: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            AliasAction::Rm { alias } => match service.remove_alias(&alias).await {
+                Ok(true) => println!("Removed alias {}", alias),
+                Ok(false) => {
+                    eprintln!("No alias named {}", alias);
+                    std::process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Error removing alias: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            AliasAction::Ls => match service.list_aliases().await {
+                Ok(aliases) => {
+                    println!("{} aliases:", aliases.len());
+                    for alias in aliases {
+                        println!(
+                            "  {:<20} {}{}",
+                            alias.name,
+                            alias.id,
+                            if alias.automatic { "  (short ID)" } else { "" }
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error listing aliases: {}", e);
+                    std::process::exit(1);
+                }
+            },
+        },
+
+        Commands::Bootstrap { from, bearer_token } => {
+            let Some(repository) = repository else {
+                eprintln!("Cache is disabled (see [cache] in the config file)");
+                std::process::exit(1);
+            };
+            let snapshot = match snapshot::load(&from, bearer_token.as_deref()).await {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    eprintln!("Error reading snapshot: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let exported_at = snapshot.exported_at;
+            let report = match service.import_snapshot(snapshot).await {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("Error importing snapshot: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            println!(
+                "Imported {} resources ({} already as recent here) from a snapshot taken {}",
+                report.imported,
+                report.kept,
+                dates.timestamp(exported_at)
+            );
+            if !report.synced.is_empty() {
+                println!("Searched locally while fresh: {}", report.synced.join(", "));
+            }
+            match repository.index_status() {
+                Ok(status) => println!("Indexed {} cached resources", status.indexed),
+                Err(e) => eprintln!("Error reading index: {}", e),
+            }
+        }
+
+        Commands::Cache { action } => {
+            let Some(repository) = repository else {
+                eprintln!("Cache is disabled (see [cache] in the config file)");
+                std::process::exit(1);
+            };
+            let stale_before = Utc::now() - chrono::Duration::seconds(config.cache.ttl_secs as i64);
+
+            match action {
+                CacheAction::Stats => {
+                    if let Err(e) = print_cache_stats(&repository, config.cache.ttl_secs, dates) {
+                        eprintln!("Error reading cache: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+
+                CacheAction::Ls {
+                    source,
+                    stale,
+                    include_archived,
+                } => {
+                    match repository
+                        .list_entries(source.as_deref(), include_archived)
+                        .await
+                    {
+                        Ok(entries) => {
+                            let entries: Vec<_> = entries
+                                .into_iter()
+                                .filter(|entry| !stale || entry.cached_at < stale_before)
+                                .collect();
+
+                            println!("{} cached resources:", entries.len());
+                            for entry in entries {
+                                println!(
+                                    "  {}  {:<8} {:>8}{}  {}",
+                                    entry.resource.id,
+                                    entry.resource.source.provider_name(),
+                                    dates.age(entry.cached_at),
+                                    if entry.deleted_at.is_some() {
+                                        " (deleted)"
+                                    } else if entry.cached_at < stale_before {
+                                        " (stale)"
+                                    } else {
+                                        ""
+                                    },
+                                    entry.resource.title
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Error reading cache: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                CacheAction::Invalidate {
+                    id,
+                    source,
+                    stale,
+                    all,
+                } => {
+                    let result = if let Some(id) = id {
+                        repository.delete(&id).await.map(|_| 1)
+                    } else if let Some(source) = source {
+                        repository.delete_by_provider(&source).await
+                    } else if stale {
+                        repository.delete_stale(stale_before).await
+                    } else if all {
+                        repository.clear().await
+                    } else {
+                        Ok(0)
+                    };
+
+                    match result {
+                        Ok(removed) => println!("Removed {} cached resources", removed),
+                        Err(e) => {
+                            eprintln!("Error invalidating cache: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                CacheAction::Prune => match repository.prune() {
+                    Ok(report) => println!(
+                        "Forgot {} outdated attachments; removed {} unused blobs ({})",
+                        report.attachments,
+                        report.blobs,
+                        format_bytes(report.bytes)
+                    ),
+                    Err(e) => {
+                        eprintln!("Error pruning cache: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+
+                CacheAction::Export { path } => {
+                    let exported = service
+                        .export_snapshot(|_| true)
+                        .await
+                        .and_then(|exported| snapshot::write(&path, &exported).map(|_| exported));
+                    match exported {
+                        Ok(exported) => println!(
+                            "Exported {} resources to {}",
+                            exported.resources.len(),
+                            path.display()
+                        ),
+                        Err(e) => {
+                            eprintln!("Error exporting cache: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::Index { action } => {
+            let Some(repository) = repository else {
+                eprintln!("Cache is disabled (see [cache] in the config file)");
+                std::process::exit(1);
+            };
+            match action {
+                IndexAction::Status => match repository.index_status() {
+                    Ok(status) => {
+                        println!("Cached resources: {}", status.resources);
+                        println!("Indexed:          {}", status.indexed);
+                        println!("Missing:          {}", status.missing);
+                        println!("Orphaned:         {}", status.orphaned);
+                        if !status.is_current() {
+                            println!("Run `mcp-rs index rebuild` to bring the index up to date");
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error reading index: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+                IndexAction::Rebuild => match repository.rebuild_index() {
+                    Ok(indexed) => println!("Indexed {} cached resources", indexed),
+                    Err(e) => {
+                        eprintln!("Error rebuilding index: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+            }
+        }
+
+        Commands::Stats { usage } => {
+            let Some(repository) = repository else {
+                eprintln!("Cache is disabled (see [cache] in the config file)");
+                std::process::exit(1);
+            };
+            let printed = if usage {
+                print_usage(&repository, dates)
+            } else {
+                print_cache_stats(&repository, config.cache.ttl_secs, dates)
+            };
+            if let Err(e) = printed {
+                eprintln!("Error reading cache: {}", e);
+                std::process::exit(1);
+            }
+        }
+
         Commands::Config { action } => {
             match action {
                 ConfigAction::Set { provider, key } => {
-                    println!("To set API keys, use environment variables:");
-                    match provider.to_lowercase().as_str() {
-                        "notion" => println!("export NOTION_API_KEY=\"{}\"", key),
-                        "linear" => println!("export LINEAR_API_KEY=\"{}\"", key),
-                        _ => println!("Unknown provider: {}", provider),
+                    let name = provider.clone();
+                    match tokio::task::spawn_blocking(move || {
+                        credentials::store_in_keyring(&name, &key)
+                    })
+                    .await?
+                    {
+                        Ok(()) => println!("Stored {} API key in the OS keyring", provider),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            eprintln!(
+                                "Set {} in the environment or config file instead",
+                                CredentialResolver::env_var(&provider)
+                            );
+                            std::process::exit(1);
+                        }
                     }
                 }
 
                 ConfigAction::List => {
-                    println!("Configuration:");
-                    println!(
-                        "  NOTION_API_KEY: {}",
-                        if env::var("NOTION_API_KEY").is_ok() {
-                            "✓ Set"
-                        } else {
-                            "✗ Not set"
-                        }
-                    );
-                    println!(
-                        "  LINEAR_API_KEY: {}",
-                        if env::var("LINEAR_API_KEY").is_ok() {
-                            "✓ Set"
-                        } else {
-                            "✗ Not set"
-                        }
-                    );
+                    println!("Configuration ({}):", config_path.display());
+                    for provider in credentials::PROVIDERS {
+                        let status = match credentials.get(provider) {
+                            Some(credential) => format!("✓ Set ({})", credential.source),
+                            None => "✗ Not set".to_string(),
+                        };
+                        println!("  {}: {}", CredentialResolver::env_var(provider), status);
+                    }
                 }
 
                 ConfigAction::Test { provider } => {
@@ -220,26 +1297,341 @@ async fn main() -> Result<()> {
                     let providers_to_test = if let Some(p) = provider {
                         vec![p]
                     } else {
-                        vec!["notion".to_string(), "linear".to_string()]
+                        credentials::PROVIDERS.map(str::to_string).to_vec()
                     };
 
                     for provider_name in providers_to_test {
                         // Simple test by trying to search for a basic query
-                        let query_source = match provider_name.as_str() {
-                            "notion" => QuerySource::Notion,
-                            "linear" => QuerySource::Linear,
-                            _ => continue,
-                        };
+                        let query_source = QuerySource::parse(&provider_name);
 
-                        match service.search("test", Some(vec![query_source])).await {
-                            Ok(_) => println!("  {}: ✓ Connected", provider_name),
+                        // Always live, so the provider is actually reached.
+                        let options = SearchOptions {
+                            include_archived: true,
+                            max_staleness: Some(Duration::ZERO),
+                        };
+                        match service
+                            .search("test", Some(vec![query_source]), &options)
+                            .await
+                        {
+                            Ok(result) => match result.errors.first() {
+                                None => println!("  {}: ✓ Connected", provider_name),
+                                Some(failure) => {
+                                    println!("  {}: ✗ Failed ({})", provider_name, failure.error)
+                                }
+                            },
                             Err(e) => println!("  {}: ✗ Failed ({})", provider_name, e),
                         }
                     }
                 }
+
+                ConfigAction::Encrypt {
+                    recipient,
+                    passphrase,
+                    gpg,
+                } => {
+                    let target = match (passphrase, gpg) {
+                        (true, _) => EncryptionTarget::Passphrase,
+                        (false, Some(key_id)) => EncryptionTarget::Gpg(key_id),
+                        (false, None) => EncryptionTarget::Recipients(recipient),
+                    };
+
+                    let path = config_path.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        config::encrypt_file(&path, &cipher, &target)
+                    })
+                    .await?;
+
+                    match result {
+                        Ok(providers) if providers.is_empty() => {
+                            println!("No plaintext API keys in {}", config_path.display())
+                        }
+                        Ok(providers) => println!(
+                            "Encrypted API keys for {} in {}",
+                            providers.join(", "),
+                            config_path.display()
+                        ),
+                        Err(e) => {
+                            eprintln!("Error encrypting config: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                ConfigAction::Passphrase => {
+                    let passphrase = rpassword::prompt_password("Config passphrase: ")?;
+                    let confirmation = rpassword::prompt_password("Confirm passphrase: ")?;
+                    if passphrase != confirmation {
+                        eprintln!("Passphrases do not match");
+                        std::process::exit(1);
+                    }
+
+                    match tokio::task::spawn_blocking(move || {
+                        secrets::store_passphrase(&passphrase)
+                    })
+                    .await?
+                    {
+                        Ok(()) => println!("Passphrase stored in the OS keyring"),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::Daemon { action } => match action {
+            DaemonAction::Install { print, serve_args } => {
+                let args = match daemon_serve_args(serve_args) {
+                    Ok(args) => args,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let spec = DaemonSpec {
+                    executable: std::env::current_exe()?,
+                    config: std::path::absolute(&config_path)?,
+                    args,
+                };
+                if print {
+                    if cfg!(target_os = "linux") {
+                        print!("{}", spec.systemd_unit());
+                    } else {
+                        println!("{}", spec.command_line().join(" "));
+                    }
+                    return Ok(());
+                }
+
+                match tokio::task::spawn_blocking(move || daemon::install(&spec)).await? {
+                    Ok(installed) => println!("Installed and started {}", installed),
+                    Err(e) => {
+                        eprintln!("Error installing daemon: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                // The daemon has its own environment, without this shell's
+                // variables or .env file.
+                for (provider, credential) in &credentials {
+                    if matches!(credential.source, CredentialSource::Environment) {
+                        eprintln!(
+                            "Warning: the daemon won't see the {} API key in this environment; store it with `mcp-rs config set {}`",
+                            provider, provider
+                        );
+                    }
+                }
+                if cfg!(target_os = "linux") {
+                    println!("It runs while you're logged in; `loginctl enable-linger` starts it at boot");
+                }
             }
+            DaemonAction::Uninstall => {
+                match tokio::task::spawn_blocking(daemon::uninstall).await? {
+                    Ok(true) => println!("Stopped and removed the daemon"),
+                    Ok(false) => println!("No daemon installed"),
+                    Err(e) => {
+                        eprintln!("Error removing daemon: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Serves MCP over `transport`, one of the network transports, at `addr`.
+#[cfg(feature = "http-server")]
+async fn serve_network(
+    server: Arc<McpServer>,
+    transport: Transport,
+    addr: SocketAddr,
+    allow_origin: Vec<String>,
+) -> Result<()> {
+    let snapshots = mcp::snapshot::router(server.clone());
+    let router = match transport {
+        Transport::Http => mcp::streamable::router(server.clone()).merge(mcp::sse::router(server)),
+        Transport::Ws => mcp::websocket::router(server),
+        _ => mcp::sse::router(server),
+    }
+    .merge(snapshots);
+    let origins = mcp::http::OriginPolicy {
+        allowed: allow_origin,
+    };
+    mcp::http::serve(router, addr, origins).await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "http-server"))]
+async fn serve_network(
+    _: Arc<McpServer>,
+    _: Transport,
+    _: SocketAddr,
+    _: Vec<String>,
+) -> Result<()> {
+    anyhow::bail!(features::missing("http-server"))
+}
+
+/// Syncs expected to take at least this long at providers' rate limits are
+/// warned of before they start.
+const LONG_SYNC: Duration = Duration::from_secs(60);
+
+/// Warns of the syncs about to take a long time, or more calls than
+/// `--max-calls` allows, with how many calls they take and how long.
+fn warn_of_long_syncs(estimates: &[(String, SyncEstimate)], max_calls: Option<usize>) {
+    for (provider, estimate) in estimates {
+        let over_budget = max_calls.filter(|max| estimate.calls() > *max);
+        if estimate.duration() < LONG_SYNC && over_budget.is_none() {
+            continue;
+        }
+        eprintln!(
+            "Warning: syncing {} takes about {} API calls ({} resources listed in {} calls, then about {:.1} calls each for content), roughly {} at {} requests/s",
+            provider,
+            estimate.calls(),
+            estimate.resources,
+            estimate.listing_calls,
+            estimate.calls_per_resource(),
+            humantime::format_duration(Duration::from_secs(estimate.duration().as_secs())),
+            estimate.requests_per_second
+        );
+        if let Some(max) = over_budget {
+            eprintln!(
+                "  --max-calls {} stops it about {}% of the way through",
+                max,
+                max * 100 / estimate.calls()
+            );
+        }
+    }
+}
+
+/// Leaves out the results that don't match `--where`, if given.
+fn keep_matching(result: &mut QueryResult, condition: Option<&Condition>) {
+    if let Some(condition) = condition {
+        result
+            .resources
+            .retain(|resource| condition.matches(resource));
+    }
+}
+
+fn print_cache_stats(
+    repository: &SqliteRepository,
+    ttl_secs: u64,
+    dates: Dates,
+) -> Result<(), DomainError> {
+    let stale_before = Utc::now() - chrono::Duration::seconds(ttl_secs as i64);
+    let stats = repository.stats(stale_before)?;
+    println!(
+        "Cache: {} ({})",
+        repository.path().display(),
+        format_bytes(repository.file_size())
+    );
+    let (blob_dir, blobs, blob_bytes) = repository.blob_usage();
+    println!(
+        "Blobs: {} ({} files, {})",
+        blob_dir.display(),
+        blobs,
+        format_bytes(blob_bytes)
+    );
+    println!("TTL: {}s", ttl_secs);
+
+    if stats.is_empty() {
+        println!("\nCache is empty.");
+        return Ok(());
+    }
+    println!(
+        "\n{:<10} {:>8} {:>6} {:>10} {:>10} {:>10}",
+        "provider", "entries", "stale", "size", "oldest", "newest"
+    );
+    for provider in stats {
+        println!(
+            "{:<10} {:>8} {:>6} {:>10} {:>10} {:>10}",
+            provider.provider,
+            provider.entries,
+            provider.stale,
+            format_bytes(provider.bytes),
+            dates.age(provider.oldest),
+            dates.age(provider.newest)
+        );
+    }
+    Ok(())
+}
+
+/// Prints the local usage statistics. Every cache hit stands in for at
+/// least one provider call, so hits are what the cache saved.
+fn print_usage(repository: &SqliteRepository, dates: Dates) -> Result<(), DomainError> {
+    let counts = repository.usage()?;
+    let Some(since) = counts.iter().map(|count| count.first_at).min() else {
+        println!("No usage recorded yet.");
+        return Ok(());
+    };
+    println!(
+        "Usage since {} (recorded locally, never sent anywhere)",
+        dates.date(since)
+    );
+
+    let commands: Vec<_> = counts
+        .iter()
+        .filter(|count| count.event == UsageEvent::Command)
+        .collect();
+    if !commands.is_empty() {
+        println!("\n{:<14} {:>8} {:>10}", "command", "runs", "last run");
+        for command in commands {
+            println!(
+                "{:<14} {:>8} {:>10}",
+                command.name,
+                command.count,
+                dates.age(command.last_at)
+            );
         }
     }
 
+    let mut providers: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+    for count in &counts {
+        match count.event {
+            UsageEvent::ProviderCall => providers.entry(&count.name).or_default().0 += count.count,
+            UsageEvent::CacheHit => providers.entry(&count.name).or_default().1 += count.count,
+            UsageEvent::Command => {}
+        }
+    }
+    if providers.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "\n{:<14} {:>8} {:>10} {:>8}",
+        "provider", "calls", "cache hits", "hit rate"
+    );
+    for (provider, (calls, hits)) in &providers {
+        println!(
+            "{:<14} {:>8} {:>10} {:>7.0}%",
+            provider,
+            calls,
+            hits,
+            *hits as f64 * 100.0 / (calls + hits).max(1) as f64
+        );
+    }
+    let avoided: u64 = providers.values().map(|(_, hits)| hits).sum();
+    println!("\nEstimated API calls avoided: at least {}", avoided);
     Ok(())
 }
+
+fn print_bench_report(report: &BenchReport) {
+    let ms = |d: Option<std::time::Duration>| {
+        d.map(|d| format!("{:.1}ms", d.as_secs_f64() * 1000.0))
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    println!(
+        "{:<8} {:>6} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>8.2} {:>7}",
+        report.operation.name(),
+        report.calls(),
+        report.errors.len(),
+        ms(report.latencies.first().copied()),
+        ms(report.percentile(50.0)),
+        ms(report.percentile(90.0)),
+        ms(report.percentile(99.0)),
+        ms(report.latencies.last().copied()),
+        ms(report.mean()),
+        report.throughput(),
+        report.items
+    );
+}
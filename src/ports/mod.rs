@@ -1,5 +1,29 @@
-use crate::domain::{DomainError, Query, Resource};
+use crate::domain::{
+    Alias, Attachment, Blob, CachedResource, CachedSearch, Change, DiscoveredItem, DomainError,
+    ListingPage, Query, Resource, SyncEstimate, UsageEvent,
+};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tokio_util::sync::CancellationToken;
+
+/// Filters that only tune how a provider is queried. Any other filter narrows
+/// the listing, so resources missing from it may still exist upstream.
+pub const NON_NARROWING_FILTERS: [&str; 3] = ["page_size", "records", "include_archived"];
+
+/// Filter providers are asked to list only resources updated since, as an
+/// RFC 3339 timestamp, when a sync scope has a window. Providers that
+/// don't know it list everything, and the window is applied afterwards.
+pub const UPDATED_AFTER_FILTER: &str = "updated_after";
+
+/// Whether a filter steers the listing rather than naming a field to match:
+/// one of `NON_NARROWING_FILTERS`, or the `UPDATED_AFTER_FILTER` window.
+pub fn is_listing_filter(key: &str) -> bool {
+    key == UPDATED_AFTER_FILTER || NON_NARROWING_FILTERS.contains(&key)
+}
 
 #[async_trait]
 pub trait ResourceProvider: Send + Sync {
@@ -7,12 +31,534 @@ pub trait ResourceProvider: Send + Sync {
     async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError>;
     async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError>;
     fn provider_name(&self) -> &'static str;
+
+    /// Lists one page of `query`'s results, starting at `cursor`. Resources
+    /// may come back without their content, which `hydrate` fills in. The
+    /// default returns everything `fetch_resources` does as a single page.
+    async fn list_page(
+        &self,
+        query: &Query,
+        _cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        Ok(ListingPage {
+            resources: self.fetch_resources(query).await?,
+            next_cursor: None,
+        })
+    }
+
+    /// Fetches the content of a resource returned by `list_page`.
+    async fn hydrate(&self, resource: Resource) -> Result<Resource, DomainError> {
+        Ok(resource)
+    }
+
+    /// Enumerates everything the provider's credentials can see.
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        Err(DomainError::InvalidQuery(format!(
+            "{} does not support discovery",
+            self.provider_name()
+        )))
+    }
+
+    /// Downloads a file listed in a resource's attachments.
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        Err(DomainError::InvalidQuery(format!(
+            "{} does not serve attachments such as {}",
+            self.provider_name(),
+            attachment.name
+        )))
+    }
+
+    /// Makes `change` upstream, returning the resource it created or
+    /// changed. Every write a provider supports goes through here, so
+    /// `ReadOnlyProvider` can refuse them all.
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        Err(DomainError::InvalidQuery(format!(
+            "{} does not support {}",
+            self.provider_name(),
+            change.kind()
+        )))
+    }
+
+    /// Gets ready to serve, e.g. by connecting, so the first request
+    /// doesn't wait for it. Providers that are always ready do nothing.
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        Ok(())
+    }
+
+    /// Works out what syncing `query` would cost, for providers whose
+    /// syncs take long enough to be worth knowing beforehand. The default
+    /// doesn't say.
+    async fn estimate_sync(&self, _query: &Query) -> Result<Option<SyncEstimate>, DomainError> {
+        Ok(None)
+    }
+}
+
+/// A provider that builds the one it stands for the first time it's used,
+/// so configuring many costs nothing until they're needed, and one that
+/// fails to build only fails the requests that reach it. A failed build is
+/// tried again on the next use.
+pub struct LazyProvider {
+    name: &'static str,
+    build: Arc<dyn Fn() -> Result<Arc<dyn ResourceProvider>, DomainError> + Send + Sync>,
+    inner: OnceCell<Arc<dyn ResourceProvider>>,
+}
+
+impl LazyProvider {
+    /// Stands for the provider named `name` that `build` returns.
+    pub fn new(
+        name: &'static str,
+        build: impl Fn() -> Result<Arc<dyn ResourceProvider>, DomainError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name,
+            build: Arc::new(build),
+            inner: OnceCell::new(),
+        }
+    }
+
+    /// The provider, built off the async workers: building one may load
+    /// TLS certificates or look at the file system.
+    async fn inner(&self) -> Result<&Arc<dyn ResourceProvider>, DomainError> {
+        self.inner
+            .get_or_try_init(|| async {
+                let build = self.build.clone();
+                tokio::task::spawn_blocking(move || build())
+                    .await
+                    .map_err(|e| DomainError::ProviderError(e.to_string()))?
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for LazyProvider {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        self.inner().await?.fetch_resources(query).await
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        self.inner().await?.fetch_resource_by_id(id).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        self.inner().await?.search(query).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        self.inner().await?.list_page(query, cursor).await
+    }
+
+    async fn hydrate(&self, resource: Resource) -> Result<Resource, DomainError> {
+        self.inner().await?.hydrate(resource).await
+    }
+
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        self.inner().await?.discover().await
+    }
+
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        self.inner().await?.download(attachment).await
+    }
+
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        self.inner().await?.apply(change).await
+    }
+
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        self.inner().await?.warm_up().await
+    }
+
+    async fn estimate_sync(&self, query: &Query) -> Result<Option<SyncEstimate>, DomainError> {
+        self.inner().await?.estimate_sync(query).await
+    }
+}
+
+/// Passes reads through to `inner` and refuses every change, for services
+/// exposed to clients that must not mutate anything upstream.
+pub struct ReadOnlyProvider {
+    inner: Arc<dyn ResourceProvider>,
+}
+
+impl ReadOnlyProvider {
+    pub fn new(inner: Arc<dyn ResourceProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ResourceProvider for ReadOnlyProvider {
+    async fn fetch_resources(&self, query: &Query) -> Result<Vec<Resource>, DomainError> {
+        self.inner.fetch_resources(query).await
+    }
+
+    async fn fetch_resource_by_id(&self, id: &str) -> Result<Resource, DomainError> {
+        self.inner.fetch_resource_by_id(id).await
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<Resource>, DomainError> {
+        self.inner.search(query).await
+    }
+
+    fn provider_name(&self) -> &'static str {
+        self.inner.provider_name()
+    }
+
+    async fn list_page(
+        &self,
+        query: &Query,
+        cursor: Option<String>,
+    ) -> Result<ListingPage, DomainError> {
+        self.inner.list_page(query, cursor).await
+    }
+
+    async fn hydrate(&self, resource: Resource) -> Result<Resource, DomainError> {
+        self.inner.hydrate(resource).await
+    }
+
+    async fn discover(&self) -> Result<Vec<DiscoveredItem>, DomainError> {
+        self.inner.discover().await
+    }
+
+    async fn download(&self, attachment: &Attachment) -> Result<Blob, DomainError> {
+        self.inner.download(attachment).await
+    }
+
+    async fn apply(&self, change: &Change) -> Result<Resource, DomainError> {
+        Err(DomainError::ReadOnly(format!(
+            "refusing to {} in {}",
+            change.kind(),
+            self.provider_name()
+        )))
+    }
+
+    async fn warm_up(&self) -> Result<(), DomainError> {
+        self.inner.warm_up().await
+    }
+
+    async fn estimate_sync(&self, query: &Query) -> Result<Option<SyncEstimate>, DomainError> {
+        self.inner.estimate_sync(query).await
+    }
 }
 
 #[async_trait]
 pub trait ResourceRepository: Send + Sync {
     async fn save(&self, resource: &Resource) -> Result<(), DomainError>;
-    async fn find_by_id(&self, id: &str) -> Result<Option<Resource>, DomainError>;
-    async fn find_all(&self) -> Result<Vec<Resource>, DomainError>;
     async fn delete(&self, id: &str) -> Result<(), DomainError>;
+
+    async fn save_all(&self, resources: &[Resource]) -> Result<(), DomainError> {
+        for resource in resources {
+            self.save(resource).await?;
+        }
+        Ok(())
+    }
+
+    /// Like `find_by_id`, but includes when the resource was stored.
+    async fn find_entry(&self, id: &str) -> Result<Option<CachedResource>, DomainError>;
+
+    /// Stored entries, optionally restricted to one provider, most recently
+    /// stored first. Tombstoned entries are only included if asked for.
+    async fn list_entries(
+        &self,
+        provider: Option<&str>,
+        include_tombstoned: bool,
+    ) -> Result<Vec<CachedResource>, DomainError>;
+
+    /// Marks resources as deleted upstream, returning how many were newly
+    /// tombstoned. Saving a resource again clears its tombstone.
+    async fn tombstone(&self, ids: &[String]) -> Result<usize, DomainError>;
+
+    /// IDs of every tombstoned resource.
+    async fn tombstoned_ids(&self) -> Result<Vec<String>, DomainError>;
+
+    /// Non-tombstoned resources from `provider` whose title or content
+    /// has every word of `query`, ignoring case and allowing any ending,
+    /// most relevant first.
+    async fn search_entries(
+        &self,
+        provider: &str,
+        query: &str,
+    ) -> Result<Vec<CachedResource>, DomainError>;
+
+    /// The resource as the cache knew it at `as_of`: its newest version
+    /// updated by then, or `None` if none that old was stored or it had been
+    /// tombstoned by then.
+    async fn find_version(
+        &self,
+        id: &str,
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<Resource>, DomainError>;
+
+    /// Resources from `provider` as the cache knew them at `as_of` (see
+    /// `find_version`) whose title or content contains every word of
+    /// `query`, ignoring case, most recently updated first. Tombstoned ones
+    /// are only included if asked for.
+    async fn search_versions(
+        &self,
+        provider: &str,
+        query: &str,
+        as_of: DateTime<Utc>,
+        include_tombstoned: bool,
+    ) -> Result<Vec<Resource>, DomainError>;
+
+    /// Records that `sync` pulled a complete listing of `provider`.
+    async fn record_sync(
+        &self,
+        provider: &str,
+        synced_at: DateTime<Utc>,
+    ) -> Result<(), DomainError>;
+
+    /// When `provider` was last completely synced.
+    async fn last_sync(&self, provider: &str) -> Result<Option<DateTime<Utc>>, DomainError>;
+
+    /// Stores the result IDs `provider` returned for `query`, replacing any
+    /// earlier results.
+    async fn save_search(
+        &self,
+        provider: &str,
+        query: &str,
+        ids: &[String],
+    ) -> Result<(), DomainError>;
+
+    async fn find_search(
+        &self,
+        provider: &str,
+        query: &str,
+    ) -> Result<Option<CachedSearch>, DomainError>;
+
+    /// The `index`th file attached to resource `id`, if one was stored
+    /// while the resource was at `updated_at`; files stored before it last
+    /// changed are ignored, as the attachment may have been replaced.
+    async fn find_attachment(
+        &self,
+        id: &str,
+        index: usize,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Option<Blob>, DomainError>;
+
+    /// Stores a downloaded attachment for `find_attachment`.
+    async fn save_attachment(
+        &self,
+        id: &str,
+        index: usize,
+        updated_at: DateTime<Utc>,
+        blob: &Blob,
+    ) -> Result<(), DomainError>;
+
+    /// Adds one to the local usage count of `event` for `name`.
+    async fn record_usage(&self, event: UsageEvent, name: &str) -> Result<(), DomainError>;
+
+    /// Points `alias` at resource `id`, replacing any alias of that name.
+    /// Resources with a short ID get one automatically when saved.
+    async fn set_alias(&self, alias: &str, id: &str) -> Result<(), DomainError>;
+
+    /// Removes an alias, returning whether it existed.
+    async fn remove_alias(&self, alias: &str) -> Result<bool, DomainError>;
+
+    /// The resource ID `alias` points at, ignoring case.
+    async fn resolve_alias(&self, alias: &str) -> Result<Option<String>, DomainError>;
+
+    /// Every alias, by name.
+    async fn list_aliases(&self) -> Result<Vec<Alias>, DomainError>;
+
+    /// Removes every resource from `provider`, along with its sync and search
+    /// records, returning how many resources were removed.
+    async fn delete_by_provider(&self, provider: &str) -> Result<usize, DomainError>;
+
+    /// Removes resources stored before `cached_before`, returning how many
+    /// were removed.
+    async fn delete_stale(&self, cached_before: DateTime<Utc>) -> Result<usize, DomainError>;
+
+    /// Removes every resource, sync and search record, returning how many
+    /// resources were removed.
+    async fn clear(&self) -> Result<usize, DomainError>;
+}
+
+/// Looks up the titles of web pages that resource content links to.
+#[async_trait]
+pub trait LinkResolver: Send + Sync {
+    /// The title of the page at `url`, or `None` if it has none, e.g.
+    /// because it isn't HTML.
+    async fn title(&self, url: &str) -> Result<Option<String>, DomainError>;
+}
+
+/// A user-supplied step that results pass through before they are returned,
+/// such as a script. Hooks run in the order they are configured.
+pub trait ResourceHook: Send + Sync {
+    /// Name failures are reported under, e.g. the script's path.
+    fn name(&self) -> &str;
+
+    /// Whether the hook applies to resources from `provider`.
+    fn applies_to(&self, provider: &str) -> bool;
+
+    /// The resource to return in place of `resource`, or `None` to leave it
+    /// out of the results.
+    fn transform(&self, resource: &Resource) -> Result<Option<Resource>, DomainError>;
+
+    /// How relevant `resource` is to the search `query`; higher ranks first.
+    /// `None` if the hook doesn't rank.
+    fn rank(&self, resource: &Resource, query: &str) -> Result<Option<f64>, DomainError>;
+}
+
+/// Receives progress of long-running provider calls made inside
+/// `with_progress`.
+pub trait ProgressReporter: Send + Sync {
+    /// `progress` counts the steps completed so far and only ever increases.
+    fn report(&self, progress: u64, message: &str);
+}
+
+struct ProgressScope {
+    reporter: Arc<dyn ProgressReporter>,
+    steps: AtomicU64,
+}
+
+tokio::task_local! {
+    static PROGRESS: Arc<ProgressScope>;
+}
+
+/// Runs `future`, passing the progress providers report while it runs to
+/// `reporter`. Tasks it spawns only report if spawned `in_current_scope`.
+pub async fn with_progress<F: Future>(reporter: Arc<dyn ProgressReporter>, future: F) -> F::Output {
+    let scope = ProgressScope {
+        reporter,
+        steps: AtomicU64::new(0),
+    };
+    PROGRESS.scope(Arc::new(scope), future).await
+}
+
+/// Reports that a provider finished one step of a long-running call, such
+/// as one page of a paginated listing. `message` is only built when someone
+/// is listening.
+pub fn report_progress(message: impl FnOnce() -> String) {
+    let _ = PROGRESS.try_with(|scope| {
+        let progress = scope.steps.fetch_add(1, Ordering::Relaxed) + 1;
+        scope.reporter.report(progress, &message());
+    });
+}
+
+tokio::task_local! {
+    static CANCELLATION: CancellationToken;
+}
+
+/// Runs `future`, aborting the provider calls it makes once `token` is
+/// cancelled. Tasks it spawns are only affected if spawned
+/// `in_current_scope`.
+pub async fn with_cancellation<F: Future>(token: CancellationToken, future: F) -> F::Output {
+    CANCELLATION.scope(token, future).await
+}
+
+/// Runs a provider call, failing it early if the surrounding
+/// `with_cancellation` scope is cancelled.
+pub async fn cancellable<T>(
+    call: impl Future<Output = Result<T, DomainError>>,
+) -> Result<T, DomainError> {
+    let Ok(token) = CANCELLATION.try_with(CancellationToken::clone) else {
+        return call.await;
+    };
+    token
+        .run_until_cancelled(call)
+        .await
+        .unwrap_or_else(|| Err(DomainError::ProviderError("Request cancelled".to_string())))
+}
+
+/// Provider calls one command or request may still make.
+#[derive(Debug)]
+pub struct CallBudget {
+    max: usize,
+    spent: AtomicUsize,
+    /// The setting the budget comes from, named when it's spent.
+    allowed_by: &'static str,
+}
+
+impl CallBudget {
+    pub fn new(max: usize, allowed_by: &'static str) -> Self {
+        Self {
+            max,
+            spent: AtomicUsize::new(0),
+            allowed_by,
+        }
+    }
+
+    /// Takes one call to `provider` out of the budget, failing once it's
+    /// spent.
+    pub fn charge(&self, provider: &str) -> Result<(), DomainError> {
+        let spent = self.spent.fetch_add(1, Ordering::Relaxed);
+        if spent >= self.max {
+            return Err(DomainError::LimitExceeded(format!(
+                "refusing to call {} beyond the {} API calls allowed by {}",
+                provider, self.max, self.allowed_by
+            )));
+        }
+        Ok(())
+    }
+}
+
+tokio::task_local! {
+    static CALL_BUDGET: Arc<CallBudget>;
+}
+
+/// Runs `future`, charging the provider calls made while it runs to
+/// `budget` instead of any budget outside. Tasks it spawns are only
+/// charged if spawned `in_current_scope`.
+pub async fn with_call_budget<F: Future>(budget: Arc<CallBudget>, future: F) -> F::Output {
+    CALL_BUDGET.scope(budget, future).await
+}
+
+/// The budget of the surrounding `with_call_budget` scope, if any.
+pub fn current_call_budget() -> Option<Arc<CallBudget>> {
+    CALL_BUDGET.try_with(Arc::clone).ok()
+}
+
+tokio::task_local! {
+    static REQUEST_BUDGET: Arc<CallBudget>;
+}
+
+/// Runs `future`, charging every request providers send upstream while it
+/// runs, retries included, to `budget`. Where a call budget counts calls
+/// into providers, this counts what the APIs see. Tasks it spawns are only
+/// charged if spawned `in_current_scope`.
+pub async fn with_request_budget<F: Future>(budget: Arc<CallBudget>, future: F) -> F::Output {
+    REQUEST_BUDGET.scope(budget, future).await
+}
+
+/// Takes a request to `provider` out of the surrounding
+/// `with_request_budget` scope's budget, if there is one.
+pub fn charge_request(provider: &str) -> Result<(), DomainError> {
+    REQUEST_BUDGET
+        .try_with(|budget| budget.charge(provider))
+        .unwrap_or(Ok(()))
+}
+
+/// Wraps `future`, typically one about to be spawned, so it reports
+/// progress, is cancelled and spends calls like the code calling this.
+pub fn in_current_scope<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let progress = PROGRESS.try_with(Arc::clone).ok();
+    let cancellation = CANCELLATION.try_with(CancellationToken::clone).ok();
+    let budget = current_call_budget();
+    let requests = REQUEST_BUDGET.try_with(Arc::clone).ok();
+    async move {
+        let future = within(&REQUEST_BUDGET, requests, future);
+        let future = within(&CALL_BUDGET, budget, future);
+        let future = within(&CANCELLATION, cancellation, future);
+        within(&PROGRESS, progress, future).await
+    }
+}
+
+/// Runs `future` with `key` set to `value`, if there is one.
+async fn within<T: 'static, F: Future>(
+    key: &'static tokio::task::LocalKey<T>,
+    value: Option<T>,
+    future: F,
+) -> F::Output {
+    match value {
+        Some(value) => key.scope(value, future).await,
+        None => future.await,
+    }
 }